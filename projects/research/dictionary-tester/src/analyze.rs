@@ -164,6 +164,7 @@ fn analyze_compression(
             args.chunk_size,
             CompressionPreference::ZStandard,
             CompressionPreference::ZStandard,
+            &HashMap::new(),
         )
         .blocks;
 
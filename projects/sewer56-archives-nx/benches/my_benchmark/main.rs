@@ -3,6 +3,8 @@ mod assets;
 mod create_string_pool;
 mod table_of_contents;
 mod table_of_contents_v2;
+mod zstd_compression;
+mod zstd_decompression;
 
 // Used Modules
 use create_string_pool::benchmark_string_pool;
@@ -12,6 +14,8 @@ use criterion::{criterion_group, criterion_main, Criterion};
 use pprof::criterion::{Output, PProfProfiler};
 use table_of_contents::*;
 use table_of_contents_v2::*;
+use zstd_compression::bench_zstd_compression;
+use zstd_decompression::bench_zstd_decompression;
 
 fn criterion_benchmark(c: &mut Criterion) {
     benchmark_string_pool(c);
@@ -19,6 +23,8 @@ fn criterion_benchmark(c: &mut Criterion) {
     bench_deserialize_toc(c);
     bench_serialize_toc_v2(c);
     bench_deserialize_toc_v2(c);
+    bench_zstd_decompression(c);
+    bench_zstd_compression(c);
 
     #[cfg(not(feature = "pgo"))]
     {
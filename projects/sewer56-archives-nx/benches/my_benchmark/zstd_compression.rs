@@ -0,0 +1,70 @@
+use core::ffi::c_void;
+use criterion::{black_box, Criterion, Throughput};
+use sewer56_archives_nx::utilities::compression::zstd;
+use zstd_sys::ZSTD_cParameter::*;
+use zstd_sys::ZSTD_format_e::ZSTD_f_zstd1_magicless;
+use zstd_sys::*;
+
+/// Size of each block; small files in their own blocks, as packed by the LowLatencyVFS preset.
+const BLOCK_SIZE: usize = 4096;
+
+/// Number of blocks compressed per iteration.
+const NUM_BLOCKS: usize = 64;
+
+/// Compression level of the LowLatencyVFS preset.
+const LEVEL: i32 = 12;
+
+/// Compresses a block with a newly created context, as done before contexts were pooled.
+fn compress_with_new_context(source: &[u8], destination: &mut [u8]) -> usize {
+    unsafe {
+        let cctx = ZSTD_createCCtx();
+        ZSTD_CCtx_setParameter(cctx, ZSTD_c_compressionLevel, LEVEL);
+        ZSTD_CCtx_setParameter(
+            cctx,
+            ZSTD_c_experimentalParam2, // zstd_c_format
+            ZSTD_f_zstd1_magicless as i32,
+        );
+        ZSTD_CCtx_setParameter(cctx, ZSTD_c_contentSizeFlag, 0);
+        ZSTD_CCtx_setParameter(cctx, ZSTD_c_checksumFlag, 0);
+        ZSTD_CCtx_setParameter(cctx, ZSTD_c_dictIDFlag, 0);
+        let result = ZSTD_compress2(
+            cctx,
+            destination.as_mut_ptr() as *mut c_void,
+            destination.len(),
+            source.as_ptr() as *const c_void,
+            source.len(),
+        );
+        ZSTD_freeCCtx(cctx);
+        result
+    }
+}
+
+pub fn bench_zstd_compression(c: &mut Criterion) {
+    let blocks: Vec<Vec<u8>> = (0..NUM_BLOCKS)
+        .map(|block| {
+            (0..BLOCK_SIZE)
+                .map(|x| (x % 64) as u8 ^ (x / 512) as u8 ^ block as u8)
+                .collect()
+        })
+        .collect();
+    let mut destination = vec![0u8; zstd::max_alloc_for_compress_size(BLOCK_SIZE)];
+
+    let mut group = c.benchmark_group("zstd_compress_small_blocks");
+    group.throughput(Throughput::Bytes((BLOCK_SIZE * NUM_BLOCKS) as u64));
+    group.bench_function("new_context", |b| {
+        b.iter(|| {
+            for block in &blocks {
+                compress_with_new_context(black_box(block), &mut destination);
+            }
+        })
+    });
+    group.bench_function("pooled_context", |b| {
+        b.iter(|| {
+            let mut used_copy = false;
+            for block in &blocks {
+                zstd::compress(LEVEL, black_box(block), &mut destination, &mut used_copy).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
@@ -0,0 +1,53 @@
+use core::ffi::c_void;
+use criterion::{black_box, Criterion};
+use sewer56_archives_nx::utilities::compression::zstd;
+use zstd_sys::ZSTD_dParameter::ZSTD_d_experimentalParam1;
+use zstd_sys::ZSTD_format_e::ZSTD_f_zstd1_magicless;
+use zstd_sys::*;
+
+/// Size of each block; small SOLID blocks, as used in archives optimized for low latency reads.
+const BLOCK_SIZE: usize = 4096;
+
+/// Decompresses a block with a newly created context, as done before contexts were reused.
+fn decompress_with_new_context(source: &[u8], destination: &mut [u8]) -> usize {
+    unsafe {
+        let dctx = ZSTD_createDCtx();
+        ZSTD_DCtx_setParameter(
+            dctx,
+            ZSTD_d_experimentalParam1,
+            ZSTD_f_zstd1_magicless as i32,
+        );
+        let result = ZSTD_decompressDCtx(
+            dctx,
+            destination.as_mut_ptr() as *mut c_void,
+            destination.len(),
+            source.as_ptr() as *const c_void,
+            source.len(),
+        );
+        ZSTD_freeDCtx(dctx);
+        result
+    }
+}
+
+pub fn bench_zstd_decompression(c: &mut Criterion) {
+    let data: Vec<u8> = (0..BLOCK_SIZE)
+        .map(|x| (x % 64) as u8 ^ (x / 512) as u8)
+        .collect();
+    let mut compressed = vec![0u8; zstd::max_alloc_for_compress_size(data.len())];
+    let mut used_copy = false;
+    let size = zstd::compress(16, &data, &mut compressed, &mut used_copy).unwrap();
+    compressed.truncate(size);
+
+    let mut group = c.benchmark_group("zstd_decompress_small_block");
+    let mut destination = vec![0u8; BLOCK_SIZE];
+    group.bench_function("new_context", |b| {
+        b.iter(|| decompress_with_new_context(black_box(&compressed), &mut destination))
+    });
+    group.bench_function("reused_context", |b| {
+        b.iter(|| zstd::decompress(black_box(&compressed), &mut destination).unwrap())
+    });
+    group.bench_function("reused_context_partial", |b| {
+        b.iter(|| zstd::decompress_partial(black_box(&compressed), &mut destination).unwrap())
+    });
+    group.finish();
+}
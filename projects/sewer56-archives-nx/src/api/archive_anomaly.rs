@@ -0,0 +1,137 @@
+use crate::headers::managed::FileEntry;
+use crate::headers::parser::ChunkSizes;
+use crate::headers::raw::native_file_header::NativeFileHeader;
+use crate::prelude::*;
+
+/// A problem found while opening an archive, which does not prevent reading it.
+///
+/// These are collected by [`NxArchiveReader`] on open, and can be inspected with
+/// [`NxArchiveReader::anomalies`]. They are useful as feedback when writing tools which
+/// produce Nx archives. With the `log` feature, each anomaly is also logged as a warning.
+///
+/// [`NxArchiveReader`]: crate::api::archive_reader::NxArchiveReader
+/// [`NxArchiveReader::anomalies`]: crate::api::archive_reader::NxArchiveReader::anomalies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveAnomaly {
+    /// The archive was written for a newer version of the format than this library implements.
+    /// Features introduced by that version are ignored.
+    UnknownArchiveVersion(u8),
+
+    /// The header has feature flags set which this library does not know about.
+    UnknownFeatureFlags(u8),
+
+    /// The header reserves more pages than needed to hold the table of contents.
+    UnusedHeaderPages {
+        /// Number of bytes used by the header and table of contents.
+        used_bytes: u32,
+        /// Number of bytes reserved for them by the header.
+        reserved_bytes: u32,
+    },
+
+    /// The padding after the table of contents is not zeroed.
+    NonZeroHeaderPadding {
+        /// Offset of the first non-zero byte from the start of the archive.
+        offset: u32,
+    },
+
+    /// A file's data is stored in blocks which do not exist; reading that file fails.
+    EntryBlockOutOfRange {
+        /// Index of the entry in the table of contents.
+        entry_index: usize,
+        /// Index of the last block the entry needs.
+        block_index: u64,
+    },
+}
+
+/// Checks the header, and the space after the table of contents.
+///
+/// # Arguments
+///
+/// * `header` - Header of the archive.
+/// * `header_page` - Raw bytes of the header and table of contents.
+/// * `used_bytes` - Number of bytes of `header_page` used by the header and table of contents.
+pub(crate) fn check_header(
+    header: &NativeFileHeader,
+    header_page: &[u8],
+    used_bytes: u32,
+    anomalies: &mut Vec<ArchiveAnomaly>,
+) {
+    if header.version() > NativeFileHeader::CURRENT_ARCHIVE_VERSION {
+        report(
+            anomalies,
+            ArchiveAnomaly::UnknownArchiveVersion(header.version()),
+        );
+    }
+
+    let flags = header.feature_flags();
+    let collation = flags & NativeFileHeader::PATH_COLLATION_FLAGS;
+    if flags & !NativeFileHeader::KNOWN_FEATURE_FLAGS != 0
+        || collation == NativeFileHeader::PATH_COLLATION_FLAGS
+    {
+        report(anomalies, ArchiveAnomaly::UnknownFeatureFlags(flags));
+    }
+
+    let reserved_bytes = header.header_page_bytes();
+    if used_bytes.next_multiple_of(NativeFileHeader::HEADER_PAGE_SIZE) < reserved_bytes {
+        report(
+            anomalies,
+            ArchiveAnomaly::UnusedHeaderPages {
+                used_bytes,
+                reserved_bytes,
+            },
+        );
+    }
+
+    let padding = header_page.get(used_bytes as usize..reserved_bytes as usize);
+    if let Some(position) = padding.and_then(|x| x.iter().position(|byte| *byte != 0)) {
+        report(
+            anomalies,
+            ArchiveAnomaly::NonZeroHeaderPadding {
+                offset: used_bytes + position as u32,
+            },
+        );
+    }
+}
+
+/// Checks that every entry only references existing blocks.
+pub(crate) fn check_entries(
+    entries: &[FileEntry],
+    block_count: usize,
+    chunk_size: u32,
+    chunk_sizes: Option<&ChunkSizes>,
+    anomalies: &mut Vec<ArchiveAnomaly>,
+) {
+    for (entry_index, entry) in entries.iter().enumerate() {
+        // Empty files do not have any data to read.
+        if entry.decompressed_size == 0 {
+            continue;
+        }
+
+        let chunk_size = chunk_sizes
+            .and_then(|x| x.get(entry.first_block_index))
+            .unwrap_or(chunk_size);
+
+        let num_blocks = if entry.is_chunked(chunk_size) {
+            entry.decompressed_size.div_ceil(chunk_size as u64)
+        } else {
+            1
+        };
+
+        let block_index = entry.first_block_index as u64 + num_blocks - 1;
+        if block_index >= block_count as u64 {
+            report(
+                anomalies,
+                ArchiveAnomaly::EntryBlockOutOfRange {
+                    entry_index,
+                    block_index,
+                },
+            );
+        }
+    }
+}
+
+fn report(anomalies: &mut Vec<ArchiveAnomaly>, anomaly: ArchiveAnomaly) {
+    #[cfg(feature = "log")]
+    log::warn!("Anomaly in Nx archive: {anomaly:?}");
+    anomalies.push(anomaly);
+}
@@ -0,0 +1,344 @@
+use crate::api::archive_reader::{ArchiveReadError, NxArchiveReader};
+use crate::prelude::*;
+use alloc::string::{String, ToString};
+use hashbrown::{HashMap, HashSet};
+use thiserror_no_std::Error;
+
+/// A file present in only one of the compared archives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComparedFile {
+    /// Path of the file within the archive.
+    pub path: String,
+
+    /// Size of the file.
+    pub size: u64,
+
+    /// XXH3 hash of the file's contents.
+    pub hash: u64,
+}
+
+/// A file present in both archives, with different contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    /// Path of the file within the archives.
+    pub path: String,
+
+    /// Size of the file in the old archive.
+    pub old_size: u64,
+
+    /// Size of the file in the new archive.
+    pub new_size: u64,
+
+    /// Hash of the file in the old archive.
+    pub old_hash: u64,
+
+    /// Hash of the file in the new archive.
+    pub new_hash: u64,
+}
+
+/// A file whose path changed between the archives, but whose contents did not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenamedFile {
+    /// Path of the file in the old archive.
+    pub old_path: String,
+
+    /// Path of the file in the new archive.
+    pub new_path: String,
+
+    /// Size of the file.
+    pub size: u64,
+}
+
+/// The differences between two archives, returned by [`compare_archives`].
+///
+/// Files are listed in path order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ArchiveComparison {
+    /// Files only in the new archive.
+    pub added: Vec<ComparedFile>,
+
+    /// Files only in the old archive.
+    pub removed: Vec<ComparedFile>,
+
+    /// Files in both archives, with different contents.
+    pub changed: Vec<ChangedFile>,
+
+    /// Files of the old archive found in the new one at a different path, with the same contents.
+    /// These are not listed in [`Self::added`] or [`Self::removed`].
+    pub renamed: Vec<RenamedFile>,
+
+    /// Number of files in both archives, at the same path with the same contents.
+    pub unchanged_files: u32,
+
+    /// Number of blocks in the old archive.
+    pub old_block_count: u32,
+
+    /// Number of blocks of the old archive holding only unchanged files, which an
+    /// incremental repack (see [`pack_incremental`]) would copy without recompressing.
+    ///
+    /// [`pack_incremental`]: crate::api::incremental_pack::pack_incremental
+    pub reusable_blocks: u32,
+
+    /// Total compressed size of the [`Self::reusable_blocks`].
+    pub reusable_block_bytes: u64,
+
+    /// Total size of the files in the old archive.
+    pub old_size: u64,
+
+    /// Total size of the files in the new archive.
+    pub new_size: u64,
+
+    /// Total compressed size of the blocks in the old archive.
+    pub old_compressed_size: u64,
+
+    /// Total compressed size of the blocks in the new archive.
+    pub new_compressed_size: u64,
+}
+
+/// Errors that can occur when comparing archives.
+#[derive(Debug, Error)]
+pub enum ArchiveComparisonError {
+    /// One of the archives has no file hashes, so changed files can't be detected.
+    #[error("The archives must both store file hashes")]
+    MissingHashes,
+
+    /// Failed to read one of the archives.
+    #[error("Failed to read archive: {0:?}")]
+    Read(#[from] ArchiveReadError),
+}
+
+impl ArchiveComparison {
+    /// Returns true if both archives contain the same files, at the same paths.
+    pub fn is_identical(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.renamed.is_empty()
+    }
+
+    /// Returns the change in total file size, from the old archive to the new one.
+    pub fn size_delta(&self) -> i64 {
+        self.new_size as i64 - self.old_size as i64
+    }
+
+    /// Returns the change in total compressed size, from the old archive to the new one.
+    pub fn compressed_size_delta(&self) -> i64 {
+        self.new_compressed_size as i64 - self.old_compressed_size as i64
+    }
+}
+
+/// Lists the files added, removed, changed and renamed between two versions of an archive,
+/// along with how much of the old archive could be reused when updating it.
+///
+/// Files are matched by path, and compared by hash and size.
+///
+/// # Arguments
+///
+/// * `old` - The old version of the archive.
+/// * `new` - The new version of the archive.
+///
+/// # Errors
+///
+/// Returns [`ArchiveComparisonError::MissingHashes`] if either (non-empty) archive was packed
+/// without file hashes.
+pub fn compare_archives(
+    old: &NxArchiveReader,
+    new: &NxArchiveReader,
+) -> Result<ArchiveComparison, ArchiveComparisonError> {
+    for archive in [old, new] {
+        if !archive.has_file_hashes() && !archive.entries().is_empty() {
+            return Err(ArchiveComparisonError::MissingHashes);
+        }
+    }
+
+    let mut result = ArchiveComparison {
+        old_block_count: old.block_count(),
+        old_compressed_size: compressed_size(old),
+        new_compressed_size: compressed_size(new),
+        ..Default::default()
+    };
+
+    // Path => (size, hash)
+    let mut new_files: HashMap<&str, (u64, u64)> = HashMap::with_capacity(new.entries().len());
+    for (path, info) in new.iter_entries()? {
+        new_files.insert(path, (info.size(), info.hash()));
+        result.new_size += info.size();
+    }
+
+    let mut block_reusable = vec![true; old.block_count() as usize];
+    let mut old_paths: HashSet<&str> = HashSet::with_capacity(old.entries().len());
+    for (path, info) in old.iter_entries()? {
+        old_paths.insert(path);
+        result.old_size += info.size();
+        match new_files.get(path) {
+            Some(&(size, hash)) if size == info.size() && hash == info.hash() => {
+                result.unchanged_files += 1;
+                continue;
+            }
+            Some(&(size, hash)) => result.changed.push(ChangedFile {
+                path: path.to_string(),
+                old_size: info.size(),
+                new_size: size,
+                old_hash: info.hash(),
+                new_hash: hash,
+            }),
+            None => result.removed.push(ComparedFile {
+                path: path.to_string(),
+                size: info.size(),
+                hash: info.hash(),
+            }),
+        }
+
+        for block_index in old.block_mapping(info.entry()).block_indices() {
+            block_reusable[block_index as usize] = false;
+        }
+    }
+
+    for (path, &(size, hash)) in &new_files {
+        if !old_paths.contains(path) {
+            result.added.push(ComparedFile {
+                path: path.to_string(),
+                size,
+                hash,
+            });
+        }
+    }
+
+    let blocks = &old.table_of_contents().blocks;
+    for (block, _) in blocks.iter().zip(&block_reusable).filter(|(_, x)| **x) {
+        result.reusable_blocks += 1;
+        result.reusable_block_bytes += block.compressed_size as u64;
+    }
+
+    result.added.sort_by(|a, b| a.path.cmp(&b.path));
+    result.removed.sort_by(|a, b| a.path.cmp(&b.path));
+    find_renames(&mut result);
+    result.changed.sort_by(|a, b| a.path.cmp(&b.path));
+    result.renamed.sort_by(|a, b| a.new_path.cmp(&b.new_path));
+    Ok(result)
+}
+
+/// Moves added files with the same contents as a removed file to [`ArchiveComparison::renamed`].
+fn find_renames(result: &mut ArchiveComparison) {
+    // (size, hash) => indices into `removed`, so duplicates each pair up with one added file.
+    let mut removed: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+    for (index, file) in result.removed.iter().enumerate() {
+        removed
+            .entry((file.size, file.hash))
+            .or_default()
+            .push(index);
+    }
+
+    let mut renamed_from = vec![false; result.removed.len()];
+    let mut added = Vec::with_capacity(result.added.len());
+    for file in core::mem::take(&mut result.added) {
+        let Some(index) = removed
+            .get_mut(&(file.size, file.hash))
+            .and_then(|x| x.pop())
+        else {
+            added.push(file);
+            continue;
+        };
+
+        renamed_from[index] = true;
+        result.renamed.push(RenamedFile {
+            old_path: result.removed[index].path.clone(),
+            new_path: file.path,
+            size: file.size,
+        });
+    }
+
+    result.added = added;
+    let mut index = 0;
+    result.removed.retain(|_| {
+        index += 1;
+        !renamed_from[index - 1]
+    });
+}
+
+fn compressed_size(archive: &NxArchiveReader) -> u64 {
+    archive
+        .table_of_contents()
+        .blocks
+        .iter()
+        .map(|x| x.compressed_size as u64)
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{enums::CompressionPreference, filedata::FromSliceReferenceProvider};
+    use crate::headers::types::xxh3sum::XXH3sum;
+    use crate::unsize_box2;
+    use crate::utilities::tests::archive_for_testing::*;
+
+    fn open(archive: &[u8]) -> NxArchiveReader<'_> {
+        let provider = Box::new(FromSliceReferenceProvider::new(archive));
+        NxArchiveReader::new(unsize_box2!(provider)).unwrap()
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn lists_differences_between_archives() {
+        let old = create_test_archive(
+            4096,
+            &[
+                &[TestFile::new("same.txt", b"Unchanged")],
+                &[
+                    TestFile::new("edit.txt", b"Old contents"),
+                    TestFile::new("gone.txt", b"Removed"),
+                    TestFile::new("old_name.txt", b"Moved file"),
+                ],
+            ],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+        let new = create_test_archive(
+            4096,
+            &[&[
+                TestFile::new("same.txt", b"Unchanged"),
+                TestFile::new("edit.txt", b"New contents!"),
+                TestFile::new("new_name.txt", b"Moved file"),
+                TestFile::new("added.txt", b"Added"),
+            ]],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+
+        let result = compare_archives(&open(&old), &open(&new)).unwrap();
+        assert!(!result.is_identical());
+        assert_eq!(result.unchanged_files, 1);
+        assert_eq!(
+            result.added.as_slice(),
+            &[ComparedFile {
+                path: "added.txt".to_string(),
+                size: 5,
+                hash: XXH3sum::create(b"Added").0,
+            }]
+        );
+        assert_eq!(result.removed.len(), 1);
+        assert_eq!(result.removed[0].path, "gone.txt");
+        assert_eq!(result.changed.len(), 1);
+        assert_eq!(result.changed[0].path, "edit.txt");
+        assert_eq!(result.changed[0].new_size, 13);
+        assert_eq!(
+            result.renamed.as_slice(),
+            &[RenamedFile {
+                old_path: "old_name.txt".to_string(),
+                new_path: "new_name.txt".to_string(),
+                size: 10,
+            }]
+        );
+
+        assert_eq!(result.old_block_count, 2);
+        assert_eq!(result.reusable_blocks, 1);
+        assert_eq!(result.old_size, 9 + 12 + 7 + 10);
+        assert_eq!(result.size_delta(), 13 + 5 - 12 - 7);
+
+        let same = compare_archives(&open(&old), &open(&old)).unwrap();
+        assert!(same.is_identical());
+        assert_eq!(same.reusable_blocks, 2);
+        assert_eq!(same.compressed_size_delta(), 0);
+    }
+}
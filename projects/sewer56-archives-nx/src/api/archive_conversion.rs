@@ -0,0 +1,529 @@
+use crate::api::{enums::CompressionPreference, traits::HasRelativePath};
+use crate::headers::{
+    enums::v1::TableOfContentsVersion,
+    managed::{
+        v1::{
+            self, calculate_table_size, table_of_contents_checked_reader::deserialize_v1xx_checked,
+            MAX_BLOCK_COUNT_V0V1, MAX_FILE_COUNT_V0V1,
+        },
+        v2::{
+            calculate_toc_size, serialize_table_of_contents_into, BuilderInfo, InitError,
+            SerializeError,
+        },
+        *,
+    },
+    parser::{DictionaryData, StringPool},
+    raw::{
+        native_file_header::NativeFileHeader,
+        toc::{determine_optimal_toc_format, v1::MAX_STRING_POOL_SIZE, NativeTocHeader, ToCFormat},
+    },
+};
+use crate::prelude::*;
+use thiserror_no_std::Error;
+
+/// Errors that can occur when converting an archive between format versions.
+#[derive(Debug, Error)]
+pub enum ArchiveConversionError {
+    /// The data does not start with a valid Nx header.
+    #[error("The archive does not have a valid Nx header")]
+    InvalidMagicHeader,
+
+    /// The archive is shorter than what the header claims.
+    #[error("The archive is truncated, expected at least {0} bytes")]
+    Truncated(u64),
+
+    /// Failed to deserialize the table of contents.
+    #[error("Failed to deserialize the table of contents: {0:?}")]
+    TableOfContents(#[from] DeserializeError),
+
+    /// A block uses a compression method this library does not know about.
+    #[error("Block {0} uses an unknown compression method")]
+    UnknownCompression(u32),
+
+    /// There is no table of contents format which can hold the archive's files.
+    #[error("Failed to create the table of contents: {0:?}")]
+    TableOfContentsCreation(#[from] InitError),
+
+    /// Failed to write the table of contents.
+    #[error("Failed to write the table of contents: {0:?}")]
+    Serialize(#[from] SerializeError),
+
+    /// Failed to write the legacy (V1) table of contents.
+    #[error("Failed to write the V1 table of contents: {0:?}")]
+    SerializeV1(v1::SerializeError),
+
+    /// The archive uses features which can't be represented in the V1 format.
+    #[error("The archive can't be converted to V1: {0:?}")]
+    DowngradeBlocked(Vec<DowngradeBlocker>),
+}
+
+/// A feature of an archive which prevents converting it to the legacy (V1) format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DowngradeBlocker {
+    /// Blocks are compressed with a dictionary, which V1 readers can't load.
+    Dictionaries {
+        /// Number of blocks which use a dictionary.
+        block_count: u32,
+    },
+
+    /// The archive was written without file hashes, which V1 always stores.
+    NoFileHashes,
+
+    /// The archive was written without file paths; V1 readers need a path for every file.
+    NoFilePaths,
+
+    /// The archive has more files than V1 can hold.
+    TooManyFiles(usize),
+
+    /// The archive has more blocks than V1 can hold.
+    TooManyBlocks(usize),
+
+    /// The compressed string pool is larger than V1 can hold.
+    StringPoolTooLarge(usize),
+}
+
+/// Converts an archive with a legacy (V1) table of contents into the current (V2) format.
+///
+/// Only the header and table of contents are rewritten, using the most compact format which
+/// fits the archive's files; the blocks are copied byte for byte, without recompressing them.
+/// The smaller table of contents usually fits in the first 4K page, so the archive can be
+/// opened with a single read.
+///
+/// # Arguments
+///
+/// * `input` - The complete V1 archive.
+/// * `output` - Buffer the upgraded archive is appended to.
+///
+/// # Errors
+///
+/// Returns an error if `input` is not a valid V1 archive, or no V2 format can hold its files.
+pub fn upgrade_archive(input: &[u8], output: &mut Vec<u8>) -> Result<(), ArchiveConversionError> {
+    let header = read_header(input)?;
+    let header_bytes = header.header_page_bytes() as usize;
+    let toc_data = &input[NativeFileHeader::SIZE_BYTES..header_bytes];
+
+    // The V1 deserializer does not check bounds, so ensure the whole table is present first.
+    if toc_data.len() < NativeTocHeader::SIZE_BYTES {
+        return Err(DeserializeError::from(InsufficientDataError::new(
+            toc_data.len() as u32,
+            NativeTocHeader::SIZE_BYTES as u32,
+        ))
+        .into());
+    }
+
+    let toc_header = NativeTocHeader::from_raw(u64::from_le_bytes(
+        toc_data[..NativeTocHeader::SIZE_BYTES].try_into().unwrap(),
+    ));
+    let version = toc_header
+        .get_version()
+        .map_err(|_| DeserializeError::UnsupportedTocVersion)?;
+    let pool_size = toc_header.string_pool_size() as usize;
+    let table_size = calculate_table_size(
+        toc_header.file_count() as usize,
+        toc_header.block_count() as usize,
+        pool_size,
+        version,
+    );
+    if toc_data.len() < table_size {
+        return Err(DeserializeError::from(InsufficientDataError::new(
+            toc_data.len() as u32,
+            table_size as u32,
+        ))
+        .into());
+    }
+
+    let toc = if cfg!(feature = "forbid-unsafe-fastpaths") {
+        deserialize_v1xx_checked(toc_data, Global, Global)?
+    } else {
+        // SAFETY: The table fits within `toc_data`, as checked above.
+        unsafe { TableOfContents::deserialize_v1xx(toc_data.as_ptr())? }
+    };
+    if let Some(block_index) = toc
+        .block_compressions
+        .iter()
+        .position(|x| *x == CompressionPreference::NoPreference)
+    {
+        return Err(ArchiveConversionError::UnknownCompression(
+            block_index as u32,
+        ));
+    }
+
+    // Both versions store the string pool in the same format, so it is copied as-is.
+    let string_pool = &toc_data[table_size - pool_size..table_size];
+    let max_decomp_block_offset = toc
+        .entries
+        .iter()
+        .map(|entry| entry.decompressed_block_offset)
+        .max()
+        .unwrap_or(0);
+    let max_file_size = toc
+        .entries
+        .iter()
+        .map(|entry| entry.decompressed_size)
+        .max()
+        .unwrap_or(0);
+    let format = determine_optimal_toc_format(
+        pool_size as u32,
+        max_decomp_block_offset,
+        toc.blocks.len() as u32,
+        toc.entries.len() as u32,
+        toc.entries.iter().any(|entry| entry.hash != 0),
+        max_file_size,
+    );
+    if format == ToCFormat::Error {
+        return Err(InitError::NoSuitableTocFormat(format).into());
+    }
+
+    let new_table_size = calculate_toc_size(
+        format,
+        pool_size as u32,
+        toc.blocks.len() as u32,
+        toc.entries.len() as u32,
+    );
+    let info = BuilderInfo {
+        format,
+        can_create_chunks: max_file_size > header.chunk_size_bytes() as u64,
+        table_size: new_table_size,
+        max_decomp_block_offset,
+        string_pool: Vec::from(string_pool),
+    };
+
+    // Write the header and table of contents
+    let new_header_bytes = (NativeFileHeader::SIZE_BYTES + new_table_size as usize)
+        .next_multiple_of(NativeFileHeader::HEADER_PAGE_SIZE as usize);
+    let start = output.len();
+    output.resize(start + new_header_bytes, 0);
+    output[start..start + NativeFileHeader::SIZE_BYTES].copy_from_slice(
+        &NativeFileHeader::init(header.chunk_size_bytes(), new_header_bytes as u32).to_bytes(),
+    );
+    serialize_table_of_contents_into(
+        &toc.block_compressions,
+        &toc.blocks,
+        &toc.entries,
+        &info,
+        &mut output[start + NativeFileHeader::SIZE_BYTES..],
+    )?;
+
+    // Blocks are aligned relative to the end of the header, which is page aligned in both.
+    output.extend_from_slice(&input[header_bytes..]);
+    Ok(())
+}
+
+/// Converts an archive in the current (V2) format into the legacy (V1) format, so it can be
+/// read by older readers.
+///
+/// As with [`upgrade_archive`], only the header and table of contents are rewritten and
+/// the blocks are copied byte for byte. The string pool is repacked with the default settings,
+/// since older readers only understand ZStandard compressed pools.
+///
+/// # Arguments
+///
+/// * `input` - The complete V2 archive.
+/// * `dictionaries` - Dictionaries the archive's blocks were compressed with, if any.
+///   Dictionaries are not stored in the archive itself, so their use can only be detected
+///   when they are provided here.
+/// * `output` - Buffer the downgraded archive is appended to.
+///
+/// # Errors
+///
+/// Returns [`ArchiveConversionError::DowngradeBlocked`] listing every feature which the V1
+/// format can't represent, or another error if `input` is not a valid V2 archive.
+pub fn downgrade_archive(
+    input: &[u8],
+    dictionaries: Option<&DictionaryData>,
+    output: &mut Vec<u8>,
+) -> Result<(), ArchiveConversionError> {
+    let header = read_header(input)?;
+    let header_bytes = header.header_page_bytes() as usize;
+    let toc_data = &input[NativeFileHeader::SIZE_BYTES..header_bytes];
+
+    // SAFETY: The ToC deserializer is bounds checked against the length with `hardened`.
+    let toc =
+        unsafe { TableOfContents::deserialize_v2xx(toc_data.as_ptr(), toc_data.len() as u32)? };
+
+    // Repacking sorts the paths, giving us their new indices.
+    let mut paths: Vec<PoolPath> = toc
+        .pool
+        .iter()
+        .enumerate()
+        .map(|(index, path)| PoolPath { path, index })
+        .collect();
+    let string_pool =
+        StringPool::pack_v0(&mut paths, true).map_err(InitError::FailedToCreateStringPool)?;
+
+    let mut blockers = Vec::new();
+    if let Some(dictionaries) = dictionaries {
+        let block_count = (0..toc.blocks.len())
+            .filter(|x| {
+                toc.block_compressions[*x] == CompressionPreference::ZStandard
+                    && dictionaries.get_dictionary_index_for_block(*x).is_some()
+            })
+            .count();
+        if block_count > 0 {
+            blockers.push(DowngradeBlocker::Dictionaries {
+                block_count: block_count as u32,
+            });
+        }
+    }
+
+    if !toc.has_hashes {
+        blockers.push(DowngradeBlocker::NoFileHashes);
+    }
+
+    if toc.pool.is_empty() && !toc.entries.is_empty() {
+        blockers.push(DowngradeBlocker::NoFilePaths);
+    }
+
+    if toc.entries.len() > MAX_FILE_COUNT_V0V1 {
+        blockers.push(DowngradeBlocker::TooManyFiles(toc.entries.len()));
+    }
+
+    if toc.blocks.len() > MAX_BLOCK_COUNT_V0V1 {
+        blockers.push(DowngradeBlocker::TooManyBlocks(toc.blocks.len()));
+    }
+
+    if string_pool.len() > MAX_STRING_POOL_SIZE {
+        blockers.push(DowngradeBlocker::StringPoolTooLarge(string_pool.len()));
+    }
+
+    if !blockers.is_empty() {
+        return Err(ArchiveConversionError::DowngradeBlocked(blockers));
+    }
+
+    let mut path_indices = vec![0u32; paths.len()];
+    for (new_index, path) in paths.iter().enumerate() {
+        path_indices[path.index] = new_index as u32;
+    }
+
+    let mut entries: Vec<FileEntry> = Vec::from(&toc.entries[..]);
+    for entry in entries.iter_mut() {
+        if let Some(index) = path_indices.get(entry.file_path_index as usize) {
+            entry.file_path_index = *index;
+        }
+    }
+
+    let max_file_size = entries
+        .iter()
+        .map(|entry| entry.decompressed_size)
+        .max()
+        .unwrap_or(0);
+    let version = if max_file_size > u32::MAX as u64 {
+        TableOfContentsVersion::V1
+    } else {
+        TableOfContentsVersion::V0
+    };
+
+    // Write the header and table of contents
+    let table_size =
+        calculate_table_size(entries.len(), toc.blocks.len(), string_pool.len(), version);
+    let new_header_bytes = (NativeFileHeader::SIZE_BYTES + table_size)
+        .next_multiple_of(NativeFileHeader::HEADER_PAGE_SIZE as usize);
+    let start = output.len();
+    output.resize(start + new_header_bytes, 0);
+    output[start..start + NativeFileHeader::SIZE_BYTES].copy_from_slice(
+        &NativeFileHeader::init(header.chunk_size_bytes(), new_header_bytes as u32).to_bytes(),
+    );
+    unsafe {
+        v1::serialize_table_of_contents(
+            &toc.block_compressions,
+            &toc.blocks,
+            &entries,
+            version,
+            header_ptr.add(NativeFileHeader::SIZE_BYTES),
+            &string_pool,
+        )
+        .map_err(ArchiveConversionError::SerializeV1)?;
+    }
+
+    output.extend_from_slice(&input[header_bytes..]);
+    Ok(())
+}
+
+/// Path from the string pool, remembering its index in the original pool once sorted.
+struct PoolPath<'a> {
+    path: &'a str,
+    index: usize,
+}
+
+impl HasRelativePath for PoolPath<'_> {
+    fn relative_path(&self) -> &str {
+        self.path
+    }
+}
+
+/// Reads the header of an archive, ensuring the header pages it claims are present.
+fn read_header(input: &[u8]) -> Result<NativeFileHeader, ArchiveConversionError> {
+    let Some(bytes) = input.first_chunk() else {
+        return Err(ArchiveConversionError::Truncated(
+            NativeFileHeader::SIZE_BYTES as u64,
+        ));
+    };
+
+    let header = NativeFileHeader::from_bytes(*bytes);
+    if !header.is_valid_magic_header() {
+        return Err(ArchiveConversionError::InvalidMagicHeader);
+    }
+
+    let header_bytes = header.header_page_bytes() as usize;
+    if input.len() < header_bytes || header_bytes < NativeFileHeader::SIZE_BYTES {
+        return Err(ArchiveConversionError::Truncated(header_bytes as u64));
+    }
+
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::archive_reader::NxArchiveReader;
+    use crate::api::filedata::FromSliceReferenceProvider;
+    use crate::unsize_box2;
+    use crate::utilities::tests::archive_for_testing::*;
+    use crate::utilities::tests::packer_file_for_testing::PackerFileForTesting;
+
+    const CHUNK_SIZE: u32 = 4096;
+
+    fn open(archive: &[u8]) -> NxArchiveReader<'_> {
+        let provider = Box::new(FromSliceReferenceProvider::new(archive));
+        NxArchiveReader::new(unsize_box2!(provider)).unwrap()
+    }
+
+    /// Rewrites the table of contents of a V2 archive in the V1 format.
+    fn create_v1_archive(archive: &[u8]) -> Vec<u8> {
+        let reader = open(archive);
+        let toc = reader.table_of_contents();
+        let mut items: Vec<PackerFileForTesting> = toc
+            .pool
+            .iter()
+            .map(|path| PackerFileForTesting::new(path, 0))
+            .collect();
+        let string_pool = StringPool::pack_v0(&mut items, true).unwrap();
+
+        let version = TableOfContentsVersion::V0;
+        let table_size = calculate_table_size(
+            toc.entries.len(),
+            toc.blocks.len(),
+            string_pool.len(),
+            version,
+        );
+        let header_bytes = (NativeFileHeader::SIZE_BYTES + table_size)
+            .next_multiple_of(NativeFileHeader::HEADER_PAGE_SIZE as usize);
+        let mut result = vec![0u8; header_bytes];
+        result[..NativeFileHeader::SIZE_BYTES].copy_from_slice(
+            &NativeFileHeader::init(reader.chunk_size(), header_bytes as u32).to_bytes(),
+        );
+        unsafe {
+            v1::serialize_table_of_contents(
+                &toc.block_compressions,
+                &toc.blocks,
+                &toc.entries,
+                version,
+                result.as_mut_ptr().add(NativeFileHeader::SIZE_BYTES),
+                &string_pool,
+            )
+            .unwrap();
+        }
+
+        result.extend_from_slice(&archive[reader.header().header_page_bytes() as usize..]);
+        result
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_upgrade_v1_archive() {
+        let large: Vec<u8> = (0..10_000_u32).map(|x| (x % 251) as u8).collect();
+        let archive = create_test_archive(
+            CHUNK_SIZE,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new("b.txt", b"Nx archives!"),
+            ]],
+            &[TestFile::new("large.bin", &large)],
+            CompressionPreference::ZStandard,
+        );
+        let v1_archive = create_v1_archive(&archive);
+
+        let mut upgraded = Vec::new();
+        upgrade_archive(&v1_archive, &mut upgraded).unwrap();
+
+        let reader = open(&upgraded);
+        assert_eq!(reader.header().header_page_bytes(), 4096);
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"Hello");
+        assert_eq!(
+            reader.read_file("b.txt").unwrap().as_slice(),
+            b"Nx archives!"
+        );
+        assert_eq!(reader.read_file("large.bin").unwrap(), large);
+
+        // Blocks are copied as-is.
+        let original = open(&archive);
+        for block_index in 0..original.block_count() {
+            assert_eq!(
+                reader.raw_block(block_index).unwrap().data,
+                original.raw_block(block_index).unwrap().data
+            );
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_downgrade_and_upgrade_archive() {
+        let archive = create_test_archive(
+            CHUNK_SIZE,
+            &[&[
+                TestFile::new("b.txt", b"Nx archives!"),
+                TestFile::new("a.txt", b"Hello"),
+            ]],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+
+        let mut downgraded = Vec::new();
+        downgrade_archive(&archive, None, &mut downgraded).unwrap();
+        let mut upgraded = Vec::new();
+        upgrade_archive(&downgraded, &mut upgraded).unwrap();
+
+        let reader = open(&upgraded);
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"Hello");
+        assert_eq!(
+            reader.read_file("b.txt").unwrap().as_slice(),
+            b"Nx archives!"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn downgrade_reports_blocking_features() {
+        let archive = create_test_archive_without_paths(
+            CHUNK_SIZE,
+            &[&[TestFile::new("a.txt", b"Hello")]],
+            &[],
+            CompressionPreference::Copy,
+        );
+
+        let result = downgrade_archive(&archive, None, &mut Vec::new());
+        match result {
+            Err(ArchiveConversionError::DowngradeBlocked(blockers)) => {
+                assert_eq!(blockers.as_slice(), &[DowngradeBlocker::NoFilePaths]);
+            }
+            _ => panic!("Expected downgrade to be blocked"),
+        }
+    }
+
+    #[test]
+    fn truncated_table_returns_error() {
+        let mut archive = vec![0u8; 4096];
+        archive[..NativeFileHeader::SIZE_BYTES]
+            .copy_from_slice(&NativeFileHeader::init(CHUNK_SIZE, 4096).to_bytes());
+
+        // Claim more files than fit in the header page.
+        let toc_header = NativeTocHeader::new(1000, 0, 0, TableOfContentsVersion::V0);
+        archive[NativeFileHeader::SIZE_BYTES..][..8].copy_from_slice(&toc_header.0.to_le_bytes());
+        assert!(matches!(
+            upgrade_archive(&archive, &mut Vec::new()),
+            Err(ArchiveConversionError::TableOfContents(
+                DeserializeError::InsufficientData(_)
+            ))
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::api::{
+    archive_lock::{ArchiveLock, LockError, LockOptions},
+    archive_reader::{ArchiveReadError, NxArchiveReader},
+    filedata::FromFilePathProvider,
+    traits::*,
+};
+use crate::{prelude::*, unsize_box2};
+use alloc::format;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror_no_std::Error;
+
+/// Errors that can occur when checking an archive for concurrent modification.
+#[derive(Debug, Error)]
+pub enum GenerationError {
+    /// The archive on disk was edited by someone else since it was opened.
+    #[error(
+        "The archive was modified concurrently (expected generation {expected}, found {actual})"
+    )]
+    Modified {
+        /// Generation of the archive when it was opened.
+        expected: u64,
+        /// Generation of the archive on disk.
+        actual: u64,
+    },
+
+    /// Failed to read the archive on disk.
+    #[error("Failed to read archive: {0:?}")]
+    Read(#[from] ArchiveReadError),
+
+    /// Failed to open the archive on disk.
+    #[error(transparent)]
+    FileProvider(#[from] FileProviderError),
+
+    /// Failed to take the lock on the archive.
+    #[error(transparent)]
+    Lock(#[from] LockError),
+
+    /// Failed to write the archive.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Reads the generation of the archive at a given path, without unpacking its string pool.
+/// See [`NxArchiveReader::generation`].
+///
+/// # Arguments
+///
+/// * `path` - Path of the archive.
+///
+/// # Returns
+///
+/// The generation, or 0 if the file does not exist.
+pub fn read_generation(path: &Path) -> Result<u64, GenerationError> {
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let provider = Box::new(FromFilePathProvider::new(&path.to_string_lossy())?);
+    let reader = NxArchiveReader::new_with_lazy_pool(unsize_box2!(provider))?;
+    Ok(reader.generation())
+}
+
+/// Checks that the archive at a given path is still at the generation it was opened at,
+/// i.e. that nobody else has edited it since.
+///
+/// # Arguments
+///
+/// * `path` - Path of the archive.
+/// * `expected` - Generation of the archive when it was opened, e.g. from
+///   [`NxArchiveReader::generation`]. 0 if the file did not exist yet.
+pub fn check_unmodified(path: &Path, expected: u64) -> Result<(), GenerationError> {
+    let actual = read_generation(path)?;
+    match actual == expected {
+        true => Ok(()),
+        false => Err(GenerationError::Modified { expected, actual }),
+    }
+}
+
+/// Replaces the archive at a given path with a new version, unless it was edited by
+/// someone else since it was opened.
+///
+/// The new archive is written to a temporary file next to the old one, which then replaces
+/// it; readers therefore see either the old or the new archive, never a partial one.
+/// The [`ArchiveLock`] is held while checking and replacing the archive, so that two
+/// processes saving at the same time can't both pass the check.
+///
+/// # Arguments
+///
+/// * `path` - Path of the archive.
+/// * `expected` - Generation of the archive when it was opened. 0 if the file did not exist yet.
+/// * `archive` - The new version of the archive, whose generation should be higher than `expected`
+///   (e.g. from [`ContentStore::to_archive`]).
+/// * `lock` - How long to wait for other processes editing the archive.
+///
+/// # Errors
+///
+/// Returns [`GenerationError::Modified`] if the generation on disk is not `expected`;
+/// the archive on disk is then left untouched.
+///
+/// [`ContentStore::to_archive`]: crate::api::content_store::ContentStore::to_archive
+pub fn save_if_unmodified(
+    path: &Path,
+    expected: u64,
+    archive: &[u8],
+    lock: &LockOptions,
+) -> Result<(), GenerationError> {
+    let _lock = ArchiveLock::acquire(path, lock)?;
+    check_unmodified(path, expected)?;
+
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+    fs::write(&temp_path, archive)?;
+    if let Err(error) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(error.into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{content_store::ContentStore, packing::packing_settings::PackingSettings};
+    use tempfile::tempdir;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn detects_concurrent_modification() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cache.nx");
+
+        let mut store = ContentStore::new(PackingSettings::new()).unwrap();
+        store.put(b"Hello").unwrap();
+        let lock = LockOptions::default();
+        save_if_unmodified(&path, 0, &store.to_archive().unwrap(), &lock).unwrap();
+        assert_eq!(read_generation(&path).unwrap(), 2);
+
+        // Two processes open the same archive.
+        let mut first =
+            ContentStore::open(fs::read(&path).unwrap(), PackingSettings::new()).unwrap();
+        let mut second =
+            ContentStore::open(fs::read(&path).unwrap(), PackingSettings::new()).unwrap();
+        let opened_at = first.generation();
+
+        first.put(b"First").unwrap();
+        save_if_unmodified(&path, opened_at, &first.to_archive().unwrap(), &lock).unwrap();
+
+        second.put(b"Second").unwrap();
+        let result = save_if_unmodified(&path, opened_at, &second.to_archive().unwrap(), &lock);
+        assert!(matches!(
+            result,
+            Err(GenerationError::Modified {
+                expected: 2,
+                actual: 3
+            })
+        ));
+
+        let saved = ContentStore::open(fs::read(&path).unwrap(), PackingSettings::new()).unwrap();
+        assert_eq!(saved.len(), 2);
+        // Only the archive and its lock file remain; no temporary files.
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
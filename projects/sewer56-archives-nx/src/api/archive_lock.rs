@@ -0,0 +1,158 @@
+use core::time::Duration;
+use fs2::FileExt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use thiserror_no_std::Error;
+
+/// How long [`ArchiveLock::acquire`] waits for another process to release the lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockOptions {
+    /// Longest time to wait for the lock, or [`None`] to wait indefinitely.
+    pub timeout: Option<Duration>,
+
+    /// Time between attempts to take the lock while waiting.
+    pub poll_interval: Duration,
+}
+
+impl Default for LockOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Some(Duration::from_secs(30)),
+            poll_interval: Duration::from_millis(50),
+        }
+    }
+}
+
+impl LockOptions {
+    /// Fails right away if the lock is held by another process.
+    pub fn no_wait() -> Self {
+        Self {
+            timeout: Some(Duration::ZERO),
+            ..Default::default()
+        }
+    }
+
+    /// Sets the longest time to wait for the lock.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Longest time to wait, or [`None`] to wait indefinitely.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Errors that can occur when locking an archive.
+#[derive(Debug, Error)]
+pub enum LockError {
+    /// Another process held the lock for longer than [`LockOptions::timeout`].
+    #[error("Timed out waiting for the lock on {0:?}")]
+    Timeout(PathBuf),
+
+    /// Failed to create or lock the lock file.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// An exclusive advisory lock on an archive, held until dropped.
+///
+/// Processes which edit the same archive (e.g. multiple mod manager instances sharing a
+/// cache) take this lock around each edit, so one process's write can't interleave with
+/// another's. The lock is advisory; it only excludes other processes which also take it.
+///
+/// The lock is held on a separate `<archive>.lock` file, rather than the archive itself,
+/// so the archive can be replaced (see [`save_if_unmodified`]) while locked. The lock file
+/// is left in place when the lock is released.
+///
+/// Uses `flock` on Unix and `LockFileEx` on Windows.
+///
+/// [`save_if_unmodified`]: crate::api::archive_generation::save_if_unmodified
+#[derive(Debug)]
+pub struct ArchiveLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl ArchiveLock {
+    /// Takes the lock on an archive, waiting for other processes to release it as
+    /// per the options.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive_path` - Path of the archive. It doesn't need to exist.
+    /// * `options` - How long to wait for the lock.
+    pub fn acquire(archive_path: &Path, options: &LockOptions) -> Result<Self, LockError> {
+        let mut name = archive_path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        let path = archive_path.with_file_name(name);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        let start = Instant::now();
+        loop {
+            match file.try_lock_exclusive() {
+                Ok(()) => return Ok(Self { file, path }),
+                Err(error) if error.kind() != fs2::lock_contended_error().kind() => {
+                    return Err(error.into())
+                }
+                Err(_) => {}
+            }
+
+            let elapsed = start.elapsed();
+            let wait = match options.timeout {
+                Some(timeout) if elapsed >= timeout => return Err(LockError::Timeout(path)),
+                Some(timeout) => options.poll_interval.min(timeout - elapsed),
+                None => options.poll_interval,
+            };
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// Returns the path of the lock file.
+    pub fn lock_path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ArchiveLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn excludes_other_holders_until_released() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cache.nx");
+
+        let lock = ArchiveLock::acquire(&path, &LockOptions::default()).unwrap();
+        assert!(lock.lock_path().ends_with("cache.nx.lock"));
+
+        // Locks are per file handle, so a second handle in this process is excluded too.
+        let options = LockOptions::no_wait();
+        assert!(matches!(
+            ArchiveLock::acquire(&path, &options),
+            Err(LockError::Timeout(_))
+        ));
+
+        drop(lock);
+        ArchiveLock::acquire(&path, &options).unwrap();
+    }
+}
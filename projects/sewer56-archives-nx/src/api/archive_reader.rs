@@ -0,0 +1,2785 @@
+use crate::api::{
+    archive_anomaly::{self, ArchiveAnomaly},
+    block_stream::BlockFrame,
+    enums::{CompressionPreference, DataFilter, PathCollation},
+    filedata::OffsetProvider,
+    path_aliases::{PathAliasError, PathAliases},
+    path_interner::{PathId, PathInterner},
+    read_throttle::ReadThrottle,
+    traits::*,
+};
+use crate::headers::{
+    managed::*,
+    parser::{
+        deserialize_dictionary_data, BlockFilter, BlockFilters, ChunkHashes, ChunkSizes,
+        DictionaryData, DictionaryReadError, DirectoryIndex, DirectoryLookup, HeaderExtensionError,
+        HeaderExtensionKind, HeaderExtensions, InlineFiles, PathFilter, StringPool,
+    },
+    raw::native_file_header::NativeFileHeader,
+    types::xxh3sum::XXH3sum,
+};
+use crate::utilities::compression::{
+    self, dictionary::ZstdDecompressionDict, filters::reverse_filter, zstd, NxDecompressionError,
+};
+use crate::{prelude::*, unsize_box2};
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::cmp::Ordering;
+use core::ops::{ControlFlow, Range};
+use once_cell::sync::OnceCell;
+use thiserror_no_std::Error;
+
+/// Alignment of each block within the archive.
+/// All sections of an archive are aligned to this value, as per the specification.
+const BLOCK_ALIGNMENT: u64 = NativeFileHeader::BLOCK_ALIGNMENT as u64;
+
+/// Errors that can occur when reading from an Nx archive.
+#[derive(Debug, Error)]
+pub enum ArchiveReadError {
+    /// The data does not start with a valid Nx header.
+    #[error("The archive does not have a valid Nx header")]
+    InvalidMagicHeader,
+
+    /// The archive is shorter than what the header claims.
+    #[error("The archive is truncated, expected at least {0} bytes")]
+    Truncated(u64),
+
+    /// Failed to deserialize the table of contents.
+    #[error("Failed to deserialize the table of contents: {0:?}")]
+    TableOfContents(#[from] DeserializeError),
+
+    /// Failed to read data from the underlying provider.
+    #[error(transparent)]
+    FileProvider(#[from] FileProviderError),
+
+    /// Failed to decompress a block.
+    #[error("Failed to decompress block: {0:?}")]
+    Decompression(#[from] NxDecompressionError),
+
+    /// A file entry references a block which does not exist.
+    #[error("Block index {0} is out of range")]
+    BlockOutOfRange(u32),
+
+    /// A block decompressed to fewer bytes than the file entries require.
+    #[error("Block {0} is smaller than expected")]
+    BlockTooSmall(u32),
+
+    /// No file with the given path exists in the archive.
+    #[error("File not found in archive")]
+    FileNotFound,
+
+    /// The requested byte range exceeds the size of the file.
+    #[error("Requested range {offset}..{offset}+{length} exceeds file size {file_size}")]
+    RangeOutOfBounds {
+        offset: u64,
+        length: u64,
+        file_size: u64,
+    },
+
+    /// The header extensions stored after the string pool could not be parsed.
+    #[error("Invalid header extensions: {0}")]
+    InvalidHeaderExtensions(#[from] HeaderExtensionError),
+
+    /// The path alias table stored in the archive could not be parsed.
+    #[error("Invalid path alias table: {0}")]
+    InvalidPathAliases(#[from] PathAliasError),
+
+    /// A file is larger than allowed by [`ReadLimits::max_file_size`].
+    #[error("File size {size} exceeds the maximum allowed size {max_size}")]
+    FileTooLarge { size: u64, max_size: u64 },
+
+    /// Not enough memory could be allocated for the data, e.g. because the archive claims
+    /// an implausible file size.
+    #[error("Failed to allocate {0} bytes")]
+    OutOfMemory(u64),
+
+    /// A block would decompress to more data than allowed by [`ReadLimits::max_compression_ratio`].
+    #[error("Block {block_index} claims {decompressed_size} bytes from {compressed_size} compressed bytes, exceeding the maximum compression ratio")]
+    CompressionRatioExceeded {
+        block_index: u32,
+        compressed_size: u64,
+        decompressed_size: u64,
+    },
+
+    /// The dictionary segment supplied with [`NxArchiveReader::with_dictionary_segment`]
+    /// could not be decompressed or parsed.
+    #[error("Invalid dictionary segment: {0}")]
+    InvalidDictionaries(#[from] DictionaryReadError),
+
+    /// A block could not be decompressed, and may need a dictionary;
+    /// but dictionaries were turned off with [`DictionaryLoading::Off`].
+    #[error("Block {0} may need a dictionary, but dictionaries are turned off")]
+    DictionariesDisabled(u32),
+}
+
+/// When a reader decompresses the dictionary segment supplied with
+/// [`NxArchiveReader::with_dictionary_segment`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DictionaryLoading {
+    /// Right away, so a corrupt segment is reported before any file is read.
+    Eager,
+
+    /// When the first ZStandard or LZ4 block is read. Callers which only list files or
+    /// read hashes never pay for it.
+    #[default]
+    Lazy,
+
+    /// Never. Blocks compressed without a dictionary are read as normal;
+    /// reads of blocks which fail to decompress return [`ArchiveReadError::DictionariesDisabled`].
+    Off,
+}
+
+/// Limits enforced when reading files, as a safeguard against crafted archives which
+/// claim huge sizes to exhaust memory.
+///
+/// These are checked before any memory is allocated for a file. All limits are disabled
+/// by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReadLimits {
+    /// Maximum size of a single file, in bytes.
+    pub max_file_size: Option<u64>,
+
+    /// Maximum ratio between the decompressed and compressed size of a block.
+    /// Highly repetitive data (e.g. zero filled files) can legitimately reach ratios in the
+    /// thousands, so the limit should account for the expected contents.
+    pub max_compression_ratio: Option<u32>,
+}
+
+impl ReadLimits {
+    /// Sets the maximum size of a single file, see [`Self::max_file_size`].
+    pub fn with_max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = Some(max_file_size);
+        self
+    }
+
+    /// Sets the maximum compression ratio of a block, see [`Self::max_compression_ratio`].
+    pub fn with_max_compression_ratio(mut self, max_compression_ratio: u32) -> Self {
+        self.max_compression_ratio = Some(max_compression_ratio);
+        self
+    }
+}
+
+/// A block of an archive in its compressed form, as stored on disk.
+///
+/// These can be moved between archives without decompressing them,
+/// see [`NxRawArchiveWriter`](crate::api::raw_archive_writer::NxRawArchiveWriter).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawBlock {
+    /// The compressed data of the block.
+    pub data: Vec<u8>,
+
+    /// The compression used by the block.
+    pub compression: CompressionPreference,
+
+    /// The filter applied to the block's data before it was compressed, if any.
+    pub filter: Option<BlockFilter>,
+
+    /// The dictionary the block was compressed with, if any. Only known if the dictionaries
+    /// were supplied to the reader, see [`NxArchiveReader::with_dictionaries`].
+    pub dictionary: Option<Vec<u8>>,
+}
+
+/// Reference to a file entry within an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryRef<'r> {
+    /// Index of the entry in [`NxArchiveReader::entries`].
+    pub index: usize,
+
+    /// The entry itself.
+    pub entry: &'r FileEntry,
+}
+
+/// Information about a file in an archive, as returned by [`NxArchiveReader::iter_entries`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryInfo<'r> {
+    index: usize,
+    entry: &'r FileEntry,
+}
+
+impl<'r> EntryInfo<'r> {
+    /// Returns the index of the entry in [`NxArchiveReader::entries`].
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the size of the file, in bytes.
+    pub fn size(&self) -> u64 {
+        self.entry.decompressed_size
+    }
+
+    /// Returns the XXH3 hash of the file's contents.
+    /// This is 0 if the archive was packed without hashes.
+    pub fn hash(&self) -> u64 {
+        self.entry.hash
+    }
+
+    /// Returns the underlying entry, e.g. for [`NxArchiveReader::read_entry_range`].
+    pub fn entry(&self) -> &'r FileEntry {
+        self.entry
+    }
+}
+
+/// An owned copy of a file's entry and path, detached from the reader.
+///
+/// Unlike [`EntryInfo`], this does not borrow the table of contents, so it can be held
+/// by async tasks, sent to other threads (e.g. a UI thread) or kept after the reader is
+/// dropped. Cloning is cheap, as the path is reference counted.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EntrySnapshot {
+    /// Relative path of the file within the archive.
+    pub path: Arc<str>,
+
+    /// Index of the entry in [`NxArchiveReader::entries`].
+    pub index: usize,
+
+    /// Size of the file, in bytes.
+    pub size: u64,
+
+    /// XXH3 hash of the file's contents.
+    /// This is 0 if the archive was packed without hashes.
+    pub hash: u64,
+
+    /// The blocks the file's data is stored in.
+    pub blocks: FileBlockMapping,
+}
+
+static_assertions::assert_impl_all!(EntrySnapshot: Send, Sync);
+
+/// Information about a file, resolved from the table of contents without reading block data.
+/// Returned by [`NxArchiveReader::stat`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryStat {
+    /// Size of the file, in bytes.
+    pub size: u64,
+
+    /// XXH3 hash of the file's contents, or [`None`] if the archive stores no hashes.
+    pub hash: Option<u64>,
+
+    /// Number of blocks which must be decompressed to read the whole file.
+    /// This is 0 for empty and inlined files.
+    pub block_count: u32,
+
+    /// Compression of the first block holding the file, or [`None`] if it has no blocks.
+    pub compression: Option<CompressionPreference>,
+
+    /// True if the file's data can be read without fetching it from a slow source,
+    /// see [`InputDataProvider::is_resident`].
+    pub resident_in_cache: bool,
+}
+
+/// A file, or chunk of a file, stored in a block. Returned by [`NxArchiveReader::block_files`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockFile {
+    /// Index of the file's entry in [`NxArchiveReader::entries`].
+    pub entry_index: usize,
+
+    /// Offset of the data within the decompressed block.
+    pub offset_in_block: u64,
+
+    /// Offset of the data within the file. This is 0 unless the block holds a chunk of a
+    /// file after the first.
+    pub offset_in_file: u64,
+
+    /// Length of the data, in bytes.
+    pub length: u64,
+}
+
+/// The decompressed contents of a block, and the files stored in it.
+/// Returned by [`NxArchiveReader::extract_block`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecompressedBlock {
+    block_index: u32,
+    data: Vec<u8>,
+    files: Vec<BlockFile>,
+}
+
+impl DecompressedBlock {
+    /// Returns the index of the block in the archive.
+    pub fn block_index(&self) -> u32 {
+        self.block_index
+    }
+
+    /// Returns the decompressed data of the block.
+    ///
+    /// This ends at the end of the last file in the block; data after it (if any) is not read.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the files stored in the block, ordered by entry index.
+    pub fn files(&self) -> &[BlockFile] {
+        &self.files
+    }
+
+    /// Returns the data of a file stored in the block.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - One of [`Self::files`].
+    pub fn file_data(&self, file: &BlockFile) -> &[u8] {
+        let start = file.offset_in_block as usize;
+        &self.data[start..start + file.length as usize]
+    }
+
+    /// Takes the decompressed data of the block.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Reads files from an existing Nx archive.
+///
+/// The header and table of contents are parsed on creation, block data is only
+/// fetched (and decompressed) from the underlying [`InputDataProvider`] when a file is read.
+///
+/// When opened with [`Self::new_with_lazy_pool`], the string pool (file paths) is also left
+/// compressed until a path is first needed.
+///
+/// # Thread Safety
+///
+/// The reader is [`Send`] and [`Sync`]; a single opened archive can be shared between threads
+/// (e.g. via `&` or [`Arc`]), and files read from all of them at once without any locking
+/// by the caller.
+///
+/// - The header and table of contents are immutable after opening.
+/// - The lazily unpacked string pool is initialized at most once, even if multiple threads
+///   request it at the same time.
+/// - Decompression contexts are kept per thread, and are never shared between threads.
+///   Digested dictionaries are created at most once, and shared between all threads.
+/// - Block data is fetched with positioned reads from the [`InputDataProvider`], which must
+///   itself be [`Sync`]. Providers which hold a single stream (e.g. [`FromStreamProvider`])
+///   serialize their reads internally, memory mapped and in-memory providers do not.
+///
+/// [`Arc`]: std::sync::Arc
+/// [`FromStreamProvider`]: crate::api::filedata::FromStreamProvider
+pub struct NxArchiveReader<'a> {
+    /// Provides access to the raw archive bytes.
+    provider: Box<dyn InputDataProvider + Send + Sync + 'a>,
+
+    /// The header of the archive.
+    header: NativeFileHeader,
+
+    /// The deserialized table of contents.
+    toc: TableOfContents,
+
+    /// Offset of each block from the start of the archive.
+    block_offsets: Box<[u64]>,
+
+    /// Location of the string pool, if it has not been unpacked into [`Self::toc`].
+    lazy_pool_location: Option<StringPoolLocation>,
+
+    /// The string pool, once unpacked on demand.
+    lazy_pool: OnceCell<StringPool>,
+
+    /// The paths, once moved into a [`PathInterner`] with [`Self::intern_paths`].
+    interned_pool: Option<InternedPool<'a>>,
+
+    /// Aliases of moved files, once loaded with [`Self::load_path_aliases`].
+    path_aliases: Option<PathAliases>,
+
+    /// Small files stored in the header, see [`Self::inline_files`].
+    inline_files: Option<InlineFiles>,
+
+    /// Filter of the paths in the archive, see [`Self::may_contain`].
+    path_filter: Option<PathFilter>,
+
+    /// Range of paths within each directory, see [`Self::entries_in_directory`].
+    directory_index: Option<DirectoryIndex>,
+
+    /// Number of times the archive was edited, see [`Self::generation`].
+    generation: u64,
+
+    /// Seed of the file hashes, see [`Self::hash_seed`].
+    hash_seed: u64,
+
+    /// Hashes of each chunk of the chunked files, see [`Self::chunk_hashes`].
+    chunk_hashes: Option<ChunkHashes>,
+
+    /// Filters applied to the data of blocks before compression, reversed when decompressing.
+    block_filters: Option<BlockFilters>,
+
+    /// Chunk sizes of files not using the header's chunk size, see [`Self::entry_chunk_size`].
+    chunk_sizes: Option<ChunkSizes>,
+
+    /// Index of the entry with each path index, created when first needed by [`Self::find_entry`]
+    /// or [`Self::entries_in_directory`].
+    entries_by_path: OnceCell<Box<[u32]>>,
+
+    /// Decompressed size of the data used in each block, created when first needed by [`Self::compressed_footprint`].
+    block_used_sizes: OnceCell<Box<[u64]>>,
+
+    /// Prefix of every path in the archive, see [`Self::open_with_mount_prefix`].
+    mount_prefix: String,
+
+    /// Comparison the paths were sorted with, for archives packed with [`PathCollation::Custom`].
+    /// See [`Self::with_path_collation`].
+    custom_collation: Option<PathCollation>,
+
+    /// Dictionaries the blocks were compressed with, see [`Self::with_dictionaries`].
+    /// Set on first use if loaded lazily from [`Self::dictionary_segment`].
+    dictionaries: OnceCell<LoadedDictionaries>,
+
+    /// Compressed dictionary segment, for [`DictionaryLoading::Lazy`].
+    dictionary_segment: Option<Vec<u8>>,
+
+    /// True with [`DictionaryLoading::Off`].
+    dictionaries_disabled: bool,
+
+    /// Limits enforced when reading files, see [`Self::with_read_limits`].
+    limits: ReadLimits,
+
+    /// Caps how fast blocks are decompressed, see [`Self::with_read_throttle`].
+    throttle: Option<Arc<ReadThrottle>>,
+
+    /// Problems found when opening the archive, see [`Self::anomalies`].
+    anomalies: Vec<ArchiveAnomaly>,
+}
+
+static_assertions::assert_impl_all!(NxArchiveReader<'static>: Send, Sync);
+
+impl<'a> NxArchiveReader<'a> {
+    /// Opens an archive, parsing its header and table of contents.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - Provides the raw bytes of the archive.
+    pub fn new(
+        provider: Box<dyn InputDataProvider + Send + Sync + 'a>,
+    ) -> Result<Self, ArchiveReadError> {
+        Self::open(provider, false)
+    }
+
+    /// Opens an archive, parsing its header and table of contents,
+    /// but deferring decompression of the string pool until a path is first needed.
+    ///
+    /// This reduces the cost of opening archives where only a few paths
+    /// (or none at all) are resolved.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - Provides the raw bytes of the archive.
+    pub fn new_with_lazy_pool(
+        provider: Box<dyn InputDataProvider + Send + Sync + 'a>,
+    ) -> Result<Self, ArchiveReadError> {
+        Self::open(provider, true)
+    }
+
+    /// Opens an archive, presenting all of its files as if they were inside a folder.
+    ///
+    /// Every path returned by the reader starts with the prefix, and files are looked up
+    /// by their prefixed paths. This allows multiple archives to share a single namespace,
+    /// e.g. when combined with [`PathInterner`] or
+    /// [`NxPackerBuilder::add_archive`](crate::api::packer_builder::NxPackerBuilder::add_archive).
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - Provides the raw bytes of the archive.
+    /// * `prefix` - Folder to mount the archive at, e.g. `mods/foo`.
+    ///   A trailing separator is added if missing.
+    pub fn open_with_mount_prefix(
+        provider: Box<dyn InputDataProvider + Send + Sync + 'a>,
+        prefix: &str,
+    ) -> Result<Self, ArchiveReadError> {
+        let mut reader = Self::open(provider, false)?;
+        let prefix = prefix.trim_end_matches(['/', '\\']);
+        if !prefix.is_empty() {
+            reader.mount_prefix = String::from(prefix);
+            reader.mount_prefix.push('/');
+            reader.toc.pool = reader.toc.pool.with_prefix(&reader.mount_prefix);
+        }
+
+        Ok(reader)
+    }
+
+    /// Opens an archive embedded inside another file, such as a self-extracting executable
+    /// or a game's package file, reading it in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - Provides the raw bytes of the containing file.
+    /// * `offset` - Offset of the archive within the containing file.
+    /// * `length` - Length of the archive. Reads past its end fail, rather than reading
+    ///   whatever follows the archive in the containing file.
+    ///
+    /// # Remarks
+    ///
+    /// The archive does not need to be aligned within the containing file; the alignment
+    /// of its blocks is relative to the start of the archive.
+    pub fn open_at_offset(
+        provider: Box<dyn InputDataProvider + Send + Sync + 'a>,
+        offset: u64,
+        length: u64,
+    ) -> Result<Self, ArchiveReadError> {
+        let provider = Box::new(OffsetProvider::new(provider, offset, length));
+        Self::open(unsize_box2!(provider), false)
+    }
+
+    /// Lists the file entries of an archive in fixed-size batches, without opening it.
+    ///
+    /// Entries are decoded straight from the table of contents into a buffer of
+    /// `batch_size` entries, which is reused for every batch; the full entry array,
+    /// the blocks and the string pool are never held in memory. Use this to list
+    /// archives with many millions of files on memory constrained devices.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - Provides the raw bytes of the archive.
+    /// * `batch_size` - Maximum number of entries passed to the visitor at once. At least 1.
+    /// * `visitor` - Called with each batch of entries, in table of contents order.
+    ///   Return [`ControlFlow::Break`] to stop early.
+    ///
+    /// # Returns
+    ///
+    /// The number of files in the archive.
+    ///
+    /// # Remarks
+    ///
+    /// Paths are not available, as they are stored in the compressed string pool.
+    /// Entries are in the same order as [`Self::entries`], so the `n`th entry visited
+    /// has the `n`th path of the pool.
+    pub fn visit_entries(
+        provider: &(dyn InputDataProvider + '_),
+        batch_size: usize,
+        visitor: impl FnMut(&[FileEntry]) -> ControlFlow<()>,
+    ) -> Result<u32, ArchiveReadError> {
+        let header = Self::read_header(provider)?;
+        let header_bytes = header.header_page_bytes();
+        let data = provider.get_file_data(0, header_bytes as u64)?;
+        let data = data.data();
+        if data.len() < header_bytes as usize || data.len() < NativeFileHeader::SIZE_BYTES {
+            return Err(ArchiveReadError::Truncated(header_bytes as u64));
+        }
+
+        // SAFETY: The ToC deserializer is bounds checked against `avail_bytes` with `hardened`.
+        let toc_ptr = unsafe { data.as_ptr().add(NativeFileHeader::SIZE_BYTES) };
+        let avail_bytes = header_bytes - NativeFileHeader::SIZE_BYTES as u32;
+        let file_count = unsafe {
+            TableOfContents::visit_v2xx_entries(toc_ptr, avail_bytes, batch_size, visitor)?
+        };
+        Ok(file_count)
+    }
+
+    fn read_header(
+        provider: &(dyn InputDataProvider + '_),
+    ) -> Result<NativeFileHeader, ArchiveReadError> {
+        let header = {
+            let data = provider.get_file_data(0, NativeFileHeader::SIZE_BYTES as u64)?;
+            let Some(bytes) = data.data().first_chunk() else {
+                return Err(ArchiveReadError::Truncated(
+                    NativeFileHeader::SIZE_BYTES as u64,
+                ));
+            };
+
+            NativeFileHeader::from_bytes(*bytes)
+        };
+
+        if !header.is_valid_magic_header() {
+            return Err(ArchiveReadError::InvalidMagicHeader);
+        }
+
+        Ok(header)
+    }
+
+    fn open(
+        provider: Box<dyn InputDataProvider + Send + Sync + 'a>,
+        lazy_pool: bool,
+    ) -> Result<Self, ArchiveReadError> {
+        let header = Self::read_header(&*provider)?;
+        let header_bytes = header.header_page_bytes();
+        let mut anomalies = Vec::new();
+        let mut inline_files = None;
+        let mut path_filter = None;
+        let mut directory_index = None;
+        let mut generation = 0;
+        let mut hash_seed = 0;
+        let mut chunk_hashes = None;
+        let mut block_filters = None;
+        let mut chunk_sizes = None;
+        let (toc, lazy_pool_location) = {
+            let data = provider.get_file_data(0, header_bytes as u64)?;
+            let data = data.data();
+            if data.len() < header_bytes as usize || data.len() < NativeFileHeader::SIZE_BYTES {
+                return Err(ArchiveReadError::Truncated(header_bytes as u64));
+            }
+
+            // SAFETY: The ToC deserializer is bounds checked against `avail_bytes` with `hardened`.
+            let toc_ptr = unsafe { data.as_ptr().add(NativeFileHeader::SIZE_BYTES) };
+            let avail_bytes = header_bytes - NativeFileHeader::SIZE_BYTES as u32;
+            let (mut toc, location) = unsafe {
+                TableOfContents::deserialize_v2xx_without_pool_with_allocator(
+                    toc_ptr,
+                    avail_bytes,
+                    Global,
+                )?
+            };
+
+            let pool_start = NativeFileHeader::SIZE_BYTES + location.offset as usize;
+            let pool_end = pool_start + location.compressed_size as usize;
+            if !lazy_pool {
+                let pool = data
+                    .get(pool_start..pool_end)
+                    .ok_or(ArchiveReadError::Truncated(pool_end as u64))?;
+                toc.pool = StringPool::unpack_v0(pool, toc.entries.len(), true)
+                    .map_err(|e| ArchiveReadError::TableOfContents(e.into()))?;
+            }
+
+            let mut used_bytes = pool_end;
+            if header.feature_flags() & NativeFileHeader::EXTENSIONS_FLAG != 0 {
+                let region = data
+                    .get(pool_end..)
+                    .ok_or(ArchiveReadError::Truncated(pool_end as u64))?;
+                let extensions = HeaderExtensions::parse(region)?;
+                if let Some(x) = extensions.get(HeaderExtensionKind::InlineFiles) {
+                    inline_files = Some(InlineFiles::parse(x)?);
+                }
+                if let Some(x) = extensions.get(HeaderExtensionKind::PathFilter) {
+                    path_filter = Some(PathFilter::parse(x)?);
+                }
+                if let Some(x) = extensions.get(HeaderExtensionKind::DirectoryIndex) {
+                    directory_index = Some(DirectoryIndex::parse(x)?);
+                }
+                if let Some(x) = extensions.get(HeaderExtensionKind::Generation) {
+                    let x: [u8; 8] = x.try_into().map_err(|_| {
+                        HeaderExtensionError::Malformed(HeaderExtensionKind::Generation)
+                    })?;
+                    generation = u64::from_le_bytes(x);
+                }
+                if let Some(x) = extensions.get(HeaderExtensionKind::HashSeed) {
+                    let x: [u8; 8] = x.try_into().map_err(|_| {
+                        HeaderExtensionError::Malformed(HeaderExtensionKind::HashSeed)
+                    })?;
+                    hash_seed = u64::from_le_bytes(x);
+                }
+                if let Some(x) = extensions.get(HeaderExtensionKind::ChunkHashes) {
+                    chunk_hashes = Some(ChunkHashes::parse(x)?);
+                }
+                if let Some(x) = extensions.get(HeaderExtensionKind::BlockFilters) {
+                    block_filters = Some(BlockFilters::parse(x)?);
+                }
+                if let Some(x) = extensions.get(HeaderExtensionKind::ChunkSizes) {
+                    chunk_sizes = Some(ChunkSizes::parse(x)?);
+                }
+                used_bytes += extensions.size();
+            }
+
+            archive_anomaly::check_header(&header, data, used_bytes as u32, &mut anomalies);
+            (toc, lazy_pool.then_some(location))
+        };
+
+        archive_anomaly::check_entries(
+            &toc.entries,
+            toc.blocks.len(),
+            header.chunk_size_bytes(),
+            chunk_sizes.as_ref(),
+            &mut anomalies,
+        );
+
+        let mut block_offsets = Vec::with_capacity(toc.blocks.len());
+        let mut current_offset = header_bytes as u64;
+        for block in toc.blocks.iter() {
+            block_offsets.push(current_offset);
+            current_offset =
+                (current_offset + block.compressed_size as u64).next_multiple_of(BLOCK_ALIGNMENT);
+        }
+
+        Ok(Self {
+            provider,
+            header,
+            toc,
+            block_offsets: block_offsets.into_boxed_slice(),
+            lazy_pool_location,
+            lazy_pool: OnceCell::new(),
+            interned_pool: None,
+            path_aliases: None,
+            inline_files,
+            path_filter,
+            directory_index,
+            generation,
+            hash_seed,
+            chunk_hashes,
+            block_filters,
+            chunk_sizes,
+            entries_by_path: OnceCell::new(),
+            block_used_sizes: OnceCell::new(),
+            mount_prefix: String::new(),
+            custom_collation: None,
+            dictionaries: OnceCell::new(),
+            dictionary_segment: None,
+            dictionaries_disabled: false,
+            limits: ReadLimits::default(),
+            throttle: None,
+            anomalies,
+        })
+    }
+
+    /// Returns the problems found when opening the archive, which do not prevent reading it.
+    /// This is empty for archives written by this library.
+    pub fn anomalies(&self) -> &[ArchiveAnomaly] {
+        &self.anomalies
+    }
+
+    /// Sets limits enforced when reading files, to fail early on archives which claim
+    /// implausible sizes rather than allocating memory for them.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` - The limits to enforce.
+    pub fn with_read_limits(mut self, limits: ReadLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the limits enforced when reading files.
+    pub fn read_limits(&self) -> &ReadLimits {
+        &self.limits
+    }
+
+    /// Caps how fast this reader decompresses data, e.g. to stream assets in the background
+    /// without causing frame hitches. The throttle may be shared between readers.
+    ///
+    /// # Arguments
+    ///
+    /// * `throttle` - The throttle to charge decompressed bytes against.
+    pub fn with_read_throttle(mut self, throttle: Arc<ReadThrottle>) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    /// Returns the throttle set with [`Self::with_read_throttle`].
+    pub fn read_throttle(&self) -> Option<&Arc<ReadThrottle>> {
+        self.throttle.as_ref()
+    }
+
+    /// Supplies the dictionaries the blocks of the archive were compressed with.
+    ///
+    /// Each dictionary is digested the first time a block using it is read, and kept for
+    /// later reads; so reading many files of the same extension group only pays the cost
+    /// of loading their dictionary once.
+    ///
+    /// # Arguments
+    ///
+    /// * `dictionaries` - The dictionaries, e.g. from [`deserialize_dictionary_data`].
+    ///
+    /// [`deserialize_dictionary_data`]: crate::headers::parser::deserialize_dictionary_data
+    pub fn with_dictionaries(mut self, dictionaries: DictionaryData) -> Self {
+        self.dictionaries = OnceCell::with_value(LoadedDictionaries::new(dictionaries));
+        self.dictionary_segment = None;
+        self.dictionaries_disabled = false;
+        self
+    }
+
+    /// Supplies the dictionaries the blocks of the archive were compressed with, still in their
+    /// serialized (compressed) form, choosing when they are decompressed.
+    ///
+    /// Decompressing the segment can cost more than opening the archive itself; callers which
+    /// only list files or read hashes can defer it with [`DictionaryLoading::Lazy`], or skip it
+    /// with [`DictionaryLoading::Off`].
+    ///
+    /// # Arguments
+    ///
+    /// * `segment` - The serialized dictionaries, as accepted by [`deserialize_dictionary_data`].
+    /// * `loading` - When to decompress the segment.
+    ///
+    /// # Errors
+    ///
+    /// With [`DictionaryLoading::Eager`], returns an error if the segment is invalid.
+    /// Otherwise, this is reported by the first read which needs the dictionaries.
+    ///
+    /// # Safety
+    ///
+    /// Unless the `hardened` feature is enabled, the segment must come from a trusted source,
+    /// see [`deserialize_dictionary_data`].
+    pub unsafe fn with_dictionary_segment(
+        mut self,
+        segment: Vec<u8>,
+        loading: DictionaryLoading,
+    ) -> Result<Self, ArchiveReadError> {
+        self.dictionaries = OnceCell::new();
+        self.dictionary_segment = None;
+        self.dictionaries_disabled = false;
+        match loading {
+            DictionaryLoading::Eager => {
+                let dictionaries = deserialize_dictionary_data(&segment)?;
+                self.dictionaries = OnceCell::with_value(LoadedDictionaries::new(dictionaries));
+            }
+            DictionaryLoading::Lazy => self.dictionary_segment = Some(segment),
+            DictionaryLoading::Off => self.dictionaries_disabled = true,
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the dictionaries supplied with [`Self::with_dictionaries`] or
+    /// [`Self::with_dictionary_segment`].
+    ///
+    /// Dictionaries loaded with [`DictionaryLoading::Lazy`] are only returned once loaded.
+    pub fn dictionaries(&self) -> Option<&DictionaryData> {
+        self.dictionaries.get().map(|x| &x.data)
+    }
+
+    /// Returns the prefix of every path in the archive, including the trailing separator.
+    /// This is empty unless the archive was opened with [`Self::open_with_mount_prefix`].
+    pub fn mount_prefix(&self) -> &str {
+        &self.mount_prefix
+    }
+
+    /// Returns the header of the archive.
+    pub fn header(&self) -> &NativeFileHeader {
+        &self.header
+    }
+
+    /// Returns the collation the paths of the archive were sorted with.
+    ///
+    /// # Returns
+    ///
+    /// The collation, or [`None`] if the paths were sorted with [`PathCollation::Custom`],
+    /// in which case the original comparison function must be known by other means.
+    pub fn path_collation(&self) -> Option<PathCollation> {
+        PathCollation::from_feature_flags(self.header.feature_flags())
+    }
+
+    /// Supplies the comparison function the paths were sorted with, for archives packed
+    /// with [`PathCollation::Custom`].
+    ///
+    /// Finding a file binary searches the paths with the archive's collation; without it,
+    /// paths of such archives are compared one by one. Paths the collation considers equal
+    /// are treated as the same path. Archives which record their collation ignore this.
+    ///
+    /// # Arguments
+    ///
+    /// * `collation` - How the paths of the archive were compared when packing.
+    pub fn with_path_collation(mut self, collation: PathCollation) -> Self {
+        self.custom_collation = Some(collation);
+        self
+    }
+
+    /// Returns the table of contents of the archive.
+    ///
+    /// If the archive was opened with [`Self::new_with_lazy_pool`], the pool in the returned
+    /// table of contents is empty; use [`Self::string_pool`] instead.
+    pub fn table_of_contents(&self) -> &TableOfContents {
+        &self.toc
+    }
+
+    /// Returns the size of the chunks large files are split into.
+    ///
+    /// Individual files may be split with their own chunk size, see [`Self::entry_chunk_size`].
+    pub fn chunk_size(&self) -> u32 {
+        self.header.chunk_size_bytes()
+    }
+
+    /// Returns the chunk size of the files which were split with their own chunk size
+    /// rather than [`Self::chunk_size`], if any.
+    pub fn chunk_sizes(&self) -> Option<&ChunkSizes> {
+        self.chunk_sizes.as_ref()
+    }
+
+    /// Returns the size of the chunks a given entry is split into, if it is large enough
+    /// to be chunked; i.e. its own chunk size if it has one, else [`Self::chunk_size`].
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The entry of the file.
+    pub fn entry_chunk_size(&self, entry: &FileEntry) -> u32 {
+        self.chunk_sizes
+            .as_ref()
+            .and_then(|x| x.get(entry.first_block_index))
+            .unwrap_or_else(|| self.chunk_size())
+    }
+
+    /// Returns the number of blocks in the archive.
+    pub fn block_count(&self) -> u32 {
+        self.toc.blocks.len() as u32
+    }
+
+    /// Returns the offset of a block from the start of the archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_index` - Index of the block in the archive.
+    pub fn block_offset(&self, block_index: u32) -> Option<u64> {
+        self.block_offsets.get(block_index as usize).copied()
+    }
+
+    /// Returns the raw bytes of the header page, i.e. the header and table of contents.
+    /// Together with the blocks, this is everything needed to recreate the archive.
+    pub fn header_page(&self) -> Result<Vec<u8>, ArchiveReadError> {
+        let header_bytes = self.header.header_page_bytes() as u64;
+        let data = self.provider.get_file_data(0, header_bytes)?;
+
+        let mut result = Vec::with_capacity(header_bytes as usize);
+        result.extend_from_slice(data.data());
+        Ok(result)
+    }
+
+    /// Streams the compressed blocks in a given range, for sending an archive elsewhere
+    /// block by block. Use [`NxArchiveAssembler`] to put the archive back together.
+    ///
+    /// # Arguments
+    ///
+    /// * `blocks` - Range of block indices to stream.
+    ///
+    /// [`NxArchiveAssembler`]: crate::api::block_stream::NxArchiveAssembler
+    pub fn stream_blocks(
+        &self,
+        blocks: Range<u32>,
+    ) -> impl Iterator<Item = Result<BlockFrame, ArchiveReadError>> + '_ {
+        blocks.map(move |block_index| {
+            self.raw_block(block_index).map(|block| BlockFrame {
+                block_index,
+                data: block.data,
+            })
+        })
+    }
+
+    /// Returns the compressed bytes of a block, without decompressing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_index` - Index of the block in the archive.
+    pub fn raw_block(&self, block_index: u32) -> Result<RawBlock, ArchiveReadError> {
+        let idx = block_index as usize;
+        if idx >= self.toc.blocks.len() {
+            return Err(ArchiveReadError::BlockOutOfRange(block_index));
+        }
+
+        let compressed_size = self.toc.blocks[idx].compressed_size as u64;
+        let compressed = self
+            .provider
+            .get_file_data(self.block_offsets[idx], compressed_size)?;
+
+        let mut data = Vec::with_capacity(compressed_size as usize);
+        data.extend_from_slice(compressed.data());
+        let dictionary = self.loaded_dictionaries()?.and_then(|dictionaries| {
+            let dict_index = dictionaries.data.get_dictionary_index_for_block(idx)?;
+            dictionaries.data.get_dictionary(dict_index).map(Vec::from)
+        });
+        Ok(RawBlock {
+            data,
+            compression: self.toc.block_compressions[idx],
+            filter: self.block_filter(block_index),
+            dictionary,
+        })
+    }
+
+    /// Returns the files (or chunks of files) stored in a block, without reading it.
+    /// Inlined files are not stored in blocks, so they are never returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_index` - Index of the block in the archive.
+    pub fn block_files(&self, block_index: u32) -> Result<Vec<BlockFile>, ArchiveReadError> {
+        if block_index >= self.block_count() {
+            return Err(ArchiveReadError::BlockOutOfRange(block_index));
+        }
+
+        let mut result = Vec::new();
+        for (entry_index, entry) in self.toc.entries.iter().enumerate() {
+            if self.is_inlined(entry) {
+                continue;
+            }
+
+            let chunk_size = self.entry_chunk_size(entry);
+            let mapping = FileBlockMapping::from_entry(entry, chunk_size);
+            let Some(range) = mapping.range_in_block(block_index) else {
+                continue;
+            };
+
+            let offset_in_file = match mapping.is_multi_block() {
+                true => (block_index - mapping.first_block_index) as u64 * chunk_size as u64,
+                false => 0,
+            };
+            result.push(BlockFile {
+                entry_index,
+                offset_in_block: range.start,
+                offset_in_file,
+                length: range.end - range.start,
+            });
+        }
+
+        Ok(result)
+    }
+
+    /// Decompresses a whole block, for working with an archive block by block rather than
+    /// file by file (e.g. defragmenting, analysing or warming a cache).
+    ///
+    /// # Arguments
+    ///
+    /// * `block_index` - Index of the block in the archive.
+    ///
+    /// # Remarks
+    ///
+    /// The block is decompressed up to the end of its last file, see [`Self::block_files`].
+    /// Blocks no file refers to are not decompressed, and are returned empty.
+    pub fn extract_block(&self, block_index: u32) -> Result<DecompressedBlock, ArchiveReadError> {
+        let files = self.block_files(block_index)?;
+        let length = files
+            .iter()
+            .map(|file| file.offset_in_block + file.length)
+            .max()
+            .unwrap_or(0);
+
+        let data = match length {
+            0 => Vec::new(),
+            _ => self.decompress_block(block_index, length)?,
+        };
+        Ok(DecompressedBlock {
+            block_index,
+            data,
+            files,
+        })
+    }
+
+    /// Returns true if the entries of this archive contain file hashes.
+    ///
+    /// Archives may be packed without hashes to save space, see [`FileHashStorage`].
+    /// In that case [`Self::find_by_hash`] never finds anything.
+    ///
+    /// [`FileHashStorage`]: crate::api::enums::FileHashStorage
+    pub fn has_file_hashes(&self) -> bool {
+        self.toc.has_hashes
+    }
+
+    /// Returns all file entries in the archive.
+    pub fn entries(&self) -> &[FileEntry] {
+        &self.toc.entries
+    }
+
+    /// Returns the string pool, containing the relative paths of all files.
+    ///
+    /// If the archive was opened with [`Self::new_with_lazy_pool`], the pool is
+    /// decompressed on the first call. Once the paths are moved into a [`PathInterner`]
+    /// with [`Self::intern_paths`], the pool is empty.
+    pub fn string_pool(&self) -> Result<&StringPool, ArchiveReadError> {
+        let location = match self.lazy_pool_location {
+            Some(location) => location,
+            None => return Ok(&self.toc.pool),
+        };
+
+        self.lazy_pool
+            .get_or_try_init(|| self.unpack_string_pool(location))
+    }
+
+    fn unpack_string_pool(
+        &self,
+        location: StringPoolLocation,
+    ) -> Result<StringPool, ArchiveReadError> {
+        let offset = NativeFileHeader::SIZE_BYTES as u64 + location.offset as u64;
+        let data = self
+            .provider
+            .get_file_data(offset, location.compressed_size as u64)?;
+        StringPool::unpack_v0(data.data(), self.toc.entries.len(), true)
+            .map_err(|e| ArchiveReadError::TableOfContents(e.into()))
+    }
+
+    /// Returns true if the string pool has been decompressed.
+    /// This is always true, unless the archive was opened with [`Self::new_with_lazy_pool`].
+    pub fn is_string_pool_loaded(&self) -> bool {
+        self.lazy_pool_location.is_none() || self.lazy_pool.get().is_some()
+    }
+
+    /// Returns the relative path of a given entry.
+    pub fn file_path(&self, entry: &FileEntry) -> Result<Option<&str>, ArchiveReadError> {
+        Ok(self.paths()?.get(entry.file_path_index))
+    }
+
+    fn paths(&self) -> Result<EntryPaths<'_>, ArchiveReadError> {
+        match &self.interned_pool {
+            Some(interned) => Ok(EntryPaths::Interned(interned)),
+            None => Ok(EntryPaths::Pool(self.string_pool()?)),
+        }
+    }
+
+    /// Returns an iterator over every file in the archive, along with its path.
+    ///
+    /// Paths are borrowed from the string pool, so listing even very large archives
+    /// does not allocate.
+    ///
+    /// # Remarks
+    ///
+    /// Entries without a path (e.g. in archives packed without file paths) are skipped.
+    pub fn iter_entries(
+        &self,
+    ) -> Result<impl Iterator<Item = (&str, EntryInfo<'_>)> + '_, ArchiveReadError> {
+        let paths = self.paths()?;
+        Ok(self
+            .toc
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, entry)| {
+                let path = paths.get(entry.file_path_index)?;
+                Some((path, EntryInfo { index, entry }))
+            }))
+    }
+
+    /// Returns owned copies of every file in the archive, along with its path.
+    /// See [`EntrySnapshot`].
+    ///
+    /// # Remarks
+    ///
+    /// Entries without a path (e.g. in archives packed without file paths) are skipped.
+    pub fn snapshot_entries(&self) -> Result<Vec<EntrySnapshot>, ArchiveReadError> {
+        Ok(self
+            .iter_entries()?
+            .map(|(path, info)| EntrySnapshot {
+                path: Arc::from(path),
+                index: info.index,
+                size: info.size(),
+                hash: info.hash(),
+                blocks: self.block_mapping(info.entry),
+            })
+            .collect())
+    }
+
+    /// Returns information about a file, without reading or decompressing any block data,
+    /// e.g. to answer `fstat` style queries in a virtual filesystem.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Relative path of the file within the archive.
+    ///
+    /// # Remarks
+    ///
+    /// For archives opened with [`Self::new_with_lazy_pool`], the first lookup by path
+    /// unpacks the string pool.
+    pub fn stat(&self, path: &str) -> Result<EntryStat, ArchiveReadError> {
+        let entry = self
+            .find_entry(path)?
+            .ok_or(ArchiveReadError::FileNotFound)?;
+        Ok(self.stat_entry(entry))
+    }
+
+    /// Returns information about the file described by a given entry.
+    /// See [`Self::stat`] for details.
+    pub fn stat_entry(&self, entry: &FileEntry) -> EntryStat {
+        let mut result = EntryStat {
+            size: entry.decompressed_size,
+            hash: self.has_file_hashes().then_some(entry.hash),
+            block_count: 0,
+            compression: None,
+            resident_in_cache: true,
+        };
+        if entry.decompressed_size == 0 || self.is_inlined(entry) {
+            return result;
+        }
+
+        let mut blocks = self.block_mapping(entry).block_indices();
+        result.block_count = blocks.len() as u32;
+        result.compression = self
+            .toc
+            .block_compressions
+            .get(blocks.start as usize)
+            .copied();
+        result.resident_in_cache = blocks.all(|index| {
+            let index = index as usize;
+            match (self.block_offsets.get(index), self.toc.blocks.get(index)) {
+                (Some(offset), Some(block)) => self
+                    .provider
+                    .is_resident(*offset, block.compressed_size as u64),
+                _ => false,
+            }
+        });
+        result
+    }
+
+    /// Returns the blocks a given entry's data is stored in.
+    pub fn block_mapping(&self, entry: &FileEntry) -> FileBlockMapping {
+        FileBlockMapping::from_entry(entry, self.entry_chunk_size(entry))
+    }
+
+    /// Returns the space a file takes up in the archive, in compressed bytes.
+    ///
+    /// This is exact for files stored in blocks of their own (e.g. chunked files). For files
+    /// sharing a SOLID block, the block's compressed size is split between its files by their
+    /// decompressed size, so the result is an estimate. Files deduplicated against each other
+    /// are each given the full size of their shared data.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The entry of the file.
+    pub fn compressed_footprint(&self, entry: &FileEntry) -> u64 {
+        let blocks = &self.toc.blocks;
+        let mapping = self.block_mapping(entry);
+        if mapping.is_multi_block() {
+            return mapping
+                .block_indices()
+                .filter_map(|x| blocks.get(x as usize))
+                .map(|x| x.compressed_size as u64)
+                .sum();
+        }
+
+        let Some(block) = blocks.get(entry.first_block_index as usize) else {
+            return 0;
+        };
+        let used_sizes = self.block_used_sizes.get_or_init(|| {
+            let mut sizes = vec![0u64; blocks.len()];
+            for entry in self.toc.entries.iter() {
+                let mapping = self.block_mapping(entry);
+                if mapping.is_multi_block() {
+                    continue;
+                }
+                if let Some(size) = sizes.get_mut(entry.first_block_index as usize) {
+                    let end = entry.decompressed_block_offset as u64 + entry.decompressed_size;
+                    *size = (*size).max(end);
+                }
+            }
+            sizes.into_boxed_slice()
+        });
+
+        let used = used_sizes[entry.first_block_index as usize];
+        if used == 0 {
+            return 0;
+        }
+        (block.compressed_size as u128 * entry.decompressed_size as u128 / used as u128) as u64
+    }
+
+    /// Returns the compression ratio of a file, i.e. its [`Self::compressed_footprint`]
+    /// divided by its size. Lower is better; 1.0 means the file was stored as-is.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The entry of the file.
+    ///
+    /// # Returns
+    ///
+    /// The ratio, or 1.0 for empty files.
+    pub fn compression_ratio(&self, entry: &FileEntry) -> f64 {
+        if entry.decompressed_size == 0 {
+            return 1.0;
+        }
+        self.compressed_footprint(entry) as f64 / entry.decompressed_size as f64
+    }
+
+    /// Moves the paths of all entries into a [`PathInterner`], so archives with overlapping
+    /// file trees share a single copy of each path.
+    ///
+    /// Afterwards, the reader no longer holds a string pool of its own; lookups such as
+    /// [`Self::find_entry`] and [`Self::file_path`] resolve paths through the interner.
+    /// If the archive was opened with [`Self::new_with_lazy_pool`] and the pool is not yet loaded,
+    /// the pool is decompressed temporarily. Does nothing if the paths are already interned.
+    ///
+    /// # Arguments
+    ///
+    /// * `interner` - The interner to add the paths to, e.g. [`PathInterner::global`].
+    pub fn intern_paths(&mut self, interner: &'a PathInterner) -> Result<(), ArchiveReadError> {
+        if self.interned_pool.is_some() {
+            return Ok(());
+        }
+
+        let pool = match (self.lazy_pool_location, self.lazy_pool.take()) {
+            (_, Some(pool)) => pool,
+            (Some(location), None) => self.unpack_string_pool(location)?,
+            (None, None) => core::mem::replace(&mut self.toc.pool, StringPool::empty_in(&Global)),
+        };
+
+        let paths = pool
+            .iter()
+            .map(|path| interner.intern_shared(path))
+            .collect();
+        self.lazy_pool_location = None;
+        self.interned_pool = Some(InternedPool { interner, paths });
+        Ok(())
+    }
+
+    /// Returns the [`PathId`] of an entry's path, once interned with [`Self::intern_paths`].
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The entry of the file.
+    pub fn path_id(&self, entry: &FileEntry) -> Option<PathId> {
+        let interned = self.interned_pool.as_ref()?;
+        interned
+            .paths
+            .get(entry.file_path_index as usize)
+            .map(|(id, _)| *id)
+    }
+
+    /// Finds all entries whose contents have the given hash.
+    ///
+    /// This does not require the string pool, so works for archives packed without
+    /// file paths, and does not trigger decompression of the pool for [`Self::new_with_lazy_pool`].
+    ///
+    /// # Arguments
+    ///
+    /// * `hash` - The XXH3 hash of the file's contents.
+    ///
+    /// # Returns
+    ///
+    /// All matching entries; multiple entries can share contents. Empty if the
+    /// hash is not found, or if the archive was packed without hashes.
+    pub fn find_by_hash(&self, hash: u64) -> Vec<EntryRef<'_>> {
+        self.toc
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.hash == hash)
+            .map(|(index, entry)| EntryRef { index, entry })
+            .collect()
+    }
+
+    /// Finds the entry with the given relative path.
+    ///
+    /// If the path is not found and aliases were loaded with [`Self::load_path_aliases`],
+    /// the file the path was moved to is returned instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Relative path of the file within the archive.
+    pub fn find_entry(&self, path: &str) -> Result<Option<&FileEntry>, ArchiveReadError> {
+        if let Some(entry) = self.find_entry_exact(path)? {
+            return Ok(Some(entry));
+        }
+
+        let aliases = match &self.path_aliases {
+            Some(aliases) => aliases,
+            None => return Ok(None),
+        };
+
+        // Aliases are stored without the mount prefix.
+        let resolved = path
+            .strip_prefix(self.mount_prefix.as_str())
+            .and_then(|x| aliases.resolve(x));
+        match resolved {
+            Some(new_path) => self.find_entry_exact(&self.mounted_path(new_path)),
+            None => Ok(None),
+        }
+    }
+
+    /// Loads the path alias table stored in the archive, if any.
+    ///
+    /// Once loaded, files can also be found by the paths they had before being moved.
+    /// Archives without a table get an empty one.
+    pub fn load_path_aliases(&mut self) -> Result<&PathAliases, ArchiveReadError> {
+        let aliases = match self.find_entry_exact(&self.mounted_path(PathAliases::FILE_PATH))? {
+            Some(entry) => {
+                let data = self.read_entry_range(entry, 0, entry.decompressed_size)?;
+                PathAliases::from_bytes(&data)?
+            }
+            None => PathAliases::new(),
+        };
+
+        Ok(self.path_aliases.insert(aliases))
+    }
+
+    /// Returns the small files stored in the header of the archive, if any.
+    ///
+    /// Reads of these files are served from the header, without fetching a block.
+    pub fn inline_files(&self) -> Option<&InlineFiles> {
+        self.inline_files.as_ref()
+    }
+
+    /// Returns false if a file is definitely not in the archive, without unpacking the string pool.
+    ///
+    /// Archives packed with a path filter reject nearly all missing paths this way. For other
+    /// archives (or paths which pass the filter), this returns true, and only a lookup
+    /// (e.g. [`Self::find_entry`]) can tell for sure.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Relative path of the file within the archive, including the mount prefix.
+    pub fn may_contain(&self, path: &str) -> bool {
+        let Some(filter) = &self.path_filter else {
+            return true;
+        };
+
+        path.strip_prefix(self.mount_prefix.as_str())
+            .is_some_and(|x| filter.may_contain(x))
+    }
+
+    /// Returns all files within a directory, including its subdirectories, ordered by path.
+    ///
+    /// Archives packed with a directory index find the files with a binary search, without
+    /// unpacking the string pool. For other archives, every path is checked.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - Path of the directory, including the mount prefix, e.g. `textures/ui`.
+    ///   A trailing separator is ignored. An empty path returns every file.
+    pub fn entries_in_directory(
+        &self,
+        directory: &str,
+    ) -> Result<Vec<EntryInfo<'_>>, ArchiveReadError> {
+        let directory = directory.trim_end_matches('/');
+
+        // Directories containing the mount prefix contain the whole archive.
+        let contains_archive = directory.is_empty()
+            || self
+                .mount_prefix
+                .strip_prefix(directory)
+                .is_some_and(|x| x.starts_with('/'));
+        if contains_archive {
+            let count = self.toc.entries.len() as u32;
+            return Ok(self.entries_in_path_range(0..count));
+        }
+
+        let Some(relative) = directory.strip_prefix(self.mount_prefix.as_str()) else {
+            return Ok(Vec::new());
+        };
+
+        if let Some(index) = &self.directory_index {
+            match index.find(relative) {
+                DirectoryLookup::Range(range) => return Ok(self.entries_in_path_range(range)),
+                DirectoryLookup::Missing => return Ok(Vec::new()),
+                DirectoryLookup::Scattered => {}
+            }
+        }
+
+        let mut prefix = String::from(directory);
+        prefix.push('/');
+        let mut result: Vec<EntryInfo<'_>> = self
+            .iter_entries()?
+            .filter(|(path, _)| path.starts_with(prefix.as_str()))
+            .map(|(_, info)| info)
+            .collect();
+        result.sort_by_key(|x| x.entry.file_path_index);
+        Ok(result)
+    }
+
+    /// Returns the directory index stored in the header of the archive, if any.
+    pub fn directory_index(&self) -> Option<&DirectoryIndex> {
+        self.directory_index.as_ref()
+    }
+
+    /// Returns the number of times the archive was edited, e.g. by compacting a
+    /// [`ContentStore`]. Archives without a generation (such as ones written once by
+    /// the packer) return 0.
+    ///
+    /// Comparing the generation of an archive when it was opened against the one on disk
+    /// detects edits made by another process; see [`archive_generation`].
+    ///
+    /// [`ContentStore`]: crate::api::content_store::ContentStore
+    /// [`archive_generation`]: crate::api::archive_generation
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns the XXH3 seed the file hashes were computed with; 0 if unseeded.
+    ///
+    /// Seeded hashes can't be matched against databases of hashes of known files,
+    /// but can still be used to deduplicate and verify files; see [`Self::hash_data`].
+    pub fn hash_seed(&self) -> u64 {
+        self.hash_seed
+    }
+
+    /// Hashes data the same way as the files of this archive, i.e. with [`Self::hash_seed`],
+    /// so the result can be compared against [`FileEntry::hash`].
+    pub fn hash_data(&self, data: &[u8]) -> u64 {
+        XXH3sum::create_with_seed(data, self.hash_seed).0
+    }
+
+    /// Returns the hashes of each chunk of the chunked files stored in the header, if any.
+    pub fn chunk_hashes(&self) -> Option<&ChunkHashes> {
+        self.chunk_hashes.as_ref()
+    }
+
+    /// Reads a single chunk of a chunked file, and checks it against its stored hash,
+    /// e.g. to validate a partially downloaded file without hashing all of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The entry of the file.
+    /// * `chunk_index` - Index of the chunk within the file.
+    ///
+    /// # Returns
+    ///
+    /// Whether the chunk matches its hash, or [`None`] if no hash is stored for the chunk.
+    pub fn verify_chunk(
+        &self,
+        entry: &FileEntry,
+        chunk_index: u32,
+    ) -> Result<Option<bool>, ArchiveReadError> {
+        let Some(&expected) = self
+            .chunk_hashes
+            .as_ref()
+            .and_then(|x| x.get(entry.first_block_index))
+            .and_then(|x| x.get(chunk_index as usize))
+        else {
+            return Ok(None);
+        };
+
+        let chunk_size = self.entry_chunk_size(entry) as u64;
+        let offset = chunk_index as u64 * chunk_size;
+        let length = entry
+            .decompressed_size
+            .saturating_sub(offset)
+            .min(chunk_size);
+        let data = self.read_entry_range(entry, offset, length)?;
+        Ok(Some(self.hash_data(&data) == expected))
+    }
+
+    /// Returns the path filter stored in the header of the archive, if any.
+    pub fn path_filter(&self) -> Option<&PathFilter> {
+        self.path_filter.as_ref()
+    }
+
+    /// Returns the index of the entry with each path index.
+    fn entries_by_path(&self) -> &[u32] {
+        self.entries_by_path.get_or_init(|| {
+            let mut result = vec![u32::MAX; self.toc.entries.len()].into_boxed_slice();
+            for (index, entry) in self.toc.entries.iter().enumerate() {
+                if let Some(slot) = result.get_mut(entry.file_path_index as usize) {
+                    *slot = index as u32;
+                }
+            }
+            result
+        })
+    }
+
+    fn entries_in_path_range(&self, range: Range<u32>) -> Vec<EntryInfo<'_>> {
+        let entries_by_path = self.entries_by_path();
+        let range = range.start as usize..(range.end as usize).min(entries_by_path.len());
+        entries_by_path
+            .get(range)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|&index| {
+                let entry = self.toc.entries.get(index as usize)?;
+                Some(EntryInfo {
+                    index: index as usize,
+                    entry,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the path alias table, if loaded with [`Self::load_path_aliases`].
+    pub fn path_aliases(&self) -> Option<&PathAliases> {
+        self.path_aliases.as_ref()
+    }
+
+    fn mounted_path(&self, path: &str) -> String {
+        let mut result = self.mount_prefix.clone();
+        result.push_str(path);
+        result
+    }
+
+    fn find_entry_exact(&self, path: &str) -> Result<Option<&FileEntry>, ArchiveReadError> {
+        if !self.may_contain(path) {
+            return Ok(None);
+        }
+
+        let collation = self.path_collation().or(self.custom_collation);
+        let path_index = match self.paths()?.position(path, collation) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+
+        let index = self.entries_by_path().get(path_index as usize);
+        Ok(index.and_then(|&index| self.toc.entries.get(index as usize)))
+    }
+
+    /// Reads the entire contents of a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Relative path of the file within the archive.
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, ArchiveReadError> {
+        let entry = self
+            .find_entry(path)?
+            .ok_or(ArchiveReadError::FileNotFound)?;
+        self.read_entry_range(entry, 0, entry.decompressed_size)
+    }
+
+    /// Reads the entire contents of multiple files at once.
+    ///
+    /// Files stored in the same SOLID block share a single decompression of that block,
+    /// which makes this much faster than calling [`Self::read_file`] for each file when
+    /// loading many small files together (e.g. all assets of a game level).
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - Relative paths of the files within the archive.
+    ///
+    /// # Returns
+    ///
+    /// The contents (or error) of each file, in the same order as `paths`.
+    pub fn read_files(&self, paths: &[&str]) -> Vec<Result<Vec<u8>, ArchiveReadError>> {
+        let mut results = Vec::with_capacity(paths.len());
+
+        // Files in SOLID blocks, along with their index in `results`.
+        let mut solid = Vec::new();
+        for path in paths {
+            let entry = self
+                .find_entry(path)
+                .and_then(|entry| entry.ok_or(ArchiveReadError::FileNotFound))
+                .and_then(|entry| self.check_file_size(entry).map(|_| entry));
+
+            match entry {
+                Ok(entry)
+                    if !entry.is_chunked(self.entry_chunk_size(entry))
+                        && entry.decompressed_size > 0
+                        && !self.is_inlined(entry) =>
+                {
+                    solid.push((results.len(), entry));
+                    results.push(Ok(Vec::new()));
+                }
+                Ok(entry) => results.push(self.read_entry_range(entry, 0, entry.decompressed_size)),
+                Err(e) => results.push(Err(e)),
+            }
+        }
+
+        solid.sort_by_key(|(_, entry)| entry.first_block_index);
+        for group in solid.chunk_by(|a, b| a.1.first_block_index == b.1.first_block_index) {
+            let end = group
+                .iter()
+                .map(|(_, entry)| entry.decompressed_block_offset as u64 + entry.decompressed_size)
+                .max()
+                .unwrap_or(0);
+
+            match self.decompress_block(group[0].1.first_block_index, end) {
+                Ok(data) => {
+                    for (index, entry) in group {
+                        let start = entry.decompressed_block_offset as usize;
+                        let end = start + entry.decompressed_size as usize;
+                        results[*index] = Ok(Vec::from(&data[start..end]));
+                    }
+                }
+                // Read each file on its own, so each gets its own error.
+                Err(_) => {
+                    for (index, entry) in group {
+                        results[*index] = self.read_entry_range(entry, 0, entry.decompressed_size);
+                    }
+                }
+            }
+        }
+
+        results
+    }
+
+    /// Reads a byte range of a file.
+    ///
+    /// Only the blocks (chunks) which overlap the range are fetched, and each is only
+    /// decompressed up to the end of the requested range.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Relative path of the file within the archive.
+    /// * `offset` - Offset of the first byte to read, relative to the start of the file.
+    /// * `length` - Number of bytes to read.
+    ///
+    /// # Returns
+    ///
+    /// Exactly `length` bytes, or an error if the range exceeds the file size.
+    pub fn read_file_range(
+        &self,
+        path: &str,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, ArchiveReadError> {
+        let entry = self
+            .find_entry(path)?
+            .ok_or(ArchiveReadError::FileNotFound)?;
+        self.read_entry_range(entry, offset, length)
+    }
+
+    /// Reads a byte range of the file described by a given entry.
+    ///
+    /// See [`Self::read_file_range`] for details.
+    pub fn read_entry_range(
+        &self,
+        entry: &FileEntry,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, ArchiveReadError> {
+        let end = offset
+            .checked_add(length)
+            .filter(|end| *end <= entry.decompressed_size)
+            .ok_or(ArchiveReadError::RangeOutOfBounds {
+                offset,
+                length,
+                file_size: entry.decompressed_size,
+            })?;
+        self.check_file_size(entry)?;
+
+        let mut result = Vec::new();
+        result
+            .try_reserve_exact(length as usize)
+            .map_err(|_| ArchiveReadError::OutOfMemory(length))?;
+        if length == 0 {
+            return Ok(result);
+        }
+
+        if let Some(data) = self.inline_files.as_ref().and_then(|x| x.get(entry)) {
+            result.extend_from_slice(&data[offset as usize..end as usize]);
+            return Ok(result);
+        }
+
+        let chunk_size = self.entry_chunk_size(entry) as u64;
+        if !entry.is_chunked(chunk_size as u32) {
+            // The whole file lives in a single block, possibly alongside other files.
+            let block_start = entry.decompressed_block_offset as u64;
+            let data = self.decompress_block(entry.first_block_index, block_start + end)?;
+            result.extend_from_slice(&data[(block_start + offset) as usize..]);
+            return Ok(result);
+        }
+
+        // Chunked file, read only the chunks overlapping the range.
+        let first_chunk = offset / chunk_size;
+        let last_chunk = (end - 1) / chunk_size;
+        for chunk in first_chunk..=last_chunk {
+            let chunk_start = chunk * chunk_size;
+            let from = offset.max(chunk_start) - chunk_start;
+            let to = end.min(chunk_start + chunk_size) - chunk_start;
+            let block_index = entry.first_block_index.saturating_add(chunk as u32);
+            let data = self.decompress_block(block_index, to)?;
+            result.extend_from_slice(&data[from as usize..]);
+        }
+
+        Ok(result)
+    }
+
+    /// Reads the file described by a given entry from its start up to one of its semantic
+    /// boundaries, such as the end of a mip level in a DDS texture.
+    ///
+    /// Only the chunks before the boundary are fetched, and the chunk containing the
+    /// boundary is only decompressed up to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - Entry of the file to read.
+    /// * `hint` - Describes the boundaries within the file.
+    /// * `boundary` - Index of the boundary to read up to, within [`ChunkBoundaryHint::valid_boundaries`].
+    ///
+    /// # Returns
+    ///
+    /// The data before the boundary, or [`None`] if the hint has no such boundary.
+    pub fn read_entry_to_boundary<H: ChunkBoundaryHint + ?Sized>(
+        &self,
+        entry: &FileEntry,
+        hint: &H,
+        boundary: usize,
+    ) -> Result<Option<Vec<u8>>, ArchiveReadError> {
+        let Some(&end) = hint.valid_boundaries(entry.decompressed_size).get(boundary) else {
+            return Ok(None);
+        };
+
+        let chunk_size = self.entry_chunk_size(entry);
+        let read = ChunkedRead::to_offset(end, chunk_size)
+            .filter(|_| entry.is_chunked(chunk_size) && !self.is_inlined(entry));
+        let Some(read) = read else {
+            return self.read_entry_range(entry, 0, end).map(Some);
+        };
+        self.check_file_size(entry)?;
+
+        let mut result = Vec::new();
+        result
+            .try_reserve_exact(end as usize)
+            .map_err(|_| ArchiveReadError::OutOfMemory(end))?;
+        for chunk in 0..read.chunk_count {
+            let length = match chunk + 1 == read.chunk_count {
+                true => read.bytes_in_last_chunk,
+                false => chunk_size,
+            };
+            let block_index = entry.first_block_index.saturating_add(chunk);
+            result.extend_from_slice(&self.decompress_block(block_index, length as u64)?);
+        }
+
+        Ok(Some(result))
+    }
+
+    /// Returns the filter applied to a block's data before it was compressed, if any.
+    fn block_filter(&self, block_index: u32) -> Option<BlockFilter> {
+        self.block_filters.as_ref()?.get(block_index)
+    }
+
+    /// Decompresses the first `length` bytes of a block, reversing its filter if it has one.
+    fn decompress_block(&self, block_index: u32, length: u64) -> Result<Vec<u8>, ArchiveReadError> {
+        let idx = block_index as usize;
+        if idx >= self.toc.blocks.len() {
+            return Err(ArchiveReadError::BlockOutOfRange(block_index));
+        }
+
+        // Transposed data can only be restored as a whole; deltas only need what precedes them.
+        let filter = self.block_filter(block_index);
+        let requested = length;
+        let length = match filter {
+            Some(BlockFilter {
+                filter: DataFilter::ByteTranspose4 | DataFilter::ByteTranspose8,
+                size,
+            }) => match length <= size as u64 {
+                true => size as u64,
+                false => return Err(ArchiveReadError::BlockTooSmall(block_index)),
+            },
+            _ => length,
+        };
+
+        let compressed_size = self.toc.blocks[idx].compressed_size as u64;
+        if let Some(ratio) = self.limits.max_compression_ratio {
+            if length > compressed_size.saturating_mul(ratio as u64) {
+                return Err(ArchiveReadError::CompressionRatioExceeded {
+                    block_index,
+                    compressed_size,
+                    decompressed_size: length,
+                });
+            }
+        }
+
+        let compressed = self
+            .provider
+            .get_file_data(self.block_offsets[idx], compressed_size)?;
+
+        let mut decompressed = Vec::new();
+        decompressed
+            .try_reserve_exact(length as usize)
+            .map_err(|_| ArchiveReadError::OutOfMemory(length))?;
+        decompressed.resize(length as usize, 0);
+        if let Some(throttle) = &self.throttle {
+            throttle.acquire(length);
+        }
+
+        let num_decompressed = match self.block_dictionary(idx)? {
+            Some(BlockDictionary::ZStandard(dict)) => zstd::decompress_partial_with_dictionary(
+                dict,
+                compressed.data(),
+                &mut decompressed,
+            )?,
+            #[cfg(feature = "lz4")]
+            Some(BlockDictionary::Lz4(dict)) => {
+                compression::lz4::decompress_partial_with_dictionary(
+                    dict,
+                    compressed.data(),
+                    &mut decompressed,
+                )?
+            }
+            None => compression::decompress_partial(
+                self.toc.block_compressions[idx],
+                compressed.data(),
+                &mut decompressed,
+            )
+            .map_err(|error| match self.dictionaries_disabled {
+                true => ArchiveReadError::DictionariesDisabled(block_index),
+                false => error.into(),
+            })?,
+        };
+
+        if num_decompressed < decompressed.len() {
+            return Err(ArchiveReadError::BlockTooSmall(block_index));
+        }
+
+        match filter {
+            Some(filter) => {
+                let mut restored = vec![0u8; decompressed.len()];
+                // The destination is as large as the source, so this can't fail.
+                let _ = reverse_filter(filter.filter, &decompressed, &mut restored);
+                restored.truncate(requested as usize);
+                Ok(restored)
+            }
+            None => Ok(decompressed),
+        }
+    }
+
+    fn is_inlined(&self, entry: &FileEntry) -> bool {
+        self.inline_files
+            .as_ref()
+            .is_some_and(|x| x.get(entry).is_some())
+    }
+
+    fn check_file_size(&self, entry: &FileEntry) -> Result<(), ArchiveReadError> {
+        match self.limits.max_file_size {
+            Some(max_size) if entry.decompressed_size > max_size => {
+                Err(ArchiveReadError::FileTooLarge {
+                    size: entry.decompressed_size,
+                    max_size,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the dictionaries supplied to the reader, decompressing
+    /// the segment first if loaded with [`DictionaryLoading::Lazy`].
+    fn loaded_dictionaries(&self) -> Result<Option<&LoadedDictionaries>, ArchiveReadError> {
+        if let Some(dictionaries) = self.dictionaries.get() {
+            return Ok(Some(dictionaries));
+        }
+
+        let Some(segment) = &self.dictionary_segment else {
+            return Ok(None);
+        };
+        self.dictionaries
+            .get_or_try_init(|| {
+                // SAFETY: Trusted (or `hardened`), as promised by the caller of `with_dictionary_segment`.
+                let data = unsafe { deserialize_dictionary_data(segment)? };
+                Ok(LoadedDictionaries::new(data))
+            })
+            .map(Some)
+    }
+
+    /// Returns the dictionary a block was compressed with.
+    /// ZStandard dictionaries are digested on first use.
+    fn block_dictionary(
+        &self,
+        block_index: usize,
+    ) -> Result<Option<BlockDictionary<'_>>, ArchiveReadError> {
+        let compression = self.toc.block_compressions[block_index];
+        if !matches!(
+            compression,
+            CompressionPreference::ZStandard | CompressionPreference::Lz4
+        ) {
+            return Ok(None);
+        }
+
+        let Some(dictionaries) = self.loaded_dictionaries()? else {
+            return Ok(None);
+        };
+        let Some(dict_index) = dictionaries
+            .data
+            .get_dictionary_index_for_block(block_index)
+        else {
+            return Ok(None);
+        };
+        let dict_data = dictionaries
+            .data
+            .get_dictionary(dict_index)
+            .unwrap_or_default();
+
+        match compression {
+            #[cfg(feature = "lz4")]
+            CompressionPreference::Lz4 => Ok(Some(BlockDictionary::Lz4(dict_data))),
+            CompressionPreference::ZStandard => {
+                let dict = dictionaries.digested[dict_index as usize]
+                    .get_or_try_init(|| ZstdDecompressionDict::new(dict_data))?;
+                Ok(Some(BlockDictionary::ZStandard(dict)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Dictionaries supplied to a reader, see [`NxArchiveReader::with_dictionaries`].
+struct LoadedDictionaries {
+    data: DictionaryData,
+
+    /// Digested form of each dictionary, created when first used; index is the dictionary index.
+    digested: Box<[OnceCell<ZstdDecompressionDict>]>,
+}
+
+impl LoadedDictionaries {
+    fn new(data: DictionaryData) -> Self {
+        let digested = (0..data.num_dictionaries())
+            .map(|_| OnceCell::new())
+            .collect();
+        Self { data, digested }
+    }
+}
+
+/// Paths of a reader's entries, moved into a [`PathInterner`] by [`NxArchiveReader::intern_paths`].
+struct InternedPool<'a> {
+    interner: &'a PathInterner,
+
+    /// ID and shared copy of each path; index is the [`FileEntry::file_path_index`].
+    paths: Box<[(PathId, Arc<str>)]>,
+}
+
+/// Where the paths of a reader's entries are looked up.
+#[derive(Clone, Copy)]
+enum EntryPaths<'r> {
+    Pool(&'r StringPool),
+    Interned(&'r InternedPool<'r>),
+}
+
+impl<'r> EntryPaths<'r> {
+    /// Returns the path with a given [`FileEntry::file_path_index`].
+    fn get(self, path_index: u32) -> Option<&'r str> {
+        match self {
+            Self::Pool(pool) => pool.get(path_index as usize),
+            Self::Interned(interned) => interned
+                .paths
+                .get(path_index as usize)
+                .map(|(_, path)| &**path),
+        }
+    }
+
+    /// Returns the number of paths.
+    fn len(self) -> usize {
+        match self {
+            Self::Pool(pool) => pool.len(),
+            Self::Interned(interned) => interned.paths.len(),
+        }
+    }
+
+    /// Returns the [`FileEntry::file_path_index`] of a path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to find.
+    /// * `collation` - The collation the paths are sorted with, used to binary search them.
+    ///   If [`None`], every path is compared byte by byte.
+    fn position(self, path: &str, collation: Option<PathCollation>) -> Option<u32> {
+        let Some(collation) = collation else {
+            let index = match self {
+                Self::Pool(pool) => pool.iter().position(|x| x == path),
+                Self::Interned(interned) => {
+                    let id = interned.interner.find(path)?;
+                    interned.paths.iter().position(|(x, _)| *x == id)
+                }
+            };
+            return index.map(|x| x as u32);
+        };
+
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match collation.compare(self.get(middle as u32)?, path) {
+                Ordering::Less => low = middle + 1,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => return Some(middle as u32),
+            }
+        }
+        None
+    }
+}
+
+/// A dictionary a block was compressed with, in the form its algorithm needs.
+enum BlockDictionary<'a> {
+    ZStandard(&'a ZstdDecompressionDict),
+    /// LZ4 uses the raw dictionary data, there is nothing to digest.
+    #[cfg(feature = "lz4")]
+    Lz4(&'a [u8]),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        enums::CompressionPreference, filedata::FromSliceReferenceProvider,
+        raw_archive_writer::NxRawArchiveWriter,
+    };
+    use crate::headers::types::xxh3sum::XXH3sum;
+    use crate::unsize_box2;
+    use crate::utilities::tests::archive_for_testing::*;
+    use alloc::format;
+    use rstest::rstest;
+
+    const CHUNK_SIZE: u32 = 4096;
+
+    fn large_file() -> Vec<u8> {
+        (0..10_000_u32).map(|x| (x % 251) as u8).collect()
+    }
+
+    fn create_archive(compression: CompressionPreference) -> Vec<u8> {
+        let large = large_file();
+        create_test_archive(
+            CHUNK_SIZE,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new("b.txt", b"Nx archives!"),
+            ]],
+            &[TestFile::new("large.bin", &large)],
+            compression,
+        )
+    }
+
+    fn open(archive: &[u8]) -> NxArchiveReader<'_> {
+        let provider = Box::new(FromSliceReferenceProvider::new(archive));
+        NxArchiveReader::new(unsize_box2!(provider)).unwrap()
+    }
+
+    #[rstest]
+    #[case::copy(CompressionPreference::Copy)]
+    #[case::zstd(CompressionPreference::ZStandard)]
+    #[cfg_attr(feature = "lz4", case::lz4(CompressionPreference::Lz4))]
+    #[cfg_attr(miri, ignore)]
+    fn can_read_files(#[case] compression: CompressionPreference) {
+        let archive = create_archive(compression);
+        let reader = open(&archive);
+
+        assert_eq!(reader.entries().len(), 3);
+        assert!(reader.has_file_hashes());
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"Hello");
+        assert_eq!(
+            reader.read_file("b.txt").unwrap().as_slice(),
+            b"Nx archives!"
+        );
+        assert_eq!(
+            reader.read_file("large.bin").unwrap().as_slice(),
+            large_file().as_slice()
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_read_archive_at_offset() {
+        let archive = create_archive(CompressionPreference::ZStandard);
+        let mut container = vec![0xAAu8; 1234];
+        container.extend_from_slice(&archive);
+        container.extend_from_slice(&[0xBB; 100]);
+
+        let provider = Box::new(FromSliceReferenceProvider::new(&container));
+        let reader =
+            NxArchiveReader::open_at_offset(unsize_box2!(provider), 1234, archive.len() as u64)
+                .unwrap();
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"Hello");
+        assert_eq!(
+            reader.read_file("large.bin").unwrap().as_slice(),
+            large_file().as_slice()
+        );
+    }
+
+    #[rstest]
+    #[case::solid("b.txt", 3, 5)]
+    #[case::within_chunk("large.bin", 100, 200)]
+    #[case::across_chunks("large.bin", 4000, 5000)]
+    #[case::last_chunk("large.bin", 9000, 1000)]
+    #[case::empty("large.bin", 10_000, 0)]
+    #[cfg_attr(miri, ignore)]
+    fn can_read_file_range(#[case] path: &str, #[case] offset: u64, #[case] length: u64) {
+        let archive = create_archive(CompressionPreference::ZStandard);
+        let reader = open(&archive);
+
+        let full = reader.read_file(path).unwrap();
+        let range = reader.read_file_range(path, offset, length).unwrap();
+        assert_eq!(
+            range.as_slice(),
+            &full[offset as usize..(offset + length) as usize]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn finds_every_entry_by_path() {
+        let paths: Vec<String> = (0..100).map(|x| format!("dir/file{x:03}.bin")).collect();
+        let files: Vec<TestFile> = paths
+            .iter()
+            .rev()
+            .map(|path| TestFile::new(path, path.as_bytes()))
+            .collect();
+        let archive = create_test_archive(CHUNK_SIZE, &[&files], &[], CompressionPreference::Copy);
+        let reader = open(&archive);
+
+        for path in &paths {
+            let entry = reader.find_entry(path).unwrap().unwrap();
+            assert_eq!(reader.file_path(entry).unwrap(), Some(path.as_str()));
+        }
+        for missing in ["", "dir/file.bin", "dir/file0505.bin", "zzz"] {
+            assert!(reader.find_entry(missing).unwrap().is_none());
+        }
+    }
+
+    fn compare_ignoring_case(a: &str, b: &str) -> core::cmp::Ordering {
+        a.bytes()
+            .map(|x| x.to_ascii_lowercase())
+            .cmp(b.bytes().map(|x| x.to_ascii_lowercase()))
+    }
+
+    #[rstest]
+    #[case::natural(PathCollation::Natural)]
+    #[case::custom(PathCollation::Custom(compare_ignoring_case))]
+    #[cfg_attr(miri, ignore)]
+    fn finds_entries_with_path_collation(#[case] collation: PathCollation) {
+        let paths: Vec<String> = (0..30).map(|x| format!("Dir/file{x}.bin")).collect();
+        let files: Vec<TestFile> = paths
+            .iter()
+            .map(|path| TestFile::new(path, path.as_bytes()))
+            .collect();
+        let archive = create_test_archive(CHUNK_SIZE, &[&files], &[], CompressionPreference::Copy);
+        let mut writer = NxRawArchiveWriter::new(CHUNK_SIZE).with_path_collation(collation);
+        writer.append_archive(&open(&archive)).unwrap();
+        let written = writer.build().unwrap();
+        let reader = open(&written).with_path_collation(collation);
+
+        for path in &paths {
+            let entry = reader.find_entry(path).unwrap().unwrap();
+            assert_eq!(reader.file_path(entry).unwrap(), Some(path.as_str()));
+        }
+        assert!(reader.find_entry("Dir/file30.bin").unwrap().is_none());
+
+        // Paths the collation considers equal are the same path.
+        let found = reader.find_entry("dir/FILE7.bin").unwrap().is_some();
+        assert_eq!(found, matches!(collation, PathCollation::Custom(_)));
+    }
+
+    #[rstest]
+    #[case::solid("b.txt", &[4, 12], 1, Some(12))]
+    #[case::first_chunk("large.bin", &[100, 5000, 10_000], 0, Some(100))]
+    #[case::across_chunks("large.bin", &[100, 5000, 10_000], 1, Some(5000))]
+    #[case::whole_file("large.bin", &[100, 5000, 10_000], 2, Some(10_000))]
+    #[case::missing_boundary("large.bin", &[100, 20_000], 1, None)]
+    #[cfg_attr(miri, ignore)]
+    fn can_read_entry_to_boundary(
+        #[case] path: &str,
+        #[case] boundaries: &[u64],
+        #[case] boundary: usize,
+        #[case] end: Option<usize>,
+    ) {
+        let archive = create_archive(CompressionPreference::ZStandard);
+        let reader = open(&archive);
+
+        let full = reader.read_file(path).unwrap();
+        let entry = reader.find_entry(path).unwrap().unwrap();
+        let data = reader
+            .read_entry_to_boundary(entry, boundaries, boundary)
+            .unwrap();
+        assert_eq!(data.as_deref(), end.map(|end| &full[..end]));
+    }
+
+    #[rstest]
+    #[case::copy(CompressionPreference::Copy)]
+    #[case::zstd(CompressionPreference::ZStandard)]
+    #[cfg_attr(miri, ignore)]
+    fn can_read_multiple_files(#[case] compression: CompressionPreference) {
+        let archive = create_archive(compression);
+        let reader = open(&archive);
+
+        let results = reader.read_files(&["b.txt", "missing.txt", "large.bin", "a.txt"]);
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().as_slice(), b"Nx archives!");
+        assert!(matches!(results[1], Err(ArchiveReadError::FileNotFound)));
+        assert_eq!(
+            results[2].as_ref().unwrap().as_slice(),
+            large_file().as_slice()
+        );
+        assert_eq!(results[3].as_ref().unwrap().as_slice(), b"Hello");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_iterate_entries() {
+        let archive = create_archive(CompressionPreference::ZStandard);
+        let reader = open(&archive);
+
+        let mut listed: Vec<(&str, u64, u64)> = reader
+            .iter_entries()
+            .unwrap()
+            .map(|(path, info)| (path, info.size(), info.hash()))
+            .collect();
+        listed.sort();
+        assert_eq!(
+            listed.as_slice(),
+            &[
+                ("a.txt", 5, XXH3sum::create(b"Hello").0),
+                ("b.txt", 12, XXH3sum::create(b"Nx archives!").0),
+                ("large.bin", 10_000, XXH3sum::create(&large_file()).0),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn snapshots_outlive_reader() {
+        let archive = create_archive(CompressionPreference::ZStandard);
+        let snapshots = open(&archive).snapshot_entries().unwrap();
+
+        let large = std::thread::spawn(move || {
+            snapshots
+                .into_iter()
+                .find(|snapshot| &*snapshot.path == "large.bin")
+                .unwrap()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(large.size, 10_000);
+        assert_eq!(large.hash, XXH3sum::create(&large_file()).0);
+        assert!(large.blocks.is_multi_block());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn stat_reports_residency_without_reading() {
+        use crate::api::filedata::ReadThroughCacheProvider;
+
+        let archive = create_archive(CompressionPreference::ZStandard);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let remote = Box::new(FromSliceReferenceProvider::new(&archive));
+        let provider = Box::new(
+            ReadThroughCacheProvider::new_with_page_size(
+                unsize_box2!(remote),
+                archive.len() as u64,
+                temp_dir.path().join("archive.nx"),
+                BLOCK_ALIGNMENT as u32,
+            )
+            .unwrap(),
+        );
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+
+        let stat = reader.stat("large.bin").unwrap();
+        assert_eq!(stat.size, 10_000);
+        assert_eq!(stat.hash, Some(XXH3sum::create(&large_file()).0));
+        assert_eq!(stat.block_count, 3);
+        assert!(stat.compression.is_some());
+        assert!(!stat.resident_in_cache);
+
+        // Only the first chunk is fetched.
+        reader.read_file_range("large.bin", 0, 100).unwrap();
+        assert!(!reader.stat("large.bin").unwrap().resident_in_cache);
+        reader.read_file("large.bin").unwrap();
+        assert!(reader.stat("large.bin").unwrap().resident_in_cache);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn extracts_blocks_with_their_files() {
+        let archive = create_archive(CompressionPreference::ZStandard);
+        let reader = open(&archive);
+        let entry_index = |path: &str| {
+            let entry = reader.find_entry(path).unwrap().unwrap();
+            reader
+                .entries()
+                .iter()
+                .position(|x| core::ptr::eq(x, entry))
+                .unwrap()
+        };
+
+        // Chunked file first, then the SOLID block.
+        assert_eq!(reader.block_count(), 4);
+        let chunk = reader.extract_block(2).unwrap();
+        assert_eq!(chunk.files().len(), 1);
+        let file = chunk.files()[0];
+        assert_eq!(file.entry_index, entry_index("large.bin"));
+        assert_eq!(file.offset_in_file, 2 * CHUNK_SIZE as u64);
+        assert_eq!(
+            chunk.file_data(&file),
+            &large_file()[2 * CHUNK_SIZE as usize..]
+        );
+
+        let solid = reader.extract_block(3).unwrap();
+        let contents: Vec<(usize, &[u8])> = solid
+            .files()
+            .iter()
+            .map(|file| (file.entry_index, solid.file_data(file)))
+            .collect();
+        assert!(contents.contains(&(entry_index("a.txt"), b"Hello".as_slice())));
+        assert!(contents.contains(&(entry_index("b.txt"), b"Nx archives!".as_slice())));
+        assert_eq!(solid.data().len(), 17);
+
+        assert!(matches!(
+            reader.extract_block(4),
+            Err(ArchiveReadError::BlockOutOfRange(4))
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_visit_entries_in_batches() {
+        let archive = create_archive(CompressionPreference::ZStandard);
+        let provider = FromSliceReferenceProvider::new(&archive);
+
+        let mut batches = Vec::new();
+        let mut visited = Vec::new();
+        let file_count = NxArchiveReader::visit_entries(&provider, 2, |entries| {
+            batches.push(entries.len());
+            visited.extend_from_slice(entries);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        assert_eq!(file_count, 3);
+        assert_eq!(batches.as_slice(), &[2, 1]);
+        assert_eq!(visited.as_slice(), open(&archive).entries());
+
+        let mut visits = 0;
+        NxArchiveReader::visit_entries(&provider, 1, |_| {
+            visits += 1;
+            ControlFlow::Break(())
+        })
+        .unwrap();
+        assert_eq!(visits, 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn splits_compressed_size_between_files() {
+        let archive = create_archive(CompressionPreference::ZStandard);
+        let reader = open(&archive);
+        let blocks = &reader.table_of_contents().blocks;
+        let entry = |path: &str| *reader.find_entry(path).unwrap().unwrap();
+
+        // Chunked files own their blocks, so their footprint is exact.
+        let large = entry("large.bin");
+        let chunks = reader.block_mapping(&large).block_indices();
+        let expected: u64 = chunks
+            .map(|x| blocks[x as usize].compressed_size as u64)
+            .sum();
+        assert_eq!(reader.compressed_footprint(&large), expected);
+        assert_eq!(reader.compression_ratio(&large), expected as f64 / 10_000.0);
+
+        // Files sharing a block split its size by their share of the data.
+        let a = entry("a.txt");
+        let solid = blocks[a.first_block_index as usize].compressed_size as u64;
+        let a = reader.compressed_footprint(&a);
+        let b = reader.compressed_footprint(&entry("b.txt"));
+        assert!(a < b);
+        assert!(a + b <= solid && a + b >= solid - 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn enforces_read_limits() {
+        let archive = create_archive(CompressionPreference::ZStandard);
+        let reader = open(&archive).with_read_limits(ReadLimits::default().with_max_file_size(100));
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"Hello");
+        assert!(matches!(
+            reader.read_file("large.bin"),
+            Err(ArchiveReadError::FileTooLarge {
+                size: 10_000,
+                max_size: 100
+            })
+        ));
+
+        // The repeating data of 'large.bin' compresses far better than 1:1.
+        let reader =
+            open(&archive).with_read_limits(ReadLimits::default().with_max_compression_ratio(1));
+        assert!(matches!(
+            reader.read_file("large.bin"),
+            Err(ArchiveReadError::CompressionRatioExceeded { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn range_out_of_bounds_returns_error() {
+        let archive = create_archive(CompressionPreference::Copy);
+        let reader = open(&archive);
+
+        let result = reader.read_file_range("a.txt", 3, 3);
+        assert!(matches!(
+            result,
+            Err(ArchiveReadError::RangeOutOfBounds { file_size: 5, .. })
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn missing_file_returns_error() {
+        let archive = create_archive(CompressionPreference::Copy);
+        let reader = open(&archive);
+
+        assert!(matches!(
+            reader.read_file("missing.txt"),
+            Err(ArchiveReadError::FileNotFound)
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_read_files_by_alias() {
+        let mut aliases = PathAliases::new();
+        aliases.insert("old/a.txt", "a.txt");
+        let alias_bytes = aliases.to_bytes();
+        let archive = create_test_archive(
+            CHUNK_SIZE,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new(PathAliases::FILE_PATH, &alias_bytes),
+            ]],
+            &[],
+            CompressionPreference::Copy,
+        );
+        let mut reader = open(&archive);
+
+        assert!(reader.read_file("old/a.txt").is_err());
+        assert_eq!(reader.load_path_aliases().unwrap().len(), 1);
+        assert_eq!(reader.read_file("old/a.txt").unwrap().as_slice(), b"Hello");
+    }
+
+    #[rstest]
+    #[case::no_separator("mods/foo")]
+    #[case::trailing_separator("mods/foo/")]
+    #[cfg_attr(miri, ignore)]
+    fn can_mount_at_prefix(#[case] prefix: &str) {
+        let archive = create_archive(CompressionPreference::Copy);
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader =
+            NxArchiveReader::open_with_mount_prefix(unsize_box2!(provider), prefix).unwrap();
+
+        assert_eq!(reader.mount_prefix(), "mods/foo/");
+        let entry = reader.find_entry("mods/foo/a.txt").unwrap().unwrap();
+        assert_eq!(reader.file_path(entry).unwrap(), Some("mods/foo/a.txt"));
+        assert_eq!(
+            reader.read_file("mods/foo/a.txt").unwrap().as_slice(),
+            b"Hello"
+        );
+        assert!(reader.find_entry("a.txt").unwrap().is_none());
+    }
+
+    #[rstest]
+    #[case::eager_pool(false)]
+    #[case::lazy_pool(true)]
+    #[cfg_attr(miri, ignore)]
+    fn supports_concurrent_reads(#[case] lazy_pool: bool) {
+        let archive = create_archive(CompressionPreference::ZStandard);
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = if lazy_pool {
+            NxArchiveReader::new_with_lazy_pool(unsize_box2!(provider)).unwrap()
+        } else {
+            NxArchiveReader::new(unsize_box2!(provider)).unwrap()
+        };
+        let large = large_file();
+
+        std::thread::scope(|scope| {
+            for thread_index in 0..8_u64 {
+                let reader = &reader;
+                let large = &large;
+                scope.spawn(move || {
+                    for iteration in 0..50_u64 {
+                        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"Hello");
+                        assert_eq!(
+                            reader.read_file("b.txt").unwrap().as_slice(),
+                            b"Nx archives!"
+                        );
+
+                        let offset = (thread_index * 1000 + iteration * 37) % 9000;
+                        let range = reader.read_file_range("large.bin", offset, 1000).unwrap();
+                        assert_eq!(
+                            range.as_slice(),
+                            &large[offset as usize..offset as usize + 1000]
+                        );
+                    }
+                });
+            }
+        });
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn lazy_pool_is_unpacked_on_first_lookup() {
+        let archive = create_archive(CompressionPreference::Copy);
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new_with_lazy_pool(unsize_box2!(provider)).unwrap();
+
+        assert!(!reader.is_string_pool_loaded());
+        assert_eq!(reader.entries().len(), 3);
+
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"Hello");
+        assert!(reader.is_string_pool_loaded());
+        assert_eq!(reader.string_pool().unwrap().len(), 3);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_intern_paths_across_archives() {
+        let first = create_archive(CompressionPreference::Copy);
+        let second = create_test_archive(
+            CHUNK_SIZE,
+            &[&[
+                TestFile::new("b.txt", b"Layered"),
+                TestFile::new("c.txt", b"New"),
+            ]],
+            &[],
+            CompressionPreference::Copy,
+        );
+
+        let interner = PathInterner::new();
+        let provider = Box::new(FromSliceReferenceProvider::new(&second));
+        let mut lazy = NxArchiveReader::new_with_lazy_pool(unsize_box2!(provider)).unwrap();
+        let mut first = open(&first);
+        first.intern_paths(&interner).unwrap();
+        lazy.intern_paths(&interner).unwrap();
+
+        // 'b.txt' is shared, so only stored once, and neither reader keeps its own pool.
+        assert_eq!(interner.len(), 4);
+        assert!(first.string_pool().unwrap().is_empty());
+        assert!(lazy.string_pool().unwrap().is_empty());
+
+        let b = interner.find("b.txt").unwrap();
+        let first_b = first.find_entry("b.txt").unwrap().unwrap();
+        let lazy_b = lazy.find_entry("b.txt").unwrap().unwrap();
+        assert_eq!(first.path_id(first_b), Some(b));
+        assert_eq!(lazy.path_id(lazy_b), Some(b));
+
+        // Lookups resolve through the interner.
+        assert_eq!(lazy.read_file("b.txt").unwrap().as_slice(), b"Layered");
+        assert_eq!(
+            first.read_file("b.txt").unwrap().as_slice(),
+            b"Nx archives!"
+        );
+        assert!(lazy.find_entry("a.txt").unwrap().is_none());
+        for entry in lazy.entries() {
+            let path = interner.resolve(lazy.path_id(entry).unwrap()).unwrap();
+            assert_eq!(lazy.file_path(entry).unwrap(), Some(&*path));
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_find_by_hash_without_paths() {
+        let archive = create_test_archive_without_paths(
+            CHUNK_SIZE,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new("b.txt", b"Nx archives!"),
+                TestFile::new("c.txt", b"Hello"),
+            ]],
+            &[],
+            CompressionPreference::Copy,
+        );
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new_with_lazy_pool(unsize_box2!(provider)).unwrap();
+
+        let found = reader.find_by_hash(XXH3sum::create(b"Hello").0);
+        assert_eq!(found.len(), 2);
+        for entry_ref in &found {
+            assert_eq!(
+                reader
+                    .read_entry_range(entry_ref.entry, 0, 5)
+                    .unwrap()
+                    .as_slice(),
+                b"Hello"
+            );
+        }
+
+        assert!(reader.find_by_hash(0).is_empty());
+        assert!(reader.string_pool().unwrap().is_empty());
+        assert!(matches!(
+            reader.read_file("a.txt"),
+            Err(ArchiveReadError::FileNotFound)
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn caches_digested_dictionaries() {
+        use crate::api::raw_archive_writer::NxRawArchiveWriter;
+        use crate::headers::parser::{deserialize_dictionary_data, serialize_dictionary_data};
+        use crate::utilities::compression::{
+            dictionary::ZstdCompressionDict, max_alloc_for_compress_size,
+        };
+        use crate::utilities::tests::mock_block::create_mock_block;
+
+        // Raw content dictionary, shared by both files.
+        let dict_data = b"The quick brown fox jumps over the lazy dog. Nx archives!";
+        let dict = ZstdCompressionDict::new(dict_data, 3).unwrap();
+
+        let mut writer = NxRawArchiveWriter::new(CHUNK_SIZE);
+        let files: [(&str, &[u8]); 2] = [
+            ("a.txt", b"The quick brown fox jumps over the lazy dog."),
+            (
+                "b.txt",
+                b"Nx archives! The lazy dog jumps over the quick brown fox.",
+            ),
+        ];
+        for (path, data) in files {
+            let mut compressed = vec![0u8; max_alloc_for_compress_size(data.len())];
+            let mut used_copy = false;
+            let size = zstd::compress_with_dictionary(&dict, data, &mut compressed, &mut used_copy)
+                .unwrap();
+            assert!(!used_copy);
+            compressed.truncate(size);
+
+            let block_index = writer.add_block(RawBlock {
+                data: compressed,
+                compression: CompressionPreference::ZStandard,
+                filter: None,
+                dictionary: None,
+            });
+            writer.add_file(
+                path,
+                FileEntry {
+                    hash: XXH3sum::create(data).0,
+                    decompressed_size: data.len() as u64,
+                    decompressed_block_offset: 0,
+                    file_path_index: 0,
+                    first_block_index: block_index,
+                },
+            );
+        }
+        let archive = writer.build().unwrap();
+
+        let blocks = [create_mock_block(0), create_mock_block(0)];
+        let serialized = serialize_dictionary_data(&[dict_data], &blocks, false, true).unwrap();
+        let dictionaries = unsafe { deserialize_dictionary_data(&serialized).unwrap() };
+        let reader = open(&archive).with_dictionaries(dictionaries);
+        let digested = |reader: &NxArchiveReader| {
+            reader.dictionaries.get().unwrap().digested[0]
+                .get()
+                .map(|x| x.as_ptr())
+        };
+        assert!(digested(&reader).is_none());
+
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), files[0].1);
+        let first = digested(&reader).unwrap();
+        assert_eq!(reader.read_file("b.txt").unwrap().as_slice(), files[1].1);
+        assert_eq!(digested(&reader), Some(first));
+
+        // Same archive, with the segment loaded on demand or not at all.
+        let lazy = unsafe {
+            open(&archive)
+                .with_dictionary_segment(serialized.clone(), DictionaryLoading::Lazy)
+                .unwrap()
+        };
+        assert!(lazy.stat("a.txt").is_ok());
+        assert!(lazy.dictionaries().is_none());
+        assert_eq!(lazy.read_file("b.txt").unwrap().as_slice(), files[1].1);
+        assert!(lazy.dictionaries().is_some());
+
+        let off = unsafe {
+            open(&archive)
+                .with_dictionary_segment(serialized, DictionaryLoading::Off)
+                .unwrap()
+        };
+        assert!(matches!(
+            off.read_file("a.txt"),
+            Err(ArchiveReadError::DictionariesDisabled(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    #[cfg_attr(miri, ignore)]
+    fn applies_lz4_dictionaries() {
+        use crate::api::raw_archive_writer::NxRawArchiveWriter;
+        use crate::headers::parser::{deserialize_dictionary_data, serialize_dictionary_data};
+        use crate::utilities::compression::{lz4, max_alloc_for_compress_size};
+        use crate::utilities::tests::mock_block::create_mock_block;
+
+        let dict_data = b"The quick brown fox jumps over the lazy dog. Nx archives!";
+        let data = b"Nx archives! The lazy dog jumps over the quick brown fox.";
+        let mut compressed = vec![0u8; max_alloc_for_compress_size(data.len())];
+        let mut used_copy = false;
+        let size =
+            lz4::compress_with_dictionary(dict_data, 12, data, &mut compressed, &mut used_copy)
+                .unwrap();
+        assert!(!used_copy);
+        compressed.truncate(size);
+
+        let mut writer = NxRawArchiveWriter::new(CHUNK_SIZE);
+        let block_index = writer.add_block(RawBlock {
+            data: compressed,
+            compression: CompressionPreference::Lz4,
+            filter: None,
+            dictionary: None,
+        });
+        writer.add_file(
+            "a.txt",
+            FileEntry {
+                hash: XXH3sum::create(data).0,
+                decompressed_size: data.len() as u64,
+                decompressed_block_offset: 0,
+                file_path_index: 0,
+                first_block_index: block_index,
+            },
+        );
+        let archive = writer.build().unwrap();
+
+        let blocks = [create_mock_block(0)];
+        let serialized = serialize_dictionary_data(&[dict_data], &blocks, false, true).unwrap();
+        let dictionaries = unsafe { deserialize_dictionary_data(&serialized).unwrap() };
+        let reader = open(&archive).with_dictionaries(dictionaries);
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), data);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reports_anomalies() {
+        let mut archive = create_archive(CompressionPreference::ZStandard);
+        assert!(open(&archive).anomalies().is_empty());
+
+        // Last byte of the header page is padding in small archives.
+        let header_bytes = open(&archive).header().header_page_bytes();
+        archive[header_bytes as usize - 1] = 1;
+        assert_eq!(
+            open(&archive).anomalies(),
+            &[ArchiveAnomaly::NonZeroHeaderPadding {
+                offset: header_bytes - 1
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_magic_returns_error() {
+        let data = [0u8; 4096];
+        let provider = Box::new(FromSliceReferenceProvider::new(&data));
+        let result = NxArchiveReader::new(unsize_box2!(provider));
+        assert!(matches!(result, Err(ArchiveReadError::InvalidMagicHeader)));
+    }
+}
@@ -0,0 +1,394 @@
+use crate::api::{
+    archive_reader::{ArchiveReadError, NxArchiveReader},
+    filedata::FromSliceReferenceProvider,
+};
+use crate::headers::types::xxh3sum::XXH3sum;
+use crate::prelude::*;
+use crate::unsize_box2;
+use alloc::string::String;
+use core::fmt::Write as _;
+use core::hash::Hasher;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use thiserror_no_std::Error;
+use twox_hash::XxHash3_64;
+
+/// Extension appended to the path of an archive, for its sidecar. See [`ArchiveSidecar`].
+pub const SIDECAR_EXTENSION: &str = "nxsum";
+
+/// Version of the format written by [`ArchiveSidecar::to_text`].
+pub const SIDECAR_FORMAT_VERSION: u32 = 1;
+
+/// Amount of data hashed at once when verifying the whole archive.
+const VERIFY_BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Errors that can occur when reading or checking an [`ArchiveSidecar`].
+#[derive(Debug, Error)]
+pub enum SidecarError {
+    /// Failed to read the archive or the sidecar.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// The sidecar is not in the format written by [`ArchiveSidecar::to_text`].
+    #[error("Invalid sidecar, at line {0}")]
+    InvalidFormat(usize),
+
+    /// The archive has a different size than recorded in the sidecar.
+    #[error("Archive is {actual} bytes, but the sidecar expects {expected}")]
+    SizeMismatch { expected: u64, actual: u64 },
+
+    /// The header and table of contents of the archive were modified.
+    #[error("Archive header does not match the sidecar")]
+    HeaderMismatch,
+
+    /// The archive was modified, outside of its header.
+    #[error("Archive contents do not match the sidecar")]
+    ContentMismatch,
+}
+
+/// How much of an archive [`ArchiveSidecar::verify`] reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SidecarCheck {
+    /// Checks the size of the archive and hash of its header pages only.
+    /// This reads a few kilobytes, and catches replaced or truncated archives.
+    #[default]
+    Quick,
+
+    /// Also hashes the whole archive, catching edits to any block.
+    Full,
+}
+
+/// Signature of an archive, made by an external tool (e.g. minisign or GPG).
+///
+/// The library does not create or check signatures; it only carries them in the
+/// sidecar, so a mirror can hand them to the tool which checks them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SidecarSignature {
+    /// Name of the signature scheme, e.g. `ed25519`. Must not contain whitespace.
+    pub algorithm: String,
+
+    /// Identifies the key the archive was signed with. Must not contain whitespace.
+    pub key_id: String,
+
+    /// The signature itself.
+    pub signature: Vec<u8>,
+}
+
+/// A small file published next to an archive, letting distribution mirrors check the
+/// archive was not tampered with (or corrupted) without parsing it.
+///
+/// # Format
+///
+/// ```text
+/// # nx-sum 1
+/// size <size>
+/// header-size <size>
+/// header-xxh3 <hash>
+/// xxh3 <hash>
+/// signature <algorithm> <key id> <signature>
+/// ```
+///
+/// Sizes are decimal, hashes are 16 lowercase hex digits and the signature is lowercase hex.
+/// `header-xxh3` covers the first `header-size` bytes (the header and table of contents),
+/// `xxh3` covers the whole archive. The `signature` line is optional. Lines with unknown
+/// keys are ignored, so keys can be added without breaking older readers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveSidecar {
+    /// Size of the archive, in bytes.
+    pub archive_size: u64,
+
+    /// Size of the header pages (header and table of contents), in bytes.
+    pub header_size: u64,
+
+    /// XXH3 hash of the header pages.
+    pub header_hash: u64,
+
+    /// XXH3 hash of the whole archive.
+    pub archive_hash: u64,
+
+    /// Signature of the archive, if it was signed.
+    pub signature: Option<SidecarSignature>,
+}
+
+impl ArchiveSidecar {
+    /// Creates the sidecar of an archive, e.g. right after packing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - The complete archive.
+    pub fn from_archive(archive: &[u8]) -> Result<Self, ArchiveReadError> {
+        let provider = Box::new(FromSliceReferenceProvider::new(archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider))?;
+        let header_size = reader.header().header_page_bytes() as usize;
+        let header = archive
+            .get(..header_size)
+            .ok_or(ArchiveReadError::Truncated(header_size as u64))?;
+
+        Ok(Self {
+            archive_size: archive.len() as u64,
+            header_size: header_size as u64,
+            header_hash: XXH3sum::create(header).0,
+            archive_hash: XXH3sum::create(archive).0,
+            signature: None,
+        })
+    }
+
+    /// Attaches a signature of the archive, made by an external tool.
+    ///
+    /// # Arguments
+    ///
+    /// * `signature` - The signature.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_signature(mut self, signature: SidecarSignature) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    /// Returns the path of the sidecar for a given archive, i.e. the archive's path
+    /// with [`SIDECAR_EXTENSION`] appended (`mods.nx` => `mods.nx.nxsum`).
+    pub fn path_for(archive_path: &Path) -> PathBuf {
+        let mut path = archive_path.as_os_str().to_owned();
+        path.push(".");
+        path.push(SIDECAR_EXTENSION);
+        PathBuf::from(path)
+    }
+
+    /// Writes the sidecar next to an archive, see [`Self::path_for`].
+    ///
+    /// # Arguments
+    ///
+    /// * `archive_path` - Path of the archive the sidecar belongs to.
+    pub fn save(&self, archive_path: &Path) -> io::Result<()> {
+        fs::write(Self::path_for(archive_path), self.to_text())
+    }
+
+    /// Reads the sidecar of an archive, see [`Self::path_for`].
+    ///
+    /// # Arguments
+    ///
+    /// * `archive_path` - Path of the archive the sidecar belongs to.
+    pub fn load(archive_path: &Path) -> Result<Self, SidecarError> {
+        Self::parse(&fs::read_to_string(Self::path_for(archive_path))?)
+    }
+
+    /// Writes the sidecar in its text format, see [`ArchiveSidecar`].
+    pub fn to_text(&self) -> String {
+        let mut result = String::new();
+        let _ = writeln!(result, "# nx-sum {SIDECAR_FORMAT_VERSION}");
+        let _ = writeln!(result, "size {}", self.archive_size);
+        let _ = writeln!(result, "header-size {}", self.header_size);
+        let _ = writeln!(result, "header-xxh3 {:016x}", self.header_hash);
+        let _ = writeln!(result, "xxh3 {:016x}", self.archive_hash);
+        if let Some(signature) = &self.signature {
+            let _ = write!(
+                result,
+                "signature {} {} ",
+                signature.algorithm, signature.key_id
+            );
+            for byte in &signature.signature {
+                let _ = write!(result, "{byte:02x}");
+            }
+            result.push('\n');
+        }
+
+        result
+    }
+
+    /// Parses a sidecar written by [`Self::to_text`].
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - Contents of the sidecar.
+    pub fn parse(text: &str) -> Result<Self, SidecarError> {
+        let mut lines = text.lines().enumerate();
+        let version = lines
+            .next()
+            .and_then(|(_, line)| line.strip_prefix("# nx-sum "))
+            .and_then(|x| x.trim().parse::<u32>().ok());
+        if version != Some(SIDECAR_FORMAT_VERSION) {
+            return Err(SidecarError::InvalidFormat(1));
+        }
+
+        let (mut archive_size, mut header_size, mut header_hash, mut archive_hash) =
+            (None, None, None, None);
+        let mut signature = None;
+        for (index, line) in lines {
+            let invalid = SidecarError::InvalidFormat(index + 1);
+            let mut fields = line.split_whitespace();
+            match fields.next() {
+                Some("size") => archive_size = Some(parse_field(fields.next(), 10, invalid)?),
+                Some("header-size") => header_size = Some(parse_field(fields.next(), 10, invalid)?),
+                Some("header-xxh3") => header_hash = Some(parse_field(fields.next(), 16, invalid)?),
+                Some("xxh3") => archive_hash = Some(parse_field(fields.next(), 16, invalid)?),
+                Some("signature") => {
+                    let (Some(algorithm), Some(key_id)) = (fields.next(), fields.next()) else {
+                        return Err(invalid);
+                    };
+                    let hex = fields.next().unwrap_or_default();
+                    signature = Some(SidecarSignature {
+                        algorithm: String::from(algorithm),
+                        key_id: String::from(key_id),
+                        signature: parse_hex(hex).ok_or(invalid)?,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        // A missing key is reported at the end of the file.
+        let missing = || SidecarError::InvalidFormat(text.lines().count());
+        Ok(Self {
+            archive_size: archive_size.ok_or_else(missing)?,
+            header_size: header_size.ok_or_else(missing)?,
+            header_hash: header_hash.ok_or_else(missing)?,
+            archive_hash: archive_hash.ok_or_else(missing)?,
+            signature,
+        })
+    }
+
+    /// Checks an archive against this sidecar.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - The archive to check.
+    /// * `check` - How much of the archive to read.
+    ///
+    /// # Remarks
+    ///
+    /// The signature is not checked, see [`SidecarSignature`].
+    pub fn verify(
+        &self,
+        mut archive: impl Read + Seek,
+        check: SidecarCheck,
+    ) -> Result<(), SidecarError> {
+        let size = archive.seek(SeekFrom::End(0))?;
+        if size != self.archive_size {
+            return Err(SidecarError::SizeMismatch {
+                expected: self.archive_size,
+                actual: size,
+            });
+        }
+
+        archive.seek(SeekFrom::Start(0))?;
+        let mut header = vec![0u8; self.header_size.min(size) as usize];
+        archive.read_exact(&mut header)?;
+        if XXH3sum::create(&header).0 != self.header_hash {
+            return Err(SidecarError::HeaderMismatch);
+        }
+
+        if check == SidecarCheck::Full {
+            let mut hasher = XxHash3_64::new();
+            hasher.write(&header);
+            let mut buffer = vec![0u8; VERIFY_BUFFER_SIZE];
+            loop {
+                let num_read = archive.read(&mut buffer)?;
+                if num_read == 0 {
+                    break;
+                }
+                hasher.write(&buffer[..num_read]);
+            }
+
+            if hasher.finish() != self.archive_hash {
+                return Err(SidecarError::ContentMismatch);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks an archive on disk against this sidecar, see [`Self::verify`].
+    ///
+    /// # Arguments
+    ///
+    /// * `archive_path` - Path of the archive to check.
+    /// * `check` - How much of the archive to read.
+    pub fn verify_file(
+        &self,
+        archive_path: &Path,
+        check: SidecarCheck,
+    ) -> Result<(), SidecarError> {
+        self.verify(File::open(archive_path)?, check)
+    }
+}
+
+fn parse_field(
+    value: Option<&str>,
+    radix: u32,
+    invalid: SidecarError,
+) -> Result<u64, SidecarError> {
+    value
+        .and_then(|x| u64::from_str_radix(x, radix).ok())
+        .ok_or(invalid)
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|x| u8::from_str_radix(hex.get(x..x + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::enums::CompressionPreference;
+    use crate::utilities::tests::archive_for_testing::*;
+    use std::io::Cursor;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn detects_tampered_archives() {
+        let mut archive = create_test_archive(
+            4096,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new("b.txt", b"Nx archives!"),
+            ]],
+            &[],
+            CompressionPreference::Copy,
+        );
+        let sidecar = ArchiveSidecar::from_archive(&archive)
+            .unwrap()
+            .with_signature(SidecarSignature {
+                algorithm: String::from("ed25519"),
+                key_id: String::from("mirror-key"),
+                signature: vec![0xDE, 0xAD],
+            });
+
+        let parsed = ArchiveSidecar::parse(&sidecar.to_text()).unwrap();
+        assert_eq!(parsed, sidecar);
+        parsed
+            .verify(Cursor::new(&archive), SidecarCheck::Full)
+            .unwrap();
+
+        // Blocks are only covered by the full check.
+        archive[sidecar.header_size as usize] ^= 1;
+        parsed
+            .verify(Cursor::new(&archive), SidecarCheck::Quick)
+            .unwrap();
+        assert!(matches!(
+            parsed.verify(Cursor::new(&archive), SidecarCheck::Full),
+            Err(SidecarError::ContentMismatch)
+        ));
+
+        archive[0] ^= 1;
+        assert!(matches!(
+            parsed.verify(Cursor::new(&archive), SidecarCheck::Quick),
+            Err(SidecarError::HeaderMismatch)
+        ));
+
+        archive.pop();
+        assert!(matches!(
+            parsed.verify(Cursor::new(&archive), SidecarCheck::Quick),
+            Err(SidecarError::SizeMismatch { .. })
+        ));
+    }
+}
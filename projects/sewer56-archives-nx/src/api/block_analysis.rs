@@ -0,0 +1,322 @@
+use crate::api::{
+    archive_reader::{ArchiveReadError, NxArchiveReader},
+    enums::CompressionPreference,
+};
+use crate::prelude::*;
+use crate::utilities::compression;
+
+/// Settings for [`analyze_blocks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockAnalysisSettings {
+    /// SOLID blocks which decompress to fewer bytes than this are reported as tiny.
+    pub min_block_size: u32,
+
+    /// Uncompressed blocks are reported as compressible if a sample compresses to
+    /// at most this percentage of its size.
+    pub compressible_percent: u32,
+
+    /// Maximum number of bytes of each uncompressed block to test compress.
+    pub sample_size: u32,
+
+    /// ZStandard level used to test compress the samples.
+    pub sample_level: i32,
+}
+
+impl Default for BlockAnalysisSettings {
+    fn default() -> Self {
+        Self {
+            min_block_size: 64 * 1024,
+            compressible_percent: 80,
+            sample_size: 64 * 1024,
+            sample_level: 3,
+        }
+    }
+}
+
+/// Usage of a single compression algorithm within an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlgorithmUsage {
+    /// The compression algorithm.
+    pub algorithm: CompressionPreference,
+
+    /// Number of blocks compressed with the algorithm.
+    pub block_count: u32,
+
+    /// Total size of these blocks in the archive.
+    pub compressed_bytes: u64,
+
+    /// Total size of these blocks after decompression.
+    pub decompressed_bytes: u64,
+}
+
+/// A block which was not packed optimally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockFinding {
+    /// A compressed block is larger than its decompressed data.
+    Expanded {
+        block_index: u32,
+        compressed_size: u64,
+        decompressed_size: u64,
+    },
+
+    /// An uncompressed block whose data compresses well.
+    Compressible {
+        block_index: u32,
+        sample_size: u64,
+        compressed_sample_size: u64,
+    },
+
+    /// A SOLID block much smaller than the others, which could be merged with another.
+    Tiny {
+        block_index: u32,
+        decompressed_size: u64,
+    },
+}
+
+/// A suggested change when repacking an archive, derived from the [`BlockFinding`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepackRecommendation {
+    /// Store these blocks uncompressed, since compressing them made them larger.
+    StoreUncompressed(Vec<u32>),
+
+    /// Compress these blocks, which are currently stored uncompressed.
+    Compress(Vec<u32>),
+
+    /// Merge these blocks into fewer SOLID blocks, e.g. by grouping their files together
+    /// or raising [`PackingSettings::block_size`].
+    ///
+    /// [`PackingSettings::block_size`]: crate::api::packing::packing_settings::PackingSettings::block_size
+    Merge(Vec<u32>),
+}
+
+/// Result of [`analyze_blocks`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BlockAnalysis {
+    /// Blocks per compression algorithm, in order of first use.
+    pub histogram: Vec<AlgorithmUsage>,
+
+    /// Blocks which were not packed optimally, in order of block index.
+    pub findings: Vec<BlockFinding>,
+}
+
+impl BlockAnalysis {
+    /// Groups the findings into changes to make when repacking the archive.
+    pub fn recommendations(&self) -> Vec<RepackRecommendation> {
+        let mut expanded = Vec::new();
+        let mut compressible = Vec::new();
+        let mut tiny = Vec::new();
+        for finding in &self.findings {
+            match *finding {
+                BlockFinding::Expanded { block_index, .. } => expanded.push(block_index),
+                BlockFinding::Compressible { block_index, .. } => compressible.push(block_index),
+                BlockFinding::Tiny { block_index, .. } => tiny.push(block_index),
+            }
+        }
+
+        let mut result = Vec::new();
+        if !expanded.is_empty() {
+            result.push(RepackRecommendation::StoreUncompressed(expanded));
+        }
+        if !compressible.is_empty() {
+            result.push(RepackRecommendation::Compress(compressible));
+        }
+        if !tiny.is_empty() {
+            result.push(RepackRecommendation::Merge(tiny));
+        }
+        result
+    }
+}
+
+/// Scans the blocks of an archive, counting the blocks of each compression algorithm and
+/// reporting blocks which could be packed better.
+///
+/// # Arguments
+///
+/// * `archive` - The archive to analyze.
+/// * `settings` - Thresholds used to report blocks.
+///
+/// # Remarks
+///
+/// Only uncompressed blocks are read, to test compress a sample of their data;
+/// everything else is derived from the table of contents.
+pub fn analyze_blocks(
+    archive: &NxArchiveReader,
+    settings: &BlockAnalysisSettings,
+) -> Result<BlockAnalysis, ArchiveReadError> {
+    let toc = archive.table_of_contents();
+
+    // Decompressed size of each block, and whether it holds a chunk of a large file.
+    let mut decompressed_sizes = vec![0u64; toc.blocks.len()];
+    let mut is_chunk = vec![false; toc.blocks.len()];
+    for entry in toc.entries.iter() {
+        let mapping = archive.block_mapping(entry);
+        for block_index in mapping.block_indices() {
+            let Some(size) = decompressed_sizes.get_mut(block_index as usize) else {
+                break;
+            };
+            if let Some(range) = mapping.range_in_block(block_index) {
+                *size = (*size).max(range.end);
+            }
+            is_chunk[block_index as usize] |= mapping.is_multi_block();
+        }
+    }
+
+    let mut result = BlockAnalysis::default();
+    let mut tiny = Vec::new();
+    for (index, block) in toc.blocks.iter().enumerate() {
+        let block_index = index as u32;
+        let algorithm = toc.block_compressions[index];
+        let compressed_size = block.compressed_size as u64;
+        let decompressed_size = decompressed_sizes[index];
+        add_to_histogram(
+            &mut result.histogram,
+            algorithm,
+            compressed_size,
+            decompressed_size,
+        );
+
+        if algorithm == CompressionPreference::Copy {
+            if let Some(finding) = test_compress(archive, block_index, settings)? {
+                result.findings.push(finding);
+            }
+        } else if compressed_size > decompressed_size {
+            result.findings.push(BlockFinding::Expanded {
+                block_index,
+                compressed_size,
+                decompressed_size,
+            });
+        }
+
+        if !is_chunk[index] && decompressed_size < settings.min_block_size as u64 {
+            tiny.push(BlockFinding::Tiny {
+                block_index,
+                decompressed_size,
+            });
+        }
+    }
+
+    // A single tiny block has nothing to be merged with.
+    if tiny.len() > 1 {
+        result.findings.extend(tiny);
+        result.findings.sort_by_key(|finding| match *finding {
+            BlockFinding::Expanded { block_index, .. } => block_index,
+            BlockFinding::Compressible { block_index, .. } => block_index,
+            BlockFinding::Tiny { block_index, .. } => block_index,
+        });
+    }
+
+    Ok(result)
+}
+
+fn add_to_histogram(
+    histogram: &mut Vec<AlgorithmUsage>,
+    algorithm: CompressionPreference,
+    compressed_bytes: u64,
+    decompressed_bytes: u64,
+) {
+    match histogram.iter_mut().find(|x| x.algorithm == algorithm) {
+        Some(usage) => {
+            usage.block_count += 1;
+            usage.compressed_bytes += compressed_bytes;
+            usage.decompressed_bytes += decompressed_bytes;
+        }
+        None => histogram.push(AlgorithmUsage {
+            algorithm,
+            block_count: 1,
+            compressed_bytes,
+            decompressed_bytes,
+        }),
+    }
+}
+
+/// Compresses the start of an uncompressed block, to see if it is worth compressing.
+fn test_compress(
+    archive: &NxArchiveReader,
+    block_index: u32,
+    settings: &BlockAnalysisSettings,
+) -> Result<Option<BlockFinding>, ArchiveReadError> {
+    let block = archive.raw_block(block_index)?;
+    let sample = &block.data[..block.data.len().min(settings.sample_size as usize)];
+    if sample.is_empty() {
+        return Ok(None);
+    }
+
+    let mut compressed = vec![0u8; compression::max_alloc_for_compress_size(sample.len())];
+    let mut used_copy = false;
+    let Ok(size) = compression::compress(
+        CompressionPreference::ZStandard,
+        settings.sample_level,
+        sample,
+        &mut compressed,
+        &mut used_copy,
+    ) else {
+        return Ok(None);
+    };
+
+    let compressible = !used_copy
+        && size as u64 * 100 <= sample.len() as u64 * settings.compressible_percent as u64;
+    Ok(compressible.then_some(BlockFinding::Compressible {
+        block_index,
+        sample_size: sample.len() as u64,
+        compressed_sample_size: size as u64,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::filedata::FromSliceReferenceProvider;
+    use crate::unsize_box2;
+    use crate::utilities::tests::archive_for_testing::*;
+
+    fn open(archive: &[u8]) -> NxArchiveReader<'_> {
+        let provider = Box::new(FromSliceReferenceProvider::new(archive));
+        NxArchiveReader::new(unsize_box2!(provider)).unwrap()
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reports_compressible_and_tiny_blocks() {
+        let text: Vec<u8> = b"Nx packs files into SOLID blocks. "
+            .iter()
+            .copied()
+            .cycle()
+            .take(10_000)
+            .collect();
+        let archive = create_test_archive(
+            4096,
+            &[
+                &[TestFile::new("a.txt", b"Hello")],
+                &[TestFile::new("b.txt", &text)],
+            ],
+            &[],
+            CompressionPreference::Copy,
+        );
+
+        let analysis = analyze_blocks(&open(&archive), &BlockAnalysisSettings::default()).unwrap();
+        assert_eq!(
+            analysis.histogram,
+            [AlgorithmUsage {
+                algorithm: CompressionPreference::Copy,
+                block_count: 2,
+                compressed_bytes: 10_005,
+                decompressed_bytes: 10_005,
+            }]
+        );
+        assert!(matches!(
+            analysis.findings.as_slice(),
+            [
+                BlockFinding::Tiny { block_index: 0, .. },
+                BlockFinding::Compressible { block_index: 1, .. },
+                BlockFinding::Tiny { block_index: 1, .. },
+            ]
+        ));
+        assert_eq!(
+            analysis.recommendations(),
+            [
+                RepackRecommendation::Compress(vec![1]),
+                RepackRecommendation::Merge(vec![0, 1]),
+            ]
+        );
+    }
+}
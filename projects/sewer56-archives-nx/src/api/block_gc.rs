@@ -0,0 +1,213 @@
+use crate::api::{
+    archive_reader::NxArchiveReader,
+    raw_archive_writer::{NxRawArchiveWriter, RawArchiveWriteError},
+};
+use crate::headers::{format_limits, parser::DEFAULT_PATH_FILTER_BITS};
+use crate::prelude::*;
+
+/// Alignment of each block within the archive, as per the specification.
+const BLOCK_ALIGNMENT: u64 = format_limits::BLOCK_ALIGNMENT as u64;
+
+/// A block which no file refers to, e.g. because the files stored in it were replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnreferencedBlock {
+    /// Index of the block.
+    pub block_index: u32,
+
+    /// Offset of the block from the start of the archive.
+    pub offset: u64,
+
+    /// Space taken by the block, including the padding after it.
+    pub size: u64,
+}
+
+/// Space reclaimed by [`collect_garbage`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GcStats {
+    /// Number of blocks removed.
+    pub removed_blocks: u32,
+
+    /// Space taken by the removed blocks, including their padding.
+    pub reclaimed_bytes: u64,
+}
+
+/// Finds the blocks of an archive which no file refers to.
+///
+/// Archives written by the packer never contain such blocks, but archives assembled
+/// from existing blocks with [`NxRawArchiveWriter`] may, after files are replaced.
+///
+/// # Arguments
+///
+/// * `archive` - The archive to check.
+///
+/// # Returns
+///
+/// The unreferenced blocks, in the order they are stored.
+pub fn find_unreferenced_blocks(archive: &NxArchiveReader) -> Vec<UnreferencedBlock> {
+    let referenced = referenced_blocks(archive);
+    let blocks = &archive.table_of_contents().blocks;
+    referenced
+        .iter()
+        .enumerate()
+        .filter(|(_, referenced)| !**referenced)
+        .filter_map(|(index, _)| {
+            let block_index = index as u32;
+            Some(UnreferencedBlock {
+                block_index,
+                offset: archive.block_offset(block_index)?,
+                size: (blocks[index].compressed_size as u64).next_multiple_of(BLOCK_ALIGNMENT),
+            })
+        })
+        .collect()
+}
+
+/// Writes a copy of an archive without the blocks which no file refers to.
+///
+/// Blocks are copied without being decompressed; files keep their paths, contents,
+/// and inlined copies. The path collation, directory index, chunk hashes and chunk sizes
+/// are kept, and a path filter, if present, is rebuilt with [`DEFAULT_PATH_FILTER_BITS`].
+///
+/// # Arguments
+///
+/// * `archive` - The archive to compact.
+///
+/// # Returns
+///
+/// The compacted archive, and the space reclaimed. If nothing can be reclaimed,
+/// the archive is still rewritten.
+pub fn collect_garbage(
+    archive: &NxArchiveReader,
+) -> Result<(Vec<u8>, GcStats), RawArchiveWriteError> {
+    let referenced = referenced_blocks(archive);
+    let mut writer =
+        NxRawArchiveWriter::new(archive.chunk_size()).with_hash_seed(archive.hash_seed());
+    if let Some(collation) = archive.path_collation() {
+        writer = writer.with_path_collation(collation);
+    }
+    if archive.directory_index().is_some() {
+        writer = writer.with_directory_index();
+    }
+    if archive.path_filter().is_some() {
+        writer = writer.with_path_filter(DEFAULT_PATH_FILTER_BITS);
+    }
+
+    let mut stats = GcStats::default();
+    let mut new_block_index = vec![u32::MAX; referenced.len()];
+    let blocks = &archive.table_of_contents().blocks;
+    for (index, referenced) in referenced.iter().enumerate() {
+        if *referenced {
+            new_block_index[index] = writer.add_block(archive.raw_block(index as u32)?);
+        } else {
+            stats.removed_blocks += 1;
+            stats.reclaimed_bytes +=
+                (blocks[index].compressed_size as u64).next_multiple_of(BLOCK_ALIGNMENT);
+        }
+    }
+
+    if let Some(chunk_hashes) = archive.chunk_hashes() {
+        for (block_index, hashes) in chunk_hashes.iter() {
+            if let Some(&new_index) = new_block_index.get(block_index as usize) {
+                if new_index != u32::MAX {
+                    writer.add_chunk_hashes(new_index, Vec::from(hashes));
+                }
+            }
+        }
+    }
+
+    if let Some(chunk_sizes) = archive.chunk_sizes() {
+        for (block_index, chunk_size) in chunk_sizes.iter() {
+            if let Some(&new_index) = new_block_index.get(block_index as usize) {
+                if new_index != u32::MAX {
+                    writer.add_chunk_size(new_index, chunk_size);
+                }
+            }
+        }
+    }
+
+    for entry in archive.entries() {
+        let path = archive
+            .file_path(entry)?
+            .ok_or(RawArchiveWriteError::MissingFilePath)?;
+
+        let inline_data = archive.inline_files().and_then(|x| x.get(entry));
+        let mut new_entry = *entry;
+        // Blocks of a file are contiguous and all referenced, so only the first is remapped.
+        if let Some(first_block) = new_block_index.get(entry.first_block_index as usize) {
+            new_entry.first_block_index = *first_block;
+        }
+        match inline_data {
+            Some(data) => writer.add_inline_file(path, new_entry, data),
+            None => writer.add_file(path, new_entry),
+        }
+    }
+
+    Ok((writer.build()?, stats))
+}
+
+/// Returns whether each block of the archive holds the data of at least one file.
+fn referenced_blocks(archive: &NxArchiveReader) -> Vec<bool> {
+    let mut referenced = vec![false; archive.block_count() as usize];
+    for entry in archive.entries() {
+        for block_index in archive.block_mapping(entry).block_indices() {
+            if let Some(x) = referenced.get_mut(block_index as usize) {
+                *x = true;
+            }
+        }
+    }
+
+    referenced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{enums::CompressionPreference, filedata::FromSliceReferenceProvider};
+    use crate::unsize_box2;
+    use crate::utilities::tests::archive_for_testing::*;
+
+    fn open(archive: &[u8]) -> NxArchiveReader<'_> {
+        let provider = Box::new(FromSliceReferenceProvider::new(archive));
+        NxArchiveReader::new(unsize_box2!(provider)).unwrap()
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn removes_unreferenced_blocks() {
+        let source = create_test_archive(
+            4096,
+            &[
+                &[TestFile::new("old.txt", b"Replaced contents")],
+                &[TestFile::new("kept.txt", b"Kept contents")],
+            ],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+        let source = open(&source);
+
+        // Keep only the second block and its file, leaving the first block unreferenced.
+        let mut writer = NxRawArchiveWriter::new(source.chunk_size());
+        writer.add_block(source.raw_block(0).unwrap());
+        writer.add_block(source.raw_block(1).unwrap());
+        let kept = *source.find_entry("kept.txt").unwrap().unwrap();
+        writer.add_file("kept.txt", kept);
+        let archive = writer.build().unwrap();
+        let archive = open(&archive);
+
+        let unreferenced = find_unreferenced_blocks(&archive);
+        assert_eq!(unreferenced.len(), 1);
+        assert_eq!(unreferenced[0].block_index, 0);
+        assert_eq!(unreferenced[0].size, BLOCK_ALIGNMENT);
+
+        let (compacted, stats) = collect_garbage(&archive).unwrap();
+        assert_eq!(stats.removed_blocks, 1);
+        assert_eq!(stats.reclaimed_bytes, BLOCK_ALIGNMENT);
+
+        let compacted = open(&compacted);
+        assert_eq!(compacted.block_count(), 1);
+        assert!(find_unreferenced_blocks(&compacted).is_empty());
+        assert_eq!(
+            compacted.read_file("kept.txt").unwrap().as_slice(),
+            b"Kept contents"
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use crate::api::{
+    archive_reader::{ArchiveReadError, NxArchiveReader},
+    filedata::FromSliceReferenceProvider,
+};
+use crate::headers::format_limits;
+use crate::prelude::*;
+use crate::unsize_box2;
+use core::ops::Range;
+use thiserror_no_std::Error;
+
+/// Alignment of each block within the archive, as per the specification.
+const BLOCK_ALIGNMENT: u64 = format_limits::BLOCK_ALIGNMENT as u64;
+
+/// A single compressed block of an archive, as produced by [`NxArchiveReader::stream_blocks`].
+///
+/// In serialized form (see [`Self::write_to`]), a frame is laid out as:
+///
+/// - `u32` block index (little endian)
+/// - `u32` payload length (little endian)
+/// - payload (the compressed block)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockFrame {
+    /// Index of the block in the archive.
+    pub block_index: u32,
+
+    /// The compressed data of the block.
+    pub data: Vec<u8>,
+}
+
+impl BlockFrame {
+    /// Size of the serialized frame header, before the payload.
+    pub const HEADER_SIZE: usize = 8;
+
+    /// Appends the serialized frame to a buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - The buffer to write the frame to.
+    pub fn write_to(&self, output: &mut Vec<u8>) {
+        output.reserve(Self::HEADER_SIZE + self.data.len());
+        output.extend_from_slice(&self.block_index.to_le_bytes());
+        output.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        output.extend_from_slice(&self.data);
+    }
+
+    /// Reads a serialized frame from the start of a buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The buffer to read the frame from.
+    ///
+    /// # Returns
+    ///
+    /// The frame and the number of bytes it occupied in `input`,
+    /// or [`None`] if `input` does not yet contain a whole frame.
+    pub fn read_from(input: &[u8]) -> Option<(Self, usize)> {
+        if input.len() < Self::HEADER_SIZE {
+            return None;
+        }
+
+        let block_index = u32::from_le_bytes(input[0..4].try_into().unwrap());
+        let length = u32::from_le_bytes(input[4..8].try_into().unwrap()) as usize;
+        let frame_size = Self::HEADER_SIZE.checked_add(length)?;
+        let payload = input.get(Self::HEADER_SIZE..frame_size)?;
+
+        let mut data = Vec::with_capacity(length);
+        data.extend_from_slice(payload);
+        Some((Self { block_index, data }, frame_size))
+    }
+}
+
+/// Errors that can occur when reassembling an archive from [`BlockFrame`]s.
+#[derive(Debug, Error)]
+pub enum AssembleError {
+    /// The header page could not be parsed.
+    #[error("Failed to parse the header page: {0:?}")]
+    InvalidHeader(#[from] ArchiveReadError),
+
+    /// The frame refers to a block which does not exist in the archive.
+    #[error("Block index {0} is out of range")]
+    BlockOutOfRange(u32),
+
+    /// The size of the frame's payload does not match the table of contents.
+    #[error("Block {block_index} has size {actual}, expected {expected}")]
+    SizeMismatch {
+        block_index: u32,
+        expected: u32,
+        actual: u32,
+    },
+
+    /// The archive described by the header page is too large to allocate.
+    #[error("Failed to allocate {0} bytes")]
+    OutOfMemory(u64),
+
+    /// Not all blocks have been received yet.
+    #[error("{0} blocks are still missing")]
+    Incomplete(u32),
+}
+
+/// Puts an archive back together from its header page and [`BlockFrame`]s,
+/// which may arrive in any order.
+///
+/// If a transfer is interrupted, [`Self::missing_blocks`] gives the ranges of blocks
+/// that still need to be requested with [`NxArchiveReader::stream_blocks`].
+pub struct NxArchiveAssembler {
+    /// The archive being assembled; sized to fit all blocks up front.
+    archive: Vec<u8>,
+
+    /// Offset and compressed size of each block.
+    blocks: Box<[(u64, u32)]>,
+
+    /// Whether each block has been received.
+    received: Box<[bool]>,
+
+    /// Number of blocks not yet received.
+    missing_count: u32,
+}
+
+impl NxArchiveAssembler {
+    /// Creates an assembler for the archive with the given header page.
+    ///
+    /// # Arguments
+    ///
+    /// * `header_page` - Header and table of contents, from [`NxArchiveReader::header_page`].
+    pub fn new(header_page: &[u8]) -> Result<Self, AssembleError> {
+        let provider = Box::new(FromSliceReferenceProvider::new(header_page));
+        let reader = NxArchiveReader::new_with_lazy_pool(unsize_box2!(provider))?;
+
+        let mut archive_size = header_page.len() as u64;
+        let mut blocks = Vec::with_capacity(reader.block_count() as usize);
+        for block_index in 0..reader.block_count() {
+            let offset = reader.block_offset(block_index).unwrap();
+            let size = reader.table_of_contents().blocks[block_index as usize].compressed_size;
+            archive_size = offset
+                .saturating_add(size as u64)
+                .checked_next_multiple_of(BLOCK_ALIGNMENT)
+                .unwrap_or(u64::MAX)
+                .max(archive_size);
+            blocks.push((offset, size));
+        }
+
+        // The header page is untrusted, so the archive may be implausibly large.
+        let mut archive = Vec::new();
+        usize::try_from(archive_size)
+            .ok()
+            .and_then(|size| archive.try_reserve_exact(size).ok())
+            .ok_or(AssembleError::OutOfMemory(archive_size))?;
+        archive.resize(archive_size as usize, 0);
+        archive[..header_page.len()].copy_from_slice(header_page);
+        Ok(Self {
+            archive,
+            received: vec![false; blocks.len()].into_boxed_slice(),
+            missing_count: blocks.len() as u32,
+            blocks: blocks.into_boxed_slice(),
+        })
+    }
+
+    /// Places a received block into the archive.
+    /// Receiving the same block more than once is allowed.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The received block.
+    pub fn push(&mut self, frame: &BlockFrame) -> Result<(), AssembleError> {
+        let idx = frame.block_index as usize;
+        let (offset, size) = *self
+            .blocks
+            .get(idx)
+            .ok_or(AssembleError::BlockOutOfRange(frame.block_index))?;
+
+        if frame.data.len() != size as usize {
+            return Err(AssembleError::SizeMismatch {
+                block_index: frame.block_index,
+                expected: size,
+                actual: frame.data.len() as u32,
+            });
+        }
+
+        let offset = offset as usize;
+        self.archive[offset..offset + frame.data.len()].copy_from_slice(&frame.data);
+        if !self.received[idx] {
+            self.received[idx] = true;
+            self.missing_count -= 1;
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if every block has been received.
+    pub fn is_complete(&self) -> bool {
+        self.missing_count == 0
+    }
+
+    /// Returns the ranges of blocks which have not yet been received.
+    pub fn missing_blocks(&self) -> Vec<Range<u32>> {
+        let mut ranges: Vec<Range<u32>> = Vec::new();
+        for (block_index, received) in self.received.iter().enumerate() {
+            let block_index = block_index as u32;
+            if *received {
+                continue;
+            }
+
+            match ranges.last_mut() {
+                Some(last) if last.end == block_index => last.end += 1,
+                _ => ranges.push(block_index..block_index + 1),
+            }
+        }
+
+        ranges
+    }
+
+    /// Returns the assembled archive.
+    pub fn finish(self) -> Result<Vec<u8>, AssembleError> {
+        if !self.is_complete() {
+            return Err(AssembleError::Incomplete(self.missing_count));
+        }
+
+        Ok(self.archive)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::enums::CompressionPreference;
+    use crate::utilities::tests::archive_for_testing::*;
+
+    fn create_archive(large: &[u8]) -> Vec<u8> {
+        create_test_archive(
+            4096,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new("b.txt", b"Nx archives!"),
+            ]],
+            &[TestFile::new("large.bin", large)],
+            CompressionPreference::ZStandard,
+        )
+    }
+
+    fn open(archive: &[u8]) -> NxArchiveReader<'_> {
+        let provider = Box::new(FromSliceReferenceProvider::new(archive));
+        NxArchiveReader::new(unsize_box2!(provider)).unwrap()
+    }
+
+    #[test]
+    fn can_round_trip_frame() {
+        let frame = BlockFrame {
+            block_index: 7,
+            data: vec![1, 2, 3],
+        };
+        let mut bytes = Vec::new();
+        frame.write_to(&mut bytes);
+
+        assert_eq!(BlockFrame::read_from(&bytes), Some((frame, bytes.len())));
+        assert_eq!(BlockFrame::read_from(&bytes[..bytes.len() - 1]), None);
+    }
+
+    #[test]
+    fn frame_with_implausible_length_is_incomplete() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&7_u32.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2, 3]);
+
+        assert_eq!(BlockFrame::read_from(&bytes), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_resume_interrupted_transfer() {
+        let large: Vec<u8> = (0..10_000_u32).map(|x| (x % 251) as u8).collect();
+        let archive = create_archive(&large);
+        let reader = open(&archive);
+        let block_count = reader.block_count();
+
+        let mut assembler = NxArchiveAssembler::new(&reader.header_page().unwrap()).unwrap();
+        // Interrupted after the first block
+        for frame in reader.stream_blocks(0..1) {
+            let mut bytes = Vec::new();
+            frame.unwrap().write_to(&mut bytes);
+            assembler
+                .push(&BlockFrame::read_from(&bytes).unwrap().0)
+                .unwrap();
+        }
+
+        assert!(!assembler.is_complete());
+        assert_eq!(assembler.missing_blocks(), vec![1..block_count]);
+
+        for range in assembler.missing_blocks() {
+            for frame in reader.stream_blocks(range) {
+                assembler.push(&frame.unwrap()).unwrap();
+            }
+        }
+
+        let assembled = assembler.finish().unwrap();
+        let reader = open(&assembled);
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"Hello");
+        assert_eq!(reader.read_file("large.bin").unwrap(), large);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn incomplete_archive_returns_error() {
+        let archive = create_archive(b"small");
+        let reader = open(&archive);
+
+        let assembler = NxArchiveAssembler::new(&reader.header_page().unwrap()).unwrap();
+        assert!(matches!(
+            assembler.finish(),
+            Err(AssembleError::Incomplete(_))
+        ));
+    }
+}
@@ -0,0 +1,275 @@
+use crate::api::{
+    archive_reader::{ArchiveReadError, NxArchiveReader},
+    packing::packer_file::PackerFile,
+    traits::*,
+};
+use crate::headers::types::xxh3sum::XXH3sum;
+use crate::prelude::*;
+use alloc::string::String;
+use core::fmt::Write;
+
+/// Version of the text and JSON formats written by [`ChecksumManifest`].
+pub const MANIFEST_FORMAT_VERSION: u32 = 1;
+
+/// Checksums of a single file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumEntry {
+    /// Path of the file within the archive.
+    pub path: String,
+
+    /// Size of the file, in bytes.
+    pub size: u64,
+
+    /// XXH3 hash of the file's contents.
+    pub xxh3: u64,
+
+    /// SHA-256 hash of the file's contents.
+    /// Only computed when the `sha256` feature is enabled.
+    pub sha256: Option<[u8; 32]>,
+}
+
+/// A list of file checksums in a stable, documented format, so that the contents of an
+/// archive can be validated by tools which do not support Nx (e.g. file hosting platforms).
+///
+/// Entries are sorted by path, so equal archives produce identical manifests.
+///
+/// # Text Format
+///
+/// ```text
+/// # nx-checksums 1
+/// <xxh3> <sha256> <size> <path>
+/// ```
+///
+/// One line per file, where `xxh3` is 16 lowercase hex digits, `sha256` is 64 lowercase
+/// hex digits (or `-` if not computed) and `size` is decimal. The path is last, and may
+/// contain spaces.
+///
+/// # JSON Format
+///
+/// ```json
+/// {"version":1,"files":[{"path":"a.txt","size":5,"xxh3":"<hex>","sha256":"<hex>"}]}
+/// ```
+///
+/// Where `sha256` is `null` if not computed.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ChecksumManifest {
+    /// Checksums of each file, sorted by path.
+    pub entries: Vec<ChecksumEntry>,
+}
+
+impl ChecksumManifest {
+    /// Creates a manifest for files which are about to be packed.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The files to be packed.
+    pub fn from_files(files: &[PackerFile<'_>]) -> Result<Self, FileProviderError> {
+        let mut entries = Vec::with_capacity(files.len());
+        for file in files {
+            let data = file
+                .input_data_provider()
+                .get_file_data(0, file.file_size())?;
+            entries.push(ChecksumEntry::new(file.relative_path(), data.data(), None));
+        }
+
+        Ok(Self::from_entries(entries))
+    }
+
+    /// Creates a manifest for an existing archive.
+    ///
+    /// Stored hashes are used when the archive has them; files are only decompressed when
+    /// a hash has to be computed.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - The archive to create the manifest for.
+    ///
+    /// # Remarks
+    ///
+    /// Files without a path are skipped.
+    pub fn from_archive(archive: &NxArchiveReader<'_>) -> Result<Self, ArchiveReadError> {
+        let mut entries = Vec::with_capacity(archive.entries().len());
+        // Seeded hashes differ from plain XXH3, so they are computed again.
+        let use_stored_hashes = archive.has_file_hashes() && archive.hash_seed() == 0;
+        let needs_data = !use_stored_hashes || cfg!(feature = "sha256");
+        for entry in archive.entries() {
+            let Some(path) = archive.file_path(entry)? else {
+                continue;
+            };
+
+            let stored_hash = use_stored_hashes.then_some(entry.hash);
+            if needs_data {
+                let data = archive.read_entry_range(entry, 0, entry.decompressed_size)?;
+                entries.push(ChecksumEntry::new(path, &data, stored_hash));
+            } else {
+                entries.push(ChecksumEntry {
+                    path: String::from(path),
+                    size: entry.decompressed_size,
+                    xxh3: entry.hash,
+                    sha256: None,
+                });
+            }
+        }
+
+        Ok(Self::from_entries(entries))
+    }
+
+    fn from_entries(mut entries: Vec<ChecksumEntry>) -> Self {
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Self { entries }
+    }
+
+    /// Writes the manifest in the text format, see [`ChecksumManifest`].
+    pub fn to_text(&self) -> String {
+        let mut result = String::new();
+        let _ = writeln!(result, "# nx-checksums {MANIFEST_FORMAT_VERSION}");
+        for entry in &self.entries {
+            let _ = write!(result, "{:016x} ", entry.xxh3);
+            match &entry.sha256 {
+                Some(sha256) => push_hex(&mut result, sha256),
+                None => result.push('-'),
+            }
+            let _ = writeln!(result, " {} {}", entry.size, entry.path);
+        }
+
+        result
+    }
+
+    /// Writes the manifest in the JSON format, see [`ChecksumManifest`].
+    pub fn to_json(&self) -> String {
+        let mut result = String::new();
+        let _ = write!(
+            result,
+            "{{\"version\":{MANIFEST_FORMAT_VERSION},\"files\":["
+        );
+        for (index, entry) in self.entries.iter().enumerate() {
+            if index > 0 {
+                result.push(',');
+            }
+
+            result.push_str("{\"path\":");
+            push_json_string(&mut result, &entry.path);
+            let _ = write!(
+                result,
+                ",\"size\":{},\"xxh3\":\"{:016x}\",\"sha256\":",
+                entry.size, entry.xxh3
+            );
+            match &entry.sha256 {
+                Some(sha256) => {
+                    result.push('"');
+                    push_hex(&mut result, sha256);
+                    result.push('"');
+                }
+                None => result.push_str("null"),
+            }
+            result.push('}');
+        }
+
+        result.push_str("]}");
+        result
+    }
+}
+
+impl ChecksumEntry {
+    fn new(path: &str, data: &[u8], stored_hash: Option<u64>) -> Self {
+        Self {
+            path: String::from(path),
+            size: data.len() as u64,
+            xxh3: stored_hash.unwrap_or_else(|| XXH3sum::create(data).0),
+            sha256: sha256(data),
+        }
+    }
+}
+
+#[cfg(feature = "sha256")]
+fn sha256(data: &[u8]) -> Option<[u8; 32]> {
+    use sha2::{Digest, Sha256};
+    Some(Sha256::digest(data).into())
+}
+
+#[cfg(not(feature = "sha256"))]
+fn sha256(_data: &[u8]) -> Option<[u8; 32]> {
+    None
+}
+
+fn push_hex(output: &mut String, bytes: &[u8]) {
+    for byte in bytes {
+        let _ = write!(output, "{byte:02x}");
+    }
+}
+
+fn push_json_string(output: &mut String, value: &str) {
+    output.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(output, "\\u{:04x}", c as u32);
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{enums::CompressionPreference, filedata::FromSliceReferenceProvider};
+    use crate::unsize_box2;
+    use crate::utilities::tests::archive_for_testing::*;
+
+    fn manifest() -> ChecksumManifest {
+        ChecksumManifest {
+            entries: vec![ChecksumEntry {
+                path: String::from("dir/my \"file\".txt"),
+                size: 5,
+                xxh3: 0xABCD,
+                sha256: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn writes_text_format() {
+        assert_eq!(
+            manifest().to_text(),
+            "# nx-checksums 1\n000000000000abcd - 5 dir/my \"file\".txt\n"
+        );
+    }
+
+    #[test]
+    fn writes_json_format() {
+        assert_eq!(
+            manifest().to_json(),
+            r#"{"version":1,"files":[{"path":"dir/my \"file\".txt","size":5,"xxh3":"000000000000abcd","sha256":null}]}"#
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn archive_manifest_matches_file_hashes() {
+        let archive = create_test_archive(
+            4096,
+            &[&[
+                TestFile::new("b.txt", b"Nx archives!"),
+                TestFile::new("a.txt", b"Hello"),
+            ]],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+
+        let manifest = ChecksumManifest::from_archive(&reader).unwrap();
+        assert_eq!(manifest.entries.len(), 2);
+        assert_eq!(manifest.entries[0].path, "a.txt");
+        assert_eq!(manifest.entries[0].size, 5);
+        assert_eq!(manifest.entries[0].xxh3, XXH3sum::create(b"Hello").0);
+        assert_eq!(
+            manifest.entries[0].sha256.is_some(),
+            cfg!(feature = "sha256")
+        );
+    }
+}
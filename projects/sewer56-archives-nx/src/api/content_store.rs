@@ -0,0 +1,383 @@
+use crate::api::{
+    archive_reader::{ArchiveReadError, NxArchiveReader},
+    filedata::*,
+    incremental_pack::{pack_incremental, IncrementalPackError, IncrementalStats},
+    packing::{packer_file::PackerFile, packing_settings::PackingSettings},
+    raw_archive_writer::{NxRawArchiveWriter, RawArchiveWriteError},
+};
+use crate::headers::types::xxh3sum::XXH3sum;
+use crate::implementation::pack::state::{
+    chunked_deduplication_state::ChunkedDeduplicationState, pack_state::DeduplicationError,
+};
+use crate::{prelude::*, unsize_box2};
+use alloc::string::String;
+use core::fmt::Write;
+use hashbrown::HashSet;
+use thiserror_no_std::Error;
+
+/// Errors that can occur when using a [`ContentStore`].
+#[derive(Debug, Error)]
+pub enum ContentStoreError {
+    /// The archive the store was opened from has no file hashes.
+    #[error("The archive does not store file hashes")]
+    MissingHashes,
+
+    /// Failed to read from the underlying archive.
+    #[error("Failed to read archive: {0:?}")]
+    Read(#[from] ArchiveReadError),
+
+    /// Failed to write the compacted archive.
+    #[error("Failed to compact archive: {0:?}")]
+    Pack(#[from] IncrementalPackError),
+
+    /// Failed to write the archive.
+    #[error(transparent)]
+    Write(#[from] RawArchiveWriteError),
+
+    /// Failed to access the deduplication state.
+    #[error(transparent)]
+    Deduplication(#[from] DeduplicationError),
+}
+
+/// A content addressed store, backed by an Nx archive.
+///
+/// Data is stored and retrieved by the XXH3 hash of its contents, and storing the same
+/// data twice only keeps one copy. This makes it suitable as a cache, e.g. for mod managers,
+/// in place of thousands of loose files.
+///
+/// New data is held in memory until the store is compacted with [`Self::compact`] (or
+/// automatically, see [`Self::with_auto_compact_threshold`]). Compaction writes the new data
+/// into the archive, drops removed data, and copies blocks which are unaffected as-is.
+///
+/// Within the archive, each item is stored as a file named after its hash in hex.
+///
+/// Each compaction increments the [`generation`](Self::generation) of the archive. When the
+/// archive is shared between processes, save it with
+/// [`save_if_unmodified`](crate::api::archive_generation::save_if_unmodified), passing the
+/// generation it was opened at, so that concurrent edits fail instead of overwriting each other.
+pub struct ContentStore {
+    /// Settings used to compress new data.
+    settings: PackingSettings,
+
+    /// The archive holding all data as of the last compaction.
+    reader: NxArchiveReader<'static>,
+
+    /// Data added since the last compaction, along with its hash.
+    pending: Vec<(u64, Vec<u8>)>,
+
+    /// Total size of [`Self::pending`].
+    pending_bytes: u64,
+
+    /// Hashes of data removed since the last compaction.
+    removed: HashSet<u64>,
+
+    /// Maps the hash of each item to its slot; entries of [`Self::reader`] come first,
+    /// followed by [`Self::pending`].
+    dedup: ChunkedDeduplicationState,
+
+    /// Size of pending data at which [`Self::put`] compacts the store.
+    auto_compact_threshold: Option<u64>,
+}
+
+impl ContentStore {
+    /// Creates an empty store.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - Settings used to compress data when compacting.
+    pub fn new(mut settings: PackingSettings) -> Result<Self, ContentStoreError> {
+        settings.sanitize();
+        let archive = NxRawArchiveWriter::new(settings.chunk_size)
+            .with_generation(1)
+            .with_hash_seed(settings.hash_seed)
+            .build()?;
+        Self::open(archive, settings)
+    }
+
+    /// Opens a store from an archive previously written with [`Self::to_archive`].
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - The archive. Must store file hashes.
+    /// * `settings` - Settings used to compress data when compacting.
+    pub fn open(
+        archive: Vec<u8>,
+        mut settings: PackingSettings,
+    ) -> Result<Self, ContentStoreError> {
+        settings.sanitize();
+        let reader = open_reader(archive)?;
+
+        // Data is keyed by hash, so new data must be hashed like the data already stored.
+        settings.hash_seed = reader.hash_seed();
+        let dedup = ChunkedDeduplicationState::with_optional_memory_limit(
+            settings.dedup_memory_limit.as_ref(),
+        );
+        let mut result = Self {
+            settings,
+            reader,
+            pending: Vec::new(),
+            pending_bytes: 0,
+            removed: HashSet::new(),
+            dedup,
+            auto_compact_threshold: None,
+        };
+
+        result.index_archive()?;
+        Ok(result)
+    }
+
+    /// Compacts the store automatically once the data added since the last compaction
+    /// reaches a given size.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Size of pending data in bytes, or [`None`] to only compact manually.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_auto_compact_threshold(mut self, threshold: Option<u64>) -> Self {
+        self.auto_compact_threshold = threshold;
+        self
+    }
+
+    /// Stores data, unless identical data is already stored.
+    ///
+    /// # Returns
+    ///
+    /// The hash of the data, used to retrieve it with [`Self::get`].
+    pub fn put(&mut self, data: &[u8]) -> Result<u64, ContentStoreError> {
+        let hash = XXH3sum::create_with_seed(data, self.settings.hash_seed);
+        if self.dedup.try_find_duplicate_by_full_hash(hash)?.is_some() {
+            self.removed.remove(&hash.0);
+            return Ok(hash.0);
+        }
+
+        // The full hash is the key, so the short hash is never queried.
+        let slot = self.reader.entries().len() + self.pending.len();
+        self.dedup.add_file_hash(hash, hash, slot as u32)?;
+        self.pending.push((hash.0, Vec::from(data)));
+        self.pending_bytes += data.len() as u64;
+
+        if self
+            .auto_compact_threshold
+            .is_some_and(|x| self.pending_bytes >= x)
+        {
+            self.compact()?;
+        }
+
+        Ok(hash.0)
+    }
+
+    /// Retrieves data by its hash.
+    ///
+    /// # Returns
+    ///
+    /// The data, or [`None`] if no data with this hash is stored.
+    pub fn get(&self, hash: u64) -> Result<Option<Vec<u8>>, ContentStoreError> {
+        let Some(slot) = self.find_slot(hash)? else {
+            return Ok(None);
+        };
+
+        let entries = self.reader.entries();
+        match entries.get(slot) {
+            Some(entry) => Ok(Some(self.reader.read_entry_range(
+                entry,
+                0,
+                entry.decompressed_size,
+            )?)),
+            None => Ok(Some(self.pending[slot - entries.len()].1.clone())),
+        }
+    }
+
+    /// Returns true if data with the given hash is stored.
+    pub fn contains(&self, hash: u64) -> Result<bool, ContentStoreError> {
+        Ok(self.find_slot(hash)?.is_some())
+    }
+
+    /// Removes data from the store. The space is reclaimed on the next compaction.
+    ///
+    /// # Returns
+    ///
+    /// True if the data was stored.
+    pub fn remove(&mut self, hash: u64) -> Result<bool, ContentStoreError> {
+        Ok(self.contains(hash)? && self.removed.insert(hash))
+    }
+
+    /// Returns the number of items stored.
+    pub fn len(&self) -> usize {
+        self.reader.entries().len() + self.pending.len() - self.removed.len()
+    }
+
+    /// Returns true if nothing is stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the generation of the archive as of the last compaction.
+    /// See [`NxArchiveReader::generation`].
+    pub fn generation(&self) -> u64 {
+        self.reader.generation()
+    }
+
+    /// Returns true if the store has changed since the last compaction.
+    pub fn needs_compaction(&self) -> bool {
+        !self.pending.is_empty() || !self.removed.is_empty()
+    }
+
+    /// Writes new data into the archive and drops removed data.
+    ///
+    /// Blocks whose items are all kept are copied without recompressing,
+    /// see [`pack_incremental`].
+    ///
+    /// # Returns
+    ///
+    /// How much of the previous archive was reused.
+    pub fn compact(&mut self) -> Result<IncrementalStats, ContentStoreError> {
+        if !self.needs_compaction() {
+            return Ok(IncrementalStats::default());
+        }
+
+        let (archive, stats) = {
+            let mut files = Vec::with_capacity(self.len());
+            for entry in self.reader.entries() {
+                if self.removed.contains(&entry.hash) {
+                    continue;
+                }
+
+                let provider = Box::new(FromArchiveEntryProvider::new(&self.reader, *entry));
+                files.push(PackerFile::new(
+                    hash_path(entry.hash),
+                    entry.decompressed_size,
+                    unsize_box2!(provider),
+                ));
+            }
+
+            for (hash, data) in &self.pending {
+                if self.removed.contains(hash) {
+                    continue;
+                }
+
+                let provider = Box::new(FromSliceReferenceProvider::new(data));
+                files.push(PackerFile::new(
+                    hash_path(*hash),
+                    data.len() as u64,
+                    unsize_box2!(provider),
+                ));
+            }
+
+            pack_incremental(&self.reader, &files, &self.settings)?
+        };
+
+        self.reader = open_reader(archive)?;
+        self.pending.clear();
+        self.pending_bytes = 0;
+        self.removed.clear();
+        self.dedup = ChunkedDeduplicationState::with_optional_memory_limit(
+            self.settings.dedup_memory_limit.as_ref(),
+        );
+        self.index_archive()?;
+        Ok(stats)
+    }
+
+    /// Compacts the store, and returns the archive holding all of its data.
+    /// The store can be reopened from it with [`Self::open`].
+    pub fn to_archive(&mut self) -> Result<Vec<u8>, ContentStoreError> {
+        self.compact()?;
+        let mut writer = NxRawArchiveWriter::new(self.reader.chunk_size())
+            .with_hash_seed(self.reader.hash_seed())
+            .with_generation(self.reader.generation());
+        writer.append_archive(&self.reader)?;
+        Ok(writer.build()?)
+    }
+
+    fn index_archive(&mut self) -> Result<(), ContentStoreError> {
+        if !self.reader.has_file_hashes() && !self.reader.entries().is_empty() {
+            return Err(ContentStoreError::MissingHashes);
+        }
+
+        self.dedup.ensure_capacity(self.reader.entries().len())?;
+        for (slot, entry) in self.reader.entries().iter().enumerate() {
+            let hash = XXH3sum(entry.hash);
+            self.dedup.add_file_hash(hash, hash, slot as u32)?;
+        }
+
+        Ok(())
+    }
+
+    fn find_slot(&self, hash: u64) -> Result<Option<usize>, ContentStoreError> {
+        if self.removed.contains(&hash) {
+            return Ok(None);
+        }
+
+        Ok(self
+            .dedup
+            .try_find_duplicate_by_full_hash(XXH3sum(hash))?
+            .map(|x| x.0 as usize))
+    }
+}
+
+fn open_reader(archive: Vec<u8>) -> Result<NxArchiveReader<'static>, ArchiveReadError> {
+    let provider = Box::new(FromBoxedSliceProvider::new(archive.into_boxed_slice()));
+    NxArchiveReader::new(unsize_box2!(provider))
+}
+
+fn hash_path(hash: u64) -> String {
+    let mut result = String::new();
+    let _ = write!(result, "{hash:016x}");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn deduplicates_and_retrieves_data() {
+        let mut store = ContentStore::new(PackingSettings::new()).unwrap();
+        let hello = store.put(b"Hello").unwrap();
+        let world = store.put(b"World").unwrap();
+        assert_eq!(store.put(b"Hello").unwrap(), hello);
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get(hello).unwrap().unwrap().as_slice(), b"Hello");
+
+        assert_eq!(store.generation(), 1);
+        store.compact().unwrap();
+        assert_eq!(store.generation(), 2);
+        assert!(!store.needs_compaction());
+        assert_eq!(store.get(world).unwrap().unwrap().as_slice(), b"World");
+        assert_eq!(store.get(0).unwrap(), None);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_remove_and_reopen() {
+        let mut store = ContentStore::new(PackingSettings::new()).unwrap();
+        let hello = store.put(b"Hello").unwrap();
+        let world = store.put(b"World").unwrap();
+        store.compact().unwrap();
+
+        assert!(store.remove(hello).unwrap());
+        assert!(!store.contains(hello).unwrap());
+        let archive = store.to_archive().unwrap();
+
+        let store = ContentStore::open(archive, PackingSettings::new()).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get(hello).unwrap(), None);
+        assert_eq!(store.get(world).unwrap().unwrap().as_slice(), b"World");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn compacts_automatically() {
+        let mut store = ContentStore::new(PackingSettings::new())
+            .unwrap()
+            .with_auto_compact_threshold(Some(8));
+        store.put(b"Hello").unwrap();
+        assert!(store.needs_compaction());
+        store.put(b"World").unwrap();
+        assert!(!store.needs_compaction());
+        assert_eq!(store.len(), 2);
+    }
+}
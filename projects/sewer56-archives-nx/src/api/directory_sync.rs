@@ -0,0 +1,552 @@
+use crate::api::{
+    archive_reader::{ArchiveReadError, NxArchiveReader},
+    enums::ExtractConflictPolicy,
+    extraction_estimate::ExtractionEstimator,
+    extraction_plan::check_free_space,
+    incremental_pack::{pack_incremental, IncrementalPackError, IncrementalStats},
+    packing::packing_settings::PackingSettings,
+    traits::*,
+};
+use crate::headers::managed::FileEntry;
+use crate::prelude::*;
+use crate::utilities::io::file_finder::find_files;
+use alloc::string::String;
+use hashbrown::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, MAIN_SEPARATOR};
+use std::time::Instant;
+use thiserror_no_std::Error;
+
+/// Errors that can occur when syncing a directory with an archive.
+#[derive(Debug, Error)]
+pub enum DirectorySyncError {
+    /// Failed to read from the archive.
+    #[error("Failed to read archive: {0:?}")]
+    Read(#[from] ArchiveReadError),
+
+    /// Failed to access the directory.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// A path in the archive would point outside of the directory, e.g. `../file.txt`.
+    #[error("Path {0} points outside of the target directory")]
+    UnsafePath(String),
+
+    /// A file being extracted already exists, and [`ExtractOptions::on_conflict`]
+    /// is [`ExtractConflictPolicy::Error`].
+    #[error("File {0} already exists in the target directory")]
+    FileExists(String),
+
+    /// The target directory's volume does not have enough free space for the extracted files,
+    /// as checked when [`ExtractOptions::check_free_space`] is enabled.
+    #[error(
+        "Extraction requires {required} bytes of free space, but only {available} are available"
+    )]
+    InsufficientSpace {
+        /// Additional space needed by the extracted files.
+        required: u64,
+        /// Free space on the target directory's volume.
+        available: u64,
+    },
+
+    /// Failed to read a file from the directory.
+    #[error(transparent)]
+    FileProvider(#[from] FileProviderError),
+
+    /// Failed to pack the updated archive.
+    #[error(transparent)]
+    Pack(#[from] IncrementalPackError),
+}
+
+/// Options for [`extract_to_dir`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractOptions {
+    /// What to do when a file being extracted already exists.
+    pub on_conflict: ExtractConflictPolicy,
+
+    /// Deletes files in the directory which are not in the archive,
+    /// along with any directories left empty by doing so.
+    pub delete_extra_files: bool,
+
+    /// Checks the target directory's volume has enough free space before extracting
+    /// anything, see [`check_free_space`]. Enabled by default.
+    ///
+    /// [`check_free_space`]: crate::api::extraction_plan::check_free_space
+    pub check_free_space: bool,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            on_conflict: ExtractConflictPolicy::default(),
+            delete_extra_files: false,
+            check_free_space: true,
+        }
+    }
+}
+
+/// How an existing file was handled during extraction, see [`ExtractConflictPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// The existing file was replaced by the file from the archive.
+    Overwritten,
+
+    /// The existing file was kept, as per [`ExtractConflictPolicy::Skip`].
+    Skipped,
+
+    /// The existing file already matched the archive, and was left untouched.
+    Unchanged,
+}
+
+/// What was changed by [`sync_to_dir`] or [`extract_to_dir`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncToDirStats {
+    /// Number of files written, because they were missing or had changed.
+    pub extracted_files: u32,
+
+    /// Total size of the files written.
+    pub extracted_bytes: u64,
+
+    /// Number of files already matching the archive, which were left untouched.
+    pub unchanged_files: u32,
+
+    /// Number of existing files kept instead of extracting, as per [`ExtractConflictPolicy::Skip`].
+    pub skipped_files: u32,
+
+    /// Number of existing files replaced by files from the archive.
+    pub overwritten_files: u32,
+
+    /// Number of files deleted, because they were not in the archive.
+    pub deleted_files: u32,
+
+    /// Path of each file which already existed in the directory, and how it was handled.
+    pub conflicts: Vec<(String, ConflictResolution)>,
+}
+
+/// Makes a directory match the contents of an archive, e.g. to install or update a mod.
+///
+/// Only files which are missing from the directory, or whose contents differ from the
+/// archive are extracted. A file is considered unchanged if it has the same size, and the
+/// same hash as stored in the archive; if the archive has no hashes, the contents are compared
+/// directly instead.
+///
+/// # Arguments
+///
+/// * `archive` - The archive to extract from.
+/// * `target_dir` - The directory to sync. Created if it does not exist.
+/// * `delete_extra_files` - Deletes files in the directory which are not in the archive,
+///   along with any directories left empty by doing so.
+///
+/// # Remarks
+///
+/// Files in the archive without a path are skipped.
+pub fn sync_to_dir(
+    archive: &NxArchiveReader<'_>,
+    target_dir: impl AsRef<Path>,
+    delete_extra_files: bool,
+) -> Result<SyncToDirStats, DirectorySyncError> {
+    sync_to_dir_with_progress(archive, target_dir, delete_extra_files, |_| {})
+}
+
+/// Same as [`sync_to_dir`], but reports progress after each file.
+///
+/// # Arguments
+///
+/// * `archive` - The archive to extract from.
+/// * `target_dir` - The directory to sync. Created if it does not exist.
+/// * `delete_extra_files` - Deletes files in the directory which are not in the archive,
+///   along with any directories left empty by doing so.
+/// * `on_progress` - Called after each file, with the progress so far and the estimated
+///   time left, see [`ExtractionEstimator::estimated_remaining`].
+pub fn sync_to_dir_with_progress(
+    archive: &NxArchiveReader<'_>,
+    target_dir: impl AsRef<Path>,
+    delete_extra_files: bool,
+    on_progress: impl FnMut(&ExtractionEstimator),
+) -> Result<SyncToDirStats, DirectorySyncError> {
+    let options = ExtractOptions {
+        on_conflict: ExtractConflictPolicy::OverwriteIfDifferentHash,
+        delete_extra_files,
+        ..ExtractOptions::default()
+    };
+    extract_to_dir_with_progress(archive, target_dir, &options, on_progress)
+}
+
+/// Extracts all files of an archive into a directory.
+///
+/// # Arguments
+///
+/// * `archive` - The archive to extract from.
+/// * `target_dir` - The directory to extract to. Created if it does not exist.
+/// * `options` - What to do with files which already exist, and files not in the archive.
+///
+/// # Returns
+///
+/// What was changed, including how each existing file was handled.
+///
+/// # Remarks
+///
+/// Unless [`ExtractOptions::check_free_space`] is disabled, fails with
+/// [`DirectorySyncError::InsufficientSpace`] before writing anything if the target
+/// directory's volume lacks the space for the extracted files.
+///
+/// Files in the archive without a path are skipped. Conflicts are checked before writing
+/// each file; with [`ExtractConflictPolicy::Error`], files extracted before the conflicting
+/// one are left in place.
+pub fn extract_to_dir(
+    archive: &NxArchiveReader<'_>,
+    target_dir: impl AsRef<Path>,
+    options: &ExtractOptions,
+) -> Result<SyncToDirStats, DirectorySyncError> {
+    extract_to_dir_with_progress(archive, target_dir, options, |_| {})
+}
+
+/// Same as [`extract_to_dir`], but reports progress after each file.
+///
+/// # Arguments
+///
+/// * `archive` - The archive to extract from.
+/// * `target_dir` - The directory to extract to. Created if it does not exist.
+/// * `options` - What to do with files which already exist, and files not in the archive.
+/// * `on_progress` - Called after each file, with the progress so far and the estimated
+///   time left, see [`ExtractionEstimator::estimated_remaining`].
+pub fn extract_to_dir_with_progress(
+    archive: &NxArchiveReader<'_>,
+    target_dir: impl AsRef<Path>,
+    options: &ExtractOptions,
+    mut on_progress: impl FnMut(&ExtractionEstimator),
+) -> Result<SyncToDirStats, DirectorySyncError> {
+    let target_dir = target_dir.as_ref();
+    let mut stats = SyncToDirStats::default();
+    let mut archive_paths = HashSet::new();
+    let mut estimator = ExtractionEstimator::for_archive(archive);
+
+    if options.check_free_space {
+        check_free_space(archive, target_dir, options)?;
+    }
+
+    fs::create_dir_all(target_dir)?;
+    for entry in archive.entries() {
+        let Some(path) = archive.file_path(entry)? else {
+            estimator.skip_entry(archive, entry);
+            continue;
+        };
+
+        if !is_safe_relative_path(path) {
+            return Err(DirectorySyncError::UnsafePath(String::from(path)));
+        }
+
+        archive_paths.insert(path);
+        let destination = target_dir.join(path);
+        let exists = fs::symlink_metadata(&destination).is_ok();
+        if exists {
+            let resolution = match options.on_conflict {
+                ExtractConflictPolicy::Overwrite => ConflictResolution::Overwritten,
+                ExtractConflictPolicy::Skip => ConflictResolution::Skipped,
+                ExtractConflictPolicy::Error => {
+                    return Err(DirectorySyncError::FileExists(String::from(path)))
+                }
+                ExtractConflictPolicy::OverwriteIfDifferentHash => {
+                    match is_unchanged(archive, entry, &destination)? {
+                        true => ConflictResolution::Unchanged,
+                        false => ConflictResolution::Overwritten,
+                    }
+                }
+            };
+
+            stats.conflicts.push((String::from(path), resolution));
+            match resolution {
+                ConflictResolution::Overwritten => stats.overwritten_files += 1,
+                ConflictResolution::Skipped => stats.skipped_files += 1,
+                ConflictResolution::Unchanged => stats.unchanged_files += 1,
+            }
+
+            if resolution != ConflictResolution::Overwritten {
+                estimator.skip_entry(archive, entry);
+                on_progress(&estimator);
+                continue;
+            }
+        }
+
+        let start = Instant::now();
+        let data = archive.read_entry_range(entry, 0, entry.decompressed_size)?;
+        estimator.record_entry(archive, entry, start.elapsed());
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&destination, &data)?;
+        stats.extracted_files += 1;
+        stats.extracted_bytes += entry.decompressed_size;
+        on_progress(&estimator);
+    }
+
+    if options.delete_extra_files {
+        delete_extra(target_dir, target_dir, &archive_paths, &mut stats)?;
+    }
+
+    Ok(stats)
+}
+
+/// What was changed by [`sync_from_dir`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SyncFromDirStats {
+    /// How much of the previous archive was reused.
+    pub packed: IncrementalStats,
+
+    /// Number of files in the previous archive which are no longer in the directory.
+    pub removed_files: u32,
+}
+
+/// Updates an archive to match the contents of a directory, e.g. for backups.
+///
+/// Blocks of the previous archive whose files are all unchanged are copied as-is,
+/// changed and new files are compressed again, and files no longer in the directory
+/// are dropped. See [`pack_incremental`] for details on what is reused.
+///
+/// # Arguments
+///
+/// * `archive` - The previous version of the archive. Must store file hashes.
+/// * `source_dir` - The directory to pack.
+/// * `settings` - Settings used to compress the files which are not reused.
+///
+/// # Returns
+///
+/// The updated archive, along with what was changed.
+pub fn sync_from_dir(
+    archive: &NxArchiveReader<'_>,
+    source_dir: impl AsRef<Path>,
+    settings: &PackingSettings,
+) -> Result<(Vec<u8>, SyncFromDirStats), DirectorySyncError> {
+    let mut files = Vec::new();
+    find_files(source_dir, |file| files.push(file))?;
+
+    let source_paths: HashSet<&str> = files.iter().map(|x| x.relative_path()).collect();
+    let mut removed_files = 0;
+    for entry in archive.entries() {
+        if let Some(path) = archive.file_path(entry)? {
+            if !source_paths.contains(path) {
+                removed_files += 1;
+            }
+        }
+    }
+
+    let (data, packed) = pack_incremental(archive, &files, settings)?;
+    Ok((
+        data,
+        SyncFromDirStats {
+            packed,
+            removed_files,
+        },
+    ))
+}
+
+pub(crate) fn is_safe_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|x| matches!(x, Component::Normal(_) | Component::CurDir))
+}
+
+fn is_unchanged(
+    archive: &NxArchiveReader<'_>,
+    entry: &FileEntry,
+    destination: &Path,
+) -> Result<bool, DirectorySyncError> {
+    match fs::metadata(destination) {
+        Ok(metadata) if metadata.is_file() && metadata.len() == entry.decompressed_size => {}
+        _ => return Ok(false),
+    }
+
+    let existing = fs::read(destination)?;
+    if archive.has_file_hashes() {
+        return Ok(archive.hash_data(&existing) == entry.hash);
+    }
+
+    let data = archive.read_entry_range(entry, 0, entry.decompressed_size)?;
+    Ok(existing.as_slice() == data.as_slice())
+}
+
+/// Deletes the files under `current_dir` which are not in `keep`.
+///
+/// # Returns
+///
+/// True if `current_dir` is empty afterwards.
+fn delete_extra(
+    current_dir: &Path,
+    base_dir: &Path,
+    keep: &HashSet<&str>,
+    stats: &mut SyncToDirStats,
+) -> Result<bool, DirectorySyncError> {
+    let mut is_empty = true;
+    for entry in fs::read_dir(current_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            if delete_extra(&path, base_dir, keep, stats)? {
+                fs::remove_dir(&path)?;
+            } else {
+                is_empty = false;
+            }
+            continue;
+        }
+
+        let relative_path = path
+            .strip_prefix(base_dir)
+            .map(|x| x.to_string_lossy().replace(MAIN_SEPARATOR, "/"))
+            .unwrap_or_default();
+        if keep.contains(relative_path.as_str()) {
+            is_empty = false;
+        } else {
+            fs::remove_file(&path)?;
+            stats.deleted_files += 1;
+        }
+    }
+
+    Ok(is_empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{enums::CompressionPreference, filedata::FromSliceReferenceProvider};
+    use crate::unsize_box2;
+    use crate::utilities::tests::archive_for_testing::*;
+    use tempfile::tempdir;
+
+    fn create_archive() -> Vec<u8> {
+        create_test_archive(
+            4096,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new("data/b.txt", b"Nx archives!"),
+            ]],
+            &[],
+            CompressionPreference::ZStandard,
+        )
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn only_extracts_changed_files() {
+        let archive = create_archive();
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+        let dir = tempdir().unwrap();
+
+        let stats = sync_to_dir(&reader, dir.path(), false).unwrap();
+        assert_eq!(stats.extracted_files, 2);
+        assert_eq!(
+            fs::read(dir.path().join("data/b.txt")).unwrap(),
+            b"Nx archives!"
+        );
+
+        fs::write(dir.path().join("a.txt"), b"World").unwrap();
+        let stats = sync_to_dir(&reader, dir.path(), false).unwrap();
+        assert_eq!(stats.extracted_files, 1);
+        assert_eq!(stats.unchanged_files, 1);
+        assert_eq!(fs::read(dir.path().join("a.txt")).unwrap(), b"Hello");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn applies_conflict_policy() {
+        let archive = create_archive();
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+        let dir = tempdir().unwrap();
+        let extract = |on_conflict| {
+            let options = ExtractOptions {
+                on_conflict,
+                ..ExtractOptions::default()
+            };
+            extract_to_dir(&reader, dir.path(), &options)
+        };
+
+        fs::write(dir.path().join("a.txt"), b"World").unwrap();
+        let stats = extract(ExtractConflictPolicy::Skip).unwrap();
+        assert_eq!(stats.extracted_files, 1);
+        assert_eq!(stats.skipped_files, 1);
+        assert_eq!(
+            stats.conflicts.as_slice(),
+            &[(String::from("a.txt"), ConflictResolution::Skipped)]
+        );
+        assert_eq!(fs::read(dir.path().join("a.txt")).unwrap(), b"World");
+
+        assert!(matches!(
+            extract(ExtractConflictPolicy::Error),
+            Err(DirectorySyncError::FileExists(_))
+        ));
+
+        let stats = extract(ExtractConflictPolicy::Overwrite).unwrap();
+        assert_eq!(stats.extracted_files, 2);
+        assert_eq!(stats.overwritten_files, 2);
+        assert_eq!(fs::read(dir.path().join("a.txt")).unwrap(), b"Hello");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reports_progress() {
+        let archive = create_archive();
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+        let dir = tempdir().unwrap();
+
+        let mut reports = Vec::new();
+        sync_to_dir_with_progress(&reader, dir.path(), false, |x| reports.push(x.progress()))
+            .unwrap();
+        assert_eq!(reports.len(), 2);
+        assert!(reports[0] > 0.0);
+        assert_eq!(reports[1], 1.0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn deletes_extra_files() {
+        let archive = create_archive();
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("old/nested")).unwrap();
+        fs::write(dir.path().join("old/nested/c.txt"), b"Stale").unwrap();
+        fs::write(dir.path().join("data.txt"), b"Stale").unwrap();
+
+        let stats = sync_to_dir(&reader, dir.path(), true).unwrap();
+        assert_eq!(stats.deleted_files, 2);
+        assert!(!dir.path().join("old").exists());
+        assert!(!dir.path().join("data.txt").exists());
+        assert!(dir.path().join("a.txt").exists());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn updates_archive_from_directory() {
+        let archive = create_test_archive(
+            4096,
+            &[
+                &[TestFile::new("a.txt", b"Hello")],
+                &[TestFile::new("data/b.txt", b"Nx archives!")],
+            ],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), b"Hello").unwrap();
+        fs::write(dir.path().join("c.txt"), b"Added").unwrap();
+
+        let (updated, stats) = sync_from_dir(&reader, dir.path(), &PackingSettings::new()).unwrap();
+        assert_eq!(stats.removed_files, 1);
+        assert_eq!(stats.packed.reused_files, 1);
+        assert_eq!(stats.packed.compressed_files, 1);
+
+        let provider = Box::new(FromSliceReferenceProvider::new(&updated));
+        let updated = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+        assert_eq!(updated.entries().len(), 2);
+        assert_eq!(updated.read_file("a.txt").unwrap().as_slice(), b"Hello");
+        assert_eq!(updated.read_file("c.txt").unwrap().as_slice(), b"Added");
+        assert!(updated.read_file("data/b.txt").is_err());
+    }
+}
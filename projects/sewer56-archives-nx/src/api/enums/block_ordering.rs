@@ -0,0 +1,22 @@
+/// Controls the order of files within each SOLID block.
+///
+/// # Remarks
+///
+/// Reading a file from a SOLID block requires decompressing the block up to the end of that
+/// file, so files placed early are cheaper to extract on their own. Placing similar files
+/// next to each other can also improve compression ratio.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum BlockOrdering {
+    /// Smallest files first, so extracting a single small file decompresses less data.
+    #[default]
+    SizeAscending,
+
+    /// Files are ordered by their path, keeping files from the same folder together.
+    Path,
+
+    /// Files with similar contents are placed next to each other, smallest first within
+    /// each cluster of similar files.
+    ///
+    /// This reads a few small samples of every file in a SOLID block before packing.
+    ContentSimilarity,
+}
@@ -1,8 +1,9 @@
 /// Preferred option for compression.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
 #[repr(u8)]
 pub enum CompressionPreference {
     /// No preference is specified.
+    #[default]
     NoPreference = 255,
 
     // Note: Values below match their encoding in ToC, so we use 255 as 'none'.
@@ -15,4 +16,14 @@ pub enum CompressionPreference {
 
     /// Compress with LZ4.
     Lz4 = 2,
+
+    /// The block is split into segments, each compressed with its own codec
+    /// (e.g. Copy for already compressed files, ZStandard for the rest).
+    /// See [`mixed`](crate::utilities::compression::mixed) for the format.
+    ///
+    /// This describes a block, rather than an algorithm; as a packing preference,
+    /// it means ZStandard with [`PackingSettings::mixed_codec_blocks`] enabled.
+    ///
+    /// [`PackingSettings::mixed_codec_blocks`]: crate::api::packing::packing_settings::PackingSettings::mixed_codec_blocks
+    Mixed = 3,
 }
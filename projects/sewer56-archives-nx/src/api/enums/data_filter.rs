@@ -0,0 +1,55 @@
+/// Reversible transform applied to block data before compression.
+///
+/// # Remarks
+///
+/// Filters rearrange structured data (e.g. arrays of `f32` in meshes or animations)
+/// such that the compressor sees longer runs of similar bytes. The filter used for a block
+/// is recorded alongside it, so decompression can reverse it.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+#[repr(u8)]
+pub enum DataFilter {
+    /// No filter is applied, data is compressed as-is.
+    #[default]
+    None = 0,
+
+    /// Groups the Nth byte of every 4 byte element together.
+    /// Suited to arrays of `f32`/`u32`.
+    ByteTranspose4 = 1,
+
+    /// Groups the Nth byte of every 8 byte element together.
+    /// Suited to arrays of `f64`/`u64`.
+    ByteTranspose8 = 2,
+
+    /// Stores each byte as the difference from the byte 4 positions before it.
+    /// Suited to slowly changing arrays of 4 byte elements.
+    Delta4 = 3,
+
+    /// Stores each byte as the difference from the byte 8 positions before it.
+    /// Suited to slowly changing arrays of 8 byte elements.
+    Delta8 = 4,
+}
+
+impl DataFilter {
+    /// Returns the filter with a given id, as stored in an archive.
+    /// Returns [`None`] for unknown ids.
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(DataFilter::None),
+            1 => Some(DataFilter::ByteTranspose4),
+            2 => Some(DataFilter::ByteTranspose8),
+            3 => Some(DataFilter::Delta4),
+            4 => Some(DataFilter::Delta8),
+            _ => None,
+        }
+    }
+
+    /// Returns the size of the elements this filter operates on, in bytes.
+    /// Returns `0` for [`DataFilter::None`].
+    pub fn stride(&self) -> usize {
+        match self {
+            DataFilter::None => 0,
+            DataFilter::ByteTranspose4 | DataFilter::Delta4 => 4,
+            DataFilter::ByteTranspose8 | DataFilter::Delta8 => 8,
+        }
+    }
+}
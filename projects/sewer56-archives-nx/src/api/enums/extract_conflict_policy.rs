@@ -0,0 +1,21 @@
+/// Controls what happens when a file is extracted to a path where a file already exists.
+///
+/// # Remarks
+///
+/// This saves callers from having to clean the target directory before extracting.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ExtractConflictPolicy {
+    /// The existing file is replaced.
+    #[default]
+    Overwrite,
+
+    /// The existing file is kept, and the file from the archive is not extracted.
+    Skip,
+
+    /// Extraction fails with an error naming the existing file.
+    Error,
+
+    /// The existing file is replaced, unless it already matches the file in the archive
+    /// (same size and hash), in which case it is left untouched.
+    OverwriteIfDifferentHash,
+}
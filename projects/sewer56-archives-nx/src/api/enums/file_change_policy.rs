@@ -0,0 +1,16 @@
+/// Controls what happens when a file added to the packer changes before it is packed,
+/// e.g. because a build system is still writing it.
+///
+/// Changes are detected by comparing the size and modification time of files on disk
+/// with those recorded when the file was added; see [`InputDataProvider::detect_change`].
+///
+/// [`InputDataProvider::detect_change`]: crate::api::traits::filedata::InputDataProvider::detect_change
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum FileChangePolicy {
+    /// Packing fails with an error naming the changed file.
+    #[default]
+    Fail,
+
+    /// The file is packed with its current contents, and a warning is reported.
+    Repack,
+}
@@ -0,0 +1,18 @@
+/// Controls how files are arranged into SOLID blocks.
+///
+/// # Remarks
+///
+/// Files placed next to each other are likely to share a SOLID block, and the compressor
+/// can only find matches between files within the same block.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum FileGrouping {
+    /// Files with the same extension are placed together, in ascending size order.
+    #[default]
+    Extension,
+
+    /// Files are grouped by extension, then files with similar contents (e.g. localized
+    /// variants of an asset) are placed next to each other within each group.
+    ///
+    /// This reads a few small samples of every file before packing.
+    ContentSimilarity,
+}
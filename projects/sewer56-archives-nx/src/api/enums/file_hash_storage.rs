@@ -0,0 +1,22 @@
+/// Controls whether the hashes of files are stored in the table of contents.
+///
+/// # Remarks
+///
+/// Hashes allow files to be verified after extraction, and looked up by content.
+/// Omitting them shrinks each file entry by up to 8 bytes.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum FileHashStorage {
+    /// Always store hashes.
+    #[default]
+    Always,
+
+    /// Never store hashes.
+    ///
+    /// If the archive is too large for any table of contents format without hashes,
+    /// a format with hashes is used anyway.
+    Never,
+
+    /// Store hashes, unless that would push the header over a single 4096 byte page,
+    /// in which case a format without hashes is picked.
+    Auto,
+}
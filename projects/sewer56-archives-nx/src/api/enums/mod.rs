@@ -1,8 +1,35 @@
+/// Allows you to specify the order of files within SOLID blocks.
+pub mod block_ordering;
 /// Allows you to specify how the data should be compressed.
 pub mod compression_preference;
+/// Allows you to specify a transform applied to data before compression.
+pub mod data_filter;
+/// Allows you to specify what happens when extracting over an existing file.
+pub mod extract_conflict_policy;
+/// Allows you to specify what happens when a file changes between being added and packed.
+pub mod file_change_policy;
+/// Allows you to specify how files are arranged into SOLID blocks.
+pub mod file_grouping;
+/// Allows you to specify whether file hashes are stored in the archive.
+pub mod file_hash_storage;
+/// Allows you to specify the order file paths are sorted in.
+pub mod path_collation;
+/// Allows you to specify how to handle files added at the same path.
+pub mod path_collision_policy;
 /// Allows you to specify whether a given file should be SOLID or not.
 pub mod solid_preference;
+/// Allows you to specify the CPU priority of packing.
+pub mod worker_priority;
 
 /// Prelude
+pub use block_ordering::*;
 pub use compression_preference::*;
+pub use data_filter::*;
+pub use extract_conflict_policy::*;
+pub use file_change_policy::*;
+pub use file_grouping::*;
+pub use file_hash_storage::*;
+pub use path_collation::*;
+pub use path_collision_policy::*;
 pub use solid_preference::*;
+pub use worker_priority::*;
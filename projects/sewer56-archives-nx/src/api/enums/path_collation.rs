@@ -0,0 +1,159 @@
+use crate::headers::raw::native_file_header::NativeFileHeader;
+use core::cmp::Ordering;
+
+/// Controls the order file paths are sorted in, when packing the string pool.
+///
+/// # Remarks
+///
+/// Entries in the table of contents follow the order of the string pool, so this is
+/// also the order of the entries. The collation is recorded in the archive header, readable
+/// with [`NxArchiveReader::path_collation`], so tools searching the sorted paths can use
+/// the same comparison.
+///
+/// [`NxArchiveReader::path_collation`]: crate::api::archive_reader::NxArchiveReader::path_collation
+#[derive(Debug, Default, Clone, Copy)]
+pub enum PathCollation {
+    /// Paths are compared byte by byte.
+    #[default]
+    Ordinal,
+
+    /// Runs of digits are compared by their numeric value, such that `file2` sorts
+    /// before `file10`. Other characters are compared byte by byte.
+    Natural,
+
+    /// Paths are compared with a user provided function.
+    ///
+    /// The function itself can't be stored in the archive; only the fact that
+    /// a custom collation was used is recorded.
+    Custom(fn(&str, &str) -> Ordering),
+}
+
+impl PathCollation {
+    /// Header feature flags recorded for [`PathCollation::Natural`].
+    const NATURAL_FLAGS: u8 = 0b01;
+
+    /// Header feature flags recorded for [`PathCollation::Custom`].
+    const CUSTOM_FLAGS: u8 = 0b10;
+
+    /// Compares two paths with this collation.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            PathCollation::Ordinal => a.cmp(b),
+            PathCollation::Natural => compare_natural(a, b),
+            PathCollation::Custom(compare) => compare(a, b),
+        }
+    }
+
+    /// Returns the header feature flags which record this collation.
+    /// These are within [`NativeFileHeader::PATH_COLLATION_FLAGS`].
+    pub fn feature_flags(&self) -> u8 {
+        match self {
+            PathCollation::Ordinal => 0,
+            PathCollation::Natural => Self::NATURAL_FLAGS,
+            PathCollation::Custom(_) => Self::CUSTOM_FLAGS,
+        }
+    }
+
+    /// Gets the collation recorded in the header feature flags.
+    ///
+    /// # Returns
+    ///
+    /// The collation, or [`None`] if the paths were sorted with a custom function,
+    /// or the recorded value is unknown.
+    pub fn from_feature_flags(flags: u8) -> Option<Self> {
+        match flags & NativeFileHeader::PATH_COLLATION_FLAGS {
+            0 => Some(PathCollation::Ordinal),
+            Self::NATURAL_FLAGS => Some(PathCollation::Natural),
+            _ => None,
+        }
+    }
+}
+
+/// Compares two strings, treating runs of ASCII digits as numbers.
+///
+/// Numbers with equal value but different amounts of leading zeros (e.g. `01` and `1`)
+/// are ordered by their length; remaining ties are broken by byte order,
+/// so only equal strings compare as [`Ordering::Equal`].
+pub fn compare_natural(a: &str, b: &str) -> Ordering {
+    let (a_bytes, b_bytes) = (a.as_bytes(), b.as_bytes());
+    let (mut a_pos, mut b_pos) = (0, 0);
+    while a_pos < a_bytes.len() && b_pos < b_bytes.len() {
+        let (x, y) = (a_bytes[a_pos], b_bytes[b_pos]);
+        if !x.is_ascii_digit() || !y.is_ascii_digit() {
+            match x.cmp(&y) {
+                Ordering::Equal => {
+                    a_pos += 1;
+                    b_pos += 1;
+                    continue;
+                }
+                ordering => return ordering,
+            }
+        }
+
+        let a_run = digit_run(&a_bytes[a_pos..]);
+        let b_run = digit_run(&b_bytes[b_pos..]);
+        let a_num = trim_leading_zeros(a_run);
+        let b_num = trim_leading_zeros(b_run);
+        let ordering = a_num
+            .len()
+            .cmp(&b_num.len())
+            .then_with(|| a_num.cmp(b_num))
+            .then_with(|| a_run.len().cmp(&b_run.len()));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+
+        a_pos += a_run.len();
+        b_pos += b_run.len();
+    }
+
+    (a_bytes.len() - a_pos)
+        .cmp(&(b_bytes.len() - b_pos))
+        .then_with(|| a.cmp(b))
+}
+
+fn digit_run(bytes: &[u8]) -> &[u8] {
+    let len = bytes
+        .iter()
+        .position(|x| !x.is_ascii_digit())
+        .unwrap_or(bytes.len());
+    &bytes[..len]
+}
+
+fn trim_leading_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|x| **x == b'0').count();
+    &digits[zeros..]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("file2", "file10", Ordering::Less)]
+    #[case("file10", "file2", Ordering::Greater)]
+    #[case("file1", "file01", Ordering::Less)]
+    #[case("a/2/b", "a/10/a", Ordering::Less)]
+    #[case("file", "file1", Ordering::Less)]
+    #[case("file1a", "file1b", Ordering::Less)]
+    #[case("same9", "same9", Ordering::Equal)]
+    fn compares_naturally(#[case] a: &str, #[case] b: &str, #[case] expected: Ordering) {
+        assert_eq!(compare_natural(a, b), expected);
+    }
+
+    #[rstest]
+    #[case(PathCollation::Ordinal)]
+    #[case(PathCollation::Natural)]
+    fn feature_flags_round_trip(#[case] collation: PathCollation) {
+        let recovered = PathCollation::from_feature_flags(collation.feature_flags()).unwrap();
+        assert_eq!(recovered.feature_flags(), collation.feature_flags());
+    }
+
+    #[test]
+    fn custom_collation_is_not_recoverable() {
+        let collation = PathCollation::Custom(|a, b| b.cmp(a));
+        assert_eq!(collation.compare("a", "b"), Ordering::Greater);
+        assert!(PathCollation::from_feature_flags(collation.feature_flags()).is_none());
+    }
+}
@@ -0,0 +1,15 @@
+/// Controls what happens when a file is added to the packer at a path which is already taken.
+///
+/// # Remarks
+///
+/// This allows an archive to be composed from multiple sources (folders, other archives,
+/// in-memory files) which may overlap, without merging them on disk first.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum PathCollisionPolicy {
+    /// The file added last replaces the existing one, like copying one folder over another.
+    #[default]
+    Replace,
+
+    /// The file added first is kept, the new one is ignored.
+    KeepExisting,
+}
@@ -0,0 +1,14 @@
+/// Controls the CPU priority of the thread compressing blocks, relative to other programs.
+///
+/// Lowering the priority keeps packing from causing stutters in programs running at the
+/// same time (e.g. games, or streaming software), at the cost of packing slower while the
+/// CPU is busy.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum WorkerPriority {
+    /// The thread runs at the priority it was created with.
+    #[default]
+    Normal,
+
+    /// The thread yields to threads of normal priority.
+    BelowNormal,
+}
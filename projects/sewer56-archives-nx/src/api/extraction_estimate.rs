@@ -0,0 +1,261 @@
+use crate::api::{archive_reader::NxArchiveReader, enums::CompressionPreference};
+use crate::headers::managed::FileEntry;
+use crate::prelude::*;
+use core::time::Duration;
+
+/// Progress of extracting the data compressed with a single algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AlgorithmProgress {
+    algorithm: CompressionPreference,
+
+    /// Total decompressed size of the files using this algorithm.
+    total_bytes: u64,
+
+    /// Decompressed size of the files already extracted (or skipped).
+    done_bytes: u64,
+
+    /// Bytes whose decompression was timed, for measuring throughput.
+    measured_bytes: u64,
+
+    /// Time spent decompressing [`Self::measured_bytes`].
+    measured_time: Duration,
+}
+
+/// Estimates the time left to extract files, e.g. to show in an installer.
+///
+/// The estimate is based on the remaining bytes of each compression algorithm, and the
+/// decompression throughput measured for that algorithm so far. Algorithms which have not
+/// been measured yet use the average throughput of all extracted data.
+///
+/// # Example
+///
+/// ```no_run
+/// use sewer56_archives_nx::api::archive_reader::{ArchiveReadError, NxArchiveReader};
+/// use sewer56_archives_nx::api::extraction_estimate::ExtractionEstimator;
+/// use std::time::Instant;
+///
+/// fn extract(reader: &NxArchiveReader<'_>) -> Result<(), ArchiveReadError> {
+///     let mut estimator = ExtractionEstimator::for_archive(reader);
+///     for entry in reader.entries() {
+///         let start = Instant::now();
+///         let _data = reader.read_entry_range(entry, 0, entry.decompressed_size)?;
+///         estimator.record_entry(reader, entry, start.elapsed());
+///         println!("{:?} left", estimator.estimated_remaining());
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtractionEstimator {
+    algorithms: Vec<AlgorithmProgress>,
+}
+
+impl ExtractionEstimator {
+    /// Creates an estimator with nothing to extract; add work with [`Self::add`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an estimator for extracting all files in an archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - The archive to be extracted.
+    pub fn for_archive(archive: &NxArchiveReader<'_>) -> Self {
+        let mut result = Self::new();
+        for entry in archive.entries() {
+            result.add(entry_algorithm(archive, entry), entry.decompressed_size);
+        }
+        result
+    }
+
+    /// Adds data to be extracted.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - Algorithm the data is compressed with.
+    /// * `num_bytes` - Size of the data after decompression.
+    pub fn add(&mut self, algorithm: CompressionPreference, num_bytes: u64) {
+        self.get_or_insert(algorithm).total_bytes += num_bytes;
+    }
+
+    /// Records that data was extracted, and how long it took.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - Algorithm the data was compressed with.
+    /// * `num_bytes` - Size of the data after decompression.
+    /// * `elapsed` - Time taken to extract the data.
+    pub fn record(&mut self, algorithm: CompressionPreference, num_bytes: u64, elapsed: Duration) {
+        let progress = self.get_or_insert(algorithm);
+        progress.done_bytes += num_bytes;
+        progress.measured_bytes += num_bytes;
+        progress.measured_time += elapsed;
+    }
+
+    /// Records that a file of an archive was extracted, and how long it took.
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - The archive containing the file.
+    /// * `entry` - The extracted file.
+    /// * `elapsed` - Time taken to extract the file.
+    pub fn record_entry(
+        &mut self,
+        archive: &NxArchiveReader<'_>,
+        entry: &FileEntry,
+        elapsed: Duration,
+    ) {
+        self.record(
+            entry_algorithm(archive, entry),
+            entry.decompressed_size,
+            elapsed,
+        );
+    }
+
+    /// Records that data no longer needs to be extracted (e.g. it is already up to date),
+    /// without affecting the measured throughput.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - Algorithm the data is compressed with.
+    /// * `num_bytes` - Size of the data after decompression.
+    pub fn skip(&mut self, algorithm: CompressionPreference, num_bytes: u64) {
+        self.get_or_insert(algorithm).done_bytes += num_bytes;
+    }
+
+    /// Records that a file of an archive no longer needs to be extracted,
+    /// see [`Self::skip`].
+    pub fn skip_entry(&mut self, archive: &NxArchiveReader<'_>, entry: &FileEntry) {
+        self.skip(entry_algorithm(archive, entry), entry.decompressed_size);
+    }
+
+    /// Returns the decompressed size of the data left to extract.
+    pub fn remaining_bytes(&self) -> u64 {
+        self.algorithms
+            .iter()
+            .map(|x| x.total_bytes.saturating_sub(x.done_bytes))
+            .sum()
+    }
+
+    /// Returns the fraction of data extracted so far, between 0.0 and 1.0.
+    pub fn progress(&self) -> f64 {
+        let total: u64 = self.algorithms.iter().map(|x| x.total_bytes).sum();
+        if total == 0 {
+            return 1.0;
+        }
+
+        let done: u64 = self
+            .algorithms
+            .iter()
+            .map(|x| x.done_bytes.min(x.total_bytes))
+            .sum();
+        done as f64 / total as f64
+    }
+
+    /// Estimates the time left to extract the remaining data.
+    ///
+    /// # Returns
+    ///
+    /// The estimate, or [`None`] if nothing has been measured yet.
+    pub fn estimated_remaining(&self) -> Option<Duration> {
+        let measured_bytes: u64 = self.algorithms.iter().map(|x| x.measured_bytes).sum();
+        let measured_time: Duration = self.algorithms.iter().map(|x| x.measured_time).sum();
+        let average = bytes_per_second(measured_bytes, measured_time)?;
+
+        let mut seconds = 0.0;
+        for progress in &self.algorithms {
+            let remaining = progress.total_bytes.saturating_sub(progress.done_bytes);
+            let throughput = bytes_per_second(progress.measured_bytes, progress.measured_time)
+                .unwrap_or(average);
+            seconds += remaining as f64 / throughput;
+        }
+
+        Some(Duration::from_secs_f64(seconds))
+    }
+
+    fn get_or_insert(&mut self, algorithm: CompressionPreference) -> &mut AlgorithmProgress {
+        let index = match self
+            .algorithms
+            .iter()
+            .position(|x| x.algorithm == algorithm)
+        {
+            Some(index) => index,
+            None => {
+                self.algorithms.push(AlgorithmProgress {
+                    algorithm,
+                    total_bytes: 0,
+                    done_bytes: 0,
+                    measured_bytes: 0,
+                    measured_time: Duration::ZERO,
+                });
+                self.algorithms.len() - 1
+            }
+        };
+
+        &mut self.algorithms[index]
+    }
+}
+
+fn entry_algorithm(archive: &NxArchiveReader<'_>, entry: &FileEntry) -> CompressionPreference {
+    archive
+        .table_of_contents()
+        .block_compressions
+        .get(entry.first_block_index as usize)
+        .copied()
+        .unwrap_or(CompressionPreference::Copy)
+}
+
+fn bytes_per_second(num_bytes: u64, elapsed: Duration) -> Option<f64> {
+    if num_bytes == 0 || elapsed.is_zero() {
+        return None;
+    }
+
+    Some(num_bytes as f64 / elapsed.as_secs_f64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_per_algorithm() {
+        let mut estimator = ExtractionEstimator::new();
+        estimator.add(CompressionPreference::ZStandard, 3_000);
+        estimator.add(CompressionPreference::Copy, 10_000);
+        assert_eq!(estimator.estimated_remaining(), None);
+
+        // ZStandard at 1000 B/s, Copy at 10000 B/s
+        estimator.record(
+            CompressionPreference::ZStandard,
+            1_000,
+            Duration::from_secs(1),
+        );
+        estimator.record(
+            CompressionPreference::Copy,
+            5_000,
+            Duration::from_millis(500),
+        );
+
+        assert_eq!(estimator.remaining_bytes(), 7_000);
+        assert_eq!(estimator.progress(), 6_000.0 / 13_000.0);
+        let remaining = estimator.estimated_remaining().unwrap();
+        assert_eq!(remaining.as_millis(), 2_500);
+    }
+
+    #[test]
+    fn unmeasured_algorithms_use_average() {
+        let mut estimator = ExtractionEstimator::new();
+        estimator.add(CompressionPreference::ZStandard, 2_000);
+        estimator.add(CompressionPreference::Lz4, 2_000);
+        estimator.skip(CompressionPreference::Lz4, 1_000);
+        estimator.record(
+            CompressionPreference::ZStandard,
+            1_000,
+            Duration::from_secs(1),
+        );
+
+        let remaining = estimator.estimated_remaining().unwrap();
+        assert_eq!(remaining.as_millis(), 2_000);
+    }
+}
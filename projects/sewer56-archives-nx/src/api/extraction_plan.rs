@@ -0,0 +1,399 @@
+use crate::api::{
+    archive_reader::NxArchiveReader,
+    directory_sync::{is_safe_relative_path, DirectorySyncError, ExtractOptions},
+    enums::ExtractConflictPolicy,
+};
+use crate::headers::managed::FileEntry;
+use crate::prelude::*;
+use alloc::string::String;
+use hashbrown::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+
+/// The operations [`extract_to_dir`] would perform, without extracting anything.
+///
+/// Returned by [`plan_extraction`]. Useful for showing a confirmation screen
+/// (e.g. in an installer) before touching the target directory.
+///
+/// [`extract_to_dir`]: crate::api::directory_sync::extract_to_dir
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ExtractionPlan {
+    /// The files of the archive, in the order they would be extracted.
+    pub files: Vec<PlannedExtraction>,
+
+    /// Paths, relative to the target directory, of the files which would be deleted
+    /// because they are not in the archive. Empty unless [`ExtractOptions::delete_extra_files`] is set.
+    pub deleted_files: Vec<String>,
+}
+
+/// What would happen to a single file of the archive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedExtraction {
+    /// Path of the file within the archive.
+    pub relative_path: String,
+
+    /// Where the file would be written.
+    pub destination: PathBuf,
+
+    /// Size of the file in the archive.
+    pub file_size: u64,
+
+    /// Size of the file already at [`Self::destination`], if any.
+    pub existing_size: Option<u64>,
+
+    /// What would be done with the file.
+    pub action: PlannedAction,
+}
+
+/// What would be done with a file of the archive, after applying [`ExtractOptions::on_conflict`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedAction {
+    /// The file does not exist yet, and would be written.
+    Create,
+
+    /// The file exists, and would be replaced.
+    Overwrite,
+
+    /// The file exists, and would be kept, as per [`ExtractConflictPolicy::Skip`].
+    Skip,
+
+    /// The file exists, and already matches the archive, so would be left untouched.
+    Unchanged,
+
+    /// The file exists, and extraction would fail, as per [`ExtractConflictPolicy::Error`].
+    Conflict,
+}
+
+impl ExtractionPlan {
+    /// Returns true if extracting would fail, because a file conflicts
+    /// with [`ExtractConflictPolicy::Error`].
+    pub fn would_fail(&self) -> bool {
+        self.files
+            .iter()
+            .any(|x| x.action == PlannedAction::Conflict)
+    }
+
+    /// Returns the total size of the files which would be written.
+    pub fn bytes_to_write(&self) -> u64 {
+        self.files
+            .iter()
+            .filter(|x| matches!(x.action, PlannedAction::Create | PlannedAction::Overwrite))
+            .map(|x| x.file_size)
+            .sum()
+    }
+
+    /// Returns the additional disk space needed to extract, i.e. the size of new files,
+    /// plus the growth of overwritten files. Space freed by overwriting files with smaller
+    /// ones, or by deleting files, is not subtracted.
+    pub fn required_disk_space(&self) -> u64 {
+        self.files
+            .iter()
+            .map(|x| match x.action {
+                PlannedAction::Create => x.file_size,
+                PlannedAction::Overwrite => {
+                    x.file_size.saturating_sub(x.existing_size.unwrap_or(0))
+                }
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Returns the planned extraction of a file, by its path within the archive.
+    pub fn file(&self, relative_path: &str) -> Option<&PlannedExtraction> {
+        self.files.iter().find(|x| x.relative_path == relative_path)
+    }
+}
+
+/// Lists what [`extract_to_dir`] would do with the given options, without writing
+/// anything or reading any block data.
+///
+/// # Arguments
+///
+/// * `archive` - The archive to extract from.
+/// * `target_dir` - The directory to extract to. Does not have to exist.
+/// * `options` - What to do with files which already exist, and files not in the archive.
+///
+/// # Remarks
+///
+/// With [`ExtractConflictPolicy::OverwriteIfDifferentHash`], existing files of the same size
+/// are read and hashed to find out whether they changed. If the archive has no file hashes,
+/// these are planned as [`PlannedAction::Overwrite`], since finding out would require
+/// decompressing the archive's copy.
+///
+/// [`extract_to_dir`]: crate::api::directory_sync::extract_to_dir
+pub fn plan_extraction(
+    archive: &NxArchiveReader<'_>,
+    target_dir: impl AsRef<Path>,
+    options: &ExtractOptions,
+) -> Result<ExtractionPlan, DirectorySyncError> {
+    let target_dir = target_dir.as_ref();
+    let mut plan = ExtractionPlan::default();
+    let mut archive_paths = HashSet::new();
+    for entry in archive.entries() {
+        let Some(path) = archive.file_path(entry)? else {
+            continue;
+        };
+
+        if !is_safe_relative_path(path) {
+            return Err(DirectorySyncError::UnsafePath(String::from(path)));
+        }
+
+        archive_paths.insert(path);
+        let destination = target_dir.join(path);
+        let existing_size = fs::symlink_metadata(&destination).ok().map(|x| x.len());
+        let action = match (existing_size, options.on_conflict) {
+            (None, _) => PlannedAction::Create,
+            (Some(_), ExtractConflictPolicy::Overwrite) => PlannedAction::Overwrite,
+            (Some(_), ExtractConflictPolicy::Skip) => PlannedAction::Skip,
+            (Some(_), ExtractConflictPolicy::Error) => PlannedAction::Conflict,
+            (Some(_), ExtractConflictPolicy::OverwriteIfDifferentHash) => {
+                match is_unchanged(archive, entry, &destination)? {
+                    true => PlannedAction::Unchanged,
+                    false => PlannedAction::Overwrite,
+                }
+            }
+        };
+
+        plan.files.push(PlannedExtraction {
+            relative_path: String::from(path),
+            destination,
+            file_size: entry.decompressed_size,
+            existing_size,
+            action,
+        });
+    }
+
+    if options.delete_extra_files && target_dir.is_dir() {
+        find_extra(
+            target_dir,
+            target_dir,
+            &archive_paths,
+            &mut plan.deleted_files,
+        )?;
+    }
+
+    Ok(plan)
+}
+
+/// Returns the additional disk space needed to extract an archive into a directory,
+/// computed from the sizes in the table of contents and the sizes of existing files,
+/// without reading any file or block data.
+///
+/// # Arguments
+///
+/// * `archive` - The archive to extract from.
+/// * `target_dir` - The directory to extract to. Does not have to exist.
+/// * `options` - What to do with files which already exist.
+///
+/// # Remarks
+///
+/// New files count with their full size, overwritten files with how much they grow.
+/// Files are written in place, so no temporary space is needed on top of this.
+/// Space freed by deleting extra files is not subtracted.
+pub fn required_disk_space(
+    archive: &NxArchiveReader<'_>,
+    target_dir: impl AsRef<Path>,
+    options: &ExtractOptions,
+) -> Result<u64, DirectorySyncError> {
+    let target_dir = target_dir.as_ref();
+    let mut required = 0;
+    for entry in archive.entries() {
+        let Some(path) = archive.file_path(entry)? else {
+            continue;
+        };
+
+        if !is_safe_relative_path(path) {
+            return Err(DirectorySyncError::UnsafePath(String::from(path)));
+        }
+
+        let existing_size = fs::symlink_metadata(target_dir.join(path))
+            .ok()
+            .map(|x| x.len());
+        required += match (existing_size, options.on_conflict) {
+            (None, _) => entry.decompressed_size,
+            (Some(_), ExtractConflictPolicy::Skip | ExtractConflictPolicy::Error) => 0,
+            (Some(existing_size), _) => entry.decompressed_size.saturating_sub(existing_size),
+        };
+    }
+
+    Ok(required)
+}
+
+/// Checks the volume of a directory has enough free space to extract an archive into it,
+/// see [`required_disk_space`].
+///
+/// # Arguments
+///
+/// * `archive` - The archive to extract from.
+/// * `target_dir` - The directory to extract to. Does not have to exist; the free space
+///   of the volume of its closest existing parent is checked instead.
+/// * `options` - What to do with files which already exist.
+///
+/// # Errors
+///
+/// Returns [`DirectorySyncError::InsufficientSpace`] if there is not enough free space.
+pub fn check_free_space(
+    archive: &NxArchiveReader<'_>,
+    target_dir: impl AsRef<Path>,
+    options: &ExtractOptions,
+) -> Result<(), DirectorySyncError> {
+    let target_dir = target_dir.as_ref();
+    let required = required_disk_space(archive, target_dir, options)?;
+    if required == 0 {
+        return Ok(());
+    }
+
+    let existing_dir = target_dir
+        .ancestors()
+        .find(|x| x.is_dir())
+        .unwrap_or(Path::new("."));
+    ensure_free_space(required, fs2::available_space(existing_dir)?)
+}
+
+fn ensure_free_space(required: u64, available: u64) -> Result<(), DirectorySyncError> {
+    match required <= available {
+        true => Ok(()),
+        false => Err(DirectorySyncError::InsufficientSpace {
+            required,
+            available,
+        }),
+    }
+}
+
+/// Checks whether the file at `destination` matches the archive, using only the stored hash.
+fn is_unchanged(
+    archive: &NxArchiveReader<'_>,
+    entry: &FileEntry,
+    destination: &Path,
+) -> Result<bool, DirectorySyncError> {
+    if !archive.has_file_hashes() {
+        return Ok(false);
+    }
+
+    match fs::metadata(destination) {
+        Ok(metadata) if metadata.is_file() && metadata.len() == entry.decompressed_size => {}
+        _ => return Ok(false),
+    }
+
+    Ok(archive.hash_data(&fs::read(destination)?) == entry.hash)
+}
+
+/// Lists the files under `current_dir` which are not in `keep`.
+fn find_extra(
+    current_dir: &Path,
+    base_dir: &Path,
+    keep: &HashSet<&str>,
+    extra: &mut Vec<String>,
+) -> Result<(), DirectorySyncError> {
+    for entry in fs::read_dir(current_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            find_extra(&path, base_dir, keep, extra)?;
+            continue;
+        }
+
+        let relative_path = path
+            .strip_prefix(base_dir)
+            .map(|x| x.to_string_lossy().replace(MAIN_SEPARATOR, "/"))
+            .unwrap_or_default();
+        if !keep.contains(relative_path.as_str()) {
+            extra.push(relative_path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{enums::CompressionPreference, filedata::FromSliceReferenceProvider};
+    use crate::unsize_box2;
+    use crate::utilities::tests::archive_for_testing::*;
+    use tempfile::tempdir;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn plans_without_writing() {
+        let archive = create_test_archive(
+            4096,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new("data/b.txt", b"Nx archives!"),
+                TestFile::new("c.txt", b"Same"),
+            ]],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), b"Hi").unwrap();
+        fs::write(dir.path().join("c.txt"), b"Same").unwrap();
+        fs::write(dir.path().join("extra.txt"), b"Stale").unwrap();
+
+        let options = ExtractOptions {
+            on_conflict: ExtractConflictPolicy::OverwriteIfDifferentHash,
+            delete_extra_files: true,
+            check_free_space: true,
+        };
+        let plan = plan_extraction(&reader, dir.path(), &options).unwrap();
+        assert_eq!(plan.file("a.txt").unwrap().action, PlannedAction::Overwrite);
+        assert_eq!(plan.file("c.txt").unwrap().action, PlannedAction::Unchanged);
+        let created = plan.file("data/b.txt").unwrap();
+        assert_eq!(created.action, PlannedAction::Create);
+        assert_eq!(created.destination, dir.path().join("data/b.txt"));
+        assert_eq!(plan.deleted_files.as_slice(), &[String::from("extra.txt")]);
+        assert_eq!(plan.bytes_to_write(), 5 + 12);
+        assert_eq!(plan.required_disk_space(), 3 + 12);
+        assert!(!plan.would_fail());
+        assert!(!dir.path().join("data").exists());
+
+        let options = ExtractOptions {
+            on_conflict: ExtractConflictPolicy::Error,
+            ..ExtractOptions::default()
+        };
+        let plan = plan_extraction(&reader, dir.path(), &options).unwrap();
+        assert!(plan.would_fail());
+        assert!(plan.deleted_files.is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn checks_free_space() {
+        let archive = create_test_archive(
+            4096,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new("data/b.txt", b"Nx archives!"),
+            ]],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), b"Hi").unwrap();
+
+        let mut options = ExtractOptions::default();
+        assert_eq!(
+            required_disk_space(&reader, dir.path(), &options).unwrap(),
+            3 + 12
+        );
+        options.on_conflict = ExtractConflictPolicy::Skip;
+        assert_eq!(
+            required_disk_space(&reader, dir.path(), &options).unwrap(),
+            12
+        );
+        check_free_space(&reader, dir.path().join("missing/dir"), &options).unwrap();
+
+        assert!(matches!(
+            ensure_free_space(100, 99),
+            Err(DirectorySyncError::InsufficientSpace {
+                required: 100,
+                available: 99
+            })
+        ));
+    }
+}
@@ -3,6 +3,7 @@ use crate::api::{enums::CompressionPreference, traits::*};
 use crate::headers::managed::FileEntry;
 use crate::prelude::*;
 use crate::utilities::compression;
+use crate::utilities::memory_audit::{default_slice, InternalAlloc};
 use alloc::sync::Arc;
 use core::cell::UnsafeCell;
 use once_cell::sync::OnceCell;
@@ -29,7 +30,7 @@ use once_cell::sync::OnceCell;
 /// This maxes maximum memory size bound by [`MAX_BLOCK_SIZE`], so we can represent this as [`u32`].
 pub struct LazyDecompressedSolidNxBlock {
     /// Raw decompressed data, lazily initialized when needed
-    data: OnceCell<Box<[u8]>>,
+    data: OnceCell<Box<[u8], InternalAlloc>>,
 
     /// Provides access to the original Nx archive
     source_nx_data_provider: Arc<dyn InputDataProvider + Send + Sync>,
@@ -118,7 +119,7 @@ impl LazyDecompressedSolidNxBlock {
             .get_or_try_init(|| {
                 // Do decompression
                 let num_bytes = unsafe { (*self.num_bytes_to_decompress.get()) as usize };
-                let mut decompressed = unsafe { Box::new_uninit_slice(num_bytes).assume_init() };
+                let mut decompressed = default_slice(num_bytes);
 
                 // Get compressed data
                 let compressed = self
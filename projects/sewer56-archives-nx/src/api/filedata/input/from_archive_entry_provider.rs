@@ -0,0 +1,86 @@
+use crate::api::{
+    archive_reader::{ArchiveReadError, NxArchiveReader},
+    traits::*,
+};
+use crate::headers::managed::FileEntry;
+use crate::{prelude::*, unsize_box2};
+use alloc::string::ToString;
+
+/// Provides the data of a file stored in another Nx archive.
+///
+/// The file is decompressed on demand, each time data is requested;
+/// only the blocks (chunks) overlapping the requested range are decompressed.
+pub struct FromArchiveEntryProvider<'a, 'r> {
+    reader: &'a NxArchiveReader<'r>,
+    entry: FileEntry,
+}
+
+impl<'a, 'r> FromArchiveEntryProvider<'a, 'r> {
+    /// Creates a provider for a file in an archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The archive containing the file.
+    /// * `entry` - Entry of the file, from [`NxArchiveReader::entries`].
+    pub fn new(reader: &'a NxArchiveReader<'r>, entry: FileEntry) -> Self {
+        Self { reader, entry }
+    }
+}
+
+impl InputDataProvider for FromArchiveEntryProvider<'_, '_> {
+    fn get_file_data<'b>(
+        &'b self,
+        start: u64,
+        length: u64,
+    ) -> Result<Box<dyn ReadOnlyFileData + 'b>, FileProviderError> {
+        let data = self
+            .reader
+            .read_entry_range(&self.entry, start, length)
+            .map_err(|e| match e {
+                ArchiveReadError::FileProvider(e) => e,
+                ArchiveReadError::Decompression(e) => FileProviderError::NxDecompressionError(e),
+                e => FileProviderError::ThirdPartyError(e.to_string()),
+            })?;
+
+        Ok(unsize_box2!(Box::new(ArchiveEntryData { data })))
+    }
+}
+
+/// Decompressed data of a file from another archive.
+pub struct ArchiveEntryData {
+    data: Vec<u8>,
+}
+
+impl ReadOnlyFileData for ArchiveEntryData {
+    fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::enums::CompressionPreference;
+    use crate::api::filedata::FromSliceReferenceProvider;
+    use crate::utilities::tests::archive_for_testing::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reads_file_from_archive() {
+        let archive = create_test_archive(
+            4096,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new("b.txt", b"Nx archives!"),
+            ]],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+
+        let entry = *reader.find_entry("b.txt").unwrap().unwrap();
+        let provider = FromArchiveEntryProvider::new(&reader, entry);
+        assert_eq!(provider.get_file_data(3, 8).unwrap().data(), b"archives");
+    }
+}
@@ -1,21 +1,214 @@
 use crate::api::traits::*;
+use crate::utilities::memory_audit::{default_slice, InternalAlloc};
 use crate::{prelude::*, unsize_box2};
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use hashbrown::HashMap;
 use lightweight_mmap::handles::ReadOnlyFileHandle;
 use lightweight_mmap::mmap::ReadOnlyMmap;
 use nanokit::string_concat_unsafe::*;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::*;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// How a [`FromFilePathProvider`] manages the handle of its file.
+///
+/// Keeping every file open is fastest, but packing hundreds of thousands of small files
+/// that way can exhaust the handle limit of the process (e.g. `ulimit -n`). The other
+/// policies trade this for extra `open` calls.
+#[derive(Debug, Clone, Default)]
+pub enum FileHandlePolicy {
+    /// Opens the file when the provider is created, and keeps it open until the provider
+    /// is dropped. Reads are memory mapped.
+    #[default]
+    KeepOpen,
+
+    /// Opens the file on every read, and closes it right after.
+    ReopenEachTime,
+
+    /// Keeps files open in a pool shared between providers, closing the least recently
+    /// opened file when the pool is full.
+    PooledWithLimit(Arc<FileHandlePool>),
+}
+
+impl FileHandlePolicy {
+    /// Creates a [`Self::PooledWithLimit`] policy with a new pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Maximum number of files kept open by the pool. At least 1.
+    pub fn pooled(limit: usize) -> Self {
+        Self::PooledWithLimit(Arc::new(FileHandlePool::new(limit)))
+    }
+}
+
+/// File handles shared between providers using [`FileHandlePolicy::PooledWithLimit`].
+///
+/// # Remarks
+///
+/// A handle closed by the pool while another thread is still reading from it stays open
+/// until that read completes, so the limit can briefly be exceeded by the number of
+/// threads reading at the same time.
+#[derive(Debug)]
+pub struct FileHandlePool {
+    /// Maximum number of files kept open.
+    limit: usize,
+
+    /// The open files.
+    state: Mutex<PoolState>,
+
+    /// ID assigned to the next provider using the pool.
+    next_id: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    /// Open file of each provider, by provider ID.
+    handles: HashMap<u64, Arc<Mutex<File>>>,
+
+    /// IDs of the providers with open files, in the order they were opened.
+    /// May contain providers which were since dropped.
+    order: VecDeque<u64>,
+}
+
+impl FileHandlePool {
+    /// Creates an empty pool.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - Maximum number of files kept open. At least 1.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit: limit.max(1),
+            state: Mutex::new(PoolState::default()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the maximum number of files kept open.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Returns the number of files currently kept open by the pool.
+    pub fn open_files(&self) -> usize {
+        self.state.lock().map(|x| x.handles.len()).unwrap_or(0)
+    }
+
+    fn register(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn acquire(&self, id: u64, path: &str) -> Result<Arc<Mutex<File>>, FileProviderError> {
+        let lock_error = |_| FileProviderError::FailedToAcquireLock();
+        if let Some(file) = self.state.lock().map_err(lock_error)?.handles.get(&id) {
+            return Ok(file.clone());
+        }
+
+        // Open outside the lock, so other providers can use their files meanwhile.
+        let file = Arc::new(Mutex::new(File::open(path)?));
+        let mut state = self.state.lock().map_err(lock_error)?;
+        while state.handles.len() >= self.limit {
+            let Some(oldest) = state.order.pop_front() else {
+                break;
+            };
+            state.handles.remove(&oldest);
+        }
+
+        state.handles.insert(id, file.clone());
+        state.order.push_back(id);
+        Ok(file)
+    }
+
+    fn release(&self, id: u64) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        state.handles.remove(&id);
+        if state.order.len() > self.limit * 2 {
+            let PoolState { handles, order } = &mut *state;
+            order.retain(|x| handles.contains_key(x));
+        }
+    }
+}
 
 /// A struct that implements file data provider functionality for files on disk.
 /// Each provider instance corresponds to a single file and is accessed by only one thread at a time.
 pub struct FromFilePathProvider {
-    file_handle: ReadOnlyFileHandle,
+    path: String,
+    source: FileSource,
+
+    /// Size and modification time of the file when the provider was created,
+    /// used to detect changes. [`None`] if the metadata could not be read.
+    stamp: Option<FileStamp>,
+}
+
+/// Where a [`FromFilePathProvider`] gets its file handle from, see [`FileHandlePolicy`].
+enum FileSource {
+    Open(ReadOnlyFileHandle),
+    Reopen,
+    Pooled { id: u64, pool: Arc<FileHandlePool> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    fn read(path: &str) -> Result<Self, FileProviderError> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
 }
 
 impl FromFilePathProvider {
-    /// Creates a new provider for the given file path
+    /// Creates a new provider for the given file path, keeping the file open
     pub fn new(path: &str) -> Result<Self, FileProviderError> {
         let file_handle = ReadOnlyFileHandle::open(path)?;
-        Ok(Self { file_handle })
+        Ok(Self {
+            path: String::from(path),
+            source: FileSource::Open(file_handle),
+            stamp: FileStamp::read(path).ok(),
+        })
+    }
+
+    /// Creates a new provider for the given file path, managing the file's handle
+    /// as per the given policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file on disk.
+    /// * `policy` - When the file is opened and closed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file does not exist, or can't be opened with [`FileHandlePolicy::KeepOpen`].
+    pub fn with_policy(path: &str, policy: &FileHandlePolicy) -> Result<Self, FileProviderError> {
+        let source = match policy {
+            FileHandlePolicy::KeepOpen => return Self::new(path),
+            FileHandlePolicy::ReopenEachTime => FileSource::Reopen,
+            FileHandlePolicy::PooledWithLimit(pool) => FileSource::Pooled {
+                id: pool.register(),
+                pool: pool.clone(),
+            },
+        };
+
+        let stamp = FileStamp::read(path)?;
+        Ok(Self {
+            path: String::from(path),
+            source,
+            stamp: Some(stamp),
+        })
     }
 
     /// Creates a new provider by combining a directory path and file name
@@ -32,7 +225,12 @@ impl FromFilePathProvider {
 
     /// Returns the size of the file behind the provider
     pub fn file_size(&self) -> Result<i64, FileProviderError> {
-        self.file_handle.size().map_err(|op| op.into())
+        match &self.source {
+            FileSource::Open(handle) => handle.size().map_err(|op| op.into()),
+            FileSource::Reopen | FileSource::Pooled { .. } => {
+                Ok(fs::metadata(&self.path)?.len() as i64)
+            }
+        }
     }
 }
 
@@ -42,11 +240,63 @@ impl InputDataProvider for FromFilePathProvider {
         start: u64,
         length: u64,
     ) -> Result<Box<dyn ReadOnlyFileData + 'a>, FileProviderError> {
-        let mapping = ReadOnlyMmap::new(&self.file_handle, start, length as usize)?;
-        Ok(unsize_box2!(Box::new(ReadOnlyMappedFileData::new(mapping))))
+        let data = match &self.source {
+            FileSource::Open(handle) => {
+                let mapping = ReadOnlyMmap::new(handle, start, length as usize)?;
+                return Ok(unsize_box2!(Box::new(ReadOnlyMappedFileData::new(mapping))));
+            }
+            FileSource::Reopen => read_file(&mut File::open(&self.path)?, start, length)?,
+            FileSource::Pooled { id, pool } => {
+                let file = pool.acquire(*id, &self.path)?;
+                let mut file = file
+                    .lock()
+                    .map_err(|_| FileProviderError::FailedToAcquireLock())?;
+                read_file(&mut file, start, length)?
+            }
+        };
+
+        Ok(unsize_box2!(Box::new(StreamData::new(data))))
+    }
+
+    /// Compares the size and modification time of the file with those recorded when
+    /// the provider was created.
+    ///
+    /// With [`FileHandlePolicy::KeepOpen`], a file replaced on disk (rather than rewritten
+    /// in place) is reported as changed, but reads still return the contents of the
+    /// original file, which was kept open; the returned size is that of the open file.
+    fn detect_change(&self) -> Result<Option<u64>, FileProviderError> {
+        let Some(stamp) = self.stamp else {
+            return Ok(None);
+        };
+
+        let current = FileStamp::read(&self.path)?;
+        let size = self.file_size()? as u64;
+        Ok((current != stamp || size != stamp.size).then_some(size))
     }
 }
 
+impl Drop for FromFilePathProvider {
+    fn drop(&mut self) {
+        if let FileSource::Pooled { id, pool } = &self.source {
+            pool.release(*id);
+        }
+    }
+}
+
+fn read_file(
+    file: &mut File,
+    start: u64,
+    length: u64,
+) -> Result<Box<[u8], InternalAlloc>, FileProviderError> {
+    file.seek(SeekFrom::Start(start))
+        .map_err(|_| FileProviderError::FailedToSeekStream(start))?;
+
+    let mut buffer = default_slice(length as usize);
+    file.read_exact(&mut buffer)
+        .map_err(|_| FileProviderError::FailedToReadFromStream(length, start))?;
+    Ok(buffer)
+}
+
 /// A struct that implements FileData trait for memory mapped regions of a file
 pub struct ReadOnlyMappedFileData<'a> {
     mapping: ReadOnlyMmap<'a>,
@@ -127,6 +377,75 @@ mod tests {
         assert_eq!(data.data(), b"World");
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_reopen_file_for_each_read() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(b"Hello, World!").unwrap();
+        temp_file.flush().unwrap();
+
+        let path = temp_file.path().to_str().unwrap();
+        let provider =
+            FromFilePathProvider::with_policy(path, &FileHandlePolicy::ReopenEachTime).unwrap();
+        assert_eq!(provider.file_size().unwrap(), 13);
+        assert_eq!(provider.get_file_data(7, 5).unwrap().data(), b"World");
+
+        let missing = FromFilePathProvider::with_policy(
+            "nonexistent_file.txt",
+            &FileHandlePolicy::ReopenEachTime,
+        );
+        assert!(matches!(missing.err(), Some(FileProviderError::IoError(_))));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn pool_limits_open_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let policy = FileHandlePolicy::pooled(2);
+        let FileHandlePolicy::PooledWithLimit(pool) = &policy else {
+            unreachable!();
+        };
+
+        let providers: Vec<FromFilePathProvider> = (0..4)
+            .map(|x| {
+                let path = temp_dir.path().join(format!("{x}.txt"));
+                write(&path, format!("File {x}")).unwrap();
+                FromFilePathProvider::with_policy(path.to_str().unwrap(), &policy).unwrap()
+            })
+            .collect();
+
+        for (x, provider) in providers.iter().enumerate() {
+            let data = provider.get_file_data(0, 6).unwrap();
+            assert_eq!(data.data(), format!("File {x}").as_bytes());
+            assert!(pool.open_files() <= 2);
+        }
+
+        // Files closed by the pool are opened again.
+        assert_eq!(providers[0].get_file_data(5, 1).unwrap().data(), b"0");
+        drop(providers);
+        assert_eq!(pool.open_files(), 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn detects_changed_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("test.txt");
+        write(&path, b"Hello").unwrap();
+
+        let path = path.to_str().unwrap();
+        let provider =
+            FromFilePathProvider::with_policy(path, &FileHandlePolicy::ReopenEachTime).unwrap();
+        assert_eq!(provider.detect_change().unwrap(), None);
+
+        write(path, b"Hello, World!").unwrap();
+        assert_eq!(provider.detect_change().unwrap(), Some(13));
+        assert_eq!(provider.get_file_data(7, 5).unwrap().data(), b"World");
+
+        remove_file(path).unwrap();
+        assert!(provider.detect_change().is_err());
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)]
     fn can_create_multiple_mappings() {
@@ -1,4 +1,5 @@
 use crate::api::traits::*;
+use crate::utilities::memory_audit::{default_slice, InternalAlloc};
 use crate::{prelude::*, unsize_box2};
 use std::io::{Read, Seek, SeekFrom};
 use std::sync::Mutex;
@@ -34,7 +35,7 @@ impl<T: Read + Seek + Send> InputDataProvider for FromStreamProvider<T> {
             .map_err(|_| FileProviderError::FailedToSeekStream(start))?;
 
         // Read the requested length
-        let mut buffer = unsafe { Box::new_uninit_slice(length as usize).assume_init() };
+        let mut buffer = default_slice(length as usize);
         stream
             .read_exact(&mut buffer)
             .map_err(|_| FileProviderError::FailedToReadFromStream(length, start))?;
@@ -45,7 +46,14 @@ impl<T: Read + Seek + Send> InputDataProvider for FromStreamProvider<T> {
 
 /// A struct that holds a chunk of data read from a stream
 pub struct StreamData {
-    data: Box<[u8]>,
+    data: Box<[u8], InternalAlloc>,
+}
+
+impl StreamData {
+    /// Creates a new instance holding data which was already read.
+    pub(crate) fn new(data: Box<[u8], InternalAlloc>) -> Self {
+        Self { data }
+    }
 }
 
 impl ReadOnlyFileData for StreamData {
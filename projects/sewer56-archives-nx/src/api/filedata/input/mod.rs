@@ -1,12 +1,22 @@
 pub mod existing_nx_block;
+pub mod from_archive_entry_provider;
 pub mod from_boxed_slice_provider;
 pub mod from_file_path_provider;
 pub mod from_slice_reference_provider;
 pub mod from_stream_provider;
+pub mod offset_provider;
+pub mod read_through_cache_provider;
+pub mod retrying_provider;
+pub mod spooled_stream_provider;
 
 // Prelude
 pub use existing_nx_block::*;
+pub use from_archive_entry_provider::*;
 pub use from_boxed_slice_provider::*;
 pub use from_file_path_provider::*;
 pub use from_slice_reference_provider::*;
 pub use from_stream_provider::*;
+pub use offset_provider::*;
+pub use read_through_cache_provider::*;
+pub use retrying_provider::*;
+pub use spooled_stream_provider::*;
@@ -0,0 +1,89 @@
+use crate::api::traits::*;
+use crate::prelude::*;
+
+/// Provides a range of the data of another provider, such that offset `0` corresponds
+/// to the start of the range.
+///
+/// This allows reading data embedded inside another file, e.g. an archive appended to a
+/// self-extracting executable or stored inside a game's package file, without copying it out.
+pub struct OffsetProvider<'a> {
+    inner: Box<dyn InputDataProvider + Send + Sync + 'a>,
+    offset: u64,
+    length: u64,
+}
+
+impl<'a> OffsetProvider<'a> {
+    /// Creates a new [`OffsetProvider`].
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - Provides the data of the whole containing file.
+    /// * `offset` - Offset of the range within the containing file.
+    /// * `length` - Length of the range. Requests past the end of the range fail.
+    pub fn new(
+        inner: Box<dyn InputDataProvider + Send + Sync + 'a>,
+        offset: u64,
+        length: u64,
+    ) -> Self {
+        Self {
+            inner,
+            offset,
+            length,
+        }
+    }
+
+    /// Returns the offset of the range within the containing file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the length of the range.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns true if the range is empty.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+}
+
+impl InputDataProvider for OffsetProvider<'_> {
+    fn get_file_data<'b>(
+        &'b self,
+        start: u64,
+        length: u64,
+    ) -> Result<Box<dyn ReadOnlyFileData + 'b>, FileProviderError> {
+        match start.checked_add(length) {
+            Some(end) if end <= self.length => {
+                self.inner.get_file_data(self.offset + start, length)
+            }
+            _ => Err(FileProviderError::OutOfRange(start, length)),
+        }
+    }
+
+    fn is_resident(&self, start: u64, length: u64) -> bool {
+        self.inner.is_resident(self.offset + start, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::filedata::FromSliceReferenceProvider;
+    use crate::unsize_box2;
+
+    #[test]
+    fn reads_relative_to_offset() {
+        let data = [10, 20, 30, 40, 50, 60];
+        let inner = Box::new(FromSliceReferenceProvider::new(&data));
+        let provider = OffsetProvider::new(unsize_box2!(inner), 2, 3);
+
+        assert_eq!(provider.get_file_data(0, 2).unwrap().data(), &[30, 40]);
+        assert_eq!(provider.get_file_data(1, 2).unwrap().data(), &[40, 50]);
+        assert!(matches!(
+            provider.get_file_data(2, 2),
+            Err(FileProviderError::OutOfRange(2, 2))
+        ));
+    }
+}
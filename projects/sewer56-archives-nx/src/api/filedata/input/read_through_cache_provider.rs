@@ -0,0 +1,344 @@
+use crate::api::traits::*;
+use crate::utilities::memory_audit::{default_slice, InternalAlloc};
+use crate::{prelude::*, unsize_box2};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Default amount of data fetched from the remote at once.
+/// This is a multiple of the block alignment of Nx archives.
+pub const DEFAULT_CACHE_PAGE_SIZE: u32 = 65536;
+
+/// Extension appended to the path of the local copy, for the file tracking which pages are present.
+pub const CACHE_SIDECAR_EXTENSION: &str = "nxcache";
+
+/// Size of the sidecar header; page size (u32) followed by file size (u64).
+const SIDECAR_HEADER_SIZE: usize = 12;
+
+/// Provides data from a remote source (e.g. an HTTP range reader), keeping a local copy
+/// of everything fetched, so every byte is only downloaded once.
+///
+/// The local copy is a sparse file of the full size, filled in page by page as data is read.
+/// Which pages are present is tracked in a sidecar file next to it (see [`CACHE_SIDECAR_EXTENSION`]),
+/// so the cache survives restarts; reopening it with the same size resumes where it left off.
+///
+/// Used as the provider of an [`NxArchiveReader`], this gives lazy downloads of archives;
+/// only the blocks which are read are fetched.
+///
+/// [`NxArchiveReader`]: crate::api::archive_reader::NxArchiveReader
+pub struct ReadThroughCacheProvider<'a> {
+    /// Provides the data which is not cached yet.
+    remote: Box<dyn InputDataProvider + Send + Sync + 'a>,
+
+    /// Size of the remote data.
+    file_size: u64,
+
+    /// Size of each page fetched from the remote.
+    page_size: u64,
+
+    /// Local copy, and which of its pages are present.
+    state: Mutex<CacheState>,
+}
+
+struct CacheState {
+    file: File,
+    sidecar_path: PathBuf,
+    /// Sidecar header, followed by one bit per page.
+    sidecar: Vec<u8>,
+}
+
+impl<'a> ReadThroughCacheProvider<'a> {
+    /// Creates a provider with the default page size.
+    ///
+    /// # Arguments
+    ///
+    /// * `remote` - Provides the data which is not cached yet.
+    /// * `file_size` - Size of the remote data.
+    /// * `cache_path` - Path of the local copy. Created if it does not exist.
+    pub fn new(
+        remote: Box<dyn InputDataProvider + Send + Sync + 'a>,
+        file_size: u64,
+        cache_path: impl AsRef<Path>,
+    ) -> Result<Self, FileProviderError> {
+        Self::new_with_page_size(remote, file_size, cache_path, DEFAULT_CACHE_PAGE_SIZE)
+    }
+
+    /// Creates a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `remote` - Provides the data which is not cached yet.
+    /// * `file_size` - Size of the remote data.
+    /// * `cache_path` - Path of the local copy. Created if it does not exist.
+    /// * `page_size` - Amount of data fetched from the remote at once.
+    ///
+    /// # Remarks
+    ///
+    /// If an existing local copy was created with a different page or file size,
+    /// it is discarded.
+    pub fn new_with_page_size(
+        remote: Box<dyn InputDataProvider + Send + Sync + 'a>,
+        file_size: u64,
+        cache_path: impl AsRef<Path>,
+        page_size: u32,
+    ) -> Result<Self, FileProviderError> {
+        let page_size = page_size.max(1);
+        let cache_path = cache_path.as_ref();
+        let mut sidecar_path = cache_path.as_os_str().to_owned();
+        sidecar_path.push(".");
+        sidecar_path.push(CACHE_SIDECAR_EXTENSION);
+        let sidecar_path = PathBuf::from(sidecar_path);
+
+        let mut header = [0u8; SIDECAR_HEADER_SIZE];
+        header[..4].copy_from_slice(&page_size.to_le_bytes());
+        header[4..].copy_from_slice(&file_size.to_le_bytes());
+        let page_count = file_size.div_ceil(page_size as u64) as usize;
+        let sidecar_size = SIDECAR_HEADER_SIZE + page_count.div_ceil(8);
+
+        let mut sidecar = match fs::read(&sidecar_path) {
+            Ok(existing) if existing.len() == sidecar_size && existing.starts_with(&header) => {
+                Vec::from(existing.as_slice())
+            }
+            _ => Vec::new(),
+        };
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(sidecar.is_empty())
+            .open(cache_path)?;
+        file.set_len(file_size)?;
+
+        if sidecar.is_empty() {
+            sidecar.extend_from_slice(&header);
+            sidecar.resize(sidecar_size, 0);
+            fs::write(&sidecar_path, &sidecar)?;
+        }
+
+        Ok(Self {
+            remote,
+            file_size,
+            page_size: page_size as u64,
+            state: Mutex::new(CacheState {
+                file,
+                sidecar_path,
+                sidecar,
+            }),
+        })
+    }
+
+    /// Returns the number of bytes available locally.
+    pub fn cached_bytes(&self) -> Result<u64, FileProviderError> {
+        let state = self.lock()?;
+        let page_count = self.file_size.div_ceil(self.page_size);
+        Ok((0..page_count)
+            .filter(|page| state.has_page(*page))
+            .map(|page| self.page_length(page))
+            .sum())
+    }
+
+    /// Returns true if all data is available locally.
+    pub fn is_complete(&self) -> Result<bool, FileProviderError> {
+        Ok(self.cached_bytes()? == self.file_size)
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, CacheState>, FileProviderError> {
+        self.state
+            .lock()
+            .map_err(|_| FileProviderError::FailedToAcquireLock())
+    }
+
+    fn page_length(&self, page: u64) -> u64 {
+        (self.file_size - page * self.page_size).min(self.page_size)
+    }
+}
+
+impl CacheState {
+    fn has_page(&self, page: u64) -> bool {
+        let bits = &self.sidecar[SIDECAR_HEADER_SIZE..];
+        bits[(page / 8) as usize] & (1 << (page % 8)) != 0
+    }
+
+    fn set_page(&mut self, page: u64) {
+        let bits = &mut self.sidecar[SIDECAR_HEADER_SIZE..];
+        bits[(page / 8) as usize] |= 1 << (page % 8);
+    }
+}
+
+impl InputDataProvider for ReadThroughCacheProvider<'_> {
+    fn get_file_data<'b>(
+        &'b self,
+        start: u64,
+        length: u64,
+    ) -> Result<Box<dyn ReadOnlyFileData + 'b>, FileProviderError> {
+        if start + length > self.file_size {
+            return Err(FileProviderError::FailedToReadFromStream(length, start));
+        }
+
+        let mut state = self.lock()?;
+        if length > 0 {
+            // Fetch the missing pages.
+            let mut fetched = false;
+            for page in start / self.page_size..=(start + length - 1) / self.page_size {
+                if state.has_page(page) {
+                    continue;
+                }
+
+                let page_start = page * self.page_size;
+                let data = self
+                    .remote
+                    .get_file_data(page_start, self.page_length(page))?;
+                state.file.seek(SeekFrom::Start(page_start))?;
+                state.file.write_all(data.data())?;
+                state.set_page(page);
+                fetched = true;
+            }
+
+            // Data first, then the bitmap; so a crash never marks missing data as present.
+            if fetched {
+                state.file.flush()?;
+                fs::write(&state.sidecar_path, &state.sidecar)?;
+            }
+        }
+
+        let mut buffer = default_slice(length as usize);
+        state.file.seek(SeekFrom::Start(start))?;
+        state
+            .file
+            .read_exact(&mut buffer)
+            .map_err(|_| FileProviderError::FailedToReadFromStream(length, start))?;
+
+        Ok(unsize_box2!(Box::new(CachedData { data: buffer })))
+    }
+
+    fn is_resident(&self, start: u64, length: u64) -> bool {
+        if length == 0 {
+            return true;
+        }
+        if start.saturating_add(length) > self.file_size {
+            return false;
+        }
+
+        let Ok(state) = self.lock() else {
+            return false;
+        };
+        (start / self.page_size..=(start + length - 1) / self.page_size)
+            .all(|page| state.has_page(page))
+    }
+}
+
+/// Data read from the local copy of a [`ReadThroughCacheProvider`].
+pub struct CachedData {
+    data: Box<[u8], InternalAlloc>,
+}
+
+impl ReadOnlyFileData for CachedData {
+    fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::filedata::FromSliceReferenceProvider;
+    use core::sync::atomic::{AtomicU64, Ordering};
+    use tempfile::tempdir;
+
+    /// Counts the bytes fetched from the remote.
+    struct CountingProvider<'a> {
+        inner: FromSliceReferenceProvider<'a>,
+        fetched: &'a AtomicU64,
+    }
+
+    impl InputDataProvider for CountingProvider<'_> {
+        fn get_file_data<'b>(
+            &'b self,
+            start: u64,
+            length: u64,
+        ) -> Result<Box<dyn ReadOnlyFileData + 'b>, FileProviderError> {
+            self.fetched.fetch_add(length, Ordering::Relaxed);
+            self.inner.get_file_data(start, length)
+        }
+    }
+
+    fn remote<'a>(
+        data: &'a [u8],
+        fetched: &'a AtomicU64,
+    ) -> Box<dyn InputDataProvider + Send + Sync + 'a> {
+        unsize_box2!(Box::new(CountingProvider {
+            inner: FromSliceReferenceProvider::new(data),
+            fetched,
+        }))
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn only_fetches_each_page_once() {
+        let data: Vec<u8> = (0..10_000_u32).map(|x| x as u8).collect();
+        let fetched = AtomicU64::new(0);
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("archive.nx");
+
+        let provider = ReadThroughCacheProvider::new_with_page_size(
+            remote(&data, &fetched),
+            10_000,
+            &path,
+            4096,
+        )
+        .unwrap();
+        assert_eq!(
+            provider.get_file_data(5000, 100).unwrap().data(),
+            &data[5000..5100]
+        );
+        assert_eq!(fetched.load(Ordering::Relaxed), 4096);
+        assert_eq!(
+            provider.get_file_data(4096, 50).unwrap().data(),
+            &data[4096..4146]
+        );
+        assert_eq!(fetched.load(Ordering::Relaxed), 4096);
+
+        assert_eq!(
+            provider.get_file_data(0, 10_000).unwrap().data(),
+            data.as_slice()
+        );
+        assert_eq!(fetched.load(Ordering::Relaxed), 10_000);
+        assert!(provider.is_complete().unwrap());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn resumes_from_existing_cache() {
+        let data: Vec<u8> = (0..10_000_u32).map(|x| x as u8).collect();
+        let fetched = AtomicU64::new(0);
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("archive.nx");
+
+        let provider = ReadThroughCacheProvider::new_with_page_size(
+            remote(&data, &fetched),
+            10_000,
+            &path,
+            4096,
+        )
+        .unwrap();
+        provider.get_file_data(0, 100).unwrap();
+        drop(provider);
+
+        let refetched = AtomicU64::new(0);
+        let provider = ReadThroughCacheProvider::new_with_page_size(
+            remote(&data, &refetched),
+            10_000,
+            &path,
+            4096,
+        )
+        .unwrap();
+        assert_eq!(provider.cached_bytes().unwrap(), 4096);
+        assert_eq!(
+            provider.get_file_data(10, 20).unwrap().data(),
+            &data[10..30]
+        );
+        assert_eq!(refetched.load(Ordering::Relaxed), 0);
+    }
+}
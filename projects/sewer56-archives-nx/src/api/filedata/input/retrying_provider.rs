@@ -0,0 +1,215 @@
+use crate::api::traits::*;
+use crate::prelude::*;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+/// How many times, and how often, a [`RetryingProvider`] retries failed reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Number of times a read is retried before giving up.
+    pub max_retries: u32,
+
+    /// Time waited before the first retry. Doubled on each following retry.
+    pub initial_backoff: Duration,
+
+    /// Longest time waited between two retries.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(100))
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy, with a maximum backoff of 30 seconds.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_retries` - Number of times a read is retried before giving up.
+    /// * `initial_backoff` - Time waited before the first retry. Doubled on each following retry.
+    pub fn new(max_retries: u32, initial_backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            initial_backoff,
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+
+    /// Sets the longest time waited between two retries.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_backoff` - Longest time waited between two retries.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Returns the time waited before a given retry.
+    ///
+    /// # Arguments
+    ///
+    /// * `retry` - Number of the retry, starting at 0.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(1 << retry.min(31))
+            .min(self.max_backoff)
+    }
+}
+
+/// Retries failed reads of another provider, as per a [`RetryPolicy`].
+///
+/// Meant for providers whose reads can fail transiently, such as files on network shares
+/// or remote (e.g. HTTP) sources, so a single failed read doesn't abort a long running
+/// pack. Only errors for which [`FileProviderError::is_retryable`] returns true are
+/// retried; others are returned right away.
+pub struct RetryingProvider<'a> {
+    inner: Box<dyn InputDataProvider + Send + Sync + 'a>,
+    policy: RetryPolicy,
+
+    /// Number of retries made so far.
+    retries: AtomicU64,
+}
+
+impl<'a> RetryingProvider<'a> {
+    /// Creates a new [`RetryingProvider`].
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - Provides the data; reads of it which fail are retried.
+    /// * `policy` - How many times, and how often, reads are retried.
+    pub fn new(inner: Box<dyn InputDataProvider + Send + Sync + 'a>, policy: RetryPolicy) -> Self {
+        Self {
+            inner,
+            policy,
+            retries: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the policy reads are retried with.
+    pub fn policy(&self) -> RetryPolicy {
+        self.policy
+    }
+
+    /// Returns the number of retries made so far, across all reads.
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+}
+
+impl InputDataProvider for RetryingProvider<'_> {
+    fn get_file_data<'b>(
+        &'b self,
+        start: u64,
+        length: u64,
+    ) -> Result<Box<dyn ReadOnlyFileData + 'b>, FileProviderError> {
+        let mut retry = 0;
+        loop {
+            match self.inner.get_file_data(start, length) {
+                Err(error) if error.is_retryable() && retry < self.policy.max_retries => {
+                    std::thread::sleep(self.policy.backoff(retry));
+                    self.retries.fetch_add(1, Ordering::Relaxed);
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn detect_change(&self) -> Result<Option<u64>, FileProviderError> {
+        self.inner.detect_change()
+    }
+
+    fn is_resident(&self, start: u64, length: u64) -> bool {
+        self.inner.is_resident(start, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::filedata::SliceFileData;
+    use crate::unsize_box2;
+    use alloc::string::String;
+    use core::sync::atomic::AtomicU32;
+
+    /// Fails the given number of reads with a transient error, then reads from a slice.
+    struct FlakyProvider {
+        failures: AtomicU32,
+        data: &'static [u8],
+    }
+
+    impl InputDataProvider for FlakyProvider {
+        fn get_file_data<'a>(
+            &'a self,
+            start: u64,
+            length: u64,
+        ) -> Result<Box<dyn ReadOnlyFileData + 'a>, FileProviderError> {
+            let remaining = self.failures.load(Ordering::Relaxed);
+            if remaining > 0 {
+                self.failures.store(remaining - 1, Ordering::Relaxed);
+                return Err(FileProviderError::Transient(String::from("timed out")));
+            }
+
+            let data = self
+                .data
+                .get(start as usize..(start + length) as usize)
+                .ok_or(FileProviderError::OutOfRange(start, length))?;
+            Ok(unsize_box2!(Box::new(SliceFileData::new(data))))
+        }
+    }
+
+    fn flaky(failures: u32, max_retries: u32) -> RetryingProvider<'static> {
+        let inner = Box::new(FlakyProvider {
+            failures: AtomicU32::new(failures),
+            data: b"Hello, World!",
+        });
+        RetryingProvider::new(
+            unsize_box2!(inner),
+            RetryPolicy::new(max_retries, Duration::ZERO),
+        )
+    }
+
+    #[test]
+    fn retries_transient_errors() {
+        let provider = flaky(2, 3);
+        assert_eq!(provider.get_file_data(7, 5).unwrap().data(), b"World");
+        assert_eq!(provider.retries(), 2);
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let provider = flaky(5, 3);
+        assert!(matches!(
+            provider.get_file_data(0, 5),
+            Err(FileProviderError::Transient(_))
+        ));
+        assert_eq!(provider.retries(), 3);
+    }
+
+    #[test]
+    fn does_not_retry_fatal_errors() {
+        let provider = flaky(0, 3);
+        assert!(matches!(
+            provider.get_file_data(10, 10),
+            Err(FileProviderError::OutOfRange(10, 10))
+        ));
+        assert_eq!(provider.retries(), 0);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let policy = RetryPolicy::new(10, Duration::from_millis(100))
+            .with_max_backoff(Duration::from_millis(500));
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(3), Duration::from_millis(500));
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(500));
+    }
+}
@@ -0,0 +1,114 @@
+use crate::api::{filedata::FromStreamProvider, traits::*};
+use crate::prelude::*;
+use alloc::format;
+use core::mem::ManuallyDrop;
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Number of spool files created by this process so far, used to name them.
+static SPOOL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Provides data from a stream which can't be seeked (e.g. a pipe or a network download),
+/// by copying it to a temporary file first.
+///
+/// The temporary file is deleted when the provider is dropped.
+pub struct SpooledStreamProvider {
+    inner: ManuallyDrop<FromStreamProvider<File>>,
+    path: PathBuf,
+    length: u64,
+}
+
+impl SpooledStreamProvider {
+    /// Copies a stream to a new file in the given directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The stream to copy, read until its end.
+    /// * `temp_dir` - Directory the temporary file is created in.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be created, or the stream can't be read.
+    /// The temporary file is deleted in that case.
+    pub fn new(mut stream: impl Read, temp_dir: &Path) -> Result<Self, FileProviderError> {
+        let id = SPOOL_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = temp_dir.join(format!("nx-spool-{}-{id}.tmp", std::process::id()));
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+
+        let length = match io::copy(&mut stream, &mut file) {
+            Ok(length) => length,
+            Err(error) => {
+                drop(file);
+                let _ = fs::remove_file(&path);
+                return Err(error.into());
+            }
+        };
+
+        Ok(Self {
+            inner: ManuallyDrop::new(FromStreamProvider::new(file)),
+            path,
+            length,
+        })
+    }
+
+    /// Returns the number of bytes copied from the stream.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns true if the stream was empty.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the path of the temporary file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl InputDataProvider for SpooledStreamProvider {
+    fn get_file_data<'a>(
+        &'a self,
+        start: u64,
+        length: u64,
+    ) -> Result<Box<dyn ReadOnlyFileData + 'a>, FileProviderError> {
+        self.inner.get_file_data(start, length)
+    }
+}
+
+impl Drop for SpooledStreamProvider {
+    fn drop(&mut self) {
+        // Close the file first, as open files can't be deleted on Windows.
+        unsafe { ManuallyDrop::drop(&mut self.inner) };
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::TempDir;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn spools_stream_to_temp_dir() {
+        let temp_dir = TempDir::new().unwrap();
+        let provider =
+            SpooledStreamProvider::new(Cursor::new(b"Hello, World!"), temp_dir.path()).unwrap();
+        assert_eq!(provider.len(), 13);
+        assert!(provider.path().starts_with(temp_dir.path()));
+        assert_eq!(provider.get_file_data(7, 5).unwrap().data(), b"World");
+
+        let path = provider.path().to_path_buf();
+        drop(provider);
+        assert!(!path.exists());
+    }
+}
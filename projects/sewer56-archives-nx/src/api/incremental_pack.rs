@@ -0,0 +1,1790 @@
+use crate::api::{
+    archive_reader::{ArchiveReadError, NxArchiveReader, RawBlock},
+    enums::{CompressionPreference, DataFilter, FileChangePolicy},
+    packing::{
+        pack_diagnostics::{PackDiagnostics, PackWarning},
+        pack_plan::{plan_blocks, PackPlan},
+        packer_file::PackerFile,
+        packing_settings::{CompressionCandidate, PackingSettings},
+    },
+    raw_archive_writer::{NxRawArchiveWriter, RawArchiveWriteError},
+    traits::*,
+};
+use crate::headers::{managed::FileEntry, parser::BlockFilter, types::xxh3sum::XXH3sum};
+use crate::prelude::*;
+use crate::utilities::compression::{
+    self,
+    copy_fallback::compress_with_fallback,
+    filters::apply_filter,
+    level_tuner::LevelTuner,
+    mixed::{self, MixedSegment, MAX_SEGMENTS},
+    NxCompressionError,
+};
+use crate::utilities::worker_scheduling::run_with_scheduling;
+use alloc::string::String;
+use core::time::Duration;
+use hashbrown::HashMap;
+use std::time::Instant;
+use thiserror_no_std::Error;
+
+/// Errors that can occur when packing incrementally.
+#[derive(Debug, Error)]
+pub enum IncrementalPackError {
+    /// The previous archive has no file hashes, so unchanged files can't be detected.
+    #[error("The previous archive does not store file hashes")]
+    MissingHashes,
+
+    /// Failed to read the previous archive.
+    #[error("Failed to read previous archive: {0:?}")]
+    Read(#[from] ArchiveReadError),
+
+    /// Failed to read one of the new files.
+    #[error(transparent)]
+    FileProvider(#[from] FileProviderError),
+
+    /// Failed to compress a changed file.
+    #[error("Failed to compress block: {0:?}")]
+    Compression(#[from] NxCompressionError),
+
+    /// A newly compressed block did not decompress back to the original data,
+    /// as checked when [`PackingSettings::verify_blocks`] is enabled.
+    #[error("Compressed block failed verification; it does not decompress to the original data")]
+    VerificationFailed,
+
+    /// Failed to write the new archive.
+    #[error(transparent)]
+    Write(#[from] RawArchiveWriteError),
+
+    /// A file changed between being added and being packed, and
+    /// [`PackingSettings::file_change_policy`] is [`FileChangePolicy::Fail`].
+    #[error(
+        "'{path}' changed after being added to the packer (size {added_size} -> {current_size})"
+    )]
+    FileChanged {
+        /// Path of the file within the archive.
+        path: String,
+        /// Size of the file when it was added.
+        added_size: u64,
+        /// Size of the file when it was about to be packed.
+        current_size: u64,
+    },
+
+    /// A path in [`PackingSettings::manual_blocks`] does not match any of the files.
+    #[error("'{0}' is assigned to a block, but is not one of the files being packed")]
+    UnknownManualBlockFile(String),
+
+    /// A file is listed more than once in [`PackingSettings::manual_blocks`].
+    #[error("'{0}' is assigned to more than one block")]
+    DuplicateManualBlockFile(String),
+
+    /// The files of a block in [`PackingSettings::manual_blocks`] do not fit in a SOLID block.
+    #[error("Manual block {block} holds {size} bytes, more than the block size of {limit}")]
+    ManualBlockTooLarge {
+        /// Index of the block in [`PackingSettings::manual_blocks`].
+        block: usize,
+        /// Total size of the files in the block.
+        size: u64,
+        /// Largest size allowed.
+        limit: u64,
+    },
+
+    /// A block in [`PackingSettings::manual_blocks`] holds more files than
+    /// [`PackingSettings::max_files_per_block`] allows.
+    #[error("Manual block {block} holds {files} files, more than the limit of {limit}")]
+    ManualBlockTooManyFiles {
+        /// Index of the block in [`PackingSettings::manual_blocks`].
+        block: usize,
+        /// Number of files in the block.
+        files: usize,
+        /// Largest number of files allowed.
+        limit: u32,
+    },
+}
+
+/// How much of the previous archive was reused by [`pack_incremental`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IncrementalStats {
+    /// Number of blocks copied from the previous archive without recompressing.
+    pub reused_blocks: u32,
+
+    /// Number of files whose data was taken from the previous archive.
+    pub reused_files: u32,
+
+    /// Total size of the files taken from the previous archive.
+    pub reused_bytes: u64,
+
+    /// Number of newly compressed blocks.
+    pub compressed_blocks: u32,
+
+    /// Number of newly compressed blocks stored raw, because compression did not save
+    /// the space required by [`PackingSettings::copy_fallback`]. These are included in
+    /// [`Self::compressed_blocks`].
+    pub stored_raw_blocks: u32,
+
+    /// Total size of the blocks counted in [`Self::stored_raw_blocks`].
+    pub stored_raw_bytes: u64,
+
+    /// Number of newly compressed SOLID blocks which mix codecs, as enabled by
+    /// [`PackingSettings::mixed_codec_blocks`]. These are included in [`Self::compressed_blocks`].
+    pub mixed_blocks: u32,
+
+    /// Number of files which had to be compressed again (new, changed,
+    /// or sharing a block with a changed file).
+    pub compressed_files: u32,
+
+    /// Total size of the files which had to be compressed again.
+    pub compressed_bytes: u64,
+
+    /// Number of new files identical to another new file, which were stored
+    /// as references to that file's data rather than compressed again.
+    pub deduplicated_files: u32,
+
+    /// Warnings raised while packing.
+    pub diagnostics: PackDiagnostics,
+
+    /// How the algorithms tried on each newly compressed block compared, in block order.
+    /// Only recorded if [`PackingSettings::compression_candidates`] is set.
+    pub block_comparisons: Vec<BlockComparison>,
+}
+
+impl IncrementalStats {
+    /// Sums up [`Self::block_comparisons`] for each algorithm and level that was tried,
+    /// i.e. what the archive would have been like if each had been used for every block.
+    ///
+    /// # Returns
+    ///
+    /// One total per algorithm and level, in the order they were first tried.
+    pub fn comparison_totals(&self) -> Vec<CandidateTotal> {
+        let mut totals: Vec<CandidateTotal> = Vec::new();
+        for comparison in &self.block_comparisons {
+            for (index, result) in comparison.results.iter().enumerate() {
+                let position = totals
+                    .iter()
+                    .position(|total| total.candidate == result.candidate);
+                let total = match position {
+                    Some(position) => &mut totals[position],
+                    None => {
+                        totals.push(CandidateTotal {
+                            candidate: result.candidate,
+                            blocks: 0,
+                            chosen_blocks: 0,
+                            decompressed_size: 0,
+                            compressed_size: 0,
+                            elapsed: Duration::ZERO,
+                        });
+                        totals.last_mut().unwrap()
+                    }
+                };
+
+                total.blocks += 1;
+                total.decompressed_size += comparison.decompressed_size;
+                total.compressed_size += result.compressed_size;
+                total.elapsed += result.elapsed;
+                if index == comparison.chosen {
+                    total.chosen_blocks += 1;
+                }
+            }
+        }
+
+        totals
+    }
+}
+
+/// How the algorithms tried on a block compared.
+/// Recorded when [`PackingSettings::compression_candidates`] is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockComparison {
+    /// Index of the block in the new archive.
+    pub block_index: u32,
+
+    /// Size of the block before compression.
+    pub decompressed_size: u64,
+
+    /// Result of each attempt. The algorithm and level configured for the block come first,
+    /// followed by each of [`PackingSettings::compression_candidates`], in order.
+    pub results: Vec<CandidateResult>,
+
+    /// Index of the result which was kept, i.e. the smallest, in [`Self::results`].
+    pub chosen: usize,
+}
+
+/// The result of compressing a block with one algorithm and level, see [`BlockComparison`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CandidateResult {
+    /// The algorithm and level used.
+    pub candidate: CompressionCandidate,
+
+    /// Size of the block after compression.
+    pub compressed_size: u64,
+
+    /// True if compression did not save the space required by
+    /// [`PackingSettings::copy_fallback`], so the block would be stored raw.
+    pub stored_raw: bool,
+
+    /// Time taken to compress the block.
+    pub elapsed: Duration,
+}
+
+/// Totals of the [`CandidateResult`]s of one algorithm and level across all blocks,
+/// returned by [`IncrementalStats::comparison_totals`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CandidateTotal {
+    /// The algorithm and level used.
+    pub candidate: CompressionCandidate,
+
+    /// Number of blocks compressed with it.
+    pub blocks: u32,
+
+    /// Number of those blocks for which it gave the smallest result.
+    pub chosen_blocks: u32,
+
+    /// Total size of those blocks before compression.
+    pub decompressed_size: u64,
+
+    /// Total size of those blocks after compression.
+    pub compressed_size: u64,
+
+    /// Total time spent compressing those blocks.
+    pub elapsed: Duration,
+}
+
+/// Packs a set of files, reusing the compressed blocks of a previous version of the archive
+/// for any files which have not changed.
+///
+/// A file is unchanged if the previous archive has a file at the same path with the same hash.
+/// A block of the previous archive is copied as-is if all of its files are unchanged;
+/// everything else (new files, changed files, and unchanged files that shared a SOLID block
+/// with a changed or removed file) is compressed again.
+///
+/// # Arguments
+///
+/// * `previous` - The previous version of the archive. Must store file hashes.
+/// * `files` - The files to pack.
+/// * `settings` - Settings used to compress the files which are not reused.
+///
+/// # Returns
+///
+/// The new archive, along with statistics on how much was reused and any warnings.
+///
+/// # Remarks
+///
+/// New blocks which compress poorly are stored raw, as decided by
+/// [`PackingSettings::copy_fallback`]. If [`PackingSettings::verify_blocks`] is enabled,
+/// each new block is decompressed and hashed before being written.
+///
+/// Files listed in [`PackingSettings::manual_blocks`] are always compressed again,
+/// into the blocks they are assigned to, so the requested layout is kept.
+///
+/// Files are checked for changes since they were added before being read (see
+/// [`InputDataProvider::detect_change`]); changed files are handled as per
+/// [`PackingSettings::file_change_policy`].
+///
+/// Files are hashed with [`PackingSettings::hash_seed`]; if the previous archive used a
+/// different seed, its hashes can't be compared, so nothing is reused.
+///
+/// If [`PackingSettings::worker_priority`] or [`PackingSettings::worker_cores`] is set,
+/// packing runs on a worker thread with that priority and affinity, and this waits for it
+/// to finish. Options the platform could not apply are reported as
+/// [`PackWarning::SchedulingNotApplied`].
+///
+/// Every file is read once to compute its hash. The chunk size of the previous
+/// archive is kept, so its chunked files can be reused; [`PackingSettings::chunk_size`]
+/// is only used if the previous archive contains no files.
+pub fn pack_incremental(
+    previous: &NxArchiveReader,
+    files: &[PackerFile<'_>],
+    settings: &PackingSettings,
+) -> Result<(Vec<u8>, IncrementalStats), IncrementalPackError> {
+    let (result, failures) = run_with_scheduling(
+        settings.worker_priority,
+        settings.worker_cores.as_deref(),
+        || pack_incremental_on_current_thread(previous, files, settings),
+    );
+
+    let (archive, mut stats) = result?;
+    let mut diagnostics = PackDiagnostics::default();
+    for failure in failures {
+        diagnostics.report(PackWarning::SchedulingNotApplied(failure));
+    }
+    diagnostics.append(core::mem::take(&mut stats.diagnostics));
+    stats.diagnostics = diagnostics;
+    Ok((archive, stats))
+}
+
+fn pack_incremental_on_current_thread(
+    previous: &NxArchiveReader,
+    files: &[PackerFile<'_>],
+    settings: &PackingSettings,
+) -> Result<(Vec<u8>, IncrementalStats), IncrementalPackError> {
+    if !previous.has_file_hashes() && !previous.entries().is_empty() {
+        return Err(IncrementalPackError::MissingHashes);
+    }
+
+    let mut settings = settings.clone();
+    settings.sanitize();
+    let chunk_size = if previous.entries().is_empty() {
+        settings.chunk_size
+    } else {
+        previous.chunk_size()
+    };
+
+    // Check the new files did not change since they were added, so a file being rewritten
+    // (e.g. by a build system) is not packed half old and half new.
+    let mut stats = IncrementalStats::default();
+    let mut sizes: Vec<u64> = Vec::with_capacity(files.len());
+    for file in files {
+        let Some(current_size) = file.input_data_provider().detect_change()? else {
+            sizes.push(file.file_size());
+            continue;
+        };
+
+        let path = String::from(file.relative_path());
+        let added_size = file.file_size();
+        if settings.file_change_policy == FileChangePolicy::Fail {
+            return Err(IncrementalPackError::FileChanged {
+                path,
+                added_size,
+                current_size,
+            });
+        }
+
+        stats.diagnostics.report(PackWarning::FileChanged {
+            path,
+            added_size,
+            current_size,
+        });
+        sizes.push(current_size);
+    }
+
+    // Hash the new files
+    let mut hashes: Vec<u64> = Vec::with_capacity(files.len());
+    let mut new_files: HashMap<&str, usize> = HashMap::with_capacity(files.len());
+    for (index, file) in files.iter().enumerate() {
+        hashes.push(hash_file(file, sizes[index], settings.hash_seed)?);
+        new_files.insert(file.relative_path(), index);
+    }
+
+    // Check the manually assigned blocks before compressing anything.
+    // SOLID blocks must stay below the chunk size, else readers would treat their files as chunked.
+    let block_size = settings.block_size.min(chunk_size - 1) as u64;
+    let mut manual = vec![false; files.len()];
+    let mut manual_blocks: Vec<Vec<usize>> = Vec::with_capacity(settings.manual_blocks.len());
+    for (block, paths) in settings.manual_blocks.iter().enumerate() {
+        let mut indices = Vec::with_capacity(paths.len());
+        let mut size = 0;
+        for path in paths {
+            let Some(&index) = new_files.get(path.as_str()) else {
+                return Err(IncrementalPackError::UnknownManualBlockFile(path.clone()));
+            };
+            if core::mem::replace(&mut manual[index], true) {
+                return Err(IncrementalPackError::DuplicateManualBlockFile(path.clone()));
+            }
+            size += sizes[index];
+            indices.push(index);
+        }
+
+        if indices.len() > 1 && size > block_size {
+            return Err(IncrementalPackError::ManualBlockTooLarge {
+                block,
+                size,
+                limit: block_size,
+            });
+        }
+        if let Some(limit) = settings.max_files_per_block {
+            if indices.len() > limit as usize {
+                return Err(IncrementalPackError::ManualBlockTooManyFiles {
+                    block,
+                    files: indices.len(),
+                    limit,
+                });
+            }
+        }
+        if !indices.is_empty() {
+            manual_blocks.push(indices);
+        }
+    }
+
+    // Find the unchanged files, and the blocks made up entirely of them.
+    // Chunked files are only reused if they were split with the chunk size they ask for.
+    let same_seed = previous.hash_seed() == settings.hash_seed;
+    let threshold = (settings.effective_solid_size_threshold() as u64).min(block_size);
+    let mut unchanged: Vec<Option<usize>> = Vec::with_capacity(previous.entries().len());
+    let mut block_reusable = vec![true; previous.block_count() as usize];
+    for entry in previous.entries() {
+        let new_file = previous
+            .file_path(entry)?
+            .and_then(|path| new_files.get(path).copied())
+            .filter(|index| {
+                same_seed
+                    && hashes[*index] == entry.hash
+                    && sizes[*index] == entry.decompressed_size
+                    && !manual[*index]
+                    && (sizes[*index] <= threshold
+                        || files[*index].chunk_size_override().unwrap_or(chunk_size)
+                            == previous.entry_chunk_size(entry))
+            });
+
+        if new_file.is_none() {
+            for block_index in previous.block_mapping(entry).block_indices() {
+                block_reusable[block_index as usize] = false;
+            }
+        }
+        unchanged.push(new_file);
+    }
+
+    // Copy the reusable blocks and their files.
+    if settings.enable_per_extension_dictionary {
+        stats
+            .diagnostics
+            .report(PackWarning::IneffectiveDictionaries);
+    }
+
+    let mut writer = NxRawArchiveWriter::new(chunk_size)
+        .with_hash_seed(settings.hash_seed)
+        .with_string_pool_compression(settings.string_pool_compression)
+        .with_file_hashes(settings.store_file_hashes)
+        .with_file_paths(settings.store_file_paths);
+    if let Some(bits) = settings.path_filter_bits {
+        writer = writer.with_path_filter(bits);
+    }
+    if settings.store_directory_index {
+        writer = writer.with_directory_index();
+    }
+    if previous.generation() != 0 {
+        writer = writer.with_generation(previous.generation() + 1);
+    }
+    let mut new_block_index = vec![u32::MAX; block_reusable.len()];
+    for (block_index, reusable) in block_reusable.iter().enumerate() {
+        if *reusable {
+            new_block_index[block_index] =
+                writer.add_block(previous.raw_block(block_index as u32)?);
+            stats.reused_blocks += 1;
+        }
+    }
+
+    let mut reused = vec![false; files.len()];
+    for (entry, new_file) in previous.entries().iter().zip(unchanged) {
+        let Some(new_file) = new_file else {
+            continue;
+        };
+
+        let first_block = new_block_index[entry.first_block_index as usize];
+        if first_block == u32::MAX || reused[new_file] {
+            continue;
+        }
+
+        let file = &files[new_file];
+        let entry_chunk_size = previous.entry_chunk_size(entry);
+        writer.add_chunk_size(first_block, entry_chunk_size);
+        if settings.store_chunk_hashes && entry.is_chunked(entry_chunk_size) {
+            let hashes = match previous
+                .chunk_hashes()
+                .and_then(|x| x.get(entry.first_block_index))
+            {
+                Some(hashes) => Vec::from(hashes),
+                None => hash_chunks(file, sizes[new_file], entry_chunk_size, settings.hash_seed)?,
+            };
+            writer.add_chunk_hashes(first_block, hashes);
+        }
+
+        let mut entry = *entry;
+        entry.first_block_index = first_block;
+        if should_inline(&settings, &entry) {
+            let data = file
+                .input_data_provider()
+                .get_file_data(0, sizes[new_file])?;
+            writer.add_inline_file(file.relative_path(), entry, data.data());
+        } else {
+            writer.add_file(file.relative_path(), entry);
+        }
+        reused[new_file] = true;
+        stats.reused_files += 1;
+        stats.reused_bytes += entry.decompressed_size;
+    }
+
+    let mut compressor = BlockCompressor {
+        writer: &mut writer,
+        settings: &settings,
+        stats: &mut stats,
+        entries: vec![None; files.len()],
+        level_tuner: settings.target_throughput_mbps.map(|mbps| {
+            LevelTuner::new(
+                mbps,
+                settings.adaptive_level_range.clone(),
+                settings.solid_compression_level,
+                1,
+            )
+        }),
+    };
+
+    // Manually assigned blocks come first, in the order given; everything else
+    // is arranged into blocks as explained by `NxPackerBuilder::plan`.
+    let include: Vec<bool> = (0..files.len()).map(|x| !reused[x] && !manual[x]).collect();
+    let plan = plan_blocks(
+        files,
+        &sizes,
+        &include,
+        &manual_blocks,
+        &settings,
+        chunk_size,
+        |index| Ok(hashes[index]),
+    )?;
+    compressor.add_planned_blocks(&plan, files, &sizes, &hashes, &new_files, chunk_size)?;
+
+    let archive = writer.build_with_diagnostics(&mut stats.diagnostics)?;
+    Ok((archive, stats))
+}
+
+/// Appends a file to the SOLID block being built.
+fn append_solid_file(
+    index: usize,
+    file: &PackerFile<'_>,
+    size: u64,
+    hash: u64,
+    data: &mut Vec<u8>,
+    entries: &mut Vec<(usize, FileEntry)>,
+) -> Result<(), IncrementalPackError> {
+    let entry = FileEntry::new(hash, size, data.len() as u32, 0, 0);
+    if size > 0 {
+        let file_data = file.input_data_provider().get_file_data(0, size)?;
+        data.extend_from_slice(file_data.data());
+    }
+    entries.push((index, entry));
+    Ok(())
+}
+
+/// Files smaller than this keep the codec of the files before them in a mixed block.
+/// On their own they rarely compress, even when they do alongside the rest of the block.
+const MIN_MIXED_FILE_SIZE: u64 = 4096;
+
+/// Compresses new blocks into the archive being written.
+struct BlockCompressor<'a> {
+    writer: &'a mut NxRawArchiveWriter,
+    settings: &'a PackingSettings,
+    stats: &'a mut IncrementalStats,
+
+    /// Entries of the newly compressed files, by index of the file.
+    /// Used to point duplicates at the data of their original.
+    entries: Vec<Option<FileEntry>>,
+
+    /// Picks the ZStandard level, if [`PackingSettings::target_throughput_mbps`] is set.
+    level_tuner: Option<LevelTuner>,
+}
+
+impl BlockCompressor<'_> {
+    /// Compresses the blocks of a plan made by [`plan_blocks`], in order. Files which
+    /// duplicate another are added last, sharing the data of the file they duplicate.
+    fn add_planned_blocks(
+        &mut self,
+        plan: &PackPlan,
+        files: &[PackerFile<'_>],
+        sizes: &[u64],
+        hashes: &[u64],
+        paths: &HashMap<&str, usize>,
+        chunk_size: u32,
+    ) -> Result<(), IncrementalPackError> {
+        let mut solid_data: Vec<u8> = Vec::new();
+        let mut solid_entries: Vec<(usize, FileEntry)> = Vec::new();
+        for block in &plan.blocks {
+            match block.chunk_index {
+                // All chunks of a file are compressed along with the first.
+                Some(0) => {
+                    let index = block.files[0];
+                    self.stats.compressed_files += 1;
+                    self.stats.compressed_bytes += sizes[index];
+                    self.add_chunked_file(
+                        index,
+                        &files[index],
+                        sizes[index],
+                        hashes[index],
+                        files[index].chunk_size_override().unwrap_or(chunk_size),
+                        block.compression,
+                        block.filter,
+                    )?;
+                }
+                Some(_) => {}
+                None => {
+                    for &index in &block.files {
+                        self.stats.compressed_files += 1;
+                        self.stats.compressed_bytes += sizes[index];
+                        append_solid_file(
+                            index,
+                            &files[index],
+                            sizes[index],
+                            hashes[index],
+                            &mut solid_data,
+                            &mut solid_entries,
+                        )?;
+                    }
+                    self.add_solid_block(
+                        &mut solid_data,
+                        &mut solid_entries,
+                        files,
+                        block.compression,
+                        block.filter,
+                    )?;
+                }
+            }
+        }
+
+        for (index, planned) in plan.files.iter().enumerate() {
+            let Some(entry) = planned
+                .duplicate_of
+                .as_deref()
+                .and_then(|original| self.entries[paths[original]])
+            else {
+                continue;
+            };
+
+            let file = &files[index];
+            if should_inline(self.settings, &entry) {
+                let data = file.input_data_provider().get_file_data(0, sizes[index])?;
+                self.writer
+                    .add_inline_file(file.relative_path(), entry, data.data());
+            } else {
+                self.writer.add_file(file.relative_path(), entry);
+            }
+            self.entries[index] = Some(entry);
+            self.stats.deduplicated_files += 1;
+        }
+
+        Ok(())
+    }
+
+    fn add_block(
+        &mut self,
+        data: &[u8],
+        mut algorithm: CompressionPreference,
+        solid: bool,
+        filter: DataFilter,
+    ) -> Result<u32, IncrementalPackError> {
+        let level = if solid {
+            self.settings.solid_compression_level
+        } else {
+            self.settings.chunked_compression_level
+        };
+        // Blocks may use another algorithm than configured, e.g. for files stored on their own.
+        let tuner = self
+            .level_tuner
+            .as_ref()
+            .filter(|_| algorithm == CompressionPreference::ZStandard);
+        let level = match tuner {
+            Some(tuner) => tuner.level(),
+            None => self.settings.clamp_compression(level, &algorithm),
+        };
+
+        // Filters rearrange the data so it compresses better; readers reverse them.
+        let mut filtered = Vec::new();
+        let data = match filter {
+            DataFilter::None => data,
+            filter => {
+                filtered.resize(data.len(), 0);
+                // The destination is as large as the source, so this can't fail.
+                let _ = apply_filter(filter, data, &mut filtered);
+                filtered.as_slice()
+            }
+        };
+
+        self.inject_fault()?;
+        let configured = CompressionCandidate { algorithm, level };
+        let (mut compressed, mut used_copy, elapsed) = self.compress(data, configured)?;
+
+        // Blocks stored raw say nothing about how fast the level is.
+        if let Some(tuner) = tuner.filter(|_| !used_copy) {
+            tuner.record(level, data.len(), elapsed);
+        }
+
+        // What-if mode: try each candidate, keeping the smallest result.
+        if !self.settings.compression_candidates.is_empty() {
+            let mut results = vec![CandidateResult {
+                candidate: configured,
+                compressed_size: compressed.len() as u64,
+                stored_raw: used_copy,
+                elapsed,
+            }];
+            let mut chosen = 0;
+            for candidate in &self.settings.compression_candidates {
+                let (attempt, attempt_used_copy, elapsed) = self.compress(data, *candidate)?;
+                results.push(CandidateResult {
+                    candidate: *candidate,
+                    compressed_size: attempt.len() as u64,
+                    stored_raw: attempt_used_copy,
+                    elapsed,
+                });
+                if attempt.len() < compressed.len() {
+                    compressed = attempt;
+                    used_copy = attempt_used_copy;
+                    algorithm = candidate.algorithm;
+                    chosen = results.len() - 1;
+                }
+            }
+
+            self.stats.block_comparisons.push(BlockComparison {
+                block_index: self.writer.block_count(),
+                decompressed_size: data.len() as u64,
+                results,
+                chosen,
+            });
+        }
+
+        let size = compressed.len();
+        let compression = if used_copy {
+            CompressionPreference::Copy
+        } else {
+            algorithm
+        };
+
+        if self.settings.verify_blocks {
+            verify_block(&compressed, compression, data)?;
+        }
+
+        self.stats.compressed_blocks += 1;
+        if used_copy && algorithm != CompressionPreference::Copy {
+            self.stats.stored_raw_blocks += 1;
+            self.stats.stored_raw_bytes += size as u64;
+        }
+        Ok(self.writer.add_block(RawBlock {
+            data: compressed,
+            compression,
+            filter: (filter != DataFilter::None).then_some(BlockFilter {
+                filter,
+                size: data.len() as u32,
+            }),
+            dictionary: None,
+        }))
+    }
+
+    /// Compresses a block with a given algorithm and level, falling back to storing it raw
+    /// as per [`PackingSettings::copy_fallback`].
+    ///
+    /// # Returns
+    ///
+    /// The compressed block, whether it was stored raw, and the time taken.
+    fn compress(
+        &self,
+        data: &[u8],
+        candidate: CompressionCandidate,
+    ) -> Result<(Vec<u8>, bool, Duration), NxCompressionError> {
+        let start = Instant::now();
+        let mut compressed = vec![0u8; compression::max_alloc_for_compress_size(data.len())];
+        let mut used_copy = false;
+        let size = compress_with_fallback(
+            candidate.algorithm,
+            candidate.level,
+            data,
+            &mut compressed,
+            &self.settings.copy_fallback,
+            &self.settings.zstd_advanced,
+            &mut used_copy,
+        )?;
+        compressed.truncate(size);
+        Ok((compressed, used_copy, start.elapsed()))
+    }
+
+    /// Applies the [`FaultPoint::Compress`] faults of [`PackingSettings::fault_injector`].
+    ///
+    /// [`FaultPoint::Compress`]: crate::utilities::fault_injection::FaultPoint::Compress
+    #[cfg(feature = "fault_injection")]
+    fn inject_fault(&self) -> Result<(), NxCompressionError> {
+        use crate::utilities::fault_injection::FaultPoint;
+        match &self.settings.fault_injector {
+            Some(injector) if injector.hit(FaultPoint::Compress) => {
+                Err(NxCompressionError::InjectedFault)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(not(feature = "fault_injection"))]
+    #[inline(always)]
+    fn inject_fault(&self) -> Result<(), NxCompressionError> {
+        Ok(())
+    }
+
+    fn add_chunked_file(
+        &mut self,
+        index: usize,
+        file: &PackerFile<'_>,
+        size: u64,
+        hash: u64,
+        chunk_size: u32,
+        algorithm: CompressionPreference,
+        filter: DataFilter,
+    ) -> Result<(), IncrementalPackError> {
+        let first_block = self.writer.block_count();
+        let mut chunk_hashes = Vec::new();
+        for start in (0..size).step_by(chunk_size as usize) {
+            let length = (size - start).min(chunk_size as u64);
+            let data = file.input_data_provider().get_file_data(start, length)?;
+            if self.settings.store_chunk_hashes {
+                chunk_hashes
+                    .push(XXH3sum::create_with_seed(data.data(), self.settings.hash_seed).0);
+            }
+            self.add_block(data.data(), algorithm, false, filter)?;
+        }
+
+        let entry = FileEntry::new(hash, size, 0, 0, first_block);
+        self.writer.add_file(file.relative_path(), entry);
+        self.writer.add_chunk_size(first_block, chunk_size);
+        self.entries[index] = Some(entry);
+        if !chunk_hashes.is_empty() {
+            self.writer.add_chunk_hashes(first_block, chunk_hashes);
+        }
+        Ok(())
+    }
+
+    fn add_solid_block(
+        &mut self,
+        data: &mut Vec<u8>,
+        entries: &mut Vec<(usize, FileEntry)>,
+        files: &[PackerFile<'_>],
+        algorithm: CompressionPreference,
+        filter: DataFilter,
+    ) -> Result<(), IncrementalPackError> {
+        // Mixed blocks are split by file, so filters don't apply to them.
+        let segments = match filter {
+            DataFilter::None => self.mixed_segments(data, entries, algorithm)?,
+            _ => None,
+        };
+        let block_index = match segments {
+            Some(segments) => self.add_mixed_block(data, &segments)?,
+            None => self.add_block(data, algorithm, true, filter)?,
+        };
+        for (index, mut entry) in entries.drain(..) {
+            entry.first_block_index = block_index;
+            self.entries[index] = Some(entry);
+            let path = files[index].relative_path();
+            if should_inline(self.settings, &entry) {
+                let start = entry.decompressed_block_offset as usize;
+                let end = start + entry.decompressed_size as usize;
+                self.writer.add_inline_file(path, entry, &data[start..end]);
+            } else {
+                self.writer.add_file(path, entry);
+            }
+        }
+
+        data.clear();
+        Ok(())
+    }
+
+    /// Picks a codec for each file of a SOLID block, merging consecutive files with the same codec.
+    /// Returns `None` if a single codec suits the whole block.
+    fn mixed_segments(
+        &self,
+        data: &[u8],
+        entries: &[(usize, FileEntry)],
+        algorithm: CompressionPreference,
+    ) -> Result<Option<Vec<MixedSegment>>, IncrementalPackError> {
+        if !self.settings.mixed_codec_blocks
+            || algorithm == CompressionPreference::Copy
+            || entries.len() < 2
+        {
+            return Ok(None);
+        }
+
+        let mut segments: Vec<MixedSegment> = Vec::new();
+        let mut scratch: Vec<u8> = Vec::new();
+        for (_, entry) in entries {
+            let codec = match segments.last() {
+                Some(last) if entry.decompressed_size < MIN_MIXED_FILE_SIZE => last.compression,
+                _ => {
+                    let start = entry.decompressed_block_offset as usize;
+                    let file_data = &data[start..start + entry.decompressed_size as usize];
+                    scratch.resize(compression::max_alloc_for_compress_size(file_data.len()), 0);
+                    let mut used_copy = false;
+                    compress_with_fallback(
+                        algorithm,
+                        self.settings
+                            .clamp_compression(self.settings.solid_compression_level, &algorithm),
+                        file_data,
+                        &mut scratch,
+                        &self.settings.copy_fallback,
+                        &self.settings.zstd_advanced,
+                        &mut used_copy,
+                    )?;
+                    match used_copy {
+                        true => CompressionPreference::Copy,
+                        false => algorithm,
+                    }
+                }
+            };
+
+            let length = entry.decompressed_size as u32;
+            match segments.last_mut() {
+                Some(last) if last.compression == codec => last.length += length,
+                _ => segments.push(MixedSegment {
+                    compression: codec,
+                    length,
+                }),
+            }
+        }
+
+        Ok((segments.len() > 1 && segments.len() <= MAX_SEGMENTS).then_some(segments))
+    }
+
+    fn add_mixed_block(
+        &mut self,
+        data: &[u8],
+        segments: &[MixedSegment],
+    ) -> Result<u32, IncrementalPackError> {
+        self.inject_fault()?;
+        let mut compressed = vec![0u8; mixed::max_alloc_for_compress_size(segments)];
+        let size = mixed::compress(
+            segments,
+            self.settings.solid_compression_level,
+            &self.settings.zstd_advanced,
+            data,
+            &mut compressed,
+        )?;
+        compressed.truncate(size);
+
+        if self.settings.verify_blocks {
+            verify_block(&compressed, CompressionPreference::Mixed, data)?;
+        }
+
+        self.stats.compressed_blocks += 1;
+        self.stats.mixed_blocks += 1;
+        Ok(self.writer.add_block(RawBlock {
+            data: compressed,
+            compression: CompressionPreference::Mixed,
+            filter: None,
+            dictionary: None,
+        }))
+    }
+}
+
+/// Decompresses a freshly compressed block and checks that its hash matches the original data.
+fn verify_block(
+    compressed: &[u8],
+    compression: CompressionPreference,
+    original: &[u8],
+) -> Result<(), IncrementalPackError> {
+    // Copy 'decompression' does not check the destination size in release builds.
+    if compression == CompressionPreference::Copy && compressed.len() != original.len() {
+        return Err(IncrementalPackError::VerificationFailed);
+    }
+
+    let mut decompressed = vec![0u8; original.len()];
+    match compression::decompress(compression, compressed, &mut decompressed) {
+        Ok(size)
+            if size == original.len()
+                && XXH3sum::create(&decompressed) == XXH3sum::create(original) =>
+        {
+            Ok(())
+        }
+        _ => Err(IncrementalPackError::VerificationFailed),
+    }
+}
+
+/// Returns true if a file is small enough to be inlined, see [`PackingSettings::inline_file_threshold`].
+/// Empty files are never inlined; they are read without fetching a block anyway.
+fn should_inline(settings: &PackingSettings, entry: &FileEntry) -> bool {
+    settings
+        .inline_file_threshold
+        .is_some_and(|max| entry.decompressed_size > 0 && entry.decompressed_size <= max as u64)
+}
+
+/// Hashes each chunk of a file, see [`PackingSettings::store_chunk_hashes`].
+fn hash_chunks(
+    file: &PackerFile<'_>,
+    size: u64,
+    chunk_size: u32,
+    seed: u64,
+) -> Result<Vec<u64>, FileProviderError> {
+    let mut result = Vec::with_capacity(size.div_ceil(chunk_size as u64) as usize);
+    for start in (0..size).step_by(chunk_size as usize) {
+        let length = (size - start).min(chunk_size as u64);
+        let data = file.input_data_provider().get_file_data(start, length)?;
+        result.push(XXH3sum::create_with_seed(data.data(), seed).0);
+    }
+
+    Ok(result)
+}
+
+pub(crate) fn hash_file(
+    file: &PackerFile<'_>,
+    size: u64,
+    seed: u64,
+) -> Result<u64, FileProviderError> {
+    if size == 0 {
+        return Ok(XXH3sum::create_with_seed(&[], seed).0);
+    }
+
+    let data = file.input_data_provider().get_file_data(0, size)?;
+    Ok(XXH3sum::create_with_seed(data.data(), seed).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::enums::FileHashStorage;
+    use crate::api::filedata::{FileHandlePolicy, FromSliceReferenceProvider};
+    use crate::api::packing::pack_diagnostics::PackWarningCode;
+    use crate::api::packing::packing_settings::MIN_BLOCK_SIZE;
+    use crate::unsize_box2;
+    use crate::utilities::tests::archive_for_testing::*;
+    use alloc::string::String;
+    use rstest::rstest;
+
+    const CHUNK_SIZE: u32 = 32768;
+
+    fn open(archive: &[u8]) -> NxArchiveReader<'_> {
+        let provider = Box::new(FromSliceReferenceProvider::new(archive));
+        NxArchiveReader::new(unsize_box2!(provider)).unwrap()
+    }
+
+    fn file<'a>(path: &str, data: &'a [u8]) -> PackerFile<'a> {
+        let provider = Box::new(FromSliceReferenceProvider::new(data));
+        PackerFile::new(
+            String::from(path),
+            data.len() as u64,
+            unsize_box2!(provider),
+        )
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reuses_blocks_of_unchanged_files() {
+        let large: Vec<u8> = (0..100_000_u32).map(|x| (x % 251) as u8).collect();
+        let previous = create_test_archive(
+            CHUNK_SIZE,
+            &[
+                &[TestFile::new("a.txt", b"Hello")],
+                &[
+                    TestFile::new("b.txt", b"Old"),
+                    TestFile::new("c.txt", b"Nx"),
+                ],
+            ],
+            &[TestFile::new("large.bin", &large)],
+            CompressionPreference::ZStandard,
+        );
+        let previous = open(&previous);
+
+        let files = [
+            file("a.txt", b"Hello"),
+            file("b.txt", b"New contents"),
+            file("c.txt", b"Nx"),
+            file("large.bin", &large),
+            file("d.txt", b"Added"),
+        ];
+        let (archive, stats) =
+            pack_incremental(&previous, &files, &PackingSettings::new()).unwrap();
+
+        // large.bin (4 chunks) and a.txt are reused; b.txt's block is rebuilt with c.txt and d.txt
+        assert_eq!(stats.reused_blocks, 5);
+        assert_eq!(stats.reused_files, 2);
+        assert_eq!(stats.reused_bytes, large.len() as u64 + 5);
+        assert_eq!(stats.compressed_files, 3);
+        assert_eq!(stats.compressed_blocks, 1);
+
+        let reader = open(&archive);
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"Hello");
+        assert_eq!(
+            reader.read_file("b.txt").unwrap().as_slice(),
+            b"New contents"
+        );
+        assert_eq!(reader.read_file("c.txt").unwrap().as_slice(), b"Nx");
+        assert_eq!(reader.read_file("d.txt").unwrap().as_slice(), b"Added");
+        assert_eq!(reader.read_file("large.bin").unwrap(), large);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn stable_order_breaks_size_ties_by_path() {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let previous = open(&empty);
+        let mut settings = PackingSettings::new();
+        settings.stable_order = true;
+
+        // Both files have the same size, so only their paths order them.
+        let forward = [file("a.txt", b"Same"), file("b.txt", b"Size")];
+        let reverse = [file("b.txt", b"Size"), file("a.txt", b"Same")];
+        let (forward, _) = pack_incremental(&previous, &forward, &settings).unwrap();
+        let (reverse, _) = pack_incremental(&previous, &reverse, &settings).unwrap();
+        assert_eq!(forward, reverse);
+
+        let reader = open(&reverse);
+        let a = reader.find_entry("a.txt").unwrap().unwrap();
+        let b = reader.find_entry("b.txt").unwrap().unwrap();
+        assert_eq!(a.first_block_index, b.first_block_index);
+        assert!(a.decompressed_block_offset < b.decompressed_block_offset);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn hashes_files_with_seed() {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let files = [file("a.txt", b"Hello")];
+        let mut settings = PackingSettings::new();
+        settings.hash_seed = 1234;
+
+        let (archive, _) = pack_incremental(&open(&empty), &files, &settings).unwrap();
+        let reader = open(&archive);
+        let entry = reader.find_entry("a.txt").unwrap().unwrap();
+        assert_eq!(reader.hash_seed(), 1234);
+        assert_ne!(entry.hash, XXH3sum::create(b"Hello").0);
+        assert_eq!(entry.hash, reader.hash_data(b"Hello"));
+
+        // Hashes are only comparable with the same seed.
+        let (_, stats) = pack_incremental(&reader, &files, &settings).unwrap();
+        assert_eq!(stats.reused_files, 1);
+        let (_, stats) = pack_incremental(&reader, &files, &PackingSettings::new()).unwrap();
+        assert_eq!(stats.reused_files, 0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn stores_chunk_hashes() {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let large: Vec<u8> = (0..100_000u32).map(|x| (x % 251) as u8).collect();
+        let files = [file("large.bin", &large), file("a.txt", b"Hello")];
+        let mut settings = PackingSettings::new();
+        settings.block_size = MIN_BLOCK_SIZE;
+        settings.chunk_size = CHUNK_SIZE;
+        settings.store_chunk_hashes = true;
+
+        let (archive, _) = pack_incremental(&open(&empty), &files, &settings).unwrap();
+        let reader = open(&archive);
+        let entry = reader.find_entry("large.bin").unwrap().unwrap();
+        let hashes = reader
+            .chunk_hashes()
+            .unwrap()
+            .get(entry.first_block_index)
+            .unwrap();
+        assert_eq!(hashes.len(), 4);
+        assert_eq!(
+            hashes[3],
+            XXH3sum::create(&large[3 * CHUNK_SIZE as usize..]).0
+        );
+        for chunk in 0..4 {
+            assert_eq!(reader.verify_chunk(entry, chunk).unwrap(), Some(true));
+        }
+        assert_eq!(reader.verify_chunk(entry, 4).unwrap(), None);
+
+        // Hashes of reused files are kept.
+        let (repacked, stats) = pack_incremental(&reader, &files, &settings).unwrap();
+        assert_eq!(stats.reused_files, 2);
+        let repacked = open(&repacked);
+        let entry = repacked.find_entry("large.bin").unwrap().unwrap();
+        assert_eq!(
+            repacked
+                .chunk_hashes()
+                .unwrap()
+                .get(entry.first_block_index),
+            Some(hashes)
+        );
+    }
+
+    #[rstest]
+    #[case::smaller(CHUNK_SIZE, 4)]
+    #[case::larger(CHUNK_SIZE * 32, 1)]
+    #[cfg_attr(miri, ignore)]
+    fn splits_files_with_their_own_chunk_size(#[case] chunk_size: u32, #[case] blocks: u32) {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let large: Vec<u8> = (0..100_000u32).map(|x| (x % 251) as u8).collect();
+        let files = [
+            file("large.bin", &large).with_chunk_size_override(Some(chunk_size)),
+            file("a.txt", b"Hello"),
+        ];
+        let mut settings = PackingSettings::new();
+        settings.block_size = MIN_BLOCK_SIZE;
+        settings.chunk_size = CHUNK_SIZE * 4;
+
+        let (archive, _) = pack_incremental(&open(&empty), &files, &settings).unwrap();
+        let reader = open(&archive);
+        let entry = reader.find_entry("large.bin").unwrap().unwrap();
+        assert_eq!(reader.chunk_size(), CHUNK_SIZE * 4);
+        assert_eq!(reader.entry_chunk_size(entry), chunk_size);
+        assert_eq!(
+            reader.block_mapping(entry).block_indices().len() as u32,
+            blocks
+        );
+        assert_eq!(reader.block_count(), blocks + 1);
+        assert_eq!(reader.read_file("large.bin").unwrap(), large);
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"Hello");
+
+        // Unchanged files are reused along with their chunk size.
+        let (repacked, stats) = pack_incremental(&reader, &files, &settings).unwrap();
+        assert_eq!(stats.reused_files, 2);
+        let repacked = open(&repacked);
+        let entry = repacked.find_entry("large.bin").unwrap().unwrap();
+        assert_eq!(
+            repacked.entry_chunk_size(entry),
+            reader.entry_chunk_size(entry)
+        );
+        assert_eq!(repacked.read_file("large.bin").unwrap(), large);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn manual_blocks_are_packed_as_assigned() {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let previous = open(&empty);
+        let files = [
+            file("a.txt", b"First"),
+            file("b.txt", b"Second"),
+            file("c.txt", b"Third"),
+        ];
+        let mut settings = PackingSettings::new();
+        settings.manual_blocks = vec![vec![String::from("c.txt"), String::from("a.txt")]];
+
+        let (archive, stats) = pack_incremental(&previous, &files, &settings).unwrap();
+        assert_eq!(stats.compressed_blocks, 2);
+        let reader = open(&archive);
+        let block_of = |path: &str| reader.find_entry(path).unwrap().unwrap().first_block_index;
+        assert_eq!(block_of("c.txt"), 0);
+        assert_eq!(block_of("a.txt"), 0);
+        assert_eq!(block_of("b.txt"), 1);
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"First");
+
+        settings.manual_blocks = vec![vec![String::from("missing.txt")]];
+        assert!(matches!(
+            pack_incremental(&previous, &files, &settings),
+            Err(IncrementalPackError::UnknownManualBlockFile(_))
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn automatic_blocks_respect_max_files_per_block() {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let previous = open(&empty);
+        let names = ["a.txt", "b.txt", "c.txt", "d.txt", "e.txt"];
+        let files: Vec<PackerFile> = names
+            .iter()
+            .map(|name| file(name, name.as_bytes()))
+            .collect();
+        let mut settings = PackingSettings::new();
+        settings.max_files_per_block = Some(2);
+
+        let (archive, stats) = pack_incremental(&previous, &files, &settings).unwrap();
+        assert_eq!(stats.compressed_blocks, 3);
+        let reader = open(&archive);
+        let mut files_per_block = vec![0; reader.block_count() as usize];
+        for name in names {
+            let entry = reader.find_entry(name).unwrap().unwrap();
+            files_per_block[entry.first_block_index as usize] += 1;
+            assert_eq!(reader.read_file(name).unwrap().as_slice(), name.as_bytes());
+        }
+        assert_eq!(files_per_block, [2, 2, 1]);
+    }
+
+    #[rstest]
+    #[case::transpose(DataFilter::ByteTranspose4)]
+    #[case::delta(DataFilter::Delta4)]
+    #[cfg_attr(miri, ignore)]
+    fn extension_filters_round_trip(#[case] filter: DataFilter) {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let previous = open(&empty);
+        let floats = |count: u32| -> Vec<u8> {
+            (0..count)
+                .flat_map(|x| (x as f32 * 0.25).to_le_bytes())
+                .collect()
+        };
+        let (small, large) = (floats(1000), floats(20_000));
+        let files = [
+            file("small.mesh", &small),
+            file("large.mesh", &large),
+            file("readme.txt", b"Not filtered"),
+        ];
+        let mut settings = PackingSettings::new();
+        settings
+            .extension_filters
+            .insert(String::from("mesh"), filter);
+
+        let (archive, _) = pack_incremental(&previous, &files, &settings).unwrap();
+        let reader = open(&archive);
+        for path in ["small.mesh", "large.mesh"] {
+            let block = reader.find_entry(path).unwrap().unwrap().first_block_index;
+            assert_eq!(
+                reader.raw_block(block).unwrap().filter.unwrap().filter,
+                filter
+            );
+        }
+        let text_block = reader
+            .find_entry("readme.txt")
+            .unwrap()
+            .unwrap()
+            .first_block_index;
+        assert_eq!(reader.raw_block(text_block).unwrap().filter, None);
+
+        assert_eq!(reader.read_file("small.mesh").unwrap(), small);
+        assert_eq!(reader.read_file("large.mesh").unwrap(), large);
+        assert_eq!(
+            reader.read_file_range("large.mesh", 40_000, 100).unwrap(),
+            &large[40_000..40_100]
+        );
+        assert_eq!(
+            reader.read_file("readme.txt").unwrap().as_slice(),
+            b"Not filtered"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn new_blocks_follow_the_arranger() {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let previous = open(&empty);
+        let text = |seed: u32| -> Vec<u8> { (0..4000_u32).map(|x| (x % 7 + seed) as u8).collect() };
+        let (a, b, manifest) = (text(1), text(2), text(3));
+        let files = [
+            file("a.json", &a),
+            file("manifest.json", &manifest).with_own_block(true),
+            file("b.json", &b),
+            file("copy_of_a.json", &a),
+        ];
+
+        let (archive, stats) =
+            pack_incremental(&previous, &files, &PackingSettings::new()).unwrap();
+        assert_eq!(stats.compressed_blocks, 2);
+        assert_eq!(stats.compressed_files, 3);
+        assert_eq!(stats.deduplicated_files, 1);
+
+        let reader = open(&archive);
+        let entry = |path: &str| *reader.find_entry(path).unwrap().unwrap();
+        assert_eq!(
+            entry("a.json").first_block_index,
+            entry("b.json").first_block_index
+        );
+        assert_ne!(
+            entry("a.json").first_block_index,
+            entry("manifest.json").first_block_index
+        );
+        assert_eq!(
+            entry("copy_of_a.json").decompressed_block_offset,
+            entry("a.json").decompressed_block_offset
+        );
+        assert_eq!(reader.read_file("copy_of_a.json").unwrap(), a);
+        assert_eq!(reader.read_file("manifest.json").unwrap(), manifest);
+    }
+
+    #[rstest]
+    #[case::always(FileHashStorage::Always, true)]
+    #[case::never(FileHashStorage::Never, false)]
+    #[case::auto(FileHashStorage::Auto, true)]
+    #[cfg_attr(miri, ignore)]
+    fn file_hash_storage_is_applied(#[case] storage: FileHashStorage, #[case] expected: bool) {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let previous = open(&empty);
+        let files = [file("a.txt", b"Hello"), file("b.txt", b"World")];
+        let mut settings = PackingSettings::new();
+        settings.store_file_hashes = storage;
+
+        let (archive, _) = pack_incremental(&previous, &files, &settings).unwrap();
+        let reader = open(&archive);
+        assert_eq!(reader.has_file_hashes(), expected);
+        assert_eq!(reader.read_file("b.txt").unwrap().as_slice(), b"World");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn files_are_found_by_hash_without_paths() {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let previous = open(&empty);
+        let files = [file("a.txt", b"Hello"), file("b.txt", b"World")];
+        let mut settings = PackingSettings::new();
+        settings.store_file_paths = false;
+
+        let (archive, _) = pack_incremental(&previous, &files, &settings).unwrap();
+        let contains = |needle: &[u8]| archive.windows(needle.len()).any(|x| x == needle);
+        assert!(!contains(b"a.txt") && !contains(b"b.txt"));
+
+        let reader = open(&archive);
+        assert!(reader.find_entry("b.txt").unwrap().is_none());
+        let hash = hash_file(&files[1], 5, reader.hash_seed()).unwrap();
+        let found = reader.find_by_hash(hash);
+        assert_eq!(found.len(), 1);
+        assert_eq!(reader.file_path(found[0].entry).unwrap(), None);
+        assert_eq!(
+            reader
+                .read_entry_range(found[0].entry, 0, 5)
+                .unwrap()
+                .as_slice(),
+            b"World"
+        );
+    }
+
+    #[rstest]
+    #[case::raw(CompressionPreference::Copy, true)]
+    #[case::compressed(CompressionPreference::ZStandard, false)]
+    #[cfg_attr(miri, ignore)]
+    fn string_pool_compression_is_applied(
+        #[case] algorithm: CompressionPreference,
+        #[case] paths_stored_raw: bool,
+    ) {
+        use crate::headers::parser::StringPoolCompression;
+
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let previous = open(&empty);
+        let paths: Vec<String> = (0..16)
+            .map(|x| alloc::format!("a_directory_with_a_long_name/file_{x}.txt"))
+            .collect();
+        let files: Vec<PackerFile> = paths.iter().map(|path| file(path, b"Data")).collect();
+        let mut settings = PackingSettings::new();
+        settings.string_pool_compression = StringPoolCompression {
+            algorithm,
+            ..StringPoolCompression::default()
+        };
+
+        let (archive, _) = pack_incremental(&previous, &files, &settings).unwrap();
+        let contains = |needle: &[u8]| archive.windows(needle.len()).any(|x| x == needle);
+        assert_eq!(
+            paths.iter().all(|path| contains(path.as_bytes())),
+            paths_stored_raw
+        );
+        assert_eq!(
+            open(&archive).read_file(&paths[7]).unwrap().as_slice(),
+            b"Data"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn target_throughput_adjusts_zstd_level() {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let previous = open(&empty);
+        let large: Vec<u8> = (0..CHUNK_SIZE * 5).map(|x| (x % 251) as u8).collect();
+        let files = [file("large.bin", &large)];
+        let mut settings = PackingSettings::new();
+        settings.chunked_file_algorithm = CompressionPreference::ZStandard;
+        settings.solid_compression_level = 1;
+        // Any machine compresses faster than 1MB/s at these levels, so the level rises each block.
+        settings.target_throughput_mbps = Some(1);
+        settings.adaptive_level_range = 1..=3;
+        // Comparing candidates records the level each block was compressed with.
+        settings.compression_candidates = vec![CompressionCandidate {
+            algorithm: CompressionPreference::Copy,
+            level: 1,
+        }];
+
+        let (archive, stats) = pack_incremental(&previous, &files, &settings).unwrap();
+        let levels: Vec<i32> = stats
+            .block_comparisons
+            .iter()
+            .map(|x| x.results[0].candidate.level)
+            .collect();
+        assert_eq!(levels, [1, 2, 3, 3, 3]);
+        assert_eq!(open(&archive).read_file("large.bin").unwrap(), large);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn compares_candidates_and_keeps_the_smallest() {
+        let previous = create_test_archive(
+            CHUNK_SIZE,
+            &[&[TestFile::new("removed.txt", b"Gone")]],
+            &[],
+            CompressionPreference::Copy,
+        );
+        let previous = open(&previous);
+
+        let text: Vec<u8> = (0..4000_u32).map(|x| (x % 7) as u8 + b'a').collect();
+        let files = [file("a.txt", &text)];
+        let mut settings = PackingSettings::new();
+        settings.solid_block_algorithm = CompressionPreference::Copy;
+        settings.compression_candidates = vec![CompressionCandidate {
+            algorithm: CompressionPreference::ZStandard,
+            level: 3,
+        }];
+
+        let (archive, stats) = pack_incremental(&previous, &files, &settings).unwrap();
+        assert_eq!(stats.block_comparisons.len(), 1);
+        let comparison = &stats.block_comparisons[0];
+        assert_eq!(comparison.decompressed_size, text.len() as u64);
+        assert_eq!(comparison.results.len(), 2);
+        assert_eq!(comparison.results[0].compressed_size, text.len() as u64);
+        assert!(comparison.results[1].compressed_size < text.len() as u64);
+        assert_eq!(comparison.chosen, 1);
+
+        let reader = open(&archive);
+        assert_eq!(
+            reader.raw_block(0).unwrap().compression,
+            CompressionPreference::ZStandard
+        );
+        assert_eq!(reader.read_file("a.txt").unwrap(), text);
+
+        let totals = stats.comparison_totals();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[1].chosen_blocks, 1);
+        assert_eq!(
+            totals[1].compressed_size,
+            comparison.results[1].compressed_size
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn blocks_with_marginal_gain_are_stored_raw() {
+        let previous = create_test_archive(
+            CHUNK_SIZE,
+            &[&[TestFile::new("removed.txt", b"Gone")]],
+            &[],
+            CompressionPreference::Copy,
+        );
+        let previous = open(&previous);
+
+        // Compression saves space, but can never save all of it.
+        let text: Vec<u8> = (0..4000_u32).map(|x| (x % 7) as u8 + b'a').collect();
+        let files = [file("a.txt", &text)];
+        let mut settings = PackingSettings::new();
+        settings.copy_fallback.min_savings_percent = 100;
+
+        let (archive, stats) = pack_incremental(&previous, &files, &settings).unwrap();
+        assert_eq!(stats.compressed_blocks, 1);
+        assert_eq!(stats.stored_raw_blocks, 1);
+        assert_eq!(stats.stored_raw_bytes, text.len() as u64);
+
+        let reader = open(&archive);
+        assert_eq!(
+            reader.raw_block(0).unwrap().compression,
+            CompressionPreference::Copy
+        );
+        assert_eq!(reader.read_file("a.txt").unwrap(), text);
+
+        let (_, stats) = pack_incremental(&previous, &files, &PackingSettings::new()).unwrap();
+        assert_eq!(stats.stored_raw_blocks, 0);
+    }
+
+    #[rstest]
+    #[case::zstd(CompressionPreference::ZStandard)]
+    #[cfg_attr(feature = "lz4", case::lz4(CompressionPreference::Lz4))]
+    #[case::copy(CompressionPreference::Copy)]
+    #[cfg_attr(miri, ignore)]
+    fn verified_blocks_are_written(#[case] algorithm: CompressionPreference) {
+        let previous = create_test_archive(
+            CHUNK_SIZE,
+            &[&[TestFile::new("removed.txt", b"Gone")]],
+            &[],
+            CompressionPreference::Copy,
+        );
+        let previous = open(&previous);
+
+        let text: Vec<u8> = (0..4000_u32).map(|x| (x % 7) as u8 + b'a').collect();
+        let files = [file("a.txt", &text)];
+        let mut settings = PackingSettings::new();
+        settings.solid_block_algorithm = algorithm;
+        settings.verify_blocks = true;
+
+        let (archive, stats) = pack_incremental(&previous, &files, &settings).unwrap();
+        assert_eq!(stats.compressed_blocks, 1);
+        assert_eq!(open(&archive).read_file("a.txt").unwrap(), text);
+    }
+
+    #[rstest]
+    #[case::verified(true)]
+    #[case::unverified(false)]
+    #[cfg_attr(miri, ignore)]
+    fn incompressible_files_are_stored_raw_within_solid_block(#[case] verify: bool) {
+        let previous = create_test_archive(
+            CHUNK_SIZE,
+            &[&[TestFile::new("removed.txt", b"Gone")]],
+            &[],
+            CompressionPreference::Copy,
+        );
+        let previous = open(&previous);
+
+        let text: Vec<u8> = (0..8000_u32).map(|x| (x % 7) as u8 + b'a').collect();
+        let mut state = 0x12345678_u32;
+        let noise: Vec<u8> = (0..12000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        let files = [file("a.txt", &text), file("b.bin", &noise)];
+        let mut settings = PackingSettings::new();
+        settings.mixed_codec_blocks = true;
+        settings.verify_blocks = verify;
+
+        let (archive, stats) = pack_incremental(&previous, &files, &settings).unwrap();
+        assert_eq!(stats.compressed_blocks, 1);
+        assert_eq!(stats.mixed_blocks, 1);
+
+        let reader = open(&archive);
+        let block = reader.raw_block(0).unwrap();
+        assert_eq!(block.compression, CompressionPreference::Mixed);
+        assert!(block.data.len() < text.len() + noise.len());
+        assert_eq!(reader.read_file("a.txt").unwrap(), text);
+        assert_eq!(reader.read_file("b.bin").unwrap(), noise);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn small_files_are_inlined() {
+        let previous = create_test_archive(
+            CHUNK_SIZE,
+            &[&[TestFile::new("kept.txt", b"Kept")]],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+        let previous = open(&previous);
+
+        let text: Vec<u8> = (0..4000_u32).map(|x| (x % 7) as u8 + b'a').collect();
+        let files = [
+            file("kept.txt", b"Kept"),
+            file("flags.txt", b"fast=1"),
+            file("large.txt", &text),
+        ];
+        let mut settings = PackingSettings::new();
+        settings.inline_file_threshold = Some(64);
+
+        let (archive, stats) = pack_incremental(&previous, &files, &settings).unwrap();
+        assert_eq!(stats.reused_files, 1);
+
+        let reader = open(&archive);
+        let inline = reader.inline_files().unwrap();
+        assert_eq!(inline.len(), 2);
+        for (path, data) in [("kept.txt", &b"Kept"[..]), ("flags.txt", b"fast=1")] {
+            let entry = reader.find_entry(path).unwrap().unwrap();
+            assert_eq!(inline.get(entry), Some(data));
+            assert_eq!(reader.read_file(path).unwrap().as_slice(), data);
+        }
+        assert_eq!(reader.read_file("large.txt").unwrap(), text);
+    }
+
+    #[test]
+    fn verify_block_rejects_mismatched_data() {
+        let original = b"Hello, World!";
+        assert!(verify_block(original, CompressionPreference::Copy, original).is_ok());
+        assert!(matches!(
+            verify_block(b"Hello, Wurld!", CompressionPreference::Copy, original),
+            Err(IncrementalPackError::VerificationFailed)
+        ));
+        assert!(matches!(
+            verify_block(b"Hello", CompressionPreference::Copy, original),
+            Err(IncrementalPackError::VerificationFailed)
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn removed_files_are_dropped() {
+        let previous = create_test_archive(
+            CHUNK_SIZE,
+            &[
+                &[TestFile::new("a.txt", b"Hello")],
+                &[TestFile::new("b.txt", b"Gone")],
+            ],
+            &[],
+            CompressionPreference::Copy,
+        );
+        let previous = open(&previous);
+
+        let (archive, stats) = pack_incremental(
+            &previous,
+            &[file("a.txt", b"Hello")],
+            &PackingSettings::new(),
+        )
+        .unwrap();
+
+        assert_eq!(stats.reused_blocks, 1);
+        assert_eq!(stats.compressed_blocks, 0);
+        let reader = open(&archive);
+        assert_eq!(reader.entries().len(), 1);
+        assert!(reader.find_entry("b.txt").unwrap().is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn dictionaries_are_only_reported_when_enabled() {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let previous = open(&empty);
+        let files = [file("a.txt", b"Hello")];
+
+        let (_, stats) = pack_incremental(&previous, &files, &PackingSettings::new()).unwrap();
+        assert!(!stats
+            .diagnostics
+            .contains(PackWarningCode::IneffectiveDictionaries));
+
+        let mut settings = PackingSettings::new();
+        settings.enable_per_extension_dictionary = true;
+        let (_, stats) = pack_incremental(&previous, &files, &settings).unwrap();
+        assert!(stats
+            .diagnostics
+            .contains(PackWarningCode::IneffectiveDictionaries));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn files_changed_after_being_added_are_detected() {
+        let previous = create_test_archive(
+            CHUNK_SIZE,
+            &[&[TestFile::new("b.txt", b"Nx")]],
+            &[],
+            CompressionPreference::Copy,
+        );
+        let previous = open(&previous);
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        std::fs::write(&path, b"Old").unwrap();
+        let files = [PackerFile::from_file_path_with_policy(
+            path.to_str().unwrap(),
+            String::from("a.txt"),
+            &FileHandlePolicy::ReopenEachTime,
+        )
+        .unwrap()];
+        std::fs::write(&path, b"New contents").unwrap();
+
+        let error = pack_incremental(&previous, &files, &PackingSettings::new()).unwrap_err();
+        assert!(matches!(
+            error,
+            IncrementalPackError::FileChanged {
+                added_size: 3,
+                current_size: 12,
+                ..
+            }
+        ));
+
+        let mut settings = PackingSettings::new();
+        settings.file_change_policy = FileChangePolicy::Repack;
+        let (archive, stats) = pack_incremental(&previous, &files, &settings).unwrap();
+        assert!(stats.diagnostics.contains(PackWarningCode::FileChanged));
+
+        let reader = open(&archive);
+        let entry = reader.find_entry("a.txt").unwrap().unwrap();
+        assert_eq!(entry.decompressed_size, 12);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(miri, ignore)]
+    fn reports_worker_scheduling_which_was_not_applied() {
+        let empty = create_test_archive(CHUNK_SIZE, &[], &[], CompressionPreference::ZStandard);
+        let files = [file("a.txt", b"Hello")];
+        let mut settings = PackingSettings::new();
+        settings.worker_cores = Some(vec![usize::MAX]);
+
+        let (archive, stats) = pack_incremental(&open(&empty), &files, &settings).unwrap();
+        assert!(stats
+            .diagnostics
+            .contains(PackWarningCode::SchedulingNotApplied));
+        assert_eq!(
+            open(&archive).read_file("a.txt").unwrap().as_slice(),
+            b"Hello"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn archive_without_paths_is_compressed_again() {
+        let previous = create_test_archive_without_paths(
+            CHUNK_SIZE,
+            &[&[TestFile::new("a.txt", b"Hello")]],
+            &[],
+            CompressionPreference::Copy,
+        );
+        let previous = open(&previous);
+
+        // Without paths, nothing can be matched; everything is compressed again.
+        let (_, stats) = pack_incremental(
+            &previous,
+            &[file("a.txt", b"Hello")],
+            &PackingSettings::new(),
+        )
+        .unwrap();
+        assert_eq!(stats.reused_files, 0);
+        assert_eq!(stats.compressed_files, 1);
+    }
+}
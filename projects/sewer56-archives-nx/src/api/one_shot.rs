@@ -0,0 +1,280 @@
+use crate::api::{
+    archive_reader::{ArchiveReadError, NxArchiveReader},
+    archive_sidecar::ArchiveSidecar,
+    directory_sync::{extract_to_dir, DirectorySyncError, ExtractOptions, SyncToDirStats},
+    filedata::{FromFilePathProvider, FromSliceReferenceProvider},
+    incremental_pack::{IncrementalPackError, IncrementalStats},
+    packer_builder::{NxPackerBuilder, PackerPreset},
+    raw_archive_writer::NxRawArchiveWriter,
+    traits::FileProviderError,
+};
+use crate::prelude::*;
+use crate::unsize_box2;
+use alloc::format;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror_no_std::Error;
+
+/// Errors that can occur in [`pack_directory`], [`pack_directory_with_sidecar`]
+/// and [`extract_archive`].
+///
+/// Each error carries the path which was being worked on.
+#[derive(Debug, Error)]
+pub enum OneShotError {
+    /// Failed to list or open the files of the source directory.
+    #[error("Failed to add files from {directory:?}: {error:?}")]
+    AddFiles {
+        /// The directory being packed.
+        directory: PathBuf,
+        /// What went wrong.
+        error: FileProviderError,
+    },
+
+    /// Failed to compress the files of the source directory.
+    #[error("Failed to pack {directory:?}: {error:?}")]
+    Pack {
+        /// The directory being packed.
+        directory: PathBuf,
+        /// What went wrong.
+        error: IncrementalPackError,
+    },
+
+    /// Failed to write the archive.
+    #[error("Failed to write archive {path:?}: {error}")]
+    Write {
+        /// Path of the archive.
+        path: PathBuf,
+        /// What went wrong.
+        error: io::Error,
+    },
+
+    /// Failed to open the archive.
+    #[error("Failed to open archive {path:?}: {error:?}")]
+    Open {
+        /// Path of the archive.
+        path: PathBuf,
+        /// What went wrong.
+        error: FileProviderError,
+    },
+
+    /// The archive is not a valid Nx archive.
+    #[error("Failed to read archive {path:?}: {error:?}")]
+    Read {
+        /// Path of the archive.
+        path: PathBuf,
+        /// What went wrong.
+        error: ArchiveReadError,
+    },
+
+    /// Failed to extract the files of the archive.
+    #[error("Failed to extract archive {path:?} to {directory:?}: {error}")]
+    Extract {
+        /// Path of the archive.
+        path: PathBuf,
+        /// The directory being extracted to.
+        directory: PathBuf,
+        /// What went wrong.
+        error: DirectorySyncError,
+    },
+}
+
+/// Packs all files within a directory (including subdirectories) into an archive.
+///
+/// This is a shortcut for [`NxPackerBuilder`] with a preset and otherwise default settings.
+/// Use the builder directly for more control, e.g. over dictionaries or deduplication.
+///
+/// # Arguments
+///
+/// * `source` - The directory to pack. Paths in the archive are relative to it.
+/// * `destination` - Path of the archive to write. Replaced if it exists.
+/// * `preset` - The compression settings to use.
+///
+/// # Returns
+///
+/// Statistics about the packed files.
+///
+/// # Remarks
+///
+/// The archive is written to a temporary file next to `destination` first, which then
+/// replaces it; a failed pack never leaves a partial archive behind.
+pub fn pack_directory(
+    source: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+    preset: PackerPreset,
+) -> Result<IncrementalStats, OneShotError> {
+    let destination = destination.as_ref();
+    let (archive, stats) = pack_to_memory(source.as_ref(), preset)?;
+    write_replacing(destination, &archive).map_err(|error| OneShotError::Write {
+        path: destination.into(),
+        error,
+    })?;
+    Ok(stats)
+}
+
+/// Packs a directory like [`pack_directory`], and also writes an [`ArchiveSidecar`]
+/// next to the archive (`archive.nx` => `archive.nx.nxsum`), for publishing on mirrors.
+///
+/// # Arguments
+///
+/// * `source` - The directory to pack. Paths in the archive are relative to it.
+/// * `destination` - Path of the archive to write. Replaced if it exists.
+/// * `preset` - The compression settings to use.
+///
+/// # Returns
+///
+/// Statistics about the packed files, and the sidecar which was written.
+/// Attach a signature with [`ArchiveSidecar::with_signature`] and save it again if needed.
+///
+/// # Remarks
+///
+/// The sidecar is written after the archive, so a mirror never sees a sidecar
+/// for an archive which does not exist yet.
+pub fn pack_directory_with_sidecar(
+    source: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+    preset: PackerPreset,
+) -> Result<(IncrementalStats, ArchiveSidecar), OneShotError> {
+    let destination = destination.as_ref();
+    let (archive, stats) = pack_to_memory(source.as_ref(), preset)?;
+    let sidecar = ArchiveSidecar::from_archive(&archive).map_err(|error| OneShotError::Read {
+        path: destination.into(),
+        error,
+    })?;
+
+    write_replacing(destination, &archive).map_err(|error| OneShotError::Write {
+        path: destination.into(),
+        error,
+    })?;
+    let sidecar_path = ArchiveSidecar::path_for(destination);
+    write_replacing(&sidecar_path, sidecar.to_text().as_bytes()).map_err(|error| {
+        OneShotError::Write {
+            path: sidecar_path,
+            error,
+        }
+    })?;
+    Ok((stats, sidecar))
+}
+
+/// Packs a directory into an in-memory archive, see [`pack_directory`].
+fn pack_to_memory(
+    source: &Path,
+    preset: PackerPreset,
+) -> Result<(Vec<u8>, IncrementalStats), OneShotError> {
+    let mut builder = NxPackerBuilder::new_with_preset(preset);
+    builder
+        .add_folder(&source.to_string_lossy())
+        .map_err(|error| OneShotError::AddFiles {
+            directory: source.into(),
+            error,
+        })?;
+
+    let pack_error = |error: IncrementalPackError| OneShotError::Pack {
+        directory: source.into(),
+        error,
+    };
+    let empty = NxRawArchiveWriter::new(builder.settings.chunk_size)
+        .build()
+        .map_err(|error| pack_error(error.into()))?;
+    let empty = open_slice(&empty).map_err(|error| pack_error(error.into()))?;
+    builder.pack_incremental(&empty).map_err(pack_error)
+}
+
+/// Extracts all files of an archive into a directory.
+///
+/// This is a shortcut for opening the archive with [`NxArchiveReader`] and calling
+/// [`extract_to_dir`] with the default [`ExtractOptions`]: existing files are overwritten,
+/// other files in the directory are kept, and free space is checked before extracting.
+///
+/// # Arguments
+///
+/// * `source` - Path of the archive.
+/// * `destination` - The directory to extract to. Created if it does not exist.
+///
+/// # Returns
+///
+/// What was changed in the directory.
+pub fn extract_archive(
+    source: impl AsRef<Path>,
+    destination: impl AsRef<Path>,
+) -> Result<SyncToDirStats, OneShotError> {
+    let source = source.as_ref();
+    let destination = destination.as_ref();
+
+    let provider = FromFilePathProvider::new(&source.to_string_lossy()).map_err(|error| {
+        OneShotError::Open {
+            path: source.into(),
+            error,
+        }
+    })?;
+    let reader = NxArchiveReader::new(unsize_box2!(Box::new(provider))).map_err(|error| {
+        OneShotError::Read {
+            path: source.into(),
+            error,
+        }
+    })?;
+
+    extract_to_dir(&reader, destination, &ExtractOptions::default()).map_err(|error| {
+        OneShotError::Extract {
+            path: source.into(),
+            directory: destination.into(),
+            error,
+        }
+    })
+}
+
+fn open_slice(archive: &[u8]) -> Result<NxArchiveReader<'_>, ArchiveReadError> {
+    let provider = Box::new(FromSliceReferenceProvider::new(archive));
+    NxArchiveReader::new(unsize_box2!(provider))
+}
+
+/// Writes a file via a temporary file next to it, so it is never left partially written.
+fn write_replacing(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = path.with_file_name(temp_name);
+    fs::write(&temp_path, data)?;
+    if let Err(error) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::archive_sidecar::SidecarCheck;
+    use tempfile::tempdir;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn packs_and_extracts_directory() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("source");
+        fs::create_dir_all(source.join("data")).unwrap();
+        fs::write(source.join("a.txt"), b"Hello").unwrap();
+        fs::write(source.join("data/b.bin"), b"Nx archives!").unwrap();
+
+        let archive = dir.path().join("archive.nx");
+        let stats = pack_directory(&source, &archive, PackerPreset::GameBulkLoad).unwrap();
+        assert_eq!(stats.compressed_files, 2);
+
+        let target = dir.path().join("target");
+        extract_archive(&archive, &target).unwrap();
+        assert_eq!(fs::read(target.join("a.txt")).unwrap(), b"Hello");
+        assert_eq!(
+            fs::read(target.join("data/b.bin")).unwrap(),
+            b"Nx archives!"
+        );
+
+        let (_, sidecar) =
+            pack_directory_with_sidecar(&source, &archive, PackerPreset::GameBulkLoad).unwrap();
+        assert_eq!(ArchiveSidecar::load(&archive).unwrap(), sidecar);
+        sidecar.verify_file(&archive, SidecarCheck::Full).unwrap();
+
+        let missing = extract_archive(dir.path().join("missing.nx"), &target);
+        assert!(matches!(missing, Err(OneShotError::Open { .. })));
+    }
+}
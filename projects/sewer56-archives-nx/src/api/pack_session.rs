@@ -0,0 +1,594 @@
+use crate::api::{
+    archive_reader::{ArchiveReadError, NxArchiveReader},
+    filedata::*,
+    incremental_pack::{hash_file, pack_incremental, IncrementalPackError, IncrementalStats},
+    packing::{packer_file::PackerFile, packing_settings::PackingSettings},
+    raw_archive_writer::{NxRawArchiveWriter, RawArchiveWriteError},
+    traits::*,
+};
+use crate::headers::managed::{FileEntry, InsufficientDataError};
+use crate::utilities::io::slice_reader::SliceReader;
+use crate::{prelude::*, unsize_box2};
+use alloc::format;
+use alloc::string::String;
+use hashbrown::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror_no_std::Error;
+
+/// Identifies a file written by [`PackSession::save`].
+const SESSION_MAGIC: [u8; 4] = *b"NXPS";
+
+/// Version of the session file format.
+const SESSION_VERSION: u32 = 2;
+
+/// Errors that can occur when using a [`PackSession`].
+#[derive(Debug, Error)]
+pub enum PackSessionError {
+    /// Failed to pack the next batch of files.
+    #[error("Failed to pack files: {0:?}")]
+    Pack(#[from] IncrementalPackError),
+
+    /// Failed to read a packed batch.
+    #[error("Failed to read archive: {0:?}")]
+    Read(#[from] ArchiveReadError),
+
+    /// Failed to combine the packed batches.
+    #[error(transparent)]
+    Write(#[from] RawArchiveWriteError),
+
+    /// Failed to open one of the files being packed.
+    #[error(transparent)]
+    FileProvider(#[from] FileProviderError),
+
+    /// Failed to read or write the session file, or a packed batch.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// The session file is truncated, or was not written by [`PackSession::save`].
+    #[error("Invalid or corrupted session file")]
+    InvalidSession,
+}
+
+impl From<InsufficientDataError> for PackSessionError {
+    fn from(_: InsufficientDataError) -> Self {
+        Self::InvalidSession
+    }
+}
+
+/// A file added to a [`PackSession`], which has not been packed yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionFile {
+    /// Path of the file on disk.
+    pub source_path: PathBuf,
+
+    /// Path the file should have within the archive.
+    pub relative_path: String,
+}
+
+/// A file whose data is taken from a file packed in an earlier batch.
+struct ReusedFile {
+    /// Path the file should have within the archive.
+    relative_path: String,
+
+    /// Index of the batch holding the file's data.
+    batch: u32,
+
+    /// Location of the file's data; the block indices are relative to the batch.
+    entry: FileEntry,
+}
+
+/// Packs a set of files in batches, which can be spread across multiple runs of a process;
+/// for example, packs too large to finish before a reboot, or packs done a little at a time
+/// by a scheduled job.
+///
+/// Each call to [`Self::pack_next`] compresses some of the remaining files into a new batch,
+/// written to the spool directory. Between calls, the session can be written to disk with
+/// [`Self::save`], and resumed with [`Self::load`]. [`Self::finish`] combines the batches
+/// into the final archive.
+///
+/// # Remarks
+///
+/// Each batch is stored as an archive of its own, named `batch-<index>.nx`, so packing a
+/// batch only writes the new blocks. The session file holds the files left to pack and
+/// the deduplicated files; it does not contain any compressed data.
+///
+/// Files identical (by size and hash) to a file of an earlier batch are deduplicated
+/// against it; the hashes of the packed files are read from the batches, so they are not
+/// stored separately.
+///
+/// Files are read when they are packed, not when they are added; files changed in between
+/// are packed as they are at that time.
+///
+/// The batches always store the file paths, which are needed to combine them;
+/// if [`PackingSettings::store_file_paths`] is disabled, they are left out by [`Self::finish`].
+pub struct PackSession {
+    settings: PackingSettings,
+
+    /// Directory the batches are written to.
+    spool_dir: PathBuf,
+
+    /// Number of blocks in each packed batch.
+    batch_blocks: Vec<u32>,
+
+    /// Files deduplicated against a file of an earlier batch.
+    reused: Vec<ReusedFile>,
+
+    /// Maps the size and hash of each packed file to its batch and entry, if deduplication is enabled.
+    packed_files: HashMap<(u64, u64), (u32, FileEntry)>,
+
+    /// Sizes of the files in [`Self::packed_files`]; files of other sizes are not hashed.
+    packed_sizes: HashSet<u64>,
+
+    /// Files which have not been packed yet, in the order they were added.
+    pending: Vec<SessionFile>,
+}
+
+impl PackSession {
+    /// Creates a new, empty session.
+    ///
+    /// # Arguments
+    ///
+    /// * `spool_dir` - Directory the packed batches are written to. Created if missing.
+    /// * `settings` - Settings used to compress the files.
+    pub fn new(
+        spool_dir: impl Into<PathBuf>,
+        mut settings: PackingSettings,
+    ) -> Result<Self, PackSessionError> {
+        settings.sanitize();
+        let spool_dir = spool_dir.into();
+        fs::create_dir_all(&spool_dir)?;
+        Ok(Self {
+            settings,
+            spool_dir,
+            batch_blocks: Vec::new(),
+            reused: Vec::new(),
+            packed_files: HashMap::new(),
+            packed_sizes: HashSet::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Resumes a session previously written with [`Self::save`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the session file.
+    /// * `settings` - Settings used to compress the remaining files.
+    ///   Settings are not stored in the session, and should match those it was created with;
+    ///   the chunk size and hash seed are always taken from the session.
+    ///
+    /// # Remarks
+    ///
+    /// The batches packed before the session was saved must still be in its spool directory.
+    /// Batches packed after it was saved are packed again.
+    pub fn load(path: &Path, mut settings: PackingSettings) -> Result<Self, PackSessionError> {
+        settings.sanitize();
+        let data = fs::read(path)?;
+        let mut reader = SliceReader::new(&data);
+        if reader.read_array::<4>()? != SESSION_MAGIC || reader.read_u32()? != SESSION_VERSION {
+            return Err(PackSessionError::InvalidSession);
+        }
+
+        // Batches must use the chunk size and hash seed of the files packed so far,
+        // to be combined with them.
+        settings.chunk_size = reader.read_u32()?;
+        settings.hash_seed = reader.read_u64()?;
+        let spool_dir = PathBuf::from(read_string(&mut reader)?);
+        let batch_count = reader.read_u32()?;
+
+        let reused_count = reader.read_u32()?;
+        let mut reused = Vec::with_capacity(reused_count.min(u16::MAX as u32) as usize);
+        for _ in 0..reused_count {
+            let relative_path = read_string(&mut reader)?;
+            let batch = reader.read_u32()?;
+            if batch >= batch_count {
+                return Err(PackSessionError::InvalidSession);
+            }
+
+            reused.push(ReusedFile {
+                relative_path,
+                batch,
+                entry: read_entry(&mut reader)?,
+            });
+        }
+
+        let pending_count = reader.read_u32()?;
+        let mut pending = Vec::with_capacity(pending_count.min(u16::MAX as u32) as usize);
+        for _ in 0..pending_count {
+            let source_path = read_string(&mut reader)?;
+            let relative_path = read_string(&mut reader)?;
+            pending.push(SessionFile {
+                source_path: PathBuf::from(source_path),
+                relative_path,
+            });
+        }
+
+        let mut session = Self {
+            settings,
+            spool_dir,
+            batch_blocks: Vec::new(),
+            reused,
+            packed_files: HashMap::new(),
+            packed_sizes: HashSet::new(),
+            pending,
+        };
+        for batch in 0..batch_count {
+            let packed = open_batch(&session.spool_dir, batch)?;
+            session.index_batch(batch, &packed);
+            session.batch_blocks.push(packed.block_count());
+        }
+
+        Ok(session)
+    }
+
+    /// Writes the session to disk, so it can be resumed with [`Self::load`].
+    ///
+    /// The session is written to a temporary file next to `path`, which then replaces it;
+    /// a crash while saving therefore leaves the previously saved session intact.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the session file.
+    ///
+    /// # Remarks
+    ///
+    /// Paths on disk which are not valid UTF-8 are stored lossily.
+    pub fn save(&self, path: &Path) -> Result<(), PackSessionError> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&SESSION_MAGIC);
+        data.extend_from_slice(&SESSION_VERSION.to_le_bytes());
+        data.extend_from_slice(&self.settings.chunk_size.to_le_bytes());
+        data.extend_from_slice(&self.settings.hash_seed.to_le_bytes());
+        write_string(&mut data, &self.spool_dir.to_string_lossy());
+        data.extend_from_slice(&(self.batch_blocks.len() as u32).to_le_bytes());
+
+        data.extend_from_slice(&(self.reused.len() as u32).to_le_bytes());
+        for file in &self.reused {
+            write_string(&mut data, &file.relative_path);
+            data.extend_from_slice(&file.batch.to_le_bytes());
+            write_entry(&mut data, &file.entry);
+        }
+
+        data.extend_from_slice(&(self.pending.len() as u32).to_le_bytes());
+        for file in &self.pending {
+            write_string(&mut data, &file.source_path.to_string_lossy());
+            write_string(&mut data, &file.relative_path);
+        }
+
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(format!(".{}.tmp", std::process::id()));
+        let temp_path = path.with_file_name(temp_name);
+        fs::write(&temp_path, &data[..])?;
+        if let Err(error) = fs::rename(&temp_path, path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(error.into());
+        }
+
+        Ok(())
+    }
+
+    /// Adds a file to pack in a later call to [`Self::pack_next`].
+    ///
+    /// # Arguments
+    ///
+    /// * `source_path` - Path of the file on disk.
+    /// * `relative_path` - Path the file should have within the archive.
+    pub fn add_file(&mut self, source_path: impl Into<PathBuf>, relative_path: &str) {
+        self.pending.push(SessionFile {
+            source_path: source_path.into(),
+            relative_path: String::from(relative_path),
+        });
+    }
+
+    /// Returns the files which have not been packed yet.
+    pub fn pending_files(&self) -> &[SessionFile] {
+        &self.pending
+    }
+
+    /// Returns true if all added files have been packed.
+    pub fn is_finished(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Returns the number of batches packed so far.
+    pub fn batch_count(&self) -> u32 {
+        self.batch_blocks.len() as u32
+    }
+
+    /// Packs the next batch of files, in the order they were added.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_bytes` - Total size of the files to pack. At least one file is packed,
+    ///   however large, if any are left.
+    ///
+    /// # Returns
+    ///
+    /// Statistics on the packed batch. Files deduplicated against previous batches are
+    /// counted as reused. Block indices are those of the finished archive.
+    pub fn pack_next(&mut self, max_bytes: u64) -> Result<IncrementalStats, PackSessionError> {
+        // Open the next batch of files.
+        let mut files: Vec<PackerFile<'static>> = Vec::new();
+        let mut batch_bytes = 0;
+        for file in &self.pending {
+            if !files.is_empty() && batch_bytes >= max_bytes {
+                break;
+            }
+
+            let provider = Box::new(FromFilePathProvider::new(
+                &file.source_path.to_string_lossy(),
+            )?);
+            let size = provider.file_size()? as u64;
+            files.push(PackerFile::new(
+                file.relative_path.clone(),
+                size,
+                unsize_box2!(provider),
+            ));
+            batch_bytes += size;
+        }
+
+        // Files identical to a packed one point at its data, rather than being compressed again.
+        let mut stats = IncrementalStats::default();
+        let mut reused = Vec::new();
+        let mut remaining = Vec::with_capacity(files.len());
+        for file in files {
+            let size = file.file_size();
+            let existing = match self.packed_sizes.contains(&size) {
+                true => self
+                    .packed_files
+                    .get(&(size, hash_file(&file, size, self.settings.hash_seed)?)),
+                false => None,
+            };
+
+            match existing {
+                Some((batch, entry)) => {
+                    reused.push(ReusedFile {
+                        relative_path: String::from(file.relative_path()),
+                        batch: *batch,
+                        entry: *entry,
+                    });
+                    stats.reused_files += 1;
+                    stats.reused_bytes += size;
+                }
+                None => remaining.push(file),
+            }
+        }
+
+        let batch_count = reused.len() + remaining.len();
+        if !remaining.is_empty() {
+            let empty = NxRawArchiveWriter::new(self.settings.chunk_size)
+                .with_hash_seed(self.settings.hash_seed)
+                .build()?;
+            // The batches are combined by `finish`, which needs their paths.
+            let mut batch_settings = self.settings.clone();
+            batch_settings.store_file_paths = true;
+            let (batch, mut batch_stats) =
+                pack_incremental(&open(&empty)?, &remaining, &batch_settings)?;
+            let block_offset: u32 = self.batch_blocks.iter().sum();
+            for comparison in &mut batch_stats.block_comparisons {
+                comparison.block_index += block_offset;
+            }
+
+            let batch_index = self.batch_count();
+            fs::write(batch_path(&self.spool_dir, batch_index), &batch[..])?;
+            let packed = open(&batch)?;
+            self.index_batch(batch_index, &packed);
+            self.batch_blocks.push(packed.block_count());
+            stats = IncrementalStats {
+                reused_files: stats.reused_files,
+                reused_bytes: stats.reused_bytes,
+                ..batch_stats
+            };
+        }
+
+        self.reused.extend(reused);
+        self.pending.drain(..batch_count);
+        Ok(stats)
+    }
+
+    /// Packs all remaining files, and combines the batches into the finished archive.
+    ///
+    /// The batches are removed from the spool directory once combined.
+    pub fn finish(mut self) -> Result<Vec<u8>, PackSessionError> {
+        self.pack_next(u64::MAX)?;
+
+        let mut writer = NxRawArchiveWriter::new(self.settings.chunk_size)
+            .with_hash_seed(self.settings.hash_seed)
+            .with_string_pool_compression(self.settings.string_pool_compression)
+            .with_file_hashes(self.settings.store_file_hashes)
+            .with_file_paths(self.settings.store_file_paths);
+        if let Some(bits) = self.settings.path_filter_bits {
+            writer = writer.with_path_filter(bits);
+        }
+        if self.settings.store_directory_index {
+            writer = writer.with_directory_index();
+        }
+
+        let mut first_blocks = Vec::with_capacity(self.batch_blocks.len());
+        for batch in 0..self.batch_count() {
+            first_blocks.push(writer.block_count());
+            writer.append_archive(&open_batch(&self.spool_dir, batch)?)?;
+        }
+
+        for file in &self.reused {
+            let mut entry = file.entry;
+            entry.first_block_index += first_blocks[file.batch as usize];
+            writer.add_file(&file.relative_path, entry);
+        }
+
+        let archive = writer.build()?;
+        for batch in 0..self.batch_count() {
+            let _ = fs::remove_file(batch_path(&self.spool_dir, batch));
+        }
+
+        Ok(archive)
+    }
+
+    /// Adds the files of a packed batch to [`Self::packed_files`], if deduplication is enabled.
+    fn index_batch(&mut self, batch: u32, packed: &NxArchiveReader) {
+        let settings = &self.settings;
+        let enabled = settings.enable_solid_deduplication || settings.enable_chunked_deduplication;
+        if !enabled || !packed.has_file_hashes() {
+            return;
+        }
+
+        for entry in packed.entries() {
+            self.packed_sizes.insert(entry.decompressed_size);
+            self.packed_files
+                .entry((entry.decompressed_size, entry.hash))
+                .or_insert((batch, *entry));
+        }
+    }
+}
+
+fn batch_path(spool_dir: &Path, batch: u32) -> PathBuf {
+    spool_dir.join(format!("batch-{batch}.nx"))
+}
+
+fn open_batch(spool_dir: &Path, batch: u32) -> Result<NxArchiveReader<'static>, PackSessionError> {
+    let provider = Box::new(FromFilePathProvider::new(
+        &batch_path(spool_dir, batch).to_string_lossy(),
+    )?);
+    Ok(NxArchiveReader::new(unsize_box2!(provider))?)
+}
+
+fn open(archive: &[u8]) -> Result<NxArchiveReader<'_>, ArchiveReadError> {
+    let provider = Box::new(FromSliceReferenceProvider::new(archive));
+    NxArchiveReader::new(unsize_box2!(provider))
+}
+
+fn write_string(data: &mut Vec<u8>, value: &str) {
+    data.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    data.extend_from_slice(value.as_bytes());
+}
+
+fn read_string(reader: &mut SliceReader) -> Result<String, PackSessionError> {
+    let len = reader.read_u32()? as usize;
+    let bytes = reader.read_bytes(len)?;
+    core::str::from_utf8(bytes)
+        .map(String::from)
+        .map_err(|_| PackSessionError::InvalidSession)
+}
+
+fn write_entry(data: &mut Vec<u8>, entry: &FileEntry) {
+    data.extend_from_slice(&entry.hash.to_le_bytes());
+    data.extend_from_slice(&entry.decompressed_size.to_le_bytes());
+    data.extend_from_slice(&entry.decompressed_block_offset.to_le_bytes());
+    data.extend_from_slice(&entry.file_path_index.to_le_bytes());
+    data.extend_from_slice(&entry.first_block_index.to_le_bytes());
+}
+
+fn read_entry(reader: &mut SliceReader) -> Result<FileEntry, InsufficientDataError> {
+    Ok(FileEntry {
+        hash: reader.read_u64()?,
+        decompressed_size: reader.read_u64()?,
+        decompressed_block_offset: reader.read_u32()?,
+        file_path_index: reader.read_u32()?,
+        first_block_index: reader.read_u32()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn resumes_saved_session() {
+        let dir = tempdir().unwrap();
+        let session_path = dir.path().join("pack.session");
+        for (name, data) in [("a.txt", "Hello"), ("b.txt", "World"), ("c.txt", "Hello")] {
+            fs::write(dir.path().join(name), data).unwrap();
+        }
+
+        let mut session =
+            PackSession::new(dir.path().join("spool"), PackingSettings::new()).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            session.add_file(dir.path().join(name), name);
+        }
+        session.pack_next(1).unwrap();
+        assert_eq!(session.pending_files().len(), 2);
+        session.save(&session_path).unwrap();
+        drop(session);
+
+        let mut session = PackSession::load(&session_path, PackingSettings::new()).unwrap();
+        assert_eq!(session.pending_files()[0].relative_path, "b.txt");
+        session.pack_next(1).unwrap();
+
+        // c.txt matches a.txt, which was packed before the session was saved.
+        let stats = session.pack_next(1).unwrap();
+        assert_eq!(stats.reused_files, 1);
+        assert!(session.is_finished());
+
+        let archive = session.finish().unwrap();
+        let reader = open(&archive).unwrap();
+        assert_eq!(reader.entries().len(), 3);
+        assert_eq!(reader.block_count(), 2);
+        assert_eq!(reader.read_file("c.txt").unwrap().as_slice(), b"Hello");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn paths_are_removed_when_finished() {
+        let dir = tempdir().unwrap();
+        for (name, data) in [("a.txt", "Hello"), ("b.txt", "World")] {
+            fs::write(dir.path().join(name), data).unwrap();
+        }
+
+        let mut settings = PackingSettings::new();
+        settings.store_file_paths = false;
+        let mut session = PackSession::new(dir.path().join("spool"), settings).unwrap();
+        for name in ["a.txt", "b.txt"] {
+            session.add_file(dir.path().join(name), name);
+        }
+        session.pack_next(1).unwrap();
+        session.pack_next(1).unwrap();
+
+        let archive = session.finish().unwrap();
+        let reader = open(&archive).unwrap();
+        assert!(reader.find_entry("a.txt").unwrap().is_none());
+        let entry = reader.entries()[1];
+        assert_eq!(reader.file_path(&entry).unwrap(), None);
+        assert_eq!(
+            reader.find_by_hash(entry.hash)[0].entry.decompressed_size,
+            5
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn batches_are_spooled_to_disk() {
+        let dir = tempdir().unwrap();
+        let spool_dir = dir.path().join("spool");
+        let session_path = dir.path().join("pack.session");
+        let data: Vec<u8> = (0..65536u32)
+            .map(|x| (x.wrapping_mul(2654435761) >> 24) as u8)
+            .collect();
+        fs::write(dir.path().join("a.bin"), &data[..]).unwrap();
+        fs::write(dir.path().join("b.txt"), "Hello").unwrap();
+
+        let mut session = PackSession::new(&spool_dir, PackingSettings::new()).unwrap();
+        for name in ["a.bin", "b.txt"] {
+            session.add_file(dir.path().join(name), name);
+        }
+        session.pack_next(1).unwrap();
+        session.pack_next(1).unwrap();
+        session.save(&session_path).unwrap();
+        assert_eq!(session.batch_count(), 2);
+        assert!(spool_dir.join("batch-0.nx").exists());
+        assert!(spool_dir.join("batch-1.nx").exists());
+
+        // The compressed data lives in the batches, not the session file.
+        assert!(fs::metadata(&session_path).unwrap().len() < 1024);
+
+        let session = PackSession::load(&session_path, PackingSettings::new()).unwrap();
+        let archive = session.finish().unwrap();
+        let reader = open(&archive).unwrap();
+        assert_eq!(reader.read_file("a.bin").unwrap().as_slice(), &data[..]);
+        assert_eq!(reader.read_file("b.txt").unwrap().as_slice(), b"Hello");
+        assert!(!spool_dir.join("batch-0.nx").exists());
+    }
+}
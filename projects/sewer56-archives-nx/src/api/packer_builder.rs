@@ -1,11 +1,39 @@
-use super::{enums::*, filedata::*, packing::packer_file::PackerFile, traits::*};
+use super::{
+    archive_reader::{ArchiveReadError, NxArchiveReader},
+    checksum_manifest::ChecksumManifest,
+    enums::*,
+    filedata::*,
+    incremental_pack::{pack_incremental, IncrementalPackError, IncrementalStats},
+    packing::{
+        compression_stats::{
+            sample_compression_stats, CompressionStats, CompressionStatsError,
+            StatsSamplingSettings,
+        },
+        directory_rule::DirectoryRule,
+        pack_diagnostics::{normalize_path, DropReason, PackDiagnostics, PackWarning},
+        pack_plan::{create_plan, PackPlan},
+        packer_file::PackerFile,
+    },
+    path_aliases::PathAliases,
+    traits::*,
+};
 use crate::{
-    api::packing::packing_settings::PackingSettings, utilities::io::file_finder::find_files,
+    api::packing::packing_settings::{CompressionCandidate, PackingSettings},
+    headers::parser::StringPoolCompression,
+    implementation::pack::state::spilled_hash_index::DedupMemoryLimit,
+    utilities::arrange::pack::placement::{FilePlacement, PlacementReason},
+    utilities::compression::{copy_fallback::CopyFallback, zstd_advanced::ZstdAdvancedParams},
+    utilities::io::file_finder::find_files_with_policy,
 };
 use crate::{prelude::*, unsize_box2};
+use alloc::borrow::Cow;
+use alloc::format;
 use alloc::string::String;
 use core::marker::PhantomData;
+use core::ops::RangeInclusive;
+use hashbrown::HashMap;
 use std::io::{Read, Seek};
+use std::path::PathBuf;
 
 /// A builder pattern implementation for creating NX archives.
 ///
@@ -37,6 +65,33 @@ pub struct NxPackerBuilder<'a> {
     /// Collection of files to be included in the archive.
     pub files: Vec<PackerFile<'a>>,
 
+    /// Rules applied to files as they are added, in order of addition.
+    pub directory_rules: Vec<DirectoryRule>,
+
+    /// What to do when a file is added at a path which is already taken.
+    pub collision_policy: PathCollisionPolicy,
+
+    /// Paths which were added more than once, in order of occurrence.
+    collisions: Vec<String>,
+
+    /// Index into [`Self::files`] of each path added through the builder's methods.
+    path_indices: HashMap<String, usize>,
+
+    /// Transforms the path of each file added through the builder's methods.
+    path_transform: Option<Box<dyn Fn(&str) -> Cow<'_, str> + 'a>>,
+
+    /// Warnings raised while adding files, see [`Self::diagnostics`].
+    diagnostics: PackDiagnostics,
+
+    /// How files added from disk are opened, see [`Self::with_file_handle_policy`].
+    file_handle_policy: FileHandlePolicy,
+
+    /// How failed reads of added files are retried, see [`Self::with_retry_policy`].
+    retry_policy: Option<RetryPolicy>,
+
+    /// Directory for temporary files, see [`Self::with_temp_dir`].
+    temp_dir: Option<PathBuf>,
+
     /// Phantom data to track the lifetime of referenced slices
     _phantom: PhantomData<&'a [u8]>,
 }
@@ -55,6 +110,15 @@ impl<'a> NxPackerBuilder<'a> {
         Self {
             settings: PackingSettings::default(),
             files: Vec::new(),
+            directory_rules: Vec::new(),
+            collision_policy: PathCollisionPolicy::default(),
+            collisions: Vec::new(),
+            path_indices: HashMap::new(),
+            path_transform: None,
+            diagnostics: PackDiagnostics::default(),
+            file_handle_policy: FileHandlePolicy::default(),
+            retry_policy: None,
+            temp_dir: None,
             _phantom: PhantomData,
         }
     }
@@ -68,6 +132,15 @@ impl<'a> NxPackerBuilder<'a> {
         Self {
             settings,
             files: Vec::new(),
+            directory_rules: Vec::new(),
+            collision_policy: PathCollisionPolicy::default(),
+            collisions: Vec::new(),
+            path_indices: HashMap::new(),
+            path_transform: None,
+            diagnostics: PackDiagnostics::default(),
+            file_handle_policy: FileHandlePolicy::default(),
+            retry_policy: None,
+            temp_dir: None,
             _phantom: PhantomData,
         }
     }
@@ -91,8 +164,16 @@ impl<'a> NxPackerBuilder<'a> {
         file_path: &str,
         options: AddFileParams,
     ) -> Result<&mut Self, FileProviderError> {
-        let file = PackerFile::from_file_path_with_unknown_size(file_path, options.relative_path)?;
-        self.files.push(file);
+        let file = PackerFile::from_file_path_with_policy(
+            file_path,
+            options.relative_path,
+            &self.file_handle_policy,
+        )?
+        .with_compression(options.compression_preference)
+        .with_solid(options.solid_type)
+        .with_own_block(options.force_own_block)
+        .with_chunk_size_override(options.chunk_size_override);
+        self.push_file(file);
         Ok(self)
     }
 
@@ -118,9 +199,11 @@ impl<'a> NxPackerBuilder<'a> {
             unsize_box2!(provider),
         )
         .with_compression(options.compression_preference)
-        .with_solid(options.solid_type);
+        .with_solid(options.solid_type)
+        .with_own_block(options.force_own_block)
+        .with_chunk_size_override(options.chunk_size_override);
 
-        self.files.push(file);
+        self.push_file(file);
         self
     }
 
@@ -143,9 +226,11 @@ impl<'a> NxPackerBuilder<'a> {
         let provider = Box::new(FromBoxedSliceProvider::new(data));
         let file = PackerFile::new(options.relative_path, len as u64, unsize_box2!(provider))
             .with_compression(options.compression_preference)
-            .with_solid(options.solid_type);
+            .with_solid(options.solid_type)
+            .with_own_block(options.force_own_block)
+            .with_chunk_size_override(options.chunk_size_override);
 
-        self.files.push(file);
+        self.push_file(file);
         self
     }
 
@@ -169,12 +254,50 @@ impl<'a> NxPackerBuilder<'a> {
         let provider = Box::new(FromStreamProvider::new(stream));
         let file = PackerFile::new(options.relative_path, length, unsize_box2!(provider))
             .with_compression(options.compression_preference)
-            .with_solid(options.solid_type);
+            .with_solid(options.solid_type)
+            .with_own_block(options.force_own_block)
+            .with_chunk_size_override(options.chunk_size_override);
 
-        self.files.push(file);
+        self.push_file(file);
         self
     }
 
+    /// Adds a file from a stream which can't be seeked (e.g. a pipe or a download), by
+    /// copying it to a temporary file in [`Self::temp_dir`] first. The stream is read
+    /// until its end.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The stream containing the file data.
+    /// * `options` - Parameters controlling how the file should be packed.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the temporary file can't be created, or the stream can't be read.
+    pub fn add_file_from_unseekable_stream(
+        &mut self,
+        stream: impl Read,
+        options: AddFileParams,
+    ) -> Result<&mut Self, FileProviderError> {
+        let provider = Box::new(SpooledStreamProvider::new(stream, &self.temp_dir())?);
+        let file = PackerFile::new(
+            options.relative_path,
+            provider.len(),
+            unsize_box2!(provider),
+        )
+        .with_compression(options.compression_preference)
+        .with_solid(options.solid_type)
+        .with_own_block(options.force_own_block)
+        .with_chunk_size_override(options.chunk_size_override);
+
+        self.push_file(file);
+        Ok(self)
+    }
+
     /// Adds all files under a given directory to the archive.
     ///
     /// Files will be added recursively, maintaining their relative paths.
@@ -192,171 +315,1048 @@ impl<'a> NxPackerBuilder<'a> {
     ///
     /// Returns an error if the directory cannot be accessed or if there are issues reading file metadata.
     pub fn add_folder(&mut self, folder: &str) -> Result<&mut Self, FileProviderError> {
-        find_files(folder, |file| self.files.push(file))?;
+        self.add_folder_with_options(folder, &AddFolderOptions::default())
+    }
+
+    /// Adds all files under a given directory to the archive, rewriting their paths.
+    ///
+    /// Same as [`Self::add_folder`], but each path (relative to `folder`) is first rewritten
+    /// as per `options`, see [`AddFolderOptions::apply`]. This happens before any other
+    /// path processing, e.g. [`Self::with_path_transform`] and sorting.
+    ///
+    /// # Arguments
+    ///
+    /// * `folder` - The directory to add files from.
+    /// * `options` - How to rewrite the paths of the files.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be accessed or if there are issues reading file metadata.
+    pub fn add_folder_with_options(
+        &mut self,
+        folder: &str,
+        options: &AddFolderOptions,
+    ) -> Result<&mut Self, FileProviderError> {
+        let mut found = Vec::new();
+        find_files_with_policy(folder, &self.file_handle_policy, |file| found.push(file))?;
+        for file in found {
+            let path = options.apply(file.relative_path());
+            self.push_file(file.with_relative_path(path));
+        }
+        Ok(self)
+    }
+
+    /// Adds all files of an existing Nx archive.
+    ///
+    /// The files are decompressed from the source archive while packing,
+    /// so no intermediate copy on disk is needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The archive to add files from. Must outlive the builder.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file paths of the archive cannot be read.
+    ///
+    /// # Remarks
+    ///
+    /// Files without a path (from archives packed without paths) are skipped,
+    /// and reported in [`Self::diagnostics`].
+    pub fn add_archive<'r: 'a>(
+        &mut self,
+        reader: &'a NxArchiveReader<'r>,
+    ) -> Result<&mut Self, ArchiveReadError> {
+        for entry in reader.entries() {
+            let Some(path) = reader.file_path(entry)? else {
+                self.diagnostics.report(PackWarning::FileDropped {
+                    path: None,
+                    reason: DropReason::MissingPath,
+                });
+                continue;
+            };
+
+            let provider = Box::new(FromArchiveEntryProvider::new(reader, *entry));
+            let file = PackerFile::new(
+                String::from(path),
+                entry.decompressed_size,
+                unsize_box2!(provider),
+            );
+            self.push_file(file);
+        }
+
         Ok(self)
     }
 
+    /// Sets how files added from disk (with [`Self::add_file`] or [`Self::add_folder`])
+    /// are opened, applying to files added afterwards.
+    ///
+    /// Keeping every file open is fastest, but can exhaust the handle limit of the process
+    /// when packing many small files; see [`FileHandlePolicy`].
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - When files are opened and closed.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_file_handle_policy(mut self, policy: FileHandlePolicy) -> Self {
+        self.file_handle_policy = policy;
+        self
+    }
+
+    /// Retries failed reads of files added afterwards, for sources where reads can
+    /// fail transiently (e.g. network shares), so one failed read doesn't abort the pack.
+    ///
+    /// Only errors for which [`FileProviderError::is_retryable`] returns true are retried.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - How many times, and how often, reads are retried.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Sets the directory temporary files are created in, e.g. by
+    /// [`Self::add_file_from_unseekable_stream`].
+    ///
+    /// Packing huge inputs can use a lot of temporary space; this allows moving it off a
+    /// small system drive.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory for temporary files. Must exist.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_temp_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = Some(dir.into());
+        self
+    }
+
+    /// Returns the directory temporary files are created in; the one set with
+    /// [`Self::with_temp_dir`], or [`std::env::temp_dir`] if none was set.
+    pub fn temp_dir(&self) -> PathBuf {
+        self.temp_dir.clone().unwrap_or_else(std::env::temp_dir)
+    }
+
+    /// Sets what happens when a file changes between being added and being packed,
+    /// e.g. because a build system is still writing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Whether packing fails, or packs the file's current contents.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_file_change_policy(mut self, policy: FileChangePolicy) -> Self {
+        self.settings.file_change_policy = policy;
+        self
+    }
+
+    /// Enables or disables breaking ties between files of the same size by their path,
+    /// rather than by the order they were added in.
+    ///
+    /// Files are arranged into blocks by size; when enabled, files of the same size are
+    /// arranged in path order, matching [`Self::plan`]. Adding the same files in a different
+    /// order with the same settings then produces the same archive. See
+    /// [`PackingSettings::stable_order`].
+    ///
+    /// # Arguments
+    ///
+    /// * `enable` - Whether to pack files in a stable order.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_stable_order(mut self, enable: bool) -> Self {
+        self.settings.stable_order = enable;
+        self
+    }
+
+    /// Pins files to blocks chosen by the caller, bypassing the automatic arrangement for them.
+    /// Useful when the layout is decided externally, e.g. to stream files in install order.
+    ///
+    /// Each inner list holds the paths of the files in one block, in order; the blocks are
+    /// written first, in the given order. Files not listed are arranged automatically.
+    /// Blocks are checked against the block size and [`Self::with_max_files_per_block`]
+    /// when packing; a block holding a single file larger than the block size is chunked.
+    ///
+    /// The manual blocks are not reflected in [`Self::plan`].
+    ///
+    /// # Arguments
+    ///
+    /// * `blocks` - Paths of the files in each block, as added to the builder.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_manual_block_assignment(mut self, blocks: Vec<Vec<String>>) -> Self {
+        self.settings.manual_blocks = blocks;
+        self
+    }
+
+    /// Sets what happens when a file is added at a path which is already taken.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Whether the new or the existing file is kept.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_collision_policy(mut self, policy: PathCollisionPolicy) -> Self {
+        self.collision_policy = policy;
+        self
+    }
+
+    /// Returns the paths which were added more than once, and resolved with
+    /// [`Self::collision_policy`]. A path is listed once for each extra time it was added.
+    pub fn collisions(&self) -> &[String] {
+        &self.collisions
+    }
+
+    /// Returns the warnings raised while adding files so far, e.g. for dropped files
+    /// or normalized paths. These are also included in the result of [`Self::pack_incremental`].
+    pub fn diagnostics(&self) -> &PackDiagnostics {
+        &self.diagnostics
+    }
+
+    /// Sets the compression and SOLID preferences of all files matching a pattern.
+    ///
+    /// Rules are applied as files are added, so they must be set before adding files.
+    /// They only fill in preferences left at their defaults; explicit preferences in
+    /// [`AddFileParams`] take priority. If multiple rules match a file, each preference is
+    /// taken from the most specific rule which sets it (see [`DirectoryRule::specificity`]);
+    /// between equally specific rules, the last one added wins.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - Pattern matched against paths within the archive, e.g. `textures/**`.
+    ///   See [`DirectoryRule`] for the syntax.
+    /// * `compression` - Preferred algorithm for matching files.
+    /// * `solid` - SOLID preference for matching files.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_directory_rule(
+        mut self,
+        pattern: &str,
+        compression: CompressionPreference,
+        solid: SolidPreference,
+    ) -> Self {
+        self.directory_rules
+            .push(DirectoryRule::new(pattern, compression, solid));
+        self
+    }
+
+    /// Sets a transform applied to the path of every file added afterwards,
+    /// e.g. to move files into a different folder, or to change their case.
+    ///
+    /// The transform runs before directory rules are matched and before
+    /// path collisions are detected, so both see the new path.
+    ///
+    /// # Arguments
+    ///
+    /// * `transform` - Maps the original path of a file to the path it is packed with.
+    ///   Return [`Cow::Borrowed`] to keep the path unchanged.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_path_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(&str) -> Cow<'_, str> + 'a,
+    {
+        self.path_transform = Some(unsize_box2!(Box::new(transform)));
+        self
+    }
+
+    /// Stores a path alias table in the archive, so readers can still find
+    /// moved files by their old paths.
+    ///
+    /// The table is packed as a file at [`PathAliases::FILE_PATH`]; the path transform
+    /// set with [`Self::with_path_transform`] is not applied to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `aliases` - Maps old paths to the paths the files have in this archive.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn add_path_aliases(&mut self, aliases: &PathAliases) -> &mut Self {
+        let data = aliases.to_bytes();
+        let file_size = data.len() as u64;
+        let provider = Box::new(FromBoxedSliceProvider::new(data.into_boxed_slice()));
+        let file = PackerFile::new(
+            String::from(PathAliases::FILE_PATH),
+            file_size,
+            unsize_box2!(provider),
+        );
+        self.insert_file(file);
+        self
+    }
+
+    fn push_file(&mut self, mut file: PackerFile<'a>) {
+        if let Some(transform) = &self.path_transform {
+            let renamed = match transform(file.relative_path()) {
+                Cow::Owned(path) => Some(path),
+                Cow::Borrowed(_) => None,
+            };
+
+            if let Some(path) = renamed {
+                file = file.with_relative_path(path);
+            }
+        }
+
+        if let Some(normalized) = normalize_path(file.relative_path()) {
+            self.diagnostics.report(PackWarning::PathNormalized {
+                original: String::from(file.relative_path()),
+                normalized: normalized.clone(),
+            });
+            file = file.with_relative_path(normalized);
+        }
+
+        self.insert_file(file);
+    }
+
+    fn insert_file(&mut self, mut file: PackerFile<'a>) {
+        if let Some(policy) = self.retry_policy {
+            file = file.with_retry_policy(policy);
+        }
+
+        let file = apply_directory_rules(&self.directory_rules, file);
+        match self.path_indices.get(file.relative_path()) {
+            Some(&index) => {
+                self.collisions.push(String::from(file.relative_path()));
+                self.diagnostics.report(PackWarning::FileDropped {
+                    path: Some(String::from(file.relative_path())),
+                    reason: DropReason::PathCollision,
+                });
+                if self.collision_policy == PathCollisionPolicy::Replace {
+                    self.files[index] = file;
+                }
+            }
+            None => {
+                self.path_indices
+                    .insert(String::from(file.relative_path()), self.files.len());
+                self.files.push(file);
+            }
+        }
+    }
+
     /// Sets the size of SOLID blocks used in the archive.
     ///
-    /// SOLID blocks combine multiple small files into a single compressed unit,
-    /// which can significantly improve compression ratios. The block size determines
-    /// the maximum size of these combined units.
+    /// SOLID blocks combine multiple small files into a single compressed unit,
+    /// which can significantly improve compression ratios. The block size determines
+    /// the maximum size of these combined units.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_size` - Size of SOLID blocks in bytes. Must be between
+    ///   [`MIN_BLOCK_SIZE`] and [`MAX_BLOCK_SIZE`]. The value will be
+    ///   automatically adjusted to the nearest power of 2 minus 1.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    ///
+    /// [`MIN_BLOCK_SIZE`]: crate::api::packing::packing_settings::MIN_BLOCK_SIZE
+    /// [`MAX_BLOCK_SIZE`]: crate::api::packing::packing_settings::MAX_BLOCK_SIZE
+    pub fn with_block_size(mut self, block_size: u32) -> Self {
+        self.settings.block_size = block_size;
+        self
+    }
+
+    /// Sets the size of chunks used when splitting large files.
+    ///
+    /// Large files are split into chunks to allow for parallel compression
+    /// and decompression. This setting controls the size of those chunks.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size` - Size of chunks in bytes. Must be between
+    ///   [`MIN_CHUNK_SIZE`] and [`MAX_CHUNK_SIZE`]. The value will be
+    ///   automatically adjusted to the nearest power of 2.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    ///
+    /// [`MIN_CHUNK_SIZE`]: crate::api::packing::packing_settings::MIN_CHUNK_SIZE
+    /// [`MAX_CHUNK_SIZE`]: crate::api::packing::packing_settings::MAX_CHUNK_SIZE
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
+        self.settings.chunk_size = chunk_size;
+        self
+    }
+
+    /// Lets the packer pick a SOLID block size for each group of files with the same
+    /// extension, based on the sizes of the files. The configured block size is used
+    /// as the upper limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `enable` - Whether to pick block sizes automatically.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_auto_block_sizing(mut self, enable: bool) -> Self {
+        self.settings.auto_block_sizing = enable;
+        self
+    }
+
+    /// Limits how many files may share a single SOLID block.
+    ///
+    /// Extracting a file from a SOLID block requires decompressing the block up to that file,
+    /// so this bounds the worst case latency of reading any single small file.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_files` - Maximum number of files per block.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_max_files_per_block(mut self, max_files: u32) -> Self {
+        self.settings.max_files_per_block = Some(max_files);
+        self
+    }
+
+    /// Sets the size above which files are split into chunks, rather than SOLID packed.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Size in bytes. Capped at the SOLID block size.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_solid_size_threshold(mut self, threshold: u32) -> Self {
+        self.settings.solid_size_threshold = Some(threshold);
+        self
+    }
+
+    /// Stores a copy of small files in the header of the archive, so they can be read
+    /// without fetching a block.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_size` - Largest file to inline, in bytes. Capped at [`MAX_INLINE_FILE_SIZE`].
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    ///
+    /// [`MAX_INLINE_FILE_SIZE`]: crate::headers::parser::MAX_INLINE_FILE_SIZE
+    pub fn with_inline_files(mut self, max_size: u32) -> Self {
+        self.settings.inline_file_threshold = Some(max_size);
+        self
+    }
+
+    /// Stores a filter of the file paths in the header, so readers can cheaply tell
+    /// that a file is not in the archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits_per_path` - Size of the filter. More bits mean fewer false positives;
+    ///   [`DEFAULT_PATH_FILTER_BITS`] gives roughly 1%.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    ///
+    /// [`DEFAULT_PATH_FILTER_BITS`]: crate::headers::parser::DEFAULT_PATH_FILTER_BITS
+    pub fn with_path_filter(mut self, bits_per_path: u32) -> Self {
+        self.settings.path_filter_bits = Some(bits_per_path);
+        self
+    }
+
+    /// Sets whether to store an index of the files within each directory in the header,
+    /// for fast listing of directories.
+    ///
+    /// # Arguments
+    ///
+    /// * `enable` - True to store the index.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_directory_index(mut self, enable: bool) -> Self {
+        self.settings.store_directory_index = enable;
+        self
+    }
+
+    /// Sets whether to store the hash of each chunk of large files in the header,
+    /// so chunks can be verified on their own. See [`PackingSettings::store_chunk_hashes`].
+    ///
+    /// # Arguments
+    ///
+    /// * `enable` - True to store the hashes.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_chunk_hashes(mut self, enable: bool) -> Self {
+        self.settings.store_chunk_hashes = enable;
+        self
+    }
+
+    /// Sets additional algorithms and levels to try on each block, keeping the smallest result
+    /// and recording how each did. See [`PackingSettings::compression_candidates`].
+    ///
+    /// # Arguments
+    ///
+    /// * `candidates` - The algorithms and levels to try.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_compression_candidates(mut self, candidates: Vec<CompressionCandidate>) -> Self {
+        self.settings.compression_candidates = candidates;
+        self
+    }
+
+    /// Sets the injector which makes block compression fail or stall, for testing.
+    /// See [`PackingSettings::fault_injector`].
+    ///
+    /// # Arguments
+    ///
+    /// * `injector` - Decides which blocks fail or stall.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    #[cfg(feature = "fault_injection")]
+    pub fn with_fault_injector(
+        mut self,
+        injector: alloc::sync::Arc<crate::utilities::fault_injection::FaultInjector>,
+    ) -> Self {
+        self.settings.fault_injector = Some(injector);
+        self
+    }
+
+    /// Works out which block each added file would be placed in, with which compression,
+    /// dictionary and deduplication target, without compressing anything.
+    ///
+    /// # Returns
+    ///
+    /// The plan; its [`Display`](core::fmt::Display) implementation explains each decision.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be read when checking for duplicates.
+    pub fn plan(&self) -> Result<PackPlan, FileProviderError> {
+        create_plan(&self.files, &self.settings)
+    }
+
+    /// Estimates how well each added file compresses, totals the files by extension,
+    /// and works out the blocks they would be packed into, without packing anything.
+    ///
+    /// # Arguments
+    ///
+    /// * `sampling` - How much of each file is compressed, and at which level.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be read, or a sample cannot be compressed.
+    pub fn compression_stats(
+        &self,
+        sampling: &StatsSamplingSettings,
+    ) -> Result<CompressionStats, CompressionStatsError> {
+        sample_compression_stats(&self.files, &self.settings, sampling)
+    }
+
+    /// Packs the added files, reusing the compressed blocks of a previous version
+    /// of the archive for files which have not changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `previous` - The previous version of the archive. Must store file hashes.
+    ///
+    /// # Returns
+    ///
+    /// The new archive, and how much of the previous archive was reused.
+    /// See [`pack_incremental`] for details. The diagnostics start with the warnings
+    /// raised while adding files, see [`Self::diagnostics`].
+    pub fn pack_incremental(
+        &self,
+        previous: &NxArchiveReader,
+    ) -> Result<(Vec<u8>, IncrementalStats), IncrementalPackError> {
+        let (archive, mut stats) = pack_incremental(previous, &self.files, &self.settings)?;
+        let mut diagnostics = self.diagnostics.clone();
+        diagnostics.append(core::mem::take(&mut stats.diagnostics));
+        stats.diagnostics = diagnostics;
+        Ok((archive, stats))
+    }
+
+    /// Computes the checksums of all added files, so they can be published
+    /// alongside the archive for validation by external tools.
+    ///
+    /// # Returns
+    ///
+    /// The manifest; see [`ChecksumManifest`] for the output formats.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the files cannot be read.
+    pub fn checksum_manifest(&self) -> Result<ChecksumManifest, FileProviderError> {
+        ChecksumManifest::from_files(&self.files)
+    }
+
+    /// Reports where each added file will be placed (SOLID block, own block or chunks),
+    /// and why, based on the current settings.
+    ///
+    /// # Returns
+    ///
+    /// One entry per file, in the order the files were added.
+    pub fn placement_report(&self) -> Vec<FilePlacement<'_>> {
+        let threshold = self.settings.effective_solid_size_threshold();
+        self.files
+            .iter()
+            .map(|file| FilePlacement {
+                relative_path: file.relative_path(),
+                reason: PlacementReason::of(file, threshold),
+            })
+            .collect()
+    }
+
+    /// Configures deduplication for chunked blocks in the archive.
+    ///
+    /// When enabled, the packer will detect and reuse duplicate chunks across
+    /// large files, potentially reducing the archive size. This feature incurs
+    /// a small memory overhead during packing.
+    ///
+    /// # Arguments
+    ///
+    /// * `enable` - Whether to enable chunked deduplication.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_chunked_deduplication(mut self, enable: bool) -> Self {
+        self.settings.enable_chunked_deduplication = enable;
+        self
+    }
+
+    /// Caps the memory used by the deduplication indexes, which map the hash of each file
+    /// to the first file with the same contents.
+    ///
+    /// Once an index reaches the cap, its hashes are written to a temporary file in
+    /// `spill_directory`, and looked up from there; this keeps deduplication usable for
+    /// backup sized inputs, at the cost of reading from disk for some lookups.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_memory_bytes` - Memory the indexes may use in total, in bytes.
+    /// * `spill_directory` - Directory the temporary file is created in.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_dedup_memory_limit(
+        mut self,
+        max_memory_bytes: u64,
+        spill_directory: PathBuf,
+    ) -> Self {
+        self.settings.dedup_memory_limit =
+            Some(DedupMemoryLimit::new(max_memory_bytes, spill_directory));
+        self
+    }
+
+    /// Sets the seed of the XXH3 hashes stored for each file.
+    /// See [`PackingSettings::hash_seed`].
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - The seed; 0 for unseeded hashes.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_hash_seed(mut self, seed: u64) -> Self {
+        self.settings.hash_seed = seed;
+        self
+    }
+
+    /// Sets the CPU priority of the thread compressing blocks.
+    /// Use [`WorkerPriority::BelowNormal`] to avoid stutters in programs running at the
+    /// same time, e.g. games or streaming software.
+    ///
+    /// Supported on Linux, Windows and macOS (as the utility QoS class). Elsewhere, or if
+    /// the OS refuses, the archive is packed at normal priority and
+    /// [`PackWarning::SchedulingNotApplied`] is reported.
+    ///
+    /// [`PackWarning::SchedulingNotApplied`]: crate::api::packing::pack_diagnostics::PackWarning::SchedulingNotApplied
+    ///
+    /// # Arguments
+    ///
+    /// * `priority` - Priority of the worker thread.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_worker_priority(mut self, priority: WorkerPriority) -> Self {
+        self.settings.worker_priority = priority;
+        self
+    }
+
+    /// Pins the thread compressing blocks to specific CPU cores, e.g. to leave the
+    /// remaining cores free for a game.
+    ///
+    /// Supported on Linux and Windows; on Windows, only the first 64 cores can be used.
+    /// macOS has no way to pin threads to cores. Where unsupported, or if the OS refuses
+    /// (e.g. none of the cores exist), the worker runs on any core and
+    /// [`PackWarning::SchedulingNotApplied`] is reported.
+    ///
+    /// [`PackWarning::SchedulingNotApplied`]: crate::api::packing::pack_diagnostics::PackWarning::SchedulingNotApplied
+    ///
+    /// # Arguments
+    ///
+    /// * `cores` - Indices of the cores the worker may run on.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_worker_cores(mut self, cores: Vec<usize>) -> Self {
+        self.settings.worker_cores = Some(cores);
+        self
+    }
+
+    /// Configures deduplication for SOLID blocks in the archive.
+    ///
+    /// When enabled, the packer will detect and reuse duplicate files within
+    /// SOLID blocks. This feature has minimal performance impact during packing
+    /// and can significantly reduce archive size when there are many duplicate files.
+    ///
+    /// # Arguments
+    ///
+    /// * `enable` - Whether to enable SOLID block deduplication.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_solid_deduplication(mut self, enable: bool) -> Self {
+        self.settings.enable_solid_deduplication = enable;
+        self
+    }
+
+    /// Enables or disables verification of compressed blocks.
+    ///
+    /// When enabled, each block is decompressed and hashed right after compression,
+    /// and packing fails if the data does not match the original. Useful for archival,
+    /// where a silently corrupted block would otherwise only be found on extraction.
+    ///
+    /// # Arguments
+    ///
+    /// * `enable` - Whether to verify compressed blocks.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_verify_blocks(mut self, enable: bool) -> Self {
+        self.settings.verify_blocks = enable;
+        self
+    }
+
+    /// Enables or disables storing incompressible files raw within SOLID blocks.
+    ///
+    /// When enabled, a SOLID block containing both compressible and incompressible files
+    /// is split into runs, each stored with its own codec.
+    ///
+    /// # Arguments
+    ///
+    /// * `enable` - Whether to pick a codec per file within SOLID blocks.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_mixed_codec_blocks(mut self, enable: bool) -> Self {
+        self.settings.mixed_codec_blocks = enable;
+        self
+    }
+
+    /// Sets the compression level for SOLID block data.
+    ///
+    /// This controls how aggressively SOLID blocks are compressed. Higher levels generally
+    /// provide better compression at the cost of increased compression time.
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - Compression level to use. The valid range depends on the algorithm:
+    ///   - For ZStandard: -5 to 22
+    ///   - For LZ4: 1 to 12
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_solid_compression_level(mut self, level: i32) -> Self {
+        self.settings.solid_compression_level = level;
+        self
+    }
+
+    /// Sets the compression level for chunked file data.
+    ///
+    /// This controls how aggressively individual chunks are compressed. Higher levels generally
+    /// provide better compression at the cost of increased compression time.
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - Compression level to use. The valid range depends on the algorithm:
+    ///   - For ZStandard: -5 to 22
+    ///   - For LZ4: 1 to 12
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_chunked_compression_level(mut self, level: i32) -> Self {
+        self.settings.chunked_compression_level = level;
+        self
+    }
+
+    /// Sets the compression algorithm used for SOLID blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - The compression algorithm to use for SOLID blocks.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_solid_block_algorithm(mut self, algorithm: CompressionPreference) -> Self {
+        self.settings.solid_block_algorithm = algorithm;
+        self
+    }
+
+    /// Sets the compression algorithm used for chunked files.
+    ///
+    /// # Arguments
+    ///
+    /// * `algorithm` - The compression algorithm to use for chunked files.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_chunked_file_algorithm(mut self, algorithm: CompressionPreference) -> Self {
+        self.settings.chunked_file_algorithm = algorithm;
+        self
+    }
+
+    /// Controls whether per-extension dictionary compression is enabled.
+    ///
+    /// When enabled, the packer will create and use separate dictionaries for each file extension,
+    /// which can improve compression ratios for files of similar types.
+    ///
+    /// Disabled by default. Dictionaries are not applied when compressing yet, so enabling
+    /// this reports [`PackWarning::IneffectiveDictionaries`] instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `enable` - Whether to enable per-extension dictionary compression.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_per_extension_dictionary(mut self, enable: bool) -> Self {
+        self.settings.enable_per_extension_dictionary = enable;
+        self
+    }
+
+    /// Sets how files are arranged into SOLID blocks.
+    ///
+    /// [`FileGrouping::ContentSimilarity`] places near-identical files (e.g. localized
+    /// variants of an asset) into the same block, at the cost of sampling every file first.
+    ///
+    /// # Arguments
+    ///
+    /// * `grouping` - How to group the files.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_file_grouping(mut self, grouping: FileGrouping) -> Self {
+        self.settings.file_grouping = grouping;
+        self
+    }
+
+    /// Sets advanced ZStandard parameters, such as long distance matching.
+    ///
+    /// Long distance matching and a larger window improve compression of large chunked files
+    /// with redundancy far apart, at the cost of more memory when compressing and decompressing.
     ///
     /// # Arguments
     ///
-    /// * `block_size` - Size of SOLID blocks in bytes. Must be between
-    ///   [`MIN_BLOCK_SIZE`] and [`MAX_BLOCK_SIZE`]. The value will be
-    ///   automatically adjusted to the nearest power of 2 minus 1.
+    /// * `params` - The parameters. Out of range values are clamped when packing.
     ///
     /// # Returns
     ///
     /// Returns self for method chaining.
-    ///
-    /// [`MIN_BLOCK_SIZE`]: crate::api::packing::packing_settings::MIN_BLOCK_SIZE
-    /// [`MAX_BLOCK_SIZE`]: crate::api::packing::packing_settings::MAX_BLOCK_SIZE
-    pub fn with_block_size(mut self, block_size: u32) -> Self {
-        self.settings.block_size = block_size;
+    pub fn with_zstd_advanced(mut self, params: ZstdAdvancedParams) -> Self {
+        self.settings.zstd_advanced = params;
         self
     }
 
-    /// Sets the size of chunks used when splitting large files.
+    /// Sets the order of files within each SOLID block.
     ///
-    /// Large files are split into chunks to allow for parallel compression
-    /// and decompression. This setting controls the size of those chunks.
+    /// Extracting a single file decompresses its block up to the end of that file,
+    /// so the ordering affects both compression ratio and the cost of partial extraction.
     ///
     /// # Arguments
     ///
-    /// * `chunk_size` - Size of chunks in bytes. Must be between
-    ///   [`MIN_CHUNK_SIZE`] and [`MAX_CHUNK_SIZE`]. The value will be
-    ///   automatically adjusted to the nearest power of 2.
+    /// * `ordering` - The order to place files in.
     ///
     /// # Returns
     ///
     /// Returns self for method chaining.
-    ///
-    /// [`MIN_CHUNK_SIZE`]: crate::api::packing::packing_settings::MIN_CHUNK_SIZE
-    /// [`MAX_CHUNK_SIZE`]: crate::api::packing::packing_settings::MAX_CHUNK_SIZE
-    pub fn with_chunk_size(mut self, chunk_size: u32) -> Self {
-        self.settings.chunk_size = chunk_size;
+    pub fn with_block_ordering(mut self, ordering: BlockOrdering) -> Self {
+        self.settings.block_ordering = ordering;
         self
     }
 
-    /// Configures deduplication for chunked blocks in the archive.
+    /// Sets the order file paths are sorted in within the archive.
     ///
-    /// When enabled, the packer will detect and reuse duplicate chunks across
-    /// large files, potentially reducing the archive size. This feature incurs
-    /// a small memory overhead during packing.
+    /// The collation is recorded in the archive header, so readers can find out how
+    /// the paths were sorted with [`NxArchiveReader::path_collation`].
     ///
     /// # Arguments
     ///
-    /// * `enable` - Whether to enable chunked deduplication.
+    /// * `collation` - How to compare the paths, e.g. [`PathCollation::Natural`]
+    ///   to sort `file2` before `file10`.
     ///
     /// # Returns
     ///
     /// Returns self for method chaining.
-    pub fn with_chunked_deduplication(mut self, enable: bool) -> Self {
-        self.settings.enable_chunked_deduplication = enable;
+    ///
+    /// [`NxArchiveReader::path_collation`]: crate::api::archive_reader::NxArchiveReader::path_collation
+    pub fn with_path_collation(mut self, collation: PathCollation) -> Self {
+        self.settings.path_collation = collation;
         self
     }
 
-    /// Configures deduplication for SOLID blocks in the archive.
+    /// Sets a filter to apply to files with a given extension before compression.
     ///
-    /// When enabled, the packer will detect and reuse duplicate files within
-    /// SOLID blocks. This feature has minimal performance impact during packing
-    /// and can significantly reduce archive size when there are many duplicate files.
+    /// Filters such as byte transposition can greatly improve compression of
+    /// structured numeric data, e.g. vertex buffers or animation curves.
     ///
     /// # Arguments
     ///
-    /// * `enable` - Whether to enable SOLID block deduplication.
+    /// * `extension` - File extension, without the leading dot (e.g. `mesh`).
+    /// * `filter` - The filter to apply. [`DataFilter::None`] removes any existing filter.
     ///
     /// # Returns
     ///
     /// Returns self for method chaining.
-    pub fn with_solid_deduplication(mut self, enable: bool) -> Self {
-        self.settings.enable_solid_deduplication = enable;
+    pub fn with_extension_filter(mut self, extension: &str, filter: DataFilter) -> Self {
+        if filter == DataFilter::None {
+            self.settings.extension_filters.remove(extension);
+        } else {
+            self.settings
+                .extension_filters
+                .insert(String::from(extension), filter);
+        }
         self
     }
 
-    /// Sets the compression level for SOLID block data.
+    /// Controls whether file hashes are stored in the archive.
     ///
-    /// This controls how aggressively SOLID blocks are compressed. Higher levels generally
-    /// provide better compression at the cost of increased compression time.
+    /// Omitting hashes shrinks the table of contents, at the expense of not being
+    /// able to verify files after extraction.
     ///
     /// # Arguments
     ///
-    /// * `level` - Compression level to use. The valid range depends on the algorithm:
-    ///   - For ZStandard: -5 to 22
-    ///   - For LZ4: 1 to 12
+    /// * `storage` - When to store the hashes.
     ///
     /// # Returns
     ///
     /// Returns self for method chaining.
-    pub fn with_solid_compression_level(mut self, level: i32) -> Self {
-        self.settings.solid_compression_level = level;
+    pub fn with_file_hashes(mut self, storage: FileHashStorage) -> Self {
+        self.settings.store_file_hashes = storage;
         self
     }
 
-    /// Sets the compression level for chunked file data.
+    /// Controls whether file paths are stored in the archive.
     ///
-    /// This controls how aggressively individual chunks are compressed. Higher levels generally
-    /// provide better compression at the cost of increased compression time.
+    /// Archives without paths are content addressed; files can only be found by hash,
+    /// which gives the smallest possible header for e.g. cache systems.
     ///
     /// # Arguments
     ///
-    /// * `level` - Compression level to use. The valid range depends on the algorithm:
-    ///   - For ZStandard: -5 to 22
-    ///   - For LZ4: 1 to 12
+    /// * `store` - Whether to store the file paths.
     ///
     /// # Returns
     ///
     /// Returns self for method chaining.
-    pub fn with_chunked_compression_level(mut self, level: i32) -> Self {
-        self.settings.chunked_compression_level = level;
+    pub fn with_file_paths(mut self, store: bool) -> Self {
+        self.settings.store_file_paths = store;
         self
     }
 
-    /// Sets the compression algorithm used for SOLID blocks.
+    /// Sets how the string pool (the file paths) is compressed.
+    ///
+    /// Storing the pool raw, or with LZ4, makes opening the archive slightly faster
+    /// at the expense of a larger table of contents.
     ///
     /// # Arguments
     ///
-    /// * `algorithm` - The compression algorithm to use for SOLID blocks.
+    /// * `compression` - The codec, level and minimum savings used for the string pool.
     ///
     /// # Returns
     ///
     /// Returns self for method chaining.
-    pub fn with_solid_block_algorithm(mut self, algorithm: CompressionPreference) -> Self {
-        self.settings.solid_block_algorithm = algorithm;
+    pub fn with_string_pool_compression(mut self, compression: StringPoolCompression) -> Self {
+        self.settings.string_pool_compression = compression;
         self
     }
 
-    /// Sets the compression algorithm used for chunked files.
+    /// Sets when compressed blocks are stored raw instead, because compression saved too little.
+    ///
+    /// Useful when most of the input is already compressed, as such data can be
+    /// detected from a small sample and stored without compressing all of it.
     ///
     /// # Arguments
     ///
-    /// * `algorithm` - The compression algorithm to use for chunked files.
+    /// * `fallback` - The minimum savings (overall and per algorithm) and sample size.
     ///
     /// # Returns
     ///
     /// Returns self for method chaining.
-    pub fn with_chunked_file_algorithm(mut self, algorithm: CompressionPreference) -> Self {
-        self.settings.chunked_file_algorithm = algorithm;
+    pub fn with_copy_fallback(mut self, fallback: CopyFallback) -> Self {
+        self.settings.copy_fallback = fallback;
         self
     }
 
-    /// Controls whether per-extension dictionary compression is enabled.
-    ///
-    /// When enabled, the packer will create and use separate dictionaries for each file extension,
-    /// which can improve compression ratios for files of similar types.
+    /// Makes the packer adjust the ZStandard level while packing, to compress
+    /// roughly `mbps` megabytes of input per second.
     ///
     /// # Arguments
     ///
-    /// * `enable` - Whether to enable per-extension dictionary compression.
+    /// * `mbps` - Target throughput, in megabytes per second.
+    /// * `levels` - Range of levels the packer may choose from.
     ///
     /// # Returns
     ///
     /// Returns self for method chaining.
-    pub fn with_per_extension_dictionary(mut self, enable: bool) -> Self {
-        self.settings.enable_per_extension_dictionary = enable;
+    pub fn with_target_throughput(mut self, mbps: u32, levels: RangeInclusive<i32>) -> Self {
+        self.settings.target_throughput_mbps = Some(mbps);
+        self.settings.adaptive_level_range = levels;
         self
     }
 
@@ -438,6 +1438,38 @@ impl<'a> NxPackerBuilder<'a> {
     }
 }
 
+/// Applies the matching rules to any preferences of the file which are left at their defaults.
+/// Rules are folded from least to most specific, each preference being taken from the most
+/// specific rule which sets it. Equally specific rules added later win.
+fn apply_directory_rules<'a>(rules: &[DirectoryRule], mut file: PackerFile<'a>) -> PackerFile<'a> {
+    let mut matching: Vec<&DirectoryRule> = rules
+        .iter()
+        .filter(|rule| rule.matches(file.relative_path()))
+        .collect();
+    matching.sort_by_key(|rule| rule.specificity());
+
+    let mut compression = CompressionPreference::NoPreference;
+    let mut solid_type = SolidPreference::Default;
+    for rule in matching {
+        if rule.compression_preference != CompressionPreference::NoPreference {
+            compression = rule.compression_preference;
+        }
+        if rule.solid_type != SolidPreference::Default {
+            solid_type = rule.solid_type;
+        }
+    }
+
+    if file.compression_preference() == CompressionPreference::NoPreference {
+        file = file.with_compression(compression);
+    }
+
+    if file.solid_type() == SolidPreference::Default {
+        file.set_solid_type(solid_type);
+    }
+
+    file
+}
+
 impl Default for NxPackerBuilder<'_> {
     fn default() -> Self {
         Self::new()
@@ -461,6 +1493,29 @@ pub struct AddFileParams {
     /// Controls whether the file should be packed into a SOLID block
     /// or handled individually.
     pub solid_type: SolidPreference,
+
+    /// Always stores the file alone in a dedicated block with fast compression,
+    /// overriding [`solid_type`] and [`compression_preference`].
+    ///
+    /// Use this for files read often, such as a manifest read at startup,
+    /// so reading them never involves decompressing other files.
+    ///
+    /// [`solid_type`]: Self::solid_type
+    /// [`compression_preference`]: Self::compression_preference
+    pub force_own_block: bool,
+
+    /// Splits the file into chunks of this size rather than the archive's chunk size,
+    /// if it is large enough to be chunked.
+    ///
+    /// Use this for huge files read in large sequential pieces, such as a 20GB `.pak`,
+    /// without making every other file's chunks larger. The size is clamped to
+    /// [`MIN_CHUNK_SIZE`]..=[`MAX_CHUNK_SIZE`] and rounded up to a power of 2, and
+    /// stored in the [`ChunkSizes`] header extension.
+    ///
+    /// [`MIN_CHUNK_SIZE`]: crate::api::packing::packing_settings::MIN_CHUNK_SIZE
+    /// [`MAX_CHUNK_SIZE`]: crate::api::packing::packing_settings::MAX_CHUNK_SIZE
+    /// [`ChunkSizes`]: crate::headers::parser::ChunkSizes
+    pub chunk_size_override: Option<u32>,
 }
 
 impl AddFileParams {
@@ -479,6 +1534,8 @@ impl AddFileParams {
             relative_path,
             compression_preference: CompressionPreference::NoPreference,
             solid_type: SolidPreference::Default,
+            force_own_block: false,
+            chunk_size_override: None,
         }
     }
 
@@ -502,6 +1559,131 @@ impl AddFileParams {
             relative_path,
             compression_preference,
             solid_type,
+            force_own_block: false,
+            chunk_size_override: None,
+        }
+    }
+
+    /// Sets whether the file is stored alone in its own block.
+    ///
+    /// # Arguments
+    ///
+    /// * `force_own_block` - See [`Self::force_own_block`].
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_own_block(mut self, force_own_block: bool) -> Self {
+        self.force_own_block = force_own_block;
+        self
+    }
+
+    /// Sets the size of the chunks the file is split into, instead of the archive's.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size` - See [`Self::chunk_size_override`].
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_chunk_size_override(mut self, chunk_size: u32) -> Self {
+        self.chunk_size_override = Some(chunk_size);
+        self
+    }
+}
+
+/// How [`NxPackerBuilder::add_folder_with_options`] rewrites the paths of the files it adds.
+///
+/// Applied in order: [`Self::strip_prefix`], [`Self::flatten`], then [`Self::base_path`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddFolderOptions {
+    /// Removed from the start of each path which starts with it, e.g. `Data` turns
+    /// `Data/Textures/a.dds` into `Textures/a.dds`. Matches whole directory names only.
+    pub strip_prefix: Option<String>,
+
+    /// Prepended to each path, e.g. `mods/my-mod` turns `a.dds` into `mods/my-mod/a.dds`.
+    pub base_path: Option<String>,
+
+    /// If true, directories are dropped from each path, leaving only the file name.
+    ///
+    /// Files with the same name in different directories then collide;
+    /// see [`NxPackerBuilder::with_collision_policy`].
+    pub flatten: bool,
+}
+
+impl AddFolderOptions {
+    /// Sets the prefix removed from the start of each path.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - See [`Self::strip_prefix`].
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_strip_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.strip_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Sets the path prepended to each path.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_path` - See [`Self::base_path`].
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_base_path(mut self, base_path: impl Into<String>) -> Self {
+        self.base_path = Some(base_path.into());
+        self
+    }
+
+    /// Sets whether directories are dropped from each path.
+    ///
+    /// # Arguments
+    ///
+    /// * `flatten` - See [`Self::flatten`].
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_flatten(mut self, flatten: bool) -> Self {
+        self.flatten = flatten;
+        self
+    }
+
+    /// Rewrites a path as configured. Both `/` and `\` are treated as separators.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of a file, relative to the folder being added.
+    pub fn apply(&self, path: &str) -> String {
+        let is_separator = |c: char| c == '/' || c == '\\';
+        let mut path = path.trim_start_matches(is_separator);
+
+        if let Some(prefix) = &self.strip_prefix {
+            let prefix = prefix.trim_matches(is_separator);
+            if let Some(rest) = path.strip_prefix(prefix) {
+                if prefix.is_empty() || rest.starts_with(is_separator) {
+                    path = rest.trim_start_matches(is_separator);
+                }
+            }
+        }
+
+        if self.flatten {
+            path = path.rsplit(is_separator).next().unwrap_or(path);
+        }
+
+        match self
+            .base_path
+            .as_deref()
+            .map(|x| x.trim_matches(is_separator))
+        {
+            Some(base) if !base.is_empty() => format!("{base}/{path}"),
+            _ => String::from(path),
         }
     }
 }
@@ -579,6 +1761,326 @@ mod tests {
         assert_eq!(builder.settings.block_size, 65536); // not yet sanitized
     }
 
+    #[test]
+    fn can_enable_auto_block_sizing() {
+        let builder = NxPackerBuilder::new().with_auto_block_sizing(true);
+        assert!(builder.settings.auto_block_sizing);
+    }
+
+    #[test]
+    fn can_limit_files_per_block() {
+        let builder = NxPackerBuilder::new().with_max_files_per_block(64);
+        assert_eq!(builder.settings.max_files_per_block, Some(64));
+    }
+
+    #[test]
+    fn directory_rules_apply_to_added_files() {
+        let mut builder = NxPackerBuilder::new()
+            .with_directory_rule(
+                "textures/**",
+                CompressionPreference::Copy,
+                SolidPreference::NoSolid,
+            )
+            .with_directory_rule(
+                "textures/ui/**",
+                CompressionPreference::Lz4,
+                SolidPreference::Default,
+            );
+
+        builder.add_file_from_byte_slice(b"rock", AddFileParams::new("textures/rock.dds".into()));
+        builder
+            .add_file_from_byte_slice(b"icon", AddFileParams::new("textures/ui/icon.dds".into()));
+        builder.add_file_from_byte_slice(
+            b"explicit",
+            AddFileParams::with_options(
+                "textures/explicit.dds".into(),
+                CompressionPreference::ZStandard,
+                SolidPreference::Default,
+            ),
+        );
+        builder.add_file_from_byte_slice(b"text", AddFileParams::new("readme.txt".into()));
+
+        let preferences: Vec<_> = builder
+            .files
+            .iter()
+            .map(|x| (x.compression_preference(), x.solid_type()))
+            .collect();
+        assert_eq!(
+            preferences,
+            vec![
+                (CompressionPreference::Copy, SolidPreference::NoSolid),
+                (CompressionPreference::Lz4, SolidPreference::NoSolid),
+                (CompressionPreference::ZStandard, SolidPreference::NoSolid),
+                (
+                    CompressionPreference::NoPreference,
+                    SolidPreference::Default
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn most_specific_directory_rule_wins_regardless_of_order() {
+        let mut builder = NxPackerBuilder::new()
+            .with_directory_rule(
+                "textures/ui/**",
+                CompressionPreference::Lz4,
+                SolidPreference::Default,
+            )
+            .with_directory_rule(
+                "textures/**",
+                CompressionPreference::Copy,
+                SolidPreference::NoSolid,
+            );
+        builder
+            .add_file_from_byte_slice(b"icon", AddFileParams::new("textures/ui/icon.dds".into()));
+
+        assert_eq!(
+            builder.files[0].compression_preference(),
+            CompressionPreference::Lz4
+        );
+        assert_eq!(builder.files[0].solid_type(), SolidPreference::NoSolid);
+    }
+
+    #[test]
+    fn can_create_checksum_manifest() {
+        let mut builder = NxPackerBuilder::new();
+        builder.add_file_from_byte_slice(b"Hello", AddFileParams::new("b.txt".into()));
+        builder.add_file_from_byte_slice(b"World", AddFileParams::new("a.txt".into()));
+
+        let manifest = builder.checksum_manifest().unwrap();
+        assert_eq!(manifest.entries[0].path, "a.txt");
+        assert_eq!(manifest.entries[1].size, 5);
+    }
+
+    #[test]
+    fn placement_report_explains_each_file() {
+        let large = [0u8; 2048];
+        let mut builder = NxPackerBuilder::new()
+            .with_block_size(4095)
+            .with_solid_size_threshold(1024);
+        builder.add_file_from_byte_slice(b"small", AddFileParams::new("small.txt".into()));
+        builder.add_file_from_byte_slice(&large, AddFileParams::new("large.bin".into()));
+
+        let report = builder.placement_report();
+        assert_eq!(report[0].relative_path, "small.txt");
+        assert_eq!(report[0].reason, PlacementReason::Solid);
+        assert_eq!(
+            report[1].reason,
+            PlacementReason::Chunked {
+                file_size: 2048,
+                threshold: 1024
+            }
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_combine_sources_with_collision_policy() {
+        use crate::utilities::tests::archive_for_testing::*;
+
+        let archive = create_test_archive(
+            4096,
+            &[&[
+                TestFile::new("a.txt", b"From archive"),
+                TestFile::new("b.txt", b"Archive only"),
+            ]],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+
+        let mut builder = NxPackerBuilder::new();
+        builder.add_file_from_byte_slice(b"From memory", AddFileParams::new("a.txt".into()));
+        builder.add_archive(&reader).unwrap();
+        builder.add_file_from_byte_slice(b"Also memory", AddFileParams::new("c.txt".into()));
+
+        assert_eq!(builder.files.len(), 3);
+        assert_eq!(builder.collisions(), &[String::from("a.txt")]);
+        assert_eq!(builder.files[0].file_size(), 12); // "From archive"
+        let data = builder.files[1]
+            .input_data_provider()
+            .get_file_data(0, 12)
+            .unwrap();
+        assert_eq!(data.data(), b"Archive only");
+
+        let mut builder =
+            NxPackerBuilder::new().with_collision_policy(PathCollisionPolicy::KeepExisting);
+        builder.add_file_from_byte_slice(b"From memory", AddFileParams::new("a.txt".into()));
+        builder.add_archive(&reader).unwrap();
+        assert_eq!(builder.files[0].file_size(), 11); // "From memory"
+    }
+
+    #[test]
+    fn path_transform_renames_files() {
+        let mut builder = NxPackerBuilder::new()
+            .with_path_transform(|path| match path.strip_prefix("Data/") {
+                Some(rest) => Cow::Owned(format!("data/{rest}")),
+                None => Cow::Borrowed(path),
+            })
+            .with_directory_rule(
+                "data/**",
+                CompressionPreference::Lz4,
+                SolidPreference::Default,
+            );
+        builder.add_file_from_byte_slice(b"a", AddFileParams::new("Data/a.txt".into()));
+        builder.add_file_from_byte_slice(b"b", AddFileParams::new("b.txt".into()));
+        builder.add_file_from_byte_slice(b"c", AddFileParams::new("data/a.txt".into()));
+
+        assert_eq!(builder.files.len(), 2);
+        assert_eq!(builder.files[0].relative_path(), "data/a.txt");
+        assert_eq!(
+            builder.files[0].compression_preference(),
+            CompressionPreference::Lz4
+        );
+        assert_eq!(builder.files[1].relative_path(), "b.txt");
+        assert_eq!(builder.collisions(), &[String::from("data/a.txt")]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reports_normalized_and_dropped_files() {
+        use crate::api::packing::pack_diagnostics::PackWarningCode;
+        use crate::utilities::tests::archive_for_testing::*;
+
+        let mut builder = NxPackerBuilder::new().with_per_extension_dictionary(false);
+        builder.add_file_from_byte_slice(b"a", AddFileParams::new("\\data\\a.txt".into()));
+        builder.add_file_from_byte_slice(b"b", AddFileParams::new("data/a.txt".into()));
+
+        assert_eq!(builder.files.len(), 1);
+        assert_eq!(builder.files[0].relative_path(), "data/a.txt");
+        assert_eq!(
+            builder.diagnostics().warnings(),
+            &[
+                PackWarning::PathNormalized {
+                    original: "\\data\\a.txt".into(),
+                    normalized: "data/a.txt".into(),
+                },
+                PackWarning::FileDropped {
+                    path: Some("data/a.txt".into()),
+                    reason: DropReason::PathCollision,
+                },
+            ]
+        );
+
+        let previous = create_test_archive(
+            4096,
+            &[&[TestFile::new("old.txt", b"Old")]],
+            &[],
+            CompressionPreference::Copy,
+        );
+        let provider = Box::new(FromSliceReferenceProvider::new(&previous));
+        let previous = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+        let (_, stats) = builder.pack_incremental(&previous).unwrap();
+        let unexpected: Vec<&PackWarning> = stats
+            .diagnostics
+            .unexpected(&[PackWarningCode::PathNormalized])
+            .collect();
+        assert_eq!(unexpected.len(), 1);
+        assert_eq!(unexpected[0].code(), PackWarningCode::FileDropped);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn file_handle_policy_applies_to_added_folders() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for x in 0..3 {
+            std::fs::write(temp_dir.path().join(format!("{x}.txt")), b"Data").unwrap();
+        }
+
+        let policy = FileHandlePolicy::pooled(1);
+        let mut builder = NxPackerBuilder::new().with_file_handle_policy(policy.clone());
+        builder
+            .add_folder(temp_dir.path().to_str().unwrap())
+            .unwrap();
+
+        assert_eq!(builder.files.len(), 3);
+        for file in &builder.files {
+            let data = file.input_data_provider().get_file_data(0, 4).unwrap();
+            assert_eq!(data.data(), b"Data");
+        }
+
+        let FileHandlePolicy::PooledWithLimit(pool) = policy else {
+            unreachable!();
+        };
+        assert_eq!(pool.open_files(), 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn add_folder_rewrites_paths() {
+        let options = AddFolderOptions::default()
+            .with_strip_prefix("Data/")
+            .with_base_path("mods/example");
+        assert_eq!(
+            options.apply("Data/Textures/a.dds"),
+            "mods/example/Textures/a.dds"
+        );
+        assert_eq!(options.apply("Database/b.db"), "mods/example/Database/b.db");
+        assert_eq!(
+            options
+                .clone()
+                .with_flatten(true)
+                .apply("Data/Textures/a.dds"),
+            "mods/example/a.dds"
+        );
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("Data/Textures")).unwrap();
+        std::fs::write(temp_dir.path().join("Data/Textures/a.dds"), b"Data").unwrap();
+        std::fs::write(temp_dir.path().join("readme.txt"), b"Data").unwrap();
+
+        let mut builder = NxPackerBuilder::new();
+        builder
+            .add_folder_with_options(temp_dir.path().to_str().unwrap(), &options)
+            .unwrap();
+        let mut paths: Vec<&str> = builder.files.iter().map(|x| x.relative_path()).collect();
+        paths.sort();
+        assert_eq!(
+            paths.as_slice(),
+            &["mods/example/Textures/a.dds", "mods/example/readme.txt"]
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn unseekable_streams_are_spooled_to_temp_dir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut builder = NxPackerBuilder::new().with_temp_dir(temp_dir.path());
+        assert_eq!(builder.temp_dir(), temp_dir.path());
+
+        let stream = std::io::Cursor::new(b"Hello, World!").chain(&b" Streamed."[..]);
+        builder
+            .add_file_from_unseekable_stream(stream, AddFileParams::new("a.txt".into()))
+            .unwrap();
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+
+        let file = &builder.files[0];
+        assert_eq!(file.file_size(), 23);
+        let data = file.input_data_provider().get_file_data(14, 9).unwrap();
+        assert_eq!(data.data(), b"Streamed.");
+
+        drop(builder);
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn can_add_path_aliases() {
+        let mut aliases = PathAliases::new();
+        aliases.insert("old.txt", "new.txt");
+
+        let mut builder = NxPackerBuilder::new().with_path_transform(|_| Cow::Owned("x".into()));
+        builder.add_path_aliases(&aliases);
+
+        assert_eq!(builder.files[0].relative_path(), PathAliases::FILE_PATH);
+        let data = builder.files[0]
+            .input_data_provider()
+            .get_file_data(0, builder.files[0].file_size())
+            .unwrap();
+        assert_eq!(PathAliases::from_bytes(data.data()).unwrap(), aliases);
+    }
+
     #[test]
     fn can_configure_chunk_size() {
         let builder = NxPackerBuilder::new().with_chunk_size(4194304);
@@ -610,6 +2112,8 @@ mod tests {
             relative_path: String::from("test.txt"),
             compression_preference: CompressionPreference::NoPreference,
             solid_type: SolidPreference::Default,
+            force_own_block: false,
+            chunk_size_override: None,
         };
 
         builder.add_file_from_byte_slice(&data, options);
@@ -629,6 +2133,8 @@ mod tests {
             relative_path: String::from("test.txt"),
             compression_preference: CompressionPreference::NoPreference,
             solid_type: SolidPreference::Default,
+            force_own_block: false,
+            chunk_size_override: None,
         };
 
         builder.add_file_from_boxed_slice(data, options);
@@ -647,6 +2153,8 @@ mod tests {
             relative_path: String::from("test.txt"),
             compression_preference: CompressionPreference::NoPreference,
             solid_type: SolidPreference::Default,
+            force_own_block: false,
+            chunk_size_override: None,
         };
 
         builder.add_file_from_stream(data, 13, options);
@@ -690,6 +2198,61 @@ mod tests {
         assert!(builder.settings.enable_per_extension_dictionary);
     }
 
+    #[test]
+    fn can_set_extension_filter() {
+        let builder = NxPackerBuilder::new()
+            .with_extension_filter("mesh", DataFilter::ByteTranspose4)
+            .with_extension_filter("anim", DataFilter::Delta4)
+            .with_extension_filter("anim", DataFilter::None);
+
+        assert_eq!(
+            builder.settings.extension_filters.get("mesh"),
+            Some(&DataFilter::ByteTranspose4)
+        );
+        assert!(!builder.settings.extension_filters.contains_key("anim"));
+    }
+
+    #[test]
+    fn can_set_file_hashes() {
+        let builder = NxPackerBuilder::new().with_file_hashes(FileHashStorage::Auto);
+        assert_eq!(builder.settings.store_file_hashes, FileHashStorage::Auto);
+    }
+
+    #[test]
+    fn can_omit_file_paths() {
+        let builder = NxPackerBuilder::new().with_file_paths(false);
+        assert!(!builder.settings.store_file_paths);
+    }
+
+    #[test]
+    fn can_set_string_pool_compression() {
+        let compression = StringPoolCompression {
+            algorithm: CompressionPreference::Lz4,
+            level: 12,
+            min_savings: 256,
+        };
+        let builder = NxPackerBuilder::new().with_string_pool_compression(compression);
+        assert_eq!(builder.settings.string_pool_compression, compression);
+    }
+
+    #[test]
+    fn can_set_copy_fallback() {
+        let fallback = CopyFallback {
+            min_savings_percent: 5,
+            lz4_min_savings_percent: Some(2),
+            ..Default::default()
+        };
+        let builder = NxPackerBuilder::new().with_copy_fallback(fallback);
+        assert_eq!(builder.settings.copy_fallback, fallback);
+    }
+
+    #[test]
+    fn can_set_target_throughput() {
+        let builder = NxPackerBuilder::new().with_target_throughput(200, 3..=12);
+        assert_eq!(builder.settings.target_throughput_mbps, Some(200));
+        assert_eq!(builder.settings.adaptive_level_range, 3..=12);
+    }
+
     #[test]
     fn archival_preset_sets_correct_values() {
         let builder = NxPackerBuilder::new().with_preset(PackerPreset::Archival);
@@ -758,5 +2321,12 @@ mod add_file_params_tests {
             CompressionPreference::Lz4
         ));
         assert!(matches!(params.solid_type, SolidPreference::NoSolid));
+        assert!(!params.force_own_block);
+    }
+
+    #[test]
+    fn with_own_block_sets_flag() {
+        let params = AddFileParams::new("manifest.json".into()).with_own_block(true);
+        assert!(params.force_own_block);
     }
 }
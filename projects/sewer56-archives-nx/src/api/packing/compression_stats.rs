@@ -0,0 +1,274 @@
+use crate::api::{
+    enums::CompressionPreference,
+    packing::{
+        pack_plan::{create_plan, PackPlan},
+        packer_file::PackerFile,
+        packing_settings::PackingSettings,
+    },
+    traits::*,
+};
+use crate::prelude::*;
+use crate::utilities::arrange::pack::group_by_extension::extract_extension;
+use crate::utilities::compression::{self, NxCompressionError};
+use alloc::string::{String, ToString};
+use hashbrown::HashMap;
+use thiserror_no_std::Error;
+
+/// Settings for [`sample_compression_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatsSamplingSettings {
+    /// Maximum number of bytes read from the start of each file.
+    pub sample_size: u32,
+
+    /// ZStandard level used to compress the samples.
+    pub level: i32,
+}
+
+impl Default for StatsSamplingSettings {
+    fn default() -> Self {
+        Self {
+            sample_size: 128 * 1024,
+            level: 3,
+        }
+    }
+}
+
+/// How well a set of files compresses, and how the packer would lay them out.
+///
+/// Returned by [`sample_compression_stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressionStats {
+    /// Compressibility of each file, in the order the files were passed in.
+    pub files: Vec<FileCompressibility>,
+
+    /// Files grouped by extension, largest total size first.
+    pub extensions: Vec<ExtensionStats>,
+
+    /// The blocks the packer would create for the files.
+    pub layout: PackPlan,
+}
+
+/// Compressibility of a single file, estimated from a sample.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileCompressibility {
+    /// Path of the file within the archive.
+    pub relative_path: String,
+
+    /// Size of the file.
+    pub file_size: u64,
+
+    /// Number of bytes compressed, from the start of the file.
+    pub sample_size: u64,
+
+    /// Size of the sample after compression.
+    pub compressed_sample_size: u64,
+}
+
+/// Totals for all files with the same extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionStats {
+    /// The extension, without the dot. Empty for files without an extension.
+    pub extension: String,
+
+    /// Number of files with the extension.
+    pub file_count: u32,
+
+    /// Total size of the files.
+    pub total_size: u64,
+
+    /// Estimated total size of the files after compression.
+    pub estimated_compressed_size: u64,
+}
+
+/// Errors that can occur when sampling compression statistics.
+#[derive(Debug, Error)]
+pub enum CompressionStatsError {
+    /// Failed to read one of the files.
+    #[error(transparent)]
+    FileProvider(#[from] FileProviderError),
+
+    /// Failed to compress a sample.
+    #[error("Failed to compress sample: {0:?}")]
+    Compression(#[from] NxCompressionError),
+}
+
+impl FileCompressibility {
+    /// Returns the compressed size of the sample divided by its size;
+    /// lower is better. Empty files have a ratio of 1.
+    pub fn ratio(&self) -> f64 {
+        match self.sample_size {
+            0 => 1.0,
+            size => self.compressed_sample_size as f64 / size as f64,
+        }
+    }
+
+    /// Returns the estimated size of the whole file after compression,
+    /// assuming the rest of the file compresses as well as the sample.
+    pub fn estimated_compressed_size(&self) -> u64 {
+        (self.file_size as f64 * self.ratio()).ceil() as u64
+    }
+}
+
+impl ExtensionStats {
+    /// Returns the estimated compressed size of the files divided by their size;
+    /// lower is better.
+    pub fn ratio(&self) -> f64 {
+        match self.total_size {
+            0 => 1.0,
+            size => self.estimated_compressed_size as f64 / size as f64,
+        }
+    }
+}
+
+impl CompressionStats {
+    /// Returns the total size of all files.
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|x| x.file_size).sum()
+    }
+
+    /// Returns the estimated total size of all files after compression.
+    pub fn estimated_compressed_size(&self) -> u64 {
+        self.extensions
+            .iter()
+            .map(|x| x.estimated_compressed_size)
+            .sum()
+    }
+
+    /// Finds the totals for an extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `extension` - The extension, without the dot. Empty for files without an extension.
+    pub fn extension(&self, extension: &str) -> Option<&ExtensionStats> {
+        self.extensions.iter().find(|x| x.extension == extension)
+    }
+}
+
+/// Estimates how well each file compresses, totals the files by extension, and works out
+/// the blocks the packer would create, without packing anything.
+///
+/// This exposes the analysis used by the research tools (e.g. `nx-dictionary-tester`),
+/// so tools gathering statistics about mod files don't need to reimplement it.
+///
+/// # Arguments
+///
+/// * `files` - The files to analyze.
+/// * `settings` - The settings the files would be packed with; used for the block layout.
+/// * `sampling` - How much of each file is compressed, and at which level.
+///
+/// # Remarks
+///
+/// Each file is compressed on its own, so the estimates don't include the gains from
+/// SOLID blocks or dictionaries; treat them as an upper bound.
+pub fn sample_compression_stats(
+    files: &[PackerFile<'_>],
+    settings: &PackingSettings,
+    sampling: &StatsSamplingSettings,
+) -> Result<CompressionStats, CompressionStatsError> {
+    let max_sample = sampling.sample_size as usize;
+    let mut compressed = vec![0u8; compression::max_alloc_for_compress_size(max_sample)];
+    let mut results = Vec::with_capacity(files.len());
+    let mut extensions: HashMap<&str, ExtensionStats> = HashMap::new();
+
+    for file in files {
+        let file_size = file.file_size();
+        let sample_size = file_size.min(max_sample as u64);
+
+        // Empty files can't be read, as the provider would map a zero sized region.
+        let compressed_sample_size = if sample_size == 0 {
+            0
+        } else {
+            let sample = file.input_data_provider().get_file_data(0, sample_size)?;
+            let mut used_copy = false;
+            compression::compress(
+                CompressionPreference::ZStandard,
+                sampling.level,
+                sample.data(),
+                &mut compressed,
+                &mut used_copy,
+            )? as u64
+        };
+
+        let result = FileCompressibility {
+            relative_path: file.relative_path().to_string(),
+            file_size,
+            sample_size,
+            compressed_sample_size,
+        };
+
+        let extension = extract_extension(file.relative_path());
+        let totals = extensions
+            .entry(extension)
+            .or_insert_with(|| ExtensionStats {
+                extension: extension.to_string(),
+                file_count: 0,
+                total_size: 0,
+                estimated_compressed_size: 0,
+            });
+        totals.file_count += 1;
+        totals.total_size += file_size;
+        totals.estimated_compressed_size += result.estimated_compressed_size();
+        results.push(result);
+    }
+
+    let mut extensions: Vec<ExtensionStats> = extensions.into_values().collect();
+    extensions.sort_by(|a, b| {
+        b.total_size
+            .cmp(&a.total_size)
+            .then_with(|| a.extension.cmp(&b.extension))
+    });
+
+    Ok(CompressionStats {
+        files: results,
+        extensions,
+        layout: create_plan(files, settings)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::packer_builder::{AddFileParams, NxPackerBuilder};
+
+    #[test]
+    fn samples_compressibility_by_extension() {
+        let text: Vec<u8> = b"Nx packs files into SOLID blocks. "
+            .iter()
+            .copied()
+            .cycle()
+            .take(10_000)
+            .collect();
+        let noise: Vec<u8> = (0..10_000_u32)
+            .map(|x| (x.wrapping_mul(2654435761) >> 24) as u8)
+            .collect();
+
+        let mut builder = NxPackerBuilder::new();
+        builder.add_file_from_byte_slice(&text, AddFileParams::new("readme.txt".into()));
+        builder.add_file_from_byte_slice(&text[..5000], AddFileParams::new("a/notes.txt".into()));
+        builder.add_file_from_byte_slice(&noise, AddFileParams::new("noise.bin".into()));
+        builder.add_file_from_byte_slice(b"", AddFileParams::new("empty".into()));
+
+        let sampling = StatsSamplingSettings {
+            sample_size: 4096,
+            ..Default::default()
+        };
+        let stats = builder.compression_stats(&sampling).unwrap();
+
+        assert_eq!(stats.files.len(), 4);
+        assert_eq!(stats.files[0].sample_size, 4096);
+        assert!(stats.files[0].ratio() < 0.1);
+        assert!(stats.files[2].ratio() > 0.9);
+        assert_eq!(stats.files[3].estimated_compressed_size(), 0);
+        assert_eq!(stats.total_size(), 25_000);
+
+        let txt = stats.extension("txt").unwrap();
+        assert_eq!(txt.file_count, 2);
+        assert_eq!(txt.total_size, 15_000);
+        assert!(txt.ratio() < 0.1);
+        assert_eq!(stats.extensions[0].extension, "txt");
+        assert_eq!(stats.extension("").unwrap().file_count, 1);
+
+        assert_eq!(stats.layout.files.len(), 4);
+        assert!(!stats.layout.blocks.is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::api::enums::*;
+use alloc::string::String;
+
+/// Compression and SOLID preferences applied to all files matching a path pattern,
+/// as files are added to the packer.
+///
+/// Patterns are matched against the path of the file within the archive, using `/`
+/// as the separator:
+///
+/// - `*` matches any run of characters within a single path segment.
+/// - `?` matches any single character within a path segment.
+/// - `**` as a whole segment matches any number of segments (including none).
+///
+/// For example, `textures/**` matches every file under `textures`,
+/// while `**/*.dds` matches `.dds` files in any directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectoryRule {
+    /// Pattern the relative path of a file must match.
+    pub pattern: String,
+
+    /// Preferred algorithm for matching files.
+    pub compression_preference: CompressionPreference,
+
+    /// SOLID preference for matching files.
+    pub solid_type: SolidPreference,
+}
+
+impl DirectoryRule {
+    /// Creates a new rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - Pattern matched against paths within the archive. Backslashes are treated as `/`.
+    /// * `compression_preference` - Preferred algorithm for matching files.
+    /// * `solid_type` - SOLID preference for matching files.
+    pub fn new(
+        pattern: &str,
+        compression_preference: CompressionPreference,
+        solid_type: SolidPreference,
+    ) -> Self {
+        Self {
+            pattern: pattern.replace('\\', "/"),
+            compression_preference,
+            solid_type,
+        }
+    }
+
+    /// Returns true if the given path (within the archive) matches this rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - Path of the file within the archive.
+    pub fn matches(&self, relative_path: &str) -> bool {
+        let path = relative_path.replace('\\', "/");
+        let pattern: Vec<&str> = self.pattern.split('/').collect();
+        let path: Vec<&str> = path.split('/').collect();
+        matches_segments(&pattern, &path)
+    }
+
+    /// How specific the pattern is; rules with a higher specificity override those with a
+    /// lower one. Counts the segments other than `**`, then the characters other than wildcards.
+    pub fn specificity(&self) -> (usize, usize) {
+        let segments = self.pattern.split('/').filter(|x| *x != "**").count();
+        let literals = self
+            .pattern
+            .bytes()
+            .filter(|x| !matches!(x, b'*' | b'?' | b'/'))
+            .count();
+        (segments, literals)
+    }
+}
+
+fn matches_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| matches_segments(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                matches_segment(segment.as_bytes(), name.as_bytes())
+                    && matches_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn matches_segment(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| matches_segment(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && matches_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && matches_segment(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::directory("textures/**", "textures/rock.dds", true)]
+    #[case::nested_directory("textures/**", "textures/terrain/rock.dds", true)]
+    #[case::other_directory("textures/**", "models/rock.nif", false)]
+    #[case::prefix_is_not_directory("textures/**", "textures2/rock.dds", false)]
+    #[case::extension_anywhere("**/*.dds", "a/b/rock.dds", true)]
+    #[case::extension_at_root("**/*.dds", "rock.dds", true)]
+    #[case::wildcard_single_segment("*.dds", "a/rock.dds", false)]
+    #[case::question_mark("lod?/*", "lod1/mesh.nif", true)]
+    #[case::backslashes("textures\\**", "textures\\rock.dds", true)]
+    #[case::exact("readme.txt", "readme.txt", true)]
+    fn matches_paths(#[case] pattern: &str, #[case] path: &str, #[case] expected: bool) {
+        let rule = DirectoryRule::new(
+            pattern,
+            CompressionPreference::Copy,
+            SolidPreference::NoSolid,
+        );
+        assert_eq!(rule.matches(path), expected);
+    }
+
+    #[rstest]
+    #[case::deeper_directory("textures/**", "textures/ui/**")]
+    #[case::directory_over_extension("**/*.dds", "textures/**")]
+    #[case::exact_over_wildcard("textures/*.dds", "textures/rock.dds")]
+    fn orders_by_specificity(#[case] general: &str, #[case] specific: &str) {
+        let rule = |pattern| {
+            DirectoryRule::new(
+                pattern,
+                CompressionPreference::Copy,
+                SolidPreference::NoSolid,
+            )
+        };
+        assert!(rule(general).specificity() < rule(specific).specificity());
+    }
+}
@@ -0,0 +1,415 @@
+use crate::headers::raw::toc::{
+    v1::MAX_STRING_POOL_SIZE, PRESET0_BLOCK_COUNT_MAX, PRESET0_FILE_COUNT_MAX,
+    PRESET0_STRING_POOL_SIZE_MAX,
+};
+use crate::prelude::*;
+use crate::utilities::worker_scheduling::SchedulingFailure;
+use alloc::string::String;
+use core::fmt::{self, Display, Formatter};
+
+/// Size of the compressed string pool above which [`PackWarning::LargeStringPool`] is raised.
+/// The pool is read (and, unless opened lazily, decompressed) every time the archive is opened.
+pub const LARGE_STRING_POOL_SIZE: u32 = 1024 * 1024;
+
+/// Percentage of a table of contents limit above which [`PackWarning::TocNearLimit`] is raised.
+const NEAR_LIMIT_PERCENT: u64 = 90;
+
+/// Stable identifier of a kind of [`PackWarning`].
+///
+/// The codes never change meaning between versions, so they can be used to allowlist
+/// warnings, e.g. in CI. See [`PackDiagnostics::unexpected`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u16)]
+pub enum PackWarningCode {
+    /// See [`PackWarning::TocNearLimit`].
+    TocNearLimit = 1,
+
+    /// See [`PackWarning::FileDropped`].
+    FileDropped = 2,
+
+    /// See [`PackWarning::PathNormalized`].
+    PathNormalized = 3,
+
+    /// See [`PackWarning::LargeStringPool`].
+    LargeStringPool = 4,
+
+    /// See [`PackWarning::IneffectiveDictionaries`].
+    IneffectiveDictionaries = 5,
+
+    /// See [`PackWarning::FileChanged`].
+    FileChanged = 6,
+
+    /// See [`PackWarning::SchedulingNotApplied`].
+    SchedulingNotApplied = 7,
+}
+
+impl PackWarningCode {
+    /// All codes, in numeric order.
+    pub const ALL: [PackWarningCode; 7] = [
+        PackWarningCode::TocNearLimit,
+        PackWarningCode::FileDropped,
+        PackWarningCode::PathNormalized,
+        PackWarningCode::LargeStringPool,
+        PackWarningCode::IneffectiveDictionaries,
+        PackWarningCode::FileChanged,
+        PackWarningCode::SchedulingNotApplied,
+    ];
+
+    /// Returns the code as text, e.g. `NX-W001`.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            PackWarningCode::TocNearLimit => "NX-W001",
+            PackWarningCode::FileDropped => "NX-W002",
+            PackWarningCode::PathNormalized => "NX-W003",
+            PackWarningCode::LargeStringPool => "NX-W004",
+            PackWarningCode::IneffectiveDictionaries => "NX-W005",
+            PackWarningCode::FileChanged => "NX-W006",
+            PackWarningCode::SchedulingNotApplied => "NX-W007",
+        }
+    }
+
+    /// Parses a code from its text form, as returned by [`Self::as_str`].
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The code, e.g. `NX-W001`. Case insensitive.
+    pub fn parse(code: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.as_str().eq_ignore_ascii_case(code.trim()))
+    }
+}
+
+impl Display for PackWarningCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A limit of the table of contents, see [`PackWarning::TocNearLimit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TocLimit {
+    /// Number of files in the archive.
+    FileCount,
+
+    /// Number of blocks in the archive.
+    BlockCount,
+
+    /// Size of the compressed string pool, in bytes.
+    StringPoolSize,
+
+    /// Size of the string pool once decompressed, in bytes.
+    DecompressedStringPoolSize,
+}
+
+/// Why a file added to the packer is not in the archive, see [`PackWarning::FileDropped`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// Another file was added at the same path, and kept as per the
+    /// [`PathCollisionPolicy`](crate::api::enums::PathCollisionPolicy).
+    PathCollision,
+
+    /// The file came from an archive packed without file paths.
+    MissingPath,
+}
+
+/// A problem found while packing, which does not prevent the archive from being created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackWarning {
+    /// The archive is close to a limit of the table of contents; adding more
+    /// files may make it impossible to pack.
+    TocNearLimit {
+        /// The limit being approached.
+        limit: TocLimit,
+        /// Value in this archive.
+        value: u64,
+        /// Largest value supported by the format.
+        max: u64,
+    },
+
+    /// A file added to the packer was left out of the archive.
+    FileDropped {
+        /// Path of the file, if it had one.
+        path: Option<String>,
+        /// Why the file was left out.
+        reason: DropReason,
+    },
+
+    /// A path was changed to follow the format's conventions, i.e. `/` as the
+    /// separator and no leading separator.
+    PathNormalized {
+        /// Path as added.
+        original: String,
+        /// Path stored in the archive.
+        normalized: String,
+    },
+
+    /// The string pool is larger than [`LARGE_STRING_POOL_SIZE`], slowing down opening the archive.
+    LargeStringPool {
+        /// Size of the compressed string pool, in bytes.
+        size: u32,
+    },
+
+    /// Per extension dictionaries were enabled, but this packer does not compress with
+    /// dictionaries; the files were compressed without them.
+    IneffectiveDictionaries,
+
+    /// A file changed between being added and being packed, and was packed with its
+    /// current contents, as per [`FileChangePolicy::Repack`].
+    ///
+    /// [`FileChangePolicy::Repack`]: crate::api::enums::FileChangePolicy::Repack
+    FileChanged {
+        /// Path of the file within the archive.
+        path: String,
+        /// Size of the file when it was added.
+        added_size: u64,
+        /// Size of the file when it was packed.
+        current_size: u64,
+    },
+
+    /// The worker thread could not be given the priority or cores set in the settings,
+    /// e.g. because the platform does not support it; the archive was packed regardless.
+    /// See [`apply_to_current_thread`] for the supported platforms.
+    ///
+    /// [`apply_to_current_thread`]: crate::utilities::worker_scheduling::apply_to_current_thread
+    SchedulingNotApplied(SchedulingFailure),
+}
+
+impl PackWarning {
+    /// Returns the stable code of this kind of warning.
+    pub fn code(&self) -> PackWarningCode {
+        match self {
+            PackWarning::TocNearLimit { .. } => PackWarningCode::TocNearLimit,
+            PackWarning::FileDropped { .. } => PackWarningCode::FileDropped,
+            PackWarning::PathNormalized { .. } => PackWarningCode::PathNormalized,
+            PackWarning::LargeStringPool { .. } => PackWarningCode::LargeStringPool,
+            PackWarning::IneffectiveDictionaries => PackWarningCode::IneffectiveDictionaries,
+            PackWarning::FileChanged { .. } => PackWarningCode::FileChanged,
+            PackWarning::SchedulingNotApplied(_) => PackWarningCode::SchedulingNotApplied,
+        }
+    }
+}
+
+impl Display for PackWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.code())?;
+        match self {
+            PackWarning::TocNearLimit { limit, value, max } => {
+                write!(f, "{limit:?} of {value} is close to the limit of {max}")
+            }
+            PackWarning::FileDropped { path, reason } => {
+                let path = path.as_deref().unwrap_or("<no path>");
+                write!(f, "'{path}' was left out of the archive ({reason:?})")
+            }
+            PackWarning::PathNormalized {
+                original,
+                normalized,
+            } => write!(f, "'{original}' was stored as '{normalized}'"),
+            PackWarning::LargeStringPool { size } => {
+                write!(f, "String pool of {size} bytes is slow to load")
+            }
+            PackWarning::IneffectiveDictionaries => f.write_str(
+                "Per extension dictionaries are not supported when packing incrementally",
+            ),
+            PackWarning::FileChanged {
+                path,
+                added_size,
+                current_size,
+            } => write!(
+                f,
+                "'{path}' changed after being added (size {added_size} -> {current_size}), packed current contents"
+            ),
+            PackWarning::SchedulingNotApplied(failure) => match failure.os_error {
+                Some(error) => write!(
+                    f,
+                    "Worker {:?} could not be applied (OS error {error})",
+                    failure.option
+                ),
+                None => write!(
+                    f,
+                    "Worker {:?} is not supported on this platform",
+                    failure.option
+                ),
+            },
+        }
+    }
+}
+
+/// Warnings raised while packing an archive, in the order they were found.
+///
+/// Returned as part of the pack result, e.g. [`IncrementalStats::diagnostics`].
+/// With the `log` feature, each warning is also logged.
+///
+/// [`IncrementalStats::diagnostics`]: crate::api::incremental_pack::IncrementalStats::diagnostics
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PackDiagnostics {
+    warnings: Vec<PackWarning>,
+}
+
+impl PackDiagnostics {
+    /// Returns all warnings, in the order they were found.
+    pub fn warnings(&self) -> &[PackWarning] {
+        &self.warnings
+    }
+
+    /// Returns true if there are no warnings.
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    /// Returns true if a warning with the given code was raised.
+    pub fn contains(&self, code: PackWarningCode) -> bool {
+        self.warnings.iter().any(|x| x.code() == code)
+    }
+
+    /// Returns the warnings whose codes are not in an allowlist.
+    ///
+    /// # Arguments
+    ///
+    /// * `allowed` - Codes of the expected warnings.
+    pub fn unexpected<'s>(
+        &'s self,
+        allowed: &'s [PackWarningCode],
+    ) -> impl Iterator<Item = &'s PackWarning> + 's {
+        self.warnings
+            .iter()
+            .filter(move |x| !allowed.contains(&x.code()))
+    }
+
+    /// Adds a warning.
+    pub(crate) fn report(&mut self, warning: PackWarning) {
+        #[cfg(feature = "log")]
+        log::warn!("{warning}");
+        self.warnings.push(warning);
+    }
+
+    /// Adds all warnings of another collector, after the existing ones.
+    pub(crate) fn append(&mut self, other: PackDiagnostics) {
+        self.warnings.extend(other.warnings);
+    }
+}
+
+/// Checks the sizes of the table of contents against the limits of the format.
+///
+/// # Arguments
+///
+/// * `file_count` - Number of files in the archive.
+/// * `block_count` - Number of blocks in the archive.
+/// * `pool_size` - Size of the compressed string pool.
+/// * `decompressed_pool_size` - Size of the string pool before compression.
+pub(crate) fn check_table_of_contents(
+    file_count: u64,
+    block_count: u64,
+    pool_size: u64,
+    decompressed_pool_size: u64,
+    diagnostics: &mut PackDiagnostics,
+) {
+    let limits = [
+        (
+            TocLimit::FileCount,
+            file_count,
+            PRESET0_FILE_COUNT_MAX as u64,
+        ),
+        (
+            TocLimit::BlockCount,
+            block_count,
+            PRESET0_BLOCK_COUNT_MAX as u64,
+        ),
+        (
+            TocLimit::StringPoolSize,
+            pool_size,
+            PRESET0_STRING_POOL_SIZE_MAX as u64,
+        ),
+        (
+            TocLimit::DecompressedStringPoolSize,
+            decompressed_pool_size,
+            MAX_STRING_POOL_SIZE as u64,
+        ),
+    ];
+
+    for (limit, value, max) in limits {
+        if value * 100 >= max * NEAR_LIMIT_PERCENT {
+            diagnostics.report(PackWarning::TocNearLimit { limit, value, max });
+        }
+    }
+
+    if pool_size > LARGE_STRING_POOL_SIZE as u64 {
+        diagnostics.report(PackWarning::LargeStringPool {
+            size: pool_size as u32,
+        });
+    }
+}
+
+/// Converts a path to the format's conventions: `/` as the separator, and no leading separator.
+///
+/// # Returns
+///
+/// The normalized path, or [`None`] if the path already follows the conventions.
+pub(crate) fn normalize_path(path: &str) -> Option<String> {
+    if !path.contains('\\') && !path.starts_with('/') {
+        return None;
+    }
+
+    let normalized = path.replace('\\', "/");
+    Some(String::from(normalized.trim_start_matches('/')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn codes_round_trip() {
+        for code in PackWarningCode::ALL {
+            assert_eq!(PackWarningCode::parse(code.as_str()), Some(code));
+        }
+        assert_eq!(
+            PackWarningCode::parse("nx-w003"),
+            Some(PackWarningCode::PathNormalized)
+        );
+        assert_eq!(PackWarningCode::parse("NX-W999"), None);
+    }
+
+    #[test]
+    fn reports_tables_near_limits() {
+        let mut diagnostics = PackDiagnostics::default();
+        check_table_of_contents(10, 1, 100, 1000, &mut diagnostics);
+        assert!(diagnostics.is_empty());
+
+        let file_count = PRESET0_FILE_COUNT_MAX as u64;
+        check_table_of_contents(file_count, 1, 2_000_000, 1000, &mut diagnostics);
+        let codes: Vec<PackWarningCode> = diagnostics.warnings().iter().map(|x| x.code()).collect();
+        assert_eq!(
+            codes.as_slice(),
+            [
+                PackWarningCode::TocNearLimit,
+                PackWarningCode::TocNearLimit,
+                PackWarningCode::LargeStringPool
+            ]
+        );
+    }
+
+    #[test]
+    fn unexpected_skips_allowed_codes() {
+        let mut diagnostics = PackDiagnostics::default();
+        diagnostics.report(PackWarning::IneffectiveDictionaries);
+        diagnostics.report(PackWarning::LargeStringPool { size: 1 << 21 });
+
+        let unexpected: Vec<&PackWarning> = diagnostics
+            .unexpected(&[PackWarningCode::IneffectiveDictionaries])
+            .collect();
+        assert_eq!(unexpected.len(), 1);
+        assert!(unexpected[0].to_string().starts_with("NX-W004: "));
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(normalize_path("data/a.bin"), None);
+        assert_eq!(
+            normalize_path("\\data\\a.bin").as_deref(),
+            Some("data/a.bin")
+        );
+        assert_eq!(normalize_path("/a.bin").as_deref(), Some("a.bin"));
+    }
+}
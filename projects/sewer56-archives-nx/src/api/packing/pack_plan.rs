@@ -0,0 +1,791 @@
+use crate::api::{
+    enums::*, filedata::FromSliceReferenceProvider, packing::packer_file::PackerFile,
+    packing::packing_settings::PackingSettings, traits::*,
+};
+use crate::headers::types::xxh3sum::XXH3sum;
+use crate::implementation::pack::blocks::polyfills::{Block, ChunkedFileBlock, SolidBlock};
+use crate::implementation::pack::state::{
+    pack_state::DeduplicationError, spilled_hash_index::SpilledHashIndex,
+};
+use crate::prelude::*;
+use crate::utilities::arrange::pack::{
+    group_by_extension::group_files,
+    group_by_similarity::{cluster_by_similarity, content_simhash, DEFAULT_MAX_DISTANCE},
+    make_blocks::make_blocks,
+    order_within_block::order_within_block,
+    placement::PlacementReason,
+};
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::ops::Range;
+use hashbrown::{HashMap, HashSet};
+
+/// The placement decisions the packer would make for a set of files, without compressing anything.
+///
+/// Returned by [`NxPackerBuilder::plan`]. Useful for finding out why an archive
+/// turned out larger (or slower to read) than expected.
+///
+/// [`NxPackerBuilder::plan`]: crate::api::packer_builder::NxPackerBuilder::plan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackPlan {
+    /// The order files are placed in within each SOLID block.
+    pub block_ordering: BlockOrdering,
+
+    /// The blocks of the archive, in the order they would be written.
+    pub blocks: Vec<PlannedBlock>,
+
+    /// The files of the archive, in the order they were added to the packer.
+    pub files: Vec<PlannedFile>,
+}
+
+/// A single block the packer would create.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedBlock {
+    /// Algorithm the block would be compressed with.
+    pub compression: CompressionPreference,
+
+    /// Extension whose dictionary the block would be compressed with, if any.
+    /// Always [`None`] for now, as the packer does not compress blocks with dictionaries yet.
+    pub dictionary: Option<String>,
+
+    /// Filter applied to the block's data before compression.
+    pub filter: DataFilter,
+
+    /// Indices into [`PackPlan::files`] of the files stored in this block,
+    /// in the order they are stored.
+    pub files: Vec<usize>,
+
+    /// For blocks holding a chunk of a large file, the index of the chunk.
+    pub chunk_index: Option<u32>,
+}
+
+/// Where a single file would end up, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedFile {
+    /// Path of the file within the archive.
+    pub relative_path: String,
+
+    /// Size of the file.
+    pub file_size: u64,
+
+    /// Extension group the file was sorted into. Empty if the file has no extension.
+    pub group: String,
+
+    /// Why the file was placed in the kind of block it was.
+    pub placement: PlacementReason,
+
+    /// Indices into [`PackPlan::blocks`] of the block(s) holding the file.
+    /// Chunked files span multiple blocks.
+    pub blocks: Range<u32>,
+
+    /// Algorithm the file's block(s) would be compressed with.
+    pub compression: CompressionPreference,
+
+    /// Extension whose dictionary the file would be compressed with, if any.
+    /// Always [`None`] for now, see [`PlannedBlock::dictionary`].
+    pub dictionary: Option<String>,
+
+    /// Path of an earlier file with identical contents, if deduplication is enabled for
+    /// this kind of file. The data would then only be stored once.
+    pub duplicate_of: Option<String>,
+}
+
+impl PackPlan {
+    /// Finds the plan of a file by its path within the archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `relative_path` - Path of the file within the archive.
+    pub fn file(&self, relative_path: &str) -> Option<&PlannedFile> {
+        self.files.iter().find(|x| x.relative_path == relative_path)
+    }
+}
+
+impl fmt::Display for PlannedFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} bytes, group '{}'): {}",
+            self.relative_path, self.file_size, self.group, self.placement
+        )?;
+
+        if self.blocks.len() == 1 {
+            write!(f, "; block {}", self.blocks.start)?;
+        } else {
+            write!(f, "; blocks {}-{}", self.blocks.start, self.blocks.end - 1)?;
+        }
+
+        write!(f, ", compressed with {:?}", self.compression)?;
+        if let Some(dictionary) = &self.dictionary {
+            write!(f, " using the '{dictionary}' dictionary")?;
+        }
+
+        if let Some(original) = &self.duplicate_of {
+            write!(
+                f,
+                "; identical to '{original}', so its data is not stored again"
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for PackPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Files within SOLID blocks ordered by {:?}",
+            self.block_ordering
+        )?;
+        for file in &self.files {
+            writeln!(f, "{file}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Stand-in for a [`PackerFile`], which can be passed through [`make_blocks`] without its data.
+struct PlanItem {
+    index: usize,
+    relative_path: String,
+    file_size: u64,
+    solid_type: SolidPreference,
+    compression_preference: CompressionPreference,
+    force_own_block: bool,
+    chunk_size_override: Option<u32>,
+    no_data: FromSliceReferenceProvider<'static>,
+}
+
+impl HasFileSize for PlanItem {
+    fn file_size(&self) -> u64 {
+        self.file_size
+    }
+}
+
+impl HasRelativePath for PlanItem {
+    fn relative_path(&self) -> &str {
+        &self.relative_path
+    }
+}
+
+impl HasSolidType for PlanItem {
+    fn solid_type(&self) -> SolidPreference {
+        self.solid_type
+    }
+
+    fn set_solid_type(&mut self, preference: SolidPreference) {
+        self.solid_type = preference;
+    }
+
+    fn force_own_block(&self) -> bool {
+        self.force_own_block
+    }
+
+    fn chunk_size_override(&self) -> Option<u32> {
+        self.chunk_size_override
+    }
+}
+
+impl HasCompressionPreference for PlanItem {
+    fn compression_preference(&self) -> CompressionPreference {
+        self.compression_preference
+    }
+}
+
+impl CanProvideInputData for PlanItem {
+    fn input_data_provider(&self) -> &dyn InputDataProvider {
+        &self.no_data
+    }
+}
+
+/// Works out where the packer would place each file, using the same steps as packing
+/// (sort by size, group by extension, make blocks), but without compressing anything.
+///
+/// # Arguments
+///
+/// * `files` - The files to be packed.
+/// * `settings` - The settings to pack with. These are sanitized first, as when packing.
+///
+/// # Remarks
+///
+/// If deduplication is enabled, every file which could be deduplicated is read and hashed.
+/// With [`FileGrouping::ContentSimilarity`] or [`BlockOrdering::ContentSimilarity`],
+/// samples of the grouped or ordered files are also read.
+///
+/// Extension groups are visited in order of their extension, so the plan does not
+/// depend on hash map order. Blocks from [`PackingSettings::manual_blocks`] come first.
+/// Paths in them which match no file, or repeat an earlier path, are skipped here.
+/// Packing rejects them with an error instead.
+pub fn create_plan(
+    files: &[PackerFile<'_>],
+    settings: &PackingSettings,
+) -> Result<PackPlan, FileProviderError> {
+    let mut settings = settings.clone();
+    settings.sanitize();
+    let sizes: Vec<u64> = files.iter().map(|file| file.file_size()).collect();
+
+    // Packing rejects unknown or repeated paths; the plan skips them.
+    let index_of: HashMap<&str, usize> = files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| (file.relative_path(), index))
+        .collect();
+    let mut include = vec![true; files.len()];
+    let manual_blocks: Vec<Vec<usize>> = settings
+        .manual_blocks
+        .iter()
+        .map(|paths| {
+            paths
+                .iter()
+                .filter_map(|path| index_of.get(path.as_str()).copied())
+                .filter(|index| core::mem::replace(&mut include[*index], false))
+                .collect::<Vec<usize>>()
+        })
+        .filter(|block| !block.is_empty())
+        .collect();
+
+    plan_blocks(
+        files,
+        &sizes,
+        &include,
+        &manual_blocks,
+        &settings,
+        settings.chunk_size,
+        |index| hash_file(&files[index], sizes[index]),
+    )
+}
+
+/// Works out the blocks for a subset of files; this is the plan [`create_plan`] explains,
+/// and the one [`pack_incremental`] compresses blocks from.
+///
+/// # Arguments
+///
+/// * `files` - All files being packed.
+/// * `sizes` - Size of each file, which may differ from [`HasFileSize::file_size`]
+///   if a file changed after being added.
+/// * `include` - Whether each file is placed in a block. Excluded files (e.g. ones reused
+///   from a previous archive) are listed in [`PackPlan::files`] with no blocks.
+/// * `manual_blocks` - Indices of the files in each of [`PackingSettings::manual_blocks`].
+///   These blocks come first, in the order given; their files must not be included.
+/// * `settings` - Sanitized settings to pack with.
+/// * `chunk_size` - Size of the chunks of large files; SOLID blocks are kept smaller than it.
+/// * `hash_of` - Returns the hash of a file's contents, used to find duplicates.
+///
+/// [`pack_incremental`]: crate::api::incremental_pack::pack_incremental
+pub(crate) fn plan_blocks(
+    files: &[PackerFile<'_>],
+    sizes: &[u64],
+    include: &[bool],
+    manual_blocks: &[Vec<usize>],
+    settings: &PackingSettings,
+    chunk_size: u32,
+    hash_of: impl FnMut(usize) -> Result<u64, FileProviderError>,
+) -> Result<PackPlan, FileProviderError> {
+    // SOLID blocks must stay below the chunk size, else readers would treat their files as chunked.
+    let block_size = settings.block_size.min(chunk_size - 1);
+    let threshold = settings.effective_solid_size_threshold().min(block_size);
+
+    let all_items: Vec<Rc<PlanItem>> = files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| {
+            Rc::new(PlanItem {
+                index,
+                relative_path: file.relative_path().to_string(),
+                file_size: sizes[index],
+                solid_type: file.solid_type(),
+                compression_preference: file.compression_preference(),
+                force_own_block: file.force_own_block(),
+                chunk_size_override: file.chunk_size_override(),
+                no_data: FromSliceReferenceProvider::new(&[]),
+            })
+        })
+        .collect();
+    let mut placements: Vec<PlacementReason> = all_items
+        .iter()
+        .map(|item| PlacementReason::of(&**item, threshold))
+        .collect();
+    for &index in manual_blocks.iter().flatten() {
+        placements[index] = PlacementReason::ManualBlock;
+    }
+
+    let duplicates = find_duplicates(&placements, sizes, include, settings, hash_of)?;
+
+    // Duplicates of chunked files take no blocks at all; duplicates of SOLID packed files
+    // still take part in making blocks, so blocks they leave under-filled can be merged.
+    let mut items: Vec<Rc<PlanItem>> = all_items
+        .iter()
+        .filter(|item| {
+            include[item.index]
+                && !(duplicates[item.index].is_some()
+                    && matches!(placements[item.index], PlacementReason::Chunked { .. }))
+        })
+        .cloned()
+        .collect();
+    if settings.stable_order {
+        items.sort_by(|a, b| {
+            a.file_size
+                .cmp(&b.file_size)
+                .then_with(|| a.relative_path.cmp(&b.relative_path))
+        });
+    } else {
+        items.sort_by_key(|item| item.file_size);
+    }
+
+    let mut groups = group_files(&items);
+    if settings.file_grouping == FileGrouping::ContentSimilarity {
+        for group in groups.values_mut() {
+            cluster_by_similarity(group, DEFAULT_MAX_DISTANCE, |item| {
+                content_simhash(files[item.index].input_data_provider(), item.file_size)
+            })?;
+        }
+    }
+    let mut group_of = vec![String::new(); files.len()];
+    for (extension, group) in &groups {
+        for item in group {
+            group_of[item.index] = extension.to_string();
+        }
+    }
+    let mut groups: Vec<(&str, Vec<Rc<PlanItem>>)> = groups.into_iter().collect();
+    groups.sort_by(|a, b| a.0.cmp(b.0));
+
+    let duplicate_paths: HashSet<&str> = files
+        .iter()
+        .zip(&duplicates)
+        .filter(|(_, duplicate)| duplicate.is_some())
+        .map(|(file, _)| file.relative_path())
+        .collect();
+    let result = make_blocks(
+        groups,
+        block_size,
+        chunk_size,
+        settings.solid_block_algorithm,
+        settings.chunked_file_algorithm,
+        &settings.extension_filters,
+        settings.auto_block_sizing,
+        settings.max_files_per_block,
+        settings.solid_size_threshold,
+        &duplicate_paths,
+    );
+
+    let mut blocks = Vec::with_capacity(result.blocks.len());
+    let mut file_blocks: Vec<Option<Range<u32>>> = vec![None; files.len()];
+    let mut file_compression = vec![CompressionPreference::NoPreference; files.len()];
+    for block in manual_blocks {
+        plan_manual_block(
+            block,
+            sizes,
+            files,
+            settings,
+            block_size,
+            chunk_size,
+            &mut blocks,
+            &mut file_blocks,
+            &mut file_compression,
+        );
+    }
+
+    for block in &result.blocks {
+        let (compression, chunk_index) = describe_block(&**block, settings);
+        let mut items = Vec::from(block.items());
+        if chunk_index.is_none() {
+            order_within_block(&mut items, settings.block_ordering, |item| {
+                content_simhash(files[item.index].input_data_provider(), item.file_size)
+            })?;
+        }
+
+        // Duplicates are stored with the file they duplicate; a block left with
+        // nothing but duplicates is not written at all.
+        let file_indices: Vec<usize> = items
+            .iter()
+            .map(|x| x.index)
+            .filter(|x| duplicates[*x].is_none())
+            .collect();
+        if file_indices.is_empty() {
+            continue;
+        }
+
+        let block_index = blocks.len() as u32;
+        for &file in &file_indices {
+            let range = file_blocks[file].get_or_insert(block_index..block_index);
+            range.end = block_index + 1;
+            file_compression[file] = compression;
+        }
+
+        // The packer does not compress blocks with dictionaries yet.
+        blocks.push(PlannedBlock {
+            compression,
+            dictionary: None,
+            filter: block.filter(),
+            files: file_indices,
+            chunk_index,
+        });
+    }
+
+    for (index, original) in duplicates.iter().enumerate() {
+        if let Some(original) = *original {
+            file_blocks[index] = file_blocks[original].clone();
+            file_compression[index] = file_compression[original];
+        }
+    }
+
+    let planned_files = files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| {
+            let block_range = file_blocks[index].clone().unwrap_or(0..0);
+            PlannedFile {
+                relative_path: file.relative_path().to_string(),
+                file_size: sizes[index],
+                group: group_of[index].clone(),
+                placement: placements[index],
+                dictionary: match block_range.is_empty() {
+                    true => None,
+                    false => blocks[block_range.start as usize].dictionary.clone(),
+                },
+                blocks: block_range,
+                compression: file_compression[index],
+                duplicate_of: duplicates[index].map(|x| files[x].relative_path().to_string()),
+            }
+        })
+        .collect();
+
+    Ok(PackPlan {
+        block_ordering: settings.block_ordering,
+        blocks,
+        files: planned_files,
+    })
+}
+
+/// Adds the blocks of one of [`PackingSettings::manual_blocks`] to a plan. Files larger
+/// than a SOLID block are chunked first, then the rest share a single SOLID block.
+#[allow(clippy::too_many_arguments)]
+fn plan_manual_block(
+    block: &[usize],
+    sizes: &[u64],
+    files: &[PackerFile<'_>],
+    settings: &PackingSettings,
+    block_size: u32,
+    chunk_size: u32,
+    blocks: &mut Vec<PlannedBlock>,
+    file_blocks: &mut [Option<Range<u32>>],
+    file_compression: &mut [CompressionPreference],
+) {
+    let mut solid = Vec::new();
+    for &index in block {
+        if sizes[index] <= block_size as u64 {
+            solid.push(index);
+            continue;
+        }
+
+        let first_block = blocks.len() as u32;
+        let chunk_size = files[index].chunk_size_override().unwrap_or(chunk_size);
+        let chunk_count = sizes[index].div_ceil(chunk_size as u64) as u32;
+        for chunk_index in 0..chunk_count {
+            blocks.push(PlannedBlock {
+                compression: settings.chunked_file_algorithm,
+                dictionary: None,
+                filter: DataFilter::None,
+                files: vec![index],
+                chunk_index: Some(chunk_index),
+            });
+        }
+        file_blocks[index] = Some(first_block..first_block + chunk_count);
+        file_compression[index] = settings.chunked_file_algorithm;
+    }
+
+    if solid.is_empty() {
+        return;
+    }
+
+    let block_index = blocks.len() as u32;
+    for &index in &solid {
+        file_blocks[index] = Some(block_index..block_index + 1);
+        file_compression[index] = settings.solid_block_algorithm;
+    }
+    blocks.push(PlannedBlock {
+        compression: settings.solid_block_algorithm,
+        dictionary: None,
+        filter: DataFilter::None,
+        files: solid,
+        chunk_index: None,
+    });
+}
+
+/// Returns the compression of a block, and the chunk index if it's a chunk of a large file.
+/// Files without a compression preference of their own use the algorithm of the settings.
+fn describe_block(
+    block: &dyn Block<PlanItem>,
+    settings: &PackingSettings,
+) -> (CompressionPreference, Option<u32>) {
+    if let Some(solid) = block.as_any().downcast_ref::<SolidBlock<PlanItem>>() {
+        let compression = match solid.compression_preference {
+            CompressionPreference::NoPreference | CompressionPreference::Mixed => {
+                settings.solid_block_algorithm
+            }
+            compression => compression,
+        };
+        (compression, None)
+    } else if let Some(chunk) = block.as_any().downcast_ref::<ChunkedFileBlock<PlanItem>>() {
+        (chunk.state.compression, Some(chunk.chunk_index))
+    } else {
+        (CompressionPreference::NoPreference, None)
+    }
+}
+
+/// For each file, finds the index of an earlier file with the same contents,
+/// if deduplication is enabled for the kind of block the file is placed in.
+///
+/// The hashes are indexed within [`PackingSettings::dedup_memory_limit`], if set.
+fn find_duplicates(
+    placements: &[PlacementReason],
+    sizes: &[u64],
+    include: &[bool],
+    settings: &PackingSettings,
+    mut hash_of: impl FnMut(usize) -> Result<u64, FileProviderError>,
+) -> Result<Vec<Option<usize>>, FileProviderError> {
+    let limit = settings
+        .dedup_memory_limit
+        .as_ref()
+        .map(|limit| limit.split(2));
+    let new_index = || match &limit {
+        Some(limit) => SpilledHashIndex::with_memory_limit(limit),
+        None => SpilledHashIndex::new(),
+    };
+    let mut chunked_index = new_index();
+    let mut solid_index = new_index();
+    let mut duplicates = vec![None; placements.len()];
+
+    for (index, placement) in placements.iter().enumerate() {
+        let chunked = matches!(placement, PlacementReason::Chunked { .. });
+        let enabled = if chunked {
+            settings.enable_chunked_deduplication
+        } else {
+            settings.enable_solid_deduplication
+        };
+        if !enabled || !include[index] || sizes[index] == 0 {
+            continue;
+        }
+
+        let hash = XXH3sum(hash_of(index)?);
+        let first_with_hash = match chunked {
+            true => &mut chunked_index,
+            false => &mut solid_index,
+        };
+        match first_with_hash.get(hash).map_err(dedup_error)? {
+            // A file of another size with the same hash is a collision, not a duplicate.
+            Some(original) if sizes[original as usize] == sizes[index] => {
+                duplicates[index] = Some(original as usize)
+            }
+            Some(_) => {}
+            None => first_with_hash
+                .insert(hash, index as u32)
+                .map_err(dedup_error)?,
+        }
+    }
+
+    Ok(duplicates)
+}
+
+/// Converts a failure of the deduplication index, e.g. reading spilled hashes from disk.
+fn dedup_error(error: DeduplicationError) -> FileProviderError {
+    match error {
+        DeduplicationError::SpillError(error) => FileProviderError::IoError(error),
+        DeduplicationError::ReadLockError | DeduplicationError::WriteLockError => {
+            FileProviderError::FailedToAcquireLock()
+        }
+    }
+}
+
+/// Hashes the contents of a file, for finding duplicates.
+fn hash_file(file: &PackerFile<'_>, size: u64) -> Result<u64, FileProviderError> {
+    let data = file.input_data_provider().get_file_data(0, size)?;
+    Ok(XXH3sum::create(data.data()).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::packer_builder::{AddFileParams, NxPackerBuilder};
+
+    #[test]
+    fn explains_file_placement() {
+        let large = [7u8; 10_000];
+        let mut builder = NxPackerBuilder::new()
+            .with_block_size(4095)
+            .with_chunk_size(32768)
+            .with_solid_deduplication(true);
+        builder.add_file_from_byte_slice(b"config", AddFileParams::new("a.ini".into()));
+        builder.add_file_from_byte_slice(b"config", AddFileParams::new("b.ini".into()));
+        builder.add_file_from_byte_slice(&large, AddFileParams::new("large.bin".into()));
+
+        let plan = builder.plan().unwrap();
+        assert_eq!(plan.files.len(), 3);
+
+        let a = plan.file("a.ini").unwrap();
+        let b = plan.file("b.ini").unwrap();
+        assert_eq!(a.group, "ini");
+        assert_eq!(a.placement, PlacementReason::Solid);
+        assert_eq!(a.blocks, b.blocks);
+        assert_eq!(a.duplicate_of, None);
+        assert_eq!(b.duplicate_of.as_deref(), Some("a.ini"));
+        assert_eq!(a.dictionary, None);
+
+        let large = plan.file("large.bin").unwrap();
+        assert!(matches!(large.placement, PlacementReason::Chunked { .. }));
+        assert_eq!(large.blocks.len(), 1);
+        let block = &plan.blocks[large.blocks.start as usize];
+        assert_eq!(block.chunk_index, Some(0));
+        assert_eq!(block.files, vec![2]);
+
+        let explanation = plan.to_string();
+        assert!(explanation.contains("b.ini"));
+        assert!(explanation.contains("identical to 'a.ini'"));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn finds_duplicates_within_memory_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = NxPackerBuilder::new()
+            .with_solid_deduplication(true)
+            .with_dedup_memory_limit(1, dir.path().to_path_buf());
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            builder.add_file_from_byte_slice(name.as_bytes(), AddFileParams::new(name.into()));
+        }
+        builder.add_file_from_byte_slice(b"a.txt", AddFileParams::new("d.txt".into()));
+
+        // Every hash is spilled to disk, and still found.
+        let plan = builder.plan().unwrap();
+        assert_eq!(
+            plan.file("d.txt").unwrap().duplicate_of.as_deref(),
+            Some("a.txt")
+        );
+        assert_eq!(plan.file("b.txt").unwrap().duplicate_of, None);
+    }
+
+    #[test]
+    fn orders_files_within_solid_blocks() {
+        let mut builder = NxPackerBuilder::new().with_block_ordering(BlockOrdering::Path);
+        builder.add_file_from_byte_slice(b"large file", AddFileParams::new("a.txt".into()));
+        builder.add_file_from_byte_slice(b"small", AddFileParams::new("b.txt".into()));
+
+        let plan = builder.plan().unwrap();
+        assert_eq!(plan.block_ordering, BlockOrdering::Path);
+        assert_eq!(plan.blocks[0].files, vec![0, 1]);
+        assert!(plan.to_string().contains("ordered by Path"));
+    }
+
+    #[test]
+    fn content_similarity_places_variants_together() {
+        let mut state = 1u64;
+        let mut random = |len: usize| -> Vec<u8> {
+            (0..len)
+                .map(|_| {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    (state >> 56) as u8
+                })
+                .collect()
+        };
+        let first = random(2000);
+        let second = random(2001);
+        let mut first_variant = first.clone();
+        first_variant.extend_from_slice(&[0, 0]);
+        let mut second_variant = second.clone();
+        second_variant.extend_from_slice(&[0, 0]);
+
+        let plan_with = |grouping: FileGrouping| {
+            let mut builder = NxPackerBuilder::new()
+                .with_block_size(4095)
+                .with_solid_deduplication(false)
+                .with_file_grouping(grouping);
+            builder.add_file_from_byte_slice(&first, AddFileParams::new("en.loc".into()));
+            builder.add_file_from_byte_slice(&second, AddFileParams::new("ui.loc".into()));
+            builder.add_file_from_byte_slice(&first_variant, AddFileParams::new("de.loc".into()));
+            builder
+                .add_file_from_byte_slice(&second_variant, AddFileParams::new("ui_de.loc".into()));
+            builder.plan().unwrap()
+        };
+
+        let plan = plan_with(FileGrouping::Extension);
+        assert_eq!(
+            plan.file("en.loc").unwrap().blocks,
+            plan.file("ui.loc").unwrap().blocks
+        );
+
+        let plan = plan_with(FileGrouping::ContentSimilarity);
+        assert_eq!(
+            plan.file("en.loc").unwrap().blocks,
+            plan.file("de.loc").unwrap().blocks
+        );
+        assert_eq!(
+            plan.file("ui.loc").unwrap().blocks,
+            plan.file("ui_de.loc").unwrap().blocks
+        );
+        assert_ne!(
+            plan.file("en.loc").unwrap().blocks,
+            plan.file("ui.loc").unwrap().blocks
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn plan_matches_packed_archive() {
+        use crate::api::archive_reader::NxArchiveReader;
+        use crate::unsize_box2;
+        use crate::utilities::tests::archive_for_testing::create_test_archive;
+
+        let text =
+            |seed: u32, len: u32| -> Vec<u8> { (0..len).map(|x| (x % 13 + seed) as u8).collect() };
+        let (a, b, manifest, m1, m2) = (
+            text(1, 1000),
+            text(2, 1500),
+            text(3, 500),
+            text(4, 700),
+            text(5, 800),
+        );
+        let large = text(6, 40_000);
+        let mut builder = NxPackerBuilder::new()
+            .with_block_size(4095)
+            .with_chunk_size(32768)
+            .with_manual_block_assignment(vec![vec!["m1.txt".into(), "m2.txt".into()]]);
+        builder.add_file_from_byte_slice(&a, AddFileParams::new("a.txt".into()));
+        builder.add_file_from_byte_slice(&large, AddFileParams::new("large.bin".into()));
+        builder.add_file_from_byte_slice(
+            &manifest,
+            AddFileParams::new("manifest.json".into()).with_own_block(true),
+        );
+        builder.add_file_from_byte_slice(&m1, AddFileParams::new("m1.txt".into()));
+        builder.add_file_from_byte_slice(&b, AddFileParams::new("b.txt".into()));
+        builder.add_file_from_byte_slice(&m2, AddFileParams::new("m2.txt".into()));
+        builder.add_file_from_byte_slice(&a, AddFileParams::new("copy_of_a.txt".into()));
+
+        let plan = builder.plan().unwrap();
+        assert_eq!(plan.blocks[0].files, vec![3, 5]);
+        assert_eq!(
+            plan.file("m1.txt").unwrap().placement,
+            PlacementReason::ManualBlock
+        );
+
+        let empty = create_test_archive(32768, &[], &[], CompressionPreference::ZStandard);
+        let provider = Box::new(FromSliceReferenceProvider::new(&empty));
+        let previous = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+        let (archive, _) = builder.pack_incremental(&previous).unwrap();
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+
+        assert_eq!(plan.blocks.len() as u32, reader.block_count());
+        for file in &plan.files {
+            let entry = reader.find_entry(&file.relative_path).unwrap().unwrap();
+            assert_eq!(
+                entry.first_block_index, file.blocks.start,
+                "{}",
+                file.relative_path
+            );
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::api::packing::packing_settings::{MAX_CHUNK_SIZE, MIN_CHUNK_SIZE};
 use crate::api::{enums::*, filedata::*, traits::*};
 use crate::{prelude::*, unsize_box2};
 use alloc::string::String;
@@ -18,6 +19,12 @@ pub struct PackerFile<'a> {
 
     /// Whether this file should be in a SOLID block
     solid_preference: SolidPreference,
+
+    /// Whether this file must be stored alone in its own block
+    force_own_block: bool,
+
+    /// Size of the chunks this file is split into, if not the archive's
+    chunk_size_override: Option<u32>,
 }
 
 /// Manual implementation of Debug, to skip InputDataProvider
@@ -28,6 +35,8 @@ impl std::fmt::Debug for PackerFile<'_> {
             .field("file_size", &self.file_size)
             .field("compression_preference", &self.compression_preference)
             .field("solid_preference", &self.solid_preference)
+            .field("force_own_block", &self.force_own_block)
+            .field("chunk_size_override", &self.chunk_size_override)
             .finish()
     }
 }
@@ -51,6 +60,8 @@ impl<'a> PackerFile<'a> {
             data_provider: provider,
             compression_preference: CompressionPreference::NoPreference,
             solid_preference: SolidPreference::Default,
+            force_own_block: false,
+            chunk_size_override: None,
         }
     }
 
@@ -88,7 +99,26 @@ impl<'a> PackerFile<'a> {
         source_path: &str,
         relative_path: String,
     ) -> Result<Self, FileProviderError> {
-        let provider = Box::new(FromFilePathProvider::new(source_path)?);
+        Self::from_file_path_with_policy(source_path, relative_path, &FileHandlePolicy::KeepOpen)
+    }
+
+    /// Creates a new PackerFile from a path, managing the file's handle as per a given policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_path` - Path to the file on disk
+    /// * `relative_path` - Path the file should have within the archive
+    /// * `policy` - When the file is opened and closed
+    ///
+    /// # Returns
+    ///
+    /// A Result containing either the new PackerFile or an error if the provider couldn't be created
+    pub fn from_file_path_with_policy(
+        source_path: &str,
+        relative_path: String,
+        policy: &FileHandlePolicy,
+    ) -> Result<Self, FileProviderError> {
+        let provider = Box::new(FromFilePathProvider::with_policy(source_path, policy)?);
         Ok(Self::new(
             relative_path,
             provider.file_size()? as u64,
@@ -107,6 +137,36 @@ impl<'a> PackerFile<'a> {
         self.solid_preference = preference;
         self
     }
+
+    /// Sets whether this file is stored alone in its own, quickly decompressed block
+    pub fn with_own_block(mut self, force_own_block: bool) -> Self {
+        self.force_own_block = force_own_block;
+        self
+    }
+
+    /// Sets the size of the chunks this file is split into, instead of the archive's chunk size.
+    /// The size is clamped to [`MIN_CHUNK_SIZE`]..=[`MAX_CHUNK_SIZE`] and rounded up to a power of 2.
+    pub fn with_chunk_size_override(mut self, chunk_size: Option<u32>) -> Self {
+        self.chunk_size_override =
+            chunk_size.map(|x| x.clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE).next_power_of_two());
+        self
+    }
+
+    /// Sets the path this file should have within the archive
+    pub fn with_relative_path(mut self, relative_path: String) -> Self {
+        self.relative_path = relative_path;
+        self
+    }
+
+    /// Retries failed reads of the file's contents as per the given policy,
+    /// by wrapping its provider in a [`RetryingProvider`].
+    pub fn with_retry_policy(self, policy: RetryPolicy) -> Self {
+        let provider = Box::new(RetryingProvider::new(self.data_provider, policy));
+        Self {
+            data_provider: unsize_box2!(provider),
+            ..self
+        }
+    }
 }
 
 impl HasFileSize for PackerFile<'_> {
@@ -135,6 +195,14 @@ impl HasSolidType for PackerFile<'_> {
     fn set_solid_type(&mut self, preference: SolidPreference) {
         self.solid_preference = preference;
     }
+
+    fn force_own_block(&self) -> bool {
+        self.force_own_block
+    }
+
+    fn chunk_size_override(&self) -> Option<u32> {
+        self.chunk_size_override
+    }
 }
 
 impl CanProvideInputData for PackerFile<'_> {
@@ -186,6 +254,8 @@ mod tests {
 
         assert_eq!(file.compression_preference(), CompressionPreference::Lz4);
         assert_eq!(file.solid_type(), SolidPreference::NoSolid);
+        assert!(!file.force_own_block());
+        assert!(file.with_own_block(true).force_own_block());
     }
 
     #[test]
@@ -1,10 +1,32 @@
 #![allow(clippy::absurd_extreme_comparisons)]
 
-use core::hint::unreachable_unchecked;
+use alloc::string::String;
+use core::ops::RangeInclusive;
+use hashbrown::HashMap;
 use static_assertions::const_assert;
 
 // STD ALERT!! However it's portable traits only.
 use crate::api::enums::*;
+use crate::headers::parser::{StringPoolCompression, MAX_INLINE_FILE_SIZE, MAX_PATH_FILTER_BITS};
+use crate::implementation::pack::state::spilled_hash_index::DedupMemoryLimit;
+use crate::prelude::*;
+use crate::utilities::compression::{
+    copy_fallback::CopyFallback, zstd_advanced::ZstdAdvancedParams, LZ4_MAX_LEVEL, LZ4_MIN_LEVEL,
+};
+#[cfg(feature = "fault_injection")]
+use crate::utilities::fault_injection::FaultInjector;
+#[cfg(feature = "fault_injection")]
+use alloc::sync::Arc;
+
+/// An algorithm and level to try on each block, see [`PackingSettings::compression_candidates`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionCandidate {
+    /// The compression algorithm.
+    pub algorithm: CompressionPreference,
+
+    /// Level to compress at.
+    pub level: i32,
+}
 
 /// The minimum block size that the user is allowed to specify
 pub const MIN_BLOCK_SIZE: u32 = 4095;
@@ -26,6 +48,7 @@ pub const MAX_CHUNK_SIZE: u32 = 1_073_741_824;
 /// This struct contains settings that determine how the packing process
 /// will be performed, including block and chunk sizes, compression levels,
 /// and compression algorithms.
+#[derive(Clone)]
 pub struct PackingSettings {
     /// Size of SOLID blocks.\
     /// Range is MIN_BLOCK_SIZE to 67108863 (64 MiB).\
@@ -42,9 +65,63 @@ pub struct PackingSettings {
     /// Must be greater than [`Self::block_size`].
     pub chunk_size: u32,
 
-    /// Set this to 'true' to store hashes in the ToC.
-    /// Without this, hashes will not be stored in the ToC.
-    pub store_hashes: bool,
+    /// If enabled, a SOLID block size is picked for each extension group, based on the sizes
+    /// of its files, with [`Self::block_size`] as the upper limit.
+    ///
+    /// Groups of tiny files (e.g. configs) then get small blocks for fast access,
+    /// while groups of large files (e.g. textures) get large blocks for a better ratio.
+    pub auto_block_sizing: bool,
+
+    /// Maximum number of files which may share a single SOLID block.
+    ///
+    /// Bounds how much unrelated data is decompressed when extracting any single small file,
+    /// which keeps access latency predictable (e.g. in a virtual filesystem).
+    /// [`None`] means no limit.
+    pub max_files_per_block: Option<u32>,
+
+    /// Files larger than this are split into chunks, rather than SOLID packed.
+    ///
+    /// [`None`] uses [`Self::block_size`], the largest file which can fit in a SOLID block.
+    /// Lower values keep medium sized files out of SOLID blocks, so they can be read
+    /// (and deduplicated) on their own. Must not exceed [`Self::block_size`].
+    pub solid_size_threshold: Option<u32>,
+
+    /// Files of up to this many bytes are also stored in the header of the archive,
+    /// so reading them needs no block to be fetched or decompressed. Useful for small
+    /// files read at startup (e.g. manifests, configuration stubs).
+    ///
+    /// [`None`] disables inlining. Capped at [`MAX_INLINE_FILE_SIZE`].
+    pub inline_file_threshold: Option<u32>,
+
+    /// If set, a bloom filter of the file paths is stored in the header, using this many bits
+    /// per path. Readers use it to reject paths not in the archive without unpacking the
+    /// string pool, which helps when many archives are layered over each other.
+    ///
+    /// [`DEFAULT_PATH_FILTER_BITS`] gives roughly 1% false positives. Capped at [`MAX_PATH_FILTER_BITS`].
+    ///
+    /// [`DEFAULT_PATH_FILTER_BITS`]: crate::headers::parser::DEFAULT_PATH_FILTER_BITS
+    pub path_filter_bits: Option<u32>,
+
+    /// If true, the range of paths within each directory is stored in the header,
+    /// letting readers list a directory's files without unpacking the string pool.
+    pub store_directory_index: bool,
+
+    /// If true, the hash of each chunk of the chunked files is stored in the header,
+    /// so chunks of large files can be verified on their own (e.g. by a downloader resuming
+    /// a file). Costs 8 bytes per chunk. See [`ChunkHashes`].
+    ///
+    /// [`ChunkHashes`]: crate::headers::parser::ChunkHashes
+    pub store_chunk_hashes: bool,
+
+    /// Controls whether file hashes are stored in the ToC.
+    /// Without hashes, the ToC is smaller, but files cannot be verified or found by hash.
+    pub store_file_hashes: FileHashStorage,
+
+    /// Set this to 'false' to omit the string pool (file paths) from the ToC.
+    ///
+    /// This produces an anonymous, content addressed archive; files can then only be
+    /// found by their hash, so [`Self::store_file_hashes`] should be left enabled.
+    pub store_file_paths: bool,
 
     /// Compression level to use for SOLID data.
     ///
@@ -62,6 +139,10 @@ pub struct PackingSettings {
     /// LZ4 has Range: 1 - 12.
     pub chunked_compression_level: i32,
 
+    /// Advanced ZStandard parameters, such as long distance matching and the window size.
+    /// These apply to both SOLID blocks and chunks compressed with ZStandard.
+    pub zstd_advanced: ZstdAdvancedParams,
+
     /// Compression algorithm used for compressing SOLID blocks.
     pub solid_block_algorithm: CompressionPreference,
 
@@ -76,8 +157,120 @@ pub struct PackingSettings {
     /// Solid deduplication is virtually free for each file
     pub enable_solid_deduplication: bool,
 
+    /// If set, caps the memory used by the deduplication indexes, writing hashes past
+    /// the cap to disk. Keeps deduplication usable for inputs with more hashes than fit in memory.
+    pub dedup_memory_limit: Option<DedupMemoryLimit>,
+
+    /// Seed of the XXH3 hashes stored for each file; 0 for unseeded hashes.
+    ///
+    /// The seed is stored in the archive, so files can still be deduplicated and verified,
+    /// but the hashes of a publicly hosted archive can't be matched against databases of
+    /// hashes of known files. Note that the seed is not secret to anyone holding the archive.
+    pub hash_seed: u64,
+
     /// If enabled, a dictionary will be created per file extension.
+    ///
+    /// Off by default; the packer does not compress with dictionaries yet, so enabling this
+    /// only reports [`PackWarning::IneffectiveDictionaries`].
+    ///
+    /// [`PackWarning::IneffectiveDictionaries`]: crate::api::packing::pack_diagnostics::PackWarning::IneffectiveDictionaries
     pub enable_per_extension_dictionary: bool,
+
+    /// If enabled, every compressed block is decompressed and its hash compared against
+    /// the original data before it is written, catching faulty compression (or faulty RAM)
+    /// at pack time rather than when the archive is extracted.
+    ///
+    /// This roughly adds the cost of decompressing the whole archive to packing.
+    pub verify_blocks: bool,
+
+    /// If enabled, files within a SOLID block which don't compress (e.g. already compressed
+    /// textures or audio) are stored raw, while the rest of the block is compressed with
+    /// [`Self::solid_block_algorithm`]. Such blocks are stored as [`CompressionPreference::Mixed`].
+    ///
+    /// This avoids decompressing incompressible files on extraction, at the cost of
+    /// compressing each file of a block once more to test it.
+    pub mixed_codec_blocks: bool,
+
+    /// Controls how files are arranged into SOLID blocks.
+    pub file_grouping: FileGrouping,
+
+    /// Controls what happens when a file changes between being added and being packed.
+    pub file_change_policy: FileChangePolicy,
+
+    /// If enabled, files of the same size are arranged into blocks in path order, rather than
+    /// the order they were added in (which, for folders, depends on the order the file system
+    /// lists them).
+    ///
+    /// This is a tie-break only; packing runs on a single thread, so there is no compression
+    /// order to pin down. With the same files and settings, adding the files in a different
+    /// order then produces the same archive, which diffs cleanly against the previous one.
+    pub stable_order: bool,
+
+    /// Files to pack into blocks of their own, by path; each inner list becomes one block,
+    /// with its files in the given order. These blocks are written first, in the given order.
+    ///
+    /// Files not listed here are arranged automatically, after the listed ones.
+    /// A block holding a single file too large for a SOLID block is chunked instead.
+    pub manual_blocks: Vec<Vec<String>>,
+
+    /// Controls the order of files within each SOLID block.
+    pub block_ordering: BlockOrdering,
+
+    /// Filters applied to data before compression, keyed by file extension (without the dot).
+    /// Extensions not in this map are not filtered.
+    pub extension_filters: HashMap<String, DataFilter>,
+
+    /// Order the file paths (and therefore entries) are sorted in.
+    pub path_collation: PathCollation,
+
+    /// Controls how the string pool (file paths) is compressed.
+    /// Set [`StringPoolCompression::min_savings`] to store small pools raw, for faster opening.
+    pub string_pool_compression: StringPoolCompression,
+
+    /// Decides when compressed blocks save too little space, and are stored raw instead.
+    /// Raise the minimum savings when packing mostly pre-compressed assets (e.g. textures, audio)
+    /// to skip compressing them sooner.
+    pub copy_fallback: CopyFallback,
+
+    /// If set, the ZStandard level is adjusted while packing, aiming for this many
+    /// megabytes of input compressed per second. Trades ratio for predictable pack times.
+    ///
+    /// The levels set in this struct are used as the starting point.
+    pub target_throughput_mbps: Option<u32>,
+
+    /// Range of ZStandard levels used when [`Self::target_throughput_mbps`] is set.
+    pub adaptive_level_range: RangeInclusive<i32>,
+
+    /// CPU priority of the thread compressing blocks.
+    /// Lower it to avoid stutters in programs running at the same time, e.g. games.
+    /// See [`NxPackerBuilder::with_worker_priority`] for the supported platforms.
+    ///
+    /// [`NxPackerBuilder::with_worker_priority`]: crate::api::packer_builder::NxPackerBuilder::with_worker_priority
+    pub worker_priority: WorkerPriority,
+
+    /// If set, the thread compressing blocks only runs on the CPU cores with these indices.
+    /// See [`NxPackerBuilder::with_worker_cores`] for the supported platforms.
+    ///
+    /// [`NxPackerBuilder::with_worker_cores`]: crate::api::packer_builder::NxPackerBuilder::with_worker_cores
+    pub worker_cores: Option<Vec<usize>>,
+
+    /// Additional algorithms and levels to try on each newly compressed block, for comparing
+    /// presets with real data. Empty by default.
+    ///
+    /// Each block is compressed with its configured algorithm and level, then with each
+    /// candidate; the smallest result is kept. The size and time of every attempt is recorded
+    /// in [`IncrementalStats::block_comparisons`]. Compressing takes longer by a factor of
+    /// about the number of candidates, so this is meant for diagnostics rather than regular
+    /// packing. Blocks which mix codecs (see [`Self::mixed_codec_blocks`]) are not compared.
+    ///
+    /// [`IncrementalStats::block_comparisons`]: crate::api::incremental_pack::IncrementalStats::block_comparisons
+    pub compression_candidates: Vec<CompressionCandidate>,
+
+    /// Injects [`FaultPoint::Compress`] faults before each block is compressed.
+    ///
+    /// [`FaultPoint::Compress`]: crate::utilities::fault_injection::FaultPoint::Compress
+    #[cfg(feature = "fault_injection")]
+    pub fault_injector: Option<Arc<FaultInjector>>,
 }
 
 impl PackingSettings {
@@ -86,14 +279,43 @@ impl PackingSettings {
         PackingSettings {
             block_size: 1_048_575,
             chunk_size: 1_048_576,
+            auto_block_sizing: false,
+            max_files_per_block: None,
+            solid_size_threshold: None,
+            inline_file_threshold: None,
+            path_filter_bits: None,
+            store_directory_index: false,
+            store_chunk_hashes: false,
             solid_compression_level: 12,
             chunked_compression_level: 12,
             solid_block_algorithm: CompressionPreference::ZStandard,
             chunked_file_algorithm: CompressionPreference::ZStandard,
+            zstd_advanced: ZstdAdvancedParams::default(),
             enable_chunked_deduplication: false,
             enable_solid_deduplication: true,
-            store_hashes: true,
-            enable_per_extension_dictionary: true,
+            dedup_memory_limit: None,
+            hash_seed: 0,
+            store_file_hashes: FileHashStorage::Always,
+            store_file_paths: true,
+            enable_per_extension_dictionary: false,
+            verify_blocks: false,
+            mixed_codec_blocks: false,
+            file_grouping: FileGrouping::Extension,
+            file_change_policy: FileChangePolicy::Fail,
+            stable_order: false,
+            manual_blocks: Vec::new(),
+            block_ordering: BlockOrdering::SizeAscending,
+            extension_filters: HashMap::new(),
+            path_collation: PathCollation::Ordinal,
+            string_pool_compression: StringPoolCompression::default(),
+            copy_fallback: CopyFallback::default(),
+            target_throughput_mbps: None,
+            adaptive_level_range: -5..=22,
+            worker_priority: WorkerPriority::Normal,
+            worker_cores: None,
+            compression_candidates: Vec::new(),
+            #[cfg(feature = "fault_injection")]
+            fault_injector: None,
         }
     }
 
@@ -108,6 +330,16 @@ impl PackingSettings {
             self.chunked_file_algorithm = CompressionPreference::ZStandard;
         }
 
+        // Mixed is a block format rather than an algorithm.
+        if self.solid_block_algorithm == CompressionPreference::Mixed {
+            self.solid_block_algorithm = CompressionPreference::ZStandard;
+            self.mixed_codec_blocks = true;
+        }
+
+        if self.chunked_file_algorithm == CompressionPreference::Mixed {
+            self.chunked_file_algorithm = CompressionPreference::ZStandard;
+        }
+
         // Note: BlockSize is minus one, see spec.
         self.block_size = self.block_size.clamp(MIN_BLOCK_SIZE, MAX_BLOCK_SIZE);
         // 1GiB because larger chunks cause problems with LZ4 and the likes
@@ -120,19 +352,77 @@ impl PackingSettings {
             self.chunk_size = self.block_size + 1;
         }
 
+        // Files larger than a block can't be SOLID packed, whatever the threshold.
+        self.solid_size_threshold = self
+            .solid_size_threshold
+            .map(|threshold| threshold.min(self.block_size));
+        self.inline_file_threshold = self
+            .inline_file_threshold
+            .map(|threshold| threshold.min(MAX_INLINE_FILE_SIZE));
+        self.path_filter_bits = self
+            .path_filter_bits
+            .map(|bits| bits.clamp(1, MAX_PATH_FILTER_BITS));
+
         self.solid_compression_level =
             self.clamp_compression(self.solid_compression_level, &self.solid_block_algorithm);
         self.chunked_compression_level =
             self.clamp_compression(self.chunked_compression_level, &self.chunked_file_algorithm);
+
+        if matches!(
+            self.string_pool_compression.algorithm,
+            CompressionPreference::NoPreference | CompressionPreference::Mixed
+        ) {
+            self.string_pool_compression.algorithm = CompressionPreference::ZStandard;
+        }
+
+        self.string_pool_compression.level = self.clamp_compression(
+            self.string_pool_compression.level,
+            &self.string_pool_compression.algorithm,
+        );
+
+        self.copy_fallback.sanitize();
+        self.zstd_advanced.sanitize();
+
+        // A limit of 0 would leave nowhere to put files.
+        self.max_files_per_block = self.max_files_per_block.map(|max| max.max(1));
+
+        let min_level = self.clamp_compression(
+            *self.adaptive_level_range.start(),
+            &CompressionPreference::ZStandard,
+        );
+        let max_level = self.clamp_compression(
+            *self.adaptive_level_range.end(),
+            &CompressionPreference::ZStandard,
+        );
+        self.adaptive_level_range = min_level..=max_level.max(min_level);
+
+        // Mixed is a block format rather than an algorithm, so can't be a candidate.
+        let mut candidates = core::mem::take(&mut self.compression_candidates);
+        candidates.retain(|x| x.algorithm != CompressionPreference::Mixed);
+        for candidate in &mut candidates {
+            if candidate.algorithm == CompressionPreference::NoPreference {
+                candidate.algorithm = CompressionPreference::ZStandard;
+            }
+            candidate.level = self.clamp_compression(candidate.level, &candidate.algorithm);
+        }
+        self.compression_candidates = candidates;
+    }
+
+    /// Returns the size above which files are chunked rather than SOLID packed.
+    pub fn effective_solid_size_threshold(&self) -> u32 {
+        self.solid_size_threshold
+            .map_or(self.block_size, |threshold| threshold.min(self.block_size))
     }
 
-    /// Retrieves the compression level for the specified algorithm.
-    fn clamp_compression(&self, level: i32, preference: &CompressionPreference) -> i32 {
+    /// Clamps a compression level to the range supported by an algorithm.
+    /// Levels for [`CompressionPreference::NoPreference`] and [`CompressionPreference::Mixed`]
+    /// are returned unchanged.
+    pub(crate) fn clamp_compression(&self, level: i32, preference: &CompressionPreference) -> i32 {
         match preference {
             CompressionPreference::Copy => 1,
             CompressionPreference::ZStandard => level.clamp(-5, 22),
-            CompressionPreference::Lz4 => level.clamp(1, 12),
-            CompressionPreference::NoPreference => unsafe { unreachable_unchecked() },
+            CompressionPreference::Lz4 => level.clamp(LZ4_MIN_LEVEL, LZ4_MAX_LEVEL),
+            CompressionPreference::NoPreference | CompressionPreference::Mixed => level,
         }
     }
 }
@@ -229,4 +519,100 @@ mod tests {
         assert!(!settings.enable_chunked_deduplication);
         assert!(settings.enable_solid_deduplication);
     }
+
+    #[test]
+    fn file_hashes_are_stored_by_default() {
+        let settings = PackingSettings::new();
+        assert_eq!(settings.store_file_hashes, FileHashStorage::Always);
+    }
+
+    #[test]
+    fn auto_block_sizing_is_disabled_by_default() {
+        let settings = PackingSettings::new();
+        assert!(!settings.auto_block_sizing);
+    }
+
+    #[test]
+    fn max_files_per_block_is_at_least_one() {
+        let mut settings = PackingSettings::new();
+        assert_eq!(settings.max_files_per_block, None);
+
+        settings.max_files_per_block = Some(0);
+        settings.sanitize();
+        assert_eq!(settings.max_files_per_block, Some(1));
+    }
+
+    #[rstest]
+    #[case::unset(None, 1_048_575)]
+    #[case::below_block_size(Some(65536), 65536)]
+    #[case::above_block_size(Some(u32::MAX), 1_048_575)]
+    fn solid_size_threshold_is_capped_at_block_size(
+        #[case] threshold: Option<u32>,
+        #[case] expected: u32,
+    ) {
+        let mut settings = PackingSettings::new();
+        settings.solid_size_threshold = threshold;
+        settings.sanitize();
+        assert_eq!(settings.effective_solid_size_threshold(), expected);
+    }
+
+    #[test]
+    fn file_paths_are_stored_by_default() {
+        let settings = PackingSettings::new();
+        assert!(settings.store_file_paths);
+    }
+
+    #[test]
+    fn extension_filters_default_to_empty() {
+        let settings = PackingSettings::new();
+        assert!(settings.extension_filters.is_empty());
+    }
+
+    #[test]
+    fn string_pool_compression_is_sanitized() {
+        let mut settings = PackingSettings::new();
+        settings.string_pool_compression.algorithm = CompressionPreference::NoPreference;
+        settings.string_pool_compression.level = 99;
+        settings.sanitize();
+        assert_eq!(
+            settings.string_pool_compression.algorithm,
+            CompressionPreference::ZStandard
+        );
+        assert_eq!(settings.string_pool_compression.level, 22);
+    }
+
+    #[test]
+    fn mixed_solid_algorithm_enables_mixed_codec_blocks() {
+        let mut settings = PackingSettings::new();
+        settings.solid_block_algorithm = CompressionPreference::Mixed;
+        settings.chunked_file_algorithm = CompressionPreference::Mixed;
+        settings.sanitize();
+        assert_eq!(
+            settings.solid_block_algorithm,
+            CompressionPreference::ZStandard
+        );
+        assert_eq!(
+            settings.chunked_file_algorithm,
+            CompressionPreference::ZStandard
+        );
+        assert!(settings.mixed_codec_blocks);
+    }
+
+    #[test]
+    fn copy_fallback_is_sanitized() {
+        let mut settings = PackingSettings::new();
+        settings.copy_fallback.min_savings_percent = 200;
+        settings.copy_fallback.zstd_min_savings_percent = Some(255);
+        settings.sanitize();
+        assert_eq!(settings.copy_fallback.min_savings_percent, 100);
+        assert_eq!(settings.copy_fallback.zstd_min_savings_percent, Some(100));
+    }
+
+    #[test]
+    fn adaptive_level_range_is_sanitized() {
+        let mut settings = PackingSettings::new();
+        settings.adaptive_level_range = 30..=-10;
+        settings.sanitize();
+        assert_eq!(settings.adaptive_level_range, 22..=22);
+    }
 }
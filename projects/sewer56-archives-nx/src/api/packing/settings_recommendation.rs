@@ -0,0 +1,302 @@
+use crate::api::{enums::*, packing::packing_settings::PackingSettings, traits::*};
+use crate::prelude::*;
+use crate::utilities::compression::{self, NxCompressionError, NxDecompressionError};
+use crate::utilities::io::file_finder::find_files;
+use core::time::Duration;
+use std::path::Path;
+use std::time::Instant;
+use thiserror_no_std::Error;
+
+/// Default number of bytes of the input compressed with each candidate.
+pub const DEFAULT_SAMPLE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The sample is compressed in blocks of this size, similar to SOLID blocks in an archive.
+const SAMPLE_BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Limits which settings [`recommend_settings`] may recommend.
+/// Unset limits are not checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecommendationConstraints {
+    /// Maximum (estimated) time to compress the whole input on a single thread.
+    pub max_pack_time: Option<Duration>,
+
+    /// Minimum decompression speed, in megabytes (10^6 bytes) per second.
+    pub min_decode_mbps: Option<u32>,
+
+    /// Maximum (estimated) size of the compressed input, in bytes.
+    pub max_size: Option<u64>,
+
+    /// Number of bytes of the input to compress with each candidate.
+    /// Larger samples give better estimates, but take longer to test.
+    pub sample_size: u64,
+}
+
+impl Default for RecommendationConstraints {
+    fn default() -> Self {
+        Self {
+            max_pack_time: None,
+            min_decode_mbps: None,
+            max_size: None,
+            sample_size: DEFAULT_SAMPLE_SIZE,
+        }
+    }
+}
+
+/// The settings picked by [`recommend_settings`], along with how they performed on the sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecommendedSettings {
+    /// The compression algorithm to use.
+    pub algorithm: CompressionPreference,
+
+    /// The compression level to use.
+    pub level: i32,
+
+    /// Estimated size of the whole input after compression, in bytes.
+    pub estimated_size: u64,
+
+    /// Estimated time to compress the whole input on a single thread.
+    pub estimated_pack_time: Duration,
+
+    /// Measured decompression speed, in megabytes (10^6 bytes) per second.
+    pub decode_mbps: u32,
+}
+
+impl RecommendedSettings {
+    /// Applies the recommended algorithm and level to both SOLID blocks and chunked files.
+    ///
+    /// # Arguments
+    ///
+    /// * `settings` - The settings to update.
+    pub fn apply(&self, settings: &mut PackingSettings) {
+        settings.solid_block_algorithm = self.algorithm;
+        settings.chunked_file_algorithm = self.algorithm;
+        settings.solid_compression_level = self.level;
+        settings.chunked_compression_level = self.level;
+    }
+}
+
+/// Errors that can occur when recommending settings.
+#[derive(Debug, Error)]
+pub enum RecommendError {
+    /// Failed to read the sample files.
+    #[error(transparent)]
+    FileProvider(#[from] FileProviderError),
+
+    /// Failed to compress the sample with one of the candidates.
+    #[error("Failed to compress sample: {0:?}")]
+    Compression(#[from] NxCompressionError),
+
+    /// Failed to decompress the sample with one of the candidates.
+    #[error("Failed to decompress sample: {0:?}")]
+    Decompression(#[from] NxDecompressionError),
+
+    /// The sample directory contains no data.
+    #[error("The sample directory contains no data")]
+    EmptySample,
+
+    /// None of the candidate settings meet the constraints.
+    #[error("No candidate settings meet the constraints")]
+    NoSuitableSettings,
+}
+
+/// Algorithms and levels tested by [`recommend_settings`], fastest first.
+fn candidates() -> Vec<(CompressionPreference, i32)> {
+    let mut result = Vec::new();
+    result.push((CompressionPreference::Copy, 1));
+    #[cfg(feature = "lz4")]
+    {
+        result.push((CompressionPreference::Lz4, 1));
+        result.push((CompressionPreference::Lz4, 12));
+    }
+    for level in [1, 3, 9, 12, 16, 19] {
+        result.push((CompressionPreference::ZStandard, level));
+    }
+    result
+}
+
+/// Compresses a sample of a directory with several candidate algorithms and levels,
+/// and returns the one giving the smallest output which meets the given constraints.
+///
+/// # Arguments
+///
+/// * `sample_dir` - Directory containing the files to be packed, or a representative subset.
+/// * `constraints` - Limits on pack time, decode speed and size.
+///
+/// # Remarks
+///
+/// Times are measured on the current machine, and extrapolated from the sample
+/// to the whole directory; treat them as rough estimates.
+pub fn recommend_settings<P: AsRef<Path>>(
+    sample_dir: P,
+    constraints: &RecommendationConstraints,
+) -> Result<RecommendedSettings, RecommendError> {
+    let (sample, total_size) = read_sample(sample_dir.as_ref(), constraints.sample_size)?;
+    if sample.is_empty() {
+        return Err(RecommendError::EmptySample);
+    }
+
+    let scale = total_size as f64 / sample.len() as f64;
+    let mut best: Option<RecommendedSettings> = None;
+    for (algorithm, level) in candidates() {
+        let (compressed_size, pack_time, decode_time) = measure(&sample, algorithm, level)?;
+        let candidate = RecommendedSettings {
+            algorithm,
+            level,
+            estimated_size: (compressed_size as f64 * scale) as u64,
+            estimated_pack_time: pack_time.mul_f64(scale),
+            decode_mbps: (sample.len() as f64 / decode_time.as_secs_f64().max(1e-9) / 1e6) as u32,
+        };
+
+        let meets_constraints = constraints
+            .max_pack_time
+            .is_none_or(|max| candidate.estimated_pack_time <= max)
+            && constraints
+                .min_decode_mbps
+                .is_none_or(|min| candidate.decode_mbps >= min)
+            && constraints
+                .max_size
+                .is_none_or(|max| candidate.estimated_size <= max);
+
+        // Candidates are ordered fastest first, so only replace on strictly smaller output.
+        if meets_constraints
+            && best.is_none_or(|best| candidate.estimated_size < best.estimated_size)
+        {
+            best = Some(candidate);
+        }
+    }
+
+    best.ok_or(RecommendError::NoSuitableSettings)
+}
+
+/// Reads up to `sample_size` bytes from the start of the files in a directory.
+///
+/// # Returns
+///
+/// The sample, and the total size of all files in the directory.
+fn read_sample(directory: &Path, sample_size: u64) -> Result<(Vec<u8>, u64), RecommendError> {
+    let mut sample = Vec::new();
+    let mut total_size = 0u64;
+    let mut error: Option<FileProviderError> = None;
+
+    find_files(directory, |file| {
+        total_size += file.file_size();
+        let length = file
+            .file_size()
+            .min(sample_size.saturating_sub(sample.len() as u64));
+        if length == 0 || error.is_some() {
+            return;
+        }
+
+        match file.input_data_provider().get_file_data(0, length) {
+            Ok(data) => sample.extend_from_slice(data.data()),
+            Err(e) => error = Some(e),
+        }
+    })?;
+
+    match error {
+        Some(e) => Err(e.into()),
+        None => Ok((sample, total_size)),
+    }
+}
+
+/// Compresses and decompresses the sample, in blocks.
+///
+/// # Returns
+///
+/// The compressed size, time taken to compress and time taken to decompress.
+fn measure(
+    sample: &[u8],
+    algorithm: CompressionPreference,
+    level: i32,
+) -> Result<(usize, Duration, Duration), RecommendError> {
+    let mut compressed = vec![0u8; compression::max_alloc_for_compress_size(SAMPLE_BLOCK_SIZE)];
+    let mut decompressed = vec![0u8; SAMPLE_BLOCK_SIZE];
+    let mut compressed_size = 0;
+    let mut pack_time = Duration::ZERO;
+    let mut decode_time = Duration::ZERO;
+
+    for block in sample.chunks(SAMPLE_BLOCK_SIZE) {
+        let mut used_copy = false;
+        let start = Instant::now();
+        let size = compression::compress(algorithm, level, block, &mut compressed, &mut used_copy)?;
+        pack_time += start.elapsed();
+        compressed_size += size;
+
+        let method = if used_copy {
+            CompressionPreference::Copy
+        } else {
+            algorithm
+        };
+        let start = Instant::now();
+        compression::decompress(
+            method,
+            &compressed[..size],
+            &mut decompressed[..block.len()],
+        )?;
+        decode_time += start.elapsed();
+    }
+
+    Ok((compressed_size, pack_time, decode_time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir, write};
+    use tempfile::tempdir;
+
+    fn create_sample_dir() -> tempfile::TempDir {
+        let dir = tempdir().unwrap();
+        let text: Vec<u8> = b"Nx packs files into SOLID blocks. "
+            .iter()
+            .copied()
+            .cycle()
+            .take(100_000)
+            .collect();
+        write(dir.path().join("readme.txt"), text.as_slice()).unwrap();
+        create_dir(dir.path().join("data")).unwrap();
+        write(dir.path().join("data/config.ini"), &text[..5000]).unwrap();
+        dir
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // involves external I/O
+    fn recommends_compression_for_compressible_data() {
+        let dir = create_sample_dir();
+        let recommended =
+            recommend_settings(dir.path(), &RecommendationConstraints::default()).unwrap();
+
+        assert_ne!(recommended.algorithm, CompressionPreference::Copy);
+        assert!(recommended.estimated_size < 105_000);
+
+        let mut settings = PackingSettings::new();
+        recommended.apply(&mut settings);
+        assert_eq!(settings.solid_block_algorithm, recommended.algorithm);
+        assert_eq!(settings.chunked_compression_level, recommended.level);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn impossible_constraints_return_error() {
+        let dir = create_sample_dir();
+        let constraints = RecommendationConstraints {
+            max_size: Some(0),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            recommend_settings(dir.path(), &constraints),
+            Err(RecommendError::NoSuitableSettings)
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn empty_directory_returns_error() {
+        let dir = tempdir().unwrap();
+        assert!(matches!(
+            recommend_settings(dir.path(), &RecommendationConstraints::default()),
+            Err(RecommendError::EmptySample)
+        ));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::prelude::*;
+use alloc::string::String;
+use hashbrown::HashMap;
+use thiserror_no_std::Error;
+
+/// Maximum number of aliases followed when resolving a path, in case aliases form a cycle.
+const MAX_ALIAS_HOPS: usize = 16;
+
+/// Errors that can occur when parsing a stored alias table.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PathAliasError {
+    /// The table is not valid UTF-8.
+    #[error("The path alias table is not valid UTF-8")]
+    InvalidUtf8,
+
+    /// The table ends with an old path that has no new path.
+    #[error("The path alias table is truncated")]
+    Truncated,
+}
+
+/// Maps old file paths to new ones, for archives whose files were moved between versions.
+///
+/// The table is stored inside the archive as a regular file at [`Self::FILE_PATH`]
+/// (see [`NxPackerBuilder::add_path_aliases`]), and can be loaded by the reader with
+/// [`NxArchiveReader::load_path_aliases`], after which old paths keep working.
+///
+/// [`NxPackerBuilder::add_path_aliases`]: crate::api::packer_builder::NxPackerBuilder::add_path_aliases
+/// [`NxArchiveReader::load_path_aliases`]: crate::api::archive_reader::NxArchiveReader::load_path_aliases
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PathAliases {
+    aliases: HashMap<String, String>,
+}
+
+impl PathAliases {
+    /// Path of the file the table is stored in, within the archive.
+    pub const FILE_PATH: &'static str = ".nx/path_aliases";
+
+    /// Creates an empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an alias, making `old_path` refer to the file at `new_path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_path` - Path the file used to have.
+    /// * `new_path` - Path the file has now.
+    pub fn insert(&mut self, old_path: &str, new_path: &str) {
+        self.aliases
+            .insert(String::from(old_path), String::from(new_path));
+    }
+
+    /// Returns the number of aliases.
+    pub fn len(&self) -> usize {
+        self.aliases.len()
+    }
+
+    /// Returns true if there are no aliases.
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    /// Follows the aliases of a path, in case the file was moved more than once.
+    ///
+    /// # Returns
+    ///
+    /// The current path of the file, or [`None`] if the path has no alias.
+    pub fn resolve(&self, path: &str) -> Option<&str> {
+        let mut current = self.aliases.get(path)?.as_str();
+        for _ in 1..MAX_ALIAS_HOPS {
+            match self.aliases.get(current) {
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+
+        Some(current)
+    }
+
+    /// Serializes the table, as stored in the archive.
+    ///
+    /// The format is a list of null terminated UTF-8 strings, alternating between
+    /// old and new path.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        for (old_path, new_path) in &self.aliases {
+            result.extend_from_slice(old_path.as_bytes());
+            result.push(0);
+            result.extend_from_slice(new_path.as_bytes());
+            result.push(0);
+        }
+        result
+    }
+
+    /// Parses a table serialized with [`Self::to_bytes`].
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The serialized table.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, PathAliasError> {
+        let text = core::str::from_utf8(data).map_err(|_| PathAliasError::InvalidUtf8)?;
+        let mut result = Self::new();
+        let mut parts = text.split_terminator('\0');
+        while let Some(old_path) = parts.next() {
+            let new_path = parts.next().ok_or(PathAliasError::Truncated)?;
+            result.insert(old_path, new_path);
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_round_trip() {
+        let mut aliases = PathAliases::new();
+        aliases.insert("textures/old.dds", "textures/new.dds");
+        aliases.insert("config.ini", "settings/config.ini");
+
+        let parsed = PathAliases::from_bytes(&aliases.to_bytes()).unwrap();
+        assert_eq!(parsed, aliases);
+    }
+
+    #[test]
+    fn resolves_chains_and_cycles() {
+        let mut aliases = PathAliases::new();
+        aliases.insert("v1.txt", "v2.txt");
+        aliases.insert("v2.txt", "v3.txt");
+        aliases.insert("loop_a", "loop_b");
+        aliases.insert("loop_b", "loop_a");
+
+        assert_eq!(aliases.resolve("v1.txt"), Some("v3.txt"));
+        assert_eq!(aliases.resolve("v3.txt"), None);
+        assert!(aliases.resolve("loop_a").is_some());
+    }
+
+    #[test]
+    fn truncated_table_returns_error() {
+        assert_eq!(
+            PathAliases::from_bytes(b"old\0"),
+            Err(PathAliasError::Truncated)
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use crate::prelude::*;
+use alloc::sync::Arc;
+use hashbrown::HashMap;
+use once_cell::sync::OnceCell;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Identifies a path stored in a [`PathInterner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PathId(pub u32);
+
+/// Stores each unique file path once, handing out a [`PathId`] for it.
+///
+/// When many archives with overlapping file trees are opened at once (e.g. layered mods),
+/// each archive's string pool holds its own copy of every path. Interning the paths of all
+/// archives into one [`PathInterner`] with [`NxArchiveReader::intern_paths`] lets the archives
+/// refer to shared [`PathId`]s instead, dropping their own copy of the pool.
+///
+/// Paths are never removed from the interner.
+///
+/// [`NxArchiveReader::intern_paths`]: crate::api::archive_reader::NxArchiveReader::intern_paths
+#[derive(Default)]
+pub struct PathInterner {
+    inner: RwLock<PathInternerInner>,
+}
+
+#[derive(Default)]
+struct PathInternerInner {
+    /// Maps each path to its ID.
+    ids: HashMap<Arc<str>, PathId>,
+
+    /// Paths, indexed by ID.
+    paths: Vec<Arc<str>>,
+}
+
+static GLOBAL_INTERNER: OnceCell<PathInterner> = OnceCell::new();
+
+impl PathInterner {
+    /// Creates a new, empty interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the process-wide interner.
+    pub fn global() -> &'static PathInterner {
+        GLOBAL_INTERNER.get_or_init(PathInterner::new)
+    }
+
+    /// Interns a path, returning its ID.
+    /// Interning the same path multiple times returns the same ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to intern.
+    pub fn intern(&self, path: &str) -> PathId {
+        self.intern_shared(path).0
+    }
+
+    /// Interns a path, returning its ID along with the interner's copy of it.
+    /// Holding on to the copy does not duplicate the path in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to intern.
+    pub fn intern_shared(&self, path: &str) -> (PathId, Arc<str>) {
+        if let Some((shared, id)) = self.read().ids.get_key_value(path) {
+            return (*id, shared.clone());
+        }
+
+        let mut inner = self.write();
+        // Another thread may have interned the path while we were waiting for the lock.
+        if let Some((shared, id)) = inner.ids.get_key_value(path) {
+            return (*id, shared.clone());
+        }
+
+        let id = PathId(inner.paths.len() as u32);
+        let path: Arc<str> = Arc::from(path);
+        inner.paths.push(path.clone());
+        inner.ids.insert(path.clone(), id);
+        (id, path)
+    }
+
+    /// Returns the ID of a path, if it has been interned.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to look up.
+    pub fn find(&self, path: &str) -> Option<PathId> {
+        self.read().ids.get(path).copied()
+    }
+
+    /// Returns the path with a given ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - ID returned from [`Self::intern`].
+    pub fn resolve(&self, id: PathId) -> Option<Arc<str>> {
+        self.read().paths.get(id.0 as usize).cloned()
+    }
+
+    /// Returns the number of unique paths in the interner.
+    pub fn len(&self) -> usize {
+        self.read().paths.len()
+    }
+
+    /// Returns true if no paths have been interned.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // A panic while holding the lock can't leave the maps out of sync in a harmful way;
+    // at worst a path is in `paths` but not `ids`, so ignore poisoning.
+    fn read(&self) -> RwLockReadGuard<'_, PathInternerInner> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, PathInternerInner> {
+        self.inner.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning_same_path_returns_same_id() {
+        let interner = PathInterner::new();
+        let a = interner.intern("data/a.txt");
+        let b = interner.intern("data/b.txt");
+
+        assert_ne!(a, b);
+        assert_eq!(interner.intern("data/a.txt"), a);
+        assert_eq!(interner.len(), 2);
+    }
+
+    #[test]
+    fn can_resolve_and_find() {
+        let interner = PathInterner::new();
+        let id = interner.intern("data/a.txt");
+
+        assert_eq!(interner.resolve(id).as_deref(), Some("data/a.txt"));
+        assert_eq!(interner.find("data/a.txt"), Some(id));
+        assert_eq!(interner.find("missing.txt"), None);
+        assert_eq!(interner.resolve(PathId(99)), None);
+    }
+}
@@ -0,0 +1,375 @@
+use crate::api::archive_reader::{ArchiveReadError, NxArchiveReader};
+use crate::prelude::*;
+use alloc::format;
+use alloc::string::{String, ToString};
+use hashbrown::{HashMap, HashSet};
+
+/// Characters which can't appear in file names on Windows.
+///
+/// The backslash is included, as Windows treats it as a directory separator;
+/// Nx archives only use `/`.
+const RESERVED_CHARACTERS: &[char] = &['<', '>', ':', '"', '|', '?', '*', '\\'];
+
+/// File names reserved by Windows, regardless of their extension.
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Longest path component allowed by common file systems, in bytes of UTF-8.
+///
+/// ext4 and APFS limit components to 255 bytes, NTFS to 255 UTF-16 code units;
+/// the latter never exceeds the number of UTF-8 bytes.
+pub const MAX_COMPONENT_BYTES: usize = 255;
+
+/// A file path which can't be extracted as-is on some operating systems.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortabilityIssue {
+    /// Paths which differ only by case.
+    ///
+    /// On case-insensitive file systems (the default on Windows and macOS),
+    /// extracting these overwrites all but one of the files.
+    CaseCollision {
+        /// The colliding paths, in archive order.
+        paths: Vec<String>,
+    },
+
+    /// A path contains a character Windows does not allow in file names,
+    /// i.e. one of `<>:"|?*\` or a control character.
+    ReservedCharacter {
+        /// Path of the file.
+        path: String,
+
+        /// The first reserved character in the path.
+        character: char,
+    },
+
+    /// A path component is a device name reserved by Windows (e.g. `CON`, `NUL`, `COM1`),
+    /// with or without an extension.
+    ReservedName {
+        /// Path of the file.
+        path: String,
+
+        /// The reserved component.
+        component: String,
+    },
+
+    /// A path component ends with a dot or space, which Windows strips when creating files.
+    TrailingDotOrSpace {
+        /// Path of the file.
+        path: String,
+
+        /// The offending component.
+        component: String,
+    },
+
+    /// A path component is longer than [`MAX_COMPONENT_BYTES`].
+    ComponentTooLong {
+        /// Path of the file.
+        path: String,
+
+        /// Length of the longest component, in bytes.
+        length: usize,
+    },
+}
+
+/// A rename which makes a path portable, part of a [`PortabilityReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortableRename {
+    /// Path of the file within the archive.
+    pub from: String,
+
+    /// Suggested replacement path.
+    pub to: String,
+}
+
+/// The result of [`check_portability`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PortabilityReport {
+    /// Problems found. Collisions come first, then the problems of each path, in archive order.
+    pub issues: Vec<PortabilityIssue>,
+
+    /// Renames which fix every issue, in archive order.
+    ///
+    /// Renamed paths are unique regardless of case, and don't collide with any path
+    /// which is kept. Apply these when repacking, before shipping the archive cross-platform.
+    pub renames: Vec<PortableRename>,
+}
+
+impl PortabilityReport {
+    /// Returns true if every path can be extracted on all major operating systems.
+    pub fn is_portable(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Checks whether all files in an archive can be extracted on Windows, macOS and Linux.
+///
+/// # Arguments
+///
+/// * `archive` - The archive to check.
+///
+/// # Returns
+///
+/// The problems found, and the renames which fix them.
+pub fn check_portability(archive: &NxArchiveReader) -> Result<PortabilityReport, ArchiveReadError> {
+    Ok(check_paths_portability(
+        archive.iter_entries()?.map(|(path, _)| path),
+    ))
+}
+
+/// Checks whether a set of relative paths (separated by `/`) can be extracted
+/// on Windows, macOS and Linux, e.g. before packing them.
+///
+/// # Arguments
+///
+/// * `paths` - The paths to check.
+///
+/// # Returns
+///
+/// The problems found, and the renames which fix them.
+pub fn check_paths_portability<'a>(paths: impl IntoIterator<Item = &'a str>) -> PortabilityReport {
+    let paths: Vec<&str> = paths.into_iter().collect();
+    let mut report = PortabilityReport::default();
+
+    // Collisions
+    let mut groups: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut group_order = Vec::new();
+    for (index, path) in paths.iter().enumerate() {
+        let folded = path.to_lowercase();
+        let group = groups.entry(folded.clone()).or_default();
+        if group.is_empty() {
+            group_order.push(folded);
+        }
+        group.push(index);
+    }
+
+    for folded in &group_order {
+        let group = &groups[folded];
+        if group.len() > 1 {
+            report.issues.push(PortabilityIssue::CaseCollision {
+                paths: group
+                    .iter()
+                    .map(|index| paths[*index].to_string())
+                    .collect(),
+            });
+        }
+    }
+
+    // Per path issues
+    for path in &paths {
+        check_path(path, &mut report.issues);
+    }
+
+    // Remediation: sanitize each path, then give colliding paths unique names.
+    let sanitized: Vec<String> = paths.iter().map(|path| sanitize_path(path)).collect();
+    let mut taken: HashSet<String> = sanitized.iter().map(|path| path.to_lowercase()).collect();
+    let mut kept: HashSet<String> = HashSet::new();
+    for (path, sanitized) in paths.iter().zip(sanitized) {
+        let mut target = sanitized;
+        if !kept.insert(target.to_lowercase()) {
+            let mut suffix = 2;
+            let unique = loop {
+                let candidate = with_suffix(&target, suffix);
+                if !taken.contains(&candidate.to_lowercase()) {
+                    break candidate;
+                }
+                suffix += 1;
+            };
+            taken.insert(unique.to_lowercase());
+            kept.insert(unique.to_lowercase());
+            target = unique;
+        }
+
+        if target != *path {
+            report.renames.push(PortableRename {
+                from: path.to_string(),
+                to: target,
+            });
+        }
+    }
+
+    report
+}
+
+/// Adds the issues found within a single path.
+fn check_path(path: &str, issues: &mut Vec<PortabilityIssue>) {
+    if let Some(character) = path.chars().find(|c| is_reserved_character(*c)) {
+        issues.push(PortabilityIssue::ReservedCharacter {
+            path: path.to_string(),
+            character,
+        });
+    }
+
+    let mut longest = 0;
+    for component in path.split('/') {
+        longest = longest.max(component.len());
+        if is_reserved_name(component) {
+            issues.push(PortabilityIssue::ReservedName {
+                path: path.to_string(),
+                component: component.to_string(),
+            });
+        }
+
+        if component.ends_with(['.', ' ']) && component != "." && component != ".." {
+            issues.push(PortabilityIssue::TrailingDotOrSpace {
+                path: path.to_string(),
+                component: component.to_string(),
+            });
+        }
+    }
+
+    if longest > MAX_COMPONENT_BYTES {
+        issues.push(PortabilityIssue::ComponentTooLong {
+            path: path.to_string(),
+            length: longest,
+        });
+    }
+}
+
+fn is_reserved_character(character: char) -> bool {
+    RESERVED_CHARACTERS.contains(&character) || character.is_control()
+}
+
+/// Returns true if the component is a reserved device name, ignoring case and extension.
+fn is_reserved_name(component: &str) -> bool {
+    let stem = component.split('.').next().unwrap_or(component).trim_end();
+    RESERVED_NAMES
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(stem))
+}
+
+/// Rewrites every component of a path so it has none of the per-path issues.
+fn sanitize_path(path: &str) -> String {
+    path.split('/')
+        .map(sanitize_component)
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn sanitize_component(component: &str) -> String {
+    let mut result: String = component
+        .chars()
+        .map(|c| if is_reserved_character(c) { '_' } else { c })
+        .collect();
+
+    if result != "." && result != ".." {
+        let trimmed = result.trim_end_matches(['.', ' ']).len();
+        result.truncate(trimmed);
+    }
+
+    if result.is_empty() && !component.is_empty() {
+        result.push('_');
+    }
+
+    if is_reserved_name(&result) {
+        let (stem, extension) = split_extension(&result);
+        result = format!("{}_{extension}", stem.trim_end());
+    }
+
+    truncate_component(&result, MAX_COMPONENT_BYTES)
+}
+
+/// Shortens a component to at most `max_bytes`, keeping its extension where possible.
+fn truncate_component(component: &str, max_bytes: usize) -> String {
+    if component.len() <= max_bytes {
+        return component.to_string();
+    }
+
+    let (stem, extension) = split_extension(component);
+    let (stem, extension) = match extension.len() < max_bytes {
+        true => (stem, extension),
+        false => (component, ""),
+    };
+
+    let mut end = max_bytes - extension.len();
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{extension}", &stem[..end])
+}
+
+/// Splits the last component of a path into its stem and extension (including the dot).
+fn split_extension(component: &str) -> (&str, &str) {
+    match component.find('.') {
+        Some(0) | None => (component, ""),
+        Some(dot) => component.split_at(dot),
+    }
+}
+
+/// Inserts `~{suffix}` before the extension of the last component of a path.
+fn with_suffix(path: &str, suffix: u32) -> String {
+    let (directory, name) = match path.rfind('/') {
+        Some(slash) => path.split_at(slash + 1),
+        None => ("", path),
+    };
+
+    let (stem, extension) = split_extension(name);
+    let marker = format!("~{suffix}");
+    let stem = truncate_component(
+        stem,
+        MAX_COMPONENT_BYTES.saturating_sub(marker.len() + extension.len()),
+    );
+    format!("{directory}{stem}{marker}{extension}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_issues_and_plans_renames() {
+        let long_name = format!("{}.txt", "a".repeat(300));
+        let report = check_paths_portability([
+            "Data/file.txt",
+            "data/FILE.txt",
+            "data/file~2.txt",
+            "con.txt",
+            "what?.txt",
+            "folder./ok.txt",
+            long_name.as_str(),
+            "fine.txt",
+        ]);
+
+        assert!(!report.is_portable());
+        assert_eq!(
+            report.issues[0],
+            PortabilityIssue::CaseCollision {
+                paths: vec!["Data/file.txt".to_string(), "data/FILE.txt".to_string()]
+            }
+        );
+        assert!(report.issues.contains(&PortabilityIssue::ReservedName {
+            path: "con.txt".to_string(),
+            component: "con.txt".to_string()
+        }));
+        assert!(report
+            .issues
+            .contains(&PortabilityIssue::ReservedCharacter {
+                path: "what?.txt".to_string(),
+                character: '?'
+            }));
+        assert!(report
+            .issues
+            .contains(&PortabilityIssue::TrailingDotOrSpace {
+                path: "folder./ok.txt".to_string(),
+                component: "folder.".to_string()
+            }));
+        assert!(report.issues.contains(&PortabilityIssue::ComponentTooLong {
+            path: long_name.clone(),
+            length: 304
+        }));
+
+        let renames: Vec<(&str, &str)> = report
+            .renames
+            .iter()
+            .map(|rename| (rename.from.as_str(), rename.to.as_str()))
+            .collect();
+        assert_eq!(renames[0], ("data/FILE.txt", "data/FILE~3.txt"));
+        assert_eq!(renames[1], ("con.txt", "con_.txt"));
+        assert_eq!(renames[2], ("what?.txt", "what_.txt"));
+        assert_eq!(renames[3], ("folder./ok.txt", "folder/ok.txt"));
+        assert_eq!(renames[4].1.len(), MAX_COMPONENT_BYTES);
+        assert!(renames[4].1.ends_with(".txt"));
+        assert_eq!(renames.len(), 5);
+    }
+}
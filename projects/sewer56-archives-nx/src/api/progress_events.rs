@@ -0,0 +1,192 @@
+use crate::api::{
+    extraction_estimate::ExtractionEstimator, packing::pack_diagnostics::PackWarning,
+    traits::Progress,
+};
+use alloc::string::String;
+use core::time::Duration;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// A progress update or message, forwarded by [`ChannelProgress`] or [`EventCallback`].
+///
+/// All data is owned, so events can be sent to another thread (e.g. a UI thread).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressEvent {
+    /// Fraction of the operation completed, between 0.0 and 1.0.
+    /// Sent by [`Progress::report_progress`].
+    Progress(f64),
+
+    /// Progress of an extraction, taken from an [`ExtractionEstimator`].
+    Extraction {
+        /// Fraction of the data extracted so far, between 0.0 and 1.0.
+        progress: f64,
+        /// Decompressed size of the data left to extract.
+        remaining_bytes: u64,
+        /// Estimated time left, if anything has been measured yet.
+        estimated_remaining: Option<Duration>,
+    },
+
+    /// A warning raised while packing.
+    Warning(PackWarning),
+
+    /// A message to show to, or log for, the user.
+    Log(String),
+}
+
+impl ProgressEvent {
+    /// Creates a [`ProgressEvent::Extraction`] event from the current state of an estimator.
+    pub fn extraction(estimator: &ExtractionEstimator) -> Self {
+        Self::Extraction {
+            progress: estimator.progress(),
+            remaining_bytes: estimator.remaining_bytes(),
+            estimated_remaining: estimator.estimated_remaining(),
+        }
+    }
+}
+
+/// Forwards progress over a [`std::sync::mpsc`] channel, so it can be consumed on another
+/// thread (e.g. a UI thread) while the operation runs on a worker thread.
+///
+/// Events sent after the receiver is dropped are discarded.
+#[derive(Debug, Clone)]
+pub struct ChannelProgress {
+    sender: Sender<ProgressEvent>,
+}
+
+impl ChannelProgress {
+    /// Creates a new channel.
+    ///
+    /// # Returns
+    ///
+    /// The sending half, to pass to the operation, and the receiving half.
+    pub fn new() -> (Self, Receiver<ProgressEvent>) {
+        let (sender, receiver) = mpsc::channel();
+        (Self { sender }, receiver)
+    }
+
+    /// Forwards events into an existing channel, e.g. one shared by multiple operations.
+    ///
+    /// # Arguments
+    ///
+    /// * `sender` - The sending half of the channel.
+    pub fn from_sender(sender: Sender<ProgressEvent>) -> Self {
+        Self { sender }
+    }
+
+    /// Sends an event.
+    pub fn send(&self, event: ProgressEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Sends the warnings raised while packing, e.g. from [`PackDiagnostics::warnings`].
+    ///
+    /// [`PackDiagnostics::warnings`]: crate::api::packing::pack_diagnostics::PackDiagnostics::warnings
+    pub fn send_warnings(&self, warnings: &[PackWarning]) {
+        for warning in warnings {
+            self.send(ProgressEvent::Warning(warning.clone()));
+        }
+    }
+
+    /// Returns a callback for extraction functions such as
+    /// [`extract_to_dir_with_progress`], which sends each update as a
+    /// [`ProgressEvent::Extraction`].
+    ///
+    /// [`extract_to_dir_with_progress`]: crate::api::directory_sync::extract_to_dir_with_progress
+    pub fn extraction_callback(&self) -> impl FnMut(&ExtractionEstimator) + '_ {
+        move |estimator| self.send(ProgressEvent::extraction(estimator))
+    }
+}
+
+impl Progress for ChannelProgress {
+    fn report_progress(&self, progress: f64) {
+        self.send(ProgressEvent::Progress(progress));
+    }
+}
+
+/// Forwards progress to a callback as [`ProgressEvent`]s.
+///
+/// The callback must be [`Send`] and [`Sync`], so it can be called from worker threads;
+/// use [`ChannelProgress`] to consume the events on a single thread instead.
+pub struct EventCallback<F>
+where
+    F: Fn(ProgressEvent) + Send + Sync,
+{
+    callback: F,
+}
+
+impl<F> EventCallback<F>
+where
+    F: Fn(ProgressEvent) + Send + Sync,
+{
+    /// Creates a new [`EventCallback`].
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - Called with each event.
+    pub fn new(callback: F) -> Self {
+        Self { callback }
+    }
+
+    /// Sends an event.
+    pub fn send(&self, event: ProgressEvent) {
+        (self.callback)(event);
+    }
+
+    /// Returns a callback for extraction functions, see [`ChannelProgress::extraction_callback`].
+    pub fn extraction_callback(&self) -> impl FnMut(&ExtractionEstimator) + '_ {
+        move |estimator| self.send(ProgressEvent::extraction(estimator))
+    }
+}
+
+impl<F> Progress for EventCallback<F>
+where
+    F: Fn(ProgressEvent) + Send + Sync,
+{
+    fn report_progress(&self, progress: f64) {
+        self.send(ProgressEvent::Progress(progress));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::enums::CompressionPreference;
+    use std::sync::Mutex;
+
+    #[test]
+    fn forwards_events_to_another_thread() {
+        let (progress, receiver) = ChannelProgress::new();
+        let worker = std::thread::spawn(move || {
+            progress.report_progress(0.5);
+            progress.send(ProgressEvent::Log(String::from("Packing")));
+
+            let mut estimator = ExtractionEstimator::new();
+            estimator.add(CompressionPreference::Copy, 100);
+            estimator.record(CompressionPreference::Copy, 25, Duration::from_millis(1));
+            (progress.extraction_callback())(&estimator);
+        });
+        worker.join().unwrap();
+
+        let events: Vec<ProgressEvent> = receiver.iter().collect();
+        assert_eq!(events[0], ProgressEvent::Progress(0.5));
+        assert_eq!(events[1], ProgressEvent::Log(String::from("Packing")));
+        assert!(matches!(
+            events[2],
+            ProgressEvent::Extraction {
+                remaining_bytes: 75,
+                estimated_remaining: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn forwards_events_to_callback() {
+        let received = Mutex::new(Vec::new());
+        let progress = EventCallback::new(|event| received.lock().unwrap().push(event));
+        progress.report_progress(1.0);
+        assert_eq!(
+            received.lock().unwrap().as_slice(),
+            &[ProgressEvent::Progress(1.0)]
+        );
+    }
+}
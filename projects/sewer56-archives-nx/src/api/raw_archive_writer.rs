@@ -0,0 +1,923 @@
+use crate::api::{
+    archive_reader::{ArchiveReadError, NxArchiveReader, RawBlock},
+    enums::{CompressionPreference, FileHashStorage, PathCollation},
+    packing::pack_diagnostics::{check_table_of_contents, PackDiagnostics},
+    traits::{HasDictIndex, HasRelativePath, NO_DICTIONARY_INDEX},
+};
+use crate::headers::{
+    managed::{v2::*, *},
+    parser::{
+        serialize_dictionary_data, serialize_header_extensions, BlockFilters, ChunkHashes,
+        ChunkSizes, DictionarySerializeError, DirectoryIndex, HeaderExtensionKind, InlineFiles,
+        PathFilter, StringPool, StringPoolCompression, MAX_INLINE_FILE_SIZE,
+    },
+    raw::{
+        native_file_header::NativeFileHeader,
+        toc::{determine_optimal_toc_format, ToCFormat},
+    },
+};
+use crate::prelude::*;
+use crate::utilities::memory_audit::InternalAlloc;
+use alloc::string::String;
+use thiserror_no_std::Error;
+
+/// Alignment of each block within the archive, as per the specification.
+const BLOCK_ALIGNMENT: usize = NativeFileHeader::BLOCK_ALIGNMENT as usize;
+
+/// Errors that can occur when writing an archive from raw blocks.
+#[derive(Debug, Error)]
+pub enum RawArchiveWriteError {
+    /// Failed to read the blocks or entries of a source archive.
+    #[error("Failed to read source archive: {0:?}")]
+    Read(#[from] ArchiveReadError),
+
+    /// A source archive uses a different chunk size, so its chunked files can't be copied as-is.
+    #[error("Chunk size of source archive ({actual}) does not match the writer ({expected})")]
+    ChunkSizeMismatch { expected: u32, actual: u32 },
+
+    /// A source archive hashes its files with a different seed, so its hashes can't be copied as-is.
+    #[error("Hash seed of source archive ({actual}) does not match the writer ({expected})")]
+    HashSeedMismatch { expected: u64, actual: u64 },
+
+    /// A file in a source archive has no path, and therefore can't be added.
+    #[error("A file in the source archive has no path")]
+    MissingFilePath,
+
+    /// Failed to create the table of contents.
+    #[error("Failed to create the table of contents: {0:?}")]
+    TableOfContents(#[from] InitError),
+
+    /// Failed to write the table of contents.
+    #[error("Failed to write the table of contents: {0:?}")]
+    Serialize(#[from] SerializeError),
+
+    /// Failed to write the dictionaries of the blocks.
+    #[error("Failed to write the dictionaries: {0:?}")]
+    Dictionaries(#[from] DictionarySerializeError),
+}
+
+/// Writes a new archive out of blocks which are already compressed.
+///
+/// Blocks are taken as-is from [`NxArchiveReader::raw_block`] (or any other source),
+/// and are never decompressed. This makes it possible to re-bundle the contents of
+/// existing archives, e.g. on a server, without touching the user data itself.
+///
+/// # Remarks
+///
+/// Dictionaries are not stored in the archive itself. Blocks carry the dictionary they were
+/// compressed with (see [`RawBlock::dictionary`]); these are written separately by
+/// [`Self::dictionary_segment`], and supplied to the reader with
+/// [`NxArchiveReader::with_dictionary_segment`].
+pub struct NxRawArchiveWriter {
+    /// Size of chunks used by chunked files in the added blocks.
+    chunk_size: u32,
+
+    /// The compressed blocks, in the order they are written.
+    blocks: Vec<RawBlock>,
+
+    /// Entries for every file in the archive.
+    entries: Vec<FileEntry>,
+
+    /// Path of each file, index matches [`Self::entries`].
+    paths: Vec<String>,
+
+    /// Order the paths (and entries) are sorted in.
+    path_collation: PathCollation,
+
+    /// Index of each entry whose contents are stored in the header, with the contents.
+    inline_files: Vec<(usize, Vec<u8>)>,
+
+    /// Size of the path filter stored in the header, in bits per path. See [`Self::with_path_filter`].
+    path_filter_bits: Option<u32>,
+
+    /// Whether to store a [`DirectoryIndex`] in the header.
+    directory_index: bool,
+
+    /// Generation stored in the header, see [`Self::with_generation`].
+    generation: u64,
+
+    /// Seed of the file hashes, stored in the header. See [`Self::with_hash_seed`].
+    hash_seed: u64,
+
+    /// Hashes of the chunks of chunked files, see [`Self::add_chunk_hashes`].
+    chunk_hashes: ChunkHashes,
+
+    /// Chunked files split with a chunk size of their own, see [`Self::add_chunk_size`].
+    chunk_sizes: ChunkSizes,
+
+    /// How the string pool is compressed, see [`Self::with_string_pool_compression`].
+    string_pool_compression: StringPoolCompression,
+
+    /// Whether the file hashes are stored, see [`Self::with_file_hashes`].
+    file_hashes: FileHashStorage,
+
+    /// Whether the string pool is stored, see [`Self::with_file_paths`].
+    file_paths: bool,
+
+    /// Distinct dictionaries used by the blocks, see [`Self::dictionary_segment`].
+    dictionaries: Vec<Vec<u8>>,
+
+    /// Index into [`Self::dictionaries`] for each block, or [`NO_DICTIONARY_INDEX`].
+    block_dictionaries: Vec<u32>,
+}
+
+impl NxRawArchiveWriter {
+    /// Creates a new writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size` - Chunk size stored in the header. Must match the chunk size used
+    ///   to create any chunked files added to the archive.
+    pub fn new(chunk_size: u32) -> Self {
+        Self {
+            chunk_size,
+            blocks: Vec::new(),
+            entries: Vec::new(),
+            paths: Vec::new(),
+            path_collation: PathCollation::Ordinal,
+            inline_files: Vec::new(),
+            path_filter_bits: None,
+            directory_index: false,
+            generation: 0,
+            hash_seed: 0,
+            chunk_hashes: ChunkHashes::new(),
+            chunk_sizes: ChunkSizes::new(),
+            string_pool_compression: StringPoolCompression::default(),
+            file_hashes: FileHashStorage::default(),
+            file_paths: true,
+            dictionaries: Vec::new(),
+            block_dictionaries: Vec::new(),
+        }
+    }
+
+    /// Sets the order the paths of the archive are sorted in.
+    /// The collation is recorded in the header of the written archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `collation` - How to compare the paths.
+    pub fn with_path_collation(mut self, collation: PathCollation) -> Self {
+        self.path_collation = collation;
+        self
+    }
+
+    /// Stores a filter of the file paths in the header, letting readers reject paths
+    /// which are not in the archive without unpacking the string pool. See [`PathFilter`].
+    ///
+    /// # Arguments
+    ///
+    /// * `bits_per_path` - Size of the filter; e.g. [`DEFAULT_PATH_FILTER_BITS`]
+    ///   gives roughly 1% false positives.
+    ///
+    /// [`DEFAULT_PATH_FILTER_BITS`]: crate::headers::parser::DEFAULT_PATH_FILTER_BITS
+    pub fn with_path_filter(mut self, bits_per_path: u32) -> Self {
+        self.path_filter_bits = Some(bits_per_path);
+        self
+    }
+
+    /// Stores an index of the paths within each directory in the header, letting readers
+    /// find the files in a directory with a binary search. See [`DirectoryIndex`].
+    pub fn with_directory_index(mut self) -> Self {
+        self.directory_index = true;
+        self
+    }
+
+    /// Stores the number of times the archive was edited in the header.
+    /// See [`NxArchiveReader::generation`].
+    ///
+    /// # Arguments
+    ///
+    /// * `generation` - The generation; 0 is not stored.
+    pub fn with_generation(mut self, generation: u64) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    /// Records the seed the file hashes were computed with in the header.
+    /// See [`NxArchiveReader::hash_seed`].
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - The XXH3 seed; 0 (unseeded) is not stored.
+    pub fn with_hash_seed(mut self, seed: u64) -> Self {
+        self.hash_seed = seed;
+        self
+    }
+
+    /// Sets how the string pool (the paths of the files) is compressed.
+    ///
+    /// # Arguments
+    ///
+    /// * `compression` - Codec and level of the pool, and when to store it raw.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_string_pool_compression(mut self, compression: StringPoolCompression) -> Self {
+        self.string_pool_compression = compression;
+        self
+    }
+
+    /// Sets whether the hashes of the files are stored in the table of contents.
+    /// Hashes are stored by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - When to store the hashes.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_file_hashes(mut self, storage: FileHashStorage) -> Self {
+        self.file_hashes = storage;
+        self
+    }
+
+    /// Sets whether the paths of the files are stored. Without them, the archive is
+    /// content addressed; files can only be found by hash, see [`NxArchiveReader::find_by_hash`].
+    /// The path filter and directory index need the paths, so they are not stored either.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - False to omit the string pool.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_file_paths(mut self, store: bool) -> Self {
+        self.file_paths = store;
+        self
+    }
+
+    /// Returns the number of blocks added so far.
+    pub fn block_count(&self) -> u32 {
+        self.blocks.len() as u32
+    }
+
+    /// Adds a compressed block to the archive.
+    ///
+    /// # Returns
+    ///
+    /// Index of the block in the new archive, for use in [`FileEntry::first_block_index`].
+    pub fn add_block(&mut self, mut block: RawBlock) -> u32 {
+        let dict_index = match block.dictionary.take() {
+            Some(data) => match self.dictionaries.iter().position(|x| *x == data) {
+                Some(index) => index as u32,
+                None => {
+                    self.dictionaries.push(data);
+                    self.dictionaries.len() as u32 - 1
+                }
+            },
+            None => NO_DICTIONARY_INDEX as u32,
+        };
+
+        self.block_dictionaries.push(dict_index);
+        self.blocks.push(block);
+        self.blocks.len() as u32 - 1
+    }
+
+    /// Writes the dictionaries used by the added blocks, to be supplied to the reader with
+    /// [`NxArchiveReader::with_dictionary_segment`]. Must be called before [`Self::build`].
+    ///
+    /// # Returns
+    ///
+    /// The serialized dictionaries, or [`None`] if no block uses a dictionary.
+    pub fn dictionary_segment(&self) -> Result<Option<Vec<u8>>, RawArchiveWriteError> {
+        if self.dictionaries.is_empty() {
+            return Ok(None);
+        }
+
+        let dictionaries: Vec<&[u8]> = self.dictionaries.iter().map(|x| x.as_slice()).collect();
+        let blocks: Vec<BlockDictIndex> = self
+            .block_dictionaries
+            .iter()
+            .map(|x| BlockDictIndex(*x))
+            .collect();
+        Ok(Some(serialize_dictionary_data(
+            &dictionaries,
+            &blocks,
+            false,
+            true,
+        )?))
+    }
+
+    /// Adds a file to the archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Relative path of the file in the archive.
+    /// * `entry` - Location of the file's data. [`FileEntry::first_block_index`] refers to the
+    ///   blocks of this writer. [`FileEntry::file_path_index`] is ignored.
+    pub fn add_file(&mut self, path: &str, entry: FileEntry) {
+        self.entries.push(entry);
+        self.paths.push(String::from(path));
+    }
+
+    /// Stores the hash of each chunk of a chunked file in the header, so chunks can be
+    /// verified on their own. See [`ChunkHashes`].
+    ///
+    /// # Arguments
+    ///
+    /// * `first_block_index` - Index of the first block of the file, as returned by [`Self::add_block`].
+    /// * `hashes` - Hash of each decompressed chunk, computed with the seed set in [`Self::with_hash_seed`].
+    pub fn add_chunk_hashes(&mut self, first_block_index: u32, hashes: Vec<u64>) {
+        self.chunk_hashes.insert(first_block_index, hashes);
+    }
+
+    /// Records the chunk size of a chunked file which was split with a chunk size other than
+    /// the one passed to [`Self::new`]. See [`ChunkSizes`].
+    ///
+    /// # Arguments
+    ///
+    /// * `first_block_index` - Index of the first block of the file, as returned by [`Self::add_block`].
+    /// * `chunk_size` - Size of the file's chunks. Must be a power of 2.
+    pub fn add_chunk_size(&mut self, first_block_index: u32, chunk_size: u32) {
+        if chunk_size != self.chunk_size {
+            self.chunk_sizes.insert(first_block_index, chunk_size);
+        }
+    }
+
+    /// Adds a file to the archive, storing a copy of its contents in the header,
+    /// so it can be read without fetching its block. See [`InlineFiles`].
+    ///
+    /// The file must still be stored in a block as usual; files larger than
+    /// [`MAX_INLINE_FILE_SIZE`] are added without inlining them.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Relative path of the file in the archive.
+    /// * `entry` - Location of the file's data, see [`Self::add_file`].
+    /// * `data` - Contents of the file.
+    pub fn add_inline_file(&mut self, path: &str, entry: FileEntry, data: &[u8]) {
+        debug_assert_eq!(data.len() as u64, entry.decompressed_size);
+        if data.len() <= MAX_INLINE_FILE_SIZE as usize {
+            self.inline_files
+                .push((self.entries.len(), Vec::from(data)));
+        }
+
+        self.add_file(path, entry);
+    }
+
+    /// Copies all blocks and files of an existing archive into this one.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The archive to copy from.
+    pub fn append_archive(&mut self, reader: &NxArchiveReader) -> Result<(), RawArchiveWriteError> {
+        if reader.chunk_size() != self.chunk_size {
+            return Err(RawArchiveWriteError::ChunkSizeMismatch {
+                expected: self.chunk_size,
+                actual: reader.chunk_size(),
+            });
+        }
+
+        if reader.has_file_hashes() && reader.hash_seed() != self.hash_seed {
+            return Err(RawArchiveWriteError::HashSeedMismatch {
+                expected: self.hash_seed,
+                actual: reader.hash_seed(),
+            });
+        }
+
+        let first_block = self.block_count();
+        for block_index in 0..reader.block_count() {
+            self.add_block(reader.raw_block(block_index)?);
+        }
+
+        if let Some(chunk_hashes) = reader.chunk_hashes() {
+            for (block_index, hashes) in chunk_hashes.iter() {
+                self.add_chunk_hashes(block_index + first_block, Vec::from(hashes));
+            }
+        }
+
+        if let Some(chunk_sizes) = reader.chunk_sizes() {
+            for (block_index, chunk_size) in chunk_sizes.iter() {
+                self.add_chunk_size(block_index + first_block, chunk_size);
+            }
+        }
+
+        for entry in reader.entries() {
+            let path = reader
+                .file_path(entry)?
+                .ok_or(RawArchiveWriteError::MissingFilePath)?;
+
+            let inline_data = reader.inline_files().and_then(|x| x.get(entry));
+            let mut entry = *entry;
+            entry.first_block_index += first_block;
+            match inline_data {
+                Some(data) => self.add_inline_file(path, entry, data),
+                None => self.add_file(path, entry),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the archive.
+    ///
+    /// # Returns
+    ///
+    /// The complete archive.
+    pub fn build(self) -> Result<Vec<u8>, RawArchiveWriteError> {
+        self.build_with_diagnostics(&mut PackDiagnostics::default())
+    }
+
+    /// Writes the archive, reporting warnings such as a table of contents close to the
+    /// limits of the format.
+    ///
+    /// # Arguments
+    ///
+    /// * `diagnostics` - Receives the warnings.
+    ///
+    /// # Returns
+    ///
+    /// The complete archive.
+    pub fn build_with_diagnostics(
+        mut self,
+        diagnostics: &mut PackDiagnostics,
+    ) -> Result<Vec<u8>, RawArchiveWriteError> {
+        // Pack the string pool; this sorts the paths, giving us the path indices.
+        let mut pool_items: Vec<PoolItem> = self
+            .paths
+            .iter()
+            .enumerate()
+            .map(|(entry_index, path)| PoolItem { path, entry_index })
+            .collect();
+        let mut string_pool = StringPool::pack_v0_with_compression(
+            &mut pool_items,
+            InternalAlloc::default(),
+            Global,
+            self.string_pool_compression,
+            self.path_collation,
+        )
+        .map_err(InitError::FailedToCreateStringPool)?;
+        for (path_index, item) in pool_items.iter().enumerate() {
+            self.entries[item.entry_index].file_path_index = path_index as u32;
+        }
+        if !self.file_paths {
+            string_pool.clear();
+        }
+
+        // Determine the table of contents format
+        let max_decomp_block_offset = self
+            .entries
+            .iter()
+            .map(|entry| entry.decompressed_block_offset)
+            .max()
+            .unwrap_or(0);
+        let max_file_size = self
+            .entries
+            .iter()
+            .map(|entry| entry.decompressed_size)
+            .max()
+            .unwrap_or(0);
+        let select_format = |hashes_required| {
+            determine_optimal_toc_format(
+                string_pool.len() as u32,
+                max_decomp_block_offset,
+                self.blocks.len() as u32,
+                self.entries.len() as u32,
+                hashes_required,
+                max_file_size,
+            )
+        };
+        let mut format = select_format(self.file_hashes != FileHashStorage::Never);
+
+        // In 'Auto' mode, drop the hashes if they push the header past a single page.
+        if self.file_hashes == FileHashStorage::Auto
+            && NativeFileHeader::SIZE_BYTES as u32
+                + calculate_toc_size(
+                    format,
+                    string_pool.len() as u32,
+                    self.blocks.len() as u32,
+                    self.entries.len() as u32,
+                )
+                > NativeFileHeader::HEADER_PAGE_SIZE
+        {
+            format = select_format(false);
+        }
+        if format == ToCFormat::Error {
+            return Err(InitError::NoSuitableTocFormat(format).into());
+        }
+
+        let decompressed_pool_size = match self.file_paths {
+            true => self.paths.iter().map(|x| x.len() as u64 + 1).sum(),
+            false => 0,
+        };
+        check_table_of_contents(
+            self.entries.len() as u64,
+            self.blocks.len() as u64,
+            string_pool.len() as u64,
+            decompressed_pool_size,
+            diagnostics,
+        );
+
+        let table_size = calculate_toc_size(
+            format,
+            string_pool.len() as u32,
+            self.blocks.len() as u32,
+            self.entries.len() as u32,
+        );
+        let info = BuilderInfo {
+            format,
+            can_create_chunks: max_file_size > self.chunk_size as u64,
+            table_size,
+            max_decomp_block_offset,
+            string_pool,
+        };
+
+        let block_sizes: Vec<BlockSize> = self
+            .blocks
+            .iter()
+            .map(|block| BlockSize::new(block.data.len() as u32))
+            .collect();
+        let block_compressions: Vec<CompressionPreference> =
+            self.blocks.iter().map(|block| block.compression).collect();
+
+        // Write the header, table of contents and extensions
+        let extensions = self.header_extensions(&pool_items);
+        let toc_end = NativeFileHeader::SIZE_BYTES + table_size as usize;
+        let header_size = (toc_end + extensions.len()).next_multiple_of(BLOCK_ALIGNMENT);
+        let mut header = NativeFileHeader::init(self.chunk_size, header_size as u32);
+        let mut feature_flags = self.path_collation.feature_flags();
+        if !extensions.is_empty() {
+            feature_flags |= NativeFileHeader::EXTENSIONS_FLAG;
+        }
+        header.set_feature_flags(feature_flags);
+        let mut archive = vec![0u8; header_size];
+        archive[toc_end..toc_end + extensions.len()].copy_from_slice(&extensions);
+        archive[..NativeFileHeader::SIZE_BYTES].copy_from_slice(&header.to_bytes());
+        serialize_table_of_contents_into(
+            &block_compressions,
+            &block_sizes,
+            &self.entries,
+            &info,
+            &mut archive[NativeFileHeader::SIZE_BYTES..],
+        )?;
+
+        // Write the blocks
+        for block in &self.blocks {
+            archive.extend_from_slice(&block.data);
+            archive.resize(archive.len().next_multiple_of(BLOCK_ALIGNMENT), 0);
+        }
+
+        Ok(archive)
+    }
+
+    /// Serializes the header extensions, or returns an empty vector if there are none.
+    ///
+    /// # Arguments
+    ///
+    /// * `pool_items` - The paths, sorted by the string pool.
+    fn header_extensions(&self, pool_items: &[PoolItem]) -> Vec<u8> {
+        let mut inline_files = Vec::new();
+        if !self.inline_files.is_empty() {
+            let files: Vec<(FileEntry, &[u8])> = self
+                .inline_files
+                .iter()
+                .map(|(index, data)| (self.entries[*index], data.as_slice()))
+                .collect();
+            inline_files = InlineFiles::serialize(&files);
+        }
+
+        let path_filter = match self.path_filter_bits.filter(|_| self.file_paths) {
+            Some(bits) => PathFilter::new(self.paths.iter().map(|x| x.as_str()), bits).serialize(),
+            None => Vec::new(),
+        };
+
+        let directory_index = match self.directory_index && self.file_paths {
+            true => DirectoryIndex::new(pool_items.iter().map(|x| x.path)).serialize(),
+            false => Vec::new(),
+        };
+
+        let generation = match self.generation {
+            0 => Vec::new(),
+            x => Vec::from(x.to_le_bytes().as_slice()),
+        };
+
+        let hash_seed = match self.hash_seed {
+            0 => Vec::new(),
+            x => Vec::from(x.to_le_bytes().as_slice()),
+        };
+
+        let chunk_hashes = match self.chunk_hashes.is_empty() {
+            true => Vec::new(),
+            false => self.chunk_hashes.serialize(),
+        };
+
+        let chunk_sizes = match self.chunk_sizes.is_empty() {
+            true => Vec::new(),
+            false => self.chunk_sizes.serialize(),
+        };
+
+        let mut filters = BlockFilters::new();
+        for (block_index, block) in self.blocks.iter().enumerate() {
+            if let Some(filter) = block.filter {
+                filters.insert(block_index as u32, filter);
+            }
+        }
+        let block_filters = match filters.is_empty() {
+            true => Vec::new(),
+            false => filters.serialize(),
+        };
+
+        let segments: Vec<(HeaderExtensionKind, &[u8])> = [
+            (HeaderExtensionKind::InlineFiles, inline_files.as_slice()),
+            (HeaderExtensionKind::PathFilter, path_filter.as_slice()),
+            (
+                HeaderExtensionKind::DirectoryIndex,
+                directory_index.as_slice(),
+            ),
+            (HeaderExtensionKind::Generation, generation.as_slice()),
+            (HeaderExtensionKind::HashSeed, hash_seed.as_slice()),
+            (HeaderExtensionKind::ChunkHashes, chunk_hashes.as_slice()),
+            (HeaderExtensionKind::BlockFilters, block_filters.as_slice()),
+            (HeaderExtensionKind::ChunkSizes, chunk_sizes.as_slice()),
+        ]
+        .into_iter()
+        .filter(|(_, contents)| !contents.is_empty())
+        .collect();
+        serialize_header_extensions(&segments)
+    }
+}
+
+/// Path of a file, remembering which entry it belongs to once sorted by the string pool.
+struct PoolItem<'a> {
+    path: &'a str,
+    entry_index: usize,
+}
+
+impl HasRelativePath for PoolItem<'_> {
+    fn relative_path(&self) -> &str {
+        self.path
+    }
+}
+
+/// Dictionary used by a block, for [`serialize_dictionary_data`].
+struct BlockDictIndex(u32);
+
+impl HasDictIndex for BlockDictIndex {
+    fn dict_index(&self) -> u32 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::filedata::FromSliceReferenceProvider;
+    use crate::unsize_box2;
+    use crate::utilities::tests::archive_for_testing::*;
+
+    const CHUNK_SIZE: u32 = 4096;
+
+    fn open(archive: &[u8]) -> NxArchiveReader<'_> {
+        let provider = Box::new(FromSliceReferenceProvider::new(archive));
+        NxArchiveReader::new(unsize_box2!(provider)).unwrap()
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_merge_archives_without_decompressing() {
+        let large: Vec<u8> = (0..10_000_u32).map(|x| (x % 251) as u8).collect();
+        let first = create_test_archive(
+            CHUNK_SIZE,
+            &[&[TestFile::new("a.txt", b"Hello")]],
+            &[TestFile::new("large.bin", &large)],
+            CompressionPreference::ZStandard,
+        );
+        let second = create_test_archive(
+            CHUNK_SIZE,
+            &[&[
+                TestFile::new("b.txt", b"Nx archives!"),
+                TestFile::new("c.txt", b"Raw blocks"),
+            ]],
+            &[],
+            CompressionPreference::Copy,
+        );
+
+        let first_reader = open(&first);
+        let second_reader = open(&second);
+        assert_eq!(
+            first_reader.raw_block(0).unwrap().compression,
+            CompressionPreference::ZStandard
+        );
+
+        let mut writer = NxRawArchiveWriter::new(CHUNK_SIZE);
+        writer.append_archive(&first_reader).unwrap();
+        writer.append_archive(&second_reader).unwrap();
+        let merged = writer.build().unwrap();
+
+        let reader = open(&merged);
+        assert_eq!(
+            reader.block_count(),
+            first_reader.block_count() + second_reader.block_count()
+        );
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), b"Hello");
+        assert_eq!(reader.read_file("large.bin").unwrap(), large);
+        assert_eq!(
+            reader.read_file("b.txt").unwrap().as_slice(),
+            b"Nx archives!"
+        );
+        assert_eq!(reader.read_file("c.txt").unwrap().as_slice(), b"Raw blocks");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn dictionaries_are_carried_between_archives() {
+        use crate::api::archive_reader::DictionaryLoading;
+        use crate::headers::types::xxh3sum::XXH3sum;
+        use crate::utilities::compression::{
+            dictionary::ZstdCompressionDict, max_alloc_for_compress_size, zstd,
+        };
+
+        let dict_data = b"The quick brown fox jumps over the lazy dog. Nx archives!";
+        let data = b"Nx archives! The lazy dog jumps over the quick brown fox.";
+        let dict = ZstdCompressionDict::new(dict_data, 3).unwrap();
+        let mut compressed = vec![0u8; max_alloc_for_compress_size(data.len())];
+        let mut used_copy = false;
+        let size =
+            zstd::compress_with_dictionary(&dict, data, &mut compressed, &mut used_copy).unwrap();
+        compressed.truncate(size);
+
+        let mut writer = NxRawArchiveWriter::new(CHUNK_SIZE);
+        let block = writer.add_block(RawBlock {
+            data: compressed,
+            compression: CompressionPreference::ZStandard,
+            filter: None,
+            dictionary: Some(Vec::from(&dict_data[..])),
+        });
+        let hash = XXH3sum::create(data).0;
+        writer.add_file(
+            "a.txt",
+            FileEntry::new(hash, data.len() as u64, 0, 0, block),
+        );
+        let segment = writer.dictionary_segment().unwrap().unwrap();
+        let first = writer.build().unwrap();
+        let first_reader = unsafe {
+            open(&first)
+                .with_dictionary_segment(segment, DictionaryLoading::Eager)
+                .unwrap()
+        };
+
+        // Blocks of another archive come first, so the block indices change.
+        let other = create_test_archive(
+            CHUNK_SIZE,
+            &[&[TestFile::new("b.txt", b"Hello")]],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+        let mut writer = NxRawArchiveWriter::new(CHUNK_SIZE);
+        writer.append_archive(&open(&other)).unwrap();
+        writer.append_archive(&first_reader).unwrap();
+        let segment = writer.dictionary_segment().unwrap().unwrap();
+        let merged = writer.build().unwrap();
+
+        let reader = unsafe {
+            open(&merged)
+                .with_dictionary_segment(segment, DictionaryLoading::Eager)
+                .unwrap()
+        };
+        assert_eq!(reader.read_file("a.txt").unwrap().as_slice(), data);
+        assert_eq!(reader.read_file("b.txt").unwrap().as_slice(), b"Hello");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn records_path_collation() {
+        let archive = create_test_archive(
+            CHUNK_SIZE,
+            &[&[
+                TestFile::new("file10.txt", b"10"),
+                TestFile::new("file2.txt", b"2"),
+            ]],
+            &[],
+            CompressionPreference::Copy,
+        );
+
+        let mut writer =
+            NxRawArchiveWriter::new(CHUNK_SIZE).with_path_collation(PathCollation::Natural);
+        writer.append_archive(&open(&archive)).unwrap();
+        let written = writer.build().unwrap();
+
+        let reader = open(&written);
+        assert!(reader.anomalies().is_empty());
+        assert!(matches!(
+            reader.path_collation(),
+            Some(PathCollation::Natural)
+        ));
+        let paths: Vec<&str> = reader.string_pool().unwrap().iter().collect();
+        assert_eq!(paths, ["file2.txt", "file10.txt"]);
+        assert_eq!(reader.read_file("file2.txt").unwrap().as_slice(), b"2");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn inline_files_are_read_from_header() {
+        let mut writer = NxRawArchiveWriter::new(CHUNK_SIZE);
+        let block = writer.add_block(RawBlock {
+            data: Vec::from(&b"flagHello"[..]),
+            compression: CompressionPreference::Copy,
+            filter: None,
+            dictionary: None,
+        });
+        writer.add_inline_file("flag.txt", FileEntry::new(0, 4, 0, 0, block), b"flag");
+        writer.add_file("hello.txt", FileEntry::new(0, 5, 4, 0, block));
+        let archive = writer.build().unwrap();
+
+        let reader = open(&archive);
+        assert!(reader.anomalies().is_empty());
+        assert_eq!(reader.inline_files().map(|x| x.len()), Some(1));
+
+        // Inlined files are carried over when copying an archive.
+        let mut writer = NxRawArchiveWriter::new(CHUNK_SIZE);
+        writer.append_archive(&reader).unwrap();
+        let mut copied = writer.build().unwrap();
+
+        // Overwrite the block; the inlined file is still read from the header.
+        let block_offset = open(&copied).block_offset(0).unwrap() as usize;
+        copied[block_offset..block_offset + 9].fill(b'x');
+        let reader = open(&copied);
+        assert_eq!(reader.read_file("flag.txt").unwrap().as_slice(), b"flag");
+        assert_eq!(reader.read_file("hello.txt").unwrap().as_slice(), b"xxxxx");
+        let results = reader.read_files(&["flag.txt", "hello.txt"]);
+        assert_eq!(results[0].as_ref().unwrap().as_slice(), b"flag");
+        assert_eq!(results[1].as_ref().unwrap().as_slice(), b"xxxxx");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn path_filter_rejects_missing_files_without_unpacking_pool() {
+        let archive = create_test_archive(
+            CHUNK_SIZE,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new("b.txt", b"World"),
+            ]],
+            &[],
+            CompressionPreference::Copy,
+        );
+
+        let mut writer = NxRawArchiveWriter::new(CHUNK_SIZE).with_path_filter(16);
+        writer.append_archive(&open(&archive)).unwrap();
+        let written = writer.build().unwrap();
+
+        let provider = Box::new(FromSliceReferenceProvider::new(&written));
+        let reader = NxArchiveReader::new_with_lazy_pool(unsize_box2!(provider)).unwrap();
+        assert!(reader.anomalies().is_empty());
+        assert!(reader.may_contain("a.txt"));
+        assert!(reader.find_entry("missing.txt").unwrap().is_none());
+        assert!(!reader.is_string_pool_loaded());
+
+        assert_eq!(reader.read_file("b.txt").unwrap().as_slice(), b"World");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn directory_index_lists_files_without_unpacking_pool() {
+        let archive = create_test_archive(
+            CHUNK_SIZE,
+            &[&[
+                TestFile::new("data/a.bin", b"A"),
+                TestFile::new("data/maps/1.map", b"Map"),
+                TestFile::new("readme.txt", b"Hi"),
+            ]],
+            &[],
+            CompressionPreference::Copy,
+        );
+
+        let mut writer = NxRawArchiveWriter::new(CHUNK_SIZE).with_directory_index();
+        writer.append_archive(&open(&archive)).unwrap();
+        let written = writer.build().unwrap();
+
+        let provider = Box::new(FromSliceReferenceProvider::new(&written));
+        let reader = NxArchiveReader::new_with_lazy_pool(unsize_box2!(provider)).unwrap();
+        assert!(reader.anomalies().is_empty());
+
+        let files = reader.entries_in_directory("data/").unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(reader.entries_in_directory("missing").unwrap().is_empty());
+        assert_eq!(reader.entries_in_directory("").unwrap().len(), 3);
+        assert!(!reader.is_string_pool_loaded());
+
+        let map = reader.entries_in_directory("data/maps").unwrap();
+        let data = reader.read_entry_range(map[0].entry(), 0, 3).unwrap();
+        assert_eq!(data.as_slice(), b"Map");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn mismatched_chunk_size_returns_error() {
+        let archive = create_test_archive(
+            CHUNK_SIZE,
+            &[&[TestFile::new("a.txt", b"Hello")]],
+            &[],
+            CompressionPreference::Copy,
+        );
+
+        let mut writer = NxRawArchiveWriter::new(CHUNK_SIZE * 2);
+        assert!(matches!(
+            writer.append_archive(&open(&archive)),
+            Err(RawArchiveWriteError::ChunkSizeMismatch { .. })
+        ));
+    }
+}
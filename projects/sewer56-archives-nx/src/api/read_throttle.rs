@@ -0,0 +1,202 @@
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// How much data a [`ReadThrottle`] lets readers decompress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThrottleLimit {
+    /// Reads are not throttled.
+    #[default]
+    Unlimited,
+
+    /// At most this many decompressed bytes per second, averaged over up to a second.
+    BytesPerSecond(u64),
+
+    /// At most this many decompressed bytes per frame.
+    ///
+    /// The budget is refilled by each call to [`ReadThrottle::begin_frame`];
+    /// once spent, reads wait for the next frame.
+    PerFrame(u64),
+}
+
+/// Caps how fast archive readers decompress data, e.g. so a game streaming assets in the
+/// background doesn't take CPU time needed to render frames.
+///
+/// Share one throttle (via [`Arc`]) between the readers on loader threads and the thread
+/// which drives the budget, then attach it with [`NxArchiveReader::with_read_throttle`].
+///
+/// Each block is charged before it is decompressed. A block is never split, so a read only
+/// waits while the budget is spent; the budget may go into debt by up to one block, which is
+/// repaid before the next read proceeds. This guarantees progress even if blocks are larger
+/// than the budget.
+///
+/// [`Arc`]: alloc::sync::Arc
+/// [`NxArchiveReader::with_read_throttle`]: crate::api::archive_reader::NxArchiveReader::with_read_throttle
+#[derive(Debug, Default)]
+pub struct ReadThrottle {
+    state: Mutex<ThrottleState>,
+    budget_available: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct ThrottleState {
+    limit: ThrottleLimit,
+
+    /// Bytes which may be decompressed before waiting; negative while in debt.
+    available: i64,
+
+    /// When [`Self::available`] was last refilled, for [`ThrottleLimit::BytesPerSecond`].
+    last_refill: Option<Instant>,
+}
+
+impl ReadThrottle {
+    /// Creates a throttle with the given limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - How much data readers may decompress.
+    pub fn new(limit: ThrottleLimit) -> Self {
+        let throttle = Self::default();
+        throttle.set_limit(limit);
+        throttle
+    }
+
+    /// Returns the current limit.
+    pub fn limit(&self) -> ThrottleLimit {
+        self.lock().limit
+    }
+
+    /// Changes the limit, e.g. lifting it while a loading screen is shown.
+    /// Reads waiting on the previous limit are re-evaluated against the new one.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - How much data readers may decompress.
+    pub fn set_limit(&self, limit: ThrottleLimit) {
+        let mut state = self.lock();
+        state.limit = limit;
+        state.available = match limit {
+            ThrottleLimit::Unlimited => 0,
+            ThrottleLimit::BytesPerSecond(rate) => clamp_i64(rate),
+            ThrottleLimit::PerFrame(budget) => clamp_i64(budget),
+        };
+        state.last_refill = Some(Instant::now());
+        drop(state);
+        self.budget_available.notify_all();
+    }
+
+    /// Starts a new frame, refilling the budget of [`ThrottleLimit::PerFrame`].
+    ///
+    /// Call this once per frame (e.g. from the render loop). Unspent budget does not carry
+    /// over to the next frame; debt does. Does nothing for other limits.
+    pub fn begin_frame(&self) {
+        let mut state = self.lock();
+        let ThrottleLimit::PerFrame(budget) = state.limit else {
+            return;
+        };
+
+        state.available = state.available.min(0) + clamp_i64(budget);
+        drop(state);
+        self.budget_available.notify_all();
+    }
+
+    /// Charges `num_bytes` of decompression against the budget, first waiting
+    /// until the budget is no longer spent.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_bytes` - Number of bytes about to be decompressed.
+    pub fn acquire(&self, num_bytes: u64) {
+        let mut state = self.lock();
+        loop {
+            match state.limit {
+                ThrottleLimit::Unlimited => return,
+                ThrottleLimit::BytesPerSecond(rate) => {
+                    state.refill(rate);
+                    if state.available > 0 {
+                        break;
+                    }
+
+                    let wait =
+                        Duration::from_secs_f64((1 - state.available) as f64 / rate.max(1) as f64);
+                    state = self
+                        .budget_available
+                        .wait_timeout(state, wait)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0;
+                }
+                ThrottleLimit::PerFrame(_) => {
+                    if state.available > 0 {
+                        break;
+                    }
+
+                    state = self
+                        .budget_available
+                        .wait(state)
+                        .unwrap_or_else(|e| e.into_inner());
+                }
+            }
+        }
+
+        state.available = state.available.saturating_sub(clamp_i64(num_bytes));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ThrottleState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ThrottleState {
+    /// Adds the budget earned since the last refill, keeping at most a second's worth.
+    fn refill(&mut self, rate: u64) {
+        let now = Instant::now();
+        let elapsed = self
+            .last_refill
+            .map(|last| now.saturating_duration_since(last))
+            .unwrap_or_default();
+        let earned = (elapsed.as_nanos() * rate as u128 / 1_000_000_000) as u64;
+        if earned > 0 {
+            self.available = self
+                .available
+                .saturating_add(clamp_i64(earned))
+                .min(clamp_i64(rate));
+            self.last_refill = Some(now);
+        }
+    }
+}
+
+fn clamp_i64(value: u64) -> i64 {
+    value.min(i64::MAX as u64) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn per_frame_reads_wait_for_next_frame() {
+        let throttle = ReadThrottle::new(ThrottleLimit::PerFrame(100));
+
+        // Larger than two frames of budget, but the budget isn't spent yet.
+        throttle.acquire(250);
+
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                throttle.acquire(10);
+                sender.send(()).unwrap();
+            });
+
+            // Still 50 bytes in debt after the first frame.
+            throttle.begin_frame();
+            assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+            throttle.begin_frame();
+            receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        });
+
+        throttle.set_limit(ThrottleLimit::Unlimited);
+        throttle.acquire(u64::MAX);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::api::enums::DataFilter;
+use crate::api::traits::*;
+use crate::prelude::*;
+use alloc::rc::Rc;
+use core::any::Any;
+use hashbrown::HashTable;
+
+/// The value that indicates no dictionary is used.
+pub const NO_DICTIONARY_INDEX: u8 = u8::MAX;
+
+/// Entry of the table used to track which items were already collected by
+/// [`Block::append_items`]. Identifies an item by the address of its [`Rc`].
+#[derive(Debug)]
+pub struct PtrEntry {
+    key: u64,
+}
+
+/// A block of data to be packed into an archive.
+///
+/// The packer, Table of Contents builder and dictionary assignment work with `dyn Block<T>`,
+/// so a custom implementation (e.g. a block whose data comes from a remote content store)
+/// can be passed anywhere the built-in [`SolidBlock`] and [`ChunkedFileBlock`] are.
+///
+/// At minimum, implement [`Self::as_any`], [`Self::items`] and [`HasDictIndex::dict_index`]
+/// (returning [`NO_DICTIONARY_INDEX`] if no dictionary is used); the remaining methods
+/// have defaults suitable for blocks made of new data.
+///
+/// [`SolidBlock`]: crate::implementation::pack::blocks::polyfills::SolidBlock
+/// [`ChunkedFileBlock`]: crate::implementation::pack::blocks::polyfills::ChunkedFileBlock
+pub trait Block<T>: HasDictIndex
+where
+    T: HasFileSize + CanProvideInputData + HasRelativePath,
+{
+    /// Returns the block as [`Any`], allowing callers to downcast to the concrete block type.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Appends files to a given vector, using a [`HashTable`] to track duplicates.
+    ///
+    /// The default implementation appends every item of [`Self::items`] which
+    /// has not been seen yet, via [`append_unique_items`].
+    ///
+    /// # Arguments
+    /// * `items` - The vector to append items to
+    /// * `seen` - HashSet tracking already added items
+    fn append_items(&self, items: &mut Vec<Rc<T>>, seen: &mut HashTable<PtrEntry>) {
+        append_unique_items(self.items(), items, seen);
+    }
+
+    /// Returns a slice of references to all items in this block without allocation
+    fn items(&self) -> &[Rc<T>];
+
+    /// For any block that's based on existing data in another Nx archive, this returns
+    /// the max DecompressedBlockOffset for any existing file entry within the block.
+    fn max_decompressed_block_offset(&self) -> u32 {
+        0
+    }
+
+    /// Returns the filter applied to this block's data before compression.
+    fn filter(&self) -> DataFilter {
+        DataFilter::None
+    }
+
+    /// Returns the size limit this SOLID block was created with, if it was picked
+    /// for the block's group rather than taken from the packing settings.
+    fn block_size(&self) -> Option<u32> {
+        None
+    }
+}
+
+/// Appends each item of `source` to `items`, unless the same [`Rc`] was already
+/// recorded in `seen`. For use in implementations of [`Block::append_items`].
+///
+/// # Arguments
+/// * `source` - The items of a block.
+/// * `items` - The vector to append items to.
+/// * `seen` - Tracks the items added so far, across all blocks.
+pub fn append_unique_items<T>(
+    source: &[Rc<T>],
+    items: &mut Vec<Rc<T>>,
+    seen: &mut HashTable<PtrEntry>,
+) {
+    for item in source {
+        let ptr_value = Rc::as_ptr(item) as u64;
+
+        if seen
+            .find(ptr_value, |entry| entry.key == ptr_value)
+            .is_none()
+        {
+            seen.insert_unique(ptr_value, PtrEntry { key: ptr_value }, |entry| entry.key);
+            items.push(item.clone());
+        }
+    }
+}
+
+// Blanket implmementation
+impl<T> HasDictIndex for Box<dyn Block<T>>
+where
+    T: HasFileSize + CanProvideInputData + HasRelativePath,
+{
+    fn dict_index(&self) -> u32 {
+        (**self).dict_index()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::tests::packer_file_for_testing::PackerFileForTesting;
+    use allocator_api2::vec;
+
+    /// A block implemented the way an external crate would, relying on the defaults.
+    struct ExternalBlock {
+        items: Vec<Rc<PackerFileForTesting>>,
+    }
+
+    impl Block<PackerFileForTesting> for ExternalBlock {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn items(&self) -> &[Rc<PackerFileForTesting>] {
+            &self.items
+        }
+    }
+
+    impl HasDictIndex for ExternalBlock {
+        fn dict_index(&self) -> u32 {
+            NO_DICTIONARY_INDEX as u32
+        }
+    }
+
+    #[test]
+    fn default_append_items_skips_duplicates() {
+        let shared = PackerFileForTesting::new_rc("shared.txt", 10);
+        let first = ExternalBlock {
+            items: vec![shared.clone(), PackerFileForTesting::new_rc("a.txt", 20)],
+        };
+        let second = ExternalBlock {
+            items: vec![shared, PackerFileForTesting::new_rc("b.txt", 30)],
+        };
+
+        let mut items = Vec::new();
+        let mut seen = HashTable::new();
+        first.append_items(&mut items, &mut seen);
+        second.append_items(&mut items, &mut seen);
+
+        let paths: Vec<&str> = items.iter().map(|item| item.relative_path()).collect();
+        assert_eq!(paths, ["shared.txt", "a.txt", "b.txt"]);
+    }
+}
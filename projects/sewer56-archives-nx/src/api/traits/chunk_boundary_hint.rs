@@ -0,0 +1,164 @@
+use crate::prelude::*;
+
+/// Allows asset-aware callers to describe semantic boundaries within a file,
+/// such as the end of each mip level in a DDS texture.
+///
+/// # Remarks
+///
+/// The Nx format stores chunked files as a sequence of equally sized chunks
+/// (chunk size is stored in the file header), so chunks cannot end at arbitrary offsets. Instead, each boundary is mapped
+/// onto the chunk which contains it; data up to a boundary can then be read by
+/// decompressing the preceding chunks and partially decompressing the last one
+/// (see [`decompress_partial`]).
+///
+/// Readers locate a chunk by dividing an offset by the chunk size, so the packer
+/// always splits at multiples of it; hints only affect how much is read back, see
+/// [`NxArchiveReader::read_entry_to_boundary`].
+///
+/// [`decompress_partial`]: crate::utilities::compression::decompress_partial
+/// [`NxArchiveReader::read_entry_to_boundary`]: crate::api::archive_reader::NxArchiveReader::read_entry_to_boundary
+pub trait ChunkBoundaryHint {
+    /// Returns the offsets of semantic boundaries within the file, in ascending order.
+    ///
+    /// # Arguments
+    /// * `file_size` - Size of the file the boundaries are requested for.
+    fn chunk_boundaries(&self, file_size: u64) -> Vec<u64>;
+
+    /// Returns the boundaries which can be read up to.
+    ///
+    /// # Arguments
+    /// * `file_size` - Size of the file the boundaries are requested for.
+    ///
+    /// # Returns
+    /// The boundaries from [`Self::chunk_boundaries`], without those that are zero,
+    /// out of range or not in ascending order.
+    fn valid_boundaries(&self, file_size: u64) -> Vec<u64> {
+        let mut result = Vec::new();
+        let mut last_boundary = 0_u64;
+        for boundary in self.chunk_boundaries(file_size) {
+            if boundary <= last_boundary || boundary > file_size {
+                continue;
+            }
+
+            result.push(boundary);
+            last_boundary = boundary;
+        }
+
+        result
+    }
+
+    /// Determines which chunks must be read in order to obtain all data before each boundary.
+    ///
+    /// # Arguments
+    /// * `file_size` - Size of the file the boundaries are requested for.
+    /// * `chunk_size` - Size of the chunks in the archive.
+    ///
+    /// # Returns
+    /// One [`ChunkedRead`] per boundary in [`Self::valid_boundaries`].
+    /// Empty if `chunk_size` is zero.
+    fn chunked_reads(&self, file_size: u64, chunk_size: u32) -> Vec<ChunkedRead> {
+        let mut result = Vec::new();
+        for boundary in self.valid_boundaries(file_size) {
+            result.extend(ChunkedRead::to_offset(boundary, chunk_size));
+        }
+
+        result
+    }
+}
+
+/// Describes the chunks that need to be decompressed to read a file from its start up to a given offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkedRead {
+    /// Number of chunks that need to be read, including the final, partially read chunk.
+    pub chunk_count: u32,
+
+    /// Number of bytes to decompress from the final chunk.
+    /// This equals the chunk size if the offset is at a chunk boundary.
+    pub bytes_in_last_chunk: u32,
+}
+
+impl ChunkedRead {
+    /// Creates a [`ChunkedRead`] which covers bytes `0..offset` of a file.
+    ///
+    /// # Arguments
+    /// * `offset` - End offset of the data to read (exclusive).
+    ///   Zero results in a read of zero chunks.
+    /// * `chunk_size` - Size of the chunks in the archive.
+    ///
+    /// # Returns
+    /// The read, or [`None`] if `chunk_size` is zero.
+    pub fn to_offset(offset: u64, chunk_size: u32) -> Option<Self> {
+        if chunk_size == 0 {
+            return None;
+        }
+
+        let chunk_size = chunk_size as u64;
+        let chunk_count = offset.div_ceil(chunk_size);
+        let bytes_in_last_chunk = match chunk_count {
+            0 => 0,
+            _ => offset - ((chunk_count - 1) * chunk_size),
+        };
+        Some(Self {
+            chunk_count: chunk_count as u32,
+            bytes_in_last_chunk: bytes_in_last_chunk as u32,
+        })
+    }
+}
+
+/// Fixed list of boundaries, for callers which already know the layout of a file.
+impl ChunkBoundaryHint for [u64] {
+    fn chunk_boundaries(&self, _file_size: u64) -> Vec<u64> {
+        let mut result = Vec::with_capacity(self.len());
+        result.extend_from_slice(self);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::inside_first_chunk(100, 1, 100)]
+    #[case::exactly_one_chunk(1024, 1, 1024)]
+    #[case::inside_second_chunk(1025, 2, 1)]
+    #[case::inside_third_chunk(2100, 3, 52)]
+    #[case::empty(0, 0, 0)]
+    fn to_offset_maps_to_enclosing_chunk(
+        #[case] offset: u64,
+        #[case] chunk_count: u32,
+        #[case] bytes_in_last_chunk: u32,
+    ) {
+        let read = ChunkedRead::to_offset(offset, 1024).unwrap();
+        assert_eq!(read.chunk_count, chunk_count);
+        assert_eq!(read.bytes_in_last_chunk, bytes_in_last_chunk);
+    }
+
+    #[test]
+    fn chunked_reads_skips_invalid_boundaries() {
+        let mips: &[u64] = &[0, 2048, 1000, 2560, 9999];
+        let reads = mips.chunked_reads(3000, 1024);
+
+        assert_eq!(
+            reads.as_slice(),
+            &[
+                ChunkedRead::to_offset(2048, 1024).unwrap(),
+                ChunkedRead::to_offset(2560, 1024).unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn valid_boundaries_skips_invalid_boundaries() {
+        let mips: &[u64] = &[0, 2048, 1000, 2560, 9999];
+        assert_eq!(mips.valid_boundaries(3000).as_slice(), &[2048, 2560]);
+    }
+
+    #[test]
+    fn zero_chunk_size_has_no_reads() {
+        let mips: &[u64] = &[512, 1024];
+        assert_eq!(ChunkedRead::to_offset(512, 0), None);
+        assert!(mips.chunked_reads(2048, 0).is_empty());
+    }
+}
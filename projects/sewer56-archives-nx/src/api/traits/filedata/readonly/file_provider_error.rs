@@ -23,10 +23,19 @@ pub enum FileProviderError {
     #[error("Failed to read {0} bytes from offset {1}")]
     FailedToReadFromStream(u64, u64),
 
+    /// Requested data past the end of a provider with a known length.
+    #[error("Requested {1} bytes at offset {0}, which is past the end of the data")]
+    OutOfRange(u64, u64),
+
     /// Error omitted from 3rd party integration
     #[error("Third party error: {0}")]
     ThirdPartyError(String),
 
+    /// Error omitted from 3rd party integration, which may succeed if retried
+    /// (e.g. a dropped connection or an HTTP 503).
+    #[error("Transient error: {0}")]
+    Transient(String),
+
     /// Failed to open file handle.
     #[error(transparent)]
     FileHandleOpenError(#[from] HandleOpenError),
@@ -43,3 +52,30 @@ pub enum FileProviderError {
     #[error(transparent)]
     IoError(#[from] io::Error),
 }
+
+impl FileProviderError {
+    /// Returns true if the operation which caused this error may succeed if retried,
+    /// e.g. a read from a network share which timed out.
+    ///
+    /// Failed reads and seeks of streams, [`Self::Transient`] errors, and I/O errors
+    /// such as timeouts and dropped connections are retryable. Everything else
+    /// (missing files, out of range reads, corrupted data, ...) is fatal.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            FileProviderError::FailedToSeekStream(_)
+            | FileProviderError::FailedToReadFromStream(_, _)
+            | FileProviderError::Transient(_) => true,
+            FileProviderError::IoError(error) => matches!(
+                error.kind(),
+                io::ErrorKind::Interrupted
+                    | io::ErrorKind::WouldBlock
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::BrokenPipe
+            ),
+            _ => false,
+        }
+    }
+}
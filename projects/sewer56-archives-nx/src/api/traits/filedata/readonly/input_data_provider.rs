@@ -47,4 +47,37 @@ pub trait InputDataProvider: Send + Sync {
         start: u64,
         length: u64,
     ) -> Result<Box<dyn ReadOnlyFileData + 'a>, FileProviderError>;
+
+    /// Checks whether the data behind this provider changed since the provider was created,
+    /// e.g. a file on disk being rewritten by another process.
+    ///
+    /// # Returns
+    ///
+    /// The current size of the data if it changed, or [`None`] if it did not,
+    /// or if the provider can't detect changes (the default).
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`FileProviderError`] if the data can no longer be accessed, e.g. a deleted file.
+    fn detect_change(&self) -> Result<Option<u64>, FileProviderError> {
+        Ok(None)
+    }
+
+    /// Checks whether a range can be read without fetching it from a slow source
+    /// (e.g. a network), such as when it is already in a local cache.
+    ///
+    /// This must not perform any I/O itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Start offset into the file (in bytes).
+    /// * `length` - Length of the data (in bytes).
+    ///
+    /// # Returns
+    ///
+    /// True if the range is available locally. Providers without a cache always return true (the default).
+    fn is_resident(&self, start: u64, length: u64) -> bool {
+        let _ = (start, length);
+        true
+    }
 }
@@ -7,4 +7,17 @@ pub trait HasSolidType {
 
     /// Allows you to set whether this item should be SOLID or not
     fn set_solid_type(&mut self, preference: SolidPreference);
+
+    /// Whether this item must be stored alone in its own block, using fast compression.
+    /// Used for files which are read often (e.g. at startup), so reading them never
+    /// requires decompressing unrelated data.
+    fn force_own_block(&self) -> bool {
+        false
+    }
+
+    /// Size of the chunks this item is split into if it is chunked, when it should not use
+    /// the archive's chunk size; e.g. a single huge file read in large sequential pieces.
+    fn chunk_size_override(&self) -> Option<u32> {
+        None
+    }
 }
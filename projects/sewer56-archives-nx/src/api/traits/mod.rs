@@ -1,7 +1,11 @@
 /// Trait for items which can provide bytes corresponding to a file.
 pub mod can_provide_input_data;
+/// Allows callers to describe semantic boundaries within a file for partial reads.
+pub mod chunk_boundary_hint;
 /// Allows for specifying inputs and outputs for pack and extract operations.
 pub mod filedata;
+/// A block of data to be packed; implementable for custom block types.
+pub mod block;
 /// Used for items to with which format they would like to be compressed.
 pub mod has_compression_preference;
 /// Has a numbered dictionary index attached to the item.
@@ -12,15 +16,20 @@ pub mod has_file_size;
 pub mod has_relative_path;
 /// Used for items to specify a preference on whether they'd prefer to be SOLIDly packed or not.
 pub mod has_solid_type;
+/// Object safe read access to archives, e.g. for plugin systems.
+pub mod nx_reader;
 /// Used for reporting progress to external callers.
 pub mod progress;
 
 /// Prelude with re-exports
+pub use block::*;
 pub use can_provide_input_data::*;
+pub use chunk_boundary_hint::*;
 pub use filedata::*;
 pub use has_compression_preference::*;
 pub use has_dict_index::*;
 pub use has_file_size::*;
 pub use has_relative_path::*;
 pub use has_solid_type::*;
+pub use nx_reader::*;
 pub use progress::*;
@@ -0,0 +1,152 @@
+use crate::api::archive_reader::{ArchiveReadError, NxArchiveReader};
+use crate::prelude::*;
+use alloc::string::String;
+
+/// Information about a single file, as returned by [`NxReader::stat`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStat {
+    /// Relative path of the file within the archive.
+    pub path: String,
+
+    /// Size of the file, in bytes.
+    pub size: u64,
+
+    /// XXH3 hash of the file's contents, or [`None`] if the archive stores no hashes.
+    pub hash: Option<u64>,
+
+    /// Estimated compressed size of the file, see [`NxArchiveReader::compressed_footprint`].
+    pub compressed_size: u64,
+}
+
+/// Read access to an archive, usable as a trait object (`dyn NxReader`).
+///
+/// All methods take and return owned, non-generic types, so hosts can hand archives to
+/// plugin systems (e.g. sandboxed scripting runtimes) without exposing [`NxArchiveReader`],
+/// its lifetimes or its input providers.
+pub trait NxReader {
+    /// Returns the relative paths of all files in the archive.
+    fn list(&self) -> Result<Vec<String>, ArchiveReadError>;
+
+    /// Reads the entire contents of a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Relative path of the file within the archive.
+    fn read(&self, path: &str) -> Result<Vec<u8>, ArchiveReadError>;
+
+    /// Reads `length` bytes of a file, starting at `offset`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Relative path of the file within the archive.
+    /// * `offset` - Offset of the first byte to read, relative to the start of the file.
+    /// * `length` - Number of bytes to read.
+    fn read_range(&self, path: &str, offset: u64, length: u64)
+        -> Result<Vec<u8>, ArchiveReadError>;
+
+    /// Returns information about a file, without reading it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Relative path of the file within the archive.
+    fn stat(&self, path: &str) -> Result<FileStat, ArchiveReadError>;
+
+    /// Reads a file, and checks it against the hash stored in the archive.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Relative path of the file within the archive.
+    ///
+    /// # Returns
+    ///
+    /// False if the contents do not match the stored hash. True if they do,
+    /// or if the archive stores no hashes (see [`FileStat::hash`]).
+    fn verify(&self, path: &str) -> Result<bool, ArchiveReadError>;
+}
+
+impl NxReader for NxArchiveReader<'_> {
+    fn list(&self) -> Result<Vec<String>, ArchiveReadError> {
+        Ok(self
+            .iter_entries()?
+            .map(|(path, _)| String::from(path))
+            .collect())
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>, ArchiveReadError> {
+        self.read_file(path)
+    }
+
+    fn read_range(
+        &self,
+        path: &str,
+        offset: u64,
+        length: u64,
+    ) -> Result<Vec<u8>, ArchiveReadError> {
+        self.read_file_range(path, offset, length)
+    }
+
+    fn stat(&self, path: &str) -> Result<FileStat, ArchiveReadError> {
+        let entry = self
+            .find_entry(path)?
+            .ok_or(ArchiveReadError::FileNotFound)?;
+        Ok(FileStat {
+            path: String::from(path),
+            size: entry.decompressed_size,
+            hash: self.has_file_hashes().then_some(entry.hash),
+            compressed_size: self.compressed_footprint(entry),
+        })
+    }
+
+    fn verify(&self, path: &str) -> Result<bool, ArchiveReadError> {
+        let entry = self
+            .find_entry(path)?
+            .ok_or(ArchiveReadError::FileNotFound)?;
+        let data = self.read_entry_range(entry, 0, entry.decompressed_size)?;
+        Ok(!self.has_file_hashes() || self.hash_data(&data) == entry.hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::enums::CompressionPreference;
+    use crate::api::filedata::FromSliceReferenceProvider;
+    use crate::headers::types::xxh3sum::XXH3sum;
+    use crate::unsize_box2;
+    use crate::utilities::tests::archive_for_testing::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reads_through_trait_object() {
+        let archive = create_test_archive(
+            32768,
+            &[&[
+                TestFile::new("a.txt", b"Hello"),
+                TestFile::new("b.txt", b"Nx archives!"),
+            ]],
+            &[],
+            CompressionPreference::ZStandard,
+        );
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+        let reader: &dyn NxReader = &reader;
+
+        let mut paths = reader.list().unwrap();
+        paths.sort();
+        assert_eq!(paths, ["a.txt", "b.txt"]);
+        assert_eq!(reader.read("a.txt").unwrap().as_slice(), b"Hello");
+        assert_eq!(
+            reader.read_range("b.txt", 3, 8).unwrap().as_slice(),
+            b"archives"
+        );
+
+        let stat = reader.stat("b.txt").unwrap();
+        assert_eq!(stat.size, 12);
+        assert_eq!(stat.hash, Some(XXH3sum::create(b"Nx archives!").0));
+        assert!(reader.verify("b.txt").unwrap());
+        assert!(matches!(
+            reader.stat("missing.txt"),
+            Err(ArchiveReadError::FileNotFound)
+        ));
+    }
+}
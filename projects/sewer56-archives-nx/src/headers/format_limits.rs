@@ -0,0 +1,194 @@
+use crate::headers::enums::v1::TableOfContentsVersion;
+use crate::headers::managed::v1::{MAX_BLOCK_COUNT_V0V1, MAX_FILE_COUNT_V0V1};
+use crate::headers::raw::native_file_header::NativeFileHeader;
+use crate::headers::raw::toc::*;
+
+pub use crate::api::packing::packing_settings::{
+    MAX_BLOCK_SIZE, MAX_CHUNK_SIZE, MIN_BLOCK_SIZE, MIN_CHUNK_SIZE,
+};
+pub use crate::headers::parser::{MAX_DICTIONARIES, MAX_INLINE_FILE_SIZE, MAX_PATH_FILTER_BITS};
+
+/// Alignment of each block within the archive, in bytes.
+pub const BLOCK_ALIGNMENT: u32 = NativeFileHeader::BLOCK_ALIGNMENT;
+
+/// Largest size of the string pool (all file paths, each followed by a null terminator)
+/// before compression, in bytes.
+pub const MAX_DECOMPRESSED_STRING_POOL_SIZE: u32 = MAX_STRING_POOL_SIZE as u32;
+
+/// Longest path of a single file, in bytes, leaving room for its null terminator.
+/// In practice, the paths of all files share [`MAX_DECOMPRESSED_STRING_POOL_SIZE`].
+pub const MAX_PATH_LENGTH: u32 = MAX_DECOMPRESSED_STRING_POOL_SIZE - 1;
+
+/// Largest value of the FEF64 fields, which are up to 31 bits wide.
+const FEF64_FIELD_MAX: u32 = (1 << 31) - 1;
+
+/// The limits of a single table of contents format.
+///
+/// Packers pick the smallest format which fits the archive (see [`determine_optimal_toc_format`]),
+/// so an archive can be packed if it fits within the limits of any format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FormatLimits {
+    /// Largest number of files.
+    pub max_file_count: u32,
+
+    /// Largest number of blocks.
+    pub max_block_count: u32,
+
+    /// Largest size of the compressed string pool, in bytes.
+    pub max_string_pool_size: u32,
+
+    /// Largest offset of a file within a decompressed SOLID block.
+    /// Formats with a limit of 0 can't store SOLID blocks with more than one file.
+    pub max_decompressed_block_offset: u32,
+
+    /// Largest size of a single file, in bytes.
+    pub max_file_size: u64,
+
+    /// Whether the format stores the hash of each file.
+    pub stores_hashes: bool,
+}
+
+impl FormatLimits {
+    /// Returns the limits of a current (V2) table of contents format.
+    ///
+    /// # Remarks
+    ///
+    /// FEF64 splits 64 bits between its fields based on the archive, so its limits are those of
+    /// each field on its own; not all of them can be reached at once. Use
+    /// [`determine_optimal_toc_format`] to check a specific archive.
+    ///
+    /// # Returns
+    ///
+    /// The limits, or [`None`] for [`ToCFormat::Error`].
+    pub const fn for_format(format: ToCFormat) -> Option<Self> {
+        let limits = match format {
+            ToCFormat::FEF64 | ToCFormat::FEF64NoHash => Self {
+                max_file_count: FEF64_FIELD_MAX,
+                max_block_count: FEF64_FIELD_MAX,
+                max_string_pool_size: FEF64_FIELD_MAX,
+                max_decompressed_block_offset: FEF64_FIELD_MAX,
+                // At least 1 bit each for the file count and block count.
+                max_file_size: (1 << 62) - 1,
+                stores_hashes: matches!(format, ToCFormat::FEF64),
+            },
+            ToCFormat::Preset0 => Self {
+                max_file_count: PRESET0_FILE_COUNT_MAX,
+                max_block_count: PRESET0_BLOCK_COUNT_MAX,
+                max_string_pool_size: PRESET0_STRING_POOL_SIZE_MAX,
+                max_decompressed_block_offset: PRESET0_DECOMPRESSED_BLOCK_OFFSET_MAX,
+                max_file_size: PRESET0_MAX_FILE_SIZE as u64,
+                stores_hashes: true,
+            },
+            ToCFormat::Preset1NoHash => Self {
+                max_file_count: PRESET1_FILE_COUNT_MAX,
+                max_block_count: PRESET1_BLOCK_COUNT_MAX,
+                max_string_pool_size: PRESET1_STRING_POOL_SIZE_MAX,
+                max_decompressed_block_offset: PRESET0_DECOMPRESSED_BLOCK_OFFSET_MAX,
+                max_file_size: PRESET1_MAX_FILE_SIZE as u64,
+                stores_hashes: false,
+            },
+            ToCFormat::Preset2 => Self {
+                max_file_count: PRESET2_FILE_COUNT_MAX,
+                max_block_count: PRESET2_BLOCK_COUNT_MAX,
+                max_string_pool_size: PRESET2_STRING_POOL_SIZE_MAX,
+                max_decompressed_block_offset: PRESET0_DECOMPRESSED_BLOCK_OFFSET_MAX,
+                max_file_size: PRESET2_MAX_FILE_SIZE,
+                stores_hashes: true,
+            },
+            ToCFormat::Preset3 | ToCFormat::Preset3NoHash => Self {
+                max_file_count: PRESET3_FILE_COUNT_MAX,
+                max_block_count: PRESET3_BLOCK_COUNT_MAX,
+                max_string_pool_size: PRESET3_STRING_POOL_SIZE_MAX,
+                max_decompressed_block_offset: PRESET3_MAX_DECOMPRESSED_BLOCK_OFFSET,
+                max_file_size: PRESET3_MAX_FILE_SIZE as u64,
+                stores_hashes: matches!(format, ToCFormat::Preset3),
+            },
+            ToCFormat::Error => return None,
+        };
+
+        Some(limits)
+    }
+
+    /// Returns the limits of a legacy (V1) table of contents version,
+    /// as written by [`downgrade_archive`].
+    ///
+    /// [`downgrade_archive`]: crate::api::archive_conversion::downgrade_archive
+    pub const fn for_version(version: TableOfContentsVersion) -> Self {
+        match version {
+            TableOfContentsVersion::V0 | TableOfContentsVersion::V1 => Self {
+                max_file_count: MAX_FILE_COUNT_V0V1 as u32,
+                max_block_count: MAX_BLOCK_COUNT_V0V1 as u32,
+                max_string_pool_size: MAX_STRING_POOL_SIZE as u32,
+                max_decompressed_block_offset: (1 << 26) - 1,
+                max_file_size: match version {
+                    TableOfContentsVersion::V0 => u32::MAX as u64,
+                    _ => u64::MAX,
+                },
+                stores_hashes: true,
+            },
+            TableOfContentsVersion::V2 => Self {
+                max_file_count: (1 << 18) - 1,
+                max_block_count: (1 << 20) - 1,
+                max_string_pool_size: MAX_STRING_POOL_SIZE as u32,
+                max_decompressed_block_offset: (1 << 26) - 1,
+                max_file_size: u32::MAX as u64,
+                stores_hashes: false,
+            },
+            TableOfContentsVersion::V3 => Self {
+                max_file_count: u8::MAX as u32,
+                max_block_count: u8::MAX as u32,
+                max_string_pool_size: (1 << 29) - 1,
+                max_decompressed_block_offset: (1 << 20) - 1,
+                max_file_size: (1 << 28) - 1,
+                stores_hashes: true,
+            },
+        }
+    }
+
+    /// Returns true if an archive with the given counts and sizes is within these limits.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_count` - Number of files.
+    /// * `block_count` - Number of blocks.
+    /// * `string_pool_size` - Size of the compressed string pool.
+    /// * `max_file_size` - Size of the largest file.
+    pub const fn allows(
+        &self,
+        file_count: u32,
+        block_count: u32,
+        string_pool_size: u32,
+        max_file_size: u64,
+    ) -> bool {
+        file_count <= self.max_file_count
+            && block_count <= self.max_block_count
+            && string_pool_size <= self.max_string_pool_size
+            && max_file_size <= self.max_file_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_match_format_selection() {
+        let limits = FormatLimits::for_format(ToCFormat::Preset3).unwrap();
+        assert_eq!(limits.max_file_count, 65535);
+        assert!(limits.stores_hashes);
+        assert!(limits.allows(65535, 65535, 1024, 1024));
+        assert!(!limits.allows(65536, 1, 1024, 1024));
+        assert!(FormatLimits::for_format(ToCFormat::Error).is_none());
+
+        // An archive at the limits of a format is packed with that format.
+        let format = determine_optimal_toc_format(
+            limits.max_string_pool_size,
+            limits.max_decompressed_block_offset,
+            limits.max_block_count,
+            limits.max_file_count,
+            true,
+            limits.max_file_size,
+        );
+        assert_eq!(format, ToCFormat::Preset3);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::headers::managed::*;
+use crate::prelude::*;
+use core::ops::Range;
+
+/// Describes where the data of a single file is located within the blocks of an archive.
+///
+/// This lets advanced consumers (e.g. virtual filesystems, patchers) plan their own reads
+/// without knowing the details of how chunks are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileBlockMapping {
+    /// Index of the block containing the start of the file.
+    pub first_block_index: u32,
+
+    /// Offset of the file within the decompressed first block.
+    /// This is always `0` for chunked files.
+    pub decompressed_block_offset: u32,
+
+    /// Number of blocks the file spans. This is `1` for files stored in a single block.
+    pub block_count: u32,
+
+    /// Size of the file after decompression.
+    pub decompressed_size: u64,
+
+    /// Size of each chunk, for files spanning multiple blocks.
+    pub chunk_size: u32,
+}
+
+impl FileBlockMapping {
+    /// Creates the mapping for a given file entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The file entry to create the mapping for.
+    /// * `chunk_size` - Size of single chunk in archive.
+    pub fn from_entry(entry: &FileEntry, chunk_size: u32) -> Self {
+        Self {
+            first_block_index: entry.first_block_index,
+            decompressed_block_offset: entry.decompressed_block_offset,
+            block_count: entry.get_chunk_count(chunk_size).max(1),
+            decompressed_size: entry.decompressed_size,
+            chunk_size,
+        }
+    }
+
+    /// Returns the indices of all blocks that contain data for this file.
+    pub fn block_indices(&self) -> Range<u32> {
+        self.first_block_index..self.first_block_index + self.block_count
+    }
+
+    /// Returns true if the file is split across multiple blocks.
+    pub fn is_multi_block(&self) -> bool {
+        self.block_count > 1
+    }
+
+    /// Returns the range of the file's data within the decompressed contents of a given block.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_index` - Index of the block in the archive.
+    ///
+    /// # Returns
+    ///
+    /// The range within the decompressed block, or [`None`] if the block does not belong to this file.
+    pub fn range_in_block(&self, block_index: u32) -> Option<Range<u64>> {
+        if !self.block_indices().contains(&block_index) {
+            return None;
+        }
+
+        if !self.is_multi_block() {
+            let start = self.decompressed_block_offset as u64;
+            return Some(start..start + self.decompressed_size);
+        }
+
+        let chunk_start = (block_index - self.first_block_index) as u64 * self.chunk_size as u64;
+        let chunk_len = (self.decompressed_size - chunk_start).min(self.chunk_size as u64);
+        Some(0..chunk_len)
+    }
+}
+
+impl<ShortAlloc, LongAlloc> TableOfContents<ShortAlloc, LongAlloc>
+where
+    ShortAlloc: Allocator + Clone,
+    LongAlloc: Allocator + Clone,
+{
+    /// Returns the block mapping of the file entry at a given index.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry_index` - Index of the entry in [`Self::entries`].
+    /// * `chunk_size` - Size of single chunk in archive, from the header.
+    pub fn file_block_mapping(
+        &self,
+        entry_index: usize,
+        chunk_size: u32,
+    ) -> Option<FileBlockMapping> {
+        self.entries
+            .get(entry_index)
+            .map(|entry| FileBlockMapping::from_entry(entry, chunk_size))
+    }
+
+    /// Returns the indices of all entries which have data in a given block.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_index` - Index of the block in [`Self::blocks`].
+    /// * `chunk_size` - Size of single chunk in archive, from the header.
+    pub fn entries_in_block(
+        &self,
+        block_index: u32,
+        chunk_size: u32,
+    ) -> impl Iterator<Item = usize> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |(_, entry)| {
+                FileBlockMapping::from_entry(entry, chunk_size)
+                    .block_indices()
+                    .contains(&block_index)
+            })
+            .map(|(index, _)| index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn solid_file_maps_to_single_block() {
+        let entry = FileEntry::new(0, 256, 512, 0, 3);
+        let mapping = FileBlockMapping::from_entry(&entry, 1024);
+
+        assert_eq!(mapping.block_indices(), 3..4);
+        assert!(!mapping.is_multi_block());
+        assert_eq!(mapping.range_in_block(3), Some(512..768));
+        assert_eq!(mapping.range_in_block(4), None);
+    }
+
+    #[rstest]
+    #[case::first_chunk(2, Some(0..1024))]
+    #[case::middle_chunk(3, Some(0..1024))]
+    #[case::last_chunk(4, Some(0..452))]
+    #[case::outside(5, None)]
+    fn chunked_file_maps_to_each_chunk(
+        #[case] block_index: u32,
+        #[case] expected: Option<Range<u64>>,
+    ) {
+        let entry = FileEntry::new(0, 2500, 0, 0, 2);
+        let mapping = FileBlockMapping::from_entry(&entry, 1024);
+
+        assert_eq!(mapping.block_count, 3);
+        assert_eq!(mapping.range_in_block(block_index), expected);
+    }
+
+    #[test]
+    fn empty_file_maps_to_one_block() {
+        let entry = FileEntry::new(0, 0, 0, 0, 1);
+        let mapping = FileBlockMapping::from_entry(&entry, 1024);
+
+        assert_eq!(mapping.block_indices(), 1..2);
+        assert_eq!(mapping.range_in_block(1), Some(0..0));
+    }
+}
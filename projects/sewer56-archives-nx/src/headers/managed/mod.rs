@@ -3,6 +3,8 @@ pub mod v2;
 
 /// Represents the size of a compressed block following the header.
 pub mod block_size;
+/// Describes which blocks the data of a file is stored in.
+pub mod file_block_mapping;
 /// Represents a file entry that was decoded from the Table of Contents.
 pub mod file_entry;
 
@@ -11,5 +13,6 @@ pub mod table_of_contents;
 
 /// Prelude
 pub use block_size::*;
+pub use file_block_mapping::*;
 pub use file_entry::*;
 pub use table_of_contents::*;
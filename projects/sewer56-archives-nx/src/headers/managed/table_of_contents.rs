@@ -23,6 +23,10 @@ pub struct TableOfContents<
 
     /// String pool data.
     pub pool: StringPool<ShortAlloc, LongAlloc>,
+
+    /// Whether the entries contain file hashes.
+    /// If false, [`FileEntry::hash`] is always 0.
+    pub has_hashes: bool,
 }
 
 /// Errors that can occur when deserializing TableOfContents
@@ -36,6 +40,18 @@ pub enum DeserializeError {
     InsufficientData(#[from] InsufficientDataError),
 }
 
+/// Location of the compressed [`StringPool`] within a serialized table of contents.
+///
+/// Returned when deserializing the table of contents without unpacking the pool,
+/// so the pool can be unpacked later, when it is first needed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct StringPoolLocation {
+    /// Offset of the compressed pool from the start of the table of contents.
+    pub offset: u32,
+    /// Size of the compressed pool in bytes.
+    pub compressed_size: u32,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Error, new)]
 pub struct InsufficientDataError {
     /// Actual number of available bytes. (less than expected)
@@ -2,6 +2,8 @@
 pub mod file_entry_intrinsics;
 /// Allows for serialization of the Table of Contents during the packing operation.
 pub mod table_of_contents_builder;
+/// Bounds checked deserialization of the Table of Contents, for the `forbid-unsafe-fastpaths` feature.
+pub(crate) mod table_of_contents_checked_reader;
 /// Allows for deserialization of the Table of Contents during the unpacking operation.
 pub mod table_of_contents_reader;
 
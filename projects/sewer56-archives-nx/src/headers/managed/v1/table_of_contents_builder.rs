@@ -10,8 +10,8 @@ use crate::{
 use endian_writer::{EndianWriter, LittleEndianWriter};
 
 // Max values for V0 & V1 formats.
-const MAX_BLOCK_COUNT_V0V1: usize = 262143; // 2^18 - 1
-const MAX_FILE_COUNT_V0V1: usize = 1048575; // 2^20 - 1
+pub const MAX_BLOCK_COUNT_V0V1: usize = 262143; // 2^18 - 1
+pub const MAX_FILE_COUNT_V0V1: usize = 1048575; // 2^20 - 1
 
 /// Determines the required Table of Contents version based on the largest file size in the given blocks.
 ///
@@ -332,6 +332,8 @@ mod tests {
     #[case(TableOfContentsVersion::V0)]
     #[case(TableOfContentsVersion::V1)]
     fn can_serialize_and_deserialize(#[case] version: TableOfContentsVersion) {
+        use crate::headers::managed::v1::table_of_contents_checked_reader::deserialize_v1xx_checked;
+
         // Note: We're not testing the actual file/chunk creation logic, this data can be whatever.
         let files = [
             PackerFileForTesting::new_rc("dvdroot/textures/s01.txd", 113763968),
@@ -404,6 +406,19 @@ mod tests {
             // Deserialize
             let new_table = TableOfContents::deserialize_v1xx(data.as_ptr()).unwrap();
 
+            // The bounds checked reader produces the same table, and rejects truncated data.
+            let checked =
+                deserialize_v1xx_checked::<Global, Global>(&data, Global, Global).unwrap();
+            assert_eq!(checked.entries, new_table.entries);
+            assert_eq!(checked.blocks, new_table.blocks);
+            assert_eq!(checked.block_compressions, new_table.block_compressions);
+            assert!(deserialize_v1xx_checked::<Global, Global>(
+                &data[..data_size - 1],
+                Global,
+                Global
+            )
+            .is_err());
+
             // Compare deserialized data with original
             assert_eq!(new_table.entries.len(), entries.len());
             assert_eq!(new_table.blocks.len(), blocks.len());
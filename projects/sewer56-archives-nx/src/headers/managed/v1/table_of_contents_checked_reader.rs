@@ -0,0 +1,93 @@
+use super::calculate_table_size;
+use crate::prelude::*;
+use crate::{
+    headers::{enums::v1::*, managed::*, parser::*, raw::toc::*},
+    utilities::io::slice_reader::SliceReader,
+};
+
+/// Deserializes the table of contents [NX v1.x.x format] from a slice.
+///
+/// This is the bounds checked counterpart of
+/// [`TableOfContents::deserialize_v1xx_with_allocator`], used in its place when the
+/// `forbid-unsafe-fastpaths` feature is enabled. Every read is checked against the length of
+/// `data`; no raw pointers are used.
+///
+/// # Arguments
+///
+/// * `data` - The serialized ToC, and anything after it.
+/// * `short_alloc` - Allocator for short lived memory. Think pooled memory and rentals.
+/// * `long_alloc` - Allocator for longer lived memory. Think same lifetime as creating Nx archive creator/unpacker.
+///
+/// # Returns
+///
+/// Result containing the deserialized table of contents or a DeserializeError.
+pub(crate) fn deserialize_v1xx_checked<ShortAlloc, LongAlloc>(
+    data: &[u8],
+    short_alloc: ShortAlloc,
+    long_alloc: LongAlloc,
+) -> Result<TableOfContents<ShortAlloc, LongAlloc>, DeserializeError>
+where
+    ShortAlloc: Allocator + Clone,
+    LongAlloc: Allocator + Clone,
+{
+    let mut reader = SliceReader::new(data);
+    let toc_header = NativeTocHeader::from_raw(reader.read_u64()?);
+    let toc_version = match toc_header.get_version() {
+        Ok(x) => x,
+        Err(_) => return Err(DeserializeError::UnsupportedTocVersion),
+    };
+
+    let file_count = toc_header.file_count() as usize;
+    let block_count = toc_header.block_count() as usize;
+    let pool_size = toc_header.string_pool_size() as usize;
+
+    // Check the whole ToC is present before allocating, so a corrupt header can't
+    // request more memory than the data could possibly describe.
+    let required_size = calculate_table_size(file_count, block_count, pool_size, toc_version);
+    if data.len() < required_size {
+        return Err(InsufficientDataError::new(
+            data.len().min(u32::MAX as usize) as u32,
+            required_size.min(u32::MAX as usize) as u32,
+        )
+        .into());
+    }
+
+    let mut entries = Vec::with_capacity_in(file_count, long_alloc.clone());
+    for _ in 0..file_count {
+        let mut entry = FileEntry {
+            hash: reader.read_u64()?,
+            ..FileEntry::default()
+        };
+        entry.decompressed_size = match toc_version {
+            TableOfContentsVersion::V0 => reader.read_u32()? as u64,
+            TableOfContentsVersion::V1 => reader.read_u64()?,
+        };
+        OffsetPathIndexTuple::from_raw(reader.read_u64()?).copy_to(&mut entry);
+        entries.push(entry);
+    }
+
+    let mut blocks = Vec::with_capacity_in(block_count, long_alloc.clone());
+    let mut block_compressions = Vec::with_capacity_in(block_count, long_alloc.clone());
+    for _ in 0..block_count {
+        let block = NativeV1TocBlockEntry::from_raw(reader.read_u32()?);
+        blocks.push(BlockSize::new(block.compressed_block_size()));
+        block_compressions.push(block.compression());
+    }
+
+    let pool = StringPool::unpack_v0_with_allocators(
+        reader.read_bytes(pool_size)?,
+        file_count,
+        short_alloc,
+        long_alloc,
+        true,
+    )
+    .map_err(DeserializeError::StringPoolUnpackError)?;
+
+    Ok(TableOfContents {
+        block_compressions: block_compressions.into_boxed_slice(),
+        blocks: blocks.into_boxed_slice(),
+        entries: entries.into_boxed_slice(),
+        pool,
+        has_hashes: true,
+    })
+}
@@ -2,6 +2,7 @@ use crate::prelude::*;
 use crate::{
     api::enums::compression_preference::CompressionPreference,
     headers::{enums::v1::*, managed::*, parser::*, raw::toc::*},
+    utilities::memory_audit::default_slice_in,
 };
 use core::slice;
 use endian_writer::{EndianReader, LittleEndianReader};
@@ -61,14 +62,11 @@ where
 
         // Init the vec and resize it to the correct length.
         let mut entries: Box<[FileEntry], LongAlloc> =
-            Box::new_uninit_slice_in(toc_header.file_count() as usize, long_alloc.clone())
-                .assume_init();
+            default_slice_in(toc_header.file_count() as usize, long_alloc.clone());
         let mut blocks: Box<[BlockSize], LongAlloc> =
-            Box::new_uninit_slice_in(toc_header.block_count() as usize, long_alloc.clone())
-                .assume_init();
+            default_slice_in(toc_header.block_count() as usize, long_alloc.clone());
         let mut block_compressions: Box<[CompressionPreference], LongAlloc> =
-            Box::new_uninit_slice_in(toc_header.block_count() as usize, long_alloc.clone())
-                .assume_init();
+            default_slice_in(toc_header.block_count() as usize, long_alloc.clone());
 
         // Read all of the ToC entries.
         // Perf: Nothing gained here from unrolling.
@@ -103,6 +101,7 @@ where
             blocks,
             entries,
             pool,
+            has_hashes: true,
         })
     }
 }
@@ -4,6 +4,9 @@ pub mod table_of_contents_builder;
 /// Allows for deserialization of the Table of Contents during the unpacking operation.
 pub mod table_of_contents_reader;
 
+/// Bounds checked deserialization of the Table of Contents, for the `forbid-unsafe-fastpaths` feature.
+pub(crate) mod table_of_contents_checked_reader;
+
 /// Prelude
 pub use table_of_contents_builder::*;
 #[allow(unused_imports)]
@@ -1,7 +1,14 @@
 use crate::prelude::*;
 use crate::{
-    api::{enums::compression_preference::CompressionPreference, traits::*},
-    headers::{managed::*, parser::*, raw::toc::*},
+    api::{
+        enums::{compression_preference::CompressionPreference, FileHashStorage, PathCollation},
+        traits::*,
+    },
+    headers::{
+        managed::*,
+        parser::*,
+        raw::{native_file_header::NativeFileHeader, toc::*},
+    },
     implementation::pack::{
         blocks::polyfills::{Block, PtrEntry},
         table_of_contents_builder_state::TableOfContentsBuilderState,
@@ -39,7 +46,11 @@ pub struct BuilderInfo<LongAlloc: Allocator + Clone> {
 /// * `blocks` - A slice of [Box<dyn Block<T>>] representing the blocks in the archive.
 /// * `chunk_size` - The maximum size of a chunk in the file. From [PackingSettings].
 /// * `max_block_size` - The maximum size of a SOLID block. From [PackingSettings].
-/// * `need_hashes` - Whether to include hashes in the table of contents.
+/// * `file_hashes` - Whether to include hashes in the table of contents. From [PackingSettings].
+/// * `store_file_paths` - Whether to store the string pool. If false, an empty pool is written.
+/// * `string_pool_compression` - How to compress the string pool. From [PackingSettings].
+/// * `path_collation` - The order to sort the files (and thus entries) in. From [PackingSettings].
+///   Record it in the header with [`NativeFileHeader::set_feature_flags`].
 /// * `short_alloc` - An allocator for short lived memory. Think pooled memory and rentals.
 /// * `long_alloc` - An allocator for longer lived memory. Think same lifetime as creating Nx archive creator/unpacker.
 ///
@@ -52,6 +63,7 @@ pub struct BuilderInfo<LongAlloc: Allocator + Clone> {
 ///
 /// * `T`: Type of the items in the blocks, which must implement [HasFileSize],
 ///        [CanProvideInputData], and [HasRelativePath].
+#[allow(clippy::too_many_arguments)]
 pub fn init_toc_creation<
     T: HasFileSize + CanProvideInputData + HasRelativePath,
     ShortAlloc: Allocator + Clone,
@@ -60,7 +72,10 @@ pub fn init_toc_creation<
     blocks: &[Box<dyn Block<T>>],
     chunk_size: u32,
     mut max_decomp_block_size: u32,
-    need_hashes: bool,
+    file_hashes: FileHashStorage,
+    store_file_paths: bool,
+    string_pool_compression: StringPoolCompression,
+    path_collation: PathCollation,
     short_alloc: ShortAlloc,
     long_alloc: LongAlloc,
 ) -> Result<BuilderInfo<LongAlloc>, InitError> {
@@ -92,22 +107,44 @@ pub fn init_toc_creation<
     }
 
     // Generate string pool
-    let string_pool =
-        StringPool::pack_v0_with_allocators(&mut files, short_alloc, long_alloc, true)?;
+    let string_pool = if store_file_paths {
+        StringPool::pack_v0_with_compression(
+            &mut files,
+            short_alloc,
+            long_alloc,
+            string_pool_compression,
+            path_collation,
+        )?
+    } else {
+        Vec::new_in(long_alloc)
+    };
 
     // Determine table of contents format
     let max_block_ofs = max_decomp_block_size;
     let string_pool_len = string_pool.len() as u32;
     let block_count = blocks.len() as u32;
     let file_count = files.len() as u32;
-    let format = determine_optimal_toc_format(
-        string_pool_len,
-        max_block_ofs,
-        block_count,
-        file_count,
-        need_hashes,
-        largest_file_size,
-    );
+    let select_format = |hashes_required| {
+        determine_optimal_toc_format(
+            string_pool_len,
+            max_block_ofs,
+            block_count,
+            file_count,
+            hashes_required,
+            largest_file_size,
+        )
+    };
+
+    let mut format = select_format(file_hashes != FileHashStorage::Never);
+
+    // In 'Auto' mode, drop the hashes if they push the header past a single page.
+    if file_hashes == FileHashStorage::Auto
+        && NativeFileHeader::SIZE_BYTES as u32
+            + calculate_toc_size(format, string_pool_len, block_count, file_count)
+            > NativeFileHeader::HEADER_PAGE_SIZE
+    {
+        format = select_format(false);
+    }
 
     // Return error if the format is invalid.
     if format == ToCFormat::Error {
@@ -174,10 +211,11 @@ pub(crate) unsafe fn serialize_table_of_contents_from_state<LongAlloc: Allocator
     )
 }
 
-/// Serializes the table of contents data into a binary format from a builder state.
+/// Serializes the table of contents data into a binary format at a raw pointer.
 ///
-/// This function takes the builder state of a table of contents and serializes it into a binary
-/// format at the specified memory location.
+/// This is the unchecked fast path of [serialize_table_of_contents_into], for callers which
+/// have already sized the output; prefer [serialize_table_of_contents_into] or
+/// [serialize_table_of_contents_to_vec] otherwise.
 ///
 /// # Safety
 ///
@@ -185,6 +223,7 @@ pub(crate) unsafe fn serialize_table_of_contents_from_state<LongAlloc: Allocator
 /// - `data_ptr` points to a memory region large enough to hold the serialized data.
 /// - The memory region pointed to by `data_ptr` is writable.
 /// - The lifetime of the pointed memory is at least as long as the execution of this function.
+/// - `block_compressions` has the same length as `blocks`.
 ///
 /// # Arguments
 ///
@@ -276,6 +315,111 @@ pub unsafe fn serialize_table_of_contents<LongAlloc: Allocator + Clone>(
     }
 }
 
+/// Serializes the table of contents data into a binary format, into the given buffer.
+///
+/// The size of the table is calculated from the inputs and checked against the buffer
+/// before anything is written, so unlike [serialize_table_of_contents], this is safe.
+///
+/// # Arguments
+///
+/// * `block_compressions` - A slice of `CompressionPreference` values for each block.
+/// * `blocks` - A slice of `BlockSize` structs representing the size of each block.
+/// * `entries` - A slice of `FileEntry` structs representing file entries in the table.
+/// * `info` - The builder information constructed by init-ing the serialize operation with [init_toc_creation].
+/// * `destination` - The buffer to write to. Must be at least [calculate_toc_size] bytes long.
+///
+/// # Returns
+///
+/// The number of bytes written.
+///
+/// # Errors
+///
+/// Returns [SerializeError::BufferTooSmall] if `destination` can't fit the table, and
+/// [SerializeError::BlockCountMismatch] if the lengths of `block_compressions` and `blocks` differ.
+pub fn serialize_table_of_contents_into<LongAlloc: Allocator + Clone>(
+    block_compressions: &[CompressionPreference],
+    blocks: &[BlockSize],
+    entries: &[FileEntry],
+    info: &BuilderInfo<LongAlloc>,
+    destination: &mut [u8],
+) -> Result<usize, SerializeError> {
+    let required = checked_toc_size(block_compressions, blocks, entries, info)?;
+    if destination.len() < required {
+        return Err(SerializeError::BufferTooSmall {
+            required,
+            available: destination.len(),
+        });
+    }
+
+    unsafe {
+        serialize_table_of_contents(
+            block_compressions,
+            blocks,
+            entries,
+            info,
+            destination.as_mut_ptr(),
+        )
+    }
+}
+
+/// Serializes the table of contents data into a binary format, returning a new buffer
+/// of exactly the serialized size.
+///
+/// # Arguments
+///
+/// * `block_compressions` - A slice of `CompressionPreference` values for each block.
+/// * `blocks` - A slice of `BlockSize` structs representing the size of each block.
+/// * `entries` - A slice of `FileEntry` structs representing file entries in the table.
+/// * `info` - The builder information constructed by init-ing the serialize operation with [init_toc_creation].
+///
+/// # Errors
+///
+/// See [serialize_table_of_contents_into].
+pub fn serialize_table_of_contents_to_vec<LongAlloc: Allocator + Clone>(
+    block_compressions: &[CompressionPreference],
+    blocks: &[BlockSize],
+    entries: &[FileEntry],
+    info: &BuilderInfo<LongAlloc>,
+) -> Result<Vec<u8>, SerializeError> {
+    let mut result = vec![0u8; checked_toc_size(block_compressions, blocks, entries, info)?];
+    let written =
+        serialize_table_of_contents_into(block_compressions, blocks, entries, info, &mut result)?;
+    result.truncate(written);
+    Ok(result)
+}
+
+/// Calculates the size of the serialized table, validating the inputs which would otherwise
+/// cause [serialize_table_of_contents] to write out of bounds.
+fn checked_toc_size<LongAlloc: Allocator + Clone>(
+    block_compressions: &[CompressionPreference],
+    blocks: &[BlockSize],
+    entries: &[FileEntry],
+    info: &BuilderInfo<LongAlloc>,
+) -> Result<usize, SerializeError> {
+    if block_compressions.len() != blocks.len() {
+        return Err(SerializeError::BlockCountMismatch);
+    }
+
+    if info.format == ToCFormat::Error {
+        return Err(SerializeError::UnsupportedTocFormat);
+    }
+
+    // The size is calculated in 32 bits; larger tables would overflow it.
+    // The largest entry is 16 bytes, and the largest block entry 4 bytes.
+    let upper_bound =
+        16 + 16 * entries.len() as u64 + 4 * blocks.len() as u64 + info.string_pool.len() as u64;
+    if upper_bound > u32::MAX as u64 {
+        return Err(SerializeError::TooLarge);
+    }
+
+    Ok(calculate_toc_size(
+        info.format,
+        info.string_pool.len() as u32,
+        blocks.len() as u32,
+        entries.len() as u32,
+    ) as usize)
+}
+
 unsafe fn serialize_table_of_contents_fef64<LongAlloc: Allocator + Clone>(
     block_compressions: &[CompressionPreference],
     blocks: &[BlockSize],
@@ -426,6 +570,17 @@ unsafe fn serialize_table_of_contents_preset<LongAlloc: Allocator + Clone>(
 pub enum SerializeError {
     /// The format of the table of contents used is not supported.
     UnsupportedTocFormat,
+    /// The buffer is too small to hold the serialized table of contents.
+    BufferTooSmall {
+        /// Number of bytes needed.
+        required: usize,
+        /// Number of bytes in the buffer.
+        available: usize,
+    },
+    /// The number of block compressions does not match the number of blocks.
+    BlockCountMismatch,
+    /// The table of contents would exceed 4GiB.
+    TooLarge,
 }
 
 /// Errors that can occur when initializing the creation of Table of Contents.
@@ -656,6 +811,8 @@ mod tests {
             let has_hash = format != ToCFormat::FEF64NoHash
                 && format != ToCFormat::Preset1NoHash
                 && format != ToCFormat::Preset3NoHash;
+            assert_eq!(format.has_hashes(), has_hash);
+            assert_eq!(new_table.has_hashes, has_hash);
 
             let has_block_offset =
                 format != ToCFormat::Preset3NoHash && format != ToCFormat::Preset3;
@@ -682,6 +839,92 @@ mod tests {
         }
     }
 
+    #[rstest]
+    #[case::preset0(ToCFormat::Preset0)]
+    #[case::preset1_no_hash(ToCFormat::Preset1NoHash)]
+    #[case::preset2(ToCFormat::Preset2)]
+    #[case::preset3(ToCFormat::Preset3)]
+    #[case::preset3_no_hash(ToCFormat::Preset3NoHash)]
+    #[case::fef64(ToCFormat::FEF64)]
+    #[case::fef64_no_hash(ToCFormat::FEF64NoHash)]
+    fn checked_deserialization_matches_unsafe(#[case] format: ToCFormat) {
+        use crate::headers::managed::v2::table_of_contents_checked_reader::deserialize_v2xx_checked;
+
+        let (data, _, builder_info) = serialize_test_data(format);
+        let (checked, checked_location) =
+            deserialize_v2xx_checked::<Global, Global>(&data, Global).unwrap();
+        let (fast, fast_location) = unsafe {
+            TableOfContents::<Global, Global>::deserialize_v2xx_without_pool_with_allocator(
+                data.as_ptr(),
+                builder_info.table_size,
+                Global,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(checked.entries, fast.entries);
+        assert_eq!(checked.blocks, fast.blocks);
+        assert_eq!(checked.block_compressions, fast.block_compressions);
+        assert_eq!(checked.has_hashes, fast.has_hashes);
+        assert_eq!(checked_location, fast_location);
+
+        // Truncated data is rejected rather than read past.
+        assert!(
+            deserialize_v2xx_checked::<Global, Global>(&data[..data.len() - 1], Global).is_err()
+        );
+    }
+
+    #[rstest]
+    #[case::preset0(ToCFormat::Preset0)]
+    #[case::preset3(ToCFormat::Preset3)]
+    #[case::fef64(ToCFormat::FEF64)]
+    fn safe_serialization_matches_unsafe(#[case] format: ToCFormat) {
+        let (data, toc_builder, builder_info) = serialize_test_data(format);
+        let serialized = serialize_table_of_contents_to_vec(
+            &toc_builder.block_compressions,
+            &toc_builder.blocks,
+            &toc_builder.entries,
+            &builder_info,
+        )
+        .unwrap();
+
+        assert_eq!(serialized.as_slice(), data.as_slice());
+    }
+
+    #[test]
+    fn safe_serialization_rejects_small_buffer() {
+        let (toc_builder, builder_info) = create_test_data(ToCFormat::FEF64);
+        let mut data = vec![0u8; builder_info.table_size as usize - 1];
+        let result = serialize_table_of_contents_into(
+            &toc_builder.block_compressions,
+            &toc_builder.blocks,
+            &toc_builder.entries,
+            &builder_info,
+            &mut data,
+        );
+
+        assert_eq!(
+            result,
+            Err(SerializeError::BufferTooSmall {
+                required: builder_info.table_size as usize,
+                available: builder_info.table_size as usize - 1,
+            })
+        );
+    }
+
+    #[test]
+    fn safe_serialization_rejects_mismatched_blocks() {
+        let (toc_builder, builder_info) = create_test_data(ToCFormat::FEF64);
+        let result = serialize_table_of_contents_to_vec(
+            &toc_builder.block_compressions[1..],
+            &toc_builder.blocks,
+            &toc_builder.entries,
+            &builder_info,
+        );
+
+        assert_eq!(result, Err(SerializeError::BlockCountMismatch));
+    }
+
     #[cfg(feature = "hardened")]
     #[rstest]
     fn insufficient_data_for_header_returns_error() {
@@ -0,0 +1,173 @@
+use crate::prelude::*;
+use crate::{
+    headers::{managed::*, parser::*, raw::toc::*},
+    utilities::io::slice_reader::SliceReader,
+};
+
+use super::calculate_toc_size;
+
+/// Deserializes the table of contents [NX v2.x.x format] from a slice, leaving the string pool empty.
+///
+/// This is the bounds checked counterpart of
+/// [`TableOfContents::deserialize_v2xx_without_pool_with_allocator`], used in its place when the
+/// `forbid-unsafe-fastpaths` feature is enabled. Every read is checked against the length of
+/// `data`; no raw pointers are used.
+///
+/// # Arguments
+///
+/// * `data` - The serialized ToC, and anything after it.
+/// * `long_alloc` - Allocator for longer lived memory. Think same lifetime as creating Nx archive creator/unpacker.
+///
+/// # Returns
+///
+/// The table of contents (with an empty [`TableOfContents::pool`]) and the location of the
+/// compressed pool relative to the start of `data`, or a [`DeserializeError`].
+pub(crate) fn deserialize_v2xx_checked<ShortAlloc, LongAlloc>(
+    data: &[u8],
+    long_alloc: LongAlloc,
+) -> Result<(TableOfContents<ShortAlloc, LongAlloc>, StringPoolLocation), DeserializeError>
+where
+    ShortAlloc: Allocator + Clone,
+    LongAlloc: Allocator + Clone,
+{
+    let mut reader = SliceReader::new(data);
+
+    // The first bit in all V2 header formats is the FEF flag.
+    let toc_header = Preset3TocHeader::from_raw(reader.read_u64()?);
+    let (format, fields, pool_size, block_count, file_count) =
+        if toc_header.get_is_flexible_format() {
+            let toc_header = Fef64TocHeader::from_raw(toc_header.0);
+            let counts_raw_bytes = if toc_header.has_extended_header() {
+                toc_header.padding_or_item_counts()
+            } else {
+                reader.read_u64()?
+            };
+            let (pool_size, block_count, file_count) = unpack_item_counts(
+                counts_raw_bytes,
+                toc_header.string_pool_size_bits(),
+                toc_header.block_count_bits(),
+                toc_header.file_count_bits(),
+            );
+
+            let format = match toc_header.has_hash() {
+                true => ToCFormat::FEF64,
+                false => ToCFormat::FEF64NoHash,
+            };
+            let fields: FileEntryFieldsBits = toc_header.into();
+            (
+                format,
+                Some(fields),
+                pool_size as u32,
+                block_count as u32,
+                file_count as u32,
+            )
+        } else if toc_header.get_preset() == 3 {
+            let format = match toc_header.has_hash() {
+                true => ToCFormat::Preset3,
+                false => ToCFormat::Preset3NoHash,
+            };
+            (
+                format,
+                None,
+                toc_header.string_pool_size(),
+                toc_header.block_count() as u32,
+                toc_header.file_count() as u32,
+            )
+        } else {
+            let format = match toc_header.get_preset() {
+                0 => ToCFormat::Preset0,
+                1 => ToCFormat::Preset1NoHash,
+                _ => ToCFormat::Preset2,
+            };
+            let toc_header = Preset0TocHeader::from_raw(toc_header.0);
+            (
+                format,
+                None,
+                toc_header.string_pool_size(),
+                toc_header.block_count(),
+                toc_header.file_count(),
+            )
+        };
+
+    // Check the whole ToC is present before allocating, so a corrupt header can't
+    // request more memory than the data could possibly describe.
+    let available = data.len().min(u32::MAX as usize) as u32;
+    let required_size = calculate_toc_size(format, pool_size, block_count, file_count);
+    if available < required_size {
+        return Err(InsufficientDataError::new(available, required_size).into());
+    }
+
+    let mut entries = Vec::with_capacity_in(file_count as usize, long_alloc.clone());
+    for _ in 0..file_count {
+        entries.push(read_entry(&mut reader, format, fields)?);
+    }
+
+    let mut blocks = Vec::with_capacity_in(block_count as usize, long_alloc.clone());
+    let mut block_compressions = Vec::with_capacity_in(block_count as usize, long_alloc.clone());
+    for _ in 0..block_count {
+        let block = NativeV2TocBlockEntry::from_raw(reader.read_u32()?);
+        blocks.push(BlockSize::new(block.compressed_block_size()));
+        block_compressions.push(block.compression());
+    }
+
+    // The pool follows; it is unpacked by the caller, if needed.
+    let location = StringPoolLocation {
+        offset: reader.position() as u32,
+        compressed_size: pool_size,
+    };
+    let toc = TableOfContents {
+        block_compressions: block_compressions.into_boxed_slice(),
+        blocks: blocks.into_boxed_slice(),
+        entries: entries.into_boxed_slice(),
+        pool: StringPool::empty_in(&long_alloc),
+        has_hashes: format.has_hashes(),
+    };
+    Ok((toc, location))
+}
+
+/// Reads a single file entry in the given format.
+///
+/// `fields` holds the field sizes of the FEF64 formats, and is [`None`] for presets.
+fn read_entry(
+    reader: &mut SliceReader,
+    format: ToCFormat,
+    fields: Option<FileEntryFieldsBits>,
+) -> Result<FileEntry, InsufficientDataError> {
+    let mut entry = FileEntry::default();
+    match (format, fields) {
+        (ToCFormat::Preset0, _) => {
+            entry.hash = reader.read_u64()?;
+            entry.decompressed_size = reader.read_u32()? as u64;
+            CommonOffsetPathIndexTuple::from_raw(reader.read_u64()?).copy_to(&mut entry);
+        }
+        (ToCFormat::Preset1NoHash, _) => {
+            entry.decompressed_size = reader.read_u32()? as u64;
+            CommonOffsetPathIndexTuple::from_raw(reader.read_u64()?).copy_to(&mut entry);
+        }
+        (ToCFormat::Preset2, _) => {
+            entry.hash = reader.read_u64()?;
+            entry.decompressed_size = reader.read_u64()?;
+            CommonOffsetPathIndexTuple::from_raw(reader.read_u64()?).copy_to(&mut entry);
+        }
+        (ToCFormat::Preset3 | ToCFormat::Preset3NoHash, _) => {
+            if format == ToCFormat::Preset3 {
+                entry.hash = reader.read_u64()?;
+            }
+            entry.decompressed_size = reader.read_u32()? as u64;
+            entry.file_path_index = reader.read_u16()? as u32;
+            entry.first_block_index = reader.read_u16()? as u32;
+        }
+        (ToCFormat::FEF64, Some(fields)) => {
+            let hash = reader.read_u64()?;
+            let data = reader.read_u64()?;
+            entry = FileEntry16::from_raw(hash, data).to_file_entry(fields);
+        }
+        (ToCFormat::FEF64NoHash, Some(fields)) => {
+            entry = FileEntry8::from_raw(reader.read_u64()?).to_file_entry(fields);
+        }
+        // The format is decoded from the header above, so this can't happen.
+        _ => {}
+    }
+
+    Ok(entry)
+}
@@ -3,9 +3,14 @@ use crate::headers::managed::v2::*;
 use crate::prelude::*;
 use crate::{
     api::enums::compression_preference::CompressionPreference,
-    headers::{managed::*, parser::*, raw::toc::*},
+    headers::{
+        managed::{v2::table_of_contents_checked_reader::deserialize_v2xx_checked, *},
+        parser::*,
+        raw::toc::*,
+    },
+    utilities::memory_audit::default_slice_in,
 };
-use core::{hint::unreachable_unchecked, slice};
+use core::{hint::unreachable_unchecked, ops::ControlFlow, slice};
 use endian_writer::{EndianReader, EndianReaderExt, LittleEndianReader};
 
 impl TableOfContents {
@@ -29,6 +34,176 @@ impl TableOfContents {
     ) -> Result<Self, DeserializeError> {
         Self::deserialize_v2xx_with_allocator(data_ptr, avail_bytes, Global, Global)
     }
+
+    /// Decodes the file entries of a table of contents [NX v2.x.x format] in batches,
+    /// passing each batch to a visitor, without allocating the full entry array.
+    ///
+    /// Use this to list archives whose table of contents is too large to hold in memory;
+    /// blocks and the string pool are not read.
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because it works with raw pointers.
+    ///
+    /// # Arguments
+    ///
+    /// * `data_ptr` - Pointer to the ToC.
+    /// * `avail_bytes` - Available number of bytes that can be read from data_ptr.
+    /// * `batch_size` - Maximum number of entries passed to the visitor at once. At least 1.
+    /// * `visitor` - Called with each batch of entries, in order.
+    ///   Return [`ControlFlow::Break`] to stop early.
+    ///
+    /// # Returns
+    ///
+    /// Result containing the number of file entries in the table of contents,
+    /// or a [`DeserializeError`].
+    pub unsafe fn visit_v2xx_entries(
+        data_ptr: *const u8,
+        #[allow(unused_variables)] avail_bytes: u32, // used when hardening
+        batch_size: usize,
+        mut visitor: impl FnMut(&[FileEntry]) -> ControlFlow<()>,
+    ) -> Result<u32, DeserializeError> {
+        #[cfg(feature = "hardened")]
+        if avail_bytes < 8 {
+            return Err(InsufficientDataError::new(avail_bytes, 8).into());
+        }
+
+        let mut reader = LittleEndianReader::new(data_ptr);
+        let toc_header = Preset3TocHeader::from_raw(reader.read_u64());
+        let (format, file_count) = if toc_header.get_is_flexible_format() {
+            let toc_header = Fef64TocHeader::from_raw(toc_header.0);
+            #[cfg(feature = "hardened")]
+            if avail_bytes < 16 {
+                return Err(InsufficientDataError::new(avail_bytes, 16).into());
+            }
+
+            let counts_raw_bytes = if toc_header.has_extended_header() {
+                toc_header.padding_or_item_counts()
+            } else {
+                reader.read_u64()
+            };
+            #[allow(unused_variables)] // used when hardening
+            let (pool_size, block_count, file_count) = unpack_item_counts(
+                counts_raw_bytes,
+                toc_header.string_pool_size_bits(),
+                toc_header.block_count_bits(),
+                toc_header.file_count_bits(),
+            );
+
+            #[cfg(feature = "hardened")]
+            {
+                let format = match toc_header.has_hash() {
+                    true => ToCFormat::FEF64,
+                    false => ToCFormat::FEF64NoHash,
+                };
+                let required_size = calculate_toc_size(
+                    format,
+                    pool_size as u32,
+                    block_count as u32,
+                    file_count as u32,
+                );
+                if avail_bytes < required_size {
+                    return Err(InsufficientDataError::new(avail_bytes, required_size).into());
+                }
+            }
+
+            let format = EntryFormat::Fef64(toc_header.into(), toc_header.has_hash());
+            (format, file_count as u32)
+        } else {
+            // Preset3 shares the layout of the other presets' headers up to the hash flag.
+            let preset = toc_header.get_preset();
+            #[allow(unused_variables)] // used when hardening
+            let (pool_size, block_count, file_count, has_hash) = if preset == 3 {
+                (
+                    toc_header.string_pool_size(),
+                    toc_header.block_count() as u32,
+                    toc_header.file_count() as u32,
+                    toc_header.has_hash(),
+                )
+            } else {
+                let toc_header = Preset0TocHeader::from_raw(toc_header.0);
+                (
+                    toc_header.string_pool_size(),
+                    toc_header.block_count(),
+                    toc_header.file_count(),
+                    true,
+                )
+            };
+
+            #[cfg(feature = "hardened")]
+            {
+                let format = get_preset_toc_format(preset, has_hash);
+                let required_size = calculate_toc_size(format, pool_size, block_count, file_count);
+                if avail_bytes < required_size {
+                    return Err(InsufficientDataError::new(avail_bytes, required_size).into());
+                }
+            }
+
+            (EntryFormat::Preset(preset, has_hash), file_count)
+        };
+
+        let batch_size = batch_size.max(1);
+        let mut batch = vec![FileEntry::default(); batch_size.min(file_count as usize)];
+        let mut remaining = file_count as usize;
+        while remaining > 0 {
+            let entries = &mut batch[..remaining.min(batch_size)];
+            read_entries_batch(&mut reader, format, entries);
+            remaining -= entries.len();
+            if visitor(entries).is_break() {
+                break;
+            }
+        }
+
+        Ok(file_count)
+    }
+}
+
+/// Layout of the file entries in a table of contents, see [`TableOfContents::visit_v2xx_entries`].
+#[derive(Clone, Copy)]
+enum EntryFormat {
+    /// Preset number, and whether entries have a hash.
+    Preset(u8, bool),
+
+    /// Field sizes, and whether entries have a hash.
+    Fef64(FileEntryFieldsBits, bool),
+}
+
+/// Reads as many file entries as fit in `entries`.
+///
+/// # Safety
+///
+/// The reader must have at least `entries.len()` entries of the given format left.
+unsafe fn read_entries_batch(
+    reader: &mut LittleEndianReader,
+    format: EntryFormat,
+    entries: &mut [FileEntry],
+) {
+    match format {
+        EntryFormat::Preset(0, _) => {
+            reader.read_entries_into_unroll_2::<FileEntry, NativeFileEntryP0>(entries)
+        }
+        EntryFormat::Preset(1, _) => {
+            reader.read_entries_into_unroll_2::<FileEntry, NativeFileEntryP1>(entries)
+        }
+        EntryFormat::Preset(2, _) => {
+            reader.read_entries_into_unroll_2::<FileEntry, NativeFileEntryP2>(entries)
+        }
+        EntryFormat::Preset(_, true) => {
+            reader.read_entries_into_unroll_2::<FileEntry, NativeFileEntryP3>(entries)
+        }
+        EntryFormat::Preset(_, false) => {
+            reader.read_entries_into_unroll_2::<FileEntry, NativeFileEntryP3NoHash>(entries)
+        }
+        EntryFormat::Fef64(fields, true) => {
+            read_file_entries_with_hash(entries.len() as u64, reader, entries.as_mut_ptr(), fields)
+        }
+        EntryFormat::Fef64(fields, false) => read_file_entries_without_hash(
+            entries.len() as u64,
+            reader,
+            entries.as_mut_ptr(),
+            fields,
+        ),
+    }
 }
 
 impl<ShortAlloc, LongAlloc> TableOfContents<ShortAlloc, LongAlloc>
@@ -58,6 +233,57 @@ where
         short_alloc: ShortAlloc,
         long_alloc: LongAlloc,
     ) -> Result<Self, DeserializeError> {
+        let (mut toc, location) = Self::deserialize_v2xx_without_pool_with_allocator(
+            data_ptr,
+            avail_bytes,
+            long_alloc.clone(),
+        )?;
+
+        toc.pool = StringPool::unpack_v0_with_allocators(
+            slice::from_raw_parts(
+                data_ptr.add(location.offset as usize),
+                location.compressed_size as usize,
+            ),
+            toc.entries.len(),
+            short_alloc,
+            long_alloc,
+            true,
+        )?;
+        Ok(toc)
+    }
+
+    /// Deserializes the table of contents [NX v2.x.x format], leaving the string pool empty.
+    ///
+    /// Use this when file paths may not be needed (e.g. lookups by hash); the pool can then be
+    /// unpacked on demand with [`StringPool::unpack_v0_with_allocators`] using the returned location.
+    ///
+    /// With the `forbid-unsafe-fastpaths` feature, the ToC is instead parsed from a slice of
+    /// `avail_bytes` bytes, with every read bounds checked.
+    ///
+    /// # Safety
+    ///
+    /// This function is unsafe because it works with raw pointers.
+    ///
+    /// # Arguments
+    ///
+    /// * `data_ptr` - Pointer to the ToC.
+    /// * `avail_bytes` - Available number of bytes that can be read from data_ptr.
+    /// * `long_alloc` - Allocator for longer lived memory. Think same lifetime as creating Nx archive creator/unpacker.
+    ///
+    /// # Returns
+    ///
+    /// The table of contents (with an empty [`Self::pool`]) and the location of the compressed pool
+    /// relative to `data_ptr`, or a [`DeserializeError`].
+    pub unsafe fn deserialize_v2xx_without_pool_with_allocator(
+        data_ptr: *const u8,
+        avail_bytes: u32,
+        long_alloc: LongAlloc,
+    ) -> Result<(Self, StringPoolLocation), DeserializeError> {
+        if cfg!(feature = "forbid-unsafe-fastpaths") {
+            let data = slice::from_raw_parts(data_ptr, avail_bytes as usize);
+            return deserialize_v2xx_checked(data, long_alloc);
+        }
+
         // Validate we have enough bytes for the header
         #[cfg(feature = "hardened")]
         if avail_bytes < 8 {
@@ -69,51 +295,50 @@ where
         // The first bit in all V2 header formats is the FEF flag.
         let toc_header = Preset3TocHeader::from_raw(reader.read_u64());
 
-        if toc_header.get_is_flexible_format() {
+        let (toc, pool_size) = if toc_header.get_is_flexible_format() {
             let toc_header = Fef64TocHeader::from_raw(toc_header.0);
-            return deserialize_v2xx_fef64_entries(
-                &mut reader,
-                avail_bytes,
-                toc_header,
-                short_alloc,
-                long_alloc,
-            );
-        }
-
-        // The next two bits are the preset (in all presets), and in Preset3 specifically,
-        // the next bit is the hash flag. Therefore we can use Preset3 header to
-        // read all of the necessary information here.
-        let preset = toc_header.get_preset();
-        if preset == 0 || preset == 1 || preset == 2 {
-            let toc_header = Preset0TocHeader::from_raw(toc_header.0);
-            deserialize_v2xx_preset_entries(
-                &mut reader,
-                avail_bytes,
-                toc_header.string_pool_size(),
-                toc_header.block_count(),
-                toc_header.file_count(),
-                preset,
-                true,
-                short_alloc,
-                long_alloc,
-            )
-        } else if preset == 3 {
-            let toc_header = Preset3TocHeader::from_raw(toc_header.0);
-            deserialize_v2xx_preset_entries(
-                &mut reader,
-                avail_bytes,
-                toc_header.string_pool_size(),
-                toc_header.block_count() as u32,
-                toc_header.file_count() as u32,
-                preset,
-                toc_header.has_hash(),
-                short_alloc,
-                long_alloc,
-            )
+            deserialize_v2xx_fef64_entries(&mut reader, avail_bytes, toc_header, long_alloc)?
         } else {
-            // Unreachable by definition, since the preset_no is restricted to 2 bits.
-            unreachable_unchecked()
-        }
+            // The next two bits are the preset (in all presets), and in Preset3 specifically,
+            // the next bit is the hash flag. Therefore we can use Preset3 header to
+            // read all of the necessary information here.
+            let preset = toc_header.get_preset();
+            if preset == 0 || preset == 1 || preset == 2 {
+                let toc_header = Preset0TocHeader::from_raw(toc_header.0);
+                deserialize_v2xx_preset_entries(
+                    &mut reader,
+                    avail_bytes,
+                    toc_header.string_pool_size(),
+                    toc_header.block_count(),
+                    toc_header.file_count(),
+                    preset,
+                    true,
+                    long_alloc,
+                )?
+            } else if preset == 3 {
+                let toc_header = Preset3TocHeader::from_raw(toc_header.0);
+                deserialize_v2xx_preset_entries(
+                    &mut reader,
+                    avail_bytes,
+                    toc_header.string_pool_size(),
+                    toc_header.block_count() as u32,
+                    toc_header.file_count() as u32,
+                    preset,
+                    toc_header.has_hash(),
+                    long_alloc,
+                )?
+            } else {
+                // Unreachable by definition, since the preset_no is restricted to 2 bits.
+                unreachable_unchecked()
+            }
+        };
+
+        // The pool is the last item in the ToC, so the reader is now positioned at it.
+        let location = StringPoolLocation {
+            offset: reader.ptr.offset_from(data_ptr) as u32,
+            compressed_size: pool_size,
+        };
+        Ok((toc, location))
     }
 }
 
@@ -134,12 +359,12 @@ where
 /// * `preset` - Preset number.
 /// * `has_hash` - Whether the preset variant of table of contents has a hash.
 ///                [Applies only to variants where hash is optional]
-/// * `short_alloc` - Allocator for short lived memory. Think pooled memory and rentals.
 /// * `long_alloc` - Allocator for longer lived memory. Think same lifetime as creating Nx archive creator/unpacker.
 ///
 /// # Returns
 ///
-/// Result containing the deserialized table of contents or a [`DeserializeError`].
+/// Result containing the deserialized table of contents (with an empty pool) and the size
+/// of the compressed pool, or a [`DeserializeError`].
 #[allow(clippy::too_many_arguments)]
 unsafe fn deserialize_v2xx_preset_entries<ShortAlloc, LongAlloc>(
     reader: &mut LittleEndianReader,
@@ -149,9 +374,8 @@ unsafe fn deserialize_v2xx_preset_entries<ShortAlloc, LongAlloc>(
     file_count: u32,
     preset: u8,
     has_hash: bool,
-    short_alloc: ShortAlloc,
     long_alloc: LongAlloc,
-) -> Result<TableOfContents<ShortAlloc, LongAlloc>, DeserializeError>
+) -> Result<(TableOfContents<ShortAlloc, LongAlloc>, u32), DeserializeError>
 where
     ShortAlloc: Allocator + Clone,
     LongAlloc: Allocator + Clone,
@@ -168,7 +392,7 @@ where
 
     // Read the entries.
     let mut entries: Box<[FileEntry], LongAlloc> =
-        Box::new_uninit_slice_in(file_count as usize, long_alloc.clone()).assume_init();
+        default_slice_in(file_count as usize, long_alloc.clone());
 
     if preset == 0 {
         reader.read_entries_into_unroll_2::<FileEntry, NativeFileEntryP0>(&mut entries);
@@ -184,14 +408,15 @@ where
         }
     }
 
+    // Preset1 never has hashes, Preset3 has an optional hash, the rest always have hashes.
+    let has_hashes = preset != 1 && has_hash;
     read_stuff_after_entries_and_return_toc(
         reader,
         entries,
         block_count,
         pool_size,
-        file_count,
+        has_hashes,
         long_alloc,
-        short_alloc,
     )
 }
 
@@ -207,19 +432,18 @@ where
 /// * `reader` - Allows for reading table of contents. Should be seeked just past the 8 byte header.
 /// * `avail_bytes` - Number of available bytes that can be read by the reader.
 /// * `toc_header` - 8 byte table of contents header for flexible format.
-/// * `short_alloc` - Allocator for short lived memory. Think pooled memory and rentals.
 /// * `long_alloc` - Allocator for longer lived memory. Think same lifetime as creating Nx archive creator/unpacker.
 ///
 /// # Returns
 ///
-/// Result containing the deserialized table of contents or a [`DeserializeError`].
+/// Result containing the deserialized table of contents (with an empty pool) and the size
+/// of the compressed pool, or a [`DeserializeError`].
 unsafe fn deserialize_v2xx_fef64_entries<ShortAlloc, LongAlloc>(
     reader: &mut LittleEndianReader,
     #[allow(unused_variables)] avail_bytes: u32, // used when hardening
     toc_header: Fef64TocHeader,
-    short_alloc: ShortAlloc,
     long_alloc: LongAlloc,
-) -> Result<TableOfContents<ShortAlloc, LongAlloc>, DeserializeError>
+) -> Result<(TableOfContents<ShortAlloc, LongAlloc>, u32), DeserializeError>
 where
     ShortAlloc: Allocator + Clone,
     LongAlloc: Allocator + Clone,
@@ -268,7 +492,7 @@ where
 
     // Read the entries.
     let mut entries: Box<[FileEntry], LongAlloc> =
-        Box::new_uninit_slice_in(file_count as usize, long_alloc.clone()).assume_init();
+        default_slice_in(file_count as usize, long_alloc.clone());
 
     let file_entry_ptr = entries.as_mut_ptr();
     if toc_header.has_hash() {
@@ -282,9 +506,8 @@ where
         entries,
         block_count as u32,
         pool_size as u32,
-        file_count as u32,
+        toc_header.has_hash(),
         long_alloc,
-        short_alloc,
     )
 }
 
@@ -321,36 +544,29 @@ unsafe fn read_stuff_after_entries_and_return_toc<ShortAlloc, LongAlloc>(
     entries: Box<[FileEntry], LongAlloc>,
     block_count: u32,
     pool_size: u32,
-    file_count: u32,
+    has_hashes: bool,
     long_alloc: LongAlloc,
-    short_alloc: ShortAlloc,
-) -> Result<TableOfContents<ShortAlloc, LongAlloc>, DeserializeError>
+) -> Result<(TableOfContents<ShortAlloc, LongAlloc>, u32), DeserializeError>
 where
     ShortAlloc: Allocator + Clone,
     LongAlloc: Allocator + Clone,
 {
     // Read blocks after files
     let mut block_compressions: Box<[CompressionPreference], LongAlloc> =
-        Box::new_uninit_slice_in(block_count as usize, long_alloc.clone()).assume_init();
+        default_slice_in(block_count as usize, long_alloc.clone());
     let mut blocks: Box<[BlockSize], LongAlloc> =
-        Box::new_uninit_slice_in(block_count as usize, long_alloc.clone()).assume_init();
+        default_slice_in(block_count as usize, long_alloc.clone());
     read_blocks_unrolled(&mut blocks, &mut block_compressions, reader);
 
-    // Read the pool and return.
-    let pool = StringPool::unpack_v0_with_allocators(
-        slice::from_raw_parts(reader.ptr, pool_size as usize),
-        file_count as usize,
-        short_alloc.clone(),
-        long_alloc.clone(),
-        true,
-    )?;
-
-    Ok(TableOfContents {
+    // The pool follows; it is unpacked by the caller, if needed.
+    let toc = TableOfContents {
         block_compressions,
         blocks,
         entries,
-        pool,
-    })
+        pool: StringPool::empty_in(&long_alloc),
+        has_hashes,
+    };
+    Ok((toc, pool_size))
 }
 
 /// Helper function to read blocks in an unrolled manner for performance.
@@ -0,0 +1,153 @@
+use super::header_extensions::{HeaderExtensionError, HeaderExtensionKind};
+use crate::api::enums::DataFilter;
+use crate::prelude::*;
+use crate::utilities::io::slice_reader::SliceReader;
+
+/// A filter applied to the data of a block before it was compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockFilter {
+    /// The filter applied to the data.
+    pub filter: DataFilter,
+
+    /// Size of the filtered data. Byte transposing depends on the length of the data,
+    /// so the whole of it must be decompressed before the filter can be reversed.
+    pub size: u32,
+}
+
+/// Filters applied to the data of each block before it was compressed, see [`DataFilter`].
+/// Blocks without a filter are not listed.
+///
+/// Stored as the [`HeaderExtensionKind::BlockFilters`] header extension.
+///
+/// # Format
+///
+/// - `u32` number of filtered blocks.
+/// - For each block, ordered by block index: `u32` index of the block, `u8` filter
+///   and `u32` size of the filtered data.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BlockFilters {
+    /// Index and filter of each filtered block, sorted by block index.
+    blocks: Vec<(u32, BlockFilter)>,
+}
+
+/// Size of a single block in the serialized form.
+const BLOCK_SIZE: usize = 9;
+
+impl BlockFilters {
+    /// Creates an empty set of block filters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the filter of a block, replacing any previous one.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_index` - Index of the block.
+    /// * `filter` - Filter applied to the block's data.
+    pub fn insert(&mut self, block_index: u32, filter: BlockFilter) {
+        match self
+            .blocks
+            .binary_search_by_key(&block_index, |(block, _)| *block)
+        {
+            Ok(index) => self.blocks[index].1 = filter,
+            Err(index) => self.blocks.insert(index, (block_index, filter)),
+        }
+    }
+
+    /// Returns the filter applied to a block, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_index` - Index of the block.
+    pub fn get(&self, block_index: u32) -> Option<BlockFilter> {
+        self.blocks
+            .binary_search_by_key(&block_index, |(block, _)| *block)
+            .ok()
+            .map(|index| self.blocks[index].1)
+    }
+
+    /// Returns true if no block is filtered.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Parses the block filters.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Contents of the [`HeaderExtensionKind::BlockFilters`] header extension.
+    pub fn parse(data: &[u8]) -> Result<Self, HeaderExtensionError> {
+        let malformed = HeaderExtensionError::Malformed(HeaderExtensionKind::BlockFilters);
+        let mut reader = SliceReader::new(data);
+        let count = reader.read_u32().map_err(|_| malformed)? as usize;
+
+        // Check the count first, so a corrupt one can't over allocate.
+        if reader.remaining() != count.checked_mul(BLOCK_SIZE).ok_or(malformed)? {
+            return Err(malformed);
+        }
+
+        let mut blocks = Vec::with_capacity(count);
+        for _ in 0..count {
+            let block = reader.read_u32().map_err(|_| malformed)?;
+            let [filter] = reader.read_array::<1>().map_err(|_| malformed)?;
+            let filter = DataFilter::from_id(filter).ok_or(malformed)?;
+            let size = reader.read_u32().map_err(|_| malformed)?;
+            blocks.push((block, BlockFilter { filter, size }));
+        }
+
+        if !blocks.windows(2).all(|x| x[0].0 < x[1].0) {
+            return Err(malformed);
+        }
+
+        Ok(Self { blocks })
+    }
+
+    /// Serializes the block filters, for storing as a header extension.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(4 + self.blocks.len() * BLOCK_SIZE);
+        result.extend_from_slice(&(self.blocks.len() as u32).to_le_bytes());
+        for (block, filter) in &self.blocks {
+            result.extend_from_slice(&block.to_le_bytes());
+            result.push(filter.filter as u8);
+            result.extend_from_slice(&filter.size.to_le_bytes());
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let transpose = BlockFilter {
+            filter: DataFilter::ByteTranspose4,
+            size: 4000,
+        };
+        let delta = BlockFilter {
+            filter: DataFilter::Delta8,
+            size: 80,
+        };
+        let mut filters = BlockFilters::new();
+        filters.insert(7, delta);
+        filters.insert(2, transpose);
+        assert_eq!(filters.get(2), Some(transpose));
+        assert_eq!(filters.get(3), None);
+
+        let serialized = filters.serialize();
+        assert_eq!(BlockFilters::parse(&serialized).unwrap(), filters);
+
+        let malformed = HeaderExtensionError::Malformed(HeaderExtensionKind::BlockFilters);
+        assert_eq!(
+            BlockFilters::parse(&serialized[..serialized.len() - 1]),
+            Err(malformed)
+        );
+
+        let mut unknown_filter = serialized.clone();
+        unknown_filter[8] = 0xFF;
+        assert_eq!(BlockFilters::parse(&unknown_filter), Err(malformed));
+    }
+}
@@ -0,0 +1,157 @@
+use super::header_extensions::{HeaderExtensionError, HeaderExtensionKind};
+use crate::prelude::*;
+use crate::utilities::io::slice_reader::SliceReader;
+
+/// XXH3 hashes of each chunk of the chunked files in an archive.
+///
+/// With these, a downloader resuming a large file (or a verifier checking one) can validate
+/// each chunk on its own, rather than hashing the whole decompressed file. The hashes use the
+/// same seed as the file hashes, see [`NxArchiveReader::hash_data`].
+///
+/// Files are identified by the index of their first block, which stays valid when
+/// entries are reordered, and is shared by files deduplicated against each other.
+///
+/// Stored as the [`HeaderExtensionKind::ChunkHashes`] header extension.
+///
+/// # Format
+///
+/// - `u32` number of files.
+/// - For each file, ordered by first block index: `u32` index of the first block,
+///   `u32` number of chunks, then the `u64` hash of each chunk.
+///
+/// [`NxArchiveReader::hash_data`]: crate::api::archive_reader::NxArchiveReader::hash_data
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ChunkHashes {
+    /// First block index and chunk hashes of each file, sorted by first block index.
+    files: Vec<(u32, Vec<u64>)>,
+}
+
+impl ChunkHashes {
+    /// Creates an empty set of chunk hashes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the chunk hashes of a file, replacing any previous ones for the same blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_block_index` - Index of the block holding the first chunk of the file.
+    /// * `hashes` - Hash of each chunk, in order.
+    pub fn insert(&mut self, first_block_index: u32, hashes: Vec<u64>) {
+        match self
+            .files
+            .binary_search_by_key(&first_block_index, |(block, _)| *block)
+        {
+            Ok(index) => self.files[index].1 = hashes,
+            Err(index) => self.files.insert(index, (first_block_index, hashes)),
+        }
+    }
+
+    /// Returns the chunk hashes of the file starting at a given block.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_block_index` - Index of the block holding the first chunk of the file,
+    ///   i.e. [`FileEntry::first_block_index`].
+    ///
+    /// [`FileEntry::first_block_index`]: crate::headers::managed::FileEntry::first_block_index
+    pub fn get(&self, first_block_index: u32) -> Option<&[u64]> {
+        self.files
+            .binary_search_by_key(&first_block_index, |(block, _)| *block)
+            .ok()
+            .map(|index| self.files[index].1.as_slice())
+    }
+
+    /// Returns the first block index and chunk hashes of each file, ordered by block index.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &[u64])> + '_ {
+        self.files
+            .iter()
+            .map(|(block, hashes)| (*block, hashes.as_slice()))
+    }
+
+    /// Returns the number of files with chunk hashes.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns true if no file has chunk hashes.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Parses the chunk hashes.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Contents of the [`HeaderExtensionKind::ChunkHashes`] header extension.
+    pub fn parse(data: &[u8]) -> Result<Self, HeaderExtensionError> {
+        let malformed = HeaderExtensionError::Malformed(HeaderExtensionKind::ChunkHashes);
+        let mut reader = SliceReader::new(data);
+        let count = reader.read_u32().map_err(|_| malformed)? as usize;
+
+        // Each file takes at least 8 bytes, so a corrupt count can't over allocate.
+        let mut files = Vec::with_capacity(count.min(reader.remaining() / 8));
+        for _ in 0..count {
+            let block = reader.read_u32().map_err(|_| malformed)?;
+            let num_chunks = reader.read_u32().map_err(|_| malformed)? as usize;
+            let data = reader
+                .read_bytes(num_chunks.checked_mul(8).ok_or(malformed)?)
+                .map_err(|_| malformed)?;
+            let hashes = data
+                .chunks_exact(8)
+                .map(|x| u64::from_le_bytes(x.try_into().unwrap()))
+                .collect();
+            files.push((block, hashes));
+        }
+
+        if !files.windows(2).all(|x| x[0].0 < x[1].0) {
+            return Err(malformed);
+        }
+
+        Ok(Self { files })
+    }
+
+    /// Serializes the chunk hashes, for storing as a header extension.
+    pub fn serialize(&self) -> Vec<u8> {
+        let size = 4 + self
+            .files
+            .iter()
+            .map(|(_, hashes)| 8 + hashes.len() * 8)
+            .sum::<usize>();
+        let mut result = Vec::with_capacity(size);
+        result.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+        for (block, hashes) in &self.files {
+            result.extend_from_slice(&block.to_le_bytes());
+            result.extend_from_slice(&(hashes.len() as u32).to_le_bytes());
+            for hash in hashes {
+                result.extend_from_slice(&hash.to_le_bytes());
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let mut hashes = ChunkHashes::new();
+        hashes.insert(7, vec![3, 4]);
+        hashes.insert(2, vec![1, 2, 5]);
+        assert_eq!(hashes.get(2), Some([1, 2, 5].as_slice()));
+        assert_eq!(hashes.get(3), None);
+
+        let serialized = hashes.serialize();
+        assert_eq!(ChunkHashes::parse(&serialized).unwrap(), hashes);
+
+        let malformed = HeaderExtensionError::Malformed(HeaderExtensionKind::ChunkHashes);
+        assert_eq!(
+            ChunkHashes::parse(&serialized[..serialized.len() - 1]),
+            Err(malformed)
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use super::header_extensions::{HeaderExtensionError, HeaderExtensionKind};
+use crate::prelude::*;
+use crate::utilities::io::slice_reader::SliceReader;
+
+/// Chunk sizes of the chunked files which were split with a chunk size other than
+/// the one in the archive header, e.g. a single huge file using larger chunks.
+///
+/// Files are identified by the index of their first block, as in [`ChunkHashes`].
+/// Files which are not listed use the chunk size from the header.
+///
+/// Stored as the [`HeaderExtensionKind::ChunkSizes`] header extension.
+///
+/// # Format
+///
+/// - `u32` number of files.
+/// - For each file, ordered by first block index: `u32` index of the first block
+///   and `u32` chunk size, a power of 2.
+///
+/// [`ChunkHashes`]: super::ChunkHashes
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ChunkSizes {
+    /// First block index and chunk size of each file, sorted by first block index.
+    files: Vec<(u32, u32)>,
+}
+
+/// Size of a single file in the serialized form.
+const FILE_SIZE: usize = 8;
+
+impl ChunkSizes {
+    /// Creates an empty set of chunk sizes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the chunk size of a file, replacing any previous one.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_block_index` - Index of the block holding the first chunk of the file.
+    /// * `chunk_size` - Size of the file's chunks. Must be a power of 2.
+    pub fn insert(&mut self, first_block_index: u32, chunk_size: u32) {
+        debug_assert!(chunk_size.is_power_of_two());
+        match self
+            .files
+            .binary_search_by_key(&first_block_index, |(block, _)| *block)
+        {
+            Ok(index) => self.files[index].1 = chunk_size,
+            Err(index) => self.files.insert(index, (first_block_index, chunk_size)),
+        }
+    }
+
+    /// Returns the chunk size of the file starting at a given block, if it has its own.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_block_index` - Index of the block holding the first chunk of the file,
+    ///   i.e. [`FileEntry::first_block_index`].
+    ///
+    /// [`FileEntry::first_block_index`]: crate::headers::managed::FileEntry::first_block_index
+    pub fn get(&self, first_block_index: u32) -> Option<u32> {
+        self.files
+            .binary_search_by_key(&first_block_index, |(block, _)| *block)
+            .ok()
+            .map(|index| self.files[index].1)
+    }
+
+    /// Returns the first block index and chunk size of each file, ordered by block index.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.files.iter().copied()
+    }
+
+    /// Returns true if every file uses the chunk size from the header.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Parses the chunk sizes.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Contents of the [`HeaderExtensionKind::ChunkSizes`] header extension.
+    pub fn parse(data: &[u8]) -> Result<Self, HeaderExtensionError> {
+        let malformed = HeaderExtensionError::Malformed(HeaderExtensionKind::ChunkSizes);
+        let mut reader = SliceReader::new(data);
+        let count = reader.read_u32().map_err(|_| malformed)? as usize;
+
+        // Check the count first, so a corrupt one can't over allocate.
+        if reader.remaining() != count.checked_mul(FILE_SIZE).ok_or(malformed)? {
+            return Err(malformed);
+        }
+
+        let mut files = Vec::with_capacity(count);
+        for _ in 0..count {
+            let block = reader.read_u32().map_err(|_| malformed)?;
+            let chunk_size = reader.read_u32().map_err(|_| malformed)?;
+            if !chunk_size.is_power_of_two() {
+                return Err(malformed);
+            }
+            files.push((block, chunk_size));
+        }
+
+        if !files.windows(2).all(|x| x[0].0 < x[1].0) {
+            return Err(malformed);
+        }
+
+        Ok(Self { files })
+    }
+
+    /// Serializes the chunk sizes, for storing as a header extension.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(4 + self.files.len() * FILE_SIZE);
+        result.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+        for (block, chunk_size) in &self.files {
+            result.extend_from_slice(&block.to_le_bytes());
+            result.extend_from_slice(&chunk_size.to_le_bytes());
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        let mut sizes = ChunkSizes::new();
+        sizes.insert(7, 1 << 20);
+        sizes.insert(2, 1 << 24);
+        assert_eq!(sizes.get(2), Some(1 << 24));
+        assert_eq!(sizes.get(3), None);
+
+        let serialized = sizes.serialize();
+        assert_eq!(ChunkSizes::parse(&serialized).unwrap(), sizes);
+
+        let malformed = HeaderExtensionError::Malformed(HeaderExtensionKind::ChunkSizes);
+        assert_eq!(
+            ChunkSizes::parse(&serialized[..serialized.len() - 1]),
+            Err(malformed)
+        );
+
+        let mut zero_size = serialized.clone();
+        zero_size[8..12].copy_from_slice(&0_u32.to_le_bytes());
+        assert_eq!(ChunkSizes::parse(&zero_size), Err(malformed));
+    }
+}
@@ -1,6 +1,7 @@
 use super::{dictionary_builder::*, dictionary_reader::*};
 use crate::api::traits::*;
 use crate::prelude::*;
+use crate::utilities::memory_audit::InternalAlloc;
 
 /// Serializes compressed dictionary payload.
 ///
@@ -25,7 +26,7 @@ where
     serialize_dictionary_payload_with_allocator(
         dictionaries,
         blocks,
-        Global,
+        InternalAlloc::default(),
         write_hashes,
         compress,
     )
@@ -49,7 +50,7 @@ where
 pub unsafe fn deserialize_dictionary_data(
     dictionary_data: &[u8],
 ) -> Result<DictionaryData, DictionaryReadError> {
-    deserialize_dictionary_data_with_allocator(dictionary_data, Global)
+    deserialize_dictionary_data_with_allocator(dictionary_data, InternalAlloc::default())
 }
 
 /// Deserializes the dictionary data from its binary format using a specified allocator.
@@ -78,8 +79,13 @@ where
     parse_payload_with_allocator(&extracted)
 }
 
-/// Test & bench only method.
-#[allow(dead_code)]
+/// Serializes the dictionary header and payload, as accepted by [`deserialize_dictionary_data`].
+///
+/// # Arguments
+/// * `dictionaries` - Raw dictionary data for each dictionary
+/// * `blocks` - The blocks in the exact order they will be compressed in the archive.
+/// * `write_hashes` - Whether to write the dictionary hashes
+/// * `compress` - Whether to compress the data
 pub(crate) fn serialize_dictionary_data<THasDictIndex>(
     dictionaries: &[&[u8]],
     blocks: &[THasDictIndex],
@@ -1,7 +1,10 @@
 use crate::{
     headers::{managed::InsufficientDataError, parser::*, types::xxh3sum::XXH3sum},
     implementation::pack::blocks::polyfills::NO_DICTIONARY_INDEX,
-    utilities::compression::*,
+    utilities::{
+        compression::*,
+        memory_audit::{default_slice, InternalAlloc},
+    },
 };
 use crate::prelude::*;
 use core::{
@@ -42,7 +45,7 @@ pub enum DictionaryReadError {
     InvalidDictionaryBlockLengthData,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Default, Clone, Copy)]
 struct DictionaryRange {
     offset: u32,
     length: u32,
@@ -53,10 +56,10 @@ pub struct DictionaryData {
     raw_data: Box<[u8]>,
 
     /// Dictionary offset+length pairs
-    dict_ranges: Box<[DictionaryRange]>,
+    dict_ranges: Box<[DictionaryRange], InternalAlloc>,
 
     /// Block to dictionary mapping information
-    dict_indices_for_block: Box<[u8]>,
+    dict_indices_for_block: Box<[u8], InternalAlloc>,
 }
 
 impl DictionaryData {
@@ -84,6 +87,28 @@ impl DictionaryData {
         self.raw_data
             .get_unchecked(range.offset as usize..(range.offset + range.length) as usize)
     }
+
+    /// Returns the number of dictionaries.
+    pub fn num_dictionaries(&self) -> usize {
+        self.dict_ranges.len()
+    }
+
+    /// Gets the index of the dictionary used by the block at the specified index.
+    /// This returns [`None`] if no dictionary is used.
+    pub fn get_dictionary_index_for_block(&self, block_index: usize) -> Option<u8> {
+        self.dict_indices_for_block
+            .get(block_index)
+            .copied()
+            .filter(|x| *x != NO_DICTIONARY_INDEX && (*x as usize) < self.dict_ranges.len())
+    }
+
+    /// Gets the data of the dictionary at the specified index.
+    /// This returns [`None`] if the index is out of range.
+    pub fn get_dictionary(&self, dict_index: u8) -> Option<&[u8]> {
+        let range = self.dict_ranges.get(dict_index as usize)?;
+        self.raw_data
+            .get(range.offset as usize..(range.offset + range.length) as usize)
+    }
 }
 
 /// Reads the main dictionary header [`DictionariesHeader`] and extracts the inner compressed dictionary
@@ -149,9 +174,7 @@ pub unsafe fn extract_payload_with_allocator<ShortAlloc: Allocator + Clone>(
     let layout = Layout::from_size_align_unchecked(decompressed_size as usize, 8);
     #[allow(unused_mut)]
     let mut decompressed_data: Aligned8RawAlloc<ShortAlloc> =
-        RawAlloc::new_in(layout, short_alloc.clone())
-            .unwrap()
-            .into();
+        RawAlloc::new_in(layout, short_alloc.clone())?.into();
 
     if compressed_size == 0 {
         // Data is not compressed - copy it directly
@@ -233,8 +256,8 @@ pub unsafe fn parse_payload_with_allocator<ShortAlloc: Allocator + Clone>(
 
     // SAFETY: Max value of this field is `u22::MAX`, so by definition this can use max
     // 4MB of RAM
-    let mut dict_indices_for_block: Box<[u8]> =
-        Box::new_uninit_slice(header.last_dict_block_index() as usize).assume_init();
+    let mut dict_indices_for_block: Box<[u8], InternalAlloc> =
+        default_slice(header.last_dict_block_index() as usize);
 
     // Prepare to ingest the dictionary data
     let mut dict_index_reader = LittleEndianReader::new(reader.ptr);
@@ -291,8 +314,8 @@ pub unsafe fn parse_payload_with_allocator<ShortAlloc: Allocator + Clone>(
     // SAFETY: The decompressed payload is aligned to 8 bytes, therefore this is a valid way to align.
     dict_length_reader.align_power_of_two(4);
     let mut dict_sizes_reader = dict_length_reader;
-    let mut dict_ranges: Box<[DictionaryRange]> =
-        Box::new_uninit_slice(header.num_dictionaries() as usize).assume_init();
+    let mut dict_ranges: Box<[DictionaryRange], InternalAlloc> =
+        default_slice(header.num_dictionaries() as usize);
     let mut current_offset = 0;
 
     for x in 0..dict_ranges.len() {
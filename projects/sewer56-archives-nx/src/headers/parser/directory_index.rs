@@ -0,0 +1,231 @@
+use super::header_extensions::{HeaderExtensionError, HeaderExtensionKind};
+use crate::headers::types::xxh3sum::XXH3sum;
+use crate::prelude::*;
+use core::ops::Range;
+use hashbrown::HashMap;
+
+/// Size of the directory count at the start of the index.
+const COUNT_SIZE: usize = 4;
+
+/// Size of each directory in the index: path hash (u64), first path index (u32), path count (u32).
+const DIRECTORY_SIZE: usize = 16;
+
+/// Path count of a directory whose files are not contiguous.
+const SCATTERED: u32 = u32::MAX;
+
+/// Result of looking up a directory in a [`DirectoryIndex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirectoryLookup {
+    /// Range of path indices (see [`FileEntry::file_path_index`]) of all files within
+    /// the directory, including its subdirectories.
+    ///
+    /// [`FileEntry::file_path_index`]: crate::headers::managed::FileEntry::file_path_index
+    Range(Range<u32>),
+
+    /// The directory exists, but its files are not next to each other in the string pool;
+    /// the paths must be checked one by one.
+    Scattered,
+
+    /// The archive has no files in this directory.
+    Missing,
+}
+
+/// Maps each directory of an archive to the range of paths within it, by their index in
+/// the (sorted) string pool.
+///
+/// Because the paths are sorted, all files within a directory (including subdirectories)
+/// are next to each other. The index finds that range with a binary search, without
+/// unpacking the string pool, rather than checking every path.
+///
+/// Stored as the [`HeaderExtensionKind::DirectoryIndex`] header extension.
+///
+/// # Format
+///
+/// - `u32` number of directories.
+/// - For each directory, ordered by hash: `u64` XXH3 hash of the directory path
+///   (without trailing separator), `u32` index of the first path, `u32` number of paths.
+///
+/// # Remarks
+///
+/// Directories whose files are not contiguous (possible with some collations, e.g. when
+/// numbers with leading zeros sort between each other) are stored with a path count of
+/// `u32::MAX`, as are directories with colliding hashes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DirectoryIndex {
+    /// Hash of the directory path, and first path index and path count, sorted by hash.
+    directories: Vec<(u64, u32, u32)>,
+}
+
+impl DirectoryIndex {
+    /// Creates an index over the given paths.
+    ///
+    /// # Arguments
+    ///
+    /// * `sorted_paths` - Paths of the files in the archive, in string pool order.
+    pub fn new<'p>(sorted_paths: impl Iterator<Item = &'p str>) -> Self {
+        // Directory => (first path index, last path index, path count)
+        let mut ranges: HashMap<&str, (u32, u32, u32)> = HashMap::new();
+        for (index, path) in sorted_paths.enumerate() {
+            let index = index as u32;
+            let directories = path.match_indices('/').map(|(end, _)| &path[..end]);
+            for directory in directories {
+                ranges
+                    .entry(directory)
+                    .and_modify(|(_, last, count)| {
+                        *last = index;
+                        *count += 1;
+                    })
+                    .or_insert((index, index, 1));
+            }
+        }
+
+        let mut directories: Vec<(u64, u32, u32)> = ranges
+            .into_iter()
+            .map(
+                |(directory, (first, last, count))| match last - first + 1 == count {
+                    true => (hash_directory(directory), first, count),
+                    false => (hash_directory(directory), 0, SCATTERED),
+                },
+            )
+            .collect();
+        directories.sort_unstable();
+
+        // Directories with colliding hashes can't be told apart, so have them checked one by one.
+        directories.dedup_by(|current, previous| {
+            let collides = current.0 == previous.0;
+            if collides {
+                *previous = (previous.0, 0, SCATTERED);
+            }
+            collides
+        });
+
+        Self { directories }
+    }
+
+    /// Parses the index.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Contents of the [`HeaderExtensionKind::DirectoryIndex`] header extension.
+    pub fn parse(data: &[u8]) -> Result<Self, HeaderExtensionError> {
+        let malformed = HeaderExtensionError::Malformed(HeaderExtensionKind::DirectoryIndex);
+        let count = data
+            .get(..COUNT_SIZE)
+            .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]) as usize)
+            .ok_or(malformed)?;
+        let table = data
+            .get(COUNT_SIZE..)
+            .filter(|x| (x.len() / DIRECTORY_SIZE) >= count)
+            .ok_or(malformed)?;
+
+        let directories: Vec<(u64, u32, u32)> = table
+            .chunks_exact(DIRECTORY_SIZE)
+            .take(count)
+            .map(|x| {
+                let mut hash = [0u8; 8];
+                hash.copy_from_slice(&x[..8]);
+                (
+                    u64::from_le_bytes(hash),
+                    u32::from_le_bytes([x[8], x[9], x[10], x[11]]),
+                    u32::from_le_bytes([x[12], x[13], x[14], x[15]]),
+                )
+            })
+            .collect();
+
+        if !directories.windows(2).all(|x| x[0].0 < x[1].0) {
+            return Err(malformed);
+        }
+
+        Ok(Self { directories })
+    }
+
+    /// Serializes the index, for storing as a header extension.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(COUNT_SIZE + self.directories.len() * DIRECTORY_SIZE);
+        result.extend_from_slice(&(self.directories.len() as u32).to_le_bytes());
+        for (hash, first, count) in &self.directories {
+            result.extend_from_slice(&hash.to_le_bytes());
+            result.extend_from_slice(&first.to_le_bytes());
+            result.extend_from_slice(&count.to_le_bytes());
+        }
+
+        result
+    }
+
+    /// Finds the files within a directory, including its subdirectories.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - Path of the directory, e.g. `textures/ui`. A trailing separator is ignored.
+    pub fn find(&self, directory: &str) -> DirectoryLookup {
+        let hash = hash_directory(directory.trim_end_matches('/'));
+        let Ok(index) = self
+            .directories
+            .binary_search_by_key(&hash, |(hash, _, _)| *hash)
+        else {
+            return DirectoryLookup::Missing;
+        };
+
+        match self.directories[index] {
+            (_, _, SCATTERED) => DirectoryLookup::Scattered,
+            (_, first, count) => DirectoryLookup::Range(first..first.saturating_add(count)),
+        }
+    }
+
+    /// Returns the number of directories in the index.
+    pub fn len(&self) -> usize {
+        self.directories.len()
+    }
+
+    /// Returns true if the index contains no directories.
+    pub fn is_empty(&self) -> bool {
+        self.directories.is_empty()
+    }
+}
+
+fn hash_directory(directory: &str) -> u64 {
+    XXH3sum::create(directory.as_bytes()).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATHS: [&str; 6] = [
+        "a.txt",
+        "data/a.bin",
+        "data/maps/1.map",
+        "data/maps/2.map",
+        "data/z.bin",
+        "textures/ui/icon.dds",
+    ];
+
+    #[test]
+    fn finds_directory_ranges() {
+        let index = DirectoryIndex::new(PATHS.iter().copied());
+        assert_eq!(index.len(), 4);
+        assert_eq!(index.find("data"), DirectoryLookup::Range(1..5));
+        assert_eq!(index.find("data/maps/"), DirectoryLookup::Range(2..4));
+        assert_eq!(index.find("textures"), DirectoryLookup::Range(5..6));
+        assert_eq!(index.find("textures/ui"), DirectoryLookup::Range(5..6));
+        assert_eq!(index.find("missing"), DirectoryLookup::Missing);
+    }
+
+    #[test]
+    fn marks_directories_which_are_not_contiguous() {
+        let index = DirectoryIndex::new(["a/1.txt", "b.txt", "a/2.txt"].into_iter());
+        assert_eq!(index.find("a"), DirectoryLookup::Scattered);
+    }
+
+    #[test]
+    fn can_round_trip() {
+        let index = DirectoryIndex::new(PATHS.iter().copied());
+        assert_eq!(DirectoryIndex::parse(&index.serialize()).unwrap(), index);
+
+        let serialized = index.serialize();
+        assert_eq!(
+            DirectoryIndex::parse(&serialized[..serialized.len() - 1]).unwrap_err(),
+            HeaderExtensionError::Malformed(HeaderExtensionKind::DirectoryIndex)
+        );
+    }
+}
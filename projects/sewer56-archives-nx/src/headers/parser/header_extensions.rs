@@ -0,0 +1,183 @@
+use crate::prelude::*;
+use thiserror_no_std::Error;
+
+/// Size of the kind (u8) and length (u32) preceding each header extension segment.
+pub const SEGMENT_HEADER_SIZE: usize = 5;
+
+/// Identifies the contents of a header extension segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum HeaderExtensionKind {
+    /// Contents of small files, see [`InlineFiles`](super::InlineFiles).
+    InlineFiles = 1,
+
+    /// Bloom filter over the file paths, see [`PathFilter`](super::PathFilter).
+    PathFilter = 2,
+
+    /// Range of paths within each directory, see [`DirectoryIndex`](super::DirectoryIndex).
+    DirectoryIndex = 3,
+
+    /// Number of times the archive was edited (`u64`, little endian), see
+    /// [`NxArchiveReader::generation`](crate::api::archive_reader::NxArchiveReader::generation).
+    Generation = 4,
+
+    /// Seed of the XXH3 file hashes (`u64`, little endian), see
+    /// [`NxArchiveReader::hash_seed`](crate::api::archive_reader::NxArchiveReader::hash_seed).
+    HashSeed = 5,
+
+    /// Hashes of each chunk of the chunked files, see [`ChunkHashes`](super::ChunkHashes).
+    ChunkHashes = 6,
+
+    /// Filters applied to the data of blocks, see [`BlockFilters`](super::BlockFilters).
+    BlockFilters = 7,
+
+    /// Chunk sizes of files not using the header's chunk size, see [`ChunkSizes`](super::ChunkSizes).
+    ChunkSizes = 8,
+}
+
+/// Errors that can occur when parsing the header extensions of an archive.
+#[derive(Debug, Clone, Copy, Error, PartialEq, Eq)]
+pub enum HeaderExtensionError {
+    /// A segment extends past the end of the header.
+    #[error("Header extension segment of kind {kind} at offset {offset} is truncated")]
+    Truncated { kind: u8, offset: usize },
+
+    /// A segment's contents do not match the format of its kind.
+    #[error("Header extension segment of kind {0:?} is malformed")]
+    Malformed(HeaderExtensionKind),
+}
+
+/// Optional data stored after the string pool, within the header pages of the archive.
+/// Present if [`NativeFileHeader::EXTENSIONS_FLAG`] is set.
+///
+/// The extensions are a list of segments, each starting with its kind (u8) and
+/// length (u32, little endian). A kind of 0, such as the zero padding at the end of
+/// the header pages, ends the list. Segments of unknown kinds are skipped, so new kinds
+/// can be added without breaking older readers.
+///
+/// [`NativeFileHeader::EXTENSIONS_FLAG`]: crate::headers::raw::native_file_header::NativeFileHeader::EXTENSIONS_FLAG
+#[derive(Debug, Default, Clone)]
+pub struct HeaderExtensions<'a> {
+    /// Kind and contents of each segment, in the order they are stored.
+    segments: Vec<(u8, &'a [u8])>,
+
+    /// Number of bytes used by the segments.
+    size: usize,
+}
+
+impl<'a> HeaderExtensions<'a> {
+    /// Parses the header extensions.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The header pages, starting right after the string pool.
+    pub fn parse(data: &'a [u8]) -> Result<Self, HeaderExtensionError> {
+        let mut segments = Vec::new();
+        let mut offset = 0;
+        while let Some(&kind) = data.get(offset) {
+            if kind == 0 {
+                break;
+            }
+
+            let truncated = HeaderExtensionError::Truncated { kind, offset };
+            let length = data
+                .get(offset + 1..offset + SEGMENT_HEADER_SIZE)
+                .ok_or(truncated)?;
+            let length = u32::from_le_bytes([length[0], length[1], length[2], length[3]]) as usize;
+
+            let start = offset + SEGMENT_HEADER_SIZE;
+            let contents = start
+                .checked_add(length)
+                .and_then(|end| data.get(start..end))
+                .ok_or(truncated)?;
+            segments.push((kind, contents));
+            offset = start + length;
+        }
+
+        Ok(Self {
+            segments,
+            size: offset,
+        })
+    }
+
+    /// Returns the contents of the first segment of a given kind, if present.
+    pub fn get(&self, kind: HeaderExtensionKind) -> Option<&'a [u8]> {
+        self.segments
+            .iter()
+            .find(|(x, _)| *x == kind as u8)
+            .map(|(_, contents)| *contents)
+    }
+
+    /// Returns the number of bytes used by the segments, excluding the terminating 0.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+/// Serializes a list of header extension segments, see [`HeaderExtensions`].
+///
+/// # Arguments
+///
+/// * `segments` - Kind and contents of each segment.
+///
+/// # Returns
+///
+/// The serialized segments. The terminating 0 is not included; the header padding provides it.
+pub fn serialize_header_extensions(segments: &[(HeaderExtensionKind, &[u8])]) -> Vec<u8> {
+    let size = segments
+        .iter()
+        .map(|(_, contents)| SEGMENT_HEADER_SIZE + contents.len())
+        .sum();
+    let mut result = Vec::with_capacity(size);
+    for (kind, contents) in segments {
+        result.push(*kind as u8);
+        result.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        result.extend_from_slice(contents);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_round_trip() {
+        let mut data = serialize_header_extensions(&[(HeaderExtensionKind::InlineFiles, b"Nx")]);
+        let size = data.len();
+        data.extend_from_slice(&[0; 16]); // header padding
+
+        let extensions = HeaderExtensions::parse(&data).unwrap();
+        assert_eq!(
+            extensions.get(HeaderExtensionKind::InlineFiles),
+            Some(&b"Nx"[..])
+        );
+        assert_eq!(extensions.size(), size);
+    }
+
+    #[test]
+    fn skips_unknown_kinds() {
+        let mut data = vec![200, 3, 0, 0, 0, 1, 2, 3];
+        data.extend(serialize_header_extensions(&[(
+            HeaderExtensionKind::InlineFiles,
+            b"Nx",
+        )]));
+
+        let extensions = HeaderExtensions::parse(&data).unwrap();
+        assert_eq!(
+            extensions.get(HeaderExtensionKind::InlineFiles),
+            Some(&b"Nx"[..])
+        );
+        assert_eq!(extensions.size(), data.len());
+    }
+
+    #[test]
+    fn truncated_segment_returns_error() {
+        let data = [1, 10, 0, 0, 0, 1, 2];
+        assert_eq!(
+            HeaderExtensions::parse(&data).unwrap_err(),
+            HeaderExtensionError::Truncated { kind: 1, offset: 0 }
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use super::header_extensions::{HeaderExtensionError, HeaderExtensionKind};
+use crate::headers::managed::FileEntry;
+use crate::prelude::*;
+use core::ops::Range;
+
+/// Largest file which can be inlined into the header.
+pub const MAX_INLINE_FILE_SIZE: u32 = 4096;
+
+/// Size of the file count at the start of the inline file table.
+const COUNT_SIZE: usize = 4;
+
+/// Size of each file's location (block index, offset within block, length) in the table.
+const LOCATION_SIZE: usize = 12;
+
+/// Copies of small files (e.g. configuration stubs or manifests read at startup),
+/// stored in the header so they can be read without fetching and decompressing a block.
+///
+/// Stored as the [`HeaderExtensionKind::InlineFiles`] header extension.
+/// Each file is identified by the location of its data ([`FileEntry::first_block_index`]
+/// and [`FileEntry::decompressed_block_offset`]). Inlined files are still stored in their
+/// block as usual, so readers which don't know about inlined files can read them too.
+///
+/// # Format
+///
+/// - `u32` number of files.
+/// - For each file, ordered by location: `u32` block index, `u32` offset within the block, `u32` length.
+/// - The contents of each file, back to back, in the same order.
+#[derive(Debug, Default, Clone)]
+pub struct InlineFiles {
+    /// Block index, offset within block, and the range of [`Self::data`] holding the file.
+    locations: Vec<(u32, u32, Range<usize>)>,
+
+    /// Contents of all inlined files.
+    data: Vec<u8>,
+}
+
+impl InlineFiles {
+    /// Parses the table of inlined files.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Contents of the [`HeaderExtensionKind::InlineFiles`] header extension.
+    pub fn parse(data: &[u8]) -> Result<Self, HeaderExtensionError> {
+        let malformed = HeaderExtensionError::Malformed(HeaderExtensionKind::InlineFiles);
+        let count = data
+            .get(..COUNT_SIZE)
+            .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]) as usize)
+            .ok_or(malformed)?;
+
+        let contents_start = (count as u64 * LOCATION_SIZE as u64) + COUNT_SIZE as u64;
+        let table = data
+            .get(COUNT_SIZE..)
+            .filter(|_| contents_start <= data.len() as u64)
+            .ok_or(malformed)?;
+
+        let mut locations = Vec::with_capacity(count);
+        let mut data_offset = 0;
+        for location in table.chunks_exact(LOCATION_SIZE).take(count) {
+            let read = |x: usize| {
+                u32::from_le_bytes([
+                    location[x],
+                    location[x + 1],
+                    location[x + 2],
+                    location[x + 3],
+                ])
+            };
+            let length = read(8) as usize;
+            locations.push((read(0), read(4), data_offset..data_offset + length));
+            data_offset += length;
+        }
+
+        let contents = &data[contents_start as usize..];
+        if contents.len() < data_offset {
+            return Err(malformed);
+        }
+
+        let is_sorted = locations
+            .windows(2)
+            .all(|x| (x[0].0, x[0].1) < (x[1].0, x[1].1));
+        if !is_sorted {
+            return Err(malformed);
+        }
+
+        Ok(Self {
+            locations,
+            data: Vec::from(&contents[..data_offset]),
+        })
+    }
+
+    /// Serializes a table of inlined files.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - Entry of each file, with the final block index, and the file's contents.
+    ///   Files sharing a location (deduplicated files) are stored once.
+    pub fn serialize(files: &[(FileEntry, &[u8])]) -> Vec<u8> {
+        let mut order: Vec<&(FileEntry, &[u8])> = files.iter().collect();
+        order.sort_by_key(|(entry, _)| (entry.first_block_index, entry.decompressed_block_offset));
+        order.dedup_by_key(|(entry, _)| (entry.first_block_index, entry.decompressed_block_offset));
+
+        let contents_size: usize = order.iter().map(|(_, data)| data.len()).sum();
+        let mut result =
+            Vec::with_capacity(COUNT_SIZE + order.len() * LOCATION_SIZE + contents_size);
+        result.extend_from_slice(&(order.len() as u32).to_le_bytes());
+        for (entry, data) in &order {
+            result.extend_from_slice(&entry.first_block_index.to_le_bytes());
+            result.extend_from_slice(&entry.decompressed_block_offset.to_le_bytes());
+            result.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        }
+
+        for (_, data) in &order {
+            result.extend_from_slice(data);
+        }
+
+        result
+    }
+
+    /// Returns the contents of a file, if it was inlined.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - Entry of the file.
+    pub fn get(&self, entry: &FileEntry) -> Option<&[u8]> {
+        let key = (entry.first_block_index, entry.decompressed_block_offset);
+        let index = self
+            .locations
+            .binary_search_by_key(&key, |(block, offset, _)| (*block, *offset))
+            .ok()?;
+
+        let range = self.locations[index].2.clone();
+        // A differing size means this is a different (chunked) file starting at the same block.
+        (range.len() as u64 == entry.decompressed_size).then(|| &self.data[range])
+    }
+
+    /// Returns the number of inlined files.
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// Returns true if no files are inlined.
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_round_trip() {
+        let config = FileEntry::new(0, 6, 128, 0, 1);
+        let flags = FileEntry::new(0, 2, 0, 1, 1);
+        let duplicate = FileEntry::new(0, 2, 0, 2, 1);
+        let serialized = InlineFiles::serialize(&[
+            (config, &b"[main]"[..]),
+            (flags, &b"on"[..]),
+            (duplicate, &b"on"[..]),
+        ]);
+
+        let inline = InlineFiles::parse(&serialized).unwrap();
+        assert_eq!(inline.len(), 2);
+        assert_eq!(inline.get(&config), Some(&b"[main]"[..]));
+        assert_eq!(inline.get(&duplicate), Some(&b"on"[..]));
+        assert_eq!(inline.get(&FileEntry::new(0, 6, 0, 0, 0)), None);
+    }
+
+    #[test]
+    fn truncated_table_returns_error() {
+        let serialized = InlineFiles::serialize(&[(FileEntry::new(0, 6, 0, 0, 0), &b"[main]"[..])]);
+        let expected = HeaderExtensionError::Malformed(HeaderExtensionKind::InlineFiles);
+        for length in [2, 10, serialized.len() - 1] {
+            assert_eq!(
+                InlineFiles::parse(&serialized[..length]).unwrap_err(),
+                expected
+            );
+        }
+    }
+}
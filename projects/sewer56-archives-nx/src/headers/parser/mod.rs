@@ -4,6 +4,27 @@ pub mod string_pool;
 /// Logic belonging to multiple versions of the string pool.
 pub mod string_pool_common;
 
+/// Optional data stored after the string pool.
+pub mod header_extensions;
+
+/// Small files stored in the header.
+pub mod inline_files;
+
+/// Filter for quickly rejecting paths not in an archive.
+pub mod path_filter;
+
+/// Index of the paths within each directory.
+pub mod directory_index;
+
+/// Hashes of each chunk of large files.
+pub mod chunk_hashes;
+
+/// Filters applied to the data of blocks.
+pub mod block_filters;
+
+/// Chunk sizes of files split with their own chunk size.
+pub mod chunk_sizes;
+
 /// Logic for serializing dictionaries
 pub mod dictionary {
     pub mod dictionary_builder;
@@ -12,6 +33,13 @@ pub mod dictionary {
 }
 
 // Prelude
+pub use block_filters::*;
+pub use chunk_hashes::*;
+pub use chunk_sizes::*;
 pub use dictionary::{dictionary_builder::*, dictionary_builder_wrappers::*, dictionary_reader::*};
+pub use directory_index::*;
+pub use header_extensions::*;
+pub use inline_files::*;
+pub use path_filter::*;
 pub use string_pool::*;
 pub use string_pool_common::*;
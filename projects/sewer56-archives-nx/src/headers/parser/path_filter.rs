@@ -0,0 +1,150 @@
+use super::header_extensions::{HeaderExtensionError, HeaderExtensionKind};
+use crate::headers::types::xxh3sum::XXH3sum;
+use crate::prelude::*;
+
+/// Default number of filter bits per path, giving a false positive rate of roughly 1%.
+pub const DEFAULT_PATH_FILTER_BITS: u32 = 10;
+
+/// Largest number of filter bits per path.
+pub const MAX_PATH_FILTER_BITS: u32 = 32;
+
+/// Smallest size of the filter, in bytes, so that archives with few files still get a useful filter.
+const MIN_FILTER_BYTES: usize = 8;
+
+/// A bloom filter over the paths of an archive, answering 'is this file definitely not here?'
+/// without unpacking the string pool.
+///
+/// When many archives are layered (e.g. in a virtual filesystem), most lookups are for files
+/// an archive doesn't contain; the filter rejects nearly all of these for the cost of hashing
+/// the path.
+///
+/// Stored as the [`HeaderExtensionKind::PathFilter`] header extension.
+///
+/// # Format
+///
+/// - `u8` number of hashes per path.
+/// - The filter bits, up to the end of the segment.
+///
+/// Each path is hashed once with XXH3; the bit positions are derived from the low and high
+/// halves of the hash (double hashing).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PathFilter {
+    /// Number of bits set for each path.
+    hash_count: u8,
+
+    /// The filter bits.
+    bits: Vec<u8>,
+}
+
+impl PathFilter {
+    /// Creates a filter containing the given paths.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - Paths of the files in the archive, as stored in the string pool.
+    /// * `bits_per_path` - Size of the filter, per path. More bits mean fewer false positives.
+    ///   Clamped to 1 - [`MAX_PATH_FILTER_BITS`].
+    pub fn new<'p>(paths: impl ExactSizeIterator<Item = &'p str>, bits_per_path: u32) -> Self {
+        let bits_per_path = bits_per_path.clamp(1, MAX_PATH_FILTER_BITS);
+        let byte_count = (paths.len() as u64 * bits_per_path as u64).div_ceil(8) as usize;
+
+        // ln(2) hashes per bit of each path minimizes false positives.
+        let hash_count = ((bits_per_path * 693 + 500) / 1000).max(1) as u8;
+        let mut filter = Self {
+            hash_count,
+            bits: vec![0; byte_count.max(MIN_FILTER_BYTES)],
+        };
+
+        for path in paths {
+            for bit in filter.bit_positions(path) {
+                filter.bits[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+
+        filter
+    }
+
+    /// Parses a filter.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - Contents of the [`HeaderExtensionKind::PathFilter`] header extension.
+    pub fn parse(data: &[u8]) -> Result<Self, HeaderExtensionError> {
+        match data.split_first() {
+            Some((&hash_count, bits)) if hash_count > 0 && !bits.is_empty() => Ok(Self {
+                hash_count,
+                bits: Vec::from(bits),
+            }),
+            _ => Err(HeaderExtensionError::Malformed(
+                HeaderExtensionKind::PathFilter,
+            )),
+        }
+    }
+
+    /// Serializes the filter, for storing as a header extension.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(self.bits.len() + 1);
+        result.push(self.hash_count);
+        result.extend_from_slice(&self.bits);
+        result
+    }
+
+    /// Returns false if the path is definitely not in the archive.
+    /// True means the path is probably in the archive, and must be looked up to be sure.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path as stored in the string pool.
+    pub fn may_contain(&self, path: &str) -> bool {
+        self.bit_positions(path)
+            .all(|bit| self.bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    fn bit_positions(&self, path: &str) -> impl Iterator<Item = usize> {
+        let hash = XXH3sum::create(path.as_bytes()).0;
+        let bit_count = self.bits.len() as u64 * 8;
+        let first = hash & 0xFFFF_FFFF;
+        let step = (hash >> 32) | 1;
+        (0..self.hash_count as u64)
+            .map(move |x| (first.wrapping_add(x.wrapping_mul(step)) % bit_count) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::string::String;
+
+    fn paths(prefix: &str) -> Vec<String> {
+        (0..1000).map(|x| format!("{prefix}/file{x}.bin")).collect()
+    }
+
+    #[test]
+    fn contains_all_paths() {
+        let present = paths("data");
+        let filter = PathFilter::new(present.iter().map(|x| x.as_str()), 10);
+        assert!(present.iter().all(|x| filter.may_contain(x)));
+
+        let false_positives = paths("other")
+            .iter()
+            .filter(|x| filter.may_contain(x))
+            .count();
+        assert!(false_positives < 30, "{false_positives} false positives");
+    }
+
+    #[test]
+    fn can_round_trip() {
+        let present = paths("data");
+        let filter = PathFilter::new(present.iter().map(|x| x.as_str()), 8);
+        assert_eq!(PathFilter::parse(&filter.serialize()).unwrap(), filter);
+    }
+
+    #[test]
+    fn empty_filter_returns_error() {
+        let expected = HeaderExtensionError::Malformed(HeaderExtensionKind::PathFilter);
+        assert_eq!(PathFilter::parse(&[]).unwrap_err(), expected);
+        assert_eq!(PathFilter::parse(&[7]).unwrap_err(), expected);
+        assert_eq!(PathFilter::parse(&[0, 0xFF]).unwrap_err(), expected);
+    }
+}
@@ -1,23 +1,22 @@
 use super::string_pool_common::{
-    self, StringPoolFormat, StringPoolPackError, StringPoolUnpackError,
+    self, StringPoolCodec, StringPoolCompression, StringPoolFormat, StringPoolPackError,
+    StringPoolUnpackError, POOL_CODEC_SHIFT, POOL_SIZE_MASK,
 };
+use crate::api::enums::{CompressionPreference, PathCollation};
 use crate::api::traits::*;
 use crate::headers::raw::toc::*;
 use crate::prelude::*;
-use crate::utilities::compression::zstd::{self, force_compress, max_alloc_for_compress_size};
+use crate::utilities::arrange::sort_lexicographically::sort_with_collation;
+use crate::utilities::compression::{
+    max_alloc_for_compress_size,
+    zstd::{self, force_compress},
+};
+use crate::utilities::memory_audit::default_slice_in;
 use core::marker::PhantomData;
 use core::ptr::write_bytes;
 use core::{mem::MaybeUninit, ptr::copy_nonoverlapping};
 use memchr::Memchr;
 
-/// The compression level used for the zstd stringpool.
-/// This defaults to 16. Normally I would set this to 22,
-/// however I found higher levels to not bring any space
-/// savings in practice due to the nature of the data.
-///
-/// Levels beyond this point don't save much space.
-const DEFAULT_COMPRESSION_LEVEL: i32 = 16;
-
 /// Size of the 'decompressed size' field.
 const SIZE_OF_DECOMP_FIELD: usize = size_of::<u32>();
 
@@ -114,6 +113,28 @@ impl StringPool {
     ) -> Result<Self, StringPoolUnpackError> {
         Self::unpack_v0_with_allocators(source, file_count, Global, Global, use_compression)
     }
+
+    /// Creates a copy of this pool, with a prefix prepended to every string.
+    ///
+    /// # Arguments
+    /// * `prefix` - The prefix to prepend, e.g. `mods/foo/`.
+    pub fn with_prefix(&self, prefix: &str) -> Self {
+        let mut raw_data =
+            Vec::with_capacity(self._raw_data.len() + prefix.len() * self._offsets.len());
+        let mut offsets = Vec::with_capacity(self._offsets.len());
+        for path in self.iter() {
+            offsets.push(raw_data.len() as u32);
+            raw_data.extend_from_slice(prefix.as_bytes());
+            raw_data.extend_from_slice(path.as_bytes());
+        }
+
+        StringPool {
+            _raw_data: raw_data.into_boxed_slice(),
+            _offsets: offsets.into_boxed_slice(),
+            _temp_allocator: PhantomData,
+            _comp_allocator: PhantomData,
+        }
+    }
 }
 
 impl<ShortAlloc: Allocator + Clone, LongAlloc: Allocator + Clone>
@@ -197,6 +218,22 @@ impl<ShortAlloc: Allocator + Clone, LongAlloc: Allocator + Clone>
         string_pool_common::get_unchecked(&self._raw_data, &self._offsets, index)
     }
 
+    /// Creates a string pool which contains no strings.
+    ///
+    /// # Arguments
+    /// * `long_alloc` - Allocator for longer lived memory. Think same lifetime as creating Nx archive creator/unpacker.
+    pub fn empty_in(long_alloc: &LongAlloc) -> Self {
+        let str_offsets: Box<[u32], LongAlloc> = default_slice_in(0, long_alloc.clone());
+        let raw_data: Box<[u8], LongAlloc> = default_slice_in(0, long_alloc.clone());
+
+        StringPool {
+            _offsets: str_offsets,
+            _raw_data: raw_data,
+            _temp_allocator: PhantomData,
+            _comp_allocator: PhantomData,
+        }
+    }
+
     /// Packs a list of items into a string pool in its native binary format, using custom allocators.
     /// For more details, read [`StringPool`].
     ///
@@ -273,7 +310,66 @@ impl<ShortAlloc: Allocator + Clone, LongAlloc: Allocator + Clone>
         long_alloc: LongAlloc,
         use_compression: bool,
     ) -> Result<Vec<u8, LongAlloc>, StringPoolPackError> {
-        items.sort_by(|a, b| a.relative_path().cmp(b.relative_path()));
+        if use_compression {
+            return Self::pack_v0_with_compression(
+                items,
+                short_alloc,
+                long_alloc,
+                StringPoolCompression::default(),
+                PathCollation::Ordinal,
+            );
+        }
+
+        // This path is unoptimized in grand scheme of things, because it's only used for testing.
+        let decompressed_pool = Self::build_v0_pool(items, short_alloc, PathCollation::Ordinal);
+        let raw_data_size = decompressed_pool.len();
+        let mut result: Vec<u8, LongAlloc> = Vec::with_capacity_in(raw_data_size, long_alloc);
+        unsafe {
+            // Write raw data
+            copy_nonoverlapping(
+                decompressed_pool.as_ptr(),
+                result.as_mut_ptr(),
+                raw_data_size,
+            );
+            result.set_len(raw_data_size);
+        }
+        Ok(result)
+    }
+
+    /// Packs a list of items into a compressed string pool, with a specified codec.
+    /// For more details, read [`StringPool`].
+    ///
+    /// # Arguments
+    /// * `items` - The list of items to pack
+    /// * `short_alloc` - Allocator for short lived memory. Think pooled memory and rentals.
+    /// * `long_alloc` - Allocator for longer lived memory. Think same lifetime as creating Nx archive creator/unpacker.
+    /// * `compression` - Controls the codec and level used, and when to store the pool raw.
+    /// * `collation` - The order to sort the paths in.
+    ///
+    /// # Remarks
+    ///
+    /// The codec is recorded in the upper bits of the pool's 'decompressed size' field,
+    /// so pools packed with any codec can be read by [`Self::unpack_v0_with_allocators`].
+    /// The collation is not recorded in the pool; store it in the archive header with
+    /// [`PathCollation::feature_flags`].
+    pub fn pack_v0_with_compression<T: HasRelativePath>(
+        items: &mut [T],
+        short_alloc: ShortAlloc,
+        long_alloc: LongAlloc,
+        compression: StringPoolCompression,
+        collation: PathCollation,
+    ) -> Result<Vec<u8, LongAlloc>, StringPoolPackError> {
+        let decompressed_pool = Self::build_v0_pool(items, short_alloc, collation);
+        Self::compress_pool(&decompressed_pool, long_alloc, compression)
+    }
+
+    /// Sorts the items and writes their paths into an uncompressed V0 pool.
+    fn build_v0_pool<T: HasRelativePath>(
+        items: &mut [T],
+        short_alloc: ShortAlloc,
+        collation: PathCollation,
+    ) -> Box<[u8], ShortAlloc> {
+        sort_with_collation(items, collation);
 
         // Sum up all string lengths (incl. null terminators)
         let raw_data_size = calc_raw_data_size(items);
@@ -306,23 +402,7 @@ impl<ShortAlloc: Allocator + Clone, LongAlloc: Allocator + Clone>
             offset += path_len + 1;
         }
 
-        let decompressed_pool = unsafe { decompressed_pool.assume_init() };
-        if use_compression {
-            Self::compress_pool(&decompressed_pool, long_alloc)
-        } else {
-            // This path is unoptimized in grand scheme of things, because it's only used for testing.
-            let mut result: Vec<u8, LongAlloc> = Vec::with_capacity_in(raw_data_size, long_alloc);
-            unsafe {
-                // Write raw data
-                copy_nonoverlapping(
-                    decompressed_pool.as_ptr(),
-                    result.as_mut_ptr(),
-                    raw_data_size,
-                );
-                result.set_len(raw_data_size);
-            }
-            Ok(result)
-        }
+        unsafe { decompressed_pool.assume_init() }
     }
 
     /// Unpacks a list of items into a string pool in its native binary format.
@@ -362,7 +442,11 @@ impl<ShortAlloc: Allocator + Clone, LongAlloc: Allocator + Clone>
 
         let decompressed_size;
         let decompressed: Box<[u8], ShortAlloc> = if use_compression {
-            decompressed_size = unsafe { (*(source.as_ptr() as *const u32)).to_le() } as usize;
+            let Some(size_field) = source.first_chunk() else {
+                return Err(StringPoolUnpackError::NotEnoughData);
+            };
+            let size_field = u32::from_le_bytes(*size_field);
+            decompressed_size = (size_field & POOL_SIZE_MASK) as usize;
 
             // SAFETY: Compressed data is empty or zstd frame is missing size, return an empty pool.
             if decompressed_size == 0 {
@@ -378,26 +462,35 @@ impl<ShortAlloc: Allocator + Clone, LongAlloc: Allocator + Clone>
             }
 
             // Decompress the data
-            let mut decompressed =
-                unsafe { Box::new_uninit_slice_in(decompressed_size, short_alloc).assume_init() };
-            zstd::decompress(
-                unsafe { source.get_unchecked(SIZE_OF_DECOMP_FIELD..) },
-                &mut decompressed[..],
-            )?;
+            let mut decompressed = default_slice_in(decompressed_size, short_alloc);
+            let compressed = &source[SIZE_OF_DECOMP_FIELD..];
+            match (size_field >> POOL_CODEC_SHIFT) as u8 {
+                x if x == StringPoolCodec::ZStandard as u8 => {
+                    zstd::decompress(compressed, &mut decompressed[..])?;
+                }
+                x if x == StringPoolCodec::Copy as u8 => {
+                    if compressed.len() < decompressed_size {
+                        return Err(StringPoolUnpackError::NotEnoughData);
+                    }
+                    decompressed.copy_from_slice(&compressed[..decompressed_size]);
+                }
+                #[cfg(feature = "lz4")]
+                x if x == StringPoolCodec::Lz4 as u8 => {
+                    crate::utilities::compression::lz4::decompress(
+                        compressed,
+                        &mut decompressed[..],
+                    )?;
+                }
+                x => return Err(StringPoolUnpackError::UnsupportedCodec(x)),
+            }
             decompressed
         } else {
             decompressed_size = source.len();
 
             // For uncompressed data, we can directly use the source
-            let mut decompressed = Box::new_uninit_slice_in(source.len(), short_alloc);
-            unsafe {
-                copy_nonoverlapping(
-                    source.as_ptr(),
-                    decompressed.as_mut_ptr() as *mut u8,
-                    source.len(),
-                );
-                decompressed.assume_init()
-            }
+            let mut decompressed = default_slice_in(source.len(), short_alloc);
+            decompressed.copy_from_slice(source);
+            decompressed
         };
 
         // Validate the decompressed segment ends with a null terminator
@@ -406,15 +499,17 @@ impl<ShortAlloc: Allocator + Clone, LongAlloc: Allocator + Clone>
             return Err(StringPoolUnpackError::ShouldEndOnNullTerminator);
         }
 
+        if cfg!(feature = "forbid-unsafe-fastpaths") {
+            return Self::split_pool_checked(&decompressed, file_count, long_alloc);
+        }
+
         // Populate all offsets
         let mut str_offsets: Box<[u32], LongAlloc> =
-            unsafe { Box::new_uninit_slice_in(file_count, long_alloc.clone()).assume_init() };
+            default_slice_in(file_count, long_alloc.clone());
 
         // Allocate space for paths without null terminators
-        let mut raw_data: Box<[u8], LongAlloc> = unsafe {
-            Box::new_uninit_slice_in(decompressed_size - file_count, long_alloc.clone())
-                .assume_init()
-        };
+        let mut raw_data: Box<[u8], LongAlloc> =
+            default_slice_in(decompressed_size - file_count, long_alloc.clone());
 
         // TODO: https://github.com/BurntSushi/memchr/issues/160
         // Add compile-time substitution.
@@ -503,56 +598,120 @@ impl<ShortAlloc: Allocator + Clone, LongAlloc: Allocator + Clone>
         })
     }
 
+    /// Splits a decompressed pool into its paths, with every copy bounds checked.
+    ///
+    /// This is the counterpart of the splitting loop in [`Self::unpack_v0_with_allocators`],
+    /// used in its place when the `forbid-unsafe-fastpaths` feature is enabled.
+    ///
+    /// # Arguments
+    /// * `decompressed` - The decompressed pool, ending on a null terminator.
+    /// * `file_count` - Number of files in the archive. This is equal to number of entries.
+    /// * `long_alloc` - Allocator for longer lived memory. Think same lifetime as creating Nx archive creator/unpacker.
+    fn split_pool_checked(
+        decompressed: &[u8],
+        file_count: usize,
+        long_alloc: LongAlloc,
+    ) -> Result<StringPool<ShortAlloc, LongAlloc>, StringPoolUnpackError> {
+        // Space for paths without null terminators.
+        let Some(raw_size) = decompressed.len().checked_sub(file_count) else {
+            return Err(StringPoolUnpackError::BufferOverflow);
+        };
+
+        let mut str_offsets: Box<[u32], LongAlloc> =
+            default_slice_in(file_count, long_alloc.clone());
+        let mut raw_data: Box<[u8], LongAlloc> = default_slice_in(raw_size, long_alloc);
+
+        // Files past the last path keep an offset of 0, as in the unchecked path.
+        let paths = decompressed
+            .strip_suffix(&[0])
+            .unwrap_or(decompressed)
+            .split(|x| *x == 0);
+        let mut dest_copy_offset = 0;
+        for (offset, path) in str_offsets.iter_mut().zip(paths) {
+            let Some(dest) = raw_data.get_mut(dest_copy_offset..dest_copy_offset + path.len())
+            else {
+                return Err(StringPoolUnpackError::BufferOverflow);
+            };
+
+            dest.copy_from_slice(path);
+            *offset = dest_copy_offset as u32;
+            dest_copy_offset += path.len();
+        }
+
+        Ok(StringPool {
+            _offsets: str_offsets,
+            _raw_data: raw_data,
+            _temp_allocator: PhantomData,
+            _comp_allocator: PhantomData,
+        })
+    }
+
     fn compress_pool(
         decompressed_pool: &[u8],
         long_alloc: LongAlloc,
+        settings: StringPoolCompression,
     ) -> Result<Vec<u8, LongAlloc>, StringPoolPackError> {
-        let destination: Box<[MaybeUninit<u8>], LongAlloc> = Box::new_uninit_slice_in(
-            max_alloc_for_compress_size(decompressed_pool.len() + SIZE_OF_DECOMP_FIELD),
+        let mut destination: Box<[u8], LongAlloc> = default_slice_in(
+            max_alloc_for_compress_size(decompressed_pool.len()) + SIZE_OF_DECOMP_FIELD,
             long_alloc,
         );
+        let comp_dest = unsafe { destination.get_unchecked_mut(SIZE_OF_DECOMP_FIELD..) };
+        let (comp_result, mut codec) = match settings.algorithm {
+            CompressionPreference::Copy => (Ok(decompressed_pool.len()), StringPoolCodec::Copy),
+            #[cfg(feature = "lz4")]
+            CompressionPreference::Lz4 => {
+                let mut used_copy = false;
+                let result = crate::utilities::compression::lz4::compress(
+                    settings.level,
+                    decompressed_pool,
+                    comp_dest,
+                    &mut used_copy,
+                );
+                match used_copy {
+                    true => (result, StringPoolCodec::Copy),
+                    false => (result, StringPoolCodec::Lz4),
+                }
+            }
+            _ => (
+                force_compress(settings.level, decompressed_pool, comp_dest),
+                StringPoolCodec::ZStandard,
+            ),
+        };
 
-        let mut destination = unsafe { destination.assume_init() };
+        let mut num_bytes = match comp_result {
+            Ok(num_bytes) => num_bytes,
+            Err(x) => return Err(StringPoolPackError::FailedToCompress(x)),
+        };
 
-        // Write decompressed data size.
-        let result_ptr = destination.as_mut_ptr() as *mut u32;
-        unsafe {
-            *result_ptr = (decompressed_pool.len() as u32).to_le();
+        // Store raw if compression doesn't save enough to be worth decompressing on open.
+        let savings = decompressed_pool.len().saturating_sub(num_bytes);
+        if codec == StringPoolCodec::Copy || savings < settings.min_savings as usize {
+            comp_dest[..decompressed_pool.len()].copy_from_slice(decompressed_pool);
+            num_bytes = decompressed_pool.len();
+            codec = StringPoolCodec::Copy;
         }
 
-        let comp_dest = unsafe { destination.get_unchecked_mut(SIZE_OF_DECOMP_FIELD..) };
-        let comp_result = force_compress(DEFAULT_COMPRESSION_LEVEL, decompressed_pool, comp_dest);
-
-        match comp_result {
-            Ok(num_bytes) => {
-                if destination.len() <= MAX_STRING_POOL_SIZE {
-                    let mut vec = destination.into_vec();
-                    // SAFETY: We know exact length of pool after compression, if it did not fit, we would have matched the error branch.
-                    unsafe { vec.set_len(num_bytes + SIZE_OF_DECOMP_FIELD) };
-                    Ok(vec)
-                } else {
-                    Err(StringPoolPackError::PoolTooLarge)
-                }
-            }
-            Err(x) => Err(StringPoolPackError::FailedToCompress(x)),
+        if num_bytes + SIZE_OF_DECOMP_FIELD > MAX_STRING_POOL_SIZE {
+            return Err(StringPoolPackError::PoolTooLarge);
+        }
+
+        // Write decompressed data size, with the codec in the upper bits.
+        let size_field = decompressed_pool.len() as u32 | ((codec as u32) << POOL_CODEC_SHIFT);
+        unsafe {
+            (destination.as_mut_ptr() as *mut u32).write_unaligned(size_field.to_le());
         }
+
+        let mut vec = destination.into_vec();
+        // SAFETY: We know exact length of pool after compression, if it did not fit, we would have matched the error branch.
+        unsafe { vec.set_len(num_bytes + SIZE_OF_DECOMP_FIELD) };
+        Ok(vec)
     }
 }
 
 fn return_empty_pool<ShortAlloc: Allocator + Clone, LongAlloc: Allocator + Clone>(
     long_alloc: &LongAlloc,
 ) -> Result<StringPool<ShortAlloc, LongAlloc>, StringPoolUnpackError> {
-    let str_offsets: Box<[u32], LongAlloc> =
-        unsafe { Box::new_uninit_slice_in(0, long_alloc.clone()).assume_init() };
-    let raw_data: Box<[u8], LongAlloc> =
-        unsafe { Box::new_uninit_slice_in(0, long_alloc.clone()).assume_init() };
-
-    Ok(StringPool {
-        _offsets: str_offsets,
-        _raw_data: raw_data,
-        _temp_allocator: PhantomData,
-        _comp_allocator: PhantomData,
-    })
+    Ok(StringPool::empty_in(long_alloc))
 }
 
 /// Calculates the total size of the pool for both the
@@ -573,10 +732,17 @@ mod tests {
     use crate::utilities::compression::zstd::force_compress;
     use crate::utilities::compression::NxDecompressionError;
     use crate::{
-        api::traits::*,
+        api::{
+            enums::{CompressionPreference, PathCollation},
+            traits::*,
+        },
         headers::parser::{
             string_pool::{StringPool, StringPoolUnpackError},
-            string_pool_common::StringPoolFormat::{self, *},
+            string_pool_common::{
+                StringPoolCodec, StringPoolCompression,
+                StringPoolFormat::{self, *},
+                POOL_CODEC_SHIFT,
+            },
         },
     };
     use allocator_api2::vec;
@@ -594,6 +760,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_prefix_pool() {
+        let mut items: Vec<TestItem> = vec![
+            TestItem {
+                path: "a.txt".to_string(),
+            },
+            TestItem {
+                path: "data/b.txt".to_string(),
+            },
+        ];
+
+        let packed = StringPool::pack(&mut items, V0, false).unwrap();
+        let unpacked = StringPool::unpack(&packed, items.len(), V0, false).unwrap();
+        let prefixed = unpacked.with_prefix("mods/foo/");
+
+        let paths: Vec<&str> = prefixed.iter().collect();
+        assert_eq!(paths, vec!["mods/foo/a.txt", "mods/foo/data/b.txt"]);
+        assert_eq!(prefixed.get(1), Some("mods/foo/data/b.txt"));
+    }
+
+    #[test]
+    fn empty_pool_has_no_items() {
+        let pool: StringPool = StringPool::empty_in(&Global);
+        assert_eq!(pool.iter().count(), 0);
+        assert_eq!(pool.get(0), None);
+    }
+
     #[rstest]
     #[cfg_attr(not(miri), case(V0, true))]
     #[case(V0, false)]
@@ -630,6 +823,40 @@ mod tests {
         );
     }
 
+    #[rstest]
+    #[case::all_files(3)]
+    #[case::fewer_files(2)]
+    fn checked_split_matches_unchecked(#[case] file_count: usize) {
+        let mut items: Vec<TestItem> = vec![
+            TestItem {
+                path: "data/textures/cat.png".to_string(),
+            },
+            TestItem {
+                path: "data/textures/dog.png".to_string(),
+            },
+            TestItem {
+                path: "data/models/house.obj".to_string(),
+            },
+        ];
+
+        let packed = StringPool::pack(&mut items, V0, false).unwrap();
+        let unchecked = StringPool::unpack(&packed, file_count, V0, false).unwrap();
+        let checked =
+            StringPool::<Global, Global>::split_pool_checked(&packed, file_count, Global).unwrap();
+
+        assert_eq!(
+            checked.iter().collect::<Vec<_>>(),
+            unchecked.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(checked.iter().count(), file_count);
+
+        // A file count larger than the pool itself is rejected.
+        assert!(matches!(
+            StringPool::<Global, Global>::split_pool_checked(&packed, packed.len() + 1, Global),
+            Err(StringPoolUnpackError::BufferOverflow)
+        ));
+    }
+
     #[rstest]
     #[case(V0)]
     #[cfg_attr(miri, ignore)]
@@ -660,6 +887,87 @@ mod tests {
             .for_each(|x| unsafe { assert_eq!(items[x].path, unpacked.get_unchecked(x)) });
     }
 
+    #[rstest]
+    #[case::zstd(CompressionPreference::ZStandard, 0, StringPoolCodec::ZStandard)]
+    #[case::copy(CompressionPreference::Copy, 0, StringPoolCodec::Copy)]
+    #[cfg_attr(
+        feature = "lz4",
+        case::lz4(CompressionPreference::Lz4, 0, StringPoolCodec::Lz4)
+    )]
+    #[case::raw_when_savings_small(
+        CompressionPreference::ZStandard,
+        u32::MAX,
+        StringPoolCodec::Copy
+    )]
+    #[cfg_attr(miri, ignore)]
+    fn can_pack_and_unpack_with_codec(
+        #[case] algorithm: CompressionPreference,
+        #[case] min_savings: u32,
+        #[case] expected_codec: StringPoolCodec,
+    ) {
+        let mut items: Vec<TestItem> = (0..100)
+            .map(|i| TestItem {
+                path: format!("data/textures/file_{:03}.png", i),
+            })
+            .collect();
+
+        let compression = StringPoolCompression {
+            algorithm,
+            level: 9,
+            min_savings,
+        };
+        let packed = StringPool::pack_v0_with_compression(
+            &mut items,
+            Global,
+            Global,
+            compression,
+            PathCollation::Ordinal,
+        )
+        .unwrap();
+        let size_field = u32::from_le_bytes(packed[..4].try_into().unwrap());
+        assert_eq!(size_field >> POOL_CODEC_SHIFT, expected_codec as u32);
+
+        let unpacked = StringPool::unpack_v0(&packed, items.len(), true).unwrap();
+        assert_eq!(unpacked.len(), items.len());
+        (0..unpacked.len())
+            .for_each(|x| unsafe { assert_eq!(items[x].path, unpacked.get_unchecked(x)) });
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_pack_with_natural_collation() {
+        let mut items: Vec<TestItem> = ["file10", "file2", "file1"]
+            .iter()
+            .map(|path| TestItem {
+                path: path.to_string(),
+            })
+            .collect();
+
+        let packed = StringPool::pack_v0_with_compression(
+            &mut items,
+            Global,
+            Global,
+            StringPoolCompression::default(),
+            PathCollation::Natural,
+        )
+        .unwrap();
+
+        let unpacked = StringPool::unpack_v0(&packed, items.len(), true).unwrap();
+        let paths: Vec<&str> = unpacked.iter().collect();
+        assert_eq!(paths, ["file1", "file2", "file10"]);
+    }
+
+    #[test]
+    fn unpack_unknown_codec_returns_error() {
+        // size 4, codec 3 (reserved)
+        let data = vec![4, 0, 0, 0xC0, b'a', 0, b'b', 0];
+        let result = StringPool::unpack_v0(&data, 2, true);
+        assert!(matches!(
+            result,
+            Err(StringPoolUnpackError::UnsupportedCodec(3))
+        ));
+    }
+
     #[rstest]
     #[case(V0)]
     #[cfg_attr(miri, ignore)]
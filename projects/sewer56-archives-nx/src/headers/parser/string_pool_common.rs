@@ -1,7 +1,8 @@
+use crate::api::enums::CompressionPreference;
 use crate::utilities::compression::{
     zstd::GetDecompressedSizeError, NxCompressionError, NxDecompressionError,
 };
-use core::{iter::once, str::from_utf8_unchecked};
+use core::str::from_utf8_unchecked;
 use thiserror_no_std::Error;
 
 /// Checks if a given path is present in the raw string pool data.
@@ -52,8 +53,8 @@ pub fn iter<'a>(raw_data: &'a [u8], offsets: &'a [u32]) -> impl Iterator<Item =
             // SAFETY: The string pool is guaranteed to be valid UTF-8
             unsafe { from_utf8_unchecked(&raw_data[start..end]) }
         })
-        .chain(once({
-            let start = *offsets.last().unwrap() as usize;
+        .chain(offsets.last().map(move |start| {
+            let start = *start as usize;
             let end = raw_data.len();
 
             // SAFETY: The string pool is guaranteed to be valid UTF-8
@@ -150,6 +151,67 @@ pub enum StringPoolFormat {
     V0,
 }
 
+/// The compression level used for the zstd stringpool.
+/// This defaults to 16. Normally I would set this to 22,
+/// however I found higher levels to not bring any space
+/// savings in practice due to the nature of the data.
+///
+/// Levels beyond this point don't save much space.
+pub const DEFAULT_STRING_POOL_COMPRESSION_LEVEL: i32 = 16;
+
+/// Number of bits the codec is shifted left by in the 'decompressed size' field of the pool.
+pub(crate) const POOL_CODEC_SHIFT: u32 = 30;
+
+/// Mask for extracting the decompressed size from the 'decompressed size' field of the pool.
+pub(crate) const POOL_SIZE_MASK: u32 = (1 << POOL_CODEC_SHIFT) - 1;
+
+/// Codec of the string pool, stored in the upper 2 bits of the 'decompressed size' field.
+///
+/// The pool can never exceed [`MAX_STRING_POOL_SIZE`](crate::headers::raw::toc::MAX_STRING_POOL_SIZE)
+/// (24 bits), so these bits were always 0 in older archives, which corresponds to
+/// [`StringPoolCodec::ZStandard`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub(crate) enum StringPoolCodec {
+    /// Pool is a magicless zstd frame.
+    ZStandard = 0,
+    /// Pool is stored uncompressed.
+    Copy = 1,
+    /// Pool is a raw LZ4 block.
+    #[cfg_attr(not(feature = "lz4"), allow(dead_code))]
+    Lz4 = 2,
+}
+
+/// Controls how the string pool is compressed when packing an archive.
+///
+/// # Remarks
+///
+/// For small archives, the time taken to decompress the pool can make up a meaningful
+/// part of opening the archive. [`Self::min_savings`] lets the packer store the pool raw in
+/// cases where compression would barely help.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct StringPoolCompression {
+    /// Algorithm used to compress the pool.
+    /// [`CompressionPreference::Copy`] always stores the pool raw.
+    pub algorithm: CompressionPreference,
+
+    /// Level used to compress the pool.
+    pub level: i32,
+
+    /// If compressing the pool saves fewer bytes than this, the pool is stored raw instead.
+    pub min_savings: u32,
+}
+
+impl Default for StringPoolCompression {
+    fn default() -> Self {
+        Self {
+            algorithm: CompressionPreference::ZStandard,
+            level: DEFAULT_STRING_POOL_COMPRESSION_LEVEL,
+            min_savings: 0,
+        }
+    }
+}
+
 /// Represents an error obtained when trying to pack the string pool.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum StringPoolPackError {
@@ -187,4 +249,7 @@ pub enum StringPoolUnpackError {
 
     /// There is insufficient data to deserialize the string pool.
     NotEnoughData,
+
+    /// The pool was compressed with an unknown codec, or one not enabled in this build.
+    UnsupportedCodec(u8),
 }
@@ -1,4 +1,6 @@
+use crate::api::traits::{FileProviderError, InputDataProvider};
 use bitfield::bitfield;
+use thiserror_no_std::Error;
 
 bitfield! {
     /// Packed header data
@@ -43,7 +45,24 @@ impl NativeFileHeader {
     const EXPECTED_MAGIC: u32 = 0x4E585553_u32.to_le();
 
     /// Size of a header page in bytes.
-    const HEADER_PAGE_SIZE: u32 = 4096;
+    pub const HEADER_PAGE_SIZE: u32 = 4096;
+
+    /// Alignment of each block after the header, in bytes.
+    pub const BLOCK_ALIGNMENT: u32 = 4096;
+
+    /// Bits of the feature flags which record how the paths in the string pool were sorted.
+    /// See [`PathCollation`] for the values.
+    ///
+    /// [`PathCollation`]: crate::api::enums::PathCollation
+    pub const PATH_COLLATION_FLAGS: u8 = 0b0011;
+
+    /// Feature flag set if [`HeaderExtensions`] follow the string pool.
+    ///
+    /// [`HeaderExtensions`]: crate::headers::parser::HeaderExtensions
+    pub const EXTENSIONS_FLAG: u8 = 0b0100;
+
+    /// Feature flags understood by this library.
+    pub const KNOWN_FEATURE_FLAGS: u8 = Self::PATH_COLLATION_FLAGS | Self::EXTENSIONS_FLAG;
 
     /// Returns true if the 'Magic' in the header is valid, else false.
     pub fn is_valid_magic_header(&self) -> bool {
@@ -60,8 +79,73 @@ impl NativeFileHeader {
         Self::BASE_CHUNK_SIZE << self.header_data.chunk_size()
     }
 
+    /// Gets the version of the archive format the archive was written with.
+    pub fn version(&self) -> u8 {
+        self.header_data.version() as u8
+    }
+
+    /// Gets the 'feature flags' of the archive.
+    pub fn feature_flags(&self) -> u8 {
+        self.header_data.feature_flags() as u8
+    }
+
+    /// Sets the 'feature flags' of the archive.
+    /// Only the lower 4 bits are stored.
+    pub fn set_feature_flags(&mut self, flags: u8) {
+        self.header_data.set_feature_flags((flags & 0b1111) as u32);
+    }
+
+    /// Reads a header from its serialized form, e.g. the first [`Self::SIZE_BYTES`] of an archive.
+    /// The magic is not validated, see [`Self::is_valid_magic_header`].
+    pub fn from_bytes(bytes: [u8; Self::SIZE_BYTES]) -> Self {
+        let [m0, m1, m2, m3, d0, d1, d2, d3] = bytes;
+        Self {
+            magic: u32::from_ne_bytes([m0, m1, m2, m3]),
+            header_data: HeaderData(u32::from_ne_bytes([d0, d1, d2, d3])),
+        }
+    }
+
+    /// Serializes the header, as stored at the start of an archive.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE_BYTES] {
+        let magic = self.magic;
+        let header_data = self.header_data.0;
+        let mut bytes = [0u8; Self::SIZE_BYTES];
+        bytes[..4].copy_from_slice(&magic.to_ne_bytes());
+        bytes[4..].copy_from_slice(&header_data.to_ne_bytes());
+        bytes
+    }
+
+    /// Reads only the header of an archive, without reading or allocating the table of contents.
+    ///
+    /// This is cheap enough to triage large numbers of archives, e.g. to find the ones
+    /// written with an older version of the format.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - Provides the raw bytes of the archive. Only the first
+    ///   [`Self::SIZE_BYTES`] bytes are requested.
+    pub fn peek(provider: &dyn InputDataProvider) -> Result<HeaderSummary, HeaderPeekError> {
+        let data = provider.get_file_data(0, Self::SIZE_BYTES as u64)?;
+        let Some(bytes) = data.data().first_chunk() else {
+            return Err(HeaderPeekError::Truncated);
+        };
+
+        let header = Self::from_bytes(*bytes);
+        if !header.is_valid_magic_header() {
+            return Err(HeaderPeekError::InvalidMagicHeader);
+        }
+
+        Ok(HeaderSummary {
+            version: header.version(),
+            header_size: header.header_page_bytes(),
+            chunk_size: header.chunk_size_bytes(),
+            block_alignment: Self::BLOCK_ALIGNMENT,
+            feature_flags: header.feature_flags(),
+        })
+    }
+
     /// Initializes the header with given data.
-    /// This is the only way to create and modify a NativeFileHeader.
+    /// This is the only way to create a NativeFileHeader.
     ///
     /// # Arguments
     ///
@@ -118,9 +202,57 @@ impl NativeFileHeader {
     }
 }
 
+/// The values of an archive's header, as returned by [`NativeFileHeader::peek`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct HeaderSummary {
+    /// Version of the archive format the archive was written with.
+    pub version: u8,
+    /// Size of the header, including the table of contents and string pool.
+    /// The first block starts at this offset.
+    pub header_size: u32,
+    /// Size of the chunks large files are split into.
+    pub chunk_size: u32,
+    /// Alignment of each block, in bytes.
+    pub block_alignment: u32,
+    /// The 'feature flags' of the archive.
+    pub feature_flags: u8,
+}
+
+impl HeaderSummary {
+    /// Returns true if the archive was written with an older version of the format
+    /// than the one written by this library.
+    pub fn is_outdated(&self) -> bool {
+        self.version < NativeFileHeader::CURRENT_ARCHIVE_VERSION
+    }
+
+    /// Returns true if the archive uses a newer format version, or feature flags
+    /// not known to this library.
+    pub fn has_unknown_features(&self) -> bool {
+        self.version > NativeFileHeader::CURRENT_ARCHIVE_VERSION
+            || self.feature_flags & !NativeFileHeader::KNOWN_FEATURE_FLAGS != 0
+    }
+}
+
+/// Errors that can occur in [`NativeFileHeader::peek`].
+#[derive(Debug, Error)]
+pub enum HeaderPeekError {
+    /// The data does not start with a valid Nx header.
+    #[error("The data does not have a valid Nx header")]
+    InvalidMagicHeader,
+
+    /// The data is too short to contain a header.
+    #[error("The data is too short to contain an Nx header")]
+    Truncated,
+
+    /// Failed to read data from the underlying provider.
+    #[error(transparent)]
+    FileProvider(#[from] FileProviderError),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::api::filedata::FromSliceReferenceProvider;
 
     #[test]
     fn header_size_is_correct() {
@@ -141,9 +273,11 @@ mod tests {
 
     #[test]
     fn feature_flags() {
-        let header = NativeFileHeader::init(1024, 8192);
-        // Note: We can't modify feature_flags directly at the moment, so this test is just checking the initial value
+        let mut header = NativeFileHeader::init(1024, 8192);
         assert_eq!(header.header_data.feature_flags(), 0);
+
+        header.set_feature_flags(0b1_0010);
+        assert_eq!(header.feature_flags(), 0b0010);
     }
 
     #[test]
@@ -187,4 +321,29 @@ mod tests {
         assert_eq!(header.header_data.chunk_size(), 11);
         assert_eq!(header.chunk_size_bytes(), 1_048_576);
     }
+
+    #[test]
+    fn peek_reads_header_values() {
+        let mut header = NativeFileHeader::init(4096, 8192);
+        header.set_feature_flags(0b0001);
+        let data = header.to_bytes();
+
+        let summary = NativeFileHeader::peek(&FromSliceReferenceProvider::new(&data)).unwrap();
+        assert_eq!(summary.version, NativeFileHeader::CURRENT_ARCHIVE_VERSION);
+        assert_eq!(summary.header_size, 8192);
+        assert_eq!(summary.chunk_size, 4096);
+        assert_eq!(summary.block_alignment, 4096);
+        assert_eq!(summary.feature_flags, 0b0001);
+        assert!(!summary.is_outdated());
+        assert!(!summary.has_unknown_features());
+    }
+
+    #[test]
+    fn peek_rejects_invalid_magic() {
+        let data = [0u8; NativeFileHeader::SIZE_BYTES];
+        assert!(matches!(
+            NativeFileHeader::peek(&FromSliceReferenceProvider::new(&data)),
+            Err(HeaderPeekError::InvalidMagicHeader)
+        ));
+    }
 }
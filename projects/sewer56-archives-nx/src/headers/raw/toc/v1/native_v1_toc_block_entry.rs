@@ -1,4 +1,5 @@
 use bitfield::bitfield;
+#[cfg(not(feature = "hardened"))]
 use core::hint::unreachable_unchecked;
 use endian_writer::*;
 
@@ -38,12 +39,23 @@ impl NativeV1TocBlockEntry {
         NativeV1TocBlockEntry(unsafe { lereader.read_u32() })
     }
 
+    /// Creates an entry from its serialized value.
+    pub fn from_raw(raw: u32) -> Self {
+        NativeV1TocBlockEntry(raw)
+    }
+
     /// Get the compression preference
+    ///
+    /// With the `hardened` feature, an unknown value is returned as [`CompressionPreference::NoPreference`].
     pub fn compression(&self) -> CompressionPreference {
         match self.compression_raw() {
             0 => CompressionPreference::Copy,
             1 => CompressionPreference::ZStandard,
             2 => CompressionPreference::Lz4,
+            3 => CompressionPreference::Mixed,
+            #[cfg(feature = "hardened")]
+            _ => CompressionPreference::NoPreference,
+            #[cfg(not(feature = "hardened"))]
             _ => unsafe { unreachable_unchecked() },
         }
     }
@@ -56,6 +68,7 @@ impl NativeV1TocBlockEntry {
             CompressionPreference::Copy => 0,
             CompressionPreference::ZStandard => 1,
             CompressionPreference::Lz4 => 2,
+            CompressionPreference::Mixed => 3,
         });
     }
 }
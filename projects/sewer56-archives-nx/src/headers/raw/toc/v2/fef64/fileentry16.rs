@@ -119,6 +119,14 @@ impl FileEntry16 {
         }
     }
 
+    /// Creates a `FileEntry16` from its serialized hash and packed fields.
+    pub fn from_raw(hash: u64, data: u64) -> FileEntry16 {
+        FileEntry16 {
+            hash: hash.into(),
+            data,
+        }
+    }
+
     /// Converts `FileEntry16` to `FileEntry`.
     ///
     /// # Arguments
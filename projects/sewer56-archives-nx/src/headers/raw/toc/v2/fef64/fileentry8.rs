@@ -109,6 +109,11 @@ impl FileEntry8 {
         }
     }
 
+    /// Creates a `FileEntry8` from its serialized packed fields.
+    pub fn from_raw(data: u64) -> FileEntry8 {
+        FileEntry8 { data }
+    }
+
     /// Converts `FileEntry8` to `FileEntry`.
     ///
     /// # Arguments
@@ -13,6 +13,16 @@ pub enum ToCFormat {
     Error,
 }
 
+impl ToCFormat {
+    /// Returns true if file entries in this format contain the hash of the file.
+    pub fn has_hashes(&self) -> bool {
+        matches!(
+            self,
+            ToCFormat::FEF64 | ToCFormat::Preset0 | ToCFormat::Preset2 | ToCFormat::Preset3
+        )
+    }
+}
+
 #[allow(clippy::absurd_extreme_comparisons)] // <= is more understandable than == in context.
 pub fn determine_optimal_toc_format(
     string_pool_size: u32,
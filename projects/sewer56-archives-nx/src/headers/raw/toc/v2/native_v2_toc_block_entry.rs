@@ -1,5 +1,6 @@
 use crate::api::enums::*;
 use bitfield::bitfield;
+#[cfg(not(feature = "hardened"))]
 use core::hint::unreachable_unchecked;
 use endian_writer::*;
 
@@ -39,12 +40,26 @@ impl NativeV2TocBlockEntry {
         NativeV2TocBlockEntry(unsafe { lereader.read_u32() })
     }
 
+    /// Creates an entry from its serialized value.
+    pub fn from_raw(raw: u32) -> Self {
+        NativeV2TocBlockEntry(raw)
+    }
+
     /// Get the compression preference
+    ///
+    /// With the `hardened` feature, an unknown value is returned as [`CompressionPreference::NoPreference`],
+    /// which fails to decompress with [`NxDecompressionError::UnsupportedMethod`].
+    ///
+    /// [`NxDecompressionError::UnsupportedMethod`]: crate::utilities::compression::NxDecompressionError::UnsupportedMethod
     pub fn compression(&self) -> CompressionPreference {
         match self.compression_raw() {
             0 => CompressionPreference::Copy,
             1 => CompressionPreference::ZStandard,
             2 => CompressionPreference::Lz4,
+            3 => CompressionPreference::Mixed,
+            #[cfg(feature = "hardened")]
+            _ => CompressionPreference::NoPreference,
+            #[cfg(not(feature = "hardened"))]
             _ => unsafe { unreachable_unchecked() },
         }
     }
@@ -57,6 +72,7 @@ impl NativeV2TocBlockEntry {
             CompressionPreference::Copy => 0,
             CompressionPreference::ZStandard => 1,
             CompressionPreference::Lz4 => 2,
+            CompressionPreference::Mixed => 3,
         });
     }
 }
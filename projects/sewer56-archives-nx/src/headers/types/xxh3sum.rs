@@ -11,6 +11,12 @@ impl XXH3sum {
     pub fn create(input: &[u8]) -> XXH3sum {
         XXH3sum(XxHash3_64::oneshot(input))
     }
+
+    /// Computes the checksum of a slice of bytes with a given seed.
+    /// A seed of 0 gives the same result as [`Self::create`].
+    pub fn create_with_seed(input: &[u8], seed: u64) -> XXH3sum {
+        XXH3sum(XxHash3_64::oneshot_with_seed(seed, input))
+    }
 }
 
 impl From<u64> for XXH3sum {
@@ -4,41 +4,8 @@ use crate::prelude::*;
 use alloc::{rc::Rc, sync::Arc};
 use core::any::Any;
 use core::slice;
-use hashbrown::HashTable;
 
-/// The value that indicates no dictionary is used.
-pub const NO_DICTIONARY_INDEX: u8 = u8::MAX;
-
-// Simple table entry that just stores the pointer value
-#[derive(Debug)]
-pub struct PtrEntry {
-    key: u64,
-}
-
-// Define the Block trait
-pub trait Block<T>: HasDictIndex
-where
-    T: HasFileSize + CanProvideInputData + HasRelativePath,
-{
-    // Define necessary methods
-    fn as_any(&self) -> &dyn Any;
-
-    /// Appends files to a given vector, using a [`HashTable`] to track duplicates.
-    ///
-    /// # Arguments
-    /// * `items` - The vector to append items to
-    /// * `seen` - HashSet tracking already added items
-    fn append_items(&self, items: &mut Vec<Rc<T>>, seen: &mut HashTable<PtrEntry>);
-
-    /// Returns a slice of references to all items in this block without allocation
-    fn items(&self) -> &[Rc<T>];
-
-    /// For any block that's based on existing data in another Nx archive, this returns
-    /// the max DecompressedBlockOffset for any existing file entry within the block.
-    fn max_decompressed_block_offset(&self) -> u32 {
-        0
-    }
-}
+pub use crate::api::traits::block::{Block, PtrEntry, NO_DICTIONARY_INDEX};
 
 /// Represents an individual SOLID block packed by the Nx library.
 #[allow(dead_code)]
@@ -49,6 +16,8 @@ where
     pub(crate) items: Vec<Rc<T>>,
     pub(crate) compression_preference: CompressionPreference,
     pub(crate) dict_index: u32,
+    pub(crate) filter: DataFilter,
+    pub(crate) block_size: Option<u32>,
 }
 
 impl<T> SolidBlock<T>
@@ -70,8 +39,22 @@ where
             items,
             compression_preference,
             dict_index,
+            filter: DataFilter::None,
+            block_size: None,
         }
     }
+
+    /// Sets the filter applied to the block's data before compression.
+    pub fn with_filter(mut self, filter: DataFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Records the size limit the block was created with, when picked per group.
+    pub fn with_block_size(mut self, block_size: u32) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
 }
 
 impl<T> Block<T> for SolidBlock<T>
@@ -83,20 +66,6 @@ where
         self
     }
 
-    fn append_items(&self, items: &mut Vec<Rc<T>>, seen: &mut HashTable<PtrEntry>) {
-        for item in &self.items {
-            let ptr_value = Rc::as_ptr(item) as u64;
-
-            if seen
-                .find(ptr_value, |entry| entry.key == ptr_value)
-                .is_none()
-            {
-                seen.insert_unique(ptr_value, PtrEntry { key: ptr_value }, |entry| entry.key);
-                items.push(item.clone());
-            }
-        }
-    }
-
     fn items(&self) -> &[Rc<T>] {
         &self.items
     }
@@ -104,6 +73,14 @@ where
     fn max_decompressed_block_offset(&self) -> u32 {
         0
     }
+
+    fn filter(&self) -> DataFilter {
+        self.filter
+    }
+
+    fn block_size(&self) -> Option<u32> {
+        self.block_size
+    }
 }
 
 impl<T: HasFileSize + CanProvideInputData + HasRelativePath> HasDictIndex for SolidBlock<T> {
@@ -133,6 +110,9 @@ pub struct ChunkedBlockState<T> {
     /// Index of the dictionary for dictionary compression, if dictionary
     /// compression is being used.
     pub(crate) dict_index: u32,
+
+    /// Filter applied to each chunk before compression.
+    pub(crate) filter: DataFilter,
 }
 
 impl<T> ChunkedBlockState<T>
@@ -157,8 +137,15 @@ where
             num_chunks,
             file,
             dict_index,
+            filter: DataFilter::None,
         }
     }
+
+    /// Sets the filter applied to each chunk before compression.
+    pub fn with_filter(mut self, filter: DataFilter) -> Self {
+        self.filter = filter;
+        self
+    }
 }
 
 /// A block that represents the slice of an existing file.
@@ -212,36 +199,18 @@ where
         self
     }
 
-    fn append_items(&self, items: &mut Vec<Rc<T>>, seen: &mut HashTable<PtrEntry>) {
-        let ptr_value = Rc::as_ptr(&self.state.file) as u64;
-
-        if seen
-            .find(ptr_value, |entry| entry.key == ptr_value)
-            .is_none()
-        {
-            seen.insert_unique(ptr_value, PtrEntry { key: ptr_value }, |entry| entry.key);
-            items.push(self.state.file.clone());
-        }
-    }
-
     fn items(&self) -> &[Rc<T>] {
         // Create a static slice containing just the single file reference
         slice::from_ref(&self.state.file)
     }
-}
 
-impl<T: CanProvideInputData + HasFileSize + HasRelativePath> HasDictIndex for ChunkedFileBlock<T> {
-    fn dict_index(&self) -> u32 {
-        self.state.dict_index
+    fn filter(&self) -> DataFilter {
+        self.state.filter
     }
 }
 
-// Blanket implmementation
-impl<T> HasDictIndex for Box<dyn Block<T>>
-where
-    T: HasFileSize + CanProvideInputData + HasRelativePath,
-{
+impl<T: CanProvideInputData + HasFileSize + HasRelativePath> HasDictIndex for ChunkedFileBlock<T> {
     fn dict_index(&self) -> u32 {
-        (**self).dict_index()
+        self.state.dict_index
     }
 }
@@ -1,6 +1,6 @@
 use super::pack_state::DeduplicationError;
-use crate::headers::types::xxh3sum::{XXH3sum, XXH3sumHashBuilder};
-use hashbrown::{HashMap, HashSet};
+use super::spilled_hash_index::{DedupMemoryLimit, SpilledHashIndex};
+use crate::headers::types::xxh3sum::XXH3sum;
 use std::sync::RwLock;
 
 /// Represents the index of the first block in a chunked file
@@ -11,35 +11,44 @@ pub struct FirstChunkedBlockIndex(pub u32);
 #[derive(Default)]
 pub struct ChunkedDeduplicationState {
     /// Contains a mapping of file hashes to pre-assigned block indexes
-    hash_to_chunked_file_details:
-        RwLock<HashMap<XXH3sum, FirstChunkedBlockIndex, XXH3sumHashBuilder>>,
+    hash_to_chunked_file_details: RwLock<SpilledHashIndex>,
 
     /// Contains a set of short hashes (typically of first 4096 bytes) that have been seen
-    short_hash_set: RwLock<HashSet<XXH3sum, XXH3sumHashBuilder>>,
+    short_hash_set: RwLock<SpilledHashIndex>,
 }
 
 impl ChunkedDeduplicationState {
     /// Creates a new [`ChunkedDeduplicationState`]
     pub fn new() -> Self {
         Self {
-            hash_to_chunked_file_details: RwLock::new(HashMap::with_hasher(
-                XXH3sumHashBuilder::default(),
-            )),
-            short_hash_set: RwLock::new(HashSet::with_hasher(XXH3sumHashBuilder::default())),
+            hash_to_chunked_file_details: RwLock::new(SpilledHashIndex::new()),
+            short_hash_set: RwLock::new(SpilledHashIndex::new()),
         }
     }
 
     /// Creates a new [`ChunkedDeduplicationState`] with specified capacity
     pub fn with_capacity(num_items: usize) -> Self {
         Self {
-            hash_to_chunked_file_details: RwLock::new(HashMap::with_capacity_and_hasher(
-                num_items,
-                XXH3sumHashBuilder::default(),
-            )),
-            short_hash_set: RwLock::new(HashSet::with_capacity_and_hasher(
-                num_items,
-                XXH3sumHashBuilder::default(),
-            )),
+            hash_to_chunked_file_details: RwLock::new(SpilledHashIndex::with_capacity(num_items)),
+            short_hash_set: RwLock::new(SpilledHashIndex::with_capacity(num_items)),
+        }
+    }
+
+    /// Creates a new [`ChunkedDeduplicationState`] which spills hashes to disk past a memory limit.
+    /// The limit is shared between the short and full hashes.
+    pub fn with_memory_limit(limit: &DedupMemoryLimit) -> Self {
+        let limit = limit.split(2);
+        Self {
+            hash_to_chunked_file_details: RwLock::new(SpilledHashIndex::with_memory_limit(&limit)),
+            short_hash_set: RwLock::new(SpilledHashIndex::with_memory_limit(&limit)),
+        }
+    }
+
+    /// Creates a new [`ChunkedDeduplicationState`], with a memory limit if one is given.
+    pub fn with_optional_memory_limit(limit: Option<&DedupMemoryLimit>) -> Self {
+        match limit {
+            Some(limit) => Self::with_memory_limit(limit),
+            None => Self::new(),
         }
     }
 
@@ -58,11 +67,10 @@ impl ChunkedDeduplicationState {
 
     /// Checks if the hash of the first up to 4096 bytes has been seen before
     pub fn has_potential_duplicate(&self, hash_4096: XXH3sum) -> Result<bool, DeduplicationError> {
-        Ok(self
-            .short_hash_set
+        self.short_hash_set
             .read()
             .map_err(|_| DeduplicationError::ReadLockError)?
-            .contains(&hash_4096))
+            .contains(hash_4096)
     }
 
     /// Attempts to find a duplicate file based on its full hash
@@ -74,8 +82,8 @@ impl ChunkedDeduplicationState {
             .hash_to_chunked_file_details
             .read()
             .map_err(|_| DeduplicationError::ReadLockError)?
-            .get(&full_hash)
-            .copied())
+            .get(full_hash)?
+            .map(FirstChunkedBlockIndex))
     }
 
     /// Adds a new file hash to the deduplication state
@@ -88,11 +96,11 @@ impl ChunkedDeduplicationState {
         self.short_hash_set
             .write()
             .map_err(|_| DeduplicationError::WriteLockError)?
-            .insert(short_hash);
+            .insert(short_hash, 0)?;
         self.hash_to_chunked_file_details
             .write()
             .map_err(|_| DeduplicationError::WriteLockError)?
-            .insert(full_hash, FirstChunkedBlockIndex(block_index));
+            .insert(full_hash, block_index)?;
         Ok(())
     }
 }
@@ -49,4 +49,6 @@ pub enum DeduplicationError {
     ReadLockError,
     #[error("Failed to acquire write lock")]
     WriteLockError,
+    #[error("Failed to access spilled hashes: {0}")]
+    SpillError(#[from] std::io::Error),
 }
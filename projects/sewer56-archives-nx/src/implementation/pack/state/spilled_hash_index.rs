@@ -0,0 +1,322 @@
+use super::pack_state::DeduplicationError;
+use crate::headers::types::xxh3sum::{XXH3sum, XXH3sumHashBuilder};
+use crate::prelude::*;
+use alloc::format;
+use core::cmp::Ordering as CmpOrdering;
+use core::mem::{size_of, ManuallyDrop};
+use core::sync::atomic::{AtomicU64, Ordering};
+use hashbrown::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Number of spill files created by this process so far, used to name them.
+static SPILL_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Size of a spilled hash and its value on disk.
+const RECORD_SIZE: usize = size_of::<u64>() + size_of::<u32>();
+
+/// Number of records read from disk per lookup.
+/// The first hash of each page is kept in memory.
+const RECORDS_PER_PAGE: usize = 512;
+
+/// Approximate memory used by each hash kept in memory, including the overhead of the map.
+pub const DEDUP_ENTRY_MEMORY_COST: u64 = (size_of::<(XXH3sum, u32)>() + 1) as u64;
+
+/// Caps the memory used by deduplication indexes, for inputs too large to index in memory
+/// (e.g. backups of terabytes of data).
+///
+/// Once an index reaches the cap, its hashes are sorted and written to a file on disk;
+/// lookups which miss in memory then read a single page of each such file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DedupMemoryLimit {
+    /// Memory the hashes kept in memory may use, in bytes.
+    ///
+    /// Hashes written to disk still use 8 bytes of memory per 512 hashes.
+    pub max_memory_bytes: u64,
+
+    /// Directory the spilled hashes are written to. The files are deleted when the index is dropped.
+    pub spill_directory: PathBuf,
+}
+
+impl DedupMemoryLimit {
+    /// Creates a new memory limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_memory_bytes` - Memory the hashes kept in memory may use, in bytes.
+    /// * `spill_directory` - Directory the spilled hashes are written to.
+    pub fn new(max_memory_bytes: u64, spill_directory: PathBuf) -> Self {
+        Self {
+            max_memory_bytes,
+            spill_directory,
+        }
+    }
+
+    /// Splits the limit evenly between a number of indexes.
+    pub(crate) fn split(&self, count: u64) -> Self {
+        Self::new(
+            self.max_memory_bytes / count.max(1),
+            self.spill_directory.clone(),
+        )
+    }
+}
+
+/// A sorted run of hashes written to the spill file.
+struct SpilledRun {
+    /// Offset of the run within the spill file.
+    offset: u64,
+
+    /// Number of records in the run.
+    len: usize,
+
+    /// First hash of each page of the run.
+    page_starts: Box<[u64]>,
+}
+
+/// The file spilled hashes are written to; deleted on drop.
+struct SpillFile {
+    file: ManuallyDrop<Mutex<File>>,
+    path: PathBuf,
+    length: u64,
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        // Close the file first, as open files can't be deleted on Windows.
+        unsafe { ManuallyDrop::drop(&mut self.file) };
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Maps hashes to values, keeping at most a set number of them in memory.
+///
+/// Past the limit, the hashes in memory are sorted and appended to a file on disk as a 'run',
+/// with the first hash of each page of the run kept in memory. Looking up a hash not in memory
+/// therefore reads at most one page per run. Newer runs take priority over older ones.
+#[derive(Default)]
+pub struct SpilledHashIndex {
+    /// Hashes which have not been spilled yet.
+    memory: HashMap<XXH3sum, u32, XXH3sumHashBuilder>,
+
+    /// Maximum number of hashes kept in memory, and where to spill the rest.
+    /// [`None`] if all hashes are kept in memory.
+    limit: Option<(usize, PathBuf)>,
+
+    /// Runs written to [`Self::spill_file`], oldest first.
+    runs: Vec<SpilledRun>,
+
+    /// Created on the first spill.
+    spill_file: Option<SpillFile>,
+}
+
+impl SpilledHashIndex {
+    /// Creates an index which keeps all hashes in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an index which keeps all hashes in memory, with space for `num_items` hashes.
+    pub fn with_capacity(num_items: usize) -> Self {
+        Self {
+            memory: HashMap::with_capacity_and_hasher(num_items, XXH3sumHashBuilder::default()),
+            ..Self::default()
+        }
+    }
+
+    /// Creates an index which spills hashes to disk past the given memory limit.
+    pub fn with_memory_limit(limit: &DedupMemoryLimit) -> Self {
+        let max_entries = (limit.max_memory_bytes / DEDUP_ENTRY_MEMORY_COST).max(1) as usize;
+        Self {
+            limit: Some((max_entries, limit.spill_directory.clone())),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the number of hashes in memory, and on disk.
+    /// Hashes replaced after being spilled are counted twice.
+    pub fn len(&self) -> usize {
+        self.memory.len() + self.runs.iter().map(|run| run.len).sum::<usize>()
+    }
+
+    /// Returns true if the index holds no hashes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of hashes written to disk.
+    pub fn spilled_len(&self) -> usize {
+        self.runs.iter().map(|run| run.len).sum()
+    }
+
+    /// Reserves space for `num_items` more hashes, up to the memory limit.
+    pub fn reserve(&mut self, num_items: usize) {
+        let num_items = match &self.limit {
+            Some((max_entries, _)) => num_items.min(max_entries.saturating_sub(self.memory.len())),
+            None => num_items,
+        };
+        self.memory.reserve(num_items);
+    }
+
+    /// Adds a hash, replacing its previous value if it was already present.
+    /// Spills the hashes in memory to disk if this reaches the memory limit.
+    pub fn insert(&mut self, hash: XXH3sum, value: u32) -> Result<(), DeduplicationError> {
+        self.memory.insert(hash, value);
+        if let Some((max_entries, _)) = &self.limit {
+            if self.memory.len() >= *max_entries {
+                self.spill()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the value of a hash, reading from disk if it was spilled.
+    pub fn get(&self, hash: XXH3sum) -> Result<Option<u32>, DeduplicationError> {
+        if let Some(value) = self.memory.get(&hash) {
+            return Ok(Some(*value));
+        }
+
+        for run in self.runs.iter().rev() {
+            if let Some(value) = self.find_in_run(run, hash.0)? {
+                return Ok(Some(value));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns true if the index holds a hash.
+    pub fn contains(&self, hash: XXH3sum) -> Result<bool, DeduplicationError> {
+        Ok(self.get(hash)?.is_some())
+    }
+
+    /// Writes all hashes in memory to disk, as a new run.
+    fn spill(&mut self) -> Result<(), DeduplicationError> {
+        let Some((_, directory)) = &self.limit else {
+            return Ok(());
+        };
+
+        if self.spill_file.is_none() {
+            let id = SPILL_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = directory.join(format!("nx-dedup-{}-{id}.tmp", std::process::id()));
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+            self.spill_file = Some(SpillFile {
+                file: ManuallyDrop::new(Mutex::new(file)),
+                path,
+                length: 0,
+            });
+        }
+
+        let mut records: Vec<(u64, u32)> = self
+            .memory
+            .drain()
+            .map(|(hash, value)| (hash.0, value))
+            .collect();
+        records.sort_unstable_by_key(|(hash, _)| *hash);
+
+        let mut data = Vec::with_capacity(records.len() * RECORD_SIZE);
+        let mut page_starts = Vec::with_capacity(records.len().div_ceil(RECORDS_PER_PAGE));
+        for (index, (hash, value)) in records.iter().enumerate() {
+            if index % RECORDS_PER_PAGE == 0 {
+                page_starts.push(*hash);
+            }
+            data.extend_from_slice(&hash.to_le_bytes());
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        // Unwrap is safe: created above.
+        let spill_file = self.spill_file.as_mut().unwrap();
+        let mut file = spill_file
+            .file
+            .lock()
+            .map_err(|_| DeduplicationError::WriteLockError)?;
+        file.seek(SeekFrom::Start(spill_file.length))?;
+        file.write_all(&data)?;
+
+        self.runs.push(SpilledRun {
+            offset: spill_file.length,
+            len: records.len(),
+            page_starts: page_starts.into_boxed_slice(),
+        });
+        spill_file.length += data.len() as u64;
+        Ok(())
+    }
+
+    /// Finds a hash in a run, reading the single page which may hold it.
+    fn find_in_run(&self, run: &SpilledRun, hash: u64) -> Result<Option<u32>, DeduplicationError> {
+        let page = run.page_starts.partition_point(|start| *start <= hash);
+        let (Some(page), Some(spill_file)) = (page.checked_sub(1), &self.spill_file) else {
+            return Ok(None);
+        };
+
+        let first_record = page * RECORDS_PER_PAGE;
+        let num_records = RECORDS_PER_PAGE.min(run.len - first_record);
+        let mut data = vec![0u8; num_records * RECORD_SIZE];
+        {
+            let mut file = spill_file
+                .file
+                .lock()
+                .map_err(|_| DeduplicationError::ReadLockError)?;
+            file.seek(SeekFrom::Start(
+                run.offset + (first_record * RECORD_SIZE) as u64,
+            ))?;
+            file.read_exact(&mut data)?;
+        }
+
+        let (mut low, mut high) = (0, num_records);
+        while low < high {
+            let middle = (low + high) / 2;
+            let record = &data[middle * RECORD_SIZE..(middle + 1) * RECORD_SIZE];
+            match u64::from_le_bytes(record[..8].try_into().unwrap()).cmp(&hash) {
+                CmpOrdering::Less => low = middle + 1,
+                CmpOrdering::Greater => high = middle,
+                CmpOrdering::Equal => {
+                    return Ok(Some(u32::from_le_bytes(record[8..].try_into().unwrap())))
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn finds_hashes_spilled_to_disk() {
+        let temp_dir = TempDir::new().unwrap();
+        let limit = DedupMemoryLimit::new(100 * DEDUP_ENTRY_MEMORY_COST, temp_dir.path().into());
+        let mut index = SpilledHashIndex::with_memory_limit(&limit);
+
+        for x in 0..2000u64 {
+            index
+                .insert(XXH3sum(x.wrapping_mul(0x9E37_79B9_7F4A_7C15)), x as u32)
+                .unwrap();
+        }
+        assert_eq!(index.len(), 2000);
+        assert!(index.spilled_len() >= 1900);
+
+        for x in 0..2000u64 {
+            let hash = XXH3sum(x.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+            assert_eq!(index.get(hash).unwrap(), Some(x as u32));
+        }
+        assert!(!index.contains(XXH3sum(1)).unwrap());
+
+        // Newer values take priority over spilled ones.
+        index.insert(XXH3sum(0), 12345).unwrap();
+        assert_eq!(index.get(XXH3sum(0)).unwrap(), Some(12345));
+
+        drop(index);
+        assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    }
+}
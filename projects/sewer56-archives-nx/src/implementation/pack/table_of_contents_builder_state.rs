@@ -1,19 +1,28 @@
+use crate::headers::{
+    managed::v2::{serialize_table_of_contents_into, BuilderInfo, SerializeError},
+    raw::native_file_header::NativeFileHeader,
+};
 use crate::prelude::*;
-use crate::{api::enums::*, headers::managed::*};
+use crate::{api::enums::*, headers::managed::*, utilities::memory_audit::default_slice_in};
 use ahash::RandomState;
 use hashbrown::HashMap;
+use thiserror_no_std::Error;
 
 /// This contains the shared 'state' used to build the final binary Table of Contents.
 ///
 /// This state is updated by the blocks which write to it during the packing operation.
 ///
+/// External packers which compress blocks themselves can create one with
+/// [`Self::new_initialized`], fill it with the safe setters, and produce the archive header
+/// with [`Self::finalize`]; the compressed blocks are then appended after it.
+///
 /// # Safety
 ///
-/// This struct uses uninitialized memory. It's the caller's responsibility to ensure
-/// that all elements are properly initialized before reading from them. In this case,
-/// this is done by the blocks which write to this state.
+/// When created with [`Self::new`], this struct uses uninitialized memory.
+/// It's the caller's responsibility to ensure that all elements are properly initialized
+/// before reading from them. In this case, this is done by the blocks which write to this state.
 #[allow(dead_code)]
-pub(crate) struct TableOfContentsBuilderState<'a, LongAlloc: Allocator + Clone = Global> {
+pub struct TableOfContentsBuilderState<'a, LongAlloc: Allocator + Clone = Global> {
     /// Used formats for compression of each block.
     pub(crate) block_compressions: Box<[CompressionPreference], LongAlloc>,
 
@@ -30,7 +39,7 @@ pub(crate) struct TableOfContentsBuilderState<'a, LongAlloc: Allocator + Clone =
 
 #[allow(dead_code)]
 impl TableOfContentsBuilderState<'_> {
-    /// Creates a new [TableOfContentsBuilderState] with uninitialized boxes.
+    /// Creates a new [TableOfContentsBuilderState] with default initialized boxes.
     ///
     /// # Arguments
     ///
@@ -39,16 +48,31 @@ impl TableOfContentsBuilderState<'_> {
     ///
     /// # Safety
     ///
-    /// This function creates uninitialized memory. It's the caller's responsibility
+    /// The elements are placeholders. It's the caller's responsibility
     /// to initialize all elements before reading from them.
     pub unsafe fn new(block_count: usize, entry_count: usize) -> Self {
         Self::new_with_allocator(block_count, entry_count, Global)
     }
+
+    /// Creates a new [TableOfContentsBuilderState] with every block and entry set to
+    /// its default value, to be overwritten with the safe setters.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_count` - The number of blocks.
+    /// * `entry_count` - The number of file entries.
+    pub fn new_initialized(block_count: usize, entry_count: usize) -> Self {
+        let mut state = unsafe { Self::new(block_count, entry_count) };
+        state.block_compressions.fill(CompressionPreference::Copy);
+        state.blocks.fill(BlockSize::default());
+        state.entries.fill(FileEntry::default());
+        state
+    }
 }
 
 #[allow(dead_code)]
 impl<'a, LongAlloc: Allocator + Clone> TableOfContentsBuilderState<'a, LongAlloc> {
-    /// Creates a new [TableOfContentsBuilderState] with uninitialized boxes.
+    /// Creates a new [TableOfContentsBuilderState] with default initialized boxes.
     ///
     /// # Arguments
     ///
@@ -58,7 +82,7 @@ impl<'a, LongAlloc: Allocator + Clone> TableOfContentsBuilderState<'a, LongAlloc
     ///
     /// # Safety
     ///
-    /// This function creates uninitialized memory. It's the caller's responsibility
+    /// The elements are placeholders. It's the caller's responsibility
     /// to initialize all elements before reading from them.
     pub unsafe fn new_with_allocator(
         block_count: usize,
@@ -66,10 +90,9 @@ impl<'a, LongAlloc: Allocator + Clone> TableOfContentsBuilderState<'a, LongAlloc
         long_alloc: LongAlloc,
     ) -> Self {
         Self {
-            block_compressions: Box::new_uninit_slice_in(block_count, long_alloc.clone())
-                .assume_init(),
-            blocks: Box::new_uninit_slice_in(block_count, long_alloc.clone()).assume_init(),
-            entries: Box::new_uninit_slice_in(entry_count, long_alloc).assume_init(),
+            block_compressions: default_slice_in(block_count, long_alloc.clone()),
+            blocks: default_slice_in(block_count, long_alloc.clone()),
+            entries: default_slice_in(entry_count, long_alloc),
             relative_path_to_index: HashMap::with_capacity_and_hasher(
                 entry_count,
                 RandomState::new(),
@@ -217,6 +240,84 @@ impl<'a, LongAlloc: Allocator + Clone> TableOfContentsBuilderState<'a, LongAlloc
     pub fn file_name_count(&self) -> usize {
         self.relative_path_to_index.len()
     }
+
+    /// Returns the number of blocks.
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the number of file entries.
+    pub fn entry_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Checks that every file entry refers to blocks and a file path which exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `chunk_size` - Size of the chunks large files were split into.
+    ///
+    /// # Remarks
+    ///
+    /// All blocks and entries must have been set beforehand; this is always the case
+    /// when created with [`TableOfContentsBuilderState::new_initialized`].
+    pub fn validate(&self, chunk_size: u32) -> Result<(), TocBuilderError> {
+        let chunk_size = chunk_size.max(1) as u64;
+        for entry in self.entries.iter() {
+            if entry.file_path_index as usize >= self.entries.len() {
+                return Err(TocBuilderError::FilePathIndexOutOfBounds);
+            }
+
+            // Empty files don't read any block.
+            if entry.decompressed_size == 0 {
+                continue;
+            }
+
+            let block_count = entry.decompressed_size.div_ceil(chunk_size);
+            if entry.first_block_index as u64 + block_count > self.blocks.len() as u64 {
+                return Err(TocBuilderError::BlockIndexOutOfBounds);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates the state, then writes the archive header and Table of Contents.
+    ///
+    /// # Arguments
+    ///
+    /// * `info` - The builder information, from [init_toc_creation].
+    /// * `chunk_size` - Size of the chunks large files were split into.
+    ///
+    /// # Returns
+    ///
+    /// The start of the archive, padded to a multiple of 4096 bytes. The compressed blocks
+    /// follow, in order, each padded to a multiple of 4096 bytes.
+    ///
+    /// [init_toc_creation]: crate::headers::managed::v2::init_toc_creation
+    pub fn finalize<InfoAlloc: Allocator + Clone>(
+        &self,
+        info: &BuilderInfo<InfoAlloc>,
+        chunk_size: u32,
+    ) -> Result<Vec<u8>, TocFinalizeError> {
+        self.validate(chunk_size)
+            .map_err(TocFinalizeError::Invalid)?;
+
+        let header_size = (NativeFileHeader::SIZE_BYTES + info.table_size as usize)
+            .next_multiple_of(NativeFileHeader::HEADER_PAGE_SIZE as usize);
+        let mut result = vec![0u8; header_size];
+        result[..NativeFileHeader::SIZE_BYTES]
+            .copy_from_slice(&NativeFileHeader::init(chunk_size, header_size as u32).to_bytes());
+
+        serialize_table_of_contents_into(
+            &self.block_compressions,
+            &self.blocks,
+            &self.entries,
+            info,
+            &mut result[NativeFileHeader::SIZE_BYTES..],
+        )?;
+        Ok(result)
+    }
 }
 
 /// Error type for TableOfContentsBuilderState operations
@@ -226,11 +327,28 @@ pub enum TocBuilderError {
     IndexOutOfBounds = 0,
     /// Added a file name which was already present.
     DuplicateFileName = 1,
+    /// A file entry refers to a block which does not exist.
+    BlockIndexOutOfBounds = 2,
+    /// A file entry refers to a file path which does not exist.
+    FilePathIndexOutOfBounds = 3,
+}
+
+/// Errors that can occur in [`TableOfContentsBuilderState::finalize`].
+#[derive(Debug, PartialEq, Eq, Error)]
+pub enum TocFinalizeError {
+    /// The state is not valid.
+    #[error("Invalid table of contents: {0:?}")]
+    Invalid(TocBuilderError),
+
+    /// Failed to write the table of contents.
+    #[error("Failed to write the table of contents: {0:?}")]
+    Serialize(#[from] SerializeError),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::headers::{managed::v2::calculate_toc_size, raw::toc::ToCFormat};
 
     #[test]
     fn new_creates_correct_sizes() {
@@ -340,6 +458,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn validate_rejects_dangling_indices() {
+        let mut state = TableOfContentsBuilderState::new_initialized(2, 2);
+        state.set_entry(0, FileEntry::new(0, 100, 0, 0, 0)).unwrap();
+        state.set_entry(1, FileEntry::new(0, 250, 0, 1, 1)).unwrap();
+        assert_eq!(
+            state.validate(200),
+            Err(TocBuilderError::BlockIndexOutOfBounds)
+        );
+        assert_eq!(state.validate(250), Ok(()));
+
+        state.set_entry(1, FileEntry::new(0, 0, 0, 2, 5)).unwrap();
+        assert_eq!(
+            state.validate(250),
+            Err(TocBuilderError::FilePathIndexOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn finalize_writes_readable_header() {
+        let mut state = TableOfContentsBuilderState::new_initialized(1, 1);
+        state.set_block(0, BlockSize::new(5)).unwrap();
+        state
+            .set_block_compression(0, CompressionPreference::ZStandard)
+            .unwrap();
+        state.set_entry(0, FileEntry::new(0, 10, 0, 0, 0)).unwrap();
+
+        let format = ToCFormat::Preset3NoHash;
+        let info = BuilderInfo {
+            format,
+            can_create_chunks: false,
+            table_size: calculate_toc_size(format, 0, 1, 1),
+            max_decomp_block_offset: 0,
+            string_pool: Vec::new(),
+        };
+
+        let header = state.finalize(&info, 1024).unwrap();
+        assert_eq!(header.len(), NativeFileHeader::HEADER_PAGE_SIZE as usize);
+        let toc = unsafe {
+            TableOfContents::deserialize_v2xx(
+                header.as_ptr().add(NativeFileHeader::SIZE_BYTES),
+                info.table_size,
+            )
+        }
+        .unwrap();
+        assert_eq!(toc.blocks[0], BlockSize::new(5));
+        assert_eq!(toc.block_compressions[0], CompressionPreference::ZStandard);
+        assert_eq!(toc.entries[0].decompressed_size, 10);
+    }
+
     #[test]
     fn file_name_hashtable_operations() {
         let mut state = unsafe { TableOfContentsBuilderState::new_with_allocator(1, 1, Global) };
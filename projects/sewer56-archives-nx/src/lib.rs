@@ -17,8 +17,17 @@ pub mod api {
 
     /// Public APIs related to packing.
     pub mod packing {
+        /// Estimates how well files compress, for research and analysis tools.
+        pub mod compression_stats;
+        pub mod directory_rule;
+        /// Warnings raised while packing, with stable codes.
+        pub mod pack_diagnostics;
+        /// Explains where each file would be placed, without packing.
+        pub mod pack_plan;
         pub mod packer_file;
         pub mod packing_settings;
+        /// Picks compression settings by test compressing a sample of the input.
+        pub mod settings_recommendation;
     }
 
     /// This contains traits that are implementable by outside entities
@@ -27,6 +36,78 @@ pub mod api {
 
     /// Public API for starting a packing operation.
     pub mod packer_builder;
+
+    /// Public API for reading files from an existing archive.
+    pub mod archive_reader;
+
+    /// Deduplicates file paths shared between multiple archives.
+    pub mod path_interner;
+
+    /// Maps old file paths to new ones, for files moved between archive versions.
+    pub mod path_aliases;
+
+    /// Public API for creating archives out of already compressed blocks.
+    pub mod raw_archive_writer;
+
+    /// Converts archives between versions of the format, without recompressing them.
+    pub mod archive_conversion;
+
+    /// Moves archives block by block, e.g. for replication over a network.
+    pub mod block_stream;
+
+    /// Repacks an archive, reusing the compressed blocks of unchanged files.
+    pub mod incremental_pack;
+
+    /// Keeps a directory and an archive in sync, in either direction.
+    pub mod directory_sync;
+
+    /// Lists file checksums in a format readable without the Nx library.
+    pub mod checksum_manifest;
+
+    /// Stores data by the hash of its contents, backed by an archive.
+    pub mod content_store;
+
+    /// Detects archives edited by another process, via the generation stored in their header.
+    pub mod archive_generation;
+
+    /// Packs files in batches, saving progress between runs of a process.
+    pub mod pack_session;
+
+    /// Advisory locks keeping processes from editing the same archive at once.
+    pub mod archive_lock;
+
+    /// Estimates the time left to extract files.
+    pub mod extraction_estimate;
+
+    /// Forwards progress and warnings over a channel, e.g. to a UI thread.
+    pub mod progress_events;
+
+    /// Lists the operations an extraction would perform, without extracting.
+    pub mod extraction_plan;
+
+    /// Non-fatal problems found when reading archives.
+    pub mod archive_anomaly;
+
+    /// Finds blocks which could be packed better, e.g. to guide repacking.
+    pub mod block_analysis;
+
+    /// Lists the differences between two versions of an archive.
+    pub mod archive_comparison;
+
+    /// Removes blocks which no file refers to, reclaiming their space.
+    pub mod block_gc;
+
+    /// Finds paths which can't be extracted on every operating system, e.g. case collisions.
+    pub mod portability;
+
+    /// Caps how fast readers decompress data, e.g. when streaming assets in the background.
+    pub mod read_throttle;
+
+    /// Packs a directory or extracts an archive in a single call, with default settings.
+    pub mod one_shot;
+
+    /// Sidecar files with an archive's size and hashes, for cheap tamper checks on mirrors.
+    pub mod archive_sidecar;
 }
 
 /// This module contains all of the data structures that you'll
@@ -50,6 +131,9 @@ pub mod headers {
     /// This represents the unpacked 'managed' version of the headers.
     pub mod managed;
 
+    /// Limits of the archive format, such as the largest number of files in each ToC format.
+    pub mod format_limits;
+
     /// This contains reused traits associated with headers.
     pub mod traits {}
 
@@ -80,6 +164,9 @@ pub mod implementation {
             /// Stores the state used to deduplicate chunked blocks
             pub mod chunked_deduplication_state;
 
+            /// Hash index which spills to disk past a memory limit, used for deduplication
+            pub mod spilled_hash_index;
+
             /// Stores the state used to deduplicate solid blocks
             pub mod solid_deduplication_state;
         }
@@ -97,8 +184,14 @@ pub mod utilities {
         pub mod pack {
             /// Groups the files by extension.
             pub mod group_by_extension;
+            /// Places files with similar contents next to each other.
+            pub mod group_by_similarity;
             /// Creates the blocks from a set of input files.
             pub mod make_blocks;
+            /// Orders the files within a SOLID block.
+            pub mod order_within_block;
+            /// Explains where files are placed by [`make_blocks`](make_blocks::make_blocks).
+            pub mod placement;
         }
     }
 
@@ -108,17 +201,31 @@ pub mod utilities {
     /// Exposes the system information.
     pub mod system_info;
 
+    /// Sets the priority and CPU affinity of packing worker threads.
+    pub mod worker_scheduling;
+
     /// Number related code.
     pub mod math;
 
+    /// Canary allocator and helpers for catching buffer size miscalculations.
+    pub mod memory_audit;
+
+    /// Makes reads and compression fail or stall on demand, for testing failure handling.
+    #[cfg(feature = "fault_injection")]
+    pub mod fault_injection;
+
     /// Code related to I/O and disk operations
     pub mod io {
         /// Searches a given directory and converts it to a list of files.
         pub mod file_finder;
+
+        /// Bounds checked reading of little endian values from slices.
+        pub mod slice_reader;
     }
 
     #[cfg(test)]
     pub mod tests {
+        pub mod archive_for_testing;
         pub mod mock_block;
         pub mod packer_file_for_testing;
         pub mod packing_test_helpers;
@@ -34,7 +34,8 @@ where
     results
 }
 
-fn extract_extension(path: &str) -> &str {
+/// Returns the extension of a path, without the dot. Empty if the path has none.
+pub(crate) fn extract_extension(path: &str) -> &str {
     match path.rfind('.') {
         Some(dot_index) if dot_index > 0 && dot_index < path.len() - 1 => &path[dot_index + 1..],
         _ => "",
@@ -0,0 +1,178 @@
+use crate::api::traits::*;
+use crate::prelude::*;
+use alloc::rc::Rc;
+
+/// Size of each sample of a file hashed by [`content_simhash`].
+pub const SAMPLE_SIZE: u64 = 4096;
+
+/// Number of samples of a file hashed by [`content_simhash`].
+pub const SAMPLE_COUNT: u64 = 4;
+
+/// Maximum number of differing bits between the hashes of files considered similar.
+pub const DEFAULT_MAX_DISTANCE: u32 = 8;
+
+/// Computes a 64-bit simhash of some data.
+///
+/// Unlike a regular hash, similar data produces similar hashes; the number of differing
+/// bits between two hashes approximates how different the data is.
+/// The hash is built from every 4 byte sequence in the data.
+pub fn simhash(data: &[u8]) -> u64 {
+    let mut weights = [0i32; 64];
+    add_to_simhash(data, &mut weights);
+    finish_simhash(&weights)
+}
+
+/// Computes the [`simhash`] of evenly spaced samples of a file.
+///
+/// # Arguments
+///
+/// * `provider` - Provides the data of the file.
+/// * `file_size` - Size of the file.
+///
+/// # Remarks
+///
+/// At most [`SAMPLE_COUNT`] samples of [`SAMPLE_SIZE`] bytes are read;
+/// small files are hashed whole.
+pub fn content_simhash(
+    provider: &dyn InputDataProvider,
+    file_size: u64,
+) -> Result<u64, FileProviderError> {
+    let mut weights = [0i32; 64];
+    if file_size <= SAMPLE_SIZE * SAMPLE_COUNT {
+        add_to_simhash(provider.get_file_data(0, file_size)?.data(), &mut weights);
+    } else {
+        for sample in 0..SAMPLE_COUNT {
+            let offset = (file_size - SAMPLE_SIZE) * sample / (SAMPLE_COUNT - 1);
+            let data = provider.get_file_data(offset, SAMPLE_SIZE)?;
+            add_to_simhash(data.data(), &mut weights);
+        }
+    }
+
+    Ok(finish_simhash(&weights))
+}
+
+/// Reorders a group of files such that files with similar contents are next to each other,
+/// and therefore likely to be placed in the same SOLID block.
+///
+/// Files are clustered greedily, in their current order: each file joins the first cluster
+/// whose first file's hash is within `max_distance` bits of its own, else starts a new one.
+/// The clusters are then concatenated, in order of creation. Order within a cluster is kept,
+/// so a group sorted by size stays sorted within each cluster.
+///
+/// # Arguments
+///
+/// * `files` - The files of a group.
+/// * `max_distance` - Maximum number of differing hash bits for files to be considered similar.
+/// * `simhash_of` - Returns the hash of a file, usually with [`content_simhash`].
+pub fn cluster_by_similarity<T, E>(
+    files: &mut Vec<Rc<T>>,
+    max_distance: u32,
+    mut simhash_of: impl FnMut(&T) -> Result<u64, E>,
+) -> Result<(), E> {
+    // (hash of first file, files)
+    let mut clusters: Vec<(u64, Vec<Rc<T>>)> = Vec::new();
+    for file in files.drain(..) {
+        let hash = simhash_of(&file)?;
+        match clusters
+            .iter_mut()
+            .find(|(first, _)| (first ^ hash).count_ones() <= max_distance)
+        {
+            Some((_, cluster)) => cluster.push(file),
+            None => {
+                let mut cluster = Vec::new();
+                cluster.push(file);
+                clusters.push((hash, cluster));
+            }
+        }
+    }
+
+    for (_, cluster) in clusters {
+        files.extend(cluster);
+    }
+
+    Ok(())
+}
+
+fn add_to_simhash(data: &[u8], weights: &mut [i32; 64]) {
+    for window in data.windows(4) {
+        let feature = u32::from_le_bytes([window[0], window[1], window[2], window[3]]);
+        let hash = mix(feature as u64);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+}
+
+fn finish_simhash(weights: &[i32; 64]) -> u64 {
+    weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |hash, (bit, _)| hash | (1 << bit))
+}
+
+/// Spreads the bits of a value across the whole hash (splitmix64 finalizer).
+fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D049BB133111EB);
+    value ^ (value >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use allocator_api2::vec;
+
+    fn random_data(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn similar_data_has_similar_hash() {
+        let original = random_data(1, 4096);
+        let mut variant = original.clone();
+        variant[100] ^= 0xFF;
+        variant[2000] ^= 0xFF;
+        let unrelated = random_data(2, 4096);
+
+        let hash = simhash(&original);
+        assert!((hash ^ simhash(&variant)).count_ones() <= DEFAULT_MAX_DISTANCE);
+        assert!((hash ^ simhash(&unrelated)).count_ones() > DEFAULT_MAX_DISTANCE);
+    }
+
+    #[test]
+    fn clusters_similar_files_together() {
+        let first = random_data(1, 4096);
+        let second = random_data(2, 4096);
+        let mut first_variant = first.clone();
+        first_variant[10] = 0;
+        let mut second_variant = second.clone();
+        second_variant[10] = 0;
+
+        let mut files = vec![
+            Rc::new(("a", first)),
+            Rc::new(("b", second)),
+            Rc::new(("a_variant", first_variant)),
+            Rc::new(("b_variant", second_variant)),
+        ];
+        cluster_by_similarity(&mut files, DEFAULT_MAX_DISTANCE, |file| {
+            Ok::<_, ()>(simhash(&file.1))
+        })
+        .unwrap();
+
+        let names: Vec<&str> = files.iter().map(|file| file.0).collect();
+        assert_eq!(names, ["a", "a_variant", "b", "b_variant"]);
+    }
+}
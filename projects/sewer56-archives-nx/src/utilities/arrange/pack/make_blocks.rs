@@ -5,12 +5,14 @@ use crate::{
     implementation::pack::blocks::polyfills::{
         Block, ChunkedBlockState, ChunkedFileBlock, SolidBlock,
     },
+    utilities::arrange::pack::placement::PlacementReason,
 };
 use alloc::rc::Rc;
+use alloc::string::String;
 use alloc::sync::Arc;
 use allocator_api2::vec;
 use core::mem::take;
-use hashbrown::HashMap; // esoteric platform safe
+use hashbrown::{HashMap, HashSet}; // esoteric platform safe
 
 // Define the result struct
 pub struct BlocksResult<T> {
@@ -47,11 +49,21 @@ pub struct BlocksResult<T> {
 ///
 /// # Parameters
 ///
-/// - `groups`: A `HashMap` where each key is a file extension, and the value is a list of files with that extension.
+/// - `groups`: Pairs of a file extension and the files with that extension, e.g. a `HashMap`.
+///   Groups are visited in the order given; pass them sorted for a deterministic layout.
 /// - `block_size`: The maximum size of a solid block.
-/// - `chunk_size`: The size to use when chunking oversized files.
+/// - `chunk_size`: The size to use when chunking oversized files without a chunk size of their own.
 /// - `solid_block_algorithm`: The compression preference for solid blocks.
 /// - `chunked_block_algorithm`: The compression preference for chunked blocks.
+/// - `extension_filters`: Filters to apply to each file extension before compression.
+///   Files of a filtered extension never share a SOLID block with files using another filter.
+/// - `auto_block_sizing`: Picks a SOLID block size for each group with [`auto_block_size`],
+///   with `block_size` as the upper limit. Groups with different sizes never share a block.
+/// - `max_files_per_block`: Maximum number of files in a single SOLID block, if limited.
+/// - `solid_size_threshold`: Files larger than this are chunked rather than SOLID packed.
+///   Defaults to (and is capped at) `block_size`, see [`PlacementReason::of`].
+/// - `duplicates`: Relative paths of files which will be deduplicated when packing, and therefore
+///   take no space in their block. SOLID blocks left less than half full by these are merged.
 ///
 /// # Returns
 ///
@@ -73,12 +85,18 @@ pub struct BlocksResult<T> {
 ///
 /// [`sort_lexicographically`]: crate::utilities::arrange::sort_lexicographically
 /// [`group_by_extension`]: crate::utilities::arrange::pack::group_by_extension
-pub fn make_blocks<T>(
-    groups: HashMap<&str, Vec<Rc<T>>>,
+#[allow(clippy::too_many_arguments)]
+pub fn make_blocks<'g, T>(
+    groups: impl IntoIterator<Item = (&'g str, Vec<Rc<T>>)>,
     block_size: u32,
     chunk_size: u32,
     mut solid_block_algorithm: CompressionPreference,
     mut chunked_block_algorithm: CompressionPreference,
+    extension_filters: &HashMap<String, DataFilter>,
+    auto_block_sizing: bool,
+    max_files_per_block: Option<u32>,
+    solid_size_threshold: Option<u32>,
+    duplicates: &HashSet<&str>,
 ) -> BlocksResult<T>
 where
     T: HasFileSize
@@ -89,9 +107,13 @@ where
         + 'static,
 {
     let mut chunked_blocks: Vec<Box<dyn Block<T>>> = Vec::new();
-    let mut solid_blocks: Vec<(u64, Box<dyn Block<T>>)> = Vec::new();
+    let mut solid_blocks: Vec<SolidEntry<T>> = Vec::new();
     let mut current_block: Vec<Rc<T>> = Vec::new();
     let mut current_block_size: u64 = 0; // Must be u64 because file sizes can exceed u32
+    let mut current_filter = DataFilter::None;
+    let mut current_block_limit = block_size;
+    let max_files_per_block = max_files_per_block.map_or(usize::MAX, |max| max.max(1) as usize);
+    let solid_size_threshold = solid_size_threshold.map_or(block_size, |x| x.min(block_size));
 
     // Default algorithms if no preference is specified
     if solid_block_algorithm == CompressionPreference::NoPreference {
@@ -104,54 +126,105 @@ where
 
     // Make the blocks
     let mut dict_index = 0_u32;
-    for (_key, values) in groups {
-        for item in values {
-            // If the item is too big, it's getting chunked, regardless of preference.
-            // We treat items above the block size as chunked, they are 'single chunk' files.
-            if item.file_size() > block_size as u64 {
-                chunk_item(
-                    &item,
-                    &mut chunked_blocks,
-                    chunk_size,
-                    chunked_block_algorithm,
+    for (key, values) in groups {
+        // Filtered data must not be mixed with data using a different filter,
+        // so flush the current block when the filter changes.
+        let filter = extension_filters
+            .get(key)
+            .copied()
+            .unwrap_or(DataFilter::None);
+
+        let group_block_size = if auto_block_sizing {
+            auto_block_size(&values, block_size)
+        } else {
+            block_size
+        };
+
+        if filter != current_filter || group_block_size != current_block_limit {
+            if !current_block.is_empty() {
+                solid_blocks.push(SolidEntry::Pending(PendingBlock {
+                    items: take(&mut current_block),
+                    size: current_block_size,
                     dict_index,
-                );
-                continue;
+                    filter: current_filter,
+                    limit: current_block_limit,
+                }));
+                current_block_size = 0;
             }
+            current_filter = filter;
+            current_block_limit = group_block_size;
+        }
 
-            // If the item should not be put in a SOLID block, it
-            // will be put in a separate block.
-            if item.solid_type() == SolidPreference::NoSolid {
-                solid_blocks.push((
-                    item.file_size(),
-                    unsize_box2!(Box::new(SolidBlock::new(
-                        vec![item.clone()],
-                        item.compression_preference(),
+        for item in values {
+            match PlacementReason::of(&*item, solid_size_threshold) {
+                // If the item is too big, it's getting chunked, regardless of preference.
+                // We treat items above the threshold as chunked, they are 'single chunk' files.
+                PlacementReason::Chunked { .. } => {
+                    chunk_item(
+                        &item,
+                        &mut chunked_blocks,
+                        chunk_size,
+                        chunked_block_algorithm,
                         dict_index,
-                    ))),
-                ));
-                continue;
+                        filter,
+                    );
+                    continue;
+                }
+
+                // Files read often get a block of their own, with fast compression,
+                // so reading them never decompresses anything else.
+                PlacementReason::ForcedOwnBlock => {
+                    solid_blocks.push(SolidEntry::Final(
+                        item.file_size(),
+                        new_solid_block(
+                            vec![item.clone()],
+                            CompressionPreference::Lz4,
+                            dict_index,
+                            filter,
+                            None,
+                        ),
+                    ));
+                    continue;
+                }
+
+                // If the item should not be put in a SOLID block, it
+                // will be put in a separate block.
+                PlacementReason::NoSolid => {
+                    solid_blocks.push(SolidEntry::Final(
+                        item.file_size(),
+                        unsize_box2!(Box::new(
+                            SolidBlock::new(
+                                vec![item.clone()],
+                                item.compression_preference(),
+                                dict_index,
+                            )
+                            .with_filter(filter)
+                        )),
+                    ));
+                    continue;
+                }
+
+                PlacementReason::Solid | PlacementReason::ManualBlock => {}
             }
 
             // Check if the item fits in the current block
             // SAFETY: Block size is limited to 1GiB (fits in 32-bit range)
-            if current_block_size + item.file_size() <= block_size as u64 {
+            if current_block_size + item.file_size() <= current_block_limit as u64
+                && current_block.len() < max_files_per_block
+            {
                 // [Hot Path] Add item to SOLID block
                 current_block.push(item.clone());
                 current_block_size += item.file_size();
             } else {
                 // [Cold Path] Add the current block if it has any items and start a new block
                 if !current_block.is_empty() {
-                    let cloned = current_block.clone();
-                    solid_blocks.push((
-                        current_block_size,
-                        unsize_box2!(Box::new(SolidBlock::new(
-                            cloned,
-                            solid_block_algorithm,
-                            dict_index
-                        ))),
-                    ));
-                    current_block.clear();
+                    solid_blocks.push(SolidEntry::Pending(PendingBlock {
+                        items: take(&mut current_block),
+                        size: current_block_size,
+                        dict_index,
+                        filter: current_filter,
+                        limit: current_block_limit,
+                    }));
                 }
                 current_block.push(item.clone());
                 current_block_size = item.file_size();
@@ -163,16 +236,36 @@ where
 
     // If we have any items left, make sure to append them
     if !current_block.is_empty() {
-        solid_blocks.push((
-            current_block_size,
-            unsize_box2!(Box::new(SolidBlock::new(
-                take(&mut current_block),
-                solid_block_algorithm,
-                dict_index,
-            ))),
-        ));
+        solid_blocks.push(SolidEntry::Pending(PendingBlock {
+            items: take(&mut current_block),
+            size: current_block_size,
+            dict_index,
+            filter: current_filter,
+            limit: current_block_limit,
+        }));
     }
 
+    if !duplicates.is_empty() {
+        merge_underfilled_blocks(&mut solid_blocks, duplicates, max_files_per_block);
+    }
+
+    let mut solid_blocks: Vec<(u64, Box<dyn Block<T>>)> = solid_blocks
+        .into_iter()
+        .map(|entry| match entry {
+            SolidEntry::Final(size, block) => (size, block),
+            SolidEntry::Pending(block) => (
+                block.size,
+                new_solid_block(
+                    block.items,
+                    solid_block_algorithm,
+                    block.dict_index,
+                    block.filter,
+                    auto_block_sizing.then_some(block.limit),
+                ),
+            ),
+        })
+        .collect();
+
     // Sort the SOLID blocks by size in descending order
     // This speeds up packing, by ensuring thread that picks up last block has least work at end of operation.
     solid_blocks.sort_by(|a, b| b.0.cmp(&a.0));
@@ -196,6 +289,135 @@ where
     }
 }
 
+/// A SOLID block being built by [`make_blocks`].
+enum SolidEntry<T> {
+    /// Files packed together, which may still be merged with another block's files.
+    Pending(PendingBlock<T>),
+
+    /// A block which is never merged, e.g. one holding a single file.
+    Final(u64, Box<dyn Block<T>>),
+}
+
+struct PendingBlock<T> {
+    items: Vec<Rc<T>>,
+    /// Total size of the items; without duplicates after [`merge_underfilled_blocks`].
+    size: u64,
+    dict_index: u32,
+    filter: DataFilter,
+    /// Maximum size of the block.
+    limit: u32,
+}
+
+/// Merges SOLID blocks which are under-filled once duplicate files are removed from them.
+///
+/// Duplicates are stored only once when packing, so with many duplicates, blocks made
+/// for the full set of files end up with little data; merging them improves the ratio.
+/// A block is merged into the previous one if either is less than half full, they fit
+/// within the block size together, and they use the same filter and block size.
+///
+/// # Parameters
+///
+/// - `blocks`: The blocks made so far, in order.
+/// - `duplicates`: Relative paths of the files which will be deduplicated.
+/// - `max_files_per_block`: Maximum number of files in a single SOLID block.
+fn merge_underfilled_blocks<T: HasFileSize + HasRelativePath>(
+    blocks: &mut Vec<SolidEntry<T>>,
+    duplicates: &HashSet<&str>,
+    max_files_per_block: usize,
+) {
+    let mut result: Vec<SolidEntry<T>> = Vec::with_capacity(blocks.len());
+    let mut last_pending: Option<usize> = None;
+    for entry in blocks.drain(..) {
+        let mut block = match entry {
+            SolidEntry::Pending(block) => block,
+            final_block => {
+                result.push(final_block);
+                continue;
+            }
+        };
+
+        block.size = block
+            .items
+            .iter()
+            .filter(|item| !duplicates.contains(item.relative_path()))
+            .map(|item| item.file_size())
+            .sum();
+
+        if let Some(SolidEntry::Pending(last)) = last_pending.and_then(|x| result.get_mut(x)) {
+            let half_full = last.limit as u64 / 2;
+            if (last.size < half_full || block.size < half_full)
+                && last.filter == block.filter
+                && last.limit == block.limit
+                && last.size + block.size <= last.limit as u64
+                && last.items.len() + block.items.len() <= max_files_per_block
+            {
+                last.size += block.size;
+                last.items.extend(block.items);
+                continue;
+            }
+        }
+
+        last_pending = Some(result.len());
+        result.push(SolidEntry::Pending(block));
+    }
+
+    *blocks = result;
+}
+
+/// Lower bound of block sizes picked by [`auto_block_size`]. (128KiB)
+pub const AUTO_MIN_BLOCK_SIZE: u32 = 131_071;
+
+/// Number of files of typical (median) size [`auto_block_size`] aims to fit in a block.
+const AUTO_FILES_PER_BLOCK: u64 = 32;
+
+/// Picks a SOLID block size for a group of files, based on the distribution of their sizes.
+///
+/// Groups of tiny files (e.g. configs) get small blocks, so reading any one of them
+/// decompresses little extra data. Groups of larger files (e.g. textures) get larger
+/// blocks, to improve the compression ratio.
+///
+/// # Parameters
+///
+/// - `files`: The files of the group, sorted ascending by size.
+/// - `max_block_size`: The largest block size that may be returned.
+///
+/// # Returns
+///
+/// A power of 2 minus 1, as with [`PackingSettings::block_size`].
+///
+/// [`PackingSettings::block_size`]: crate::api::packing::packing_settings::PackingSettings::block_size
+pub fn auto_block_size<T: HasFileSize>(files: &[Rc<T>], max_block_size: u32) -> u32 {
+    let median = files
+        .get(files.len() / 2)
+        .map_or(0, |file| file.file_size());
+    let target = (median * AUTO_FILES_PER_BLOCK)
+        .max(1)
+        .next_power_of_two()
+        .saturating_sub(1);
+
+    target.clamp(
+        AUTO_MIN_BLOCK_SIZE.min(max_block_size) as u64,
+        max_block_size as u64,
+    ) as u32
+}
+
+fn new_solid_block<T>(
+    items: Vec<Rc<T>>,
+    algorithm: CompressionPreference,
+    dict_index: u32,
+    filter: DataFilter,
+    block_size: Option<u32>,
+) -> Box<dyn Block<T>>
+where
+    T: HasFileSize + CanProvideInputData + HasRelativePath + 'static,
+{
+    let mut block = SolidBlock::new(items, algorithm, dict_index).with_filter(filter);
+    if let Some(block_size) = block_size {
+        block = block.with_block_size(block_size);
+    }
+    unsize_box2!(Box::new(block))
+}
+
 // Implement the chunk_item function
 fn chunk_item<T>(
     item: &Rc<T>,
@@ -203,6 +425,7 @@ fn chunk_item<T>(
     chunk_size: u32,
     mut chunked_block_algorithm: CompressionPreference,
     dict_index: u32,
+    filter: DataFilter,
 ) where
     T: HasFileSize
         + HasSolidType
@@ -211,6 +434,7 @@ fn chunk_item<T>(
         + HasRelativePath
         + 'static,
 {
+    let chunk_size = item.chunk_size_override().unwrap_or(chunk_size);
     let size_left = item.file_size();
     let num_iterations = (size_left / chunk_size as u64) as u32;
     let remaining_size = (size_left % chunk_size as u64) as u32;
@@ -225,12 +449,15 @@ fn chunk_item<T>(
         chunked_block_algorithm = CompressionPreference::ZStandard;
     }
 
-    let state = Arc::new(ChunkedBlockState::new(
-        chunked_block_algorithm,
-        num_chunks,
-        item.clone(),
-        dict_index,
-    ));
+    let state = Arc::new(
+        ChunkedBlockState::new(
+            chunked_block_algorithm,
+            num_chunks,
+            item.clone(),
+            dict_index,
+        )
+        .with_filter(filter),
+    );
 
     let mut current_offset = 0_u64;
     for x in 0..num_iterations {
@@ -258,7 +485,7 @@ mod tests {
     use super::*;
     use alloc::rc::Rc;
     use allocator_api2::vec;
-    use hashbrown::HashMap;
+    use hashbrown::{HashMap, HashSet};
 
     #[derive(Clone)]
     struct PackerFileForTesting {
@@ -266,6 +493,7 @@ mod tests {
         relative_path: String,
         solid_type: SolidPreference,
         compression_preference: CompressionPreference,
+        force_own_block: bool,
     }
 
     // Implement the required traits for PackerFileForTesting
@@ -289,6 +517,10 @@ mod tests {
         fn set_solid_type(&mut self, preference: SolidPreference) {
             self.solid_type = preference;
         }
+
+        fn force_own_block(&self) -> bool {
+            self.force_own_block
+        }
     }
 
     impl HasCompressionPreference for PackerFileForTesting {
@@ -323,24 +555,28 @@ mod tests {
                         relative_path: "Block0File0".to_string(),
                         solid_type: SolidPreference::Default,
                         compression_preference: CompressionPreference::NoPreference,
+                        force_own_block: false,
                     }),
                     Rc::new(PackerFileForTesting {
                         file_size: 8,
                         relative_path: "Block0File1".to_string(),
                         solid_type: SolidPreference::Default,
                         compression_preference: CompressionPreference::NoPreference,
+                        force_own_block: false,
                     }),
                     Rc::new(PackerFileForTesting {
                         file_size: 1,
                         relative_path: "Block0File2".to_string(),
                         solid_type: SolidPreference::Default,
                         compression_preference: CompressionPreference::NoPreference,
+                        force_own_block: false,
                     }),
                     Rc::new(PackerFileForTesting {
                         file_size: 1,
                         relative_path: "Block1File0".to_string(),
                         solid_type: SolidPreference::Default,
                         compression_preference: CompressionPreference::NoPreference,
+                        force_own_block: false,
                     }),
                 ],
             );
@@ -354,6 +590,11 @@ mod tests {
             u32::MAX,
             CompressionPreference::Lz4,
             CompressionPreference::NoPreference,
+            &HashMap::new(),
+            false,
+            None,
+            None,
+            &HashSet::new(),
         );
 
         // Assert
@@ -410,24 +651,28 @@ mod tests {
                         relative_path: "Block0File0".to_string(),
                         solid_type: SolidPreference::Default,
                         compression_preference: CompressionPreference::NoPreference,
+                        force_own_block: false,
                     }),
                     Rc::new(PackerFileForTesting {
                         file_size: 8,
                         relative_path: "Block0File1".to_string(),
                         solid_type: SolidPreference::Default,
                         compression_preference: CompressionPreference::NoPreference,
+                        force_own_block: false,
                     }),
                     Rc::new(PackerFileForTesting {
                         file_size: 5,
                         relative_path: "Block1File0".to_string(),
                         solid_type: SolidPreference::Default,
                         compression_preference: CompressionPreference::NoPreference,
+                        force_own_block: false,
                     }),
                     Rc::new(PackerFileForTesting {
                         file_size: 1,
                         relative_path: "Block1File1".to_string(),
                         solid_type: SolidPreference::Default,
                         compression_preference: CompressionPreference::NoPreference,
+                        force_own_block: false,
                     }),
                 ],
             );
@@ -441,6 +686,11 @@ mod tests {
             u32::MAX,
             CompressionPreference::Lz4,
             CompressionPreference::NoPreference,
+            &HashMap::new(),
+            false,
+            None,
+            None,
+            &HashSet::new(),
         );
 
         // Assert
@@ -496,24 +746,28 @@ mod tests {
                         relative_path: "Block1File0".to_string(),
                         solid_type: SolidPreference::Default,
                         compression_preference: CompressionPreference::NoPreference,
+                        force_own_block: false,
                     }),
                     Rc::new(PackerFileForTesting {
                         file_size: 8,
                         relative_path: "Block0File0".to_string(),
                         solid_type: SolidPreference::NoSolid,
                         compression_preference: CompressionPreference::Lz4,
+                        force_own_block: false,
                     }),
                     Rc::new(PackerFileForTesting {
                         file_size: 1,
                         relative_path: "Block1File1".to_string(),
                         solid_type: SolidPreference::Default,
                         compression_preference: CompressionPreference::NoPreference,
+                        force_own_block: false,
                     }),
                     Rc::new(PackerFileForTesting {
                         file_size: 1,
                         relative_path: "Block1File2".to_string(),
                         solid_type: SolidPreference::Default,
                         compression_preference: CompressionPreference::NoPreference,
+                        force_own_block: false,
                     }),
                 ],
             );
@@ -528,6 +782,11 @@ mod tests {
             u32::MAX,
             CompressionPreference::ZStandard,
             CompressionPreference::NoPreference,
+            &HashMap::new(),
+            false,
+            None,
+            None,
+            &HashSet::new(),
         );
 
         // Assert
@@ -583,6 +842,7 @@ mod tests {
                     relative_path: "ChunkedFile".to_string(),
                     solid_type: SolidPreference::Default,
                     compression_preference: CompressionPreference::NoPreference,
+                    force_own_block: false,
                 })],
             );
             map
@@ -595,6 +855,11 @@ mod tests {
             chunk_size,
             CompressionPreference::NoPreference,
             CompressionPreference::ZStandard,
+            &HashMap::new(),
+            false,
+            None,
+            None,
+            &HashSet::new(),
         );
 
         // Assert
@@ -646,4 +911,306 @@ mod tests {
         assert_eq!(chunked_block2.chunk_index, 2);
         assert_eq!(chunked_block2.chunk_size, 5);
     }
+
+    /// Test that `make_blocks` records extension filters and does not mix filtered files with others.
+    ///
+    /// **Scenario:** Two small files of different extensions would fit in one SOLID block,
+    /// but one of the extensions has a filter. Each should end up in its own block.
+    #[test]
+    fn make_blocks_separates_filtered_extensions() {
+        // Setup
+        let new_file = |path: &str| {
+            Rc::new(PackerFileForTesting {
+                file_size: 1,
+                relative_path: path.to_string(),
+                solid_type: SolidPreference::Default,
+                compression_preference: CompressionPreference::NoPreference,
+                force_own_block: false,
+            })
+        };
+
+        let mut items = HashMap::new();
+        items.insert("mesh", vec![new_file("a.mesh")]);
+        items.insert("txt", vec![new_file("a.txt")]);
+
+        let mut filters = HashMap::new();
+        filters.insert("mesh".to_string(), DataFilter::ByteTranspose4);
+
+        // Act
+        let result = make_blocks(
+            items,
+            10,
+            u32::MAX,
+            CompressionPreference::ZStandard,
+            CompressionPreference::ZStandard,
+            &filters,
+            false,
+            None,
+            None,
+            &HashSet::new(),
+        );
+
+        // Assert
+        assert_eq!(result.blocks.len(), 2);
+        for block in &result.blocks {
+            let expected = if block.items()[0].relative_path == "a.mesh" {
+                DataFilter::ByteTranspose4
+            } else {
+                DataFilter::None
+            };
+            assert_eq!(block.filter(), expected);
+        }
+    }
+
+    /// Test that `make_blocks` picks a block size per group when auto block sizing is enabled.
+    ///
+    /// **Scenario:** A group of tiny configs and a group of large textures.
+    /// The configs get the smallest automatic block size, while the textures get the maximum.
+    #[test]
+    fn make_blocks_picks_block_size_per_group() {
+        // Setup
+        let new_file = |path: &str, file_size: u64| {
+            Rc::new(PackerFileForTesting {
+                file_size,
+                relative_path: path.to_string(),
+                solid_type: SolidPreference::Default,
+                compression_preference: CompressionPreference::NoPreference,
+                force_own_block: false,
+            })
+        };
+
+        let mut items = HashMap::new();
+        items.insert("ini", vec![new_file("a.ini", 100), new_file("b.ini", 200)]);
+        items.insert(
+            "dds",
+            vec![new_file("a.dds", 2_000_000), new_file("b.dds", 4_000_000)],
+        );
+
+        // Act
+        let result = make_blocks(
+            items,
+            16_777_215,
+            u32::MAX,
+            CompressionPreference::ZStandard,
+            CompressionPreference::ZStandard,
+            &HashMap::new(),
+            true,
+            None,
+            None,
+            &HashSet::new(),
+        );
+
+        // Assert
+        assert_eq!(result.blocks.len(), 2);
+        for block in &result.blocks {
+            let expected = if block.items()[0].relative_path.ends_with(".ini") {
+                AUTO_MIN_BLOCK_SIZE
+            } else {
+                16_777_215
+            };
+            assert_eq!(block.block_size(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn auto_block_size_is_clamped_to_max() {
+        let files = vec![Rc::new(PackerFileForTesting {
+            file_size: 1_000_000,
+            relative_path: "a.bin".to_string(),
+            solid_type: SolidPreference::Default,
+            compression_preference: CompressionPreference::NoPreference,
+            force_own_block: false,
+        })];
+
+        assert_eq!(auto_block_size(&files, 4_194_303), 4_194_303);
+        assert_eq!(auto_block_size(&files, 65_535), 65_535);
+    }
+
+    /// Test that `make_blocks` starts a new SOLID block once the file limit is reached,
+    /// even if the block has space left.
+    #[test]
+    fn make_blocks_respects_max_files_per_block() {
+        // Setup
+        let files = (0..5)
+            .map(|x| {
+                Rc::new(PackerFileForTesting {
+                    file_size: 1,
+                    relative_path: format!("{x}.txt"),
+                    solid_type: SolidPreference::Default,
+                    compression_preference: CompressionPreference::NoPreference,
+                    force_own_block: false,
+                })
+            })
+            .collect();
+
+        let mut items = HashMap::new();
+        items.insert("txt", files);
+
+        // Act
+        let result = make_blocks(
+            items,
+            1000,
+            u32::MAX,
+            CompressionPreference::ZStandard,
+            CompressionPreference::ZStandard,
+            &HashMap::new(),
+            false,
+            Some(2),
+            None,
+            &HashSet::new(),
+        );
+
+        // Assert
+        let mut counts: Vec<usize> = result.blocks.iter().map(|x| x.items().len()).collect();
+        counts.sort();
+        assert_eq!(counts, vec![1, 2, 2]);
+    }
+
+    /// Test that files flagged with `force_own_block` are stored alone with fast compression.
+    ///
+    /// **Scenario:** A small manifest which would otherwise share a SOLID block with other files
+    /// is flagged to be stored on its own.
+    ///
+    /// **Expected:** The manifest is alone in an LZ4 block, while the other files share a block.
+    #[test]
+    fn make_blocks_stores_forced_file_in_own_block() {
+        // Setup
+        let files = ["a.json", "manifest.json", "b.json"]
+            .iter()
+            .map(|path| {
+                Rc::new(PackerFileForTesting {
+                    file_size: 10,
+                    relative_path: path.to_string(),
+                    solid_type: SolidPreference::Default,
+                    compression_preference: CompressionPreference::NoPreference,
+                    force_own_block: *path == "manifest.json",
+                })
+            })
+            .collect();
+
+        let mut items = HashMap::new();
+        items.insert("json", files);
+
+        // Act
+        let result = make_blocks(
+            items,
+            1000,
+            u32::MAX,
+            CompressionPreference::ZStandard,
+            CompressionPreference::ZStandard,
+            &HashMap::new(),
+            false,
+            None,
+            None,
+            &HashSet::new(),
+        );
+
+        // Assert
+        assert_eq!(result.blocks.len(), 2);
+        let own_block = result
+            .blocks
+            .iter()
+            .filter_map(|x| {
+                x.as_any()
+                    .downcast_ref::<SolidBlock<PackerFileForTesting>>()
+            })
+            .find(|x| x.items.len() == 1)
+            .expect("Expected a block with only the manifest");
+
+        assert_eq!(own_block.items[0].relative_path, "manifest.json");
+        assert_eq!(own_block.compression_preference, CompressionPreference::Lz4);
+    }
+
+    /// Test that files above the SOLID size threshold are chunked, even if they fit in a block.
+    #[test]
+    fn make_blocks_chunks_files_above_solid_size_threshold() {
+        // Setup
+        let mut items = HashMap::new();
+        items.insert(
+            "bin",
+            vec![
+                Rc::new(PackerFileForTesting {
+                    file_size: 50,
+                    relative_path: "small.bin".to_string(),
+                    solid_type: SolidPreference::Default,
+                    compression_preference: CompressionPreference::NoPreference,
+                    force_own_block: false,
+                }),
+                Rc::new(PackerFileForTesting {
+                    file_size: 200,
+                    relative_path: "large.bin".to_string(),
+                    solid_type: SolidPreference::Default,
+                    compression_preference: CompressionPreference::NoPreference,
+                    force_own_block: false,
+                }),
+            ],
+        );
+
+        // Act
+        let result = make_blocks(
+            items,
+            1000,
+            u32::MAX,
+            CompressionPreference::ZStandard,
+            CompressionPreference::ZStandard,
+            &HashMap::new(),
+            false,
+            None,
+            Some(100),
+            &HashSet::new(),
+        );
+
+        // Assert
+        assert_eq!(result.num_chunked_blocks, 1);
+        assert_eq!(result.num_solid_blocks, 1);
+        assert_eq!(result.blocks[0].items()[0].relative_path, "large.bin");
+    }
+
+    /// Test that blocks left under-filled by deduplication are merged.
+    ///
+    /// **Scenario:** Four files of size 4 make two blocks of size 8 with a block size of 10,
+    /// but one file in each block is a duplicate, leaving only 4 bytes in each.
+    ///
+    /// **Expected:** The two blocks are merged into one.
+    #[test]
+    fn make_blocks_merges_blocks_underfilled_by_duplicates() {
+        // Setup
+        let files: Vec<Rc<PackerFileForTesting>> = ["a.txt", "b.txt", "c.txt", "d.txt"]
+            .iter()
+            .map(|path| {
+                Rc::new(PackerFileForTesting {
+                    file_size: 4,
+                    relative_path: path.to_string(),
+                    solid_type: SolidPreference::Default,
+                    compression_preference: CompressionPreference::NoPreference,
+                    force_own_block: false,
+                })
+            })
+            .collect();
+
+        let make = |duplicates: &HashSet<&str>| {
+            let mut items = HashMap::new();
+            items.insert("txt", files.clone());
+            make_blocks(
+                items,
+                10,
+                u32::MAX,
+                CompressionPreference::ZStandard,
+                CompressionPreference::ZStandard,
+                &HashMap::new(),
+                false,
+                None,
+                None,
+                duplicates,
+            )
+        };
+
+        // Act & Assert
+        assert_eq!(make(&HashSet::new()).num_solid_blocks, 2);
+
+        let duplicates: HashSet<&str> = ["b.txt", "d.txt"].into_iter().collect();
+        let result = make(&duplicates);
+        assert_eq!(result.num_solid_blocks, 1);
+        assert_eq!(result.blocks[0].items().len(), 4);
+    }
 }
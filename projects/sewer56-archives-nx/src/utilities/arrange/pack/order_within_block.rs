@@ -0,0 +1,87 @@
+use crate::api::{enums::BlockOrdering, traits::*};
+use crate::prelude::*;
+use crate::utilities::arrange::pack::group_by_similarity::{
+    cluster_by_similarity, DEFAULT_MAX_DISTANCE,
+};
+use alloc::rc::Rc;
+
+/// Orders the files of a single SOLID block.
+///
+/// # Arguments
+///
+/// * `items` - The files in the block.
+/// * `ordering` - The order to place the files in.
+/// * `simhash_of` - Returns the similarity hash of a file, usually with
+///   [`content_simhash`]. Only called for [`BlockOrdering::ContentSimilarity`].
+///
+/// [`content_simhash`]: crate::utilities::arrange::pack::group_by_similarity::content_simhash
+pub fn order_within_block<T, E>(
+    items: &mut Vec<Rc<T>>,
+    ordering: BlockOrdering,
+    simhash_of: impl FnMut(&T) -> Result<u64, E>,
+) -> Result<(), E>
+where
+    T: HasFileSize + HasRelativePath,
+{
+    match ordering {
+        BlockOrdering::SizeAscending => sort_by_size(items),
+        BlockOrdering::Path => items.sort_by(|a, b| a.relative_path().cmp(b.relative_path())),
+        BlockOrdering::ContentSimilarity => {
+            sort_by_size(items);
+            cluster_by_similarity(items, DEFAULT_MAX_DISTANCE, simhash_of)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn sort_by_size<T: HasFileSize + HasRelativePath>(items: &mut [Rc<T>]) {
+    items.sort_by(|a, b| {
+        a.file_size()
+            .cmp(&b.file_size())
+            .then_with(|| a.relative_path().cmp(b.relative_path()))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::tests::packer_file_for_testing::PackerFileForTesting;
+    use allocator_api2::vec;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(BlockOrdering::SizeAscending, ["b/small", "c/medium", "a/large"])]
+    #[case(BlockOrdering::Path, ["a/large", "b/small", "c/medium"])]
+    fn orders_items(#[case] ordering: BlockOrdering, #[case] expected: [&str; 3]) {
+        let mut items = vec![
+            PackerFileForTesting::new_rc("a/large", 300),
+            PackerFileForTesting::new_rc("b/small", 100),
+            PackerFileForTesting::new_rc("c/medium", 200),
+        ];
+        order_within_block(&mut items, ordering, |_| Ok::<_, ()>(0)).unwrap();
+
+        let paths: Vec<&str> = items.iter().map(|x| x.relative_path()).collect();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn content_similarity_groups_by_hash() {
+        let mut items = vec![
+            PackerFileForTesting::new_rc("a", 100),
+            PackerFileForTesting::new_rc("b", 200),
+            PackerFileForTesting::new_rc("a_variant", 300),
+        ];
+        order_within_block(&mut items, BlockOrdering::ContentSimilarity, |item| {
+            Ok::<_, ()>(if item.relative_path().starts_with('a') {
+                0
+            } else {
+                u64::MAX
+            })
+        })
+        .unwrap();
+
+        let paths: Vec<&str> = items.iter().map(|x| x.relative_path()).collect();
+        assert_eq!(paths, ["a", "a_variant", "b"]);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::api::{enums::*, traits::*};
+use core::fmt;
+
+/// Why [`make_blocks`] placed a file in the kind of block it did.
+///
+/// The reasons are checked in the order they are declared; the first that applies wins.
+///
+/// [`make_blocks`]: crate::utilities::arrange::pack::make_blocks::make_blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementReason {
+    /// The file is larger than the SOLID size threshold, so it is split into chunks.
+    Chunked {
+        /// Size of the file.
+        file_size: u64,
+        /// Largest size of a file which is still SOLID packed.
+        threshold: u32,
+    },
+
+    /// The file was flagged to be stored alone in its own block.
+    ForcedOwnBlock,
+
+    /// The file asked not to be SOLID packed, so it is alone in its block.
+    NoSolid,
+
+    /// The file shares a SOLID block with other files.
+    Solid,
+
+    /// The file was assigned a block by [`PackingSettings::manual_blocks`].
+    /// Never returned by [`PlacementReason::of`].
+    ///
+    /// [`PackingSettings::manual_blocks`]: crate::api::packing::packing_settings::PackingSettings::manual_blocks
+    ManualBlock,
+}
+
+impl PlacementReason {
+    /// Determines where a file will be placed.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The file to place.
+    /// * `solid_size_threshold` - Largest size of a file which is still SOLID packed.
+    pub fn of<T>(item: &T, solid_size_threshold: u32) -> Self
+    where
+        T: HasFileSize + HasSolidType,
+    {
+        if item.file_size() > solid_size_threshold as u64 {
+            PlacementReason::Chunked {
+                file_size: item.file_size(),
+                threshold: solid_size_threshold,
+            }
+        } else if item.force_own_block() {
+            PlacementReason::ForcedOwnBlock
+        } else if item.solid_type() == SolidPreference::NoSolid {
+            PlacementReason::NoSolid
+        } else {
+            PlacementReason::Solid
+        }
+    }
+}
+
+impl fmt::Display for PlacementReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlacementReason::Chunked {
+                file_size,
+                threshold,
+            } => write!(
+                f,
+                "chunked: size {file_size} exceeds the SOLID size threshold of {threshold}"
+            ),
+            PlacementReason::ForcedOwnBlock => {
+                write!(
+                    f,
+                    "own block: flagged to be stored alone, with fast compression"
+                )
+            }
+            PlacementReason::NoSolid => {
+                write!(f, "own block: SOLID packing disabled for this file")
+            }
+            PlacementReason::Solid => write!(f, "SOLID block: shared with other small files"),
+            PlacementReason::ManualBlock => write!(f, "manual block: assigned in the settings"),
+        }
+    }
+}
+
+/// Where a single file will be placed, as reported by
+/// [`NxPackerBuilder::placement_report`](crate::api::packer_builder::NxPackerBuilder::placement_report).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilePlacement<'a> {
+    /// Path of the file within the archive.
+    pub relative_path: &'a str,
+
+    /// Why the file is placed where it is.
+    pub reason: PlacementReason,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utilities::tests::packer_file_for_testing::PackerFileForTesting;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::small(100, SolidPreference::Default, PlacementReason::Solid)]
+    #[case::at_threshold(1000, SolidPreference::Default, PlacementReason::Solid)]
+    #[case::no_solid(100, SolidPreference::NoSolid, PlacementReason::NoSolid)]
+    #[case::large(1001, SolidPreference::NoSolid, PlacementReason::Chunked { file_size: 1001, threshold: 1000 })]
+    fn places_files(
+        #[case] size: u64,
+        #[case] solid_type: SolidPreference,
+        #[case] expected: PlacementReason,
+    ) {
+        let mut file = PackerFileForTesting::new("file.bin", size);
+        file.set_solid_type(solid_type);
+        assert_eq!(PlacementReason::of(&file, 1000), expected);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::api::traits::*;
+use crate::api::{enums::PathCollation, traits::*};
 
 /// Helper function to sort items lexicographically.
 ///
@@ -14,6 +14,17 @@ pub fn sort_lexicographically<T: HasRelativePath>(items: &mut [T]) {
     items.sort_by(|a, b| a.relative_path().cmp(b.relative_path()));
 }
 
+/// Sorts items by their paths, using the given collation.
+///
+/// # Arguments
+///
+/// * `items` - The items to sort.
+/// * `collation` - How to compare the paths. [`PathCollation::Ordinal`] sorts
+///   the same way as [sort_lexicographically].
+pub fn sort_with_collation<T: HasRelativePath>(items: &mut [T], collation: PathCollation) {
+    items.sort_by(|a, b| collation.compare(a.relative_path(), b.relative_path()));
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
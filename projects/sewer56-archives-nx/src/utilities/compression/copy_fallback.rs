@@ -0,0 +1,217 @@
+use super::{
+    compress_advanced, copy, max_alloc_for_compress_size, zstd_advanced::ZstdAdvancedParams,
+    CompressionResult,
+};
+use crate::api::enums::CompressionPreference;
+use crate::prelude::*;
+
+/// Default number of bytes compressed up front to detect incompressible data.
+pub const DEFAULT_PROBE_SIZE: u32 = 65536;
+
+/// Decides when compressed data is not worth keeping, and is stored with
+/// [`CompressionPreference::Copy`] instead.
+///
+/// By default data is only stored raw if compressing it makes it larger.
+/// Raising [`Self::min_savings_percent`] also stores data raw when compression
+/// saves too little to be worth the decompression cost.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyFallback {
+    /// Minimum space saved by compression, as a percentage of the original size.
+    /// Data which saves less is stored raw. Range is 0 - 100.
+    pub min_savings_percent: u8,
+
+    /// Overrides [`Self::min_savings_percent`] for ZStandard.
+    pub zstd_min_savings_percent: Option<u8>,
+
+    /// Overrides [`Self::min_savings_percent`] for LZ4.
+    pub lz4_min_savings_percent: Option<u8>,
+
+    /// Number of bytes at the start of the data compressed first, as a quick test.
+    /// If this sample does not meet the minimum savings, the rest is not compressed at all.
+    ///
+    /// This is only used when the minimum savings is above 0. Set to 0 to disable.
+    pub probe_size: u32,
+}
+
+impl Default for CopyFallback {
+    fn default() -> Self {
+        Self {
+            min_savings_percent: 0,
+            zstd_min_savings_percent: None,
+            lz4_min_savings_percent: None,
+            probe_size: DEFAULT_PROBE_SIZE,
+        }
+    }
+}
+
+impl CopyFallback {
+    /// Returns the minimum savings percentage for a given compression method.
+    ///
+    /// # Parameters
+    ///
+    /// * `method`: Method the data is compressed with.
+    pub fn min_savings_percent_for(&self, method: CompressionPreference) -> u8 {
+        let overridden = match method {
+            CompressionPreference::ZStandard | CompressionPreference::NoPreference => {
+                self.zstd_min_savings_percent
+            }
+            CompressionPreference::Lz4 => self.lz4_min_savings_percent,
+            // Mixed blocks already store their incompressible segments raw.
+            CompressionPreference::Copy | CompressionPreference::Mixed => Some(0),
+        };
+
+        overridden.unwrap_or(self.min_savings_percent).min(100)
+    }
+
+    /// Clamps all percentages to the 0 - 100 range.
+    pub fn sanitize(&mut self) {
+        self.min_savings_percent = self.min_savings_percent.min(100);
+        self.zstd_min_savings_percent = self.zstd_min_savings_percent.map(|x| x.min(100));
+        self.lz4_min_savings_percent = self.lz4_min_savings_percent.map(|x| x.min(100));
+    }
+}
+
+/// Compresses data with a specific method, storing it raw if compression does not
+/// save at least the space required by `fallback`.
+///
+/// # Parameters
+///
+/// * `method`: Method we compress with.
+/// * `level`: Level at which we are compressing.
+/// * `source`: Source data to compress.
+/// * `destination`: Destination buffer for compressed data.
+/// * `fallback`: Decides when to store the data raw.
+/// * `zstd_params`: Advanced parameters, used if compressing with ZStandard.
+/// * `used_copy`: If this is true, Copy compression was used, due to uncompressible data or by request.
+///
+/// # Returns
+///
+/// The number of bytes written to the destination.
+pub fn compress_with_fallback(
+    method: CompressionPreference,
+    level: i32,
+    source: &[u8],
+    destination: &mut [u8],
+    fallback: &CopyFallback,
+    zstd_params: &ZstdAdvancedParams,
+    used_copy: &mut bool,
+) -> CompressionResult {
+    let min_savings = fallback.min_savings_percent_for(method);
+    let probe_size = fallback.probe_size as usize;
+    if min_savings > 0 && probe_size > 0 && source.len() > probe_size {
+        let probe = &source[..probe_size];
+        let mut probe_dest = vec![0u8; max_alloc_for_compress_size(probe.len())];
+        let mut probe_copy = false;
+        let probe_compressed = compress_advanced(
+            method,
+            level,
+            zstd_params,
+            probe,
+            &mut probe_dest,
+            &mut probe_copy,
+        )?;
+
+        if probe_copy || !saves_enough(probe.len(), probe_compressed, min_savings) {
+            return copy::compress(source, destination, used_copy);
+        }
+    }
+
+    let compressed = compress_advanced(method, level, zstd_params, source, destination, used_copy)?;
+    if !*used_copy && !saves_enough(source.len(), compressed, min_savings) {
+        return copy::compress(source, destination, used_copy);
+    }
+
+    Ok(compressed)
+}
+
+fn saves_enough(original: usize, compressed: usize, min_savings_percent: u8) -> bool {
+    let saved = original.saturating_sub(compressed) as u64;
+    saved * 100 >= original as u64 * min_savings_percent as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    // Roughly 2:1 compressible; each byte only carries 4 bits of information.
+    fn half_compressible_data() -> Vec<u8> {
+        let mut state = 0x12345678_u32;
+        (0..200_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state & 0x0F) as u8
+            })
+            .collect()
+    }
+
+    fn compress_data(data: &[u8], fallback: &CopyFallback) -> bool {
+        let mut destination = vec![0u8; max_alloc_for_compress_size(data.len())];
+        let mut used_copy = false;
+        compress_with_fallback(
+            CompressionPreference::ZStandard,
+            3,
+            data,
+            &mut destination,
+            fallback,
+            &ZstdAdvancedParams::default(),
+            &mut used_copy,
+        )
+        .unwrap();
+        used_copy
+    }
+
+    #[rstest]
+    #[case::default(0, DEFAULT_PROBE_SIZE, false)]
+    #[case::below_savings(30, DEFAULT_PROBE_SIZE, false)]
+    #[case::above_savings(70, DEFAULT_PROBE_SIZE, true)]
+    #[case::above_savings_without_probe(70, 0, true)]
+    #[cfg_attr(miri, ignore)]
+    fn stores_raw_when_savings_too_low(
+        #[case] min_savings_percent: u8,
+        #[case] probe_size: u32,
+        #[case] expected_copy: bool,
+    ) {
+        let fallback = CopyFallback {
+            min_savings_percent,
+            probe_size,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            compress_data(&half_compressible_data(), &fallback),
+            expected_copy
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn per_algorithm_override_takes_priority() {
+        let fallback = CopyFallback {
+            min_savings_percent: 70,
+            zstd_min_savings_percent: Some(10),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            fallback.min_savings_percent_for(CompressionPreference::Lz4),
+            70
+        );
+        assert!(!compress_data(&half_compressible_data(), &fallback));
+    }
+
+    #[test]
+    fn sanitize_clamps_percentages() {
+        let mut fallback = CopyFallback {
+            min_savings_percent: 150,
+            lz4_min_savings_percent: Some(101),
+            ..Default::default()
+        };
+        fallback.sanitize();
+
+        assert_eq!(fallback.min_savings_percent, 100);
+        assert_eq!(fallback.lz4_min_savings_percent, Some(100));
+    }
+}
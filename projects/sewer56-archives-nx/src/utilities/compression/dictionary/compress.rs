@@ -83,6 +83,11 @@ impl ZstdCompressionDict {
 
         Err(NxCompressionError::ZStandard(errcode))
     }
+
+    /// Returns the underlying ZStandard dictionary.
+    pub(crate) fn as_ptr(&self) -> *const ZSTD_CDict {
+        self.dict_ptr
+    }
 }
 
 impl Drop for ZstdCompressionDict {
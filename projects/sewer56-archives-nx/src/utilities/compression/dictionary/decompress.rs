@@ -66,9 +66,15 @@ impl ZstdDecompressionDict {
             }
         }
     }
+
+    /// Returns the underlying ZStandard dictionary.
+    pub(crate) fn as_ptr(&self) -> *const ZSTD_DDict {
+        self.dict_ptr
+    }
 }
 
 unsafe impl Send for ZstdDecompressionDict {}
+unsafe impl Sync for ZstdDecompressionDict {}
 
 impl Drop for ZstdDecompressionDict {
     fn drop(&mut self) {
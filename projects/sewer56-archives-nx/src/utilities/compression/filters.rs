@@ -0,0 +1,188 @@
+use crate::api::enums::DataFilter;
+use thiserror_no_std::Error;
+
+/// Represents an error returned when applying or reversing a [`DataFilter`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Error)]
+pub enum FilterError {
+    /// The destination buffer is smaller than the source buffer.
+    #[error("Destination buffer is too small")]
+    DestinationTooSmall,
+}
+
+/// Applies a filter to data before it is compressed.
+///
+/// # Parameters
+///
+/// * `filter`: The filter to apply.
+/// * `source`: Source data to filter.
+/// * `destination`: Destination buffer for filtered data. Must be at least as large as `source`.
+///
+/// # Returns
+///
+/// The number of bytes written to the destination. This is always `source.len()`.
+///
+/// # Remarks
+///
+/// Any trailing bytes which do not make up a whole element are copied verbatim.
+pub fn apply_filter(
+    filter: DataFilter,
+    source: &[u8],
+    destination: &mut [u8],
+) -> Result<usize, FilterError> {
+    if destination.len() < source.len() {
+        return Err(FilterError::DestinationTooSmall);
+    }
+
+    let destination = &mut destination[..source.len()];
+    match filter {
+        DataFilter::None => destination.copy_from_slice(source),
+        DataFilter::ByteTranspose4 | DataFilter::ByteTranspose8 => {
+            transpose(source, destination, filter.stride())
+        }
+        DataFilter::Delta4 | DataFilter::Delta8 => {
+            delta_encode(source, destination, filter.stride())
+        }
+    }
+
+    Ok(source.len())
+}
+
+/// Reverses a filter previously applied with [`apply_filter`], after data is decompressed.
+///
+/// # Parameters
+///
+/// * `filter`: The filter that was applied to the data.
+/// * `source`: Filtered data.
+/// * `destination`: Destination buffer for original data. Must be at least as large as `source`.
+///
+/// # Returns
+///
+/// The number of bytes written to the destination. This is always `source.len()`.
+pub fn reverse_filter(
+    filter: DataFilter,
+    source: &[u8],
+    destination: &mut [u8],
+) -> Result<usize, FilterError> {
+    if destination.len() < source.len() {
+        return Err(FilterError::DestinationTooSmall);
+    }
+
+    let destination = &mut destination[..source.len()];
+    match filter {
+        DataFilter::None => destination.copy_from_slice(source),
+        DataFilter::ByteTranspose4 | DataFilter::ByteTranspose8 => {
+            untranspose(source, destination, filter.stride())
+        }
+        DataFilter::Delta4 | DataFilter::Delta8 => {
+            delta_decode(source, destination, filter.stride())
+        }
+    }
+
+    Ok(source.len())
+}
+
+fn transpose(source: &[u8], destination: &mut [u8], stride: usize) {
+    let num_elements = source.len() / stride;
+    let body_len = num_elements * stride;
+    for (element_idx, element) in source[..body_len].chunks_exact(stride).enumerate() {
+        for (byte_idx, byte) in element.iter().enumerate() {
+            destination[byte_idx * num_elements + element_idx] = *byte;
+        }
+    }
+
+    destination[body_len..].copy_from_slice(&source[body_len..]);
+}
+
+fn untranspose(source: &[u8], destination: &mut [u8], stride: usize) {
+    let num_elements = source.len() / stride;
+    let body_len = num_elements * stride;
+    for (element_idx, element) in destination[..body_len].chunks_exact_mut(stride).enumerate() {
+        for (byte_idx, byte) in element.iter_mut().enumerate() {
+            *byte = source[byte_idx * num_elements + element_idx];
+        }
+    }
+
+    destination[body_len..].copy_from_slice(&source[body_len..]);
+}
+
+fn delta_encode(source: &[u8], destination: &mut [u8], stride: usize) {
+    let head = stride.min(source.len());
+    destination[..head].copy_from_slice(&source[..head]);
+    for (x, byte) in destination.iter_mut().enumerate().skip(head) {
+        *byte = source[x].wrapping_sub(source[x - stride]);
+    }
+}
+
+fn delta_decode(source: &[u8], destination: &mut [u8], stride: usize) {
+    let head = stride.min(source.len());
+    destination[..head].copy_from_slice(&source[..head]);
+    for (x, byte) in source.iter().enumerate().skip(head) {
+        destination[x] = byte.wrapping_add(destination[x - stride]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use rstest::rstest;
+
+    fn float_data() -> Vec<u8> {
+        (0..67)
+            .map(|x| x as f32 * 0.25)
+            .flat_map(|x| x.to_le_bytes())
+            .chain([1, 2, 3]) // trailing partial element
+            .collect()
+    }
+
+    #[rstest]
+    #[case::none(DataFilter::None)]
+    #[case::transpose4(DataFilter::ByteTranspose4)]
+    #[case::transpose8(DataFilter::ByteTranspose8)]
+    #[case::delta4(DataFilter::Delta4)]
+    #[case::delta8(DataFilter::Delta8)]
+    fn can_round_trip(#[case] filter: DataFilter) {
+        let data = float_data();
+        let mut filtered = vec![0u8; data.len()];
+        let mut restored = vec![0u8; data.len()];
+
+        assert_eq!(apply_filter(filter, &data, &mut filtered), Ok(data.len()));
+        assert_eq!(
+            reverse_filter(filter, &filtered, &mut restored),
+            Ok(data.len())
+        );
+        assert_eq!(data, restored);
+    }
+
+    #[test]
+    fn transpose_groups_bytes() {
+        let data = [0, 1, 2, 3, 10, 11, 12, 13, 99];
+        let mut filtered = [0u8; 9];
+        apply_filter(DataFilter::ByteTranspose4, &data, &mut filtered).unwrap();
+        assert_eq!(filtered, [0, 10, 1, 11, 2, 12, 3, 13, 99]);
+    }
+
+    #[test]
+    fn delta_stores_differences() {
+        let data = [1, 2, 3, 4, 2, 4, 6, 8];
+        let mut filtered = [0u8; 8];
+        apply_filter(DataFilter::Delta4, &data, &mut filtered).unwrap();
+        assert_eq!(filtered, [1, 2, 3, 4, 1, 2, 3, 4]);
+    }
+
+    #[rstest]
+    #[case::apply(true)]
+    #[case::reverse(false)]
+    fn destination_too_small_returns_err(#[case] apply: bool) {
+        let data = float_data();
+        let mut small = [0u8; 4];
+        let result = if apply {
+            apply_filter(DataFilter::Delta4, &data, &mut small)
+        } else {
+            reverse_filter(DataFilter::Delta4, &data, &mut small)
+        };
+
+        assert_eq!(result, Err(FilterError::DestinationTooSmall));
+    }
+}
@@ -0,0 +1,184 @@
+use super::{compress, CompressionResult};
+use crate::api::enums::CompressionPreference;
+use core::ops::RangeInclusive;
+use core::sync::atomic::{AtomicI32, Ordering};
+use core::time::Duration;
+use std::time::Instant;
+
+/// How far the measured throughput may stray from the target (in percent)
+/// before the level is changed. Avoids flip-flopping between two levels.
+const TOLERANCE_PERCENT: u64 = 10;
+
+/// Adjusts the ZStandard compression level while packing, to hit a target throughput.
+///
+/// Each compressed block is timed; if it was compressed slower than the target,
+/// the level is lowered by one, if faster, it is raised by one. The level never leaves
+/// the range given by the user.
+///
+/// Can be shared between the compression threads.
+pub struct LevelTuner {
+    /// Target throughput of a single thread, in bytes per second.
+    target_bytes_per_second: u64,
+
+    /// Range of levels the tuner may pick from.
+    levels: RangeInclusive<i32>,
+
+    /// The level to use for the next block.
+    level: AtomicI32,
+}
+
+impl LevelTuner {
+    /// Creates a new tuner.
+    ///
+    /// # Parameters
+    ///
+    /// * `target_mbps`: Target throughput of the whole packer, in megabytes (10^6 bytes) per second.
+    /// * `levels`: Range of ZStandard levels the tuner may use.
+    /// * `initial_level`: Level used for the first blocks. Clamped to `levels`.
+    /// * `thread_count`: Number of threads compressing at once; the target is split between them.
+    pub fn new(
+        target_mbps: u32,
+        levels: RangeInclusive<i32>,
+        initial_level: i32,
+        thread_count: u32,
+    ) -> Self {
+        let initial_level = initial_level.clamp(*levels.start(), *levels.end());
+        Self {
+            target_bytes_per_second: target_mbps as u64 * 1_000_000 / thread_count.max(1) as u64,
+            levels,
+            level: AtomicI32::new(initial_level),
+        }
+    }
+
+    /// Returns the level to compress the next block with.
+    pub fn level(&self) -> i32 {
+        self.level.load(Ordering::Relaxed)
+    }
+
+    /// Reports how long compressing a block took, adjusting the level if needed.
+    ///
+    /// # Parameters
+    ///
+    /// * `level_used`: Level the block was compressed with, from [`Self::level`].
+    /// * `num_bytes`: Size of the block before compression.
+    /// * `elapsed`: Time taken to compress the block.
+    pub fn record(&self, level_used: i32, num_bytes: usize, elapsed: Duration) {
+        let nanos = elapsed.as_nanos().max(1);
+        let bytes_per_second = (num_bytes as u128 * 1_000_000_000 / nanos) as u64;
+
+        let target = self.target_bytes_per_second;
+        let margin = target * TOLERANCE_PERCENT / 100;
+        let new_level = if bytes_per_second < target - margin {
+            level_used - 1
+        } else if bytes_per_second > target + margin {
+            level_used + 1
+        } else {
+            return;
+        };
+
+        // If another thread has already changed the level since `level_used`, its result is newer.
+        let new_level = new_level.clamp(*self.levels.start(), *self.levels.end());
+        let _ = self.level.compare_exchange(
+            level_used,
+            new_level,
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Compresses a block with ZStandard at the current level, and records how long it took.
+    ///
+    /// # Parameters
+    ///
+    /// * `source`: Source data to compress.
+    /// * `destination`: Destination buffer for compressed data.
+    /// * `used_copy`: If this is true, Copy compression was used, due to uncompressible data.
+    ///
+    /// # Returns
+    ///
+    /// The number of bytes written to the destination.
+    pub fn compress(
+        &self,
+        source: &[u8],
+        destination: &mut [u8],
+        used_copy: &mut bool,
+    ) -> CompressionResult {
+        let level = self.level();
+        let start = Instant::now();
+        let result = compress(
+            CompressionPreference::ZStandard,
+            level,
+            source,
+            destination,
+            used_copy,
+        );
+
+        // Blocks stored raw say nothing about how fast the level is.
+        if result.is_ok() && !*used_copy {
+            self.record(level, source.len(), start.elapsed());
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const MB: usize = 1_000_000;
+
+    #[rstest]
+    #[case::too_slow(MB / 2, 8)]
+    #[case::on_target(MB, 9)]
+    #[case::within_tolerance(MB * 105 / 100, 9)]
+    #[case::too_fast(MB * 2, 10)]
+    fn adjusts_level_towards_target(#[case] bytes_per_second: usize, #[case] expected: i32) {
+        let tuner = LevelTuner::new(1, 1..=19, 9, 1);
+        tuner.record(9, bytes_per_second, Duration::from_secs(1));
+        assert_eq!(tuner.level(), expected);
+    }
+
+    #[test]
+    fn level_stays_within_range() {
+        let tuner = LevelTuner::new(1, 3..=5, 1, 1);
+        assert_eq!(tuner.level(), 3);
+
+        tuner.record(3, MB / 10, Duration::from_secs(1));
+        assert_eq!(tuner.level(), 3);
+    }
+
+    #[test]
+    fn stale_reports_are_ignored() {
+        let tuner = LevelTuner::new(1, 1..=19, 9, 1);
+        tuner.record(9, MB * 2, Duration::from_secs(1));
+        tuner.record(9, MB * 2, Duration::from_secs(1));
+        assert_eq!(tuner.level(), 10);
+    }
+
+    #[test]
+    fn target_is_split_between_threads() {
+        let tuner = LevelTuner::new(4, 1..=19, 9, 4);
+        tuner.record(9, MB, Duration::from_secs(1));
+        assert_eq!(tuner.level(), 9);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn compress_uses_current_level() {
+        let tuner = LevelTuner::new(1, 1..=19, 9, 1);
+        let source = [7u8; 4096];
+        let mut destination = [0u8; 8192];
+        let mut used_copy = false;
+
+        assert!(
+            tuner
+                .compress(&source, &mut destination, &mut used_copy)
+                .unwrap()
+                < source.len()
+        );
+        assert!(!used_copy);
+        assert!((1..=19).contains(&tuner.level()));
+    }
+}
@@ -87,6 +87,61 @@ pub fn compress(
     Ok(result as usize)
 }
 
+/// Compresses data with LZ4, using a dictionary.
+///
+/// # Parameters
+///
+/// * `dict`: Raw dictionary data. LZ4 only uses the last 64KiB.
+/// * `level`: Level at which we are compressing.
+/// * `source`: Source data to compress.
+/// * `destination`: Destination buffer for compressed data.
+/// * `used_copy`: If this is true, Copy compression was used, due to uncompressible data.
+///
+/// # Returns
+///
+/// The number of bytes written to the destination, or an error.
+///
+/// # Remarks
+///
+/// The data can only be decompressed with the same dictionary,
+/// via [`decompress_with_dictionary`] or [`decompress_partial_with_dictionary`].
+pub fn compress_with_dictionary(
+    dict: &[u8],
+    level: i32,
+    source: &[u8],
+    destination: &mut [u8],
+    used_copy: &mut bool,
+) -> CompressionResult {
+    *used_copy = false;
+
+    let result = unsafe {
+        let stream = SafeStreamEncode::new(LZ4_createStreamHC());
+        if stream.is_null() {
+            return Err(Lz4CompressionError::CompressionFailed.into());
+        }
+
+        LZ4_setCompressionLevel(*stream, level);
+        LZ4_loadDictHC(*stream, dict.as_ptr() as *const c_char, dict.len() as c_int);
+        LZ4_compress_HC_continue(
+            *stream,
+            source.as_ptr() as *const c_char,
+            destination.as_mut_ptr() as *mut c_char,
+            source.len() as c_int,
+            destination.len() as c_int,
+        )
+    };
+
+    if result <= 0 {
+        return Err(Lz4CompressionError::CompressionFailed.into());
+    }
+
+    if result as usize > source.len() {
+        return copy::compress(source, destination, used_copy);
+    }
+
+    Ok(result as usize)
+}
+
 /// Compresses data using streaming compression with LZ4-HC.
 ///
 /// This function allows compression of data in chunks while providing the ability
@@ -234,6 +289,76 @@ pub fn decompress_partial(source: &[u8], destination: &mut [u8]) -> Decompressio
     }
 }
 
+/// Decompresses data compressed with [`compress_with_dictionary`].
+///
+/// # Parameters
+///
+/// * `dict`: The dictionary the data was compressed with.
+/// * `source`: Source data to decompress.
+/// * `destination`: Destination buffer for decompressed data.
+///
+/// # Returns
+///
+/// The number of bytes written to the destination, or an error.
+pub fn decompress_with_dictionary(
+    dict: &[u8],
+    source: &[u8],
+    destination: &mut [u8],
+) -> DecompressionResult {
+    let result = unsafe {
+        LZ4_decompress_safe_usingDict(
+            source.as_ptr() as *const c_char,
+            destination.as_mut_ptr() as *mut c_char,
+            source.len() as c_int,
+            destination.len() as c_int,
+            dict.as_ptr() as *const c_char,
+            dict.len() as c_int,
+        )
+    };
+
+    if result <= 0 {
+        Err(Lz4DecompressionError::DecompressionFailed.into())
+    } else {
+        Ok(result as usize)
+    }
+}
+
+/// Partially decompresses data compressed with [`compress_with_dictionary`],
+/// until the destination buffer is filled.
+///
+/// # Parameters
+///
+/// * `dict`: The dictionary the data was compressed with.
+/// * `source`: Source data to decompress.
+/// * `destination`: Destination buffer for decompressed data.
+///
+/// # Returns
+///
+/// The number of bytes written to the destination, or an error.
+pub fn decompress_partial_with_dictionary(
+    dict: &[u8],
+    source: &[u8],
+    destination: &mut [u8],
+) -> DecompressionResult {
+    let result = unsafe {
+        LZ4_decompress_safe_partial_usingDict(
+            source.as_ptr() as *const c_char,
+            destination.as_mut_ptr() as *mut c_char,
+            source.len() as c_int,
+            destination.len() as c_int,
+            destination.len() as c_int,
+            dict.as_ptr() as *const c_char,
+            dict.len() as c_int,
+        )
+    };
+
+    if result <= 0 {
+        Err(Lz4DecompressionError::DecompressionFailed.into())
+    } else {
+        Ok(result as usize)
+    }
+}
+
 #[derive(Deref, DerefMut, new)]
 pub struct SafeStreamEncode(*mut LZ4StreamEncode);
 impl Drop for SafeStreamEncode {
@@ -272,4 +397,62 @@ extern "C" {
         src_size: c_int,
         dst_capacity: c_int,
     ) -> c_int;
+
+    #[allow(non_snake_case)]
+    pub fn LZ4_loadDictHC(
+        ptr: *mut LZ4StreamEncode,
+        dictionary: *const c_char,
+        dict_size: c_int,
+    ) -> c_int;
+
+    #[allow(non_snake_case)]
+    pub fn LZ4_decompress_safe_usingDict(
+        src: *const c_char,
+        dst: *mut c_char,
+        src_size: c_int,
+        dst_capacity: c_int,
+        dict_start: *const c_char,
+        dict_size: c_int,
+    ) -> c_int;
+
+    #[allow(non_snake_case)]
+    pub fn LZ4_decompress_safe_partial_usingDict(
+        src: *const c_char,
+        dst: *mut c_char,
+        compressed_size: c_int,
+        target_output_size: c_int,
+        max_output_size: c_int,
+        dict_start: *const c_char,
+        dict_size: c_int,
+    ) -> c_int;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const DICTIONARY: &[u8] = b"The quick brown fox jumps over the lazy dog. Nx archives!";
+    const TEST_DATA: &[u8] = b"Nx archives! The lazy dog jumps over the quick brown fox.";
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_round_trip_with_dictionary() {
+        let mut compressed = vec![0u8; max_alloc_for_compress_size(TEST_DATA.len())];
+        let mut used_copy = false;
+        let size =
+            compress_with_dictionary(DICTIONARY, 12, TEST_DATA, &mut compressed, &mut used_copy)
+                .unwrap();
+        assert!(!used_copy, "dictionary should make the data compressible");
+        compressed.truncate(size);
+
+        let mut decompressed = vec![0u8; TEST_DATA.len()];
+        let size = decompress_with_dictionary(DICTIONARY, &compressed, &mut decompressed).unwrap();
+        assert_eq!(&decompressed[..size], TEST_DATA);
+
+        let mut partial = vec![0u8; 12];
+        let size =
+            decompress_partial_with_dictionary(DICTIONARY, &compressed, &mut partial).unwrap();
+        assert_eq!(&partial[..size], &TEST_DATA[..12]);
+    }
 }
@@ -0,0 +1,339 @@
+use super::{
+    compress_advanced,
+    copy::{CopyCompressionError, CopyDecompressionError},
+    max_alloc_for_compress_size as max_alloc_for_segment,
+    zstd_advanced::ZstdAdvancedParams,
+    CompressionResult, DecompressionResult, NxCompressionError, NxDecompressionError,
+};
+use crate::api::enums::CompressionPreference;
+
+/// Size of the segment count at the start of a mixed block.
+pub const HEADER_SIZE: usize = 2;
+
+/// Size of each entry in the segment map of a mixed block.
+/// Stores the codec (u8), decompressed size (u32) and compressed size (u32).
+pub const SEGMENT_ENTRY_SIZE: usize = 9;
+
+/// Maximum number of segments in a single mixed block.
+pub const MAX_SEGMENTS: usize = u16::MAX as usize;
+
+/// A run of data within a [`CompressionPreference::Mixed`] block, compressed with a single codec.
+///
+/// Consecutive files using the same codec should share a segment, so they are
+/// still compressed together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MixedSegment {
+    /// Codec the segment is compressed with.
+    /// One of [`CompressionPreference::Copy`], [`CompressionPreference::ZStandard`] or [`CompressionPreference::Lz4`].
+    pub compression: CompressionPreference,
+
+    /// Number of bytes of the source data covered by this segment.
+    pub length: u32,
+}
+
+/// Determines maximum memory needed to alloc to compress data as a mixed block.
+///
+/// # Parameters
+///
+/// * `segments`: The segments the data is split into.
+pub fn max_alloc_for_compress_size(segments: &[MixedSegment]) -> usize {
+    HEADER_SIZE
+        + segments.len() * SEGMENT_ENTRY_SIZE
+        + segments
+            .iter()
+            .map(|segment| max_alloc_for_segment(segment.length as usize))
+            .sum::<usize>()
+}
+
+/// Compresses data into a mixed block, where each segment is compressed with its own codec.
+///
+/// The block starts with a segment map, made up of the segment count (u16),
+/// followed by [`SEGMENT_ENTRY_SIZE`] bytes per segment. The compressed segments follow,
+/// back to back. All values are little endian.
+///
+/// # Parameters
+///
+/// * `segments`: Segments the source is split into. Their lengths must add up to the source length.
+/// * `level`: Level at which the segments are compressed.
+/// * `zstd_params`: Advanced parameters, used for segments compressed with ZStandard.
+/// * `source`: Source data to compress.
+/// * `destination`: Destination buffer for compressed data.
+///   See [`max_alloc_for_compress_size`].
+///
+/// # Returns
+///
+/// The number of bytes written to the destination.
+///
+/// # Remarks
+///
+/// Segments which don't compress are stored with [`CompressionPreference::Copy`].
+pub fn compress(
+    segments: &[MixedSegment],
+    level: i32,
+    zstd_params: &ZstdAdvancedParams,
+    source: &[u8],
+    destination: &mut [u8],
+) -> CompressionResult {
+    let total_length: u64 = segments.iter().map(|segment| segment.length as u64).sum();
+    let valid_codecs = segments.iter().all(|segment| {
+        matches!(
+            segment.compression,
+            CompressionPreference::Copy
+                | CompressionPreference::ZStandard
+                | CompressionPreference::Lz4
+        )
+    });
+    if segments.is_empty()
+        || segments.len() > MAX_SEGMENTS
+        || total_length != source.len() as u64
+        || !valid_codecs
+    {
+        return Err(NxCompressionError::InvalidMixedSegments);
+    }
+
+    let map_size = HEADER_SIZE + segments.len() * SEGMENT_ENTRY_SIZE;
+    if destination.len() < map_size {
+        return Err(CopyCompressionError::DestinationTooSmall.into());
+    }
+
+    let (map, payload) = destination.split_at_mut(map_size);
+    map[..HEADER_SIZE].copy_from_slice(&(segments.len() as u16).to_le_bytes());
+
+    let mut source_offset = 0;
+    let mut written = 0;
+    for (segment, entry) in segments
+        .iter()
+        .zip(map[HEADER_SIZE..].chunks_exact_mut(SEGMENT_ENTRY_SIZE))
+    {
+        let data = &source[source_offset..source_offset + segment.length as usize];
+        let mut used_copy = false;
+        let size = compress_advanced(
+            segment.compression,
+            level,
+            zstd_params,
+            data,
+            &mut payload[written..],
+            &mut used_copy,
+        )?;
+
+        let codec = if used_copy {
+            CompressionPreference::Copy
+        } else {
+            segment.compression
+        };
+        entry[0] = codec as u8;
+        entry[1..5].copy_from_slice(&segment.length.to_le_bytes());
+        entry[5..9].copy_from_slice(&(size as u32).to_le_bytes());
+
+        source_offset += segment.length as usize;
+        written += size;
+    }
+
+    Ok(map_size + written)
+}
+
+/// Decompresses a mixed block.
+///
+/// # Parameters
+///
+/// * `source`: Source data to decompress.
+/// * `destination`: Destination buffer for decompressed data.
+///
+/// # Errors
+///
+/// Returns [`NxDecompressionError::MalformedMixedBlock`] if the segment map does not
+/// match the data that follows it.
+pub fn decompress(source: &[u8], destination: &mut [u8]) -> DecompressionResult {
+    decompress_segments(source, destination, false)
+}
+
+/// Partially decompresses a mixed block until the destination buffer is filled.
+/// Segments past the end of the destination are not decompressed.
+///
+/// # Parameters
+///
+/// * `source`: Source data to decompress.
+/// * `destination`: Destination buffer for decompressed data.
+pub fn decompress_partial(source: &[u8], destination: &mut [u8]) -> DecompressionResult {
+    decompress_segments(source, destination, true)
+}
+
+fn decompress_segments(
+    source: &[u8],
+    destination: &mut [u8],
+    partial: bool,
+) -> DecompressionResult {
+    let segment_count = match source.get(..HEADER_SIZE) {
+        Some(header) => u16::from_le_bytes([header[0], header[1]]) as usize,
+        None => return Err(NxDecompressionError::MalformedMixedBlock),
+    };
+
+    let map_size = HEADER_SIZE + segment_count * SEGMENT_ENTRY_SIZE;
+    let Some(map) = source.get(HEADER_SIZE..map_size) else {
+        return Err(NxDecompressionError::MalformedMixedBlock);
+    };
+
+    let mut payload = &source[map_size..];
+    let mut written = 0;
+    for entry in map.chunks_exact(SEGMENT_ENTRY_SIZE) {
+        let codec = match entry[0] {
+            0 => CompressionPreference::Copy,
+            1 => CompressionPreference::ZStandard,
+            2 => CompressionPreference::Lz4,
+            _ => return Err(NxDecompressionError::MalformedMixedBlock),
+        };
+        let decompressed_size =
+            u32::from_le_bytes([entry[1], entry[2], entry[3], entry[4]]) as usize;
+        let compressed_size = u32::from_le_bytes([entry[5], entry[6], entry[7], entry[8]]) as usize;
+
+        // Copy 'decompression' trusts the sizes it is given.
+        if compressed_size > payload.len()
+            || (codec == CompressionPreference::Copy && compressed_size != decompressed_size)
+        {
+            return Err(NxDecompressionError::MalformedMixedBlock);
+        }
+
+        let (data, rest) = payload.split_at(compressed_size);
+        payload = rest;
+
+        let remaining = destination.len() - written;
+        if partial && remaining <= decompressed_size {
+            return Ok(
+                written + super::decompress_partial(codec, data, &mut destination[written..])?
+            );
+        }
+
+        if remaining < decompressed_size {
+            return Err(CopyDecompressionError::DestinationTooSmall.into());
+        }
+
+        let size = super::decompress(
+            codec,
+            data,
+            &mut destination[written..written + decompressed_size],
+        )?;
+        if size != decompressed_size {
+            return Err(NxDecompressionError::MalformedMixedBlock);
+        }
+        written += size;
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn incompressible_data(length: usize) -> Vec<u8> {
+        let mut state = 0x9E3779B9_u32;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn mixed_source() -> (Vec<u8>, [MixedSegment; 3]) {
+        let mut source = b"compressible text, compressible text. ".repeat(64);
+        let first = source.len() as u32;
+        source.extend_from_slice(&incompressible_data(4096));
+        source.extend_from_slice(&b"more repeated text ".repeat(32));
+        let segments = [
+            MixedSegment {
+                compression: CompressionPreference::ZStandard,
+                length: first,
+            },
+            MixedSegment {
+                compression: CompressionPreference::Copy,
+                length: 4096,
+            },
+            MixedSegment {
+                compression: CompressionPreference::ZStandard,
+                length: source.len() as u32 - first - 4096,
+            },
+        ];
+        (source, segments)
+    }
+
+    fn compress_mixed(source: &[u8], segments: &[MixedSegment]) -> Vec<u8> {
+        let mut compressed = vec![0u8; max_alloc_for_compress_size(segments)];
+        let size = compress(
+            segments,
+            3,
+            &ZstdAdvancedParams::default(),
+            source,
+            &mut compressed,
+        )
+        .unwrap();
+        compressed.truncate(size);
+        compressed
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn can_round_trip() {
+        let (source, segments) = mixed_source();
+        let compressed = compress_mixed(&source, &segments);
+        assert!(compressed.len() < source.len());
+
+        let mut decompressed = vec![0u8; source.len()];
+        assert_eq!(decompress(&compressed, &mut decompressed), Ok(source.len()));
+        assert_eq!(decompressed, source);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn partial_decompression_stops_at_destination_end() {
+        let (source, segments) = mixed_source();
+        let compressed = compress_mixed(&source, &segments);
+
+        // Ends within the incompressible (Copy) segment.
+        let length = segments[0].length as usize + 100;
+        let mut decompressed = vec![0u8; length];
+        assert_eq!(
+            decompress_partial(&compressed, &mut decompressed),
+            Ok(length)
+        );
+        assert_eq!(decompressed, source[..length]);
+    }
+
+    #[test]
+    fn rejects_segments_not_covering_source() {
+        let segments = [MixedSegment {
+            compression: CompressionPreference::Copy,
+            length: 4,
+        }];
+        let mut destination = [0u8; 32];
+        assert_eq!(
+            compress(
+                &segments,
+                1,
+                &ZstdAdvancedParams::default(),
+                b"12345",
+                &mut destination
+            ),
+            Err(NxCompressionError::InvalidMixedSegments)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_map() {
+        let mut destination = [0u8; 16];
+        // One Copy segment of 8 bytes, but only 4 bytes of data.
+        let block = [1, 0, 0, 8, 0, 0, 0, 8, 0, 0, 0, 1, 2, 3, 4];
+        assert_eq!(
+            decompress(&block, &mut destination),
+            Err(NxDecompressionError::MalformedMixedBlock)
+        );
+        assert_eq!(
+            decompress(&[2, 0], &mut destination),
+            Err(NxDecompressionError::MalformedMixedBlock)
+        );
+    }
+}
@@ -1,7 +1,13 @@
 // Compression modules
 pub mod copy;
+pub mod copy_fallback;
 pub mod dictionary;
+pub mod filters;
+pub mod level_tuner;
+pub mod mixed;
 pub mod zstd;
+pub mod zstd_advanced;
+pub mod zstd_context_pool;
 pub mod zstd_stream;
 
 #[cfg(feature = "lz4")]
@@ -12,8 +18,15 @@ use lz4::*;
 
 use crate::api::enums::*;
 use copy::*;
+use zstd_advanced::ZstdAdvancedParams;
 use thiserror_no_std::Error;
 
+/// Lowest LZ4 compression level. All LZ4 levels use the high compression (HC) compressor.
+pub const LZ4_MIN_LEVEL: i32 = 1;
+
+/// Highest LZ4 (HC) compression level.
+pub const LZ4_MAX_LEVEL: i32 = 12;
+
 /// A result type around compression functions..
 /// Either a success code (number of bytes written), or an error code.
 pub type CompressionResult = Result<usize, NxCompressionError>;
@@ -34,6 +47,13 @@ pub enum NxCompressionError {
     Lz4NotEnabled,
     #[error("The operation was terminated during a stream operation with code: {0}")]
     TerminatedStream(usize),
+    /// Mixed blocks are compressed from a list of segments, see [`mixed::compress`].
+    #[error("Invalid segments for a mixed codec block")]
+    InvalidMixedSegments,
+    /// Compression was made to fail by a [`FaultInjector`](crate::utilities::fault_injection::FaultInjector).
+    #[cfg(feature = "fault_injection")]
+    #[error("Injected compression fault")]
+    InjectedFault,
 }
 
 /// A result type around compression functions..
@@ -47,6 +67,10 @@ pub enum NxDecompressionError {
     ZStandard(#[from] ZSTD_ErrorCode),
     #[cfg(feature = "lz4")]
     Lz4(#[from] Lz4DecompressionError),
+    /// The data uses a method which is unknown, or not enabled in this build.
+    UnsupportedMethod(CompressionPreference),
+    /// The segment map of a mixed codec block does not match its data.
+    MalformedMixedBlock,
 }
 
 /// Determines maximum memory needed to alloc to compress data with any method.
@@ -95,6 +119,37 @@ pub fn compress(
         CompressionPreference::NoPreference => {
             zstd::compress(level, source, destination, used_copy)
         }
+        CompressionPreference::Mixed => Err(NxCompressionError::InvalidMixedSegments),
+    }
+}
+
+/// Compresses data with a specific method, applying advanced parameters to ZStandard.
+///
+/// # Parameters
+///
+/// * `method`: Method we compress with.
+/// * `level`: Level at which we are compressing.
+/// * `zstd_params`: Advanced parameters, used if compressing with ZStandard.
+/// * `source`: Source data to compress.
+/// * `destination`: Destination buffer for compressed data.
+/// * `used_copy`: If this is true, Copy compression was used, due to uncompressible data or by request.
+///
+/// # Returns
+///
+/// The number of bytes written to the destination.
+pub fn compress_advanced(
+    method: CompressionPreference,
+    level: i32,
+    zstd_params: &ZstdAdvancedParams,
+    source: &[u8],
+    destination: &mut [u8],
+    used_copy: &mut bool,
+) -> CompressionResult {
+    match method {
+        CompressionPreference::ZStandard | CompressionPreference::NoPreference => {
+            zstd::compress_advanced(level, zstd_params, source, destination, used_copy)
+        }
+        _ => compress(method, level, source, destination, used_copy),
     }
 }
 
@@ -138,6 +193,7 @@ where
         CompressionPreference::NoPreference => {
             zstd::compress_streamed(level, source, destination, terminate_early, used_copy)
         }
+        CompressionPreference::Mixed => Err(NxCompressionError::InvalidMixedSegments),
     }
 }
 
@@ -148,6 +204,11 @@ where
 /// * `method`: Method we decompress with.
 /// * `source`: Source data to decompress.
 /// * `destination`: Destination buffer for decompressed data.
+///
+/// # Errors
+///
+/// Returns [`NxDecompressionError::UnsupportedMethod`] if the method is not supported,
+/// e.g. [`CompressionPreference::NoPreference`] or a method whose feature is not enabled.
 pub fn decompress(
     method: CompressionPreference,
     source: &[u8],
@@ -158,7 +219,8 @@ pub fn decompress(
         CompressionPreference::ZStandard => zstd::decompress(source, destination),
         #[cfg(feature = "lz4")]
         CompressionPreference::Lz4 => lz4::decompress(source, destination),
-        _ => panic!("Unsupported decompression method"),
+        CompressionPreference::Mixed => mixed::decompress(source, destination),
+        method => Err(NxDecompressionError::UnsupportedMethod(method)),
     }
 }
 
@@ -169,6 +231,11 @@ pub fn decompress(
 /// * `method`: Method we decompress with.
 /// * `source`: Source data to decompress.
 /// * `destination`: Destination buffer for decompressed data.
+///
+/// # Errors
+///
+/// Returns [`NxDecompressionError::UnsupportedMethod`] if the method is not supported,
+/// see [`decompress`].
 pub fn decompress_partial(
     method: CompressionPreference,
     source: &[u8],
@@ -179,7 +246,8 @@ pub fn decompress_partial(
         CompressionPreference::ZStandard => zstd::decompress_partial(source, destination),
         #[cfg(feature = "lz4")]
         CompressionPreference::Lz4 => lz4::decompress_partial(source, destination),
-        _ => panic!("Unsupported partial decompression method"),
+        CompressionPreference::Mixed => mixed::decompress_partial(source, destination),
+        method => Err(NxDecompressionError::UnsupportedMethod(method)),
     }
 }
 
@@ -193,6 +261,19 @@ mod tests {
         b"This is compressible test data. testtesttesttesttesttesttesttesttesttesttesttest";
     const INCOMPRESSIBLE_DATA: &[u8] = b"thisdoenatcmpres"; // does not compress
 
+    #[rstest]
+    #[case::no_preference(CompressionPreference::NoPreference)]
+    #[cfg_attr(not(feature = "lz4"), case::lz4(CompressionPreference::Lz4))]
+    fn unsupported_method_returns_error(#[case] method: CompressionPreference) {
+        let mut destination = [0u8; 16];
+        let expected = Err(NxDecompressionError::UnsupportedMethod(method));
+        assert_eq!(decompress(method, TEST_DATA, &mut destination), expected);
+        assert_eq!(
+            decompress_partial(method, TEST_DATA, &mut destination),
+            expected
+        );
+    }
+
     #[rstest]
     #[case::copy(CompressionPreference::Copy)]
     #[case::zstd(CompressionPreference::ZStandard)]
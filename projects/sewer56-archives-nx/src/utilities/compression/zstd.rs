@@ -1,4 +1,6 @@
-use super::dictionary::ZstdCompressionDict;
+use super::dictionary::{ZstdCompressionDict, ZstdDecompressionDict};
+use super::zstd_advanced::{ZstdAdvancedParams, MAX_WINDOW_LOG};
+use super::zstd_context_pool::{with_compression_context, with_decompression_context};
 use super::{CompressionResult, DecompressionResult, NxCompressionError, NxDecompressionError};
 use crate::utilities::compression::copy;
 use core::cmp::min;
@@ -35,34 +37,52 @@ pub fn compress(
     destination: &mut [u8],
     used_copy: &mut bool,
 ) -> CompressionResult {
-    *used_copy = false;
-
-    // Create a compression context
-    let cctx = unsafe { ZSTD_createCCtx() };
-    if cctx.is_null() {
-        return Err(NxCompressionError::ZStandard(
-            ZSTD_ErrorCode::ZSTD_error_GENERIC,
-        ));
-    }
-
-    // Set compression parameters (magicless format, no extra headers)
-    zstd_setcommoncompressparams(cctx, Some(level));
+    compress_advanced(
+        level,
+        &ZstdAdvancedParams::default(),
+        source,
+        destination,
+        used_copy,
+    )
+}
 
-    // Perform compression
-    let result = unsafe {
-        ZSTD_compress2(
-            cctx,
-            destination.as_mut_ptr() as *mut c_void,
-            destination.len(),
-            source.as_ptr() as *const c_void,
-            source.len(),
-        )
-    };
+/// Compresses data with ZStandard, using advanced parameters.
+///
+/// # Parameters
+///
+/// * `level`: Level at which we are compressing.
+/// * `params`: Advanced parameters applied on top of the level.
+/// * `source`: Length of the source in bytes.
+/// * `destination`: Pointer to destination.
+/// * `used_copy`: If this is true, Copy compression was used, due to uncompressible data.
+pub fn compress_advanced(
+    level: i32,
+    params: &ZstdAdvancedParams,
+    source: &[u8],
+    destination: &mut [u8],
+    used_copy: &mut bool,
+) -> CompressionResult {
+    *used_copy = false;
 
-    // Free the context
-    unsafe {
-        ZSTD_freeCCtx(cctx);
-    }
+    // Perform compression, with a pooled context
+    let result = with_compression_context(|cctx| {
+        // Set compression parameters (magicless format, no extra headers)
+        zstd_setcommoncompressparams(cctx, Some(level));
+        params.apply(cctx);
+
+        unsafe {
+            ZSTD_compress2(
+                cctx,
+                destination.as_mut_ptr() as *mut c_void,
+                destination.len(),
+                source.as_ptr() as *const c_void,
+                source.len(),
+            )
+        }
+    })
+    .ok_or(NxCompressionError::ZStandard(
+        ZSTD_ErrorCode::ZSTD_error_GENERIC,
+    ))?;
 
     let errcode = unsafe { ZSTD_getErrorCode(result) };
     if result > source.len() || errcode == ZSTD_error_dstSize_tooSmall {
@@ -228,31 +248,21 @@ pub fn compress_with_dictionary(
     destination: &mut [u8],
     used_copy: &mut bool,
 ) -> CompressionResult {
-    unsafe {
-        // Create a compression context
-        let cctx_ptr = ZSTD_createCCtx();
-        if cctx_ptr.is_null() {
-            return Err(NxCompressionError::ZStandard(
-                ZSTD_ErrorCode::ZSTD_error_memory_allocation,
-            ));
-        }
-
+    // Compress using the dictionary, with a pooled context
+    with_compression_context(|cctx_ptr| unsafe {
         // Set compression parameters (magicless format, no extra headers)
         zstd_setcommoncompressparams(cctx_ptr, None);
 
-        // Compress using the dictionary
-        let result = dict.compress(
+        dict.compress(
             source,
             destination,
             used_copy,
             NonNull::new_unchecked(cctx_ptr),
-        );
-
-        // Free the context and return result
-        ZSTD_freeCCtx(cctx_ptr);
-
-        result
-    }
+        )
+    })
+    .unwrap_or(Err(NxCompressionError::ZStandard(
+        ZSTD_ErrorCode::ZSTD_error_memory_allocation,
+    )))
 }
 
 /// Decompresses data with ZStandard
@@ -262,19 +272,8 @@ pub fn compress_with_dictionary(
 /// * `source`: Source data to decompress.
 /// * `destination`: Destination buffer for decompressed data.
 pub fn decompress(source: &[u8], destination: &mut [u8]) -> DecompressionResult {
-    // Create decompression context
-    let dctx = unsafe { ZSTD_createDCtx() };
-    if dctx.is_null() {
-        return Err(NxDecompressionError::ZStandard(
-            ZSTD_ErrorCode::ZSTD_error_GENERIC,
-        ));
-    }
-
-    // Set decompression parameters to match compression
-    zstd_setcommondecompressionparams(dctx);
-
-    // Perform decompression
-    let result = unsafe {
+    // Perform decompression, with the context of this thread
+    let result = with_decompression_context(|dctx| unsafe {
         ZSTD_decompressDCtx(
             dctx,
             destination.as_mut_ptr() as *mut c_void,
@@ -282,12 +281,10 @@ pub fn decompress(source: &[u8], destination: &mut [u8]) -> DecompressionResult
             source.as_ptr() as *const c_void,
             source.len(),
         )
-    };
-
-    // Free the context
-    unsafe {
-        ZSTD_freeDCtx(dctx);
-    }
+    })
+    .ok_or(NxDecompressionError::ZStandard(
+        ZSTD_ErrorCode::ZSTD_error_GENERIC,
+    ))?;
 
     if unsafe { ZSTD_isError(result) } != 0 {
         let errcode = unsafe { ZSTD_getErrorCode(result) };
@@ -304,11 +301,38 @@ pub fn decompress(source: &[u8], destination: &mut [u8]) -> DecompressionResult
 /// * `source`: Source data to decompress.
 /// * `destination`: Destination buffer for decompressed data.
 pub fn decompress_partial(source: &[u8], destination: &mut [u8]) -> DecompressionResult {
-    unsafe {
-        let d_stream = ZSTD_createDStream();
+    decompress_partial_using_ddict(source, destination, core::ptr::null())
+}
 
-        // Set decompression parameters to match compression
-        zstd_setcommondecompressionparams(d_stream);
+/// Partially decompresses data compressed with a ZStandard dictionary,
+/// until the destination buffer is filled.
+///
+/// # Parameters
+///
+/// * `dict`: The dictionary the data was compressed with.
+/// * `source`: Source data to decompress.
+/// * `destination`: Destination buffer for decompressed data.
+pub fn decompress_partial_with_dictionary(
+    dict: &ZstdDecompressionDict,
+    source: &[u8],
+    destination: &mut [u8],
+) -> DecompressionResult {
+    decompress_partial_using_ddict(source, destination, dict.as_ptr())
+}
+
+fn decompress_partial_using_ddict(
+    source: &[u8],
+    destination: &mut [u8],
+    ddict: *const ZSTD_DDict,
+) -> DecompressionResult {
+    // A DStream is a DCtx, so the context of this thread can be reused.
+    with_decompression_context(|d_stream| unsafe {
+        if !ddict.is_null() {
+            let result = ZSTD_DCtx_refDDict(d_stream, ddict);
+            if ZSTD_isError(result) != 0 {
+                return Err(NxDecompressionError::ZStandard(ZSTD_getErrorCode(result)));
+            }
+        }
 
         let mut out_buf = ZSTD_outBuffer {
             dst: destination.as_mut_ptr() as *mut c_void,
@@ -327,7 +351,6 @@ pub fn decompress_partial(source: &[u8], destination: &mut [u8]) -> Decompressio
             // We ran into an error, o no.
             if ZSTD_isError(result) != 0 {
                 let error_code = ZSTD_getErrorCode(result);
-                ZSTD_freeDStream(d_stream);
                 return Err(NxDecompressionError::ZStandard(error_code));
             }
 
@@ -341,9 +364,11 @@ pub fn decompress_partial(source: &[u8], destination: &mut [u8]) -> Decompressio
             ZSTD_decompressStream(d_stream, &mut out_buf, &mut in_buf);
         }
 
-        ZSTD_freeDStream(d_stream);
         Ok(out_buf.pos)
-    }
+    })
+    .ok_or(NxDecompressionError::ZStandard(
+        ZSTD_ErrorCode::ZSTD_error_GENERIC,
+    ))?
 }
 
 /// Determines the decompressed size of ZStandard compressed data
@@ -384,32 +409,24 @@ pub fn get_decompressed_size(compressed_data: &[u8]) -> Result<usize, GetDecompr
 /// * `source`: Length of the source in bytes.
 /// * `destination`: Pointer to destination.
 pub fn force_compress(level: i32, source: &[u8], destination: &mut [u8]) -> CompressionResult {
-    // Create a compression context
-    let cctx = unsafe { ZSTD_createCCtx() };
-    if cctx.is_null() {
-        return Err(NxCompressionError::ZStandard(
-            ZSTD_ErrorCode::ZSTD_error_GENERIC,
-        ));
-    }
-
-    // Set compression parameters (magicless format, no extra headers)
-    zstd_setcommoncompressparams(cctx, Some(level));
-
-    // Perform compression
-    let result = unsafe {
-        ZSTD_compress2(
-            cctx,
-            destination.as_mut_ptr() as *mut c_void,
-            destination.len(),
-            source.as_ptr() as *const c_void,
-            source.len(),
-        )
-    };
-
-    // Free the context
-    unsafe {
-        ZSTD_freeCCtx(cctx);
-    }
+    // Perform compression, with a pooled context
+    let result = with_compression_context(|cctx| {
+        // Set compression parameters (magicless format, no extra headers)
+        zstd_setcommoncompressparams(cctx, Some(level));
+
+        unsafe {
+            ZSTD_compress2(
+                cctx,
+                destination.as_mut_ptr() as *mut c_void,
+                destination.len(),
+                source.as_ptr() as *const c_void,
+                source.len(),
+            )
+        }
+    })
+    .ok_or(NxCompressionError::ZStandard(
+        ZSTD_ErrorCode::ZSTD_error_GENERIC,
+    ))?;
 
     if unsafe { ZSTD_isError(result) } == 0 {
         return Ok(result);
@@ -444,6 +461,8 @@ pub(crate) fn zstd_setcommondecompressionparams(dctx: *mut ZSTD_DCtx_s) {
             ZSTD_d_experimentalParam1, // zstd_d_format
             ZSTD_f_zstd1_magicless as i32,
         );
+        // Allow any window the packer can produce, see ZstdAdvancedParams::window_log.
+        ZSTD_DCtx_setParameter(dctx, ZSTD_d_windowLogMax, MAX_WINDOW_LOG as i32);
     };
 }
 
@@ -593,4 +612,28 @@ mod tests {
             "Should achieve some compression"
         );
     }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported because calls zstd code
+    fn can_round_trip_with_long_distance_matching() {
+        let params = ZstdAdvancedParams {
+            long_distance_matching: true,
+            window_log: Some(MAX_WINDOW_LOG),
+            target_length: Some(256),
+        };
+        let original_data = b"Hello, long distance matching!".repeat(1000);
+        let mut compressed = vec![0u8; max_alloc_for_compress_size(original_data.len())];
+        let mut used_copy = false;
+
+        let compressed_size =
+            compress_advanced(19, &params, &original_data, &mut compressed, &mut used_copy)
+                .unwrap();
+        assert!(!used_copy, "Should not fall back to copy compression");
+
+        let mut decompressed = vec![0u8; original_data.len()];
+        let decompressed_size =
+            decompress(&compressed[..compressed_size], &mut decompressed).unwrap();
+        assert_eq!(decompressed_size, original_data.len());
+        assert_eq!(decompressed, original_data);
+    }
 }
@@ -0,0 +1,83 @@
+use zstd_sys::ZSTD_cParameter::*;
+use zstd_sys::*;
+
+/// Smallest window log accepted by ZStandard.
+pub const MIN_WINDOW_LOG: u32 = 10;
+
+/// Largest window log accepted by ZStandard on 32-bit platforms.
+///
+/// Window logs are capped to this, so every archive can be read on any platform;
+/// readers allow windows up to this size when decompressing.
+pub const MAX_WINDOW_LOG: u32 = 30;
+
+/// Largest target length accepted by ZStandard.
+pub const MAX_TARGET_LENGTH: u32 = 131072;
+
+/// Advanced ZStandard parameters, applied on top of the compression level.
+///
+/// These mostly help large chunked files with redundancy far apart (e.g. repeated
+/// assets within a single package file), which the default window does not reach.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ZstdAdvancedParams {
+    /// Enables long distance matching, which finds matches far back in the data
+    /// at little extra cost. Raises the window log to 27 unless [`Self::window_log`] is set.
+    pub long_distance_matching: bool,
+
+    /// Base 2 log of the maximum distance matches can reference. Larger windows find more
+    /// matches but need more memory to decompress. [`None`] uses the level's default.
+    ///
+    /// Range is [`MIN_WINDOW_LOG`] - [`MAX_WINDOW_LOG`]. ZStandard shrinks the window
+    /// to fit the data, so it never exceeds the size of a block.
+    pub window_log: Option<u32>,
+
+    /// Minimum match length the compressor aims for; its meaning depends on the strategy
+    /// of the level. [`None`] uses the level's default. Range is 0 - [`MAX_TARGET_LENGTH`].
+    pub target_length: Option<u32>,
+}
+
+impl ZstdAdvancedParams {
+    /// Clamps all parameters to the ranges accepted by ZStandard on every platform.
+    pub fn sanitize(&mut self) {
+        self.window_log = self
+            .window_log
+            .map(|x| x.clamp(MIN_WINDOW_LOG, MAX_WINDOW_LOG));
+        self.target_length = self.target_length.map(|x| x.min(MAX_TARGET_LENGTH));
+    }
+
+    /// Applies the parameters to a compression context.
+    /// Parameters which are not set are left at the level's defaults.
+    pub(crate) fn apply(&self, cctx: *mut ZSTD_CCtx) {
+        unsafe {
+            if self.long_distance_matching {
+                ZSTD_CCtx_setParameter(cctx, ZSTD_c_enableLongDistanceMatching, 1);
+            }
+            if let Some(window_log) = self.window_log {
+                ZSTD_CCtx_setParameter(cctx, ZSTD_c_windowLog, window_log as i32);
+            }
+            if let Some(target_length) = self.target_length {
+                ZSTD_CCtx_setParameter(cctx, ZSTD_c_targetLength, target_length as i32);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_clamps_to_32_bit_limits() {
+        let mut params = ZstdAdvancedParams {
+            long_distance_matching: true,
+            window_log: Some(31),
+            target_length: Some(u32::MAX),
+        };
+        params.sanitize();
+        assert_eq!(params.window_log, Some(MAX_WINDOW_LOG));
+        assert_eq!(params.target_length, Some(MAX_TARGET_LENGTH));
+
+        params.window_log = Some(1);
+        params.sanitize();
+        assert_eq!(params.window_log, Some(MIN_WINDOW_LOG));
+    }
+}
@@ -0,0 +1,212 @@
+use super::zstd::zstd_setcommondecompressionparams;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::ptr::NonNull;
+use std::sync::Mutex;
+use zstd_sys::*;
+
+/// Maximum number of idle compression contexts kept in the pool.
+///
+/// Enough for one context per worker thread on most machines; a context compressing at a high
+/// level can hold tens of megabytes, so the pool is bounded rather than growing with each thread.
+pub(crate) const MAX_POOLED_COMPRESSION_CONTEXTS: usize = 16;
+
+/// A ZStandard decompression context, freed on drop.
+///
+/// Contexts are configured for the magicless format used by Nx on creation;
+/// [`ZSTD_DCtx_reset`] with [`ZSTD_ResetDirective::ZSTD_reset_session_only`] keeps that configuration.
+struct DecompressionContext(NonNull<ZSTD_DCtx>);
+
+impl DecompressionContext {
+    fn new() -> Option<Self> {
+        let dctx = NonNull::new(unsafe { ZSTD_createDCtx() })?;
+        zstd_setcommondecompressionparams(dctx.as_ptr());
+        Some(Self(dctx))
+    }
+}
+
+impl Drop for DecompressionContext {
+    fn drop(&mut self) {
+        unsafe {
+            ZSTD_freeDCtx(self.0.as_ptr());
+        }
+    }
+}
+
+std::thread_local! {
+    /// Decompression context of the current thread, created on first use.
+    static DECOMPRESSION_CONTEXT: RefCell<Option<DecompressionContext>> = const { RefCell::new(None) };
+}
+
+/// Runs an operation with a ZStandard decompression context, reusing the context
+/// of the current thread between calls.
+///
+/// Creating a context allocates its internal buffers, which costs more than decompressing
+/// a small block; reusing one makes reading many small blocks (e.g. SOLID blocks of a
+/// game's assets) noticeably faster.
+///
+/// The context's session is reset before being handed out, and any referenced dictionary
+/// is dropped, so any state left behind by a previous (failed) operation is discarded. If the thread's context is already in use
+/// (the operation is nested), a temporary context is created instead.
+///
+/// # Returns
+///
+/// The result of the operation, or [`None`] if a context could not be created.
+pub(crate) fn with_decompression_context<R>(
+    operation: impl FnOnce(*mut ZSTD_DCtx) -> R,
+) -> Option<R> {
+    let mut operation = Some(operation);
+    let pooled = DECOMPRESSION_CONTEXT.try_with(|cell| {
+        let mut slot = cell.try_borrow_mut().ok()?;
+        if slot.is_none() {
+            *slot = Some(DecompressionContext::new()?);
+        }
+
+        let dctx = slot.as_ref()?.0.as_ptr();
+        unsafe {
+            ZSTD_DCtx_reset(dctx, ZSTD_ResetDirective::ZSTD_reset_session_only);
+            ZSTD_DCtx_refDDict(dctx, core::ptr::null());
+        }
+        Some(operation.take()?(dctx))
+    });
+
+    match (pooled, operation) {
+        (Ok(Some(result)), _) => Some(result),
+        // Thread local unavailable (nested call, or thread shutting down).
+        (_, Some(operation)) => {
+            let context = DecompressionContext::new()?;
+            Some(operation(context.0.as_ptr()))
+        }
+        (_, None) => None,
+    }
+}
+
+/// A ZStandard compression context, freed on drop.
+struct CompressionContext(NonNull<ZSTD_CCtx>);
+
+// A context is only used by one thread at a time; the pool hands it out by value.
+unsafe impl Send for CompressionContext {}
+
+impl CompressionContext {
+    fn new() -> Option<Self> {
+        NonNull::new(unsafe { ZSTD_createCCtx() }).map(Self)
+    }
+}
+
+impl Drop for CompressionContext {
+    fn drop(&mut self) {
+        unsafe {
+            ZSTD_freeCCtx(self.0.as_ptr());
+        }
+    }
+}
+
+/// Idle compression contexts, oldest first.
+static COMPRESSION_CONTEXTS: Mutex<Vec<CompressionContext>> = Mutex::new(Vec::new());
+
+/// Runs an operation with a ZStandard compression context, taken from a pool shared
+/// by all threads.
+///
+/// Setting up a context costs more than compressing a small block at a high level, which
+/// dominates packing archives made of many small blocks (e.g. [`PackerPreset::LowLatencyVFS`]).
+/// Any idle context is reused, whatever level or dictionary it was last used with; the most
+/// recently returned one is preferred, as its buffers are most likely still in cache. If there
+/// is none, a new context is created. The pool is ZStandard only; LZ4 compresses without a context.
+///
+/// The context's session and parameters are reset before being handed out, which also drops
+/// any referenced dictionary, so the operation must set all the parameters it needs. Afterwards
+/// the context is returned to the pool, evicting the oldest idle context once
+/// [`MAX_POOLED_COMPRESSION_CONTEXTS`] are held.
+///
+/// # Returns
+///
+/// The result of the operation, or [`None`] if a context could not be created.
+///
+/// [`PackerPreset::LowLatencyVFS`]: crate::api::packer_builder::PackerPreset::LowLatencyVFS
+pub(crate) fn with_compression_context<R>(
+    operation: impl FnOnce(*mut ZSTD_CCtx) -> R,
+) -> Option<R> {
+    let context = take_compression_context();
+    let context = match context {
+        Some(context) => context,
+        None => CompressionContext::new()?,
+    };
+
+    let cctx = context.0.as_ptr();
+    unsafe {
+        ZSTD_CCtx_reset(cctx, ZSTD_ResetDirective::ZSTD_reset_session_and_parameters);
+    }
+    let result = operation(cctx);
+
+    let mut pool = COMPRESSION_CONTEXTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    pool.push(context);
+    let evicted = (pool.len() > MAX_POOLED_COMPRESSION_CONTEXTS).then(|| pool.remove(0));
+    drop(pool);
+    drop(evicted);
+    Some(result)
+}
+
+/// Takes the most recently returned idle context, if any.
+fn take_compression_context() -> Option<CompressionContext> {
+    COMPRESSION_CONTEXTS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .pop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reuses_context_of_thread() {
+        let first = with_decompression_context(|dctx| dctx as usize).unwrap();
+        let second = with_decompression_context(|dctx| dctx as usize).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn nested_calls_use_temporary_context() {
+        let (outer, inner) = with_decompression_context(|outer| {
+            let inner = with_decompression_context(|inner| inner as usize).unwrap();
+            (outer as usize, inner)
+        })
+        .unwrap();
+        assert_ne!(outer, inner);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn reuses_idle_compression_contexts() {
+        // Contexts are shared by all threads, so other tests may take the one returned
+        // in between; any context handed out must be usable at any level regardless.
+        for level in [1, 19, -5] {
+            let data = [7u8; 4096];
+            let mut compressed = [0u8; 4096];
+            let size = with_compression_context(|cctx| unsafe {
+                ZSTD_CCtx_setParameter(cctx, ZSTD_cParameter::ZSTD_c_compressionLevel, level);
+                ZSTD_compress2(
+                    cctx,
+                    compressed.as_mut_ptr() as *mut _,
+                    compressed.len(),
+                    data.as_ptr() as *const _,
+                    data.len(),
+                )
+            })
+            .unwrap();
+            assert_eq!(unsafe { ZSTD_isError(size) }, 0);
+        }
+
+        // Nested calls can't share a context.
+        let (outer, inner) = with_compression_context(|outer| {
+            let inner = with_compression_context(|inner| inner as usize).unwrap();
+            (outer as usize, inner)
+        })
+        .unwrap();
+        assert_ne!(outer, inner);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::api::traits::*;
+use crate::prelude::*;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+use std::sync::Mutex;
+
+/// A place in the library where a [`FaultInjector`] can inject faults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FaultPoint {
+    /// A read from a [`FaultInjectingProvider`].
+    Read,
+
+    /// Compressing a block while packing, see [`PackingSettings::fault_injector`].
+    ///
+    /// [`PackingSettings::fault_injector`]: crate::api::packing::packing_settings::PackingSettings::fault_injector
+    Compress,
+}
+
+/// What happens when a fault is injected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// The operation fails.
+    Fail,
+
+    /// The operation waits this long, then proceeds as normal.
+    /// Models slow disks, network shares and overloaded machines.
+    Stall(Duration),
+}
+
+/// Makes chosen operations fail or stall, for testing how the pack and extract pipelines
+/// behave when things go wrong, e.g. that they return promptly and free their resources.
+///
+/// Each [`FaultPoint`] counts how many times it was hit; faults are armed for a specific hit.
+/// Share one injector (via [`Arc`]) between the providers and settings under test, so hits
+/// are counted across all of them.
+///
+/// Only available with the `fault_injection` feature.
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    faults: Mutex<Vec<(FaultPoint, u64, Fault)>>,
+    read_hits: AtomicU64,
+    compress_hits: AtomicU64,
+}
+
+impl FaultInjector {
+    /// Creates an injector with no faults armed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Arms a fault.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - Where the fault is injected.
+    /// * `hit` - Which hit of `point` the fault is injected at, starting at 0.
+    ///   Hits made before arming count, see [`Self::hits`].
+    /// * `fault` - What happens.
+    ///
+    /// # Returns
+    ///
+    /// Returns self for method chaining.
+    pub fn with_fault(self, point: FaultPoint, hit: u64, fault: Fault) -> Self {
+        self.arm(point, hit, fault);
+        self
+    }
+
+    /// Arms a fault on an injector which is already in use, see [`Self::with_fault`].
+    pub fn arm(&self, point: FaultPoint, hit: u64, fault: Fault) {
+        self.faults
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((point, hit, fault));
+    }
+
+    /// Returns the number of times a point was hit so far.
+    pub fn hits(&self, point: FaultPoint) -> u64 {
+        self.counter(point).load(Ordering::Relaxed)
+    }
+
+    /// Records a hit of a point, injecting the faults armed for it.
+    ///
+    /// Stalls are applied before this returns.
+    ///
+    /// # Returns
+    ///
+    /// True if the operation should fail.
+    pub fn hit(&self, point: FaultPoint) -> bool {
+        let hit = self.counter(point).fetch_add(1, Ordering::Relaxed);
+        let faults: Vec<Fault> = self
+            .faults
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(p, h, _)| *p == point && *h == hit)
+            .map(|(_, _, fault)| *fault)
+            .collect();
+
+        let mut fail = false;
+        for fault in faults {
+            match fault {
+                Fault::Fail => fail = true,
+                Fault::Stall(duration) => std::thread::sleep(duration),
+            }
+        }
+
+        fail
+    }
+
+    fn counter(&self, point: FaultPoint) -> &AtomicU64 {
+        match point {
+            FaultPoint::Read => &self.read_hits,
+            FaultPoint::Compress => &self.compress_hits,
+        }
+    }
+}
+
+/// Injects [`FaultPoint::Read`] faults into the reads of another provider.
+///
+/// Failed reads return [`FileProviderError::ThirdPartyError`], which is not retried
+/// by [`RetryingProvider`].
+///
+/// [`RetryingProvider`]: crate::api::filedata::RetryingProvider
+pub struct FaultInjectingProvider<'a> {
+    inner: Box<dyn InputDataProvider + Send + Sync + 'a>,
+    injector: Arc<FaultInjector>,
+}
+
+impl<'a> FaultInjectingProvider<'a> {
+    /// Creates a new [`FaultInjectingProvider`].
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - Provides the data, unless a fault is injected.
+    /// * `injector` - Decides which reads fail or stall.
+    pub fn new(
+        inner: Box<dyn InputDataProvider + Send + Sync + 'a>,
+        injector: Arc<FaultInjector>,
+    ) -> Self {
+        Self { inner, injector }
+    }
+}
+
+impl InputDataProvider for FaultInjectingProvider<'_> {
+    fn get_file_data<'b>(
+        &'b self,
+        start: u64,
+        length: u64,
+    ) -> Result<Box<dyn ReadOnlyFileData + 'b>, FileProviderError> {
+        if self.injector.hit(FaultPoint::Read) {
+            return Err(FileProviderError::ThirdPartyError(String::from(
+                "Injected read fault",
+            )));
+        }
+
+        self.inner.get_file_data(start, length)
+    }
+
+    fn detect_change(&self) -> Result<Option<u64>, FileProviderError> {
+        self.inner.detect_change()
+    }
+
+    fn is_resident(&self, start: u64, length: u64) -> bool {
+        self.inner.is_resident(start, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{
+        archive_reader::{ArchiveReadError, NxArchiveReader},
+        directory_sync::{extract_to_dir, DirectorySyncError, ExtractOptions},
+        enums::CompressionPreference,
+        filedata::FromSliceReferenceProvider,
+        incremental_pack::IncrementalPackError,
+        packer_builder::NxPackerBuilder,
+        packing::packer_file::PackerFile,
+        raw_archive_writer::NxRawArchiveWriter,
+    };
+    use crate::unsize_box2;
+    use crate::utilities::compression::NxCompressionError;
+    use crate::utilities::tests::archive_for_testing::*;
+    use std::fs;
+    use std::time::Instant;
+    use tempfile::tempdir;
+
+    /// Generous upper bound for a failed operation to return, even on slow CI machines.
+    const DEADLINE: Duration = Duration::from_secs(10);
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn failed_pack_returns_promptly_and_releases_resources() {
+        let injector = Arc::new(
+            FaultInjector::new()
+                .with_fault(
+                    FaultPoint::Compress,
+                    0,
+                    Fault::Stall(Duration::from_millis(50)),
+                )
+                .with_fault(FaultPoint::Compress, 1, Fault::Fail),
+        );
+
+        // Distinct contents, so the files aren't deduplicated into a single block.
+        let data = [[1u8; 4096], [2u8; 4096], [3u8; 4096]];
+        let mut builder = NxPackerBuilder::new().with_fault_injector(injector.clone());
+        for (name, data) in ["a.bin", "b.bin", "c.bin"].into_iter().zip(&data) {
+            let provider = Box::new(FromSliceReferenceProvider::new(data));
+            let provider = Box::new(FaultInjectingProvider::new(
+                unsize_box2!(provider),
+                injector.clone(),
+            ));
+            builder.files.push(
+                PackerFile::new(
+                    String::from(name),
+                    data.len() as u64,
+                    unsize_box2!(provider),
+                )
+                .with_own_block(true),
+            );
+        }
+
+        let empty = NxRawArchiveWriter::new(builder.settings.chunk_size)
+            .build()
+            .unwrap();
+        let provider = Box::new(FromSliceReferenceProvider::new(&empty));
+        let previous = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+
+        let start = Instant::now();
+        let result = builder.pack_incremental(&previous);
+        assert!(start.elapsed() < DEADLINE);
+        assert!(matches!(
+            result,
+            Err(IncrementalPackError::Compression(
+                NxCompressionError::InjectedFault
+            ))
+        ));
+
+        // Packing stopped at the failed block.
+        assert_eq!(injector.hits(FaultPoint::Compress), 2);
+
+        // Nothing kept a reference to the providers or settings.
+        drop(builder);
+        assert_eq!(Arc::strong_count(&injector), 1);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn failed_extract_leaves_no_partial_files() {
+        let archive = create_test_archive(
+            4096,
+            &[
+                &[TestFile::new("a.txt", b"Hello")],
+                &[TestFile::new("b.txt", b"Nx archives!")],
+            ],
+            &[],
+            CompressionPreference::Copy,
+        );
+
+        let injector = Arc::new(FaultInjector::new());
+        let provider = Box::new(FromSliceReferenceProvider::new(&archive));
+        let provider = Box::new(FaultInjectingProvider::new(
+            unsize_box2!(provider),
+            injector.clone(),
+        ));
+        let reader = NxArchiveReader::new(unsize_box2!(provider)).unwrap();
+
+        // The first file's block is read after stalling, the second file's block fails.
+        let next = injector.hits(FaultPoint::Read);
+        injector.arm(
+            FaultPoint::Read,
+            next,
+            Fault::Stall(Duration::from_millis(50)),
+        );
+        injector.arm(FaultPoint::Read, next + 1, Fault::Fail);
+
+        let dir = tempdir().unwrap();
+        let start = Instant::now();
+        let result = extract_to_dir(&reader, dir.path(), &ExtractOptions::default());
+        assert!(start.elapsed() < DEADLINE);
+        assert!(matches!(
+            result,
+            Err(DirectorySyncError::Read(ArchiveReadError::FileProvider(_)))
+        ));
+
+        let mut files: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        files.sort();
+        assert_eq!(files.as_slice(), &[String::from("a.txt")]);
+    }
+}
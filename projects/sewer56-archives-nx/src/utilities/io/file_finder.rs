@@ -1,10 +1,11 @@
-use crate::api::filedata::FromFilePathProvider;
+use crate::api::filedata::{FileHandlePolicy, FromFilePathProvider};
 use crate::api::packing::packer_file::PackerFile;
 use crate::api::traits::*;
 use crate::prelude::*;
 use crate::unsize_box2;
 use alloc::string::String;
 use std::fs::*;
+use std::io;
 use std::path::Path;
 
 // TODO: Optimized version of this struct that doesn't use `std::fs`.
@@ -40,7 +41,31 @@ impl PathExt for Path {
 /// # Errors
 ///
 /// Returns an error if there are issues accessing the directory or files.
-pub fn find_files<'a, P, F>(directory_path: P, mut callback: F) -> Result<(), FileProviderError>
+pub fn find_files<'a, P, F>(directory_path: P, callback: F) -> Result<(), FileProviderError>
+where
+    P: AsRef<Path>,
+    F: FnMut(PackerFile<'a>),
+{
+    find_files_with_policy(directory_path, &FileHandlePolicy::KeepOpen, callback)
+}
+
+/// Iterates through all packable files from within a given directory, like [`find_files`],
+/// managing the handles of the found files as per a given policy.
+///
+/// # Arguments
+///
+/// * `directory_path` - The full path to the directory to search
+/// * `policy` - When each found file is opened and closed
+/// * `callback` - Function that will be called for each file found
+///
+/// # Errors
+///
+/// Returns an error if there are issues accessing the directory or files.
+pub fn find_files_with_policy<'a, P, F>(
+    directory_path: P,
+    policy: &FileHandlePolicy,
+    mut callback: F,
+) -> Result<(), FileProviderError>
 where
     P: AsRef<Path>,
     F: FnMut(PackerFile<'a>),
@@ -48,6 +73,7 @@ where
     walk_directory(
         directory_path.as_ref(),
         directory_path.as_ref(),
+        policy,
         &mut callback,
     )
 }
@@ -55,6 +81,7 @@ where
 fn walk_directory<'a, F>(
     current_path: &Path,
     base_path: &Path,
+    policy: &FileHandlePolicy,
     callback: &mut F,
 ) -> Result<(), FileProviderError>
 where
@@ -66,13 +93,16 @@ where
         let file_type = entry.file_type()?;
 
         if file_type.is_dir() {
-            walk_directory(&path, base_path, callback)?;
+            walk_directory(&path, base_path, policy, callback)?;
         } else if file_type.is_file() {
             let metadata = entry.metadata()?;
             if let Ok(relative_path) = path.strip_prefix(base_path) {
                 let relative_path_str = relative_path.normalize_separators();
 
-                let provider = Box::new(FromFilePathProvider::new(path.to_str().unwrap())?);
+                let path_str = path.to_str().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Path is not valid UTF-8")
+                })?;
+                let provider = Box::new(FromFilePathProvider::with_policy(path_str, policy)?);
                 let packer_file =
                     PackerFile::new(relative_path_str, metadata.len(), unsize_box2!(provider));
 
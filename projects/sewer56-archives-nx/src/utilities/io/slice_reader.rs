@@ -0,0 +1,91 @@
+use crate::headers::managed::InsufficientDataError;
+
+/// Reads little endian values from a byte slice, checking every read against its length.
+///
+/// This is the safe counterpart of [`endian_writer::LittleEndianReader`], used where
+/// the `forbid-unsafe-fastpaths` feature replaces pointer based parsing.
+#[derive(Debug, Clone)]
+pub struct SliceReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    /// Creates a reader positioned at the start of the data.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    /// Returns the number of bytes read so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    /// Reads the next `N` bytes, advancing the reader.
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], InsufficientDataError> {
+        let Some(bytes) = self.data.get(self.position..self.position + N) else {
+            return Err(InsufficientDataError::new(
+                self.data.len() as u32,
+                (self.position + N) as u32,
+            ));
+        };
+
+        self.position += N;
+        let mut result = [0u8; N];
+        result.copy_from_slice(bytes);
+        Ok(result)
+    }
+
+    /// Reads the next `len` bytes, advancing the reader.
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], InsufficientDataError> {
+        let end = self.position.saturating_add(len);
+        let Some(bytes) = self.data.get(self.position..end) else {
+            return Err(InsufficientDataError::new(
+                self.data.len() as u32,
+                end.min(u32::MAX as usize) as u32,
+            ));
+        };
+
+        self.position = end;
+        Ok(bytes)
+    }
+
+    /// Reads a little endian [`u16`], advancing the reader.
+    pub fn read_u16(&mut self) -> Result<u16, InsufficientDataError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    /// Reads a little endian [`u32`], advancing the reader.
+    pub fn read_u32(&mut self) -> Result<u32, InsufficientDataError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    /// Reads a little endian [`u64`], advancing the reader.
+    pub fn read_u64(&mut self) -> Result<u64, InsufficientDataError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_little_endian_values_until_end() {
+        let data = [1, 0, 2, 0, 0, 0, 3];
+        let mut reader = SliceReader::new(&data);
+        assert_eq!(reader.read_u16().unwrap(), 1);
+        assert_eq!(reader.read_u32().unwrap(), 2);
+        assert_eq!(reader.remaining(), 1);
+
+        let error = reader.read_u16().unwrap_err();
+        assert_eq!(error.available, 7);
+        assert_eq!(error.expected, 8);
+        assert_eq!(reader.position(), 6);
+    }
+}
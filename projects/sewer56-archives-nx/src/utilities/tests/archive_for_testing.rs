@@ -0,0 +1,179 @@
+use crate::api::{enums::CompressionPreference, traits::HasRelativePath};
+use crate::headers::{
+    managed::{v2::*, *},
+    parser::StringPool,
+    raw::{native_file_header::NativeFileHeader, toc::ToCFormat},
+    types::xxh3sum::XXH3sum,
+};
+use crate::prelude::*;
+use crate::utilities::compression;
+use crate::utilities::tests::packer_file_for_testing::PackerFileForTesting;
+use core::ptr::write_unaligned;
+
+/// Alignment of every section in an archive, as per the specification.
+const SECTION_ALIGNMENT: usize = NativeFileHeader::BLOCK_ALIGNMENT as usize;
+
+/// A file to be placed in an archive created by [`create_test_archive`].
+pub struct TestFile<'a> {
+    pub path: &'a str,
+    pub data: &'a [u8],
+}
+
+impl<'a> TestFile<'a> {
+    pub fn new(path: &'a str, data: &'a [u8]) -> Self {
+        Self { path, data }
+    }
+}
+
+/// Creates a complete Nx archive in memory.
+///
+/// # Arguments
+///
+/// * `chunk_size` - Chunk size stored in the header. Must be a power of 2.
+/// * `solid_blocks` - Groups of files, each group is packed into a single SOLID block.
+/// * `chunked_files` - Files split into chunks of `chunk_size`.
+/// * `compression` - Compression used for every block.
+pub fn create_test_archive(
+    chunk_size: u32,
+    solid_blocks: &[&[TestFile]],
+    chunked_files: &[TestFile],
+    compression: CompressionPreference,
+) -> Vec<u8> {
+    build_archive(chunk_size, solid_blocks, chunked_files, compression, true)
+}
+
+/// Creates a complete Nx archive in memory, without a string pool.
+/// Files in this archive can only be found by hash.
+///
+/// See [`create_test_archive`] for the arguments.
+pub fn create_test_archive_without_paths(
+    chunk_size: u32,
+    solid_blocks: &[&[TestFile]],
+    chunked_files: &[TestFile],
+    compression: CompressionPreference,
+) -> Vec<u8> {
+    build_archive(chunk_size, solid_blocks, chunked_files, compression, false)
+}
+
+fn build_archive(
+    chunk_size: u32,
+    solid_blocks: &[&[TestFile]],
+    chunked_files: &[TestFile],
+    compression: CompressionPreference,
+    store_paths: bool,
+) -> Vec<u8> {
+    let mut entries: Vec<FileEntry> = Vec::new();
+    let mut paths: Vec<&str> = Vec::new();
+    let mut block_data: Vec<Vec<u8>> = Vec::new();
+
+    // Chunked files come first, as in the packer.
+    for file in chunked_files {
+        entries.push(FileEntry::new(
+            XXH3sum::create(file.data).0,
+            file.data.len() as u64,
+            0,
+            0,
+            block_data.len() as u32,
+        ));
+        paths.push(file.path);
+        for chunk in file.data.chunks(chunk_size as usize) {
+            let mut data = Vec::new();
+            data.extend_from_slice(chunk);
+            block_data.push(data);
+        }
+    }
+
+    for block in solid_blocks {
+        let mut data = Vec::new();
+        for file in block.iter() {
+            entries.push(FileEntry::new(
+                XXH3sum::create(file.data).0,
+                file.data.len() as u64,
+                data.len() as u32,
+                0,
+                block_data.len() as u32,
+            ));
+            paths.push(file.path);
+            data.extend_from_slice(file.data);
+        }
+        block_data.push(data);
+    }
+
+    // Pack the string pool; this sorts the items, giving us the path indices.
+    let mut pool_items: Vec<PackerFileForTesting> = paths
+        .iter()
+        .map(|path| PackerFileForTesting::new(path, 0))
+        .collect();
+    let mut string_pool = StringPool::pack_v0(&mut pool_items, true).unwrap();
+    if !store_paths {
+        string_pool.clear();
+    }
+    for (entry, path) in entries.iter_mut().zip(paths.iter()) {
+        entry.file_path_index = pool_items
+            .iter()
+            .position(|item| item.relative_path() == *path)
+            .unwrap() as u32;
+    }
+
+    // Compress the blocks
+    let mut blocks: Vec<BlockSize> = Vec::new();
+    let mut block_compressions: Vec<CompressionPreference> = Vec::new();
+    let mut compressed_blocks: Vec<Vec<u8>> = Vec::new();
+    for data in &block_data {
+        let mut compressed = vec![0u8; compression::max_alloc_for_compress_size(data.len())];
+        let mut used_copy = false;
+        let size =
+            compression::compress(compression, 9, data, &mut compressed, &mut used_copy).unwrap();
+        compressed.truncate(size);
+
+        blocks.push(BlockSize::new(size as u32));
+        block_compressions.push(if used_copy {
+            CompressionPreference::Copy
+        } else {
+            compression
+        });
+        compressed_blocks.push(compressed);
+    }
+
+    // Write the header and table of contents
+    let format = ToCFormat::Preset0;
+    let table_size = calculate_toc_size(
+        format,
+        string_pool.len() as u32,
+        blocks.len() as u32,
+        entries.len() as u32,
+    );
+    let info = BuilderInfo {
+        format,
+        can_create_chunks: !chunked_files.is_empty(),
+        table_size,
+        max_decomp_block_offset: 0,
+        string_pool,
+    };
+
+    let header_size =
+        (NativeFileHeader::SIZE_BYTES + table_size as usize).next_multiple_of(SECTION_ALIGNMENT);
+    let mut archive = vec![0u8; header_size];
+    unsafe {
+        write_unaligned(
+            archive.as_mut_ptr() as *mut NativeFileHeader,
+            NativeFileHeader::init(chunk_size, header_size as u32),
+        );
+    }
+    serialize_table_of_contents_into(
+        &block_compressions,
+        &blocks,
+        &entries,
+        &info,
+        &mut archive[NativeFileHeader::SIZE_BYTES..],
+    )
+    .unwrap();
+
+    // Write the blocks
+    for block in &compressed_blocks {
+        archive.extend_from_slice(block);
+        archive.resize(archive.len().next_multiple_of(SECTION_ALIGNMENT), 0);
+    }
+
+    archive
+}
@@ -0,0 +1,264 @@
+use crate::api::enums::WorkerPriority;
+use crate::prelude::*;
+
+/// Amount the nice value of a thread is raised by for [`WorkerPriority::BelowNormal`].
+#[cfg(target_os = "linux")]
+const BELOW_NORMAL_NICE_INCREASE: i32 = 5;
+
+/// Highest (least favourable) nice value on Linux.
+#[cfg(target_os = "linux")]
+const MAX_NICE: i32 = 19;
+
+/// A scheduling option which could not be applied to a worker thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedulingOption {
+    /// The priority set by [`PackingSettings::worker_priority`].
+    ///
+    /// [`PackingSettings::worker_priority`]: crate::api::packing::packing_settings::PackingSettings::worker_priority
+    Priority,
+
+    /// The cores set by [`PackingSettings::worker_cores`].
+    ///
+    /// [`PackingSettings::worker_cores`]: crate::api::packing::packing_settings::PackingSettings::worker_cores
+    Affinity,
+}
+
+/// Why a scheduling option was not applied, see [`apply_to_current_thread`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchedulingFailure {
+    /// The option which was not applied.
+    pub option: SchedulingOption,
+
+    /// Error code returned by the OS, or [`None`] if the platform does not support the option.
+    pub os_error: Option<i32>,
+}
+
+/// Applies a priority and CPU affinity to the current thread.
+///
+/// This is best effort; options which fail (e.g. only listing cores which don't exist)
+/// are skipped, so the work still runs. Supported platforms are:
+///
+/// - Linux: the nice value of the thread is raised, and its affinity set.
+/// - Windows: the thread priority is lowered, and its affinity set; only the first
+///   64 cores (the first processor group) can be pinned to.
+/// - macOS: the thread runs with the utility QoS class. Pinning to cores is not supported.
+///
+/// # Arguments
+///
+/// * `priority` - Priority of the thread.
+/// * `cores` - Indices of the CPU cores the thread may run on, or [`None`] for any core.
+///
+/// # Returns
+///
+/// The options which could not be applied.
+pub fn apply_to_current_thread(
+    priority: WorkerPriority,
+    cores: Option<&[usize]>,
+) -> Vec<SchedulingFailure> {
+    let mut failures = Vec::new();
+    if priority == WorkerPriority::BelowNormal {
+        if let Err(os_error) = lower_priority() {
+            failures.push(SchedulingFailure {
+                option: SchedulingOption::Priority,
+                os_error,
+            });
+        }
+    }
+
+    if let Some(cores) = cores {
+        if let Err(os_error) = set_affinity(cores) {
+            failures.push(SchedulingFailure {
+                option: SchedulingOption::Affinity,
+                os_error,
+            });
+        }
+    }
+
+    failures
+}
+
+/// Returns the error code of the last failed OS call on this thread.
+#[cfg(any(target_os = "linux", windows))]
+fn last_os_error() -> Option<i32> {
+    std::io::Error::last_os_error().raw_os_error()
+}
+
+#[cfg(target_os = "linux")]
+fn lower_priority() -> Result<(), Option<i32>> {
+    unsafe {
+        // On Linux, the nice value is per thread, and addressed by thread id.
+        let thread_id = libc::syscall(libc::SYS_gettid) as libc::id_t;
+
+        // -1 is a valid priority, so errors can only be told apart via errno.
+        *libc::__errno_location() = 0;
+        let current = libc::getpriority(libc::PRIO_PROCESS, thread_id);
+        if *libc::__errno_location() != 0 {
+            return Err(last_os_error());
+        }
+
+        let lowered = (current + BELOW_NORMAL_NICE_INCREASE).min(MAX_NICE);
+        match libc::setpriority(libc::PRIO_PROCESS, thread_id, lowered) {
+            0 => Ok(()),
+            _ => Err(last_os_error()),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_affinity(cores: &[usize]) -> Result<(), Option<i32>> {
+    unsafe {
+        let mut set: libc::cpu_set_t = core::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for core in cores {
+            if *core < libc::CPU_SETSIZE as usize {
+                libc::CPU_SET(*core, &mut set);
+            }
+        }
+
+        // 0 is the calling thread.
+        match libc::sched_setaffinity(0, core::mem::size_of::<libc::cpu_set_t>(), &set) {
+            0 => Ok(()),
+            _ => Err(last_os_error()),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn lower_priority() -> Result<(), Option<i32>> {
+    // Threads can't lower their own priority directly; the QoS class also lets the
+    // scheduler prefer efficiency cores for the thread.
+    let result =
+        unsafe { libc::pthread_set_qos_class_self_np(libc::qos_class_t::QOS_CLASS_UTILITY, 0) };
+    match result {
+        0 => Ok(()),
+        error => Err(Some(error)),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn set_affinity(_cores: &[usize]) -> Result<(), Option<i32>> {
+    // macOS only offers affinity hints between threads, not pinning to cores.
+    Err(None)
+}
+
+#[cfg(windows)]
+mod windows {
+    pub type Handle = *mut core::ffi::c_void;
+
+    /// `THREAD_PRIORITY_BELOW_NORMAL` in `processthreadsapi.h`.
+    pub const THREAD_PRIORITY_BELOW_NORMAL: i32 = -1;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        pub fn GetCurrentThread() -> Handle;
+        pub fn SetThreadPriority(thread: Handle, priority: i32) -> i32;
+        pub fn SetThreadAffinityMask(thread: Handle, mask: usize) -> usize;
+    }
+}
+
+#[cfg(windows)]
+fn lower_priority() -> Result<(), Option<i32>> {
+    let result = unsafe {
+        windows::SetThreadPriority(
+            windows::GetCurrentThread(),
+            windows::THREAD_PRIORITY_BELOW_NORMAL,
+        )
+    };
+    match result {
+        0 => Err(last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(windows)]
+fn set_affinity(cores: &[usize]) -> Result<(), Option<i32>> {
+    let mask = cores
+        .iter()
+        .filter(|core| **core < usize::BITS as usize)
+        .fold(0_usize, |mask, core| mask | (1 << core));
+
+    // Fails on an empty mask, as on Linux.
+    let result = unsafe { windows::SetThreadAffinityMask(windows::GetCurrentThread(), mask) };
+    match result {
+        0 => Err(last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn lower_priority() -> Result<(), Option<i32>> {
+    Err(None)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn set_affinity(_cores: &[usize]) -> Result<(), Option<i32>> {
+    Err(None)
+}
+
+/// Runs work on a new thread with a given priority and CPU affinity, and waits for it.
+///
+/// Lowering the priority of a thread can't be undone without elevated permissions, so the
+/// work runs on a thread of its own rather than the caller's. If neither option is set,
+/// the work runs on the current thread instead.
+///
+/// # Arguments
+///
+/// * `priority` - Priority of the worker thread.
+/// * `cores` - Indices of the CPU cores the worker may run on, or [`None`] for any core.
+/// * `work` - The work to run.
+///
+/// # Returns
+///
+/// The result of `work`, and the options which could not be applied to the worker,
+/// see [`apply_to_current_thread`]. Panics in `work` are resumed on the current thread.
+pub fn run_with_scheduling<R: Send>(
+    priority: WorkerPriority,
+    cores: Option<&[usize]>,
+    work: impl FnOnce() -> R + Send,
+) -> (R, Vec<SchedulingFailure>) {
+    if priority == WorkerPriority::Normal && cores.is_none() {
+        return (work(), Vec::new());
+    }
+
+    std::thread::scope(|scope| {
+        let worker = scope.spawn(move || {
+            let failures = apply_to_current_thread(priority, cores);
+            (work(), failures)
+        });
+
+        match worker.join() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn runs_on_worker_only_when_scheduling_is_set() {
+        let caller = thread::current().id();
+        let (on_caller, _) =
+            run_with_scheduling(WorkerPriority::Normal, None, || thread::current().id());
+        assert_eq!(on_caller, caller);
+
+        let (on_worker, _) = run_with_scheduling(WorkerPriority::BelowNormal, Some(&[0]), || {
+            thread::current().id()
+        });
+        assert_ne!(on_worker, caller);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(miri, ignore)]
+    fn reports_options_which_could_not_be_applied() {
+        let (_, failures) = run_with_scheduling(WorkerPriority::Normal, Some(&[usize::MAX]), || ());
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].option, SchedulingOption::Affinity);
+        assert!(failures[0].os_error.is_some());
+    }
+}